@@ -0,0 +1,45 @@
+///
+/// Benchmarks of delivering the events to the callbacks, through the mock
+/// manager so that they run without a mouse
+///
+/// Run with `cargo bench --features mock`
+///
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use mouce::common::{MouseEvent, MouseListener};
+use mouce::mock::MockMouseManager;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// A manager with the given number of callbacks that count the events
+fn manager_with_callbacks(count: usize) -> (MockMouseManager, Arc<AtomicUsize>) {
+    let mut manager = MockMouseManager::new();
+    let received = Arc::new(AtomicUsize::new(0));
+    for _ in 0..count {
+        let received = received.clone();
+        manager
+            .hook(Box::new(move |_| {
+                received.fetch_add(1, Ordering::Relaxed);
+            }))
+            .unwrap();
+    }
+    (manager, received)
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    let event = MouseEvent::RelativeMove(1, -1);
+    for count in [1, 4, 16] {
+        let (manager, _received) = manager_with_callbacks(count);
+        // The listener dispatches like the listener threads of the real managers
+        let mut listener = manager.listener();
+        group.bench_with_input(BenchmarkId::new("callbacks", count), &event, |b, event| {
+            b.iter(|| listener.inject(black_box(event)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,110 @@
+/*
+ * The C interface of the mouce library, see src/ffi.rs
+ *
+ * The functions return MOUCE_OK on success and one of the
+ * negative MOUCE_ERROR_* codes on failure
+ */
+#ifndef MOUCE_H
+#define MOUCE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MOUCE_OK 0
+#define MOUCE_ERROR_PERMISSION_DENIED -1
+#define MOUCE_ERROR_NOT_IMPLEMENTED -2
+#define MOUCE_ERROR_DEVICE_NOT_FOUND -3
+#define MOUCE_ERROR_CALLBACK_NOT_FOUND -4
+#define MOUCE_ERROR_WRITE_FAILED -5
+#define MOUCE_ERROR_UNSUPPORTED -6
+#define MOUCE_ERROR_INVALID_ARGUMENT -7
+#define MOUCE_ERROR_OTHER -8
+
+#define MOUCE_BUTTON_LEFT 0
+#define MOUCE_BUTTON_RIGHT 1
+#define MOUCE_BUTTON_MIDDLE 2
+#define MOUCE_BUTTON_SIDE 3
+#define MOUCE_BUTTON_EXTRA 4
+#define MOUCE_BUTTON_FORWARD 5
+#define MOUCE_BUTTON_BACK 6
+#define MOUCE_BUTTON_TASK 7
+
+#define MOUCE_EVENT_RELATIVE_MOVE 0
+#define MOUCE_EVENT_ABSOLUTE_MOVE 1
+#define MOUCE_EVENT_PRESS 2
+#define MOUCE_EVENT_RELEASE 3
+/* x and y are -1, 0 or 1, positive values scroll up and right */
+#define MOUCE_EVENT_SCROLL 4
+/* x and y are in 1/120 of a wheel detent */
+#define MOUCE_EVENT_SCROLL_HI_RES 5
+/* x and y are in wheel detents */
+#define MOUCE_EVENT_SCROLL_2D 6
+/* code is one of the MOUCE_SCROLL_PHASE_* phases */
+#define MOUCE_EVENT_SCROLL_PHASE 7
+/* a mouse is disconnected, the id of the device is not reported */
+#define MOUCE_EVENT_DEVICE_DISCONNECTED 8
+/* code is the button, the clicks are only reported by mouce_hook_clicks */
+#define MOUCE_EVENT_CLICK 9
+#define MOUCE_EVENT_DOUBLE_CLICK 10
+#define MOUCE_EVENT_TRIPLE_CLICK 11
+
+#define MOUCE_SCROLL_PHASE_MAY_BEGIN 0
+#define MOUCE_SCROLL_PHASE_BEGAN 1
+#define MOUCE_SCROLL_PHASE_CHANGED 2
+#define MOUCE_SCROLL_PHASE_ENDED 3
+#define MOUCE_SCROLL_PHASE_CANCELLED 4
+#define MOUCE_SCROLL_PHASE_MOMENTUM_BEGAN 5
+#define MOUCE_SCROLL_PHASE_MOMENTUM_CHANGED 6
+#define MOUCE_SCROLL_PHASE_MOMENTUM_ENDED 7
+
+typedef struct MouceManager MouceManager;
+
+/*
+ * x and y are the coordinates of the moves and the amounts of the scrolls,
+ * code is the button of the presses and releases and the phase of the scrolls
+ */
+typedef struct MouceEvent {
+    int type;
+    int x;
+    int y;
+    int code;
+} MouceEvent;
+
+/* Called from the listener thread of the manager */
+typedef void (*MouceCallback)(const MouceEvent *event, void *user_data);
+
+/*
+ * Create a mouse manager, the maximum coordinates are only used
+ * by uinput and ignored on the other platforms. Returns NULL on failure
+ */
+MouceManager *mouce_new(int max_x, int max_y);
+/* Destroy the manager, which stops its listener */
+void mouce_free(MouceManager *manager);
+
+int mouce_move_to(MouceManager *manager, int x, int y);
+int mouce_move_relative(MouceManager *manager, int x_offset, int y_offset);
+int mouce_get_position(MouceManager *manager, int *x, int *y);
+int mouce_press(MouceManager *manager, int button);
+int mouce_release(MouceManager *manager, int button);
+int mouce_click(MouceManager *manager, int button);
+/* Scroll by the given number of detents, positive values scroll up and right */
+int mouce_scroll(MouceManager *manager, int dx, int dy);
+
+/*
+ * Call the callback with the user data on every mouse event,
+ * returns the id of the callback or a negative error code
+ */
+int mouce_hook(MouceManager *manager, MouceCallback callback, void *user_data);
+/*
+ * Like mouce_hook, the callback also receives the clicks that are synthesized
+ * with the double click time and slop of the system
+ */
+int mouce_hook_clicks(MouceManager *manager, MouceCallback callback, void *user_data);
+int mouce_unhook(MouceManager *manager, int callback_id);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MOUCE_H */
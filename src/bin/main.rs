@@ -1,6 +1,10 @@
+use std::fs;
 use std::time::Duration;
 use std::thread::sleep;
 
+use mouce::recorder::{Recorder, Recording};
+use mouce::script::ActionPlan;
+
 #[cfg(feature = "cli")]
 use clap::{Arg, Command};
 
@@ -58,13 +62,95 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .short('d')
                 .takes_value(true))
         )
+        .subcommand(
+            Command::new("move")
+                .about("Moves the mouse to the given position, e.g. `mouce move 960 540`")
+                .arg(Arg::new("x").required(true).index(1))
+                .arg(Arg::new("y").required(true).index(2))
+        )
+        .subcommand(
+            Command::new("click")
+                .about("Click the given mouse button, e.g. `mouce click left`")
+                .arg(Arg::new("button").default_value("left").index(1))
+        )
+        .subcommand(
+            Command::new("scroll")
+                .about("Scroll the mouse wheel the given times towards to the given direction, e.g. `mouce scroll up 3`")
+                .arg(Arg::new("direction").required(true).index(1))
+                .arg(Arg::new("amount").default_value("1").index(2))
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run the given script of actions, e.g. `mouce run \"move 100 200; sleep 50; click left\"`")
+                .arg(Arg::new("script").required_unless_present("file").index(1))
+                .arg(Arg::new("file")
+                    .long("file")
+                    .short('f')
+                    .help("Run the script of the given file instead")
+                    .takes_value(true))
+        )
         .subcommand(
             Command::new("listen")
-                .about("Listen mouse events and print them to the terminal")
+                .about("Listen mouse events and print them to the terminal as JSON lines")
+        )
+        .subcommand(
+            Command::new("record")
+                .about("Record the mouse events to the given file until enter is pressed, the recording is stored as JSON if the file ends with .json and as bytes otherwise")
+                .arg(Arg::new("file").required(true).index(1))
+                .arg(Arg::new("duration")
+                    .long("duration")
+                    .short('d')
+                    .help("Stop recording after the given seconds instead")
+                .takes_value(true))
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Replay the mouse events that are recorded to the given file")
+                .arg(Arg::new("file").required(true).index(1))
+                .arg(Arg::new("speed")
+                    .long("speed")
+                    .short('s')
+                    .help("Multiplies the playback rate, e.g. 2 replays twice as fast")
+                    .default_value("1")
+                .takes_value(true))
         );
+    #[cfg(target_os = "linux")]
+    let app = app.subcommand(
+        Command::new("install_udev_rule")
+            .about("Give a group the access to /dev/uinput and the mice with a udev rule, needs to be run as root")
+            .arg(Arg::new("group")
+                .long("group")
+                .short('g')
+                .default_value("input")
+                .takes_value(true))
+            .arg(Arg::new("dry_run")
+                .long("dry-run")
+                .help("Print the rule without installing it"))
+    );
 
-    let mut mouse_manager = mouce::Mouse::new((0, 1920), (0, 1080))?;
+    // Parse the arguments first, so that the help works without a mouse device
     let matches = app.get_matches();
+    #[cfg(target_os = "linux")]
+    if let Some(("install_udev_rule", sub_matches)) = matches.subcommand() {
+        use mouce::setup::{self, UdevRuleOptions};
+
+        let group: String = sub_matches.value_of_t_or_exit("group");
+        let options = UdevRuleOptions::new()
+            .group(&group)
+            .dry_run(sub_matches.is_present("dry_run"));
+        let rule = setup::install_udev_rule(&options)?;
+        if sub_matches.is_present("dry_run") {
+            print!("{}", rule);
+        } else {
+            eprintln!(
+                "Installed the rule to {}, log in again if the user is not in the {} group yet",
+                setup::UDEV_RULES_FILE,
+                group
+            );
+        }
+        return Ok(());
+    }
+    let mut mouse_manager = mouce::Mouse::new((0, 1920), (0, 1080))?;
 
     match matches.subcommand() {
         Some(("move_to", sub_matches)) => {
@@ -96,13 +182,71 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let direction = get_scroll_direction(&direction_arg)?;
             mouse_manager.scroll_wheel(&direction)?;
         }
+        Some(("move", sub_matches)) => {
+            let x: usize = sub_matches.value_of_t_or_exit("x");
+            let y: usize = sub_matches.value_of_t_or_exit("y");
+            mouse_manager.move_to(x, y)?;
+        }
+        Some(("click", sub_matches)) => {
+            let button_arg: String = sub_matches.value_of_t_or_exit("button");
+            let button = get_mouse_button(&button_arg)?;
+            mouse_manager.click_button(&button)?;
+        }
+        Some(("scroll", sub_matches)) => {
+            let direction_arg: String = sub_matches.value_of_t_or_exit("direction");
+            let direction = get_scroll_direction(&direction_arg)?;
+            let amount: usize = sub_matches.value_of_t_or_exit("amount");
+            for _ in 0..amount {
+                mouse_manager.scroll_wheel(&direction)?;
+            }
+        }
+        Some(("record", sub_matches)) => {
+            let file: String = sub_matches.value_of_t_or_exit("file");
+            let recorder = Recorder::start(mouse_manager.as_mut())?;
+            if sub_matches.is_present("duration") {
+                let duration: f64 = sub_matches.value_of_t_or_exit("duration");
+                sleep(Duration::from_secs_f64(duration));
+            } else {
+                eprintln!("Recording, press enter to stop");
+                std::io::stdin().read_line(&mut String::new())?;
+            }
+            let recording = recorder.stop(mouse_manager.as_mut())?;
+            if file.ends_with(".json") {
+                fs::write(&file, recording.to_json())?;
+            } else {
+                fs::write(&file, recording.to_bytes())?;
+            }
+            eprintln!("Recorded {} events to {}", recording.events.len(), file);
+        }
+        Some(("replay", sub_matches)) => {
+            let file: String = sub_matches.value_of_t_or_exit("file");
+            let speed: f64 = sub_matches.value_of_t_or_exit("speed");
+            let recording = if file.ends_with(".json") {
+                Recording::from_json(&fs::read_to_string(&file)?)?
+            } else {
+                Recording::from_bytes(&fs::read(&file)?)?
+            };
+            recording.replay(mouse_manager.as_mut(), speed)?;
+        }
+        Some(("run", sub_matches)) => {
+            let script = match sub_matches.value_of("file") {
+                Some(file) => fs::read_to_string(file)?,
+                // The script is required unless the file is given
+                None => sub_matches
+                    .value_of("script")
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            let plan = ActionPlan::parse(&script)?;
+            plan.run(mouse_manager.as_mut())?;
+        }
         Some(("listen", _)) => {
             mouse_manager.hook(Box::new(|event| {
-                println!("{:?}", event);
+                println!("{}", event.to_json());
             }))?;
             loop {
                 // Call sleep to avoid heavy cpu load
-                sleep(Duration::from_secs(u64::max_value()));
+                sleep(Duration::from_secs(u64::MAX));
             }
         }
         _ => {
@@ -116,34 +260,27 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 fn get_mouse_button(
     button: &str,
 ) -> Result<mouce::common::MouseButton, Box<dyn std::error::Error>> {
-    match button {
-        "left" => Ok(mouce::common::MouseButton::Left),
-        "right" => Ok(mouce::common::MouseButton::Right),
-        "middle" => Ok(mouce::common::MouseButton::Middle),
-        "forward" => Ok(mouce::common::MouseButton::Forward),
-        "back" => Ok(mouce::common::MouseButton::Back),
-        _ => Err(Box::new(std::io::Error::new(
+    button.parse().map_err(|_| {
+        Box::new(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
             format!(
-                "{} is not accepted as a button, please use left, right, middle, forward or back",
+                "{} is not accepted as a button, please use left, right, middle, side, extra, forward, back or task",
                 button
             ),
-        ))),
-    }
+        )) as Box<dyn std::error::Error>
+    })
 }
 
 fn get_scroll_direction(
     direction: &str,
 ) -> Result<mouce::common::ScrollDirection, Box<dyn std::error::Error>> {
-    match direction {
-        "up" => Ok(mouce::common::ScrollDirection::Up),
-        "down" => Ok(mouce::common::ScrollDirection::Down),
-        _ => Err(Box::new(std::io::Error::new(
+    direction.parse().map_err(|_| {
+        Box::new(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
             format!(
-                "{} is not accepted as a direction, please use up or down",
+                "{} is not accepted as a direction, please use up, down, left or right",
                 direction
             ),
-        ))),
-    }
+        )) as Box<dyn std::error::Error>
+    })
 }
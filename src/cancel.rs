@@ -0,0 +1,132 @@
+///
+/// This module contains the token that aborts the time-extended actions
+/// e.g. the smooth moves, the drags, the scroll bursts and the replays
+///
+/// The actions wait on the token between their steps, so cancelling it
+/// from another thread stops them at the next step instead of after the
+/// whole action, and the buttons they hold are released before they return
+///
+use crate::error::Error;
+use crate::hooks::LockUnpoisoned;
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct State {
+    cancelled: Mutex<bool>,
+    /// Notified once the token is cancelled, to wake up the sleeping actions
+    changed: Condvar,
+}
+
+/// Aborts the actions that it is passed to once it is cancelled, the clones
+/// of the token share the same state
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::cancel::CancellationToken;
+/// use mouce::common::Easing;
+/// use mouce::error::Error;
+/// use mouce::{Mouse, MouseController};
+/// use std::{thread, time::Duration};
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let token = CancellationToken::new();
+/// let stop = token.clone();
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_millis(100));
+///     stop.cancel();
+/// });
+/// let duration = Duration::from_secs(2);
+/// assert_eq!(
+///     manager.move_to_smooth_cancellable(960, 540, duration, &Easing::Linear, &token),
+///     Err(Error::Cancelled)
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<State>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the actions that the token is passed to, the actions that
+    /// are started with the token afterwards are cancelled right away
+    pub fn cancel(&self) {
+        *self.state.cancelled.lock_unpoisoned() = true;
+        self.state.changed.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.cancelled.lock_unpoisoned()
+    }
+
+    /// Fail with `Error::Cancelled` once the token is cancelled
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sleep for the given duration, or fail with `Error::Cancelled`
+    /// as soon as the token is cancelled
+    pub fn sleep(&self, duration: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + duration;
+        let mut cancelled = self.state.cancelled.lock_unpoisoned();
+        loop {
+            if *cancelled {
+                return Err(Error::Cancelled);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            cancelled = self
+                .state
+                .changed
+                .wait_timeout(cancelled, deadline - now)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CancellationToken;
+    use crate::error::Error;
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn cancel_sleep() {
+        let token = CancellationToken::new();
+        assert_eq!(token.sleep(Duration::from_millis(1)), Ok(()));
+
+        let stop = token.clone();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            stop.cancel();
+        });
+        let start = Instant::now();
+        assert_eq!(token.sleep(Duration::from_secs(10)), Err(Error::Cancelled));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        canceller.join().unwrap();
+
+        // The token stays cancelled
+        assert!(token.is_cancelled());
+        assert_eq!(token.check(), Err(Error::Cancelled));
+    }
+}
@@ -1,8 +1,115 @@
-use std::io::Result;
+use crate::cancel::CancellationToken;
+use crate::detect::{self, DetectorConfig, GestureEvent};
+use crate::devices::MouseDeviceInfo;
+use crate::display::{self, Rect};
+use crate::error::Error;
+use crate::executor::{self, DispatchPolicy};
+use crate::hooks;
+use crate::json;
+use crate::keyboard::{ModifierKeys, Modifiers};
+use crate::limit::{self, RateLimit};
+#[cfg(feature = "stream")]
+use crate::stream::EventStream;
+use crate::subscription::Subscription;
+use crate::trace;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{BufWriter, Write},
+    iter,
+    ops::{BitOr, BitOrAssign, Deref},
+    str::FromStr,
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{Duration, SystemTime},
+};
 
 pub type CallbackId = u8;
 
-#[derive(Debug)]
+/// The interval between the clicks of `double_click_button`, short enough
+/// to stay within the default double click threshold of the platforms
+pub const DEFAULT_CLICK_INTERVAL: Duration = Duration::from_millis(50);
+/// The time that `click_at` waits between the move and the press, so that the
+/// applications handle the pointer at its new position before the click
+pub const DEFAULT_CLICK_SETTLE: Duration = Duration::from_millis(10);
+/// The interval between the intermediate moves of `move_to_smooth`
+const SMOOTH_MOVE_INTERVAL: Duration = Duration::from_millis(8);
+/// The maximum distance in pixels between the intermediate moves of `drag_to`
+const DRAG_STEP: i32 = 5;
+
+/// The backends that are used to simulate and listen the mouse actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    X11,
+    UInput,
+    Windows,
+    Darwin,
+    /// The actions are logged by a `MockMouseManager`
+    Mock,
+    /// The actions are sent to a `RemoteServer`
+    Remote,
+    /// The actions are sent to the RemoteDesktop portal
+    Portal,
+    /// The actions are synthetic DOM events of the page
+    Web,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::X11 => write!(f, "x11"),
+            Backend::UInput => write!(f, "uinput"),
+            Backend::Windows => write!(f, "windows"),
+            Backend::Darwin => write!(f, "darwin"),
+            Backend::Mock => write!(f, "mock"),
+            Backend::Remote => write!(f, "remote"),
+            Backend::Portal => write!(f, "portal"),
+            Backend::Web => write!(f, "web"),
+        }
+    }
+}
+
+/// The actions that some of the backends do not support, see `MouseController::supports`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// `get_position`
+    GetPosition,
+    /// `get_button_state`
+    ButtonState,
+    /// `set_coordinate_space`
+    CoordinateSpace,
+    /// `set_motion_profile`
+    MotionProfile,
+    /// `scroll_wheel_hi_res`
+    HiResScroll,
+    /// `scroll_smooth`
+    SmoothScroll,
+    /// `confine_cursor` and `release_cursor`
+    ConfineCursor,
+    /// `set_cursor_visible`
+    CursorVisibility,
+    /// `hook_grab`
+    GrabEvents,
+    /// `inject_event`
+    InjectEvent,
+    /// `pause_hook` and `resume_hook`
+    PauseHook,
+    /// `listener_stats`
+    ListenerStats,
+    /// `on_listener_restarted`
+    ListenerRestarts,
+    /// `on_callback_panic`
+    CallbackPanics,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum MouseButton {
     Left,
     Middle,
@@ -16,13 +123,19 @@ pub enum MouseButton {
 
 #[cfg(not(any(
     target_os = "windows",
+    target_os = "android",
     target_os = "linux",
     target_os = "dragonfly",
     target_os = "freebsd",
     target_os = "netbsd",
     target_os = "openbsd"
 )))]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum ScrollDirection {
     Up,
     Down,
@@ -30,13 +143,19 @@ pub enum ScrollDirection {
 
 #[cfg(any(
     target_os = "windows",
+    target_os = "android",
     target_os = "linux",
     target_os = "dragonfly",
     target_os = "freebsd",
     target_os = "netbsd",
     target_os = "openbsd"
 ))]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum ScrollDirection {
     Up,
     Down,
@@ -44,23 +163,582 @@ pub enum ScrollDirection {
     Right,
 }
 
-#[derive(Debug)]
+impl fmt::Display for MouseButton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MouseButton::Left => "left",
+            MouseButton::Middle => "middle",
+            MouseButton::Side => "side",
+            MouseButton::Extra => "extra",
+            MouseButton::Right => "right",
+            MouseButton::Back => "back",
+            MouseButton::Forward => "forward",
+            MouseButton::Task => "task",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for MouseButton {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(MouseButton::Left),
+            "middle" => Ok(MouseButton::Middle),
+            "side" => Ok(MouseButton::Side),
+            "extra" => Ok(MouseButton::Extra),
+            "right" => Ok(MouseButton::Right),
+            "back" => Ok(MouseButton::Back),
+            "forward" => Ok(MouseButton::Forward),
+            "task" => Ok(MouseButton::Task),
+            _ => Err(Error::CustomError(format!("{} is not a mouse button", s))),
+        }
+    }
+}
+
+impl fmt::Display for ScrollDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScrollDirection::Up => "up",
+            ScrollDirection::Down => "down",
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "android",
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            ScrollDirection::Left => "left",
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "android",
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            ScrollDirection::Right => "right",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ScrollDirection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" => Ok(ScrollDirection::Up),
+            "down" => Ok(ScrollDirection::Down),
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "android",
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            "left" => Ok(ScrollDirection::Left),
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "android",
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            "right" => Ok(ScrollDirection::Right),
+            _ => Err(Error::CustomError(format!(
+                "{} is not a scroll direction",
+                s
+            ))),
+        }
+    }
+}
+
+/// The phase of a scroll gesture on the devices that report it, e.g. the trackpads
+///
+/// The momentum phases follow the gesture once the fingers are lifted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ScrollPhase {
+    MayBegin,
+    Began,
+    Changed,
+    Ended,
+    Cancelled,
+    MomentumBegan,
+    MomentumChanged,
+    MomentumEnded,
+}
+
+impl fmt::Display for ScrollPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScrollPhase::MayBegin => "may_begin",
+            ScrollPhase::Began => "began",
+            ScrollPhase::Changed => "changed",
+            ScrollPhase::Ended => "ended",
+            ScrollPhase::Cancelled => "cancelled",
+            ScrollPhase::MomentumBegan => "momentum_began",
+            ScrollPhase::MomentumChanged => "momentum_changed",
+            ScrollPhase::MomentumEnded => "momentum_ended",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ScrollPhase {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "may_begin" => Ok(ScrollPhase::MayBegin),
+            "began" => Ok(ScrollPhase::Began),
+            "changed" => Ok(ScrollPhase::Changed),
+            "ended" => Ok(ScrollPhase::Ended),
+            "cancelled" => Ok(ScrollPhase::Cancelled),
+            "momentum_began" => Ok(ScrollPhase::MomentumBegan),
+            "momentum_changed" => Ok(ScrollPhase::MomentumChanged),
+            "momentum_ended" => Ok(ScrollPhase::MomentumEnded),
+            _ => Err(Error::CustomError(format!("{} is not a scroll phase", s))),
+        }
+    }
+}
+
+/// The mouse events that are reported to the callbacks
+///
+/// With the `serde` feature, the events are represented the same as the events of
+/// the JSON recordings, e.g. `{"type":"press","button":"left"}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "crate::wire::WireEvent", into = "crate::wire::WireEvent")
+)]
 pub enum MouseEvent {
     RelativeMove(i32, i32),
     AbsoluteMove(i32, i32),
     Press(MouseButton),
     Release(MouseButton),
     Scroll(ScrollDirection),
+    /// A high resolution scroll in the units of 1/120 of a wheel detent, positive values
+    /// scroll up and right. It is reported along with the `Scroll` events of the same
+    /// scroll, only to the callbacks whose mask contains `EventMask::SCROLL_HI_RES`
+    ScrollHiRes {
+        dx: i32,
+        dy: i32,
+    },
+    /// A scroll on both axes at once in wheel detents, positive values scroll up and
+    /// right. It is reported along with the `Scroll` events of the same scroll, only
+    /// to the callbacks whose mask contains `EventMask::SCROLL_2D`
+    Scroll2D {
+        dx: i32,
+        dy: i32,
+    },
+    /// The phase of the ongoing scroll gesture, only reported by darwin
+    ScrollPhase(ScrollPhase),
+    /// The device with the given id is disconnected or failed, its events are not
+    /// reported anymore. Only reported by the evdev listener of the unix-like systems
+    DeviceDisconnected(String),
+    /// The button is pressed and released within the double click slop, reported after
+    /// the release. Only reported to the callbacks of `hook_clicks`
+    Click(MouseButton),
+    /// The second click of the button within the double click time and slop,
+    /// reported after its `Click`
+    DoubleClick(MouseButton),
+    /// The third click of the button within the double click time and slop,
+    /// reported after its `Click`
+    TripleClick(MouseButton),
+}
+
+impl MouseEvent {
+    /// Serialize the event to JSON, the same as the events of the recordings
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mouce::common::{MouseButton, MouseEvent};
+    ///
+    /// let event = MouseEvent::Press(MouseButton::Left);
+    /// assert_eq!(event.to_json(), r#"{"type":"press","button":"left"}"#);
+    /// ```
+    pub fn to_json(&self) -> String {
+        format!("{{{}}}", json::event_fields(self))
+    }
+}
+
+/// A mouse event together with the time it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EventWithTime {
+    pub event: MouseEvent,
+    pub time: SystemTime,
+}
+
+/// A mouse event together with the device that produced it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MouseEventExt {
+    /// The identifier of the device, the path of its event file on unix-like systems
+    pub device_id: String,
+    /// The name that the device reports
+    pub device_name: String,
+    pub event: MouseEvent,
+    pub time: SystemTime,
+}
+
+/// The mouse buttons that are held down, see `MouseController::get_button_state`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ButtonState {
+    pub pressed: Vec<MouseButton>,
+}
+
+impl ButtonState {
+    /// Whether the given button is held down
+    pub fn is_pressed(&self, button: &MouseButton) -> bool {
+        self.pressed.contains(button)
+    }
+}
+
+/// The statistics of the running listener, see `MouseListener::listener_stats`
+///
+/// A long running service can check them periodically, e.g. the listener
+/// may stop receiving the events after the system is suspended
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ListenerStats {
+    /// The events that are delivered to the callbacks
+    pub events_received: u64,
+    /// The events of the devices that are dropped because the callbacks fall behind
+    pub events_dropped: u64,
+    /// How many times the callbacks are invoked
+    pub callbacks_invoked: u64,
+    /// The received events of every device, by its `device_id`
+    pub per_device_counts: HashMap<String, u64>,
+    /// The time of the last received event
+    pub last_event_time: Option<SystemTime>,
+    /// Whether the threads of the listener are still running
+    pub running: bool,
+    /// How many times the listener re-opened the devices after losing them
+    pub restarts: u64,
+}
+
+/// A panic of a callback, see `MouseListener::on_callback_panic`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CallbackPanic {
+    /// The callback that panicked
+    pub callback_id: CallbackId,
+    /// The message that the callback panicked with
+    pub message: String,
+    /// Whether the callback is removed after the panic
+    pub unhooked: bool,
+}
+
+/// Whether a grabbing callback consumes the event or passes it through to the system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventAction {
+    Consume,
+    PassThrough,
+}
+
+/// Whether the callbacks of the lower priorities receive an event, see `hook_with_priority`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Propagation {
+    Continue,
+    /// The event is handled, the callbacks of the lower priorities do not receive it
+    Stop,
+}
+
+/// A set of mouse event types that a callback subscribes to
+///
+/// The events that repeat the others in another form, e.g. `ScrollHiRes`, are not
+/// in `ALL`, the callbacks that want them subscribe to them explicitly
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::common::{EventMask, MouseButton, MouseEvent};
+///
+/// let mask = EventMask::PRESS | EventMask::RELEASE;
+/// assert!(mask.matches(&MouseEvent::Press(MouseButton::Left)));
+/// assert!(!mask.matches(&MouseEvent::RelativeMove(1, 0)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventMask(u16);
+
+impl EventMask {
+    pub const PRESS: EventMask = EventMask(1 << 0);
+    pub const RELEASE: EventMask = EventMask(1 << 1);
+    pub const SCROLL: EventMask = EventMask(1 << 2);
+    pub const MOVE_REL: EventMask = EventMask(1 << 3);
+    pub const MOVE_ABS: EventMask = EventMask(1 << 4);
+    /// The changes of the devices e.g. `DeviceDisconnected`
+    pub const DEVICE: EventMask = EventMask(1 << 5);
+    /// The clicks that `hook_clicks` synthesizes e.g. `DoubleClick`
+    pub const CLICK: EventMask = EventMask(1 << 6);
+    /// The `Scroll2D` events, which repeat the `Scroll` events of both axes at once
+    pub const SCROLL_2D: EventMask = EventMask(1 << 7);
+    /// The `ScrollHiRes` events, which repeat the `Scroll` events in smaller units
+    pub const SCROLL_HI_RES: EventMask = EventMask(1 << 8);
+    /// All the event types except for the repeated ones e.g. `SCROLL_HI_RES`
+    pub const ALL: EventMask = EventMask(0b1111111);
+
+    /// A mask that does not contain any event type
+    pub const fn empty() -> Self {
+        EventMask(0)
+    }
+
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// Whether all the event types of `other` are in this mask
+    pub const fn contains(&self, other: EventMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether the type of the given event is in this mask
+    pub fn matches(&self, event: &MouseEvent) -> bool {
+        self.contains(match event {
+            MouseEvent::Press(_) => EventMask::PRESS,
+            MouseEvent::Release(_) => EventMask::RELEASE,
+            MouseEvent::Scroll(_) | MouseEvent::ScrollPhase(_) => EventMask::SCROLL,
+            MouseEvent::ScrollHiRes { .. } => EventMask::SCROLL_HI_RES,
+            MouseEvent::Scroll2D { .. } => EventMask::SCROLL_2D,
+            MouseEvent::RelativeMove(..) => EventMask::MOVE_REL,
+            MouseEvent::AbsoluteMove(..) => EventMask::MOVE_ABS,
+            MouseEvent::DeviceDisconnected(_) => EventMask::DEVICE,
+            MouseEvent::Click(_) | MouseEvent::DoubleClick(_) | MouseEvent::TripleClick(_) => {
+                EventMask::CLICK
+            }
+        })
+    }
+}
+
+impl BitOr for EventMask {
+    type Output = EventMask;
+
+    fn bitor(self, rhs: EventMask) -> EventMask {
+        EventMask(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for EventMask {
+    fn bitor_assign(&mut self, rhs: EventMask) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The units of the coordinates of the mouse actions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoordinateSpace {
+    /// The pixels of the screen
+    #[default]
+    Physical,
+    /// The pixels divided by the scale factor of the desktop, the units
+    /// that the scaled applications and toolkits work in
+    Logical,
+}
+
+impl CoordinateSpace {
+    /// Convert the given coordinates of this space to the physical pixels
+    pub(crate) fn space_to_physical(self, x: i32, y: i32, scale: f64) -> (i32, i32) {
+        match self {
+            CoordinateSpace::Physical => (x, y),
+            CoordinateSpace::Logical => (
+                (x as f64 * scale).round() as i32,
+                (y as f64 * scale).round() as i32,
+            ),
+        }
+    }
+
+    /// Convert the given physical pixels to the coordinates of this space
+    pub(crate) fn physical_to_space(self, x: i32, y: i32, scale: f64) -> (i32, i32) {
+        match self {
+            CoordinateSpace::Physical => (x, y),
+            CoordinateSpace::Logical => (
+                (x as f64 / scale).round() as i32,
+                (y as f64 / scale).round() as i32,
+            ),
+        }
+    }
+}
+
+/// How `move_relative` maps the requested offsets to the moved pixels, e.g.
+/// to keep the deltas of the remote clients consistent on every backend
+///
+/// The offsets are multiplied by the sensitivity and by the gain of the
+/// acceleration curve at the length of the offset, the fractions of the
+/// pixels are carried over to the next moves
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::common::MotionProfile;
+///
+/// // Twice as fast, and twice as fast again for the flicks
+/// let profile = MotionProfile::new()
+///     .sensitivity(2.)
+///     .curve(vec![(0., 1.), (10., 1.), (30., 2.)]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionProfile {
+    sensitivity: f64,
+    /// The points `(length, gain)` of the acceleration curve sorted by the length
+    curve: Vec<(f64, f64)>,
+}
+
+impl MotionProfile {
+    /// The identity profile, the offsets are moved as they are
+    pub fn new() -> Self {
+        MotionProfile {
+            sensitivity: 1.,
+            curve: Vec::new(),
+        }
+    }
+
+    /// The multiplier of every offset
+    pub fn sensitivity(mut self, multiplier: f64) -> Self {
+        self.sensitivity = multiplier.max(0.);
+        self
+    }
+
+    /// The acceleration curve as the points `(length, gain)`, where the length of
+    /// an offset is in pixels. The gain is interpolated linearly between the points
+    /// and stays at the gain of the first or the last point outside of them
+    pub fn curve(mut self, mut points: Vec<(f64, f64)>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.curve = points;
+        self
+    }
+
+    /// The multiplier of an offset with the given length
+    pub fn gain(&self, length: f64) -> f64 {
+        let curve = match (self.curve.first(), self.curve.last()) {
+            (Some(first), _) if length <= first.0 => first.1,
+            (_, Some(last)) if length >= last.0 => last.1,
+            // Between the first and the last point, or no curve at all
+            _ => self
+                .curve
+                .windows(2)
+                .find(|pair| length < pair[1].0)
+                .map_or(1., |pair| {
+                    let ((low, low_gain), (high, high_gain)) = (pair[0], pair[1]);
+                    low_gain + (high_gain - low_gain) * (length - low) / (high - low)
+                }),
+        };
+        self.sensitivity * curve
+    }
+
+    fn is_identity(&self) -> bool {
+        self.sensitivity == 1. && self.curve.is_empty()
+    }
+}
+
+impl Default for MotionProfile {
+    fn default() -> Self {
+        MotionProfile::new()
+    }
+}
+
+/// The motion profile of a manager and the fractions of the pixels that it
+/// has not moved yet
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Motion {
+    pub(crate) profile: MotionProfile,
+    remainder: (f64, f64),
+}
+
+impl Motion {
+    /// Set the given profile, the fractions of the previous one are dropped
+    pub(crate) fn set_profile(&mut self, profile: MotionProfile) {
+        self.profile = profile;
+        self.remainder = (0., 0.);
+    }
+
+    /// Map the given offsets with the profile to the offsets to move
+    pub(crate) fn apply(&mut self, x_offset: i32, y_offset: i32) -> (i32, i32) {
+        if self.profile.is_identity() {
+            return (x_offset, y_offset);
+        }
+        let (x, y) = (x_offset as f64, y_offset as f64);
+        let gain = self.profile.gain(x.hypot(y));
+        let scaled = (x * gain + self.remainder.0, y * gain + self.remainder.1);
+        let moved = (scaled.0.trunc(), scaled.1.trunc());
+        self.remainder = (scaled.0 - moved.0, scaled.1 - moved.1);
+        (moved.0 as i32, moved.1 as i32)
+    }
+}
+
+/// The easing curves that shape the progress of the smooth mouse movements
+#[derive(Debug, Clone, PartialEq)]
+pub enum Easing {
+    /// Constant speed from the start to the end
+    Linear,
+    /// Accelerate at the start and decelerate at the end
+    EaseInOut,
+    /// A cubic bezier curve defined by its two control points `(x1, y1, x2, y2)`,
+    /// the same as the CSS `cubic-bezier` function
+    Bezier(f64, f64, f64, f64),
+}
+
+impl Easing {
+    /// Map the given time progress (between 0 and 1) to the movement progress
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(2) / 2.
+                }
+            }
+            Easing::Bezier(x1, y1, x2, y2) => {
+                let bezier = |p1: f64, p2: f64, s: f64| {
+                    3. * (1. - s).powi(2) * s * p1 + 3. * (1. - s) * s * s * p2 + s.powi(3)
+                };
+                // Find the curve parameter that corresponds to the time
+                // with bisection, x is monotonic for x1 and x2 within [0, 1]
+                let (mut low, mut high) = (0., 1.);
+                for _ in 0..32 {
+                    let mid = (low + high) / 2.;
+                    if bezier(*x1, *x2, mid) < t {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                bezier(*y1, *y2, (low + high) / 2.)
+            }
+        }
+    }
 }
 
+/// The actions that simulate the mouse
 #[cfg(any(
+    target_os = "android",
     target_os = "linux",
     target_os = "dragonfly",
     target_os = "freebsd",
     target_os = "netbsd",
     target_os = "openbsd"
 ))]
-pub trait MouseActions {
+pub trait MouseController {
     /// Move the mouse to the given `x`, `y` coordinates
     ///
     /// # Examples
@@ -68,291 +746,2755 @@ pub trait MouseActions {
     /// ```rust,no_run
     /// use mouce::Mouse;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.move_to(0, 0), Ok(()));
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.move_to(0, 0), Ok(()));
+    /// ```
+    fn move_to(&mut self, x: usize, y: usize) -> Result<(), Error>;
+    /// Move the mouse to the given `x`, `y` coordinates relative to the top left
+    /// corner of the monitor with the given id, see `display::monitors`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // The center of the second monitor
+    /// assert_eq!(manager.move_to_monitor(1, 960, 540), Ok(()));
+    /// ```
+    fn move_to_monitor(&mut self, monitor_id: usize, x: i32, y: i32) -> Result<(), Error> {
+        let (x, y) = display::global_position(monitor_id, x, y)?;
+        self.move_to_virtual(x, y)
+    }
+    /// Move the mouse to the given `x`, `y` coordinates of the virtual desktop, the
+    /// coordinates are negative on the monitors on the left of or above the primary one
+    ///
+    /// Only windows supports the negative coordinates, the other platforms return an error
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // A monitor on the left of the primary one
+    /// assert_eq!(manager.move_to_virtual(-960, 540), Ok(()));
+    /// ```
+    fn move_to_virtual(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        if x < 0 || y < 0 {
+            return Err(Error::CustomError(format!(
+                "the global position {}, {} can not be moved to",
+                x, y
+            )));
+        }
+        self.move_to(x as usize, y as usize)
+    }
+    /// Move the mouse relative to the current position
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.move_relative(100, 100), Ok(()));
+    /// ```
+    fn move_relative(&mut self, x_offset: i32, y_offset: i32) -> Result<(), Error> {
+        let (x, y) = self.get_position()?;
+        self.move_to((x + x_offset) as usize, (y + y_offset) as usize)
+    }
+    /// Move the mouse to the given `x`, `y` coordinates along a straight line,
+    /// the progress over the given `duration` is shaped by the `easing` curve
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::Easing;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let duration = Duration::from_millis(500);
+    /// assert_eq!(manager.move_to_smooth(960, 540, duration, &Easing::EaseInOut), Ok(()));
+    /// ```
+    fn move_to_smooth(
+        &mut self,
+        x: usize,
+        y: usize,
+        duration: Duration,
+        easing: &Easing,
+    ) -> Result<(), Error> {
+        self.move_to_smooth_cancellable(x, y, duration, easing, &CancellationToken::new())
+    }
+    /// Move the mouse like `move_to_smooth`, but stop between the steps once
+    /// the given token is cancelled and fail with `Error::Cancelled`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::Easing;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let token = CancellationToken::new();
+    /// let duration = Duration::from_millis(500);
+    /// manager.move_to_smooth_cancellable(960, 540, duration, &Easing::Linear, &token).unwrap();
+    /// ```
+    fn move_to_smooth_cancellable(
+        &mut self,
+        x: usize,
+        y: usize,
+        duration: Duration,
+        easing: &Easing,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        let (start_x, start_y) = self.get_position()?;
+        let (delta_x, delta_y) = (x as f64 - start_x as f64, y as f64 - start_y as f64);
+        let steps = (duration.as_millis() / SMOOTH_MOVE_INTERVAL.as_millis()).max(1);
+
+        for step in 1..=steps {
+            let progress = easing.apply(step as f64 / steps as f64);
+            self.move_to(
+                (start_x as f64 + delta_x * progress).round() as usize,
+                (start_y as f64 + delta_y * progress).round() as usize,
+            )?;
+            if step != steps {
+                token.sleep(SMOOTH_MOVE_INTERVAL)?;
+            }
+        }
+        Ok(())
+    }
+    /// Get the current position of the mouse
+    ///
+    /// # Examples
+    ///
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::error::Error;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.move_to(0, 0);
+    /// // This function may not be implemented on some platforms such as Linux Wayland
+    /// let valid_outs = vec![Ok((0, 0)), Err(Error::NotImplemented)];
+    /// assert!(valid_outs.contains(&manager.get_position()));
+    /// ```
+    fn get_position(&self) -> Result<(i32, i32), Error>;
+    /// Get the backend that simulates the mouse actions, e.g. to
+    /// tell which backend `Mouse::new` picked
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// println!("Using {}", manager.backend());
+    /// ```
+    fn backend(&self) -> Backend;
+    /// Check whether the manager supports the given action, the unsupported
+    /// actions return `Error::NotImplemented` instead of doing anything
+    ///
+    /// The supported actions can still fail, e.g. without the permission to
+    /// read the devices
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::Capability;
+    ///
+    /// let manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// if manager.supports(Capability::GetPosition) {
+    ///     println!("The cursor is at {:?}", manager.get_position().unwrap());
+    /// }
+    /// ```
+    fn supports(&self, _capability: Capability) -> bool {
+        false
+    }
+    /// Get the mouse buttons that are held down on the physical devices, e.g. to
+    /// release the buttons that are left pressed after reconnecting a remote session
+    ///
+    /// The unix-like systems read the state of the mice from their event files,
+    /// which needs the permission to read /dev/input
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let state = manager.get_button_state().unwrap();
+    /// if state.is_pressed(&MouseButton::Left) {
+    ///     println!("The left button is held down");
+    /// }
+    /// ```
+    fn get_button_state(&self) -> Result<ButtonState, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Set the units of the coordinates of the actions and of `get_position`,
+    /// the physical pixels of the screen by default
+    ///
+    /// Only the unix-like systems support it, where the scale factor is read from
+    /// the `Xft.dpi` resource of X11 or from `GDK_SCALE` and `QT_SCALE_FACTOR`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::CoordinateSpace;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.set_coordinate_space(CoordinateSpace::Logical).unwrap();
+    /// // The center of a 2x scaled 3840x2160 screen
+    /// assert_eq!(manager.move_to(960, 540), Ok(()));
+    /// ```
+    fn set_coordinate_space(&mut self, _space: CoordinateSpace) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Set the profile that maps the offsets of `move_relative` to the moved pixels,
+    /// the offsets are moved as they are by default
+    ///
+    /// The X11, uinput and Windows backends support it
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MotionProfile;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.set_motion_profile(MotionProfile::new().sensitivity(0.5)).unwrap();
+    /// // Moves 50 pixels to the right
+    /// assert_eq!(manager.move_relative(100, 0), Ok(()));
+    /// ```
+    fn set_motion_profile(&mut self, _profile: MotionProfile) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Press down the given mouse button
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.press_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn press_button(&mut self, button: &MouseButton) -> Result<(), Error>;
+    /// Release the given mouse button
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.release_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn release_button(&mut self, button: &MouseButton) -> Result<(), Error>;
+    /// Click the given mouse button
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.click_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn click_button(&mut self, button: &MouseButton) -> Result<(), Error> {
+        self.press_button(button)?;
+        self.release_button(button)
+    }
+    /// Click the given mouse button while the given modifier keys are held,
+    /// e.g. a ctrl-click or a shift-click
+    ///
+    /// The modifiers are pressed with the keyboard manager of the platform
+    /// and released even if the click fails. The managers of the platforms keep
+    /// their keyboard manager, the default creates one for every click
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::common::MouseButton;
+    /// use mouce::keyboard::Modifiers;
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(
+    ///     manager.click_with_modifiers(&MouseButton::Left, Modifiers::SHIFT),
+    ///     Ok(())
+    /// );
+    /// ```
+    fn click_with_modifiers(
+        &mut self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        ModifierKeys::default().hold(modifiers, || self.click_button(button))
+    }
+    /// Move the mouse to the given `x`, `y` coordinates and click the given mouse
+    /// button there, waiting `DEFAULT_CLICK_SETTLE` between the move and the press
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.click_at(100, 200, &MouseButton::Left), Ok(()));
+    /// ```
+    fn click_at(&mut self, x: usize, y: usize, button: &MouseButton) -> Result<(), Error> {
+        self.click_at_with_settle(x, y, button, DEFAULT_CLICK_SETTLE)
+    }
+    /// Move the mouse to the given `x`, `y` coordinates and click the given mouse
+    /// button there, waiting `settle` between the move and the press, see `click_at`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // Some applications only notice the hover after a while
+    /// let settle = Duration::from_millis(100);
+    /// assert_eq!(
+    ///     manager.click_at_with_settle(100, 200, &MouseButton::Left, settle),
+    ///     Ok(())
+    /// );
+    /// ```
+    fn click_at_with_settle(
+        &mut self,
+        x: usize,
+        y: usize,
+        button: &MouseButton,
+        settle: Duration,
+    ) -> Result<(), Error> {
+        self.click_at_with_settle_cancellable(x, y, button, settle, &CancellationToken::new())
+    }
+    /// Click like `click_at_with_settle`, but fail with `Error::Cancelled` without
+    /// pressing the button once the given token is cancelled during the settle
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let token = CancellationToken::new();
+    /// let settle = Duration::from_millis(100);
+    /// manager
+    ///     .click_at_with_settle_cancellable(100, 200, &MouseButton::Left, settle, &token)
+    ///     .unwrap();
+    /// ```
+    fn click_at_with_settle_cancellable(
+        &mut self,
+        x: usize,
+        y: usize,
+        button: &MouseButton,
+        settle: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        self.move_to(x, y)?;
+        token.sleep(settle)?;
+        self.click_button(button)
+    }
+    /// Double click the given mouse button, the clicks are
+    /// `DEFAULT_CLICK_INTERVAL` apart from each other
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.double_click_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn double_click_button(&mut self, button: &MouseButton) -> Result<(), Error> {
+        self.multi_click(button, 2, DEFAULT_CLICK_INTERVAL)
+    }
+    /// Click the given mouse button `n` times, waiting `interval` between
+    /// the clicks. The interval should stay within the double click
+    /// threshold of the system for the clicks to be grouped together
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // Triple click selects the whole line in most text editors
+    /// let interval = Duration::from_millis(30);
+    /// assert_eq!(manager.multi_click(&MouseButton::Left, 3, interval), Ok(()));
+    /// ```
+    fn multi_click(
+        &mut self,
+        button: &MouseButton,
+        n: usize,
+        interval: Duration,
+    ) -> Result<(), Error> {
+        self.multi_click_cancellable(button, n, interval, &CancellationToken::new())
+    }
+    /// Click like `multi_click`, but stop between the clicks once the given
+    /// token is cancelled and fail with `Error::Cancelled`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let token = CancellationToken::new();
+    /// let interval = Duration::from_millis(30);
+    /// manager
+    ///     .multi_click_cancellable(&MouseButton::Left, 3, interval, &token)
+    ///     .unwrap();
+    /// ```
+    fn multi_click_cancellable(
+        &mut self,
+        button: &MouseButton,
+        n: usize,
+        interval: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        for i in 0..n {
+            self.click_button(button)?;
+            if i + 1 != n {
+                token.sleep(interval)?;
+            }
+        }
+        Ok(())
+    }
+    /// Drag with the given mouse button from the `from` coordinates to the `to`
+    /// coordinates, the mouse is moved in small steps and waits `step_delay`
+    /// after each of them, so that the applications notice the drag
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let step_delay = Duration::from_millis(5);
+    /// assert_eq!(
+    ///     manager.drag_to(&MouseButton::Left, (100, 100), (500, 300), step_delay),
+    ///     Ok(())
+    /// );
+    /// ```
+    fn drag_to(
+        &mut self,
+        button: &MouseButton,
+        from: (i32, i32),
+        to: (i32, i32),
+        step_delay: Duration,
+    ) -> Result<(), Error> {
+        self.drag_to_cancellable(button, from, to, step_delay, &CancellationToken::new())
+    }
+    /// Drag like `drag_to`, but stop between the steps once the given token is
+    /// cancelled and fail with `Error::Cancelled`, the button is released either way
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let token = CancellationToken::new();
+    /// let step_delay = Duration::from_millis(5);
+    /// manager
+    ///     .drag_to_cancellable(&MouseButton::Left, (100, 100), (500, 300), step_delay, &token)
+    ///     .unwrap();
+    /// ```
+    fn drag_to_cancellable(
+        &mut self,
+        button: &MouseButton,
+        from: (i32, i32),
+        to: (i32, i32),
+        step_delay: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        let to_position = |x: f64, y: f64| (x.round().max(0.) as usize, y.round().max(0.) as usize);
+        let (delta_x, delta_y) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+        let steps = (delta_x.abs().max(delta_y.abs()) / DRAG_STEP as f64)
+            .ceil()
+            .max(1.) as i32;
+
+        token.check()?;
+        let (x, y) = to_position(from.0 as f64, from.1 as f64);
+        self.move_to(x, y)?;
+        self.press_button(button)?;
+
+        let dragged = token.sleep(step_delay).and_then(|_| {
+            (1..=steps).try_for_each(|step| {
+                let progress = step as f64 / steps as f64;
+                let (x, y) = to_position(
+                    from.0 as f64 + delta_x * progress,
+                    from.1 as f64 + delta_y * progress,
+                );
+                self.move_to(x, y)?;
+                token.sleep(step_delay)
+            })
+        });
+        // Do not leave the button pressed, also when the drag fails or is cancelled
+        let released = self.release_button(button);
+        dragged.and(released)
+    }
+    /// Scroll the mouse wheel towards to the given direction
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::ScrollDirection;
+    /// use std::{thread, time};
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let sleep_duration = time::Duration::from_millis(250);
+    ///
+    /// for _ in 0..5 {
+    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Down), Ok(()));
+    ///     thread::sleep(sleep_duration);
+    /// }
+    ///
+    /// for _ in 0..5 {
+    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Up), Ok(()));
+    ///     thread::sleep(sleep_duration);
+    /// }
+    /// ```
+    fn scroll_wheel(&mut self, direction: &ScrollDirection) -> Result<(), Error>;
+    /// Scroll the mouse wheel towards the given direction by `count` detents,
+    /// waiting `delay_between` between the detents
+    ///
+    /// Many apps drop the wheel events that arrive in the same millisecond,
+    /// so the detents are spaced out unlike with `scroll`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::ScrollDirection;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let delay = Duration::from_millis(15);
+    /// assert_eq!(manager.scroll_n(&ScrollDirection::Down, 5, delay), Ok(()));
+    /// ```
+    fn scroll_n(
+        &mut self,
+        direction: &ScrollDirection,
+        count: u32,
+        delay_between: Duration,
+    ) -> Result<(), Error> {
+        self.scroll_n_cancellable(direction, count, delay_between, &CancellationToken::new())
+    }
+    /// Scroll like `scroll_n`, but stop between the detents once the given
+    /// token is cancelled and fail with `Error::Cancelled`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::ScrollDirection;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let token = CancellationToken::new();
+    /// let delay = Duration::from_millis(15);
+    /// manager
+    ///     .scroll_n_cancellable(&ScrollDirection::Down, 50, delay, &token)
+    ///     .unwrap();
+    /// ```
+    fn scroll_n_cancellable(
+        &mut self,
+        direction: &ScrollDirection,
+        count: u32,
+        delay_between: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        for i in 0..count {
+            self.scroll_wheel(direction)?;
+            if i + 1 != count {
+                token.sleep(delay_between)?;
+            }
+        }
+        Ok(())
+    }
+    /// Scroll the mouse wheel by the given number of detents,
+    /// positive values scroll up and right
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // Scroll down by 5 detents
+    /// assert_eq!(manager.scroll(0, -5), Ok(()));
+    /// ```
+    fn scroll(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        let vertical = if dy > 0 {
+            ScrollDirection::Up
+        } else {
+            ScrollDirection::Down
+        };
+        let horizontal = if dx > 0 {
+            ScrollDirection::Right
+        } else {
+            ScrollDirection::Left
+        };
+        for _ in 0..dy.unsigned_abs() {
+            self.scroll_wheel(&vertical)?;
+        }
+        for _ in 0..dx.unsigned_abs() {
+            self.scroll_wheel(&horizontal)?;
+        }
+        Ok(())
+    }
+    /// Scroll the mouse wheel by the given high resolution amounts, in the units
+    /// of 1/120 of a wheel detent, positive values scroll up and right
+    ///
+    /// Supported by the uinput and windows backends, the others
+    /// return `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // Scroll down by a quarter of a detent
+    /// let _ = manager.scroll_wheel_hi_res(0, -30);
+    /// ```
+    fn scroll_wheel_hi_res(&mut self, _dx: i32, _dy: i32) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Scroll by the given amounts of pixels like a trackpad, positive values scroll
+    /// up and right, the fractions of the pixels are kept
+    ///
+    /// The `phase` marks the scroll as part of a gesture, e.g. `Began`, then `Changed`
+    /// and `Ended`, optionally followed by the momentum phases, which lets the apps
+    /// scroll natively with their rubber banding and momentum. A scroll without a phase
+    /// is a plain continuous scroll
+    ///
+    /// Supported by the darwin backend, the others return `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::common::ScrollPhase;
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let _ = manager.scroll_smooth(0., 0., Some(ScrollPhase::Began));
+    /// for _ in 0..10 {
+    ///     let _ = manager.scroll_smooth(0., -12.5, Some(ScrollPhase::Changed));
+    /// }
+    /// let _ = manager.scroll_smooth(0., 0., Some(ScrollPhase::Ended));
+    /// ```
+    fn scroll_smooth(
+        &mut self,
+        _dx: f64,
+        _dy: f64,
+        _phase: Option<ScrollPhase>,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Keep the cursor within the given rectangle of the global coordinates
+    /// until `release_cursor`, the cursor is moved into it if it is outside
+    ///
+    /// Supported on windows and X11, where the physical mice are confined as well.
+    /// Wayland only confines the pointer to a surface of the application through
+    /// the pointer constraints protocol, so uinput returns `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::display::Rect;
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.confine_cursor(Rect::new(100, 100, 800, 600)).unwrap();
+    /// // ...
+    /// manager.release_cursor().unwrap();
+    /// ```
+    fn confine_cursor(&mut self, _rect: Rect) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Let the cursor move freely again after `confine_cursor`
+    fn release_cursor(&mut self) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Show or hide the cursor, e.g. while the mouse is controlled remotely
+    ///
+    /// X11 hides the cursor until it is shown again or the manager is dropped.
+    /// macOS hides it while the application is active, and windows only hides it
+    /// over the windows of the calling thread. Wayland does not let the other
+    /// clients hide the cursor, so uinput returns `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.set_cursor_visible(false).unwrap();
+    /// ```
+    fn set_cursor_visible(&mut self, _visible: bool) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+}
+
+/// The actions that listen the mouse events
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub trait MouseListener {
+    /// Attach a callback function to mouse events
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::error::Error;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let hook_result = manager.hook(Box::new(|e| println!("New event: {:?}", e)));
+    /// match hook_result {
+    ///     Ok(id) => {
+    ///         assert_eq!(manager.unhook(id), Ok(()));
+    ///     }
+    ///     // Hooking may require user privileges on some systems
+    ///     // e.g. requires super user for Linux
+    ///     Err(err) => assert_eq!(Error::PermissionDenied, err),
+    /// }
+    /// ```
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error>;
+    /// Attach a callback function to mouse events that also receives the
+    /// time of the events, e.g. to compute the speed of the movements
+    ///
+    /// The backends that do not report the time of the events
+    /// use the time the events are received by the listener
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook_with_time(Box::new(|e| println!("{:?} at {:?}", e.event, e.time)))
+    ///     .unwrap();
+    /// ```
+    fn hook_with_time(
+        &mut self,
+        callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(Box::new(move |event| {
+            callback(&EventWithTime {
+                event: event.clone(),
+                time: SystemTime::now(),
+            })
+        }))
+    }
+    /// Attach a callback function to mouse events that also receives
+    /// the device that produced the events, e.g. to tell multiple mice apart
+    ///
+    /// Only the unix-like systems report the devices of the events,
+    /// the other backends return `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook_ext(Box::new(|e| println!("{:?} from {}", e.event, e.device_name)))
+    ///     .unwrap();
+    /// ```
+    fn hook_ext(
+        &mut self,
+        _callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a callback function to the mouse events of the types in the given mask,
+    /// the callback is not invoked at all for the other events. The repeated forms of
+    /// the events, e.g. `EventMask::SCROLL_HI_RES`, are only received this way
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::EventMask;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook_filtered(
+    ///         EventMask::PRESS | EventMask::RELEASE,
+    ///         Box::new(|e| println!("New click event: {:?}", e)),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn hook_filtered(
+        &mut self,
+        mask: EventMask,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(Box::new(move |event| {
+            if mask.matches(event) {
+                callback(event);
+            }
+        }))
+    }
+    /// Attach a callback function that receives the mouse events with the given rate limit,
+    /// e.g. at most one move per frame of a UI, see `RateLimit`
+    ///
+    /// The held back events are merged and delivered from a thread of the callback
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::EventMask;
+    /// use mouce::limit::RateLimit;
+    /// use std::time::Duration;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // Report where the mouse comes to rest
+    /// let limit = RateLimit::new(EventMask::MOVE_ABS).debounce(Duration::from_millis(200));
+    /// manager
+    ///     .hook_limited(limit, Box::new(|e| println!("Rested at: {:?}", e)))
+    ///     .unwrap();
+    /// ```
+    fn hook_limited(
+        &mut self,
+        limit: RateLimit,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(limit::limited(limit, callback))
+    }
+    /// Attach a callback function that receives the clicks, the double clicks and
+    /// the drags that are detected in the mouse events, see `DetectorConfig`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::detect::{DetectorConfig, GestureEvent};
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook_gestures(
+    ///         DetectorConfig::new(),
+    ///         Box::new(|gesture| {
+    ///             if let GestureEvent::Dragging(dx, dy) = gesture {
+    ///                 println!("Dragged by {}, {}", dx, dy);
+    ///             }
+    ///         }),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn hook_gestures(
+        &mut self,
+        config: DetectorConfig,
+        callback: Box<dyn Fn(&GestureEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(detect::detecting(config, callback))
+    }
+    /// Attach a callback function that receives the mouse events along with the clicks,
+    /// the double clicks and the triple clicks that are detected in them with the
+    /// given config, e.g. the double click time and slop of the system
+    ///
+    /// The clicks are reported after the release that completes them, see `GestureDetector`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseEvent;
+    /// use mouce::detect::DetectorConfig;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook_clicks(
+    ///         DetectorConfig::system(),
+    ///         Box::new(|event| {
+    ///             if let MouseEvent::TripleClick(button) = event {
+    ///                 println!("Triple clicked {}", button);
+    ///             }
+    ///         }),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn hook_clicks(
+        &mut self,
+        config: DetectorConfig,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(detect::synthesizing(config, callback))
+    }
+    /// Attach a callback function that runs with the given policy, e.g. on its own
+    /// thread so that it does not delay the other callbacks while it is slow
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::executor::DispatchPolicy;
+    /// use std::{thread, time::Duration};
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook_with_policy(
+    ///         DispatchPolicy::Thread,
+    ///         Box::new(|_| thread::sleep(Duration::from_secs(1))),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn hook_with_policy(
+        &mut self,
+        policy: DispatchPolicy,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(executor::with_policy(policy, callback))
+    }
+    /// Attach a callback function that decides whether the mouse events are delivered
+    /// to the rest of the system, e.g. to build input remappers
+    ///
+    /// The mice are grabbed exclusively while the listener is running and the events
+    /// that are not consumed by any callback are re-emitted from a virtual mouse.
+    /// Only available on the unix-like systems, where it requires the uinput access
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::{EventAction, MouseButton, MouseEvent};
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // Disable the middle button
+    /// manager
+    ///     .hook_grab(Box::new(|e| match e {
+    ///         MouseEvent::Press(MouseButton::Middle)
+    ///         | MouseEvent::Release(MouseButton::Middle) => EventAction::Consume,
+    ///         _ => EventAction::PassThrough,
+    ///     }))
+    ///     .unwrap();
+    /// ```
+    fn hook_grab(
+        &mut self,
+        _callback: Box<dyn Fn(&MouseEvent) -> EventAction + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a callback function that is invoked in the order of the given priority,
+    /// the callbacks of the higher priorities receive the events first
+    ///
+    /// The other callbacks have the priority `0`, and the callbacks of the same priority
+    /// are invoked in the order they are attached. The callback returns `Propagation::Stop`
+    /// once it handles an event, so the callbacks of the lower priorities do not receive it
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::{MouseButton, MouseEvent, Propagation};
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.hook(Box::new(|e| println!("Unhandled: {:?}", e))).unwrap();
+    /// // Handle the right clicks before the other callbacks
+    /// manager
+    ///     .hook_with_priority(
+    ///         10,
+    ///         Box::new(|e| match e {
+    ///             MouseEvent::Press(MouseButton::Right) => Propagation::Stop,
+    ///             _ => Propagation::Continue,
+    ///         }),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn hook_with_priority(
+        &mut self,
+        _priority: i32,
+        _callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a callback function to the mouse events of the given device only,
+    /// the events of the other mice are not delivered to the callback
+    ///
+    /// Built on top of `hook_ext`, so it is only available on the unix-like systems
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::devices::list_mice;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let device = &list_mice().unwrap()[0];
+    /// manager
+    ///     .hook_device(device, Box::new(|e| println!("New event: {:?}", e)))
+    ///     .unwrap();
+    /// ```
+    fn hook_device(
+        &mut self,
+        device: &MouseDeviceInfo,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        let device_id = device.path.clone();
+        self.hook_ext(Box::new(move |event| {
+            if event.device_id == device_id {
+                callback(&event.event);
+            }
+        }))
+    }
+    /// Deliver the given event to the attached callbacks as if a mouse reported it,
+    /// without simulating it on the system, e.g. to test the callbacks or to feed
+    /// the events of software gestures to the same callbacks
+    ///
+    /// Returns `EventAction::Consume` if a grabbing callback consumed the event.
+    /// The injected events do not have a device, so the callbacks of `hook_device`
+    /// do not receive them
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::{MouseButton, MouseEvent};
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook(Box::new(|e| println!("New event: {:?}", e)))
+    ///     .unwrap();
+    /// manager
+    ///     .inject_event(MouseEvent::Press(MouseButton::Left))
+    ///     .unwrap();
+    /// ```
+    fn inject_event(&self, _event: MouseEvent) -> Result<EventAction, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Remove the callback function with the given `CallbackId`
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error>;
+    /// Pause the callback function with the given `CallbackId`, it does not receive
+    /// the events until it is resumed, e.g. to skip an expensive callback for a while
+    /// without attaching it again
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let id = manager
+    ///     .hook(Box::new(|e| println!("New event: {:?}", e)))
+    ///     .unwrap();
+    /// assert_eq!(manager.pause_hook(id), Ok(()));
+    /// assert_eq!(manager.resume_hook(id), Ok(()));
+    /// ```
+    fn pause_hook(&mut self, _callback_id: CallbackId) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Resume the paused callback function with the given `CallbackId`
+    fn resume_hook(&mut self, _callback_id: CallbackId) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Remove all callback functions
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// assert_eq!(manager.unhook_all(), Ok(()));
+    /// ```
+    fn unhook_all(&mut self) -> Result<(), Error>;
+    /// Stop the event listener and wait for its threads to exit
+    ///
+    /// The attached callbacks are kept, calling `hook` again
+    /// starts a new listener. The listener is also stopped when
+    /// the manager is dropped
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.hook(Box::new(|e| println!("New event: {:?}", e))).unwrap();
+    /// assert_eq!(manager.stop_listening(), Ok(()));
+    /// ```
+    fn stop_listening(&mut self) -> Result<(), Error>;
+    /// Get the statistics of the running listener, which start over whenever
+    /// the listener is started, they are all zero if it is not running
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.hook(Box::new(|_| {})).unwrap();
+    /// let stats = manager.listener_stats().unwrap();
+    /// println!("{} events are dropped", stats.events_dropped);
+    /// ```
+    fn listener_stats(&self) -> Result<ListenerStats, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a callback that is invoked whenever the listener recovers from
+    /// losing the devices, e.g. after the system resumes from suspend the
+    /// devices fail to be read until they are opened again
+    ///
+    /// The callback is removed with `unhook` like the other callbacks
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.hook(Box::new(|_| {})).unwrap();
+    /// manager
+    ///     .on_listener_restarted(Box::new(|| println!("The mice are opened again")))
+    ///     .unwrap();
+    /// ```
+    fn on_listener_restarted(
+        &mut self,
+        _callback: Box<dyn Fn() + Send>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a handler that is invoked whenever a callback panics
+    ///
+    /// The panics of the callbacks are always caught, so that the listener keeps
+    /// delivering the events to the other callbacks. If `unhook` is set, the
+    /// panicking callback is removed as well. Attaching a handler replaces the
+    /// previous one
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// manager.hook(Box::new(|_| panic!("the callback failed"))).unwrap();
+    /// manager
+    ///     .on_callback_panic(
+    ///         true,
+    ///         Box::new(|report| eprintln!("Callback {} panicked", report.callback_id)),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn on_callback_panic(
+        &mut self,
+        _unhook: bool,
+        _handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Get an async stream of the mouse events, the stream is fed by a
+    /// callback that is attached with `hook` and unhooked once it is dropped
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    /// use mouce::Mouse;
+    ///
+    /// async fn listen() {
+    ///     let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    ///     let mut stream = manager.event_stream().unwrap();
+    ///     while let Some(event) = stream.next().await {
+    ///         println!("New event: {:?}", event);
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "stream")]
+    fn event_stream(&mut self) -> Result<EventStream, Error> {
+        let (state, callback) = EventStream::channel();
+        let callback_id = self.hook(callback)?;
+        Ok(EventStream::new(state, callback_id))
+    }
+    /// Get a channel receiver of the mouse events, the receiver is fed by a
+    /// callback that is attached with `hook`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let events = manager.subscribe().unwrap();
+    /// for event in events.iter() {
+    ///     println!("New event: {:?}", event);
+    /// }
+    /// ```
+    fn subscribe(&mut self) -> Result<Subscription, Error> {
+        let (receiver, callback) = Subscription::channel();
+        let callback_id = self.hook(callback)?;
+        Ok(Subscription::new(receiver, callback_id))
+    }
+    /// Write every mouse event to the given writer as a line of JSON, e.g. to pipe
+    /// the events into another process or into a log collector
+    ///
+    /// A line has the time of the event in microseconds since the unix epoch, the
+    /// device that produced it (null on the backends that do not report the devices)
+    /// and the same fields as `MouseEvent::to_json`
+    ///
+    /// The lines are written through a buffer on a thread of their own, so a slow
+    /// writer does not delay the other callbacks. The first error of the writer stops
+    /// the writing, it is logged with the `tracing` feature and the callback is unhooked
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use std::io;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// // {"time":1700000000000000,"device_id":null,"device_name":null,"type":"press","button":"left"}
+    /// manager.hook_json(Box::new(io::stdout())).unwrap();
+    /// ```
+    fn hook_json(&mut self, writer: Box<dyn Write + Send>) -> Result<CallbackId, Error> {
+        let lines = spawn_json_writer(writer);
+        let sent = lines.clone();
+        let hooked = self.hook_ext(Box::new(move |event| {
+            let device = (event.device_id.as_str(), event.device_name.as_str());
+            send_json_line(
+                &sent,
+                json::event_line(&event.event, event.time, Some(device)),
+            )
+        }));
+        match hooked {
+            // Without the devices, the lines are written from the events with the time
+            Err(Error::NotImplemented) => self.hook_with_time(Box::new(move |event| {
+                send_json_line(&lines, json::event_line(&event.event, event.time, None))
+            })),
+            hooked => hooked,
+        }
+    }
+}
+
+/// The actions that simulate the mouse
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub trait MouseController {
+    /// Move the mouse to the given `x`, `y` coordinates
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.move_to(0, 0), Ok(()));
+    /// ```
+    fn move_to(&self, x: usize, y: usize) -> Result<(), Error>;
+    /// Move the mouse to the given `x`, `y` coordinates relative to the top left
+    /// corner of the monitor with the given id, see `display::monitors`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// // The center of the second monitor
+    /// assert_eq!(manager.move_to_monitor(1, 960, 540), Ok(()));
+    /// ```
+    fn move_to_monitor(&self, monitor_id: usize, x: i32, y: i32) -> Result<(), Error> {
+        let (x, y) = display::global_position(monitor_id, x, y)?;
+        self.move_to_virtual(x, y)
+    }
+    /// Move the mouse to the given `x`, `y` coordinates of the virtual desktop, the
+    /// coordinates are negative on the monitors on the left of or above the primary one
+    ///
+    /// Only windows supports the negative coordinates, the other platforms return an error
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// // A monitor on the left of the primary one
+    /// assert_eq!(manager.move_to_virtual(-960, 540), Ok(()));
+    /// ```
+    fn move_to_virtual(&self, x: i32, y: i32) -> Result<(), Error> {
+        if x < 0 || y < 0 {
+            return Err(Error::CustomError(format!(
+                "the global position {}, {} can not be moved to",
+                x, y
+            )));
+        }
+        self.move_to(x as usize, y as usize)
+    }
+    /// Move the mouse relative to the current position
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.move_relative(100, 100), Ok(()));
+    /// ```
+    fn move_relative(&self, x_offset: i32, y_offset: i32) -> Result<(), Error> {
+        let (x, y) = self.get_position()?;
+        self.move_to((x + x_offset) as usize, (y + y_offset) as usize)
+    }
+    /// Move the mouse to the given `x`, `y` coordinates along a straight line,
+    /// the progress over the given `duration` is shaped by the `easing` curve
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::Easing;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let duration = Duration::from_millis(500);
+    /// assert_eq!(manager.move_to_smooth(960, 540, duration, &Easing::EaseInOut), Ok(()));
+    /// ```
+    fn move_to_smooth(
+        &self,
+        x: usize,
+        y: usize,
+        duration: Duration,
+        easing: &Easing,
+    ) -> Result<(), Error> {
+        self.move_to_smooth_cancellable(x, y, duration, easing, &CancellationToken::new())
+    }
+    /// Move the mouse like `move_to_smooth`, but stop between the steps once
+    /// the given token is cancelled and fail with `Error::Cancelled`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::Easing;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let token = CancellationToken::new();
+    /// let duration = Duration::from_millis(500);
+    /// manager.move_to_smooth_cancellable(960, 540, duration, &Easing::Linear, &token).unwrap();
+    /// ```
+    fn move_to_smooth_cancellable(
+        &self,
+        x: usize,
+        y: usize,
+        duration: Duration,
+        easing: &Easing,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        let (start_x, start_y) = self.get_position()?;
+        let (delta_x, delta_y) = (x as f64 - start_x as f64, y as f64 - start_y as f64);
+        let steps = (duration.as_millis() / SMOOTH_MOVE_INTERVAL.as_millis()).max(1);
+
+        for step in 1..=steps {
+            let progress = easing.apply(step as f64 / steps as f64);
+            self.move_to(
+                (start_x as f64 + delta_x * progress).round() as usize,
+                (start_y as f64 + delta_y * progress).round() as usize,
+            )?;
+            if step != steps {
+                token.sleep(SMOOTH_MOVE_INTERVAL)?;
+            }
+        }
+        Ok(())
+    }
+    /// Get the current position of the mouse
+    ///
+    /// # Examples
+    ///
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::error::Error;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// manager.move_to(0, 0);
+    /// // This function may not be implemented on some platforms such as Linux Wayland
+    /// let valid_outs = vec![Ok((0, 0)), Err(Error::NotImplemented)];
+    /// assert!(valid_outs.contains(&manager.get_position()));
+    /// ```
+    fn get_position(&self) -> Result<(i32, i32), Error>;
+    /// Get the backend that simulates the mouse actions, e.g. to
+    /// tell which backend `Mouse::new` picked
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// println!("Using {}", manager.backend());
+    /// ```
+    fn backend(&self) -> Backend;
+    /// Check whether the manager supports the given action, the unsupported
+    /// actions return `Error::NotImplemented` instead of doing anything
+    ///
+    /// The supported actions can still fail, e.g. without the permission to
+    /// read the devices
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::Capability;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// if manager.supports(Capability::GetPosition) {
+    ///     println!("The cursor is at {:?}", manager.get_position().unwrap());
+    /// }
+    /// ```
+    fn supports(&self, _capability: Capability) -> bool {
+        false
+    }
+    /// Get the mouse buttons that are held down on the physical devices, e.g. to
+    /// release the buttons that are left pressed after reconnecting a remote session
+    ///
+    /// The unix-like systems read the state of the mice from their event files,
+    /// which needs the permission to read /dev/input
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let state = manager.get_button_state().unwrap();
+    /// if state.is_pressed(&MouseButton::Left) {
+    ///     println!("The left button is held down");
+    /// }
+    /// ```
+    fn get_button_state(&self) -> Result<ButtonState, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Set the units of the coordinates of the actions and of `get_position`,
+    /// the physical pixels of the screen by default
+    ///
+    /// Only the unix-like systems support it, where the scale factor is read from
+    /// the `Xft.dpi` resource of X11 or from `GDK_SCALE` and `QT_SCALE_FACTOR`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::CoordinateSpace;
+    ///
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager.set_coordinate_space(CoordinateSpace::Logical).unwrap();
+    /// // The center of a 2x scaled 3840x2160 screen
+    /// assert_eq!(manager.move_to(960, 540), Ok(()));
+    /// ```
+    fn set_coordinate_space(&mut self, _space: CoordinateSpace) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Set the profile that maps the offsets of `move_relative` to the moved pixels,
+    /// the offsets are moved as they are by default
+    ///
+    /// The X11, uinput and Windows backends support it
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MotionProfile;
+    ///
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager.set_motion_profile(MotionProfile::new().sensitivity(0.5)).unwrap();
+    /// // Moves 50 pixels to the right
+    /// assert_eq!(manager.move_relative(100, 0), Ok(()));
+    /// ```
+    fn set_motion_profile(&mut self, _profile: MotionProfile) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Press down the given mouse button
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.press_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn press_button(&self, button: &MouseButton) -> Result<(), Error>;
+    /// Release the given mouse button
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.release_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn release_button(&self, button: &MouseButton) -> Result<(), Error>;
+    /// Click the given mouse button
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.click_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn click_button(&self, button: &MouseButton) -> Result<(), Error> {
+        self.press_button(button)?;
+        self.release_button(button)
+    }
+    /// Click the given mouse button while the given modifier keys are held,
+    /// e.g. a ctrl-click or a shift-click
+    ///
+    /// The modifiers are pressed with the keyboard manager of the platform
+    /// and released even if the click fails. The managers of the platforms keep
+    /// their keyboard manager, the default creates one for every click
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::common::MouseButton;
+    /// use mouce::keyboard::Modifiers;
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(
+    ///     manager.click_with_modifiers(&MouseButton::Left, Modifiers::SHIFT),
+    ///     Ok(())
+    /// );
+    /// ```
+    fn click_with_modifiers(
+        &self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        ModifierKeys::default().hold(modifiers, || self.click_button(button))
+    }
+    /// Move the mouse to the given `x`, `y` coordinates and click the given mouse
+    /// button there, waiting `DEFAULT_CLICK_SETTLE` between the move and the press
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.click_at(100, 200, &MouseButton::Left), Ok(()));
+    /// ```
+    fn click_at(&self, x: usize, y: usize, button: &MouseButton) -> Result<(), Error> {
+        self.click_at_with_settle(x, y, button, DEFAULT_CLICK_SETTLE)
+    }
+    /// Move the mouse to the given `x`, `y` coordinates and click the given mouse
+    /// button there, waiting `settle` between the move and the press, see `click_at`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// // Some applications only notice the hover after a while
+    /// let settle = Duration::from_millis(100);
+    /// assert_eq!(
+    ///     manager.click_at_with_settle(100, 200, &MouseButton::Left, settle),
+    ///     Ok(())
+    /// );
+    /// ```
+    fn click_at_with_settle(
+        &self,
+        x: usize,
+        y: usize,
+        button: &MouseButton,
+        settle: Duration,
+    ) -> Result<(), Error> {
+        self.click_at_with_settle_cancellable(x, y, button, settle, &CancellationToken::new())
+    }
+    /// Click like `click_at_with_settle`, but fail with `Error::Cancelled` without
+    /// pressing the button once the given token is cancelled during the settle
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let token = CancellationToken::new();
+    /// let settle = Duration::from_millis(100);
+    /// manager
+    ///     .click_at_with_settle_cancellable(100, 200, &MouseButton::Left, settle, &token)
+    ///     .unwrap();
+    /// ```
+    fn click_at_with_settle_cancellable(
+        &self,
+        x: usize,
+        y: usize,
+        button: &MouseButton,
+        settle: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        self.move_to(x, y)?;
+        token.sleep(settle)?;
+        self.click_button(button)
+    }
+    /// Double click the given mouse button, the clicks are
+    /// `DEFAULT_CLICK_INTERVAL` apart from each other
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.double_click_button(&MouseButton::Left), Ok(()));
+    /// ```
+    fn double_click_button(&self, button: &MouseButton) -> Result<(), Error> {
+        self.multi_click(button, 2, DEFAULT_CLICK_INTERVAL)
+    }
+    /// Click the given mouse button `n` times, waiting `interval` between
+    /// the clicks. The interval should stay within the double click
+    /// threshold of the system for the clicks to be grouped together
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// // Triple click selects the whole line in most text editors
+    /// let interval = Duration::from_millis(30);
+    /// assert_eq!(manager.multi_click(&MouseButton::Left, 3, interval), Ok(()));
+    /// ```
+    fn multi_click(&self, button: &MouseButton, n: usize, interval: Duration) -> Result<(), Error> {
+        self.multi_click_cancellable(button, n, interval, &CancellationToken::new())
+    }
+    /// Click like `multi_click`, but stop between the clicks once the given
+    /// token is cancelled and fail with `Error::Cancelled`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let token = CancellationToken::new();
+    /// let interval = Duration::from_millis(30);
+    /// manager
+    ///     .multi_click_cancellable(&MouseButton::Left, 3, interval, &token)
+    ///     .unwrap();
+    /// ```
+    fn multi_click_cancellable(
+        &self,
+        button: &MouseButton,
+        n: usize,
+        interval: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        for i in 0..n {
+            self.click_button(button)?;
+            if i + 1 != n {
+                token.sleep(interval)?;
+            }
+        }
+        Ok(())
+    }
+    /// Drag with the given mouse button from the `from` coordinates to the `to`
+    /// coordinates, the mouse is moved in small steps and waits `step_delay`
+    /// after each of them, so that the applications notice the drag
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let step_delay = Duration::from_millis(5);
+    /// assert_eq!(
+    ///     manager.drag_to(&MouseButton::Left, (100, 100), (500, 300), step_delay),
+    ///     Ok(())
+    /// );
+    /// ```
+    fn drag_to(
+        &self,
+        button: &MouseButton,
+        from: (i32, i32),
+        to: (i32, i32),
+        step_delay: Duration,
+    ) -> Result<(), Error> {
+        self.drag_to_cancellable(button, from, to, step_delay, &CancellationToken::new())
+    }
+    /// Drag like `drag_to`, but stop between the steps once the given token is
+    /// cancelled and fail with `Error::Cancelled`, the button is released either way
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::MouseButton;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let token = CancellationToken::new();
+    /// let step_delay = Duration::from_millis(5);
+    /// manager
+    ///     .drag_to_cancellable(&MouseButton::Left, (100, 100), (500, 300), step_delay, &token)
+    ///     .unwrap();
+    /// ```
+    fn drag_to_cancellable(
+        &self,
+        button: &MouseButton,
+        from: (i32, i32),
+        to: (i32, i32),
+        step_delay: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        let to_position = |x: f64, y: f64| (x.round().max(0.) as usize, y.round().max(0.) as usize);
+        let (delta_x, delta_y) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+        let steps = (delta_x.abs().max(delta_y.abs()) / DRAG_STEP as f64)
+            .ceil()
+            .max(1.) as i32;
+
+        token.check()?;
+        let (x, y) = to_position(from.0 as f64, from.1 as f64);
+        self.move_to(x, y)?;
+        self.press_button(button)?;
+
+        let dragged = token.sleep(step_delay).and_then(|_| {
+            (1..=steps).try_for_each(|step| {
+                let progress = step as f64 / steps as f64;
+                let (x, y) = to_position(
+                    from.0 as f64 + delta_x * progress,
+                    from.1 as f64 + delta_y * progress,
+                );
+                self.move_to(x, y)?;
+                token.sleep(step_delay)
+            })
+        });
+        // Do not leave the button pressed, also when the drag fails or is cancelled
+        let released = self.release_button(button);
+        dragged.and(released)
+    }
+    /// Scroll the mouse wheel towards to the given direction
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::ScrollDirection;
+    /// use std::{thread, time};
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let sleep_duration = time::Duration::from_millis(250);
+    ///
+    /// for _ in 0..5 {
+    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Down), Ok(()));
+    ///     thread::sleep(sleep_duration);
+    /// }
+    ///
+    /// for _ in 0..5 {
+    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Up), Ok(()));
+    ///     thread::sleep(sleep_duration);
+    /// }
+    /// ```
+    fn scroll_wheel(&self, direction: &ScrollDirection) -> Result<(), Error>;
+    /// Scroll the mouse wheel towards the given direction by `count` detents,
+    /// waiting `delay_between` between the detents
+    ///
+    /// Many apps drop the wheel events that arrive in the same millisecond,
+    /// so the detents are spaced out unlike with `scroll`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::ScrollDirection;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let delay = Duration::from_millis(15);
+    /// assert_eq!(manager.scroll_n(&ScrollDirection::Down, 5, delay), Ok(()));
+    /// ```
+    fn scroll_n(
+        &self,
+        direction: &ScrollDirection,
+        count: u32,
+        delay_between: Duration,
+    ) -> Result<(), Error> {
+        self.scroll_n_cancellable(direction, count, delay_between, &CancellationToken::new())
+    }
+    /// Scroll like `scroll_n`, but stop between the detents once the given
+    /// token is cancelled and fail with `Error::Cancelled`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::common::ScrollDirection;
+    /// use std::time::Duration;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let token = CancellationToken::new();
+    /// let delay = Duration::from_millis(15);
+    /// manager
+    ///     .scroll_n_cancellable(&ScrollDirection::Down, 50, delay, &token)
+    ///     .unwrap();
+    /// ```
+    fn scroll_n_cancellable(
+        &self,
+        direction: &ScrollDirection,
+        count: u32,
+        delay_between: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        for i in 0..count {
+            self.scroll_wheel(direction)?;
+            if i + 1 != count {
+                token.sleep(delay_between)?;
+            }
+        }
+        Ok(())
+    }
+    /// Scroll the mouse wheel by the given number of detents,
+    /// positive values scroll up and right
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// // Scroll down by 5 detents
+    /// assert_eq!(manager.scroll(0, -5), Ok(()));
+    /// ```
+    fn scroll(&self, dx: i32, dy: i32) -> Result<(), Error> {
+        // There is no horizontal scrolling on the other systems, so fail before
+        // scrolling vertically instead of doing half of the action
+        #[cfg(not(target_os = "windows"))]
+        if dx != 0 {
+            return Err(Error::NotImplemented);
+        }
+        let vertical = if dy > 0 {
+            ScrollDirection::Up
+        } else {
+            ScrollDirection::Down
+        };
+        for _ in 0..dy.unsigned_abs() {
+            self.scroll_wheel(&vertical)?;
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let horizontal = if dx > 0 {
+                ScrollDirection::Right
+            } else {
+                ScrollDirection::Left
+            };
+            for _ in 0..dx.unsigned_abs() {
+                self.scroll_wheel(&horizontal)?;
+            }
+        }
+        Ok(())
+    }
+    /// Scroll the mouse wheel by the given high resolution amounts, in the units
+    /// of 1/120 of a wheel detent, positive values scroll up and right
+    ///
+    /// Supported by the uinput and windows backends, the others
+    /// return `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// // Scroll down by a quarter of a detent
+    /// let _ = manager.scroll_wheel_hi_res(0, -30);
+    /// ```
+    fn scroll_wheel_hi_res(&self, _dx: i32, _dy: i32) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Scroll by the given amounts of pixels like a trackpad, positive values scroll
+    /// up and right, the fractions of the pixels are kept
+    ///
+    /// The `phase` marks the scroll as part of a gesture, e.g. `Began`, then `Changed`
+    /// and `Ended`, optionally followed by the momentum phases, which lets the apps
+    /// scroll natively with their rubber banding and momentum. A scroll without a phase
+    /// is a plain continuous scroll
+    ///
+    /// Supported by the darwin backend, the others return `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::common::ScrollPhase;
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// let _ = manager.scroll_smooth(0., 0., Some(ScrollPhase::Began));
+    /// for _ in 0..10 {
+    ///     let _ = manager.scroll_smooth(0., -12.5, Some(ScrollPhase::Changed));
+    /// }
+    /// let _ = manager.scroll_smooth(0., 0., Some(ScrollPhase::Ended));
+    /// ```
+    fn scroll_smooth(&self, _dx: f64, _dy: f64, _phase: Option<ScrollPhase>) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Keep the cursor within the given rectangle of the global coordinates
+    /// until `release_cursor`, the cursor is moved into it if it is outside
+    ///
+    /// Supported on windows and X11, where the physical mice are confined as well.
+    /// Wayland only confines the pointer to a surface of the application through
+    /// the pointer constraints protocol, so uinput returns `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::display::Rect;
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// manager.confine_cursor(Rect::new(100, 100, 800, 600)).unwrap();
+    /// // ...
+    /// manager.release_cursor().unwrap();
+    /// ```
+    fn confine_cursor(&self, _rect: Rect) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Let the cursor move freely again after `confine_cursor`
+    fn release_cursor(&self) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Show or hide the cursor, e.g. while the mouse is controlled remotely
+    ///
+    /// X11 hides the cursor until it is shown again or the manager is dropped.
+    /// macOS hides it while the application is active, and windows only hides it
+    /// over the windows of the calling thread. Wayland does not let the other
+    /// clients hide the cursor, so uinput returns `Error::NotImplemented`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let manager = Mouse::new().unwrap();
+    /// manager.set_cursor_visible(false).unwrap();
+    /// ```
+    fn set_cursor_visible(&self, _visible: bool) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+}
+
+/// The actions that listen the mouse events
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub trait MouseListener {
+    /// Attach a callback function to mouse events
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::error::Error;
+    ///
+    /// let mut manager = Mouse::new().unwrap();
+    /// let hook_result = manager.hook(Box::new(|e| println!("New event: {:?}", e)));
+    /// match hook_result {
+    ///     Ok(id) => {
+    ///         assert_eq!(manager.unhook(id), Ok(()));
+    ///     }
+    ///     // Hooking may require user privileges on some systems
+    ///     // e.g. requires super user for Linux
+    ///     Err(err) => assert_eq!(Error::PermissionDenied, err),
+    /// }
+    /// ```
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error>;
+    /// Attach a callback function to mouse events that also receives the
+    /// time of the events, e.g. to compute the speed of the movements
+    ///
+    /// The backends that do not report the time of the events
+    /// use the time the events are received by the listener
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    ///
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager
+    ///     .hook_with_time(Box::new(|e| println!("{:?} at {:?}", e.event, e.time)))
+    ///     .unwrap();
     /// ```
-    fn move_to(&mut self, x: usize, y: usize) -> Result<()>;
-    /// Move the mouse relative to the current position
+    fn hook_with_time(
+        &mut self,
+        callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(Box::new(move |event| {
+            callback(&EventWithTime {
+                event: event.clone(),
+                time: SystemTime::now(),
+            })
+        }))
+    }
+    /// Attach a callback function to mouse events that also receives
+    /// the device that produced the events, e.g. to tell multiple mice apart
+    ///
+    /// Only the unix-like systems and the raw input listener of windows report the
+    /// devices of the events, the other backends return `Error::NotImplemented`
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.move_relative(100, 100), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager
+    ///     .hook_ext(Box::new(|e| println!("{:?} from {}", e.event, e.device_name)))
+    ///     .unwrap();
     /// ```
-    fn move_relative(&mut self, x_offset: i32, y_offset: i32) -> Result<()> {
-        let (x, y) = self.get_position()?;
-        self.move_to((x + x_offset) as usize, (y + y_offset) as usize)
+    fn hook_ext(
+        &mut self,
+        _callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
     }
-    /// Get the current position of the mouse
+    /// Attach a callback function to the mouse events of the types in the given mask,
+    /// the callback is not invoked at all for the other events. The repeated forms of
+    /// the events, e.g. `EventMask::SCROLL_HI_RES`, are only received this way
     ///
     /// # Examples
     ///
-    ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::error::Error;
+    /// use mouce::common::EventMask;
     ///
-    /// let manager = Mouse::new();
-    /// manager.move_to(0, 0);
-    /// // This function may not be implemented on some platforms such as Linux Wayland
-    /// let valid_outs = vec![Ok((0, 0)), Err(Error::NotImplemented)];
-    /// assert!(valid_outs.contains(&manager.get_position()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager
+    ///     .hook_filtered(
+    ///         EventMask::PRESS | EventMask::RELEASE,
+    ///         Box::new(|e| println!("New click event: {:?}", e)),
+    ///     )
+    ///     .unwrap();
     /// ```
-    fn get_position(&self) -> Result<(i32, i32)>;
-    /// Press down the given mouse button
+    fn hook_filtered(
+        &mut self,
+        mask: EventMask,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(Box::new(move |event| {
+            if mask.matches(event) {
+                callback(event);
+            }
+        }))
+    }
+    /// Attach a callback function that receives the mouse events with the given rate limit,
+    /// e.g. at most one move per frame of a UI, see `RateLimit`
+    ///
+    /// The held back events are merged and delivered from a thread of the callback
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::MouseButton;
+    /// use mouce::common::EventMask;
+    /// use mouce::limit::RateLimit;
+    /// use std::time::Duration;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.press_button(&MouseButton::Left), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// // Report where the mouse comes to rest
+    /// let limit = RateLimit::new(EventMask::MOVE_ABS).debounce(Duration::from_millis(200));
+    /// manager
+    ///     .hook_limited(limit, Box::new(|e| println!("Rested at: {:?}", e)))
+    ///     .unwrap();
     /// ```
-    fn press_button(&mut self, button: &MouseButton) -> Result<()>;
-    /// Release the given mouse button
+    fn hook_limited(
+        &mut self,
+        limit: RateLimit,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(limit::limited(limit, callback))
+    }
+    /// Attach a callback function that receives the clicks, the double clicks and
+    /// the drags that are detected in the mouse events, see `DetectorConfig`
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::MouseButton;
+    /// use mouce::detect::{DetectorConfig, GestureEvent};
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.release_button(&MouseButton::Left), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager
+    ///     .hook_gestures(
+    ///         DetectorConfig::new(),
+    ///         Box::new(|gesture| {
+    ///             if let GestureEvent::Dragging(dx, dy) = gesture {
+    ///                 println!("Dragged by {}, {}", dx, dy);
+    ///             }
+    ///         }),
+    ///     )
+    ///     .unwrap();
     /// ```
-    fn release_button(&mut self, button: &MouseButton) -> Result<()>;
-    /// Click the given mouse button
+    fn hook_gestures(
+        &mut self,
+        config: DetectorConfig,
+        callback: Box<dyn Fn(&GestureEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(detect::detecting(config, callback))
+    }
+    /// Attach a callback function that receives the mouse events along with the clicks,
+    /// the double clicks and the triple clicks that are detected in them with the
+    /// given config, e.g. the double click time and slop of the system
+    ///
+    /// The clicks are reported after the release that completes them, see `GestureDetector`
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::MouseButton;
+    /// use mouce::common::MouseEvent;
+    /// use mouce::detect::DetectorConfig;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.click_button(&MouseButton::Left), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager
+    ///     .hook_clicks(
+    ///         DetectorConfig::system(),
+    ///         Box::new(|event| {
+    ///             if let MouseEvent::TripleClick(button) = event {
+    ///                 println!("Triple clicked {}", button);
+    ///             }
+    ///         }),
+    ///     )
+    ///     .unwrap();
     /// ```
-    fn click_button(&mut self, button: &MouseButton) -> Result<()> {
-        self.press_button(&button)?;
-        self.release_button(&button)
+    fn hook_clicks(
+        &mut self,
+        config: DetectorConfig,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(detect::synthesizing(config, callback))
     }
-    /// Scroll the mouse wheel towards to the given direction
+    /// Attach a callback function that runs with the given policy, e.g. on its own
+    /// thread so that it does not delay the other callbacks while it is slow
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::ScrollDirection;
-    /// use std::{thread, time};
+    /// use mouce::executor::DispatchPolicy;
+    /// use std::{thread, time::Duration};
     ///
-    /// let manager = Mouse::new();
-    /// let sleep_duration = time::Duration::from_millis(250);
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager
+    ///     .hook_with_policy(
+    ///         DispatchPolicy::Thread,
+    ///         Box::new(|_| thread::sleep(Duration::from_secs(1))),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn hook_with_policy(
+        &mut self,
+        policy: DispatchPolicy,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook(executor::with_policy(policy, callback))
+    }
+    /// Attach a callback function that decides whether the mouse events are delivered
+    /// to the rest of the system, e.g. to build input remappers
     ///
-    /// for _ in 0..5 {
-    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Down), Ok(()));
-    ///     thread::sleep(sleep_duration);
-    /// }
+    /// The mice are grabbed exclusively while the listener is running and the events
+    /// that are not consumed by any callback are re-emitted from a virtual mouse.
+    /// Only available on the unix-like systems, where it requires the uinput access
     ///
-    /// for _ in 0..5 {
-    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Up), Ok(()));
-    ///     thread::sleep(sleep_duration);
-    /// }
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Mouse;
+    /// use mouce::common::{EventAction, MouseButton, MouseEvent};
+    ///
+    /// let mut manager = Mouse::new().unwrap();
+    /// // Disable the middle button
+    /// manager
+    ///     .hook_grab(Box::new(|e| match e {
+    ///         MouseEvent::Press(MouseButton::Middle)
+    ///         | MouseEvent::Release(MouseButton::Middle) => EventAction::Consume,
+    ///         _ => EventAction::PassThrough,
+    ///     }))
+    ///     .unwrap();
     /// ```
-    fn scroll_wheel(&mut self, direction: &ScrollDirection) -> Result<()>;
-    /// Attach a callback function to mouse events
+    fn hook_grab(
+        &mut self,
+        _callback: Box<dyn Fn(&MouseEvent) -> EventAction + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a callback function that is invoked in the order of the given priority,
+    /// the callbacks of the higher priorities receive the events first
+    ///
+    /// The other callbacks have the priority `0`, and the callbacks of the same priority
+    /// are invoked in the order they are attached. The callback returns `Propagation::Stop`
+    /// once it handles an event, so the callbacks of the lower priorities do not receive it
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::error::Error;
+    /// use mouce::common::{MouseButton, MouseEvent, Propagation};
     ///
-    /// let mut manager = Mouse::new();
-    /// let hook_result = manager.hook(Box::new(|e| println!("New event: {:?}", e)));
-    /// match hook_result {
-    ///     Ok(id) => {
-    ///         assert_eq!(manager.unhook(id), Ok(()));
-    ///     }
-    ///     // Hooking may require user privileges on some systems
-    ///     // e.g. requires super user for Linux
-    ///     Err(err) => assert_eq!(Error::PermissionDenied, err),
-    /// }
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager.hook(Box::new(|e| println!("Unhandled: {:?}", e))).unwrap();
+    /// // Handle the right clicks before the other callbacks
+    /// manager
+    ///     .hook_with_priority(
+    ///         10,
+    ///         Box::new(|e| match e {
+    ///             MouseEvent::Press(MouseButton::Right) => Propagation::Stop,
+    ///             _ => Propagation::Continue,
+    ///         }),
+    ///     )
+    ///     .unwrap();
     /// ```
-    fn hook(&mut self, callback: Box<dyn Fn(&MouseEvent) + Send>) -> Result<CallbackId>;
-    /// Remove the callback function with the given `CallbackId`
-    fn unhook(&mut self, callback_id: CallbackId) -> Result<()>;
-    /// Remove all callback functions
+    fn hook_with_priority(
+        &mut self,
+        _priority: i32,
+        _callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a callback function to the mouse events of the given device only,
+    /// the events of the other mice are not delivered to the callback
+    ///
+    /// Built on top of `hook_ext`, so it is only available where `hook_ext` is
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
+    /// use mouce::devices::list_mice;
     ///
-    /// let mut manager = Mouse::new();
-    /// assert_eq!(manager.unhook_all(), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// let device = &list_mice().unwrap()[0];
+    /// manager
+    ///     .hook_device(device, Box::new(|e| println!("New event: {:?}", e)))
+    ///     .unwrap();
     /// ```
-    fn unhook_all(&mut self) -> Result<()>;
-}
-
-#[cfg(not(any(
-    target_os = "linux",
-    target_os = "dragonfly",
-    target_os = "freebsd",
-    target_os = "netbsd",
-    target_os = "openbsd"
-)))]
-pub trait MouseActions {
-    /// Move the mouse to the given `x`, `y` coordinates
+    fn hook_device(
+        &mut self,
+        device: &MouseDeviceInfo,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        let device_id = device.path.clone();
+        self.hook_ext(Box::new(move |event| {
+            if event.device_id == device_id {
+                callback(&event.event);
+            }
+        }))
+    }
+    /// Deliver the given event to the attached callbacks as if a mouse reported it,
+    /// without simulating it on the system, e.g. to test the callbacks or to feed
+    /// the events of software gestures to the same callbacks
+    ///
+    /// Returns `EventAction::Consume` if a grabbing callback consumed the event.
+    /// The injected events do not have a device, so the callbacks of `hook_device`
+    /// do not receive them
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
+    /// use mouce::common::{MouseButton, MouseEvent};
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.move_to(0, 0), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager
+    ///     .hook(Box::new(|e| println!("New event: {:?}", e)))
+    ///     .unwrap();
+    /// manager
+    ///     .inject_event(MouseEvent::Press(MouseButton::Left))
+    ///     .unwrap();
     /// ```
-    fn move_to(&self, x: usize, y: usize) -> Result<()>;
-    /// Move the mouse relative to the current position
+    fn inject_event(&self, _event: MouseEvent) -> Result<EventAction, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Remove the callback function with the given `CallbackId`
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error>;
+    /// Pause the callback function with the given `CallbackId`, it does not receive
+    /// the events until it is resumed, e.g. to skip an expensive callback for a while
+    /// without attaching it again
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.move_relative(100, 100), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// let id = manager
+    ///     .hook(Box::new(|e| println!("New event: {:?}", e)))
+    ///     .unwrap();
+    /// assert_eq!(manager.pause_hook(id), Ok(()));
+    /// assert_eq!(manager.resume_hook(id), Ok(()));
     /// ```
-    fn move_relative(&self, x_offset: i32, y_offset: i32) -> Result<()> {
-        let (x, y) = self.get_position()?;
-        self.move_to((x + x_offset) as usize, (y + y_offset) as usize)
+    fn pause_hook(&mut self, _callback_id: CallbackId) -> Result<(), Error> {
+        Err(Error::NotImplemented)
     }
-    /// Get the current position of the mouse
+    /// Resume the paused callback function with the given `CallbackId`
+    fn resume_hook(&mut self, _callback_id: CallbackId) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Remove all callback functions
     ///
     /// # Examples
     ///
-    ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::error::Error;
     ///
-    /// let manager = Mouse::new();
-    /// manager.move_to(0, 0);
-    /// // This function may not be implemented on some platforms such as Linux Wayland
-    /// let valid_outs = vec![Ok((0, 0)), Err(Error::NotImplemented)];
-    /// assert!(valid_outs.contains(&manager.get_position()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// assert_eq!(manager.unhook_all(), Ok(()));
     /// ```
-    fn get_position(&self) -> Result<(i32, i32)>;
-    /// Press down the given mouse button
+    fn unhook_all(&mut self) -> Result<(), Error>;
+    /// Stop the event listener and wait for its threads to exit
+    ///
+    /// The attached callbacks are kept, calling `hook` again
+    /// starts a new listener. The listener is also stopped when
+    /// the manager is dropped
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::MouseButton;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.press_button(&MouseButton::Left), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager.hook(Box::new(|e| println!("New event: {:?}", e))).unwrap();
+    /// assert_eq!(manager.stop_listening(), Ok(()));
     /// ```
-    fn press_button(&self, button: &MouseButton) -> Result<()>;
-    /// Release the given mouse button
+    fn stop_listening(&mut self) -> Result<(), Error>;
+    /// Get the statistics of the running listener, which start over whenever
+    /// the listener is started, they are all zero if it is not running
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::MouseButton;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.release_button(&MouseButton::Left), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager.hook(Box::new(|_| {})).unwrap();
+    /// let stats = manager.listener_stats().unwrap();
+    /// println!("{} events are dropped", stats.events_dropped);
     /// ```
-    fn release_button(&self, button: &MouseButton) -> Result<()>;
-    /// Click the given mouse button
+    fn listener_stats(&self) -> Result<ListenerStats, Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Attach a callback that is invoked whenever the listener recovers from
+    /// losing the events, e.g. Windows removes the mouse hook once a callback
+    /// takes too long, and the hook listener installs it again
+    ///
+    /// The callback is removed with `unhook` like the other callbacks
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::MouseButton;
     ///
-    /// let manager = Mouse::new();
-    /// assert_eq!(manager.click_button(&MouseButton::Left), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager.hook(Box::new(|_| {})).unwrap();
+    /// manager
+    ///     .on_listener_restarted(Box::new(|| println!("The mice are opened again")))
+    ///     .unwrap();
     /// ```
-    fn click_button(&self, button: &MouseButton) -> Result<()> {
-        self.press_button(&button)?;
-        self.release_button(&button)
+    fn on_listener_restarted(
+        &mut self,
+        _callback: Box<dyn Fn() + Send>,
+    ) -> Result<CallbackId, Error> {
+        Err(Error::NotImplemented)
     }
-    /// Scroll the mouse wheel towards to the given direction
+    /// Attach a handler that is invoked whenever a callback panics
+    ///
+    /// The panics of the callbacks are always caught, so that the listener keeps
+    /// delivering the events to the other callbacks. If `unhook` is set, the
+    /// panicking callback is removed as well. Attaching a handler replaces the
+    /// previous one
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::common::ScrollDirection;
-    /// use std::{thread, time};
     ///
-    /// let manager = Mouse::new();
-    /// let sleep_duration = time::Duration::from_millis(250);
+    /// let mut manager = Mouse::new().unwrap();
+    /// manager.hook(Box::new(|_| panic!("the callback failed"))).unwrap();
+    /// manager
+    ///     .on_callback_panic(
+    ///         true,
+    ///         Box::new(|report| eprintln!("Callback {} panicked", report.callback_id)),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    fn on_callback_panic(
+        &mut self,
+        _unhook: bool,
+        _handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+    /// Get an async stream of the mouse events, the stream is fed by a
+    /// callback that is attached with `hook` and unhooked once it is dropped
     ///
-    /// for _ in 0..5 {
-    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Down), Ok(()));
-    ///     thread::sleep(sleep_duration);
-    /// }
+    /// # Examples
     ///
-    /// for _ in 0..5 {
-    ///     assert_eq!(manager.scroll_wheel(&ScrollDirection::Up), Ok(()));
-    ///     thread::sleep(sleep_duration);
+    /// ```rust,no_run
+    /// use futures::StreamExt;
+    /// use mouce::Mouse;
+    ///
+    /// async fn listen() {
+    ///     let mut manager = Mouse::new().unwrap();
+    ///     let mut stream = manager.event_stream().unwrap();
+    ///     while let Some(event) = stream.next().await {
+    ///         println!("New event: {:?}", event);
+    ///     }
     /// }
     /// ```
-    fn scroll_wheel(&self, direction: &ScrollDirection) -> Result<()>;
-    /// Attach a callback function to mouse events
+    #[cfg(feature = "stream")]
+    fn event_stream(&mut self) -> Result<EventStream, Error> {
+        let (state, callback) = EventStream::channel();
+        let callback_id = self.hook(callback)?;
+        Ok(EventStream::new(state, callback_id))
+    }
+    /// Get a channel receiver of the mouse events, the receiver is fed by a
+    /// callback that is attached with `hook`
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
-    /// use mouce::error::Error;
     ///
-    /// let mut manager = Mouse::new();
-    /// let hook_result = manager.hook(Box::new(|e| println!("New event: {:?}", e)));
-    /// match hook_result {
-    ///     Ok(id) => {
-    ///         assert_eq!(manager.unhook(id), Ok(()));
-    ///     }
-    ///     // Hooking may require user privileges on some systems
-    ///     // e.g. requires super user for Linux
-    ///     Err(err) => assert_eq!(Error::PermissionDenied, err),
+    /// let mut manager = Mouse::new().unwrap();
+    /// let events = manager.subscribe().unwrap();
+    /// for event in events.iter() {
+    ///     println!("New event: {:?}", event);
     /// }
     /// ```
-    fn hook(&mut self, callback: Box<dyn Fn(&MouseEvent) + Send>) -> Result<CallbackId>;
-    /// Remove the callback function with the given `CallbackId`
-    fn unhook(&mut self, callback_id: CallbackId) -> Result<()>;
-    /// Remove all callback functions
+    fn subscribe(&mut self) -> Result<Subscription, Error> {
+        let (receiver, callback) = Subscription::channel();
+        let callback_id = self.hook(callback)?;
+        Ok(Subscription::new(receiver, callback_id))
+    }
+    /// Write every mouse event to the given writer as a line of JSON, e.g. to pipe
+    /// the events into another process or into a log collector
+    ///
+    /// A line has the time of the event in microseconds since the unix epoch, the
+    /// device that produced it (null on the backends that do not report the devices)
+    /// and the same fields as `MouseEvent::to_json`
+    ///
+    /// The lines are written through a buffer on a thread of their own, so a slow
+    /// writer does not delay the other callbacks. The first error of the writer stops
+    /// the writing, it is logged with the `tracing` feature and the callback is unhooked
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use mouce::Mouse;
+    /// use std::io;
     ///
-    /// let mut manager = Mouse::new();
-    /// assert_eq!(manager.unhook_all(), Ok(()));
+    /// let mut manager = Mouse::new().unwrap();
+    /// // {"time":1700000000000000,"device_id":null,"device_name":null,"type":"press","button":"left"}
+    /// manager.hook_json(Box::new(io::stdout())).unwrap();
     /// ```
-    fn unhook_all(&mut self) -> Result<()>;
+    fn hook_json(&mut self, writer: Box<dyn Write + Send>) -> Result<CallbackId, Error> {
+        let lines = spawn_json_writer(writer);
+        let sent = lines.clone();
+        let hooked = self.hook_ext(Box::new(move |event| {
+            let device = (event.device_id.as_str(), event.device_name.as_str());
+            send_json_line(
+                &sent,
+                json::event_line(&event.event, event.time, Some(device)),
+            )
+        }));
+        match hooked {
+            // Without the devices, the lines are written from the events with the time
+            Err(Error::NotImplemented) => self.hook_with_time(Box::new(move |event| {
+                send_json_line(&lines, json::event_line(&event.event, event.time, None))
+            })),
+            hooked => hooked,
+        }
+    }
+}
+
+/// The reference of a `ButtonGuard` to its manager, mutable where the actions need it
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+type ManagerRef<'a, M> = &'a mut M;
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+type ManagerRef<'a, M> = &'a M;
+
+/// A mouse button that is held down, it is released when the guard is dropped
+///
+/// The guard dereferences to the manager, so that the mouse can be moved while
+/// the button is held, see `HoldButton::hold_button`
+pub struct ButtonGuard<'a, M: MouseController + ?Sized> {
+    manager: ManagerRef<'a, M>,
+    button: MouseButton,
+    released: bool,
+}
+
+impl<'a, M: MouseController + ?Sized> ButtonGuard<'a, M> {
+    /// Press down the given button of the given manager, see `HoldButton::hold_button`
+    pub fn press(manager: ManagerRef<'a, M>, button: &MouseButton) -> Result<Self, Error> {
+        manager.press_button(button)?;
+        Ok(ButtonGuard {
+            manager,
+            button: button.clone(),
+            released: false,
+        })
+    }
+
+    /// The button that is held
+    pub fn button(&self) -> &MouseButton {
+        &self.button
+    }
+
+    /// Release the button now, unlike dropping the guard this reports whether it failed
+    pub fn release(mut self) -> Result<(), Error> {
+        self.released = true;
+        self.manager.release_button(&self.button)
+    }
+}
+
+impl<M: MouseController + ?Sized> Deref for ButtonGuard<'_, M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        self.manager
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+impl<M: MouseController + ?Sized> std::ops::DerefMut for ButtonGuard<'_, M> {
+    fn deref_mut(&mut self) -> &mut M {
+        self.manager
+    }
+}
+
+impl<M: MouseController + ?Sized> Drop for ButtonGuard<'_, M> {
+    fn drop(&mut self) {
+        if !self.released {
+            // Nothing can be done about a failure while dropping
+            let _ = self.manager.release_button(&self.button);
+        }
+    }
+}
+
+/// The actions of the managers that both simulate and listen the mouse
+///
+/// Every type that implements both `MouseController` and `MouseListener` implements it,
+/// so the backends that only support one half can implement just that one
+pub trait MouseActions: MouseController + MouseListener {}
+
+impl<T: MouseController + MouseListener + ?Sized> MouseActions for T {}
+
+/// Holds the mouse buttons down with a guard, every manager implements it
+/// including the trait objects that the constructors return
+macro_rules! hold_button {
+    ($new:literal $(, $mutability:tt)?) => {
+        pub trait HoldButton: MouseController {
+            /// Press down the given mouse button until the returned guard is dropped, so
+            /// that the button is released even if the code that holds it returns early
+            /// with an error or panics
+            ///
+            /// # Examples
+            ///
+            /// ```rust,no_run
+            /// use mouce::Mouse;
+            /// use mouce::common::{HoldButton, MouseButton};
+            ///
+            #[doc = $new]
+            /// let mut held = manager.hold_button(&MouseButton::Left).unwrap();
+            /// // The manager can still be used through the guard e.g. to drag
+            /// held.move_relative(100, 0).unwrap();
+            /// // Release the button
+            /// drop(held);
+            /// ```
+            fn hold_button(
+                &$($mutability)? self,
+                button: &MouseButton,
+            ) -> Result<ButtonGuard<'_, Self>, Error>;
+        }
+
+        impl<M: MouseController + ?Sized> HoldButton for M {
+            fn hold_button(
+                &$($mutability)? self,
+                button: &MouseButton,
+            ) -> Result<ButtonGuard<'_, Self>, Error> {
+                ButtonGuard::press(self, button)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+hold_button!(
+    "let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();",
+    mut
+);
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+hold_button!("let manager = Mouse::new().unwrap();");
+
+/// Start the thread that writes the lines of `hook_json` to the given writer, until
+/// the callback is unhooked or the writer fails
+///
+/// The lines are flushed once no more of them are waiting, so that the readers of
+/// a pipe get them at once without a flush for every line of a burst
+fn spawn_json_writer(writer: Box<dyn Write + Send>) -> Sender<String> {
+    let (tx, rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        let mut writer = BufWriter::new(writer);
+        while let Ok(line) = rx.recv() {
+            let written = iter::once(line)
+                .chain(rx.try_iter())
+                .try_for_each(|line| writeln!(writer, "{}", line))
+                .and_then(|_| writer.flush());
+            if let Err(err) = written {
+                trace::warning!("hook_json stops writing: {}", err);
+                break;
+            }
+        }
+    });
+    tx
+}
+
+/// Send the given line of `hook_json` to its writer, the callback
+/// is unhooked once the writer is stopped by an error
+fn send_json_line(lines: &Sender<String>, line: String) {
+    if lines.send(line).is_err() {
+        hooks::unhook_current();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::MouseActions;
-    use crate::{common::MouseButton, common::ScrollDirection, Mouse};
+    use crate::{
+        common::CoordinateSpace, common::Easing, common::EventMask, common::Motion,
+        common::MotionProfile, common::MouseButton, common::MouseEvent, common::ScrollDirection,
+        Mouse,
+    };
     use std::{thread, time};
 
     #[ignore]
     #[cfg(any(
+        target_os = "android",
         target_os = "linux",
         target_os = "dragonfly",
         target_os = "freebsd",
@@ -365,6 +3507,7 @@ mod tests {
 
     #[ignore]
     #[cfg(not(any(
+        target_os = "android",
         target_os = "linux",
         target_os = "dragonfly",
         target_os = "freebsd",
@@ -372,7 +3515,82 @@ mod tests {
         target_os = "openbsd"
     )))]
     fn get_mouse_manager() -> Box<dyn MouseActions> {
-        Mouse::new()
+        Mouse::new().unwrap()
+    }
+
+    #[test]
+    fn event_masks() {
+        let mask = EventMask::PRESS | EventMask::SCROLL;
+        assert!(mask.contains(EventMask::PRESS));
+        assert!(!mask.contains(EventMask::PRESS | EventMask::RELEASE));
+        assert!(mask.matches(&MouseEvent::Scroll(ScrollDirection::Up)));
+        assert!(!mask.matches(&MouseEvent::Release(MouseButton::Left)));
+        assert!(!EventMask::empty().matches(&MouseEvent::RelativeMove(1, 1)));
+        assert!(EventMask::ALL.matches(&MouseEvent::AbsoluteMove(1, 1)));
+        // The combined and the high resolution scrolls are only received on request
+        let scroll_2d = MouseEvent::Scroll2D { dx: 1, dy: -1 };
+        assert!(!EventMask::ALL.matches(&scroll_2d));
+        assert!((EventMask::ALL | EventMask::SCROLL_2D).matches(&scroll_2d));
+        let scroll_hi_res = MouseEvent::ScrollHiRes { dx: 0, dy: 60 };
+        assert!(!EventMask::ALL.matches(&scroll_hi_res));
+        assert!(!EventMask::SCROLL_2D.matches(&scroll_hi_res));
+        assert!(EventMask::SCROLL_HI_RES.matches(&scroll_hi_res));
+    }
+
+    #[test]
+    fn easing_curves() {
+        let curves = [
+            Easing::Linear,
+            Easing::EaseInOut,
+            Easing::Bezier(0.42, 0., 0.58, 1.),
+        ];
+        for curve in curves.iter() {
+            assert!(curve.apply(0.).abs() < 1e-6);
+            assert!((curve.apply(1.) - 1.).abs() < 1e-6);
+            assert!((curve.apply(0.5) - 0.5).abs() < 1e-6);
+        }
+        assert!(Easing::EaseInOut.apply(0.25) < Easing::Linear.apply(0.25));
+        assert!(Easing::Bezier(0.42, 0., 0.58, 1.).apply(0.25) < 0.25);
+    }
+
+    #[test]
+    fn coordinate_spaces() {
+        let physical = CoordinateSpace::Physical;
+        assert_eq!(physical.space_to_physical(100, 50, 2.), (100, 50));
+        let logical = CoordinateSpace::Logical;
+        assert_eq!(logical.space_to_physical(100, 50, 1.5), (150, 75));
+        assert_eq!(logical.physical_to_space(151, 75, 1.5), (101, 50));
+    }
+
+    #[test]
+    fn motion_profiles() {
+        let profile =
+            MotionProfile::new()
+                .sensitivity(2.)
+                .curve(vec![(30., 3.), (0., 1.), (10., 1.)]);
+        assert_eq!(profile.gain(5.), 2.);
+        assert_eq!(profile.gain(20.), 4.);
+        assert_eq!(profile.gain(100.), 6.);
+
+        // The identity moves the offsets as they are
+        let mut motion = Motion::default();
+        assert_eq!(motion.apply(-7, 3), (-7, 3));
+        // The halves of the pixels add up
+        motion.set_profile(MotionProfile::new().sensitivity(0.5));
+        assert_eq!(motion.apply(3, 1), (1, 0));
+        assert_eq!(motion.apply(3, 1), (2, 1));
+        assert_eq!(motion.apply(-1, 0), (0, 0));
+    }
+
+    #[test]
+    #[ignore]
+    fn move_to_smooth() {
+        let mut manager = get_mouse_manager();
+        assert!(manager.move_to(0, 0).is_ok());
+        let duration = time::Duration::from_millis(500);
+        assert!(manager
+            .move_to_smooth(960, 540, duration, &Easing::EaseInOut)
+            .is_ok());
     }
 
     #[test]
@@ -449,7 +3667,7 @@ mod tests {
         let mut manager = get_mouse_manager();
         match manager.get_position() {
             Ok(_) => {
-                let positions = vec![
+                let positions = [
                     (0, 0),
                     (100, 100),
                     (250, 250),
@@ -480,6 +3698,23 @@ mod tests {
         assert!(manager.click_button(&MouseButton::Left).is_ok());
     }
 
+    #[test]
+    #[ignore]
+    fn double_click() {
+        let mut manager = get_mouse_manager();
+        assert!(manager.double_click_button(&MouseButton::Left).is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn drag_left_button() {
+        let mut manager = get_mouse_manager();
+        let step_delay = time::Duration::from_millis(2);
+        assert!(manager
+            .drag_to(&MouseButton::Left, (100, 100), (500, 300), step_delay)
+            .is_ok());
+    }
+
     #[test]
     #[ignore]
     fn scroll_down() {
@@ -504,6 +3739,7 @@ mod tests {
 
     #[cfg(any(
         target_os = "windows",
+        target_os = "android",
         target_os = "linux",
         target_os = "dragonfly",
         target_os = "freebsd",
@@ -523,6 +3759,7 @@ mod tests {
 
     #[cfg(any(
         target_os = "windows",
+        target_os = "android",
         target_os = "linux",
         target_os = "dragonfly",
         target_os = "freebsd",
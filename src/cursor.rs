@@ -0,0 +1,711 @@
+///
+/// This module contains the changing of the image of the system cursor,
+/// e.g. so that a remote desktop client can show the cursor of the remote desktop
+///
+/// The arrow cursor of the system is replaced until `reset_cursor`, the other
+/// cursors e.g. the text cursor are left as they are. The changes of the shape
+/// of the cursor can also be watched, e.g. so that a remote desktop server can
+/// forward them to its clients
+///
+use crate::error::Error;
+use std::{
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How often the watcher checks the shape of the cursor
+const CURSOR_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The shapes of the cursor, named after the cursors of CSS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CursorIcon {
+    Default,
+    Pointer,
+    Text,
+    Wait,
+    Progress,
+    Crosshair,
+    Move,
+    Grab,
+    Grabbing,
+    NotAllowed,
+    Help,
+    ResizeNs,
+    ResizeEw,
+    ResizeNesw,
+    ResizeNwse,
+    /// A cursor without a standard shape, e.g. a cursor of an application, with its
+    /// name on X11 (empty if it has none) and its handle in hex on windows
+    Other(String),
+}
+
+impl CursorIcon {
+    /// Get the shape of the cursor with the given name of a cursor theme,
+    /// both the CSS names and the legacy X11 names are recognized
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "default" | "left_ptr" | "arrow" | "top_left_arrow" => CursorIcon::Default,
+            "pointer" | "hand" | "hand1" | "hand2" | "pointing_hand" => CursorIcon::Pointer,
+            "text" | "xterm" | "ibeam" => CursorIcon::Text,
+            "wait" | "watch" => CursorIcon::Wait,
+            "progress" | "left_ptr_watch" | "half-busy" => CursorIcon::Progress,
+            "crosshair" | "cross" | "tcross" => CursorIcon::Crosshair,
+            "move" | "fleur" | "all-scroll" | "size_all" => CursorIcon::Move,
+            "grab" | "openhand" => CursorIcon::Grab,
+            "grabbing" | "closedhand" => CursorIcon::Grabbing,
+            "not-allowed" | "no-drop" | "forbidden" | "crossed_circle" => CursorIcon::NotAllowed,
+            "help" | "question_arrow" | "whats_this" => CursorIcon::Help,
+            "ns-resize" | "n-resize" | "s-resize" | "row-resize" | "size_ver"
+            | "sb_v_double_arrow" | "v_double_arrow" | "top_side" | "bottom_side" => {
+                CursorIcon::ResizeNs
+            }
+            "ew-resize" | "e-resize" | "w-resize" | "col-resize" | "size_hor"
+            | "sb_h_double_arrow" | "h_double_arrow" | "left_side" | "right_side" => {
+                CursorIcon::ResizeEw
+            }
+            "nesw-resize" | "ne-resize" | "sw-resize" | "size_bdiag" | "bd_double_arrow"
+            | "top_right_corner" | "bottom_left_corner" => CursorIcon::ResizeNesw,
+            "nwse-resize"
+            | "nw-resize"
+            | "se-resize"
+            | "size_fdiag"
+            | "fd_double_arrow"
+            | "top_left_corner"
+            | "bottom_right_corner" => CursorIcon::ResizeNwse,
+            _ => CursorIcon::Other(name.to_string()),
+        }
+    }
+}
+
+/// An image of the cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorImage {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels row by row, 4 bytes each in the red, green, blue and alpha order
+    pub rgba: Vec<u8>,
+    /// The point of the image that is at the position of the cursor
+    pub hotspot: (u32, u32),
+}
+
+impl CursorImage {
+    fn validate(&self) -> Result<(), Error> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::CustomError("the cursor image is empty".to_string()));
+        }
+        if self.rgba.len() != self.width as usize * self.height as usize * 4 {
+            return Err(Error::CustomError(format!(
+                "a {}x{} cursor image needs {} bytes, but {} are given",
+                self.width,
+                self.height,
+                self.width as usize * self.height as usize * 4,
+                self.rgba.len()
+            )));
+        }
+        if self.hotspot.0 >= self.width || self.hotspot.1 >= self.height {
+            return Err(Error::CustomError(format!(
+                "the hotspot {}, {} is outside of the cursor image",
+                self.hotspot.0, self.hotspot.1
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get the pixels as ARGB words with the colors premultiplied by the alpha
+    #[cfg_attr(not(feature = "xcursor"), allow(dead_code))]
+    pub(crate) fn premultiplied_argb(&self) -> Vec<u32> {
+        self.rgba
+            .chunks_exact(4)
+            .map(|pixel| {
+                let alpha = pixel[3] as u32;
+                let [red, green, blue] =
+                    [pixel[0], pixel[1], pixel[2]].map(|color| color as u32 * alpha / 255);
+                (alpha << 24) | (red << 16) | (green << 8) | blue
+            })
+            .collect()
+    }
+}
+
+/// Replace the arrow cursor of the system with the given image
+///
+/// Supported on windows, on X11 with the `xcursor` feature and on macOS, where
+/// NSCursor only changes the cursor over the windows of the application and has
+/// to be called on the main thread. Wayland only lets the applications set the
+/// cursor of their own surfaces, so it returns `Error::NotImplemented`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::cursor::{self, CursorImage};
+///
+/// // A red square
+/// let image = CursorImage {
+///     width: 16,
+///     height: 16,
+///     rgba: [255, 0, 0, 255].repeat(16 * 16),
+///     hotspot: (8, 8),
+/// };
+/// cursor::set_cursor_image(&image).unwrap();
+/// // ...
+/// cursor::reset_cursor().unwrap();
+/// ```
+pub fn set_cursor_image(image: &CursorImage) -> Result<(), Error> {
+    image.validate()?;
+    platform_set_cursor_image(image)
+}
+
+/// Restore the arrow cursor of the system after `set_cursor_image`
+pub fn reset_cursor() -> Result<(), Error> {
+    platform_reset_cursor()
+}
+
+/// Watches the shape of the cursor until it is dropped
+pub struct CursorWatcher {
+    /// Dropping the sender wakes up and stops the watching thread
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for CursorWatcher {
+    fn drop(&mut self) {
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Invoke the callback with the shape of the cursor whenever it changes
+///
+/// X11 reports the changes through XFixes, and windows is polled for the cursor
+/// that it shows. macOS and Wayland do not report the cursors of the other
+/// applications, so they return `Error::NotImplemented`. The watching stops
+/// once the returned watcher is dropped
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::cursor;
+///
+/// let watcher = cursor::watch_cursor(Box::new(|icon| {
+///     println!("The cursor is changed to {:?}", icon);
+/// }))
+/// .unwrap();
+/// // Stop watching
+/// drop(watcher);
+/// ```
+pub fn watch_cursor(callback: Box<dyn Fn(&CursorIcon) + Send>) -> Result<CursorWatcher, Error> {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let (ready, started) = mpsc::channel();
+    let thread = thread::spawn(move || {
+        // The connections to the display are opened on the watching thread
+        let mut source = match cursor_source() {
+            Ok(source) => {
+                let _ = ready.send(Ok(()));
+                source
+            }
+            Err(err) => {
+                let _ = ready.send(Err(err));
+                return;
+            }
+        };
+        let mut last = None;
+        while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(CURSOR_POLL_INTERVAL) {
+            for icon in source() {
+                if last.as_ref() != Some(&icon) {
+                    callback(&icon);
+                    last = Some(icon);
+                }
+            }
+        }
+    });
+
+    let watcher = CursorWatcher {
+        stop: Some(stop),
+        thread: Some(thread),
+    };
+    started
+        .recv()
+        .unwrap_or_else(|_| Err(Error::CustomError("the watcher failed".to_string())))?;
+    Ok(watcher)
+}
+
+/// Gets the shapes that the cursor is changed to since it is last called
+type CursorSource = Box<dyn FnMut() -> Vec<CursorIcon>>;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn cursor_source() -> Result<CursorSource, Error> {
+    let mut names = crate::nix::CursorNames::new()?;
+    Ok(Box::new(move || {
+        names
+            .changes()
+            .iter()
+            .map(|name| CursorIcon::from_name(name))
+            .collect()
+    }))
+}
+
+#[cfg(any(target_os = "android", target_vendor = "apple", target_arch = "wasm32"))]
+fn cursor_source() -> Result<CursorSource, Error> {
+    Err(Error::NotImplemented)
+}
+
+#[cfg(target_os = "windows")]
+use windows::cursor_source;
+
+#[cfg(all(
+    feature = "xcursor",
+    any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )
+))]
+use crate::nix::{
+    reset_cursor as platform_reset_cursor, set_cursor_image as platform_set_cursor_image,
+};
+
+#[cfg(all(
+    not(feature = "xcursor"),
+    any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )
+))]
+fn platform_set_cursor_image(_image: &CursorImage) -> Result<(), Error> {
+    Err(Error::NotImplemented)
+}
+
+#[cfg(all(
+    not(feature = "xcursor"),
+    any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )
+))]
+fn platform_reset_cursor() -> Result<(), Error> {
+    Err(Error::NotImplemented)
+}
+
+#[cfg(target_vendor = "apple")]
+use darwin::{
+    reset_cursor as platform_reset_cursor, set_cursor_image as platform_set_cursor_image,
+};
+
+#[cfg(target_arch = "wasm32")]
+fn platform_set_cursor_image(_image: &CursorImage) -> Result<(), Error> {
+    // The pages can only change the cursor over their own elements with CSS
+    Err(Error::NotImplemented)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn platform_reset_cursor() -> Result<(), Error> {
+    Err(Error::NotImplemented)
+}
+
+#[cfg(target_os = "windows")]
+use windows::{
+    reset_cursor as platform_reset_cursor, set_cursor_image as platform_set_cursor_image,
+};
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::{CursorIcon, CursorImage, CursorSource};
+    use crate::error::Error;
+    use std::{
+        ffi::c_void,
+        mem::size_of,
+        os::raw::{c_char, c_int, c_long, c_uint, c_ulong},
+        ptr::null_mut,
+    };
+
+    /// The id of the arrow cursor
+    const OCR_NORMAL: c_ulong = 32512;
+    /// Reloads the cursors of the system
+    const SPI_SETCURSORS: c_uint = 0x0057;
+
+    pub fn set_cursor_image(image: &CursorImage) -> Result<(), Error> {
+        // The color bitmap is in the BGRA order
+        let bgra: Vec<u8> = image
+            .rgba
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+            .collect();
+        // The rows of the mask are padded to 16 bits, an empty mask shows the colors as they are
+        let mask = vec![0u8; (image.width as usize).div_ceil(16) * 2 * image.height as usize];
+        unsafe {
+            let color = CreateBitmap(
+                image.width as c_int,
+                image.height as c_int,
+                1,
+                32,
+                bgra.as_ptr() as *const c_void,
+            );
+            let mask = CreateBitmap(
+                image.width as c_int,
+                image.height as c_int,
+                1,
+                1,
+                mask.as_ptr() as *const c_void,
+            );
+            let cursor = CreateIconIndirect(&IconInfo {
+                f_icon: 0,
+                x_hotspot: image.hotspot.0 as c_ulong,
+                y_hotspot: image.hotspot.1 as c_ulong,
+                hbm_mask: mask,
+                hbm_color: color,
+            });
+            // The icon keeps its own copies of the bitmaps
+            DeleteObject(color);
+            DeleteObject(mask);
+            if cursor.is_null() {
+                return Err(Error::CustomError(
+                    "failed to create the cursor".to_string(),
+                ));
+            }
+            // The system takes the ownership of the cursor
+            if SetSystemCursor(cursor, OCR_NORMAL) == 0 {
+                DestroyIcon(cursor);
+                return Err(Error::CustomError(
+                    "failed to set the system cursor".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Poll the cursor that windows shows, the standard cursors are told apart by their handles
+    pub fn cursor_source() -> Result<CursorSource, Error> {
+        let icons: Vec<(HIcon, CursorIcon)> = [
+            (32512, CursorIcon::Default),
+            (32513, CursorIcon::Text),
+            (32514, CursorIcon::Wait),
+            (32515, CursorIcon::Crosshair),
+            (32642, CursorIcon::ResizeNwse),
+            (32643, CursorIcon::ResizeNesw),
+            (32644, CursorIcon::ResizeEw),
+            (32645, CursorIcon::ResizeNs),
+            (32646, CursorIcon::Move),
+            (32648, CursorIcon::NotAllowed),
+            (32649, CursorIcon::Pointer),
+            (32650, CursorIcon::Progress),
+            (32651, CursorIcon::Help),
+        ]
+        .into_iter()
+        .map(|(id, icon)| {
+            (
+                unsafe { LoadCursorA(null_mut(), id as *const c_char) },
+                icon,
+            )
+        })
+        .collect();
+
+        Ok(Box::new(move || {
+            let mut info = CursorInfo {
+                cb_size: size_of::<CursorInfo>() as c_ulong,
+                flags: 0,
+                h_cursor: null_mut(),
+                pt_screen_pos: [0; 2],
+            };
+            if unsafe { GetCursorInfo(&mut info) } == 0 || info.h_cursor.is_null() {
+                return Vec::new();
+            }
+            let icon = icons
+                .iter()
+                .find(|(cursor, _)| *cursor == info.h_cursor)
+                .map(|(_, icon)| icon.clone())
+                .unwrap_or_else(|| CursorIcon::Other(format!("{:#x}", info.h_cursor as usize)));
+            vec![icon]
+        }))
+    }
+
+    pub fn reset_cursor() -> Result<(), Error> {
+        if unsafe { SystemParametersInfoA(SPI_SETCURSORS, 0, null_mut(), 0) } == 0 {
+            return Err(Error::CustomError(
+                "failed to reload the system cursors".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    type HBitmap = *mut c_void;
+    type HIcon = *mut c_void;
+
+    /// CURSORINFO
+    #[repr(C)]
+    struct CursorInfo {
+        cb_size: c_ulong,
+        flags: c_ulong,
+        h_cursor: HIcon,
+        pt_screen_pos: [c_long; 2],
+    }
+
+    /// ICONINFO
+    #[repr(C)]
+    struct IconInfo {
+        f_icon: c_int,
+        x_hotspot: c_ulong,
+        y_hotspot: c_ulong,
+        hbm_mask: HBitmap,
+        hbm_color: HBitmap,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn CreateIconIndirect(piconinfo: *const IconInfo) -> HIcon;
+        fn DestroyIcon(h_icon: HIcon) -> c_int;
+        fn SetSystemCursor(hcur: HIcon, id: c_ulong) -> c_int;
+        fn LoadCursorA(h_instance: *mut c_void, lp_cursor_name: *const c_char) -> HIcon;
+        fn GetCursorInfo(pci: *mut CursorInfo) -> c_int;
+        fn SystemParametersInfoA(
+            ui_action: c_uint,
+            ui_param: c_uint,
+            pv_param: *mut c_void,
+            f_win_ini: c_uint,
+        ) -> c_int;
+    }
+
+    #[link(name = "gdi32")]
+    extern "system" {
+        fn CreateBitmap(
+            n_width: c_int,
+            n_height: c_int,
+            n_planes: c_uint,
+            n_bit_count: c_uint,
+            lp_bits: *const c_void,
+        ) -> HBitmap;
+        fn DeleteObject(ho: *mut c_void) -> c_int;
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+mod darwin {
+    use super::CursorImage;
+    use crate::error::Error;
+    use crate::hooks::LockUnpoisoned;
+    use std::{
+        ffi::{c_void, CString},
+        mem::{self, transmute},
+        os::raw::c_char,
+        ptr::{copy_nonoverlapping, null_mut},
+        sync::Mutex,
+    };
+
+    /// The cursor that is set last, it is released once it is replaced
+    static CURSOR: Mutex<usize> = Mutex::new(0);
+    /// The alpha of the pixels is not premultiplied
+    const NS_BITMAP_FORMAT_ALPHA_NONPREMULTIPLIED: NSUInteger = 1 << 1;
+
+    pub fn set_cursor_image(image: &CursorImage) -> Result<(), Error> {
+        let (width, height) = (image.width as NSInteger, image.height as NSInteger);
+        unsafe {
+            let init_rep: unsafe extern "C" fn(
+                Id,
+                Sel,
+                *mut *mut u8,
+                NSInteger,
+                NSInteger,
+                NSInteger,
+                NSInteger,
+                Bool,
+                Bool,
+                Id,
+                NSUInteger,
+                NSInteger,
+                NSInteger,
+            ) -> Id = transmute(objc_msgSend as unsafe extern "C" fn());
+            // The representation allocates the pixels when it is given no planes
+            let rep = init_rep(
+                send(class("NSBitmapImageRep"), "alloc"),
+                sel(concat!(
+                    "initWithBitmapDataPlanes:pixelsWide:pixelsHigh:bitsPerSample:",
+                    "samplesPerPixel:hasAlpha:isPlanar:colorSpaceName:bitmapFormat:",
+                    "bytesPerRow:bitsPerPixel:"
+                )),
+                null_mut(),
+                width,
+                height,
+                8,
+                4,
+                1,
+                0,
+                NSDeviceRGBColorSpace,
+                NS_BITMAP_FORMAT_ALPHA_NONPREMULTIPLIED,
+                width * 4,
+                32,
+            );
+            if rep.is_null() {
+                return Err(Error::CustomError(
+                    "failed to create the cursor image".to_string(),
+                ));
+            }
+            let pixels = send(rep, "bitmapData") as *mut u8;
+            copy_nonoverlapping(image.rgba.as_ptr(), pixels, image.rgba.len());
+
+            let init_image: unsafe extern "C" fn(Id, Sel, NSSize) -> Id =
+                transmute(objc_msgSend as unsafe extern "C" fn());
+            let ns_image = init_image(
+                send(class("NSImage"), "alloc"),
+                sel("initWithSize:"),
+                NSSize {
+                    width: image.width as f64,
+                    height: image.height as f64,
+                },
+            );
+            let add_representation: unsafe extern "C" fn(Id, Sel, Id) =
+                transmute(objc_msgSend as unsafe extern "C" fn());
+            add_representation(ns_image, sel("addRepresentation:"), rep);
+            send(rep, "release");
+
+            // The hotspot is from the top left corner like the rows of the image
+            let init_cursor: unsafe extern "C" fn(Id, Sel, Id, NSPoint) -> Id =
+                transmute(objc_msgSend as unsafe extern "C" fn());
+            let cursor = init_cursor(
+                send(class("NSCursor"), "alloc"),
+                sel("initWithImage:hotSpot:"),
+                ns_image,
+                NSPoint {
+                    x: image.hotspot.0 as f64,
+                    y: image.hotspot.1 as f64,
+                },
+            );
+            send(ns_image, "release");
+            if cursor.is_null() {
+                return Err(Error::CustomError(
+                    "failed to create the cursor".to_string(),
+                ));
+            }
+            send(cursor, "set");
+            replace(cursor);
+        }
+        Ok(())
+    }
+
+    pub fn reset_cursor() -> Result<(), Error> {
+        unsafe {
+            send(send(class("NSCursor"), "arrowCursor"), "set");
+            replace(null_mut());
+        }
+        Ok(())
+    }
+
+    /// Keep the given cursor while it is set, and release the previous one
+    unsafe fn replace(cursor: Id) {
+        let previous = mem::replace(&mut *CURSOR.lock_unpoisoned(), cursor as usize) as Id;
+        if !previous.is_null() {
+            send(previous, "release");
+        }
+    }
+
+    unsafe fn class(name: &str) -> Id {
+        let name = CString::new(name).unwrap();
+        objc_getClass(name.as_ptr())
+    }
+
+    unsafe fn sel(name: &str) -> Sel {
+        let name = CString::new(name).unwrap();
+        sel_registerName(name.as_ptr())
+    }
+
+    /// Send the given message without arguments
+    unsafe fn send(receiver: Id, selector: &str) -> Id {
+        let send: unsafe extern "C" fn(Id, Sel) -> Id =
+            transmute(objc_msgSend as unsafe extern "C" fn());
+        send(receiver, sel(selector))
+    }
+
+    /// Objective-C and AppKit types
+    type Id = *mut c_void;
+    type Sel = *const c_void;
+    type Bool = i8;
+    type NSInteger = isize;
+    type NSUInteger = usize;
+
+    #[repr(C)]
+    struct NSSize {
+        width: f64,
+        height: f64,
+    }
+
+    #[repr(C)]
+    struct NSPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        /// Transmuted to the signature of each message before it is called
+        fn objc_msgSend();
+    }
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {
+        static NSDeviceRGBColorSpace: Id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CursorIcon, CursorImage};
+
+    fn image(width: u32, height: u32, hotspot: (u32, u32)) -> CursorImage {
+        CursorImage {
+            width,
+            height,
+            rgba: [255, 128, 0, 128].repeat((width * height) as usize),
+            hotspot,
+        }
+    }
+
+    #[test]
+    fn validate_images() {
+        assert!(image(2, 3, (1, 2)).validate().is_ok());
+        assert!(image(2, 3, (2, 0)).validate().is_err());
+        assert!(image(0, 0, (0, 0)).validate().is_err());
+        let mut short = image(2, 2, (0, 0));
+        short.rgba.pop();
+        assert!(short.validate().is_err());
+    }
+
+    #[test]
+    fn cursor_names() {
+        assert_eq!(CursorIcon::from_name("left_ptr"), CursorIcon::Default);
+        assert_eq!(CursorIcon::from_name("xterm"), CursorIcon::Text);
+        assert_eq!(CursorIcon::from_name("pointer"), CursorIcon::Pointer);
+        assert_eq!(
+            CursorIcon::from_name("bd_double_arrow"),
+            CursorIcon::ResizeNesw
+        );
+        assert_eq!(
+            CursorIcon::from_name("custom"),
+            CursorIcon::Other("custom".to_string())
+        );
+        assert_eq!(CursorIcon::from_name(""), CursorIcon::Other(String::new()));
+    }
+
+    #[test]
+    fn premultiply_pixels() {
+        assert_eq!(image(1, 1, (0, 0)).premultiplied_argb(), vec![0x80804000]);
+    }
+}
@@ -1,33 +1,52 @@
 // FIXME: This file is not compiled.
 
 ///
-/// This module contains the mouse action functions
-/// for the darwin systems (MacOS)
+/// This module contains the mouse and keyboard action
+/// functions for the darwin systems (MacOS)
 /// Uses the CoreGraphics (a.k.a Quartz) framework
 ///
-use crate::common::{CallbackId, MouseActions, MouseButton, MouseEvent, ScrollDirection};
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, EventAction, EventMask,
+    MouseActions, MouseButton, MouseController, MouseEvent, MouseListener, Propagation,
+    ScrollDirection, ScrollPhase,
+};
+use crate::detect::DetectorConfig;
+use crate::devices::MouseDeviceInfo;
+use crate::error::Error;
+use crate::hooks::{Dispatcher, Handler, Hooks, DEFAULT_PRIORITY};
+use crate::keyboard::{Key, KeyboardActions, Modifiers};
 use std::{
-    collections::HashMap,
-    io::{Error, ErrorKind, Result},
-    os::raw::{c_double, c_int, c_long, c_uint, c_ulong, c_void},
-    ptr::null_mut,
-    sync::Mutex,
-    thread,
+    cell::RefCell,
+    collections::HashSet,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_double, c_int, c_long, c_uint, c_ulong, c_void},
+    ptr::{null, null_mut},
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
+    time::Duration,
 };
 
 static mut TAP_EVENT_REF: Option<CFTypeRef> = None;
-static mut CALLBACKS: Option<Mutex<HashMap<CallbackId, Box<dyn Fn(&MouseEvent) + Send>>>> = None;
+static mut CALLBACKS: Option<Arc<Hooks<MouseEvent>>> = None;
+
+thread_local! {
+    /// The dispatcher of the listener thread, it keeps the snapshot of the callbacks
+    /// until they change
+    static DISPATCHER: RefCell<Option<Dispatcher<MouseEvent>>> = const { RefCell::new(None) };
+}
 
 pub struct DarwinMouseManager {
     callback_counter: CallbackId,
-    is_listening: bool,
+    /// The run loop (as an address, so it can be sent between
+    /// threads) and the handle of the thread that runs the listener
+    listener: Option<(usize, JoinHandle<()>)>,
 }
 
 impl DarwinMouseManager {
-    pub fn new() -> Result<Box<dyn MouseActions>> {
+    pub fn new() -> Result<Box<dyn MouseActions + Send>, Error> {
         Ok(Box::new(DarwinMouseManager {
             callback_counter: 0,
-            is_listening: false,
+            listener: None,
         }))
     }
 
@@ -35,7 +54,18 @@ impl DarwinMouseManager {
         &self,
         event_type: CGEventType,
         mouse_button: CGMouseButton,
-    ) -> Result<()> {
+    ) -> Result<(), Error> {
+        self.create_mouse_event_with_flags(event_type, mouse_button, None)
+    }
+
+    /// Post a mouse event, the given flags replace the modifiers of the current
+    /// keyboard state that the event would carry otherwise
+    fn create_mouse_event_with_flags(
+        &self,
+        event_type: CGEventType,
+        mouse_button: CGMouseButton,
+        flags: Option<CGEventFlags>,
+    ) -> Result<(), Error> {
         let (pos_x, pos_y) = self.get_position()?;
         let position = CGPoint {
             x: pos_x as c_double,
@@ -45,7 +75,10 @@ impl DarwinMouseManager {
         unsafe {
             let event = CGEventCreateMouseEvent(null_mut(), event_type, position, mouse_button);
             if event == null_mut() {
-                return Err(Error::new(ErrorKind::Other, "CGCouldNotCreateEvent"));
+                return Err(Error::CGCouldNotCreateEvent);
+            }
+            if let Some(flags) = flags {
+                CGEventSetFlags(event, flags);
             }
             CGEventPost(CGEventTapLocation::CGHIDEventTap, event);
             CFRelease(event as CFTypeRef);
@@ -54,7 +87,7 @@ impl DarwinMouseManager {
         Ok(())
     }
 
-    fn create_scroll_wheel_event(&self, distance: c_int) -> Result<()> {
+    fn create_scroll_wheel_event(&self, distance: c_int) -> Result<(), Error> {
         unsafe {
             let event =
                 CGEventCreateScrollWheelEvent(null_mut(), CGScrollEventUnit::Line, 1, distance);
@@ -67,45 +100,130 @@ impl DarwinMouseManager {
         Ok(())
     }
 
-    fn start_listener(&mut self) -> Result<()> {
-        thread::spawn(move || {
+    fn create_pixel_scroll_event(
+        &self,
+        dx: f64,
+        dy: f64,
+        phase: Option<ScrollPhase>,
+    ) -> Result<(), Error> {
+        unsafe {
+            // The second wheel is the horizontal one, which is positive to the left
+            let event = CGEventCreateScrollWheelEvent(
+                null_mut(),
+                CGScrollEventUnit::Pixel,
+                2,
+                dy.round() as c_int,
+                -dx.round() as c_int,
+            );
+            if event == null_mut() {
+                return Err(Error::CGCouldNotCreateEvent);
+            }
+            // The fixed point deltas keep the fractions of the pixels
+            CGEventSetDoubleValueField(event, SCROLL_WHEEL_EVENT_FIXED_PT_DELTA_AXIS_1, dy);
+            CGEventSetDoubleValueField(event, SCROLL_WHEEL_EVENT_FIXED_PT_DELTA_AXIS_2, -dx);
+            CGEventSetIntegerValueField(event, SCROLL_WHEEL_EVENT_IS_CONTINUOUS, 1);
+            if let Some(phase) = phase {
+                let (field, value) = phase_field(&phase);
+                CGEventSetIntegerValueField(event, field, value);
+            }
+            CGEventPost(CGEventTapLocation::CGHIDEventTap, event);
+            CFRelease(event as CFTypeRef);
+        }
+        Ok(())
+    }
+
+    /// Start the listener if it is not running yet and add the given
+    /// handler in the order of the given priority
+    fn add_handler(
+        &mut self,
+        priority: i32,
+        handler: Handler<MouseEvent>,
+    ) -> Result<CallbackId, Error> {
+        if self.listener.is_none() {
+            self.start_listener()?;
+        }
+
+        let id = self.callback_counter;
+        unsafe {
+            initialize_callbacks();
+            if let Some(callbacks) = &CALLBACKS {
+                callbacks.insert(id, priority, EventMask::ALL, handler);
+            }
+        }
+        self.callback_counter += 1;
+        Ok(id)
+    }
+
+    fn start_listener(&mut self) -> Result<(), Error> {
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
             unsafe extern "C" fn mouse_on_event_callback(
                 _proxy: *const c_void,
                 event_type: CGEventType,
                 cg_event: CGEventRef,
                 _user_info: *mut c_void,
             ) -> CGEventRef {
-                // Construct the library's MouseEvent
-                let mouse_event = match event_type {
-                    CGEventType::LeftMouseDown => Some(MouseEvent::Press(MouseButton::Left)),
-                    CGEventType::LeftMouseUp => Some(MouseEvent::Release(MouseButton::Left)),
-                    CGEventType::RightMouseDown => Some(MouseEvent::Press(MouseButton::Right)),
-                    CGEventType::RightMouseUp => Some(MouseEvent::Release(MouseButton::Right)),
-                    CGEventType::OtherMouseDown => Some(MouseEvent::Press(MouseButton::Middle)),
-                    CGEventType::OtherMouseUp => Some(MouseEvent::Release(MouseButton::Middle)),
-                    CGEventType::MouseMoved => {
+                // Construct the library's MouseEvents, a scroll event may also report its phase
+                let mouse_events = match event_type {
+                    CGEventType::LeftMouseDown => vec![MouseEvent::Press(MouseButton::Left)],
+                    CGEventType::LeftMouseUp => vec![MouseEvent::Release(MouseButton::Left)],
+                    CGEventType::RightMouseDown => vec![MouseEvent::Press(MouseButton::Right)],
+                    CGEventType::RightMouseUp => vec![MouseEvent::Release(MouseButton::Right)],
+                    CGEventType::OtherMouseDown => other_button(cg_event)
+                        .map(MouseEvent::Press)
+                        .into_iter()
+                        .collect(),
+                    CGEventType::OtherMouseUp => other_button(cg_event)
+                        .map(MouseEvent::Release)
+                        .into_iter()
+                        .collect(),
+                    // The moves while a button is held are reported as drags
+                    CGEventType::MouseMoved
+                    | CGEventType::LeftMouseDragged
+                    | CGEventType::RightMouseDragged
+                    | CGEventType::OtherMouseDragged => {
                         let point = CGEventGetLocation(cg_event);
-                        Some(MouseEvent::AbsoluteMove(point.x as i32, point.y as i32))
+                        vec![MouseEvent::AbsoluteMove(point.x as i32, point.y as i32)]
                     }
                     CGEventType::ScrollWheel => {
-                        // CGEventField::scrollWheelEventPointDeltaAxis1 = 96
-                        let delta = CGEventGetIntegerValueField(cg_event, 96);
+                        let mut events = Vec::new();
+                        if let Some(phase) = scroll_phase(cg_event) {
+                            events.push(MouseEvent::ScrollPhase(phase));
+                        }
+                        // The phase events of the trackpads may not scroll at all
+                        let delta = CGEventGetIntegerValueField(
+                            cg_event,
+                            SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_1,
+                        );
                         if delta > 0 {
-                            Some(MouseEvent::Scroll(ScrollDirection::Up))
-                        } else {
-                            Some(MouseEvent::Scroll(ScrollDirection::Down))
+                            events.push(MouseEvent::Scroll(ScrollDirection::Up));
+                        } else if delta < 0 {
+                            events.push(MouseEvent::Scroll(ScrollDirection::Down));
+                        }
+                        // The line deltas of both axes, the horizontal one is positive to the left
+                        let (dx, dy) = (
+                            -CGEventGetIntegerValueField(cg_event, SCROLL_WHEEL_EVENT_DELTA_AXIS_2),
+                            CGEventGetIntegerValueField(cg_event, SCROLL_WHEEL_EVENT_DELTA_AXIS_1),
+                        );
+                        if dx != 0 || dy != 0 {
+                            events.push(MouseEvent::Scroll2D {
+                                dx: dx as i32,
+                                dy: dy as i32,
+                            });
                         }
+                        events
                     }
-                    _ => None,
+                    _ => Vec::new(),
                 };
 
-                match (mouse_event, &mut CALLBACKS) {
-                    (Some(event), Some(callbacks)) => {
-                        for callback in callbacks.lock().unwrap().values() {
-                            callback(&event);
+                if let Some(callbacks) = &CALLBACKS {
+                    DISPATCHER.with(|dispatcher| {
+                        let mut dispatcher = dispatcher.borrow_mut();
+                        let dispatcher = dispatcher.get_or_insert_with(|| callbacks.dispatcher());
+                        for event in mouse_events.iter() {
+                            dispatcher.dispatch(event);
                         }
-                    }
-                    _ => {}
+                    });
                 }
 
                 cg_event
@@ -113,7 +231,7 @@ impl DarwinMouseManager {
 
             unsafe {
                 // Create the mouse listener hook
-                TAP_EVENT_REF = Some(CGEventTapCreate(
+                let tap = CGEventTapCreate(
                     CGEventTapLocation::CGHIDEventTap,
                     CGEventTapPlacement::HeadInsertEventTap,
                     CGEventTapOption::ListenOnly as u32,
@@ -124,41 +242,55 @@ impl DarwinMouseManager {
                         + (1 << CGEventType::OtherMouseDown as u64)
                         + (1 << CGEventType::OtherMouseUp as u64)
                         + (1 << CGEventType::MouseMoved as u64)
+                        + (1 << CGEventType::LeftMouseDragged as u64)
+                        + (1 << CGEventType::RightMouseDragged as u64)
+                        + (1 << CGEventType::OtherMouseDragged as u64)
                         + (1 << CGEventType::ScrollWheel as u64),
                     Some(mouse_on_event_callback),
                     null_mut(),
-                ));
+                );
+                if tap.is_null() {
+                    // The process is not trusted for the accessibility
+                    let _ = tx.send(Err(Error::PermissionDenied));
+                    return;
+                }
+                TAP_EVENT_REF = Some(tap);
 
-                let loop_source =
-                    CFMachPortCreateRunLoopSource(null_mut(), TAP_EVENT_REF.unwrap(), 0);
+                let loop_source = CFMachPortCreateRunLoopSource(null_mut(), tap, 0);
                 let current_loop = CFRunLoopGetCurrent();
                 CFRunLoopAddSource(current_loop, loop_source, kCFRunLoopDefaultMode);
-                CGEventTapEnable(TAP_EVENT_REF.unwrap(), true);
+                CGEventTapEnable(tap, true);
+                let _ = tx.send(Ok(current_loop as usize));
+
+                // Runs until stop_listening stops the run loop
                 CFRunLoopRun();
+
+                if let Some(event_ref) = TAP_EVENT_REF.take() {
+                    // Release the tap event
+                    CFRelease(event_ref);
+                }
+                CFRelease(loop_source);
             }
         });
 
+        let run_loop = rx.recv().unwrap_or_else(|_| {
+            Err(Error::CustomError(
+                "failed to start the listener thread".to_string(),
+            ))
+        })?;
+        self.listener = Some((run_loop, handle));
         Ok(())
     }
 }
 
 impl Drop for DarwinMouseManager {
     fn drop(&mut self) {
-        unsafe {
-            match TAP_EVENT_REF {
-                Some(event_ref) => {
-                    // Release the tap event
-                    CFRelease(event_ref);
-                    TAP_EVENT_REF = None;
-                }
-                None => {}
-            }
-        }
+        let _ = self.stop_listening();
     }
 }
 
-impl MouseActions for DarwinMouseManager {
-    fn move_to(&self, x: usize, y: usize) -> Result<()> {
+impl MouseController for DarwinMouseManager {
+    fn move_to(&self, x: usize, y: usize) -> Result<(), Error> {
         let cg_point = CGPoint {
             x: x as f64,
             y: y as f64,
@@ -166,9 +298,8 @@ impl MouseActions for DarwinMouseManager {
         unsafe {
             let result = CGWarpMouseCursorPosition(cg_point);
             if result != CGError::Success {
-                return Err(Error::form(
-                    ErrorKind::Other,
-                    "Failed to move the mouse, CGError is not Success",
+                return Err(Error::CustomError(
+                    "failed to move the mouse, CGError is not Success".to_string(),
                 ));
             }
         };
@@ -176,11 +307,11 @@ impl MouseActions for DarwinMouseManager {
         Ok(())
     }
 
-    fn get_position(&self) -> Result<(i32, i32)> {
+    fn get_position(&self) -> Result<(i32, i32), Error> {
         unsafe {
             let event = CGEventCreate(null_mut());
             if event == null_mut() {
-                return Err(Error::form(ErrorKind::Other, "CGCouldNotCreateEvent"));
+                return Err(Error::CGCouldNotCreateEvent);
             }
             let cursor = CGEventGetLocation(event);
             CFRelease(event as CFTypeRef);
@@ -188,31 +319,97 @@ impl MouseActions for DarwinMouseManager {
         }
     }
 
-    fn press_button(&self, button: &MouseButton) -> Result<()> {
+    fn backend(&self) -> Backend {
+        Backend::Darwin
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::GetPosition
+                | Capability::ButtonState
+                | Capability::SmoothScroll
+                | Capability::CursorVisibility
+                | Capability::InjectEvent
+                | Capability::PauseHook
+                | Capability::CallbackPanics
+        )
+    }
+
+    fn get_button_state(&self) -> Result<ButtonState, Error> {
+        let pressed = [
+            (0, MouseButton::Left),
+            (1, MouseButton::Right),
+            (2, MouseButton::Middle),
+            (3, MouseButton::Back),
+            (4, MouseButton::Forward),
+        ]
+        .into_iter()
+        .filter(|(number, _)| unsafe {
+            CGEventSourceButtonState(CGEventSourceStateID::HIDSystemState, *number)
+        })
+        .map(|(_, button)| button)
+        .collect();
+        Ok(ButtonState { pressed })
+    }
+
+    fn press_button(&self, button: &MouseButton) -> Result<(), Error> {
         let (event_type, mouse_button) = match button {
             MouseButton::Left => (CGEventType::LeftMouseDown, CGMouseButton::Left),
             MouseButton::Middle => (CGEventType::OtherMouseDown, CGMouseButton::Center),
             MouseButton::Right => (CGEventType::RightMouseDown, CGMouseButton::Right),
+            _ => return Err(Error::Unsupported(Backend::Darwin)),
         };
         self.create_mouse_event(event_type, mouse_button)?;
         Ok(())
     }
 
-    fn release_button(&self, button: &MouseButton) -> Result<()> {
+    fn release_button(&self, button: &MouseButton) -> Result<(), Error> {
         let (event_type, mouse_button) = match button {
             MouseButton::Left => (CGEventType::LeftMouseUp, CGMouseButton::Left),
             MouseButton::Middle => (CGEventType::OtherMouseUp, CGMouseButton::Center),
             MouseButton::Right => (CGEventType::RightMouseUp, CGMouseButton::Right),
+            _ => return Err(Error::Unsupported(Backend::Darwin)),
         };
         self.create_mouse_event(event_type, mouse_button)
     }
 
-    fn click_button(&self, button: &MouseButton) -> Result<()> {
+    fn click_button(&self, button: &MouseButton) -> Result<(), Error> {
         self.press_button(button)?;
         self.release_button(button)
     }
 
-    fn scroll_wheel(&self, direction: &ScrollDirection) -> Result<()> {
+    fn click_with_modifiers(
+        &self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        // The apps read the modifiers from the flags of the mouse events,
+        // so there is no need to press the keys
+        let (down, up, mouse_button) = match button {
+            MouseButton::Left => (
+                CGEventType::LeftMouseDown,
+                CGEventType::LeftMouseUp,
+                CGMouseButton::Left,
+            ),
+            MouseButton::Middle => (
+                CGEventType::OtherMouseDown,
+                CGEventType::OtherMouseUp,
+                CGMouseButton::Center,
+            ),
+            MouseButton::Right => (
+                CGEventType::RightMouseDown,
+                CGEventType::RightMouseUp,
+                CGMouseButton::Right,
+            ),
+            _ => return Err(Error::Unsupported(Backend::Darwin)),
+        };
+        let flags = event_flags(modifiers);
+        self.create_mouse_event_with_flags(down, mouse_button, Some(flags))?;
+        self.create_mouse_event_with_flags(up, mouse_button, Some(flags))
+    }
+
+    fn scroll_wheel(&self, direction: &ScrollDirection) -> Result<(), Error> {
         let distance = match direction {
             ScrollDirection::Up => 5,
             ScrollDirection::Down => -5,
@@ -220,51 +417,112 @@ impl MouseActions for DarwinMouseManager {
         self.create_scroll_wheel_event(distance)
     }
 
-    fn hook(&mut self, callback: Box<dyn Fn(&MouseEvent) + Send>) -> Result<CallbackId> {
-        if !self.is_listening {
-            self.start_listener()?;
-            self.is_listening = true;
+    fn scroll(&self, dx: i32, dy: i32) -> Result<(), Error> {
+        if dx != 0 {
+            return Err(Error::NotImplemented);
         }
+        // Post a single event for all the detents, 5 lines each like `scroll_wheel`
+        self.create_scroll_wheel_event(dy.saturating_mul(5))
+    }
 
-        let id = self.callback_counter;
+    fn scroll_smooth(&self, dx: f64, dy: f64, phase: Option<ScrollPhase>) -> Result<(), Error> {
+        self.create_pixel_scroll_event(dx, dy, phase)
+    }
+
+    fn set_cursor_visible(&self, visible: bool) -> Result<(), Error> {
+        let result = unsafe {
+            if visible {
+                CGDisplayShowCursor(CGMainDisplayID())
+            } else {
+                CGDisplayHideCursor(CGMainDisplayID())
+            }
+        };
+        if result != CGError::Success {
+            return Err(Error::CustomError(
+                "failed to change the visibility of the cursor, CGError is not Success".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl MouseListener for DarwinMouseManager {
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(
+            DEFAULT_PRIORITY,
+            Handler::Action(Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            })),
+        )
+    }
+
+    fn hook_with_priority(
+        &mut self,
+        priority: i32,
+        callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(priority, Handler::Propagate(callback))
+    }
+
+    fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+        unsafe {
+            if let Some(callbacks) = &CALLBACKS {
+                callbacks.dispatch(&event);
+            }
+        }
+        // The callbacks can not consume the events here
+        Ok(EventAction::PassThrough)
+    }
+
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
         unsafe {
             match &mut CALLBACKS {
                 Some(callbacks) => {
-                    callbacks.lock().unwrap().insert(id, callback);
+                    if callbacks.remove(callback_id) {
+                        Ok(())
+                    } else {
+                        Err(Error::CallbackNotFound)
+                    }
                 }
                 None => {
                     initialize_callbacks();
-                    return self.hook(callback);
+                    self.unhook(callback_id)
                 }
             }
         }
-        self.callback_counter += 1;
-        Ok(id)
     }
 
-    fn unhook(&mut self, callback_id: CallbackId) -> Result<()> {
+    fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        set_paused(callback_id, true)
+    }
+
+    fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        set_paused(callback_id, false)
+    }
+
+    fn on_callback_panic(
+        &mut self,
+        unhook: bool,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
         unsafe {
-            match &mut CALLBACKS {
-                Some(callbacks) => match callbacks.lock().unwrap().remove(&callback_id) {
-                    Some(_) => Ok(()),
-                    None => Err(Error::new(
-                        ErrorKind::NotFound,
-                        format!("callback id {} not found", callback_id),
-                    )),
-                },
-                None => {
-                    initialize_callbacks();
-                    self.unhook(callback_id)
-                }
+            initialize_callbacks();
+            if let Some(callbacks) = &CALLBACKS {
+                callbacks.set_panic_handler(handler, unhook);
             }
         }
+        Ok(())
     }
 
-    fn unhook_all(&mut self) -> Result<()> {
+    fn unhook_all(&mut self) -> Result<(), Error> {
         unsafe {
             match &mut CALLBACKS {
                 Some(callbacks) => {
-                    callbacks.lock().unwrap().clear();
+                    callbacks.clear();
                 }
                 None => {
                     initialize_callbacks();
@@ -274,6 +532,213 @@ impl MouseActions for DarwinMouseManager {
         }
         Ok(())
     }
+
+    fn stop_listening(&mut self) -> Result<(), Error> {
+        if let Some((run_loop, handle)) = self.listener.take() {
+            unsafe {
+                CFRunLoopStop(run_loop as *mut c_void);
+            }
+            let _ = handle.join();
+        }
+        Ok(())
+    }
+}
+
+/// Map the button of the given OtherMouse event, the same
+/// buttons that X11 reports as the back and the forward buttons
+unsafe fn other_button(cg_event: CGEventRef) -> Option<MouseButton> {
+    match CGEventGetIntegerValueField(cg_event, MOUSE_EVENT_BUTTON_NUMBER) {
+        2 => Some(MouseButton::Middle),
+        3 => Some(MouseButton::Back),
+        4 => Some(MouseButton::Forward),
+        _ => None,
+    }
+}
+
+/// Get the phase of the given scroll event, None for the scroll wheels
+/// that do not report phases
+unsafe fn scroll_phase(cg_event: CGEventRef) -> Option<ScrollPhase> {
+    match CGEventGetIntegerValueField(cg_event, SCROLL_WHEEL_EVENT_SCROLL_PHASE) {
+        SCROLL_PHASE_BEGAN => return Some(ScrollPhase::Began),
+        SCROLL_PHASE_CHANGED => return Some(ScrollPhase::Changed),
+        SCROLL_PHASE_ENDED => return Some(ScrollPhase::Ended),
+        SCROLL_PHASE_CANCELLED => return Some(ScrollPhase::Cancelled),
+        SCROLL_PHASE_MAY_BEGIN => return Some(ScrollPhase::MayBegin),
+        _ => {}
+    }
+    match CGEventGetIntegerValueField(cg_event, SCROLL_WHEEL_EVENT_MOMENTUM_PHASE) {
+        MOMENTUM_PHASE_BEGIN => Some(ScrollPhase::MomentumBegan),
+        MOMENTUM_PHASE_CONTINUE => Some(ScrollPhase::MomentumChanged),
+        MOMENTUM_PHASE_END => Some(ScrollPhase::MomentumEnded),
+        _ => None,
+    }
+}
+
+/// Simulates the keyboard with the virtual key codes of an ANSI keyboard
+/// Get the event flags of the given modifiers
+fn event_flags(modifiers: Modifiers) -> CGEventFlags {
+    [
+        (Modifiers::SHIFT, EVENT_FLAG_MASK_SHIFT),
+        (Modifiers::CONTROL, EVENT_FLAG_MASK_CONTROL),
+        (Modifiers::ALT, EVENT_FLAG_MASK_ALTERNATE),
+        (Modifiers::META, EVENT_FLAG_MASK_COMMAND),
+    ]
+    .into_iter()
+    .filter(|(modifier, _)| modifiers.contains(*modifier))
+    .fold(0, |flags, (_, flag)| flags | flag)
+}
+
+/// Get the field and the value that mark a scroll event with the given phase,
+/// the momentum phases are set in a field of their own
+fn phase_field(phase: &ScrollPhase) -> (c_uint, c_long) {
+    match phase {
+        ScrollPhase::MayBegin => (SCROLL_WHEEL_EVENT_SCROLL_PHASE, SCROLL_PHASE_MAY_BEGIN),
+        ScrollPhase::Began => (SCROLL_WHEEL_EVENT_SCROLL_PHASE, SCROLL_PHASE_BEGAN),
+        ScrollPhase::Changed => (SCROLL_WHEEL_EVENT_SCROLL_PHASE, SCROLL_PHASE_CHANGED),
+        ScrollPhase::Ended => (SCROLL_WHEEL_EVENT_SCROLL_PHASE, SCROLL_PHASE_ENDED),
+        ScrollPhase::Cancelled => (SCROLL_WHEEL_EVENT_SCROLL_PHASE, SCROLL_PHASE_CANCELLED),
+        ScrollPhase::MomentumBegan => (SCROLL_WHEEL_EVENT_MOMENTUM_PHASE, MOMENTUM_PHASE_BEGIN),
+        ScrollPhase::MomentumChanged => {
+            (SCROLL_WHEEL_EVENT_MOMENTUM_PHASE, MOMENTUM_PHASE_CONTINUE)
+        }
+        ScrollPhase::MomentumEnded => (SCROLL_WHEEL_EVENT_MOMENTUM_PHASE, MOMENTUM_PHASE_END),
+    }
+}
+
+pub struct DarwinKeyboardManager {}
+
+impl DarwinKeyboardManager {
+    pub fn new() -> Result<Box<dyn KeyboardActions + Send>, Error> {
+        Ok(Box::new(DarwinKeyboardManager {}))
+    }
+
+    fn create_keyboard_event(&self, key: &Key, key_down: bool) -> Result<(), Error> {
+        let virtual_key = key_code(key).ok_or(Error::Unsupported(Backend::Darwin))?;
+        unsafe {
+            let event = CGEventCreateKeyboardEvent(null_mut(), virtual_key, key_down);
+            if event == null_mut() {
+                return Err(Error::CGCouldNotCreateEvent);
+            }
+            CGEventPost(CGEventTapLocation::CGHIDEventTap, event);
+            CFRelease(event as CFTypeRef);
+        }
+        Ok(())
+    }
+}
+
+impl KeyboardActions for DarwinKeyboardManager {
+    fn press_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.create_keyboard_event(key, true)
+    }
+
+    fn release_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.create_keyboard_event(key, false)
+    }
+}
+
+/// The virtual key code of the given key, the codes of the character
+/// keys follow their positions on the keyboard rather than their order
+fn key_code(key: &Key) -> Option<u16> {
+    let code = match key {
+        Key::Char(ch) => match ch {
+            'a' => 0,
+            's' => 1,
+            'd' => 2,
+            'f' => 3,
+            'h' => 4,
+            'g' => 5,
+            'z' => 6,
+            'x' => 7,
+            'c' => 8,
+            'v' => 9,
+            'b' => 11,
+            'q' => 12,
+            'w' => 13,
+            'e' => 14,
+            'r' => 15,
+            'y' => 16,
+            't' => 17,
+            '1' => 18,
+            '2' => 19,
+            '3' => 20,
+            '4' => 21,
+            '6' => 22,
+            '5' => 23,
+            '=' => 24,
+            '9' => 25,
+            '7' => 26,
+            '-' => 27,
+            '8' => 28,
+            '0' => 29,
+            ']' => 30,
+            'o' => 31,
+            'u' => 32,
+            '[' => 33,
+            'i' => 34,
+            'p' => 35,
+            'l' => 37,
+            'j' => 38,
+            '\'' => 39,
+            'k' => 40,
+            ';' => 41,
+            '\\' => 42,
+            ',' => 43,
+            '/' => 44,
+            'n' => 45,
+            'm' => 46,
+            '.' => 47,
+            '`' => 50,
+            _ => return None,
+        },
+        Key::Enter => 36,
+        Key::Tab => 48,
+        Key::Space => 49,
+        Key::Backspace => 51,
+        Key::Escape => 53,
+        Key::Meta => 55,
+        Key::Shift => 56,
+        Key::CapsLock => 57,
+        Key::Alt => 58,
+        Key::Control => 59,
+        Key::F(n) => match n {
+            1 => 122,
+            2 => 120,
+            3 => 99,
+            4 => 118,
+            5 => 96,
+            6 => 97,
+            7 => 98,
+            8 => 100,
+            9 => 101,
+            10 => 109,
+            11 => 103,
+            12 => 111,
+            _ => return None,
+        },
+        // There is no insert key on the Mac keyboards, the help key is at its place
+        Key::Insert => 114,
+        Key::Home => 115,
+        Key::PageUp => 116,
+        Key::Delete => 117,
+        Key::End => 119,
+        Key::PageDown => 121,
+        Key::Left => 123,
+        Key::Right => 124,
+        Key::Down => 125,
+        Key::Up => 126,
+    };
+    Some(code)
+}
+
+/// Pause or resume the callback with the given id
+fn set_paused(callback_id: CallbackId, paused: bool) -> Result<(), Error> {
+    unsafe {
+        initialize_callbacks();
+        match &CALLBACKS {
+            Some(callbacks) if callbacks.set_paused(callback_id, paused) => Ok(()),
+            _ => Err(Error::CallbackNotFound),
+        }
+    }
 }
 
 fn initialize_callbacks() {
@@ -281,12 +746,183 @@ fn initialize_callbacks() {
         match CALLBACKS {
             Some(_) => {}
             None => {
-                CALLBACKS = Some(Mutex::new(HashMap::new()));
+                CALLBACKS = Some(Arc::new(Hooks::new()));
             }
         }
     }
 }
 
+/// List the mouse devices through the IOKit HID manager
+pub fn list_mice() -> Result<Vec<MouseDeviceInfo>, Error> {
+    let mut mice = Vec::new();
+    unsafe {
+        let manager = IOHIDManagerCreate(null_mut(), K_IOHID_OPTIONS_TYPE_NONE);
+        if manager.is_null() {
+            return Err(Error::CustomError(
+                "failed to create the hid manager".to_string(),
+            ));
+        }
+
+        // Only match the generic desktop mouse devices
+        let keys = [cf_string("DeviceUsagePage"), cf_string("DeviceUsage")];
+        let values = [
+            cf_number(HID_PAGE_GENERIC_DESKTOP),
+            cf_number(HID_USAGE_MOUSE),
+        ];
+        let matching = CFDictionaryCreate(
+            null_mut(),
+            keys.as_ptr(),
+            values.as_ptr(),
+            keys.len() as c_long,
+            &kCFTypeDictionaryKeyCallBacks as *const c_void,
+            &kCFTypeDictionaryValueCallBacks as *const c_void,
+        );
+        for value in keys.iter().chain(values.iter()) {
+            CFRelease(*value);
+        }
+        IOHIDManagerSetDeviceMatching(manager, matching);
+        CFRelease(matching);
+
+        let devices = IOHIDManagerCopyDevices(manager);
+        if !devices.is_null() {
+            let mut values = vec![null(); CFSetGetCount(devices) as usize];
+            CFSetGetValues(devices, values.as_mut_ptr());
+            for device in values {
+                mice.push(device_info(device));
+            }
+            CFRelease(devices);
+        }
+        CFRelease(manager);
+    }
+    Ok(mice)
+}
+
+/// Read the information of the given HID device
+unsafe fn device_info(device: CFTypeRef) -> MouseDeviceInfo {
+    let property = |key: &str| {
+        let key = cf_string(key);
+        let value = IOHIDDeviceGetProperty(device, key);
+        CFRelease(key);
+        value
+    };
+    let number = |key: &str| {
+        let value = property(key);
+        let mut number: c_int = 0;
+        if !value.is_null() {
+            CFNumberGetValue(
+                value,
+                K_CF_NUMBER_INT_TYPE,
+                &mut number as *mut c_int as *mut c_void,
+            );
+        }
+        number
+    };
+
+    // The elements of the device tell which buttons and wheels it has
+    let (mut wheel, mut hwheel, mut buttons) = (false, false, HashSet::new());
+    let elements = IOHIDDeviceCopyMatchingElements(device, null(), K_IOHID_OPTIONS_TYPE_NONE);
+    if !elements.is_null() {
+        for i in 0..CFArrayGetCount(elements) {
+            let element = CFArrayGetValueAtIndex(elements, i);
+            match (
+                IOHIDElementGetUsagePage(element),
+                IOHIDElementGetUsage(element),
+            ) {
+                (HID_PAGE_GENERIC_DESKTOP, HID_USAGE_WHEEL) => wheel = true,
+                (HID_PAGE_CONSUMER, HID_USAGE_AC_PAN) => hwheel = true,
+                (HID_PAGE_BUTTON, usage) => {
+                    buttons.insert(usage);
+                }
+                _ => {}
+            }
+        }
+        CFRelease(elements);
+    }
+
+    let mut path = [0 as c_char; 512];
+    IORegistryEntryGetPath(
+        IOHIDDeviceGetService(device),
+        b"IOService\0".as_ptr() as *const c_char,
+        path.as_mut_ptr(),
+    );
+
+    MouseDeviceInfo {
+        name: cf_string_to_string(property("Product")),
+        vendor_id: number("VendorID") as u16,
+        product_id: number("ProductID") as u16,
+        path: CStr::from_ptr(path.as_ptr()).to_string_lossy().into_owned(),
+        wheel,
+        hwheel,
+        extra_buttons: buttons.len() > 3,
+    }
+}
+
+/// Get the double click time of the user, the `com.apple.mouse.doubleClickThreshold`
+/// global preference in seconds. macOS has no setting for the slop, AppKit only
+/// compares the times, so the slop of `DetectorConfig::new` is kept
+pub(crate) fn detector_config() -> DetectorConfig {
+    let config = DetectorConfig::new();
+    let seconds = unsafe {
+        let key = cf_string("com.apple.mouse.doubleClickThreshold");
+        let value = CFPreferencesCopyAppValue(key, kCFPreferencesAnyApplication);
+        CFRelease(key);
+        if value.is_null() {
+            return config;
+        }
+        let mut seconds: c_double = 0.0;
+        // The preference may be set to anything with `defaults write`
+        let read = CFGetTypeID(value) == CFNumberGetTypeID()
+            && CFNumberGetValue(
+                value,
+                K_CF_NUMBER_DOUBLE_TYPE,
+                &mut seconds as *mut c_double as *mut c_void,
+            );
+        CFRelease(value);
+        if !read {
+            return config;
+        }
+        seconds
+    };
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(interval) if !interval.is_zero() => config.double_click_interval(interval),
+        _ => config,
+    }
+}
+
+/// Create a CFString from the given string, it needs to be released by the caller
+unsafe fn cf_string(value: &str) -> CFTypeRef {
+    let value = CString::new(value).unwrap();
+    CFStringCreateWithCString(null_mut(), value.as_ptr(), K_CF_STRING_ENCODING_UTF8)
+}
+
+/// Create a CFNumber from the given value, it needs to be released by the caller
+unsafe fn cf_number(value: c_uint) -> CFTypeRef {
+    let value = value as c_int;
+    CFNumberCreate(
+        null_mut(),
+        K_CF_NUMBER_INT_TYPE,
+        &value as *const c_int as *const c_void,
+    )
+}
+
+/// Copy the given CFString, returns an empty string if it is not set
+unsafe fn cf_string_to_string(value: CFTypeRef) -> String {
+    let mut buffer = [0 as c_char; 256];
+    if value.is_null()
+        || !CFStringGetCString(
+            value,
+            buffer.as_mut_ptr(),
+            buffer.len() as c_long,
+            K_CF_STRING_ENCODING_UTF8,
+        )
+    {
+        return String::new();
+    }
+    CStr::from_ptr(buffer.as_ptr())
+        .to_string_lossy()
+        .into_owned()
+}
+
 /// CoreGraphics type definitions
 #[allow(dead_code)]
 #[derive(PartialEq, Eq)]
@@ -323,14 +959,43 @@ enum CGEventType {
     RightMouseDown = 3,
     RightMouseUp = 4,
     MouseMoved = 5,
-    _LeftMouseDragged = 6,
-    _RightMouseDragged = 7,
+    LeftMouseDragged = 6,
+    RightMouseDragged = 7,
     ScrollWheel = 22,
     OtherMouseDown = 25,
     OtherMouseUp = 26,
-    _OtherMouseDragged = 27,
+    OtherMouseDragged = 27,
 }
 
+/// CGEventField definitions
+const MOUSE_EVENT_BUTTON_NUMBER: c_uint = 3;
+const SCROLL_WHEEL_EVENT_DELTA_AXIS_1: c_uint = 11;
+const SCROLL_WHEEL_EVENT_DELTA_AXIS_2: c_uint = 12;
+const SCROLL_WHEEL_EVENT_IS_CONTINUOUS: c_uint = 88;
+const SCROLL_WHEEL_EVENT_FIXED_PT_DELTA_AXIS_1: c_uint = 93;
+const SCROLL_WHEEL_EVENT_FIXED_PT_DELTA_AXIS_2: c_uint = 94;
+const SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_1: c_uint = 96;
+const SCROLL_WHEEL_EVENT_SCROLL_PHASE: c_uint = 99;
+const SCROLL_WHEEL_EVENT_MOMENTUM_PHASE: c_uint = 123;
+/// CGScrollPhase definitions
+const SCROLL_PHASE_BEGAN: c_long = 1;
+const SCROLL_PHASE_CHANGED: c_long = 2;
+const SCROLL_PHASE_ENDED: c_long = 4;
+const SCROLL_PHASE_CANCELLED: c_long = 8;
+const SCROLL_PHASE_MAY_BEGIN: c_long = 128;
+/// CGMomentumScrollPhase definitions
+const MOMENTUM_PHASE_BEGIN: c_long = 1;
+const MOMENTUM_PHASE_CONTINUE: c_long = 2;
+const MOMENTUM_PHASE_END: c_long = 3;
+
+type CGEventFlags = u64;
+/// CGEventFlags definitions
+const EVENT_FLAG_MASK_SHIFT: CGEventFlags = 0x00020000;
+const EVENT_FLAG_MASK_CONTROL: CGEventFlags = 0x00040000;
+const EVENT_FLAG_MASK_ALTERNATE: CGEventFlags = 0x00080000;
+const EVENT_FLAG_MASK_COMMAND: CGEventFlags = 0x00100000;
+
+#[derive(Clone, Copy)]
 #[repr(C)]
 enum CGMouseButton {
     Left = 0,
@@ -345,9 +1010,15 @@ enum CGEventTapLocation {
     _CGAnnotatedSessionEventTap = 2,
 }
 
+/// The state of the hardware, unlike the combined state of the session
+#[repr(C)]
+enum CGEventSourceStateID {
+    HIDSystemState = 1,
+}
+
 #[repr(C)]
 enum CGScrollEventUnit {
-    _Pixel = 0,
+    Pixel = 0,
     Line = 1,
 }
 
@@ -375,8 +1046,12 @@ type CGEventTapCallback = Option<
 #[link(name = "CoreGraphics", kind = "framework")]
 extern "C" {
     fn CGWarpMouseCursorPosition(new_cursor_position: CGPoint) -> CGError;
+    fn CGMainDisplayID() -> u32;
+    fn CGDisplayHideCursor(display: u32) -> CGError;
+    fn CGDisplayShowCursor(display: u32) -> CGError;
     fn CGEventCreate(source: CGEventSourceRef) -> CGEventRef;
     fn CGEventGetLocation(event: CGEventRef) -> CGPoint;
+    fn CGEventSourceButtonState(state_id: CGEventSourceStateID, button: u32) -> bool;
     fn CGEventCreateMouseEvent(
         source: CGEventSourceRef,
         mouse_type: CGEventType,
@@ -388,6 +1063,12 @@ extern "C" {
         units: CGScrollEventUnit,
         wheel_count: c_int,
         wheel1: c_int,
+        ...
+    ) -> CGEventRef;
+    fn CGEventCreateKeyboardEvent(
+        source: CGEventSourceRef,
+        virtual_key: u16,
+        key_down: bool,
     ) -> CGEventRef;
     fn CGEventPost(tap: CGEventTapLocation, event: CGEventRef);
     fn CGEventTapCreate(
@@ -400,10 +1081,44 @@ extern "C" {
     ) -> CFTypeRef;
     fn CGEventTapEnable(tap: *const c_void, enable: bool);
     fn CGEventGetIntegerValueField(event: CGEventRef, field: c_uint) -> c_long;
+    fn CGEventSetFlags(event: CGEventRef, flags: CGEventFlags);
+    fn CGEventSetIntegerValueField(event: CGEventRef, field: c_uint, value: c_long);
+    fn CGEventSetDoubleValueField(event: CGEventRef, field: c_uint, value: c_double);
+}
+/// IOKit and CoreFoundation definitions
+const K_IOHID_OPTIONS_TYPE_NONE: c_uint = 0;
+const K_CF_STRING_ENCODING_UTF8: c_uint = 0x08000100;
+const K_CF_NUMBER_INT_TYPE: c_long = 9;
+const K_CF_NUMBER_DOUBLE_TYPE: c_long = 13;
+const HID_PAGE_GENERIC_DESKTOP: c_uint = 0x01;
+const HID_PAGE_BUTTON: c_uint = 0x09;
+const HID_PAGE_CONSUMER: c_uint = 0x0C;
+const HID_USAGE_MOUSE: c_uint = 0x02;
+const HID_USAGE_WHEEL: c_uint = 0x38;
+const HID_USAGE_AC_PAN: c_uint = 0x238;
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOHIDManagerCreate(allocator: *mut c_void, options: c_uint) -> CFTypeRef;
+    fn IOHIDManagerSetDeviceMatching(manager: CFTypeRef, matching: CFTypeRef);
+    fn IOHIDManagerCopyDevices(manager: CFTypeRef) -> CFTypeRef;
+    fn IOHIDDeviceGetProperty(device: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+    fn IOHIDDeviceGetService(device: CFTypeRef) -> c_uint;
+    fn IOHIDDeviceCopyMatchingElements(
+        device: CFTypeRef,
+        matching: CFTypeRef,
+        options: c_uint,
+    ) -> CFTypeRef;
+    fn IOHIDElementGetUsagePage(element: CFTypeRef) -> c_uint;
+    fn IOHIDElementGetUsage(element: CFTypeRef) -> c_uint;
+    fn IORegistryEntryGetPath(entry: c_uint, plane: *const c_char, path: *mut c_char) -> c_int;
 }
 #[link(name = "CoreFoundation", kind = "framework")]
 extern "C" {
     static kCFRunLoopDefaultMode: *const c_void;
+    static kCFTypeDictionaryKeyCallBacks: c_void;
+    static kCFTypeDictionaryValueCallBacks: c_void;
+    static kCFPreferencesAnyApplication: CFTypeRef;
 
     fn CFRelease(cf: CFTypeRef);
     fn CFMachPortCreateRunLoopSource(
@@ -414,4 +1129,37 @@ extern "C" {
     fn CFRunLoopGetCurrent() -> *mut c_void;
     fn CFRunLoopAddSource(rl: *mut c_void, source: *mut c_void, mode: *const c_void);
     fn CFRunLoopRun();
+    fn CFRunLoopStop(rl: *mut c_void);
+    fn CFDictionaryCreate(
+        allocator: *mut c_void,
+        keys: *const CFTypeRef,
+        values: *const CFTypeRef,
+        num_values: c_long,
+        key_call_backs: *const c_void,
+        value_call_backs: *const c_void,
+    ) -> CFTypeRef;
+    fn CFStringCreateWithCString(
+        allocator: *mut c_void,
+        c_str: *const c_char,
+        encoding: c_uint,
+    ) -> CFTypeRef;
+    fn CFStringGetCString(
+        the_string: CFTypeRef,
+        buffer: *mut c_char,
+        buffer_size: c_long,
+        encoding: c_uint,
+    ) -> bool;
+    fn CFNumberCreate(
+        allocator: *mut c_void,
+        the_type: c_long,
+        value_ptr: *const c_void,
+    ) -> CFTypeRef;
+    fn CFNumberGetValue(number: CFTypeRef, the_type: c_long, value_ptr: *mut c_void) -> bool;
+    fn CFNumberGetTypeID() -> c_ulong;
+    fn CFGetTypeID(cf: CFTypeRef) -> c_ulong;
+    fn CFPreferencesCopyAppValue(key: CFTypeRef, application_id: CFTypeRef) -> CFTypeRef;
+    fn CFSetGetCount(the_set: CFTypeRef) -> c_long;
+    fn CFSetGetValues(the_set: CFTypeRef, values: *mut CFTypeRef);
+    fn CFArrayGetCount(the_array: CFTypeRef) -> c_long;
+    fn CFArrayGetValueAtIndex(the_array: CFTypeRef, idx: c_long) -> CFTypeRef;
 }
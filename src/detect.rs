@@ -0,0 +1,533 @@
+///
+/// This module contains the detection of the clicks and the drags in the mouse
+/// events, so that the callbacks do not need to track the presses and the moves
+///
+/// The detection runs in the callback on the thread that dispatches the events,
+/// the positions are followed from both the absolute and the relative moves
+///
+use crate::common::{MouseButton, MouseEvent};
+use crate::hooks::LockUnpoisoned;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The higher level events that are detected in the mouse events
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GestureEvent {
+    /// The button is held and the mouse moved past the drag threshold,
+    /// reported with the position where the button was pressed
+    DragStart(MouseButton, (i32, i32)),
+    /// The mouse moved by the given `x`, `y` amounts during a drag
+    Dragging(i32, i32),
+    /// The button of the drag is released
+    DragEnd(MouseButton),
+    /// The button is pressed and released without moving past the drag threshold
+    Click(MouseButton),
+    /// The second of two clicks of the same button close in time and in position,
+    /// reported after the `Click` of the second click
+    DoubleClick(MouseButton),
+    /// The third of the clicks of a `DoubleClick`, reported after the `Click` of
+    /// the third click. A fourth click starts over
+    TripleClick(MouseButton),
+}
+
+/// The thresholds of the detection
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::detect::{DetectorConfig, GestureEvent};
+/// use mouce::{Mouse, MouseListener};
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let config = DetectorConfig::new()
+///     .drag_threshold(8)
+///     .double_click_interval(Duration::from_millis(400));
+/// manager
+///     .hook_gestures(
+///         config,
+///         Box::new(|gesture| {
+///             if let GestureEvent::DoubleClick(button) = gesture {
+///                 println!("Double clicked {}", button);
+///             }
+///         }),
+///     )
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectorConfig {
+    drag_threshold: u32,
+    double_click_interval: Duration,
+    double_click_slop: (u32, u32),
+}
+
+impl DetectorConfig {
+    /// The thresholds of the common desktop environments, 4 pixels to start
+    /// a drag and 500 milliseconds within 4 pixels for a double click
+    pub fn new() -> Self {
+        DetectorConfig {
+            drag_threshold: 4,
+            double_click_interval: Duration::from_millis(500),
+            double_click_slop: (4, 4),
+        }
+    }
+
+    /// Read the double click time and slop of the system, `GetDoubleClickTime` and
+    /// `SM_CXDOUBLECLK` on Windows, the `com.apple.mouse.doubleClickThreshold`
+    /// preference on macOS and the `multiClickTime` X resource on the unix-like
+    /// systems. The thresholds that the system does not have are the ones of `new`
+    pub fn system() -> Self {
+        platform_config()
+    }
+
+    /// How far the mouse moves in pixels while a button is held before it is a drag
+    pub fn drag_threshold(mut self, pixels: u32) -> Self {
+        self.drag_threshold = pixels;
+        self
+    }
+
+    /// How soon the second click follows the first one to make a double click
+    pub fn double_click_interval(mut self, interval: Duration) -> Self {
+        self.double_click_interval = interval;
+        self
+    }
+
+    /// How far in pixels on each axis the next clicks may be from the first one
+    /// to make a double or a triple click
+    pub fn double_click_distance(self, pixels: u32) -> Self {
+        self.double_click_slop(pixels, pixels)
+    }
+
+    /// How far in pixels the next clicks may be from the first one on the `x`
+    /// and the `y` axes separately, like the double click rectangle of Windows
+    pub fn double_click_slop(mut self, x: u32, y: u32) -> Self {
+        self.double_click_slop = (x, y);
+        self
+    }
+
+    fn within_slop(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        (to.0 - from.0).unsigned_abs() <= self.double_click_slop.0
+            && (to.1 - from.1).unsigned_abs() <= self.double_click_slop.1
+    }
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        DetectorConfig::new()
+    }
+}
+
+/// A button that is held down
+struct Held {
+    button: MouseButton,
+    /// Where the button was pressed
+    origin: (i32, i32),
+    /// Where the last `Dragging` was reported, once the drag started
+    dragged: Option<(i32, i32)>,
+}
+
+/// A click that may be followed by the next click of a double or a triple click
+struct LastClick {
+    button: MouseButton,
+    /// Where the first click of the run was pressed
+    position: (i32, i32),
+    /// When the last click of the run was released
+    time: Instant,
+    /// The clicks of the run so far
+    count: u8,
+}
+
+/// Detects the clicks and the drags in the given mouse events
+///
+/// Only the first of the held buttons is followed, the presses of the
+/// other buttons while it is held are ignored, as is a second press of the
+/// held button e.g. from another mouse
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::common::{MouseButton, MouseEvent};
+/// use mouce::detect::{DetectorConfig, GestureDetector, GestureEvent};
+/// use std::time::Instant;
+///
+/// let mut detector = GestureDetector::new(DetectorConfig::new());
+/// let now = Instant::now();
+/// detector.feed(&MouseEvent::AbsoluteMove(100, 100), now);
+/// detector.feed(&MouseEvent::Press(MouseButton::Left), now);
+/// assert_eq!(
+///     detector.feed(&MouseEvent::AbsoluteMove(110, 100), now),
+///     vec![
+///         GestureEvent::DragStart(MouseButton::Left, (100, 100)),
+///         GestureEvent::Dragging(10, 0),
+///     ]
+/// );
+/// ```
+pub struct GestureDetector {
+    config: DetectorConfig,
+    position: (i32, i32),
+    held: Option<Held>,
+    last_click: Option<LastClick>,
+}
+
+impl GestureDetector {
+    pub fn new(config: DetectorConfig) -> Self {
+        GestureDetector {
+            config,
+            position: (0, 0),
+            held: None,
+            last_click: None,
+        }
+    }
+
+    /// Take the given event that happened at the given time,
+    /// and get the events that are detected with it
+    pub fn feed(&mut self, event: &MouseEvent, now: Instant) -> Vec<GestureEvent> {
+        match event {
+            MouseEvent::AbsoluteMove(x, y) => self.moved((*x, *y)),
+            MouseEvent::RelativeMove(x, y) => {
+                self.moved((self.position.0 + x, self.position.1 + y))
+            }
+            MouseEvent::Press(button) => {
+                if self.held.is_none() {
+                    self.held = Some(Held {
+                        button: button.clone(),
+                        origin: self.position,
+                        dragged: None,
+                    });
+                }
+                Vec::new()
+            }
+            MouseEvent::Release(button) => self.released(button, now),
+            _ => Vec::new(),
+        }
+    }
+
+    fn moved(&mut self, position: (i32, i32)) -> Vec<GestureEvent> {
+        self.position = position;
+        let held = match &mut self.held {
+            Some(held) => held,
+            None => return Vec::new(),
+        };
+        let mut events = Vec::new();
+        let from = match held.dragged {
+            Some(dragged) => dragged,
+            None => {
+                if distance(held.origin, position) <= self.config.drag_threshold as f64 {
+                    return events;
+                }
+                events.push(GestureEvent::DragStart(held.button.clone(), held.origin));
+                held.origin
+            }
+        };
+        held.dragged = Some(position);
+        events.push(GestureEvent::Dragging(
+            position.0 - from.0,
+            position.1 - from.1,
+        ));
+        events
+    }
+
+    fn released(&mut self, button: &MouseButton, now: Instant) -> Vec<GestureEvent> {
+        let held = match self.held.take() {
+            Some(held) if &held.button == button => held,
+            other => {
+                self.held = other;
+                return Vec::new();
+            }
+        };
+        if held.dragged.is_some() {
+            self.last_click = None;
+            return vec![GestureEvent::DragEnd(held.button)];
+        }
+
+        let run = match self.last_click.take() {
+            Some(last)
+                if last.button == held.button
+                    && last.count < 3
+                    && now.saturating_duration_since(last.time)
+                        <= self.config.double_click_interval
+                    && self.config.within_slop(last.position, held.origin) =>
+            {
+                LastClick {
+                    time: now,
+                    count: last.count + 1,
+                    ..last
+                }
+            }
+            _ => LastClick {
+                button: held.button.clone(),
+                position: held.origin,
+                time: now,
+                count: 1,
+            },
+        };
+        let mut events = vec![GestureEvent::Click(held.button.clone())];
+        match run.count {
+            2 => events.push(GestureEvent::DoubleClick(held.button)),
+            3 => events.push(GestureEvent::TripleClick(held.button)),
+            _ => {}
+        }
+        self.last_click = Some(run);
+        events
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn platform_config() -> DetectorConfig {
+    let config = DetectorConfig::new();
+    match crate::nix::double_click_time() {
+        Some(interval) => config.double_click_interval(interval),
+        None => config,
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn platform_config() -> DetectorConfig {
+    crate::windows::detector_config()
+}
+
+#[cfg(target_os = "macos")]
+fn platform_config() -> DetectorConfig {
+    crate::darwin::detector_config()
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn platform_config() -> DetectorConfig {
+    DetectorConfig::new()
+}
+
+fn distance(from: (i32, i32), to: (i32, i32)) -> f64 {
+    let (dx, dy) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Wrap the given callback so that it receives the events that are detected
+/// in the mouse events with the given config
+pub(crate) fn detecting(
+    config: DetectorConfig,
+    callback: Box<dyn Fn(&GestureEvent) + Send + Sync>,
+) -> Box<dyn Fn(&MouseEvent) + Send + Sync> {
+    let detector = Mutex::new(GestureDetector::new(config));
+    Box::new(move |event| {
+        let events = detector.lock_unpoisoned().feed(event, Instant::now());
+        for event in events.iter() {
+            callback(event);
+        }
+    })
+}
+
+/// Wrap the given callback so that it receives the clicks that are detected with
+/// the given config as mouse events, after the events that they are detected in
+pub(crate) fn synthesizing(
+    config: DetectorConfig,
+    callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+) -> Box<dyn Fn(&MouseEvent) + Send + Sync> {
+    let detector = Mutex::new(GestureDetector::new(config));
+    Box::new(move |event| {
+        callback(event);
+        let gestures = detector.lock_unpoisoned().feed(event, Instant::now());
+        for gesture in gestures {
+            let click = match gesture {
+                GestureEvent::Click(button) => MouseEvent::Click(button),
+                GestureEvent::DoubleClick(button) => MouseEvent::DoubleClick(button),
+                GestureEvent::TripleClick(button) => MouseEvent::TripleClick(button),
+                _ => continue,
+            };
+            callback(&click);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{synthesizing, DetectorConfig, GestureDetector, GestureEvent};
+    use crate::common::{MouseButton, MouseEvent};
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn detect_drags() {
+        let mut detector = GestureDetector::new(DetectorConfig::new().drag_threshold(5));
+        let now = Instant::now();
+        assert!(detector
+            .feed(&MouseEvent::AbsoluteMove(10, 10), now)
+            .is_empty());
+        assert!(detector
+            .feed(&MouseEvent::Press(MouseButton::Right), now)
+            .is_empty());
+        // Within the threshold
+        assert!(detector
+            .feed(&MouseEvent::RelativeMove(3, 0), now)
+            .is_empty());
+        assert_eq!(
+            detector.feed(&MouseEvent::RelativeMove(3, 4), now),
+            vec![
+                GestureEvent::DragStart(MouseButton::Right, (10, 10)),
+                GestureEvent::Dragging(6, 4),
+            ]
+        );
+        // The presses of the other buttons do not interrupt the drag
+        assert!(detector
+            .feed(&MouseEvent::Press(MouseButton::Left), now)
+            .is_empty());
+        assert!(detector
+            .feed(&MouseEvent::Release(MouseButton::Left), now)
+            .is_empty());
+        assert_eq!(
+            detector.feed(&MouseEvent::AbsoluteMove(20, 20), now),
+            vec![GestureEvent::Dragging(4, 6)]
+        );
+        assert_eq!(
+            detector.feed(&MouseEvent::Release(MouseButton::Right), now),
+            vec![GestureEvent::DragEnd(MouseButton::Right)]
+        );
+        assert!(detector
+            .feed(&MouseEvent::RelativeMove(50, 0), now)
+            .is_empty());
+    }
+
+    #[test]
+    fn detect_clicks() {
+        let mut detector = GestureDetector::new(
+            DetectorConfig::new().double_click_interval(Duration::from_millis(300)),
+        );
+        let start = Instant::now();
+        let mut click = |button: MouseButton, millis: u64| {
+            let now = start + Duration::from_millis(millis);
+            detector.feed(&MouseEvent::Press(button.clone()), now);
+            detector.feed(&MouseEvent::Release(button), now)
+        };
+        assert_eq!(
+            click(MouseButton::Left, 0),
+            vec![GestureEvent::Click(MouseButton::Left)]
+        );
+        assert_eq!(
+            click(MouseButton::Left, 200),
+            vec![
+                GestureEvent::Click(MouseButton::Left),
+                GestureEvent::DoubleClick(MouseButton::Left),
+            ]
+        );
+        assert_eq!(
+            click(MouseButton::Left, 300),
+            vec![
+                GestureEvent::Click(MouseButton::Left),
+                GestureEvent::TripleClick(MouseButton::Left),
+            ]
+        );
+        // A fourth click starts over
+        assert_eq!(
+            click(MouseButton::Left, 400),
+            vec![GestureEvent::Click(MouseButton::Left)]
+        );
+        // Too late
+        assert_eq!(
+            click(MouseButton::Left, 800),
+            vec![GestureEvent::Click(MouseButton::Left)]
+        );
+        // Another button
+        assert_eq!(
+            click(MouseButton::Right, 900),
+            vec![GestureEvent::Click(MouseButton::Right)]
+        );
+    }
+
+    #[test]
+    fn synthesize_clicks() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let events = received.clone();
+        let callback = synthesizing(
+            DetectorConfig::new()
+                .drag_threshold(2)
+                .double_click_slop(2, 2),
+            Box::new(move |event| events.lock().unwrap().push(event.clone())),
+        );
+        let click = |event: Option<MouseEvent>| {
+            received.lock().unwrap().clear();
+            if let Some(event) = event {
+                callback(&event);
+            }
+            callback(&MouseEvent::Press(MouseButton::Left));
+            callback(&MouseEvent::Release(MouseButton::Left));
+            let mut received = received.lock().unwrap();
+            let release = received
+                .iter()
+                .position(|event| event == &MouseEvent::Release(MouseButton::Left))
+                .unwrap();
+            received.split_off(release + 1)
+        };
+        let single = vec![MouseEvent::Click(MouseButton::Left)];
+        let double = vec![
+            MouseEvent::Click(MouseButton::Left),
+            MouseEvent::DoubleClick(MouseButton::Left),
+        ];
+        assert_eq!(click(None), single);
+        assert_eq!(click(None), double);
+        assert_eq!(
+            click(None),
+            vec![
+                MouseEvent::Click(MouseButton::Left),
+                MouseEvent::TripleClick(MouseButton::Left),
+            ]
+        );
+        // A fourth click starts over
+        assert_eq!(click(None), single);
+        // Within the slop of the first click
+        assert_eq!(click(Some(MouseEvent::RelativeMove(2, -2))), double);
+        // Too far
+        assert_eq!(click(Some(MouseEvent::RelativeMove(3, 0))), single);
+        // Moving past the drag threshold between the press and the release is not a click
+        received.lock().unwrap().clear();
+        callback(&MouseEvent::Press(MouseButton::Left));
+        callback(&MouseEvent::RelativeMove(3, 0));
+        callback(&MouseEvent::Release(MouseButton::Left));
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                MouseEvent::Press(MouseButton::Left),
+                MouseEvent::RelativeMove(3, 0),
+                MouseEvent::Release(MouseButton::Left),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_second_presses() {
+        let mut detector = GestureDetector::new(DetectorConfig::new());
+        let now = Instant::now();
+        detector.feed(&MouseEvent::Press(MouseButton::Left), now);
+        detector.feed(&MouseEvent::RelativeMove(2, 0), now);
+        // The same button pressed again e.g. on another mouse keeps the first press
+        detector.feed(&MouseEvent::Press(MouseButton::Left), now);
+        assert_eq!(
+            detector.feed(&MouseEvent::RelativeMove(3, 0), now),
+            vec![
+                GestureEvent::DragStart(MouseButton::Left, (0, 0)),
+                GestureEvent::Dragging(5, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,58 @@
+///
+/// This module contains the enumeration of the mouse devices
+/// that are connected to the system
+///
+use crate::error::Error;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The information of a connected mouse device
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MouseDeviceInfo {
+    /// The name that the device reports
+    pub name: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// The path of the device, the event file on unix-like systems
+    pub path: String,
+    /// Whether the device has a vertical scroll wheel
+    pub wheel: bool,
+    /// Whether the device has a horizontal scroll wheel
+    pub hwheel: bool,
+    /// Whether the device has buttons other than left, right and middle
+    pub extra_buttons: bool,
+}
+
+/// List the mouse devices that are connected to the system
+///
+/// Reading the devices may require user privileges on some systems
+/// e.g. requires super user for Linux
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::devices::list_mice;
+///
+/// for device in list_mice().unwrap() {
+///     println!("{} ({:04x}:{:04x})", device.name, device.vendor_id, device.product_id);
+/// }
+/// ```
+pub fn list_mice() -> Result<Vec<MouseDeviceInfo>, Error> {
+    #[cfg(any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    return crate::nix::list_mice();
+    #[cfg(target_vendor = "apple")]
+    return crate::darwin::list_mice();
+    #[cfg(target_os = "windows")]
+    return crate::windows::list_mice();
+    // The browsers do not list the devices
+    #[cfg(target_arch = "wasm32")]
+    return Ok(Vec::new());
+}
@@ -0,0 +1,407 @@
+///
+/// This module contains the checks of the permissions and the environment
+/// that the mouse actions need, with the hints to fix the found problems
+///
+/// The checks do not change anything, so they can be run before creating
+/// a manager to tell the user what to fix instead of a bare permission error
+///
+use std::fmt;
+
+/// The result of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Nothing needs to be done
+    Ok,
+    /// Some of the features may not work, e.g. listening the events
+    Warning,
+    /// The mouse can not be used until the problem is fixed
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Warning => write!(f, "warning"),
+            Status::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The result of a check with what is found and how to fix it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The short name of what is checked, e.g. `uinput`
+    pub check: &'static str,
+    pub status: Status,
+    /// What is found, in a form that can be shown to the user
+    pub message: String,
+    /// How to fix the problem, if there is one
+    pub remediation: Option<String>,
+}
+
+impl Diagnostic {
+    fn new(check: &'static str, status: Status, message: impl Into<String>) -> Self {
+        Diagnostic {
+            check,
+            status,
+            message: message.into(),
+            remediation: None,
+        }
+    }
+
+    fn with_remediation(mut self, remediation: impl Into<String>) -> Self {
+        self.remediation = Some(remediation.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.check, self.message)?;
+        if let Some(remediation) = &self.remediation {
+            write!(f, "\n    fix: {}", remediation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the permissions and the environment that the mouse actions need
+///
+/// Every check is reported, the ones that pass have the `Ok` status
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::diagnostics::{self, Status};
+///
+/// for diagnostic in diagnostics::check() {
+///     if diagnostic.status != Status::Ok {
+///         eprintln!("{}", diagnostic);
+///     }
+/// }
+/// ```
+pub fn check() -> Vec<Diagnostic> {
+    platform_checks()
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn platform_checks() -> Vec<Diagnostic> {
+    let session = crate::nix::display_manager();
+    vec![
+        nix::session(&session),
+        nix::uinput(&session),
+        nix::event_files(&session),
+        nix::input_group(),
+    ]
+}
+
+#[cfg(target_vendor = "apple")]
+fn platform_checks() -> Vec<Diagnostic> {
+    vec![darwin::accessibility()]
+}
+
+#[cfg(target_os = "windows")]
+fn platform_checks() -> Vec<Diagnostic> {
+    // The mouse actions and the hooks do not need any permissions on Windows
+    Vec::new()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn platform_checks() -> Vec<Diagnostic> {
+    // The synthetic DOM events do not need any permissions
+    Vec::new()
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod nix {
+    use super::{Diagnostic, Status};
+    use glob::glob;
+    use std::{env, fs, io::ErrorKind, os::raw::c_int};
+
+    /// The group that owns the input devices on most of the distributions
+    const INPUT_GROUP: &str = "input";
+    const ADD_TO_INPUT_GROUP: &str =
+        "add the user to the input group with `sudo usermod -aG input $USER`, then log in again";
+    /// The udev rule that gives the input group the access to /dev/uinput
+    const UINPUT_RULES_FILE: &str = "/etc/udev/rules.d/99-uinput.rules";
+    const UINPUT_RULE: &str = r#"KERNEL=="uinput", GROUP="input", MODE="0660""#;
+    const RELOAD_RULES: &str = "sudo udevadm control --reload-rules && sudo udevadm trigger";
+
+    /// Check which backend the session uses
+    pub fn session(session: &str) -> Diagnostic {
+        match session {
+            "x11" => Diagnostic::new(
+                "session",
+                Status::Ok,
+                "X11 session, the mouse is simulated through XTest",
+            ),
+            "wayland" => Diagnostic::new(
+                "session",
+                Status::Ok,
+                "Wayland session, the mouse is simulated through uinput",
+            ),
+            _ => Diagnostic::new(
+                "session",
+                Status::Warning,
+                "no graphical session is found, the mouse is simulated through uinput",
+            )
+            .with_remediation(
+                "set XDG_SESSION_TYPE, WAYLAND_DISPLAY or DISPLAY when running outside \
+                 of the session, e.g. over ssh",
+            ),
+        }
+    }
+
+    /// Check that the virtual devices can be created
+    pub fn uinput(session: &str) -> Diagnostic {
+        // X11 only needs uinput for the managers that are created explicitly
+        let status = if session == "x11" {
+            Status::Warning
+        } else {
+            Status::Error
+        };
+        match fs::OpenOptions::new().write(true).open("/dev/uinput") {
+            Ok(_) => Diagnostic::new("uinput", Status::Ok, "/dev/uinput is writable"),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Diagnostic::new("uinput", status, "/dev/uinput does not exist").with_remediation(
+                    "load the uinput module with `sudo modprobe uinput`, and add `uinput` to \
+                     /etc/modules-load.d/uinput.conf to load it on boot",
+                )
+            }
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                Diagnostic::new("uinput", status, "/dev/uinput is not writable by the user")
+                    .with_remediation(format!(
+                        "create {} with `{}`, reload the rules with `{}`, and {}",
+                        UINPUT_RULES_FILE, UINPUT_RULE, RELOAD_RULES, ADD_TO_INPUT_GROUP
+                    ))
+            }
+            Err(err) => Diagnostic::new(
+                "uinput",
+                status,
+                format!("/dev/uinput can not be opened: {}", err),
+            ),
+        }
+    }
+
+    /// Check that the event files, which the listeners read, are readable
+    pub fn event_files(session: &str) -> Diagnostic {
+        let (mut total, mut readable) = (0, 0);
+        for path in glob("/dev/input/event*")
+            .expect("Failed to read glob pattern")
+            .flatten()
+        {
+            total += 1;
+            if fs::File::open(path).is_ok() {
+                readable += 1;
+            }
+        }
+
+        if total == 0 {
+            Diagnostic::new(
+                "event files",
+                Status::Warning,
+                "there are no event files under /dev/input, the events can not be listened",
+            )
+        } else if readable == 0 {
+            let mut remediation = ADD_TO_INPUT_GROUP.to_string();
+            if session == "x11" {
+                remediation.push_str(
+                    ", or enable the `xinput2` feature to listen the events through the X server",
+                );
+            }
+            Diagnostic::new(
+                "event files",
+                Status::Warning,
+                format!(
+                    "none of the {} event files under /dev/input is readable, \
+                     the events can not be listened",
+                    total
+                ),
+            )
+            .with_remediation(remediation)
+        } else {
+            Diagnostic::new(
+                "event files",
+                Status::Ok,
+                format!(
+                    "{} of the {} event files under /dev/input are readable",
+                    readable, total
+                ),
+            )
+        }
+    }
+
+    /// Check that the process runs with the input group
+    pub fn input_group() -> Diagnostic {
+        if unsafe { geteuid() } == 0 {
+            return Diagnostic::new("input group", Status::Ok, "running as root");
+        }
+
+        let groups = fs::read_to_string("/etc/group").unwrap_or_default();
+        let (gid, members) = match find_group(&groups, INPUT_GROUP) {
+            Some(group) => group,
+            None => {
+                return Diagnostic::new(
+                    "input group",
+                    Status::Warning,
+                    "the system does not have an input group",
+                )
+                .with_remediation(
+                    "give the user the access to /dev/uinput and /dev/input/event* \
+                     with the rules of the device manager",
+                )
+            }
+        };
+
+        if process_groups().contains(&gid) {
+            return Diagnostic::new(
+                "input group",
+                Status::Ok,
+                "the process runs with the input group",
+            );
+        }
+        let user = env::var("USER").unwrap_or_default();
+        if !user.is_empty() && members.contains(&user) {
+            // The groups of a session are set when the user logs in
+            Diagnostic::new(
+                "input group",
+                Status::Warning,
+                format!(
+                    "{} is in the input group, but the session started before it is added",
+                    user
+                ),
+            )
+            .with_remediation("log in again, or start a shell with the group with `newgrp input`")
+        } else {
+            Diagnostic::new(
+                "input group",
+                Status::Warning,
+                "the user is not in the input group",
+            )
+            .with_remediation(ADD_TO_INPUT_GROUP)
+        }
+    }
+
+    /// Find the id and the members of the given group in the contents of /etc/group
+    pub fn find_group(contents: &str, name: &str) -> Option<(u32, Vec<String>)> {
+        contents.lines().find_map(|line| {
+            // name:password:gid:members
+            let mut fields = line.split(':');
+            if fields.next()? != name {
+                return None;
+            }
+            let gid = fields.nth(1)?.parse().ok()?;
+            let members = fields
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .filter(|member| !member.is_empty())
+                .map(String::from)
+                .collect();
+            Some((gid, members))
+        })
+    }
+
+    /// Get the groups of the process, including its effective group
+    fn process_groups() -> Vec<u32> {
+        unsafe {
+            let count = getgroups(0, std::ptr::null_mut());
+            let mut groups = vec![0; count.max(0) as usize];
+            let count = getgroups(groups.len() as c_int, groups.as_mut_ptr());
+            groups.truncate(count.max(0) as usize);
+            groups.push(getegid());
+            groups
+        }
+    }
+
+    extern "C" {
+        fn geteuid() -> u32;
+        fn getegid() -> u32;
+        fn getgroups(size: c_int, list: *mut u32) -> c_int;
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+mod darwin {
+    use super::{Diagnostic, Status};
+
+    /// Check that the process is allowed to control the computer,
+    /// which the event taps of the hooks need
+    pub fn accessibility() -> Diagnostic {
+        if unsafe { AXIsProcessTrusted() } {
+            Diagnostic::new(
+                "accessibility",
+                Status::Ok,
+                "the process is trusted for the accessibility",
+            )
+        } else {
+            Diagnostic::new(
+                "accessibility",
+                Status::Error,
+                "the process is not trusted for the accessibility, the events can not be listened",
+            )
+            .with_remediation(
+                "allow the application (or the terminal that runs it) in System Settings > \
+                 Privacy & Security > Accessibility",
+            )
+        }
+    }
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Status};
+
+    #[test]
+    fn display_diagnostics() {
+        let diagnostic = Diagnostic::new("uinput", Status::Error, "/dev/uinput does not exist")
+            .with_remediation("sudo modprobe uinput");
+        assert_eq!(
+            diagnostic.to_string(),
+            "[error] uinput: /dev/uinput does not exist\n    fix: sudo modprobe uinput"
+        );
+        assert_eq!(
+            Diagnostic::new("session", Status::Ok, "X11 session").to_string(),
+            "[ok] session: X11 session"
+        );
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    #[test]
+    fn find_groups() {
+        let groups = "root:x:0:\ninput:x:104:alice,bob\nvideo:x:44:\n";
+        assert_eq!(
+            super::nix::find_group(groups, "input"),
+            Some((104, vec!["alice".to_string(), "bob".to_string()]))
+        );
+        assert_eq!(super::nix::find_group(groups, "video"), Some((44, vec![])));
+        assert_eq!(super::nix::find_group(groups, "plugdev"), None);
+    }
+}
@@ -0,0 +1,376 @@
+///
+/// This module contains the layout of the monitors, so that the
+/// actions can target a monitor instead of the global coordinates
+///
+use crate::error::Error;
+
+/// A monitor and where it is on the desktop
+///
+/// The position and the size are in the global coordinates that `move_to` takes,
+/// which are the pixels except on macOS and Wayland, where they are the logical
+/// (scaled) pixels that the layouts of the monitors are made in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// The index of the monitor in the list of `monitors`, e.g. for `move_to_monitor`
+    pub id: usize,
+    /// The name of the monitor or its output e.g. `DP-1`, empty if it is not known
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// The scale factor of the monitor, 1 if it is not scaled
+    pub scale: f64,
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// Whether the given position relative to the monitor is on it
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (0..self.width).contains(&x) && (0..self.height).contains(&y)
+    }
+}
+
+/// A rectangle in the global coordinates that `move_to` takes, e.g. for `confine_cursor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the given global position is in the rectangle
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    /// Get the position in the rectangle that is the closest to the given global position
+    pub fn clamp(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            x.clamp(self.x, self.x + self.width.max(1) - 1),
+            y.clamp(self.y, self.y + self.height.max(1) - 1),
+        )
+    }
+}
+
+impl From<&Monitor> for Rect {
+    fn from(monitor: &Monitor) -> Self {
+        Rect::new(monitor.x, monitor.y, monitor.width, monitor.height)
+    }
+}
+
+/// Get the monitors of the desktop
+///
+/// The unix-like systems read the monitors through RandR with the `xrandr`
+/// feature and through the Wayland outputs with the `wayland` feature,
+/// otherwise the whole screen is reported as a single monitor
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::display;
+///
+/// for monitor in display::monitors().unwrap() {
+///     println!(
+///         "{} {}: {}x{} at {}, {}",
+///         monitor.id, monitor.name, monitor.width, monitor.height, monitor.x, monitor.y
+///     );
+/// }
+/// ```
+pub fn monitors() -> Result<Vec<Monitor>, Error> {
+    let monitors = platform_monitors()?;
+    if monitors.is_empty() {
+        return Err(Error::CustomError(
+            "the monitors can not be detected".to_string(),
+        ));
+    }
+    Ok(monitors)
+}
+
+/// Convert the given position on the monitor with the given id to the global coordinates,
+/// which are negative on the monitors on the left of or above the origin
+pub(crate) fn global_position(monitor_id: usize, x: i32, y: i32) -> Result<(i32, i32), Error> {
+    let monitors = monitors()?;
+    to_global(&monitors, monitor_id, x, y)
+}
+
+fn to_global(monitors: &[Monitor], monitor_id: usize, x: i32, y: i32) -> Result<(i32, i32), Error> {
+    let monitor = monitors
+        .iter()
+        .find(|monitor| monitor.id == monitor_id)
+        .ok_or_else(|| Error::CustomError(format!("the monitor {} does not exist", monitor_id)))?;
+    if !monitor.contains(x, y) {
+        return Err(Error::CustomError(format!(
+            "{}, {} is outside of the monitor {}",
+            x, y, monitor_id
+        )));
+    }
+    Ok((monitor.x + x, monitor.y + y))
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn platform_monitors() -> Result<Vec<Monitor>, Error> {
+    Ok(crate::nix::monitors())
+}
+
+#[cfg(target_vendor = "apple")]
+fn platform_monitors() -> Result<Vec<Monitor>, Error> {
+    Ok(darwin::monitors())
+}
+
+#[cfg(target_os = "windows")]
+fn platform_monitors() -> Result<Vec<Monitor>, Error> {
+    Ok(windows::monitors())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn platform_monitors() -> Result<Vec<Monitor>, Error> {
+    Err(Error::NotImplemented)
+}
+
+#[cfg(target_vendor = "apple")]
+mod darwin {
+    use super::Monitor;
+    use std::os::raw::c_void;
+
+    /// The most displays that are listed
+    const MAX_DISPLAYS: usize = 32;
+
+    /// Get the active displays, the main display is at the origin of the global coordinates
+    pub fn monitors() -> Vec<Monitor> {
+        unsafe {
+            let mut displays = [0; MAX_DISPLAYS];
+            let mut count = 0;
+            if CGGetActiveDisplayList(MAX_DISPLAYS as u32, displays.as_mut_ptr(), &mut count) != 0 {
+                return Vec::new();
+            }
+            let main = CGMainDisplayID();
+
+            displays[..count as usize]
+                .iter()
+                .enumerate()
+                .map(|(id, display)| {
+                    // The bounds are in the points, the modes tell the pixels of them
+                    let bounds = CGDisplayBounds(*display);
+                    let mode = CGDisplayCopyDisplayMode(*display);
+                    let mut scale = 1.;
+                    if !mode.is_null() {
+                        if bounds.size.width > 0. {
+                            scale = CGDisplayModeGetPixelWidth(mode) as f64 / bounds.size.width;
+                        }
+                        CGDisplayModeRelease(mode);
+                    }
+                    Monitor {
+                        id,
+                        name: String::new(),
+                        x: bounds.origin.x as i32,
+                        y: bounds.origin.y as i32,
+                        width: bounds.size.width as i32,
+                        height: bounds.size.height as i32,
+                        scale,
+                        primary: *display == main,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[repr(C)]
+    struct CGSize {
+        width: f64,
+        height: f64,
+    }
+
+    #[repr(C)]
+    struct CGRect {
+        origin: CGPoint,
+        size: CGSize,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+        fn CGMainDisplayID() -> u32;
+        fn CGDisplayBounds(display: u32) -> CGRect;
+        fn CGDisplayCopyDisplayMode(display: u32) -> *mut c_void;
+        fn CGDisplayModeGetPixelWidth(mode: *mut c_void) -> usize;
+        fn CGDisplayModeRelease(mode: *mut c_void);
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::Monitor;
+    use std::{
+        mem::size_of,
+        os::raw::{c_int, c_long, c_ulong, c_void},
+        ptr::null_mut,
+    };
+
+    /// The DPI of the unscaled monitors
+    const BASE_DPI: f64 = 96.;
+    const MONITORINFOF_PRIMARY: c_ulong = 1;
+    const MDT_EFFECTIVE_DPI: c_int = 0;
+
+    /// Get the monitors in the order that the system lists them
+    pub fn monitors() -> Vec<Monitor> {
+        let mut monitors: Vec<Monitor> = Vec::new();
+        unsafe {
+            EnumDisplayMonitors(
+                null_mut(),
+                null_mut(),
+                Some(monitor_callback),
+                &mut monitors as *mut Vec<Monitor> as isize,
+            );
+        }
+        monitors
+    }
+
+    unsafe extern "system" fn monitor_callback(
+        monitor: HMonitor,
+        _hdc: *mut c_void,
+        _rect: *mut Rect,
+        data: isize,
+    ) -> c_int {
+        let monitors = &mut *(data as *mut Vec<Monitor>);
+        let mut info = MonitorInfoEx {
+            cb_size: size_of::<MonitorInfoEx>() as c_ulong,
+            rc_monitor: Rect::default(),
+            rc_work: Rect::default(),
+            dw_flags: 0,
+            sz_device: [0; 32],
+        };
+        if GetMonitorInfoW(monitor, &mut info) == 0 {
+            // Continue with the other monitors
+            return 1;
+        }
+
+        let (mut dpi_x, mut dpi_y) = (0, 0);
+        let scale = if GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) == 0 {
+            dpi_x as f64 / BASE_DPI
+        } else {
+            1.
+        };
+        let name_len = info.sz_device.iter().position(|c| *c == 0).unwrap_or(32);
+        let rect = info.rc_monitor;
+        monitors.push(Monitor {
+            id: monitors.len(),
+            name: String::from_utf16_lossy(&info.sz_device[..name_len]),
+            x: rect.left,
+            y: rect.top,
+            width: rect.right - rect.left,
+            height: rect.bottom - rect.top,
+            scale,
+            primary: info.dw_flags & MONITORINFOF_PRIMARY != 0,
+        });
+        1
+    }
+
+    type HMonitor = *mut c_void;
+    type MonitorEnumProc =
+        Option<unsafe extern "system" fn(HMonitor, *mut c_void, *mut Rect, isize) -> c_int>;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct Rect {
+        left: c_long,
+        top: c_long,
+        right: c_long,
+        bottom: c_long,
+    }
+
+    #[repr(C)]
+    struct MonitorInfoEx {
+        cb_size: c_ulong,
+        rc_monitor: Rect,
+        rc_work: Rect,
+        dw_flags: c_ulong,
+        sz_device: [u16; 32],
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn EnumDisplayMonitors(
+            hdc: *mut c_void,
+            clip: *mut Rect,
+            callback: MonitorEnumProc,
+            data: isize,
+        ) -> c_int;
+        fn GetMonitorInfoW(monitor: HMonitor, info: *mut MonitorInfoEx) -> c_int;
+    }
+
+    #[link(name = "shcore")]
+    extern "system" {
+        fn GetDpiForMonitor(
+            monitor: HMonitor,
+            dpi_type: c_int,
+            dpi_x: *mut u32,
+            dpi_y: *mut u32,
+        ) -> c_long;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(id: usize, x: i32, width: i32) -> Monitor {
+        Monitor {
+            id,
+            name: String::new(),
+            x,
+            y: 0,
+            width,
+            height: 1080,
+            scale: 1.,
+            primary: id == 0,
+        }
+    }
+
+    #[test]
+    fn global_positions() {
+        let monitors = [monitor(0, 0, 1920), monitor(1, 1920, 2560)];
+        assert_eq!(to_global(&monitors, 1, 100, 200), Ok((2020, 200)));
+        assert_eq!(to_global(&monitors, 0, 1919, 1079), Ok((1919, 1079)));
+        assert!(to_global(&monitors, 0, 1920, 0).is_err());
+        assert!(to_global(&monitors, 2, 0, 0).is_err());
+
+        // A monitor on the left of the origin
+        let monitors = [monitor(0, -1920, 1920)];
+        assert_eq!(to_global(&monitors, 0, 10, 10), Ok((-1910, 10)));
+    }
+
+    #[test]
+    fn clamp_to_rects() {
+        let rect = Rect::from(&monitor(1, -1920, 1920));
+        assert!(rect.contains(-1920, 0));
+        assert!(!rect.contains(0, 0));
+        assert_eq!(rect.clamp(-1000, 500), (-1000, 500));
+        assert_eq!(rect.clamp(100, 2000), (-1, 1079));
+        assert_eq!(rect.clamp(-5000, -10), (-1920, 0));
+    }
+}
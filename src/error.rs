@@ -0,0 +1,60 @@
+use crate::common::Backend;
+use std::{fmt, io};
+
+/// The error type that is returned by the mouse actions
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The user does not have the permission for the action,
+    /// e.g. accessing /dev/uinput without super user privileges
+    PermissionDenied,
+    /// The action is not implemented on the current platform
+    NotImplemented,
+    /// The device file that is required for the action does not exist
+    DeviceNotFound,
+    /// There is no callback registered with the given `CallbackId`
+    CallbackNotFound,
+    /// Writing an event to the device failed
+    WriteFailed,
+    /// The given input is not supported by the backend,
+    /// e.g. a mouse button that the backend can not simulate
+    Unsupported(Backend),
+    /// The pointer is not on the same screen as the root window
+    X11PointerWindowMismatch,
+    /// CoreGraphics failed to create the event
+    CGCouldNotCreateEvent,
+    /// The action is stopped through its `CancellationToken`
+    Cancelled,
+    /// Any other error with its description
+    CustomError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::PermissionDenied => write!(f, "permission denied"),
+            Error::NotImplemented => write!(f, "the action is not implemented on this platform"),
+            Error::DeviceNotFound => write!(f, "the device could not be found"),
+            Error::CallbackNotFound => write!(f, "the callback could not be found"),
+            Error::WriteFailed => write!(f, "failed while trying to write to a file"),
+            Error::Unsupported(backend) => write!(f, "the input is not supported on {}", backend),
+            Error::X11PointerWindowMismatch => {
+                write!(f, "the pointer is not on the same screen as the window")
+            }
+            Error::CGCouldNotCreateEvent => write!(f, "CoreGraphics could not create the event"),
+            Error::Cancelled => write!(f, "the action is cancelled"),
+            Error::CustomError(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied,
+            io::ErrorKind::NotFound => Error::DeviceNotFound,
+            _ => Error::CustomError(error.to_string()),
+        }
+    }
+}
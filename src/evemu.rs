@@ -0,0 +1,270 @@
+///
+/// This module contains the reader and writer of the text captures of
+/// `evemu-record`, which the recordings are imported from and exported to
+///
+/// A capture lists the raw evdev events of a single device, one `E:` line per
+/// event with its time, type, code and value. The events of a frame (up to
+/// the sync event) are converted together like the listener converts them
+///
+use crate::common::{MouseButton, MouseEvent, ScrollDirection};
+use crate::error::Error;
+use crate::recorder::{RecordedEvent, Recording};
+use std::{
+    io::{BufRead, BufReader, Read},
+    time::Duration,
+};
+
+/// Read the mouse events of the given capture, the times are made relative to
+/// its first event. The events of the other axes and keys are skipped, e.g.
+/// the absolute positions of the tablets, as their ranges are unknown
+pub(crate) fn read(reader: impl Read) -> Result<Recording, Error> {
+    let mut recording = Recording::default();
+    let mut start = None;
+    let mut motion = None;
+    for (number, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let invalid = |message: &str| {
+            Error::CustomError(format!(
+                "invalid evemu recording: line {}: {}",
+                number + 1,
+                message
+            ))
+        };
+        // The description of the device and the comments are not needed to replay it
+        let Some(fields) = line.strip_prefix("E:") else {
+            continue;
+        };
+        let mut fields = fields.split_whitespace();
+        let mut field = |name: &str| {
+            fields
+                .next()
+                .ok_or_else(|| invalid(&format!("missing {}", name)))
+        };
+        let time = parse_time(field("time")?).ok_or_else(|| invalid("invalid time"))?;
+        let r#type =
+            u16::from_str_radix(field("type")?, 16).map_err(|_| invalid("invalid type"))?;
+        let code = u16::from_str_radix(field("code")?, 16).map_err(|_| invalid("invalid code"))?;
+        let value: i32 = field("value")?
+            .parse()
+            .map_err(|_| invalid("invalid value"))?;
+
+        let time = time.saturating_sub(*start.get_or_insert(time));
+        let mut push = |event| recording.events.push(RecordedEvent { time, event });
+        match (r#type, code) {
+            // The motion of both axes is a single move once the frame is complete
+            (EV_SYN, SYN_REPORT) => {
+                if let Some((x, y)) = motion.take() {
+                    push(MouseEvent::RelativeMove(x, y));
+                }
+            }
+            (EV_REL, REL_X) | (EV_REL, REL_Y) => {
+                let (x, y) = motion.get_or_insert((0, 0));
+                if code == REL_X {
+                    *x += value;
+                } else {
+                    *y += value;
+                }
+            }
+            // Every detent is a scroll, like the listener reports them
+            (EV_REL, REL_WHEEL) | (EV_REL, REL_HWHEEL) => {
+                let direction = match (code, value > 0) {
+                    (REL_WHEEL, true) => ScrollDirection::Up,
+                    (REL_WHEEL, false) => ScrollDirection::Down,
+                    (_, true) => ScrollDirection::Right,
+                    (_, false) => ScrollDirection::Left,
+                };
+                for _ in 0..value.unsigned_abs() {
+                    push(MouseEvent::Scroll(direction.clone()));
+                }
+            }
+            (EV_REL, REL_WHEEL_HI_RES) => push(MouseEvent::ScrollHiRes { dx: 0, dy: value }),
+            (EV_REL, REL_HWHEEL_HI_RES) => push(MouseEvent::ScrollHiRes { dx: value, dy: 0 }),
+            // The key repeats are not button events
+            (EV_KEY, code) if value == 0 || value == 1 => {
+                if let Some((_, button)) = BUTTONS.iter().find(|(known, _)| *known == code) {
+                    push(if value == 1 {
+                        MouseEvent::Press(button.clone())
+                    } else {
+                        MouseEvent::Release(button.clone())
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(recording)
+}
+
+/// Write the recording as the capture of a virtual mouse, each event is its own frame
+///
+/// The events that a mouse does not report as evdev events are skipped, i.e. the
+/// absolute moves, the combined scrolls, the scroll phases, the clicks and the devices
+pub(crate) fn write(recording: &Recording) -> String {
+    let mut text = String::from("# EVEMU 1.3\n# Written by mouce\nN: mouce virtual mouse\n");
+    text.push_str("I: 0006 0000 0000 0001\nP: 00 00 00 00 00 00 00 00\n");
+    // The bits of the event types, the buttons and the relative axes
+    let mut key_bits = [0u8; (KEY_MAX as usize + 1) / 8];
+    for (code, _) in BUTTONS.iter() {
+        key_bits[*code as usize / 8] |= 1 << (code % 8);
+    }
+    let mut rel_bits = [0u8; (REL_MAX as usize + 1) / 8];
+    for code in [
+        REL_X,
+        REL_Y,
+        REL_HWHEEL,
+        REL_WHEEL,
+        REL_WHEEL_HI_RES,
+        REL_HWHEEL_HI_RES,
+    ] {
+        rel_bits[code as usize / 8] |= 1 << (code % 8);
+    }
+    let type_bits = [1 << EV_SYN | 1 << EV_KEY | 1 << EV_REL, 0, 0, 0];
+    for (r#type, bits) in [
+        (EV_SYN, &type_bits[..]),
+        (EV_KEY, &key_bits[..]),
+        (EV_REL, &rel_bits[..]),
+    ] {
+        for chunk in bits.chunks(8) {
+            text.push_str(&format!("B: {:02x}", r#type));
+            for byte in chunk {
+                text.push_str(&format!(" {:02x}", byte));
+            }
+            text.push('\n');
+        }
+    }
+
+    for recorded in recording.events.iter() {
+        let button_code = |button: &MouseButton| {
+            BUTTONS
+                .iter()
+                .find(|(_, known)| known == button)
+                .map(|(code, _)| *code)
+        };
+        let events: Vec<(u16, u16, i32)> = match &recorded.event {
+            MouseEvent::RelativeMove(x, y) => [(REL_X, *x), (REL_Y, *y)]
+                .into_iter()
+                .filter(|(_, value)| *value != 0)
+                .map(|(code, value)| (EV_REL, code, value))
+                .collect(),
+            MouseEvent::Press(button) => button_code(button)
+                .map(|code| (EV_KEY, code, 1))
+                .into_iter()
+                .collect(),
+            MouseEvent::Release(button) => button_code(button)
+                .map(|code| (EV_KEY, code, 0))
+                .into_iter()
+                .collect(),
+            MouseEvent::Scroll(direction) => vec![match direction {
+                ScrollDirection::Up => (EV_REL, REL_WHEEL, 1),
+                ScrollDirection::Down => (EV_REL, REL_WHEEL, -1),
+                ScrollDirection::Right => (EV_REL, REL_HWHEEL, 1),
+                ScrollDirection::Left => (EV_REL, REL_HWHEEL, -1),
+            }],
+            MouseEvent::ScrollHiRes { dx, dy } => {
+                [(REL_HWHEEL_HI_RES, *dx), (REL_WHEEL_HI_RES, *dy)]
+                    .into_iter()
+                    .filter(|(_, value)| *value != 0)
+                    .map(|(code, value)| (EV_REL, code, value))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        if events.is_empty() {
+            continue;
+        }
+        let time = format!(
+            "{}.{:06}",
+            recorded.time.as_secs(),
+            recorded.time.subsec_micros()
+        );
+        for (r#type, code, value) in events.into_iter().chain([(EV_SYN, SYN_REPORT, 0)]) {
+            text.push_str(&format!(
+                "E: {} {:04x} {:04x} {:04}\n",
+                time, r#type, code, value
+            ));
+        }
+    }
+    text
+}
+
+/// Parse the time of an event, the seconds and the microseconds e.g. `12.000345`
+fn parse_time(time: &str) -> Option<Duration> {
+    let (secs, micros) = time.split_once('.')?;
+    Some(Duration::from_secs(secs.parse().ok()?) + Duration::from_micros(micros.parse().ok()?))
+}
+
+/// evdev definitions
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const SYN_REPORT: u16 = 0x00;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const REL_WHEEL_HI_RES: u16 = 0x0b;
+const REL_HWHEEL_HI_RES: u16 = 0x0c;
+const REL_MAX: u16 = 0x0f;
+const KEY_MAX: u16 = 0x2ff;
+const BUTTONS: [(u16, MouseButton); 8] = [
+    (0x110, MouseButton::Left),
+    (0x111, MouseButton::Right),
+    (0x112, MouseButton::Middle),
+    (0x113, MouseButton::Side),
+    (0x114, MouseButton::Extra),
+    (0x115, MouseButton::Forward),
+    (0x116, MouseButton::Back),
+    (0x117, MouseButton::Task),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{read, write};
+    use crate::common::{MouseButton, MouseEvent, ScrollDirection};
+    use crate::recorder::{RecordedEvent, Recording};
+    use std::time::Duration;
+
+    #[test]
+    fn evemu_round_trip() {
+        let capture = "\
+# EVEMU 1.3
+N: Logitech USB Optical Mouse
+I: 0003 046d c077 0111
+################################
+#      Waiting for events      #
+################################
+E: 1000.500000 0002 0000 0003\t# EV_REL / REL_X                3
+E: 1000.500000 0002 0001 -002\t# EV_REL / REL_Y                -2
+E: 1000.500000 0000 0000 0000\t# ------------ SYN_REPORT (0) ---------- +0ms
+E: 1000.510000 0004 0004 589825\t# EV_MSC / MSC_SCAN             589825
+E: 1000.510000 0001 0110 0001\t# EV_KEY / BTN_LEFT             1
+E: 1000.510000 0000 0000 0000\t# ------------ SYN_REPORT (0) ---------- +10ms
+E: 1000.600000 0002 0008 -002\t# EV_REL / REL_WHEEL            -2
+E: 1000.600000 0002 000b -240\t# EV_REL / REL_WHEEL_HI_RES     -240
+E: 1000.600000 0000 0000 0000\t# ------------ SYN_REPORT (0) ---------- +90ms
+E: 1001.000001 0001 0110 0000\t# EV_KEY / BTN_LEFT             0
+E: 1001.000001 0000 0000 0000\t# ------------ SYN_REPORT (0) ---------- +400ms
+";
+        let recording = read(capture.as_bytes()).unwrap();
+        let at = |micros, event| RecordedEvent {
+            time: Duration::from_micros(micros),
+            event,
+        };
+        let expected = Recording {
+            events: vec![
+                at(0, MouseEvent::RelativeMove(3, -2)),
+                at(10_000, MouseEvent::Press(MouseButton::Left)),
+                at(100_000, MouseEvent::Scroll(ScrollDirection::Down)),
+                at(100_000, MouseEvent::Scroll(ScrollDirection::Down)),
+                at(100_000, MouseEvent::ScrollHiRes { dx: 0, dy: -240 }),
+                at(500_001, MouseEvent::Release(MouseButton::Left)),
+            ],
+        };
+        assert_eq!(recording, expected);
+        // The export is read back to the same events
+        assert_eq!(read(write(&recording).as_bytes()).unwrap(), expected);
+
+        assert!(read("E: 0.000000 0002 0000\n".as_bytes()).is_err());
+        assert!(read("E: soon 0002 0000 0001\n".as_bytes()).is_err());
+    }
+}
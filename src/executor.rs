@@ -0,0 +1,304 @@
+///
+/// This module contains the policies that decide where the callbacks run
+///
+/// The listeners invoke the callbacks one by one on their dispatch thread, so a
+/// slow callback delays the others. The callbacks that are hooked with a policy
+/// other than `Inline` only hand the events off there, and run somewhere else
+///
+/// The panics of the callbacks that run somewhere else are caught there, and
+/// raised again on the dispatch thread with the next event of the callback, so
+/// they are reported to `on_callback_panic` like the panics of the other callbacks
+///
+use crate::common::MouseEvent;
+use crate::hooks::LockUnpoisoned;
+use std::{
+    any::Any,
+    collections::VecDeque,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// A unit of work that is handed off to an executor
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// The panic of a callback that runs somewhere else, until it is raised on the dispatch thread
+pub(crate) type Panicked = Arc<Mutex<Option<Box<dyn Any + Send>>>>;
+
+/// Where a hooked callback runs
+///
+/// The events of a callback are always delivered in order, and a callback
+/// never runs concurrently with itself
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::executor::{DispatchPolicy, ThreadPool};
+/// use mouce::{Mouse, MouseListener};
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let pool = ThreadPool::new(4);
+/// for _ in 0..8 {
+///     manager
+///         .hook_with_policy(
+///             DispatchPolicy::Pool(pool.clone()),
+///             Box::new(|e| println!("{:?}", e)),
+///         )
+///         .unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub enum DispatchPolicy {
+    /// Run on the dispatch thread of the listener, the default of `hook`
+    #[default]
+    Inline,
+    /// Run on a dedicated thread, which exits once the callback is unhooked
+    Thread,
+    /// Run on the given thread pool, that may be shared by several callbacks
+    Pool(ThreadPool),
+    /// Send the invocations as jobs to the given channel, e.g. to run them
+    /// on the thread of an event loop. The events are dropped once the
+    /// receiver is gone
+    Channel(Sender<Job>),
+}
+
+/// A fixed number of worker threads that run the jobs in the order they come,
+/// the workers exit once all the clones of the pool are dropped
+#[derive(Clone)]
+pub struct ThreadPool {
+    tx: Sender<Job>,
+    size: usize,
+}
+
+impl ThreadPool {
+    /// Start a pool with the given number of workers, at least one
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..size {
+            let rx = rx.clone();
+            thread::spawn(move || loop {
+                // Release the lock before running the job
+                let job = rx.lock_unpoisoned().recv();
+                match job {
+                    // A panicking job does not take the worker down with it
+                    Ok(job) => {
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                    Err(_) => break,
+                }
+            });
+        }
+        ThreadPool { tx, size }
+    }
+
+    /// The number of the workers
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Run the given job on one of the workers
+    pub fn execute(&self, job: Job) {
+        let _ = self.tx.send(job);
+    }
+}
+
+impl fmt::Debug for ThreadPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadPool")
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+/// Wrap the given callback so that it runs with the given policy
+pub(crate) fn with_policy(
+    policy: DispatchPolicy,
+    callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+) -> Box<dyn Fn(&MouseEvent) + Send + Sync> {
+    match policy {
+        DispatchPolicy::Inline => callback,
+        DispatchPolicy::Thread => {
+            let (tx, rx) = mpsc::channel::<MouseEvent>();
+            let panicked = Panicked::default();
+            let caught = panicked.clone();
+            // The thread exits once the wrapper is dropped and the channel is closed
+            thread::spawn(move || {
+                for event in rx {
+                    catch(&caught, || callback(&event));
+                }
+            });
+            Box::new(move |event| {
+                let _ = tx.send(event.clone());
+                raise(&panicked);
+            })
+        }
+        DispatchPolicy::Pool(pool) => queued(callback, move |job| pool.execute(job)),
+        DispatchPolicy::Channel(tx) => queued(callback, move |job| {
+            let _ = tx.send(job);
+        }),
+    }
+}
+
+/// Wrap the given callback so that every event is queued and a job that delivers
+/// the next queued event is submitted, so the jobs of the callback may run on any
+/// thread and in parallel, and still deliver the events in order one at a time
+fn queued<S>(
+    callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    submit: S,
+) -> Box<dyn Fn(&MouseEvent) + Send + Sync>
+where
+    S: Fn(Job) + Send + Sync + 'static,
+{
+    let callback = Arc::new(Mutex::new(callback));
+    let queue: Arc<Mutex<VecDeque<MouseEvent>>> = Arc::default();
+    let panicked = Panicked::default();
+    Box::new(move |event| {
+        queue.lock_unpoisoned().push_back(event.clone());
+        let callback = callback.clone();
+        let queue = queue.clone();
+        let caught = panicked.clone();
+        submit(Box::new(move || {
+            // The callback is locked first, so the jobs take the events in order
+            let callback = callback.lock_unpoisoned();
+            let event = queue.lock_unpoisoned().pop_front();
+            if let Some(event) = event {
+                catch(&caught, || callback(&event));
+            }
+        }));
+        raise(&panicked);
+    })
+}
+
+/// Run the given callback and keep its panic, the first one is kept until it is raised
+pub(crate) fn catch(panicked: &Panicked, callback: impl FnOnce()) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(callback)) {
+        panicked.lock_unpoisoned().get_or_insert(payload);
+    }
+}
+
+/// Raise the kept panic of a callback on the dispatch thread
+pub(crate) fn raise(panicked: &Panicked) {
+    let payload = panicked.lock_unpoisoned().take();
+    if let Some(payload) = payload {
+        panic::resume_unwind(payload);
+    }
+}
+
+/// Run the jobs that are sent to the given receiver on the current thread,
+/// until all the senders are dropped
+pub fn run_jobs(rx: &Receiver<Job>) {
+    for job in rx.iter() {
+        job();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_policy, DispatchPolicy, Job, ThreadPool};
+    use crate::common::MouseEvent;
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::{mpsc, Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    fn moves() -> Vec<MouseEvent> {
+        (0..50).map(|x| MouseEvent::RelativeMove(x, 0)).collect()
+    }
+
+    /// Deliver the moves through a callback with the given policy, and collect them
+    fn deliver(policy: DispatchPolicy, run: impl FnOnce()) -> Vec<MouseEvent> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let collected = received.clone();
+        let callback = with_policy(
+            policy,
+            Box::new(move |event| {
+                // Give the other jobs a chance to overtake this one
+                thread::sleep(Duration::from_micros(100));
+                collected.lock().unwrap().push(event.clone());
+            }),
+        );
+        for event in moves() {
+            callback(&event);
+        }
+        drop(callback);
+        run();
+
+        for _ in 0..100 {
+            if received.lock().unwrap().len() == moves().len() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let received = received.lock().unwrap().clone();
+        received
+    }
+
+    #[test]
+    fn deliver_in_order() {
+        assert_eq!(deliver(DispatchPolicy::Inline, || ()), moves());
+        assert_eq!(deliver(DispatchPolicy::Thread, || ()), moves());
+        assert_eq!(
+            deliver(DispatchPolicy::Pool(ThreadPool::new(4)), || ()),
+            moves()
+        );
+    }
+
+    #[test]
+    fn hand_off_jobs() {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let events = deliver(DispatchPolicy::Channel(tx), move || {
+            // Nothing runs until the receiver runs the jobs
+            assert_eq!(rx.try_iter().count(), moves().len());
+        });
+        assert!(events.is_empty());
+
+        let (tx, rx) = mpsc::channel::<Job>();
+        let events = deliver(DispatchPolicy::Channel(tx), move || super::run_jobs(&rx));
+        assert_eq!(events, moves());
+    }
+
+    #[test]
+    fn survive_panicking_callbacks() {
+        let pool = ThreadPool::new(1);
+        pool.execute(Box::new(|| panic!("the job failed")));
+        let (tx, rx) = mpsc::channel();
+        pool.execute(Box::new(move || tx.send(()).unwrap()));
+        // The only worker is still running
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let callback = with_policy(
+            DispatchPolicy::Thread,
+            Box::new(move |event| {
+                tx.send(event.clone()).unwrap();
+                if event == &MouseEvent::RelativeMove(0, 0) {
+                    panic!("the callback failed");
+                }
+            }),
+        );
+        callback(&MouseEvent::RelativeMove(0, 0));
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        // The panic is raised with the next event, which is still delivered
+        let raised = panic::catch_unwind(AssertUnwindSafe(|| {
+            callback(&MouseEvent::RelativeMove(1, 0))
+        }));
+        assert!(raised.is_err());
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Ok(MouseEvent::RelativeMove(1, 0))
+        );
+        callback(&MouseEvent::RelativeMove(2, 0));
+    }
+}
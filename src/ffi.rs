@@ -0,0 +1,443 @@
+///
+/// This module exposes the mouse actions to C, see `include/mouce.h`
+/// for the declarations of the functions and the constants
+///
+/// The functions return 0 on success and one of the negative
+/// `MOUCE_ERROR_*` codes on failure
+///
+use crate::common::{CallbackId, MouseActions, MouseButton, MouseEvent, ScrollDirection};
+use crate::detect::DetectorConfig;
+use crate::error::Error;
+use crate::Mouse;
+use std::{
+    os::raw::{c_int, c_void},
+    ptr::null_mut,
+};
+
+/// The opaque mouse manager that is handed to C
+pub struct MouceManager {
+    manager: Box<dyn MouseActions>,
+}
+
+/// The mouse event that is passed to the C callbacks
+///
+/// `x` and `y` are the coordinates of the moves and the amounts of the scrolls,
+/// `code` is the button of the presses and releases and the phase of the scrolls
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouceEvent {
+    pub r#type: c_int,
+    pub x: c_int,
+    pub y: c_int,
+    pub code: c_int,
+}
+
+pub type MouceCallback = extern "C" fn(event: *const MouceEvent, user_data: *mut c_void);
+
+/// The user data of a callback, which is only passed back to the callback
+struct UserData(*mut c_void);
+
+// The C side is responsible for the user data being usable from the listener thread,
+// and from the threads that inject the events
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// Create a mouse manager, the maximum coordinates are only used by
+/// uinput and are ignored on the other platforms
+///
+/// Returns null on failure
+#[no_mangle]
+pub extern "C" fn mouce_new(max_x: c_int, max_y: c_int) -> *mut MouceManager {
+    #[cfg(any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    let manager = Mouse::new((0, max_x), (0, max_y));
+    #[cfg(not(any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    let manager = {
+        let _ = (max_x, max_y);
+        Mouse::new()
+    };
+
+    match manager {
+        Ok(manager) => Box::into_raw(Box::new(MouceManager { manager })),
+        Err(_) => null_mut(),
+    }
+}
+
+/// Destroy the given mouse manager, which stops its listener
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`, and not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn mouce_free(manager: *mut MouceManager) {
+    if !manager.is_null() {
+        drop(Box::from_raw(manager));
+    }
+}
+
+/// Move the mouse to the given coordinates
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_move_to(manager: *mut MouceManager, x: c_int, y: c_int) -> c_int {
+    with_manager(manager, |manager| {
+        manager.move_to(x.max(0) as usize, y.max(0) as usize)
+    })
+}
+
+/// Move the mouse relative to the current position
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_move_relative(
+    manager: *mut MouceManager,
+    x_offset: c_int,
+    y_offset: c_int,
+) -> c_int {
+    with_manager(manager, |manager| manager.move_relative(x_offset, y_offset))
+}
+
+/// Write the current position of the mouse to `x` and `y`
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`, `x` and `y` must be null or valid
+#[no_mangle]
+pub unsafe extern "C" fn mouce_get_position(
+    manager: *mut MouceManager,
+    x: *mut c_int,
+    y: *mut c_int,
+) -> c_int {
+    if x.is_null() || y.is_null() {
+        return MOUCE_ERROR_INVALID_ARGUMENT;
+    }
+    with_manager(manager, |manager| {
+        let (pos_x, pos_y) = manager.get_position()?;
+        *x = pos_x;
+        *y = pos_y;
+        Ok(())
+    })
+}
+
+/// Press down the given `MOUCE_BUTTON_*` button
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_press(manager: *mut MouceManager, button: c_int) -> c_int {
+    with_button(manager, button, |manager, button| {
+        manager.press_button(button)
+    })
+}
+
+/// Release the given `MOUCE_BUTTON_*` button
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_release(manager: *mut MouceManager, button: c_int) -> c_int {
+    with_button(manager, button, |manager, button| {
+        manager.release_button(button)
+    })
+}
+
+/// Click the given `MOUCE_BUTTON_*` button
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_click(manager: *mut MouceManager, button: c_int) -> c_int {
+    with_button(manager, button, |manager, button| {
+        manager.click_button(button)
+    })
+}
+
+/// Scroll the mouse wheel by the given number of detents, positive values scroll up and right
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_scroll(manager: *mut MouceManager, dx: c_int, dy: c_int) -> c_int {
+    with_manager(manager, |manager| manager.scroll(dx, dy))
+}
+
+/// Call the given callback with the given user data on every mouse event, the
+/// callback is called from the listener thread
+///
+/// Returns the id of the callback, or a negative error code
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`, and the user data must
+/// stay valid until the callback is unhooked or the manager is freed
+#[no_mangle]
+pub unsafe extern "C" fn mouce_hook(
+    manager: *mut MouceManager,
+    callback: MouceCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    hook_with(manager, callback, user_data, |manager, callback| {
+        manager.hook(callback)
+    })
+}
+
+/// Like `mouce_hook`, and the callback also receives the clicks, the double clicks and
+/// the triple clicks with the double click time and slop of the system
+///
+/// # Safety
+///
+/// The same as `mouce_hook`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_hook_clicks(
+    manager: *mut MouceManager,
+    callback: MouceCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    hook_with(manager, callback, user_data, |manager, callback| {
+        manager.hook_clicks(DetectorConfig::system(), callback)
+    })
+}
+
+/// Attach the given C callback with the given hook function of the manager
+unsafe fn hook_with(
+    manager: *mut MouceManager,
+    callback: MouceCallback,
+    user_data: *mut c_void,
+    hook: impl FnOnce(
+        &mut dyn MouseActions,
+        Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error>,
+) -> c_int {
+    let manager = match manager.as_mut() {
+        Some(manager) => manager,
+        None => return MOUCE_ERROR_INVALID_ARGUMENT,
+    };
+    let user_data = UserData(user_data);
+    let hooked = hook(
+        manager.manager.as_mut(),
+        Box::new(move |event| {
+            // Capture the whole wrapper rather than its pointer, to keep it `Send` and `Sync`
+            let user_data = &user_data;
+            callback(&to_c_event(event), user_data.0);
+        }),
+    );
+    match hooked {
+        Ok(callback_id) => callback_id as c_int,
+        Err(err) => error_code(&err),
+    }
+}
+
+/// Remove the callback with the given id
+///
+/// # Safety
+///
+/// The manager must be null or returned by `mouce_new`
+#[no_mangle]
+pub unsafe extern "C" fn mouce_unhook(manager: *mut MouceManager, callback_id: c_int) -> c_int {
+    let callback_id = match CallbackId::try_from(callback_id) {
+        Ok(callback_id) => callback_id,
+        Err(_) => return MOUCE_ERROR_CALLBACK_NOT_FOUND,
+    };
+    with_manager(manager, |manager| manager.unhook(callback_id))
+}
+
+unsafe fn with_manager(
+    manager: *mut MouceManager,
+    action: impl FnOnce(&mut dyn MouseActions) -> Result<(), Error>,
+) -> c_int {
+    match manager.as_mut() {
+        Some(manager) => match action(manager.manager.as_mut()) {
+            Ok(()) => MOUCE_OK,
+            Err(err) => error_code(&err),
+        },
+        None => MOUCE_ERROR_INVALID_ARGUMENT,
+    }
+}
+
+unsafe fn with_button(
+    manager: *mut MouceManager,
+    button: c_int,
+    action: impl FnOnce(&mut dyn MouseActions, &MouseButton) -> Result<(), Error>,
+) -> c_int {
+    match BUTTONS.get(button as usize) {
+        Some(button) => with_manager(manager, |manager| action(manager, button)),
+        None => MOUCE_ERROR_INVALID_ARGUMENT,
+    }
+}
+
+fn error_code(error: &Error) -> c_int {
+    match error {
+        Error::PermissionDenied => MOUCE_ERROR_PERMISSION_DENIED,
+        Error::NotImplemented => MOUCE_ERROR_NOT_IMPLEMENTED,
+        Error::DeviceNotFound => MOUCE_ERROR_DEVICE_NOT_FOUND,
+        Error::CallbackNotFound => MOUCE_ERROR_CALLBACK_NOT_FOUND,
+        Error::WriteFailed => MOUCE_ERROR_WRITE_FAILED,
+        Error::Unsupported(_) => MOUCE_ERROR_UNSUPPORTED,
+        Error::X11PointerWindowMismatch
+        | Error::CGCouldNotCreateEvent
+        | Error::Cancelled
+        | Error::CustomError(_) => MOUCE_ERROR_OTHER,
+    }
+}
+
+fn to_c_event(event: &MouseEvent) -> MouceEvent {
+    let c_event = |r#type, x, y, code| MouceEvent { r#type, x, y, code };
+    let button = |button: &MouseButton| BUTTONS.iter().position(|b| b == button).unwrap() as c_int;
+    match event {
+        MouseEvent::RelativeMove(x, y) => c_event(MOUCE_EVENT_RELATIVE_MOVE, *x, *y, 0),
+        MouseEvent::AbsoluteMove(x, y) => c_event(MOUCE_EVENT_ABSOLUTE_MOVE, *x, *y, 0),
+        MouseEvent::Press(b) => c_event(MOUCE_EVENT_PRESS, 0, 0, button(b)),
+        MouseEvent::Release(b) => c_event(MOUCE_EVENT_RELEASE, 0, 0, button(b)),
+        MouseEvent::Scroll(direction) => {
+            let (x, y) = match direction {
+                ScrollDirection::Up => (0, 1),
+                ScrollDirection::Down => (0, -1),
+                #[cfg(any(
+                    target_os = "windows",
+                    target_os = "android",
+                    target_os = "linux",
+                    target_os = "dragonfly",
+                    target_os = "freebsd",
+                    target_os = "netbsd",
+                    target_os = "openbsd"
+                ))]
+                ScrollDirection::Left => (-1, 0),
+                #[cfg(any(
+                    target_os = "windows",
+                    target_os = "android",
+                    target_os = "linux",
+                    target_os = "dragonfly",
+                    target_os = "freebsd",
+                    target_os = "netbsd",
+                    target_os = "openbsd"
+                ))]
+                ScrollDirection::Right => (1, 0),
+            };
+            c_event(MOUCE_EVENT_SCROLL, x, y, 0)
+        }
+        MouseEvent::ScrollHiRes { dx, dy } => c_event(MOUCE_EVENT_SCROLL_HI_RES, *dx, *dy, 0),
+        MouseEvent::Scroll2D { dx, dy } => c_event(MOUCE_EVENT_SCROLL_2D, *dx, *dy, 0),
+        MouseEvent::ScrollPhase(phase) => c_event(MOUCE_EVENT_SCROLL_PHASE, 0, 0, *phase as c_int),
+        MouseEvent::DeviceDisconnected(_) => c_event(MOUCE_EVENT_DEVICE_DISCONNECTED, 0, 0, 0),
+        MouseEvent::Click(b) => c_event(MOUCE_EVENT_CLICK, 0, 0, button(b)),
+        MouseEvent::DoubleClick(b) => c_event(MOUCE_EVENT_DOUBLE_CLICK, 0, 0, button(b)),
+        MouseEvent::TripleClick(b) => c_event(MOUCE_EVENT_TRIPLE_CLICK, 0, 0, button(b)),
+    }
+}
+
+/// The definitions that are mirrored in `include/mouce.h`
+const MOUCE_OK: c_int = 0;
+const MOUCE_ERROR_PERMISSION_DENIED: c_int = -1;
+const MOUCE_ERROR_NOT_IMPLEMENTED: c_int = -2;
+const MOUCE_ERROR_DEVICE_NOT_FOUND: c_int = -3;
+const MOUCE_ERROR_CALLBACK_NOT_FOUND: c_int = -4;
+const MOUCE_ERROR_WRITE_FAILED: c_int = -5;
+const MOUCE_ERROR_UNSUPPORTED: c_int = -6;
+const MOUCE_ERROR_INVALID_ARGUMENT: c_int = -7;
+const MOUCE_ERROR_OTHER: c_int = -8;
+const MOUCE_EVENT_RELATIVE_MOVE: c_int = 0;
+const MOUCE_EVENT_ABSOLUTE_MOVE: c_int = 1;
+const MOUCE_EVENT_PRESS: c_int = 2;
+const MOUCE_EVENT_RELEASE: c_int = 3;
+const MOUCE_EVENT_SCROLL: c_int = 4;
+const MOUCE_EVENT_SCROLL_HI_RES: c_int = 5;
+const MOUCE_EVENT_SCROLL_2D: c_int = 6;
+const MOUCE_EVENT_SCROLL_PHASE: c_int = 7;
+const MOUCE_EVENT_DEVICE_DISCONNECTED: c_int = 8;
+const MOUCE_EVENT_CLICK: c_int = 9;
+const MOUCE_EVENT_DOUBLE_CLICK: c_int = 10;
+const MOUCE_EVENT_TRIPLE_CLICK: c_int = 11;
+/// The buttons in the order of the `MOUCE_BUTTON_*` constants
+const BUTTONS: [MouseButton; 8] = [
+    MouseButton::Left,
+    MouseButton::Right,
+    MouseButton::Middle,
+    MouseButton::Side,
+    MouseButton::Extra,
+    MouseButton::Forward,
+    MouseButton::Back,
+    MouseButton::Task,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ScrollPhase;
+
+    #[test]
+    fn c_events() {
+        assert_eq!(
+            to_c_event(&MouseEvent::RelativeMove(-2, 3)),
+            MouceEvent {
+                r#type: MOUCE_EVENT_RELATIVE_MOVE,
+                x: -2,
+                y: 3,
+                code: 0
+            }
+        );
+        assert_eq!(
+            to_c_event(&MouseEvent::Release(MouseButton::Back)),
+            MouceEvent {
+                r#type: MOUCE_EVENT_RELEASE,
+                x: 0,
+                y: 0,
+                code: 6
+            }
+        );
+        assert_eq!(
+            to_c_event(&MouseEvent::Scroll(ScrollDirection::Down)),
+            MouceEvent {
+                r#type: MOUCE_EVENT_SCROLL,
+                x: 0,
+                y: -1,
+                code: 0
+            }
+        );
+        assert_eq!(
+            to_c_event(&MouseEvent::ScrollPhase(ScrollPhase::Ended)),
+            MouceEvent {
+                r#type: MOUCE_EVENT_SCROLL_PHASE,
+                x: 0,
+                y: 0,
+                code: 3
+            }
+        );
+    }
+
+    #[test]
+    fn null_managers() {
+        unsafe {
+            mouce_free(null_mut());
+            assert_eq!(
+                mouce_move_to(null_mut(), 0, 0),
+                MOUCE_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                mouce_click(null_mut(), BUTTONS.len() as c_int),
+                MOUCE_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(mouce_unhook(null_mut(), -1), MOUCE_ERROR_CALLBACK_NOT_FOUND);
+        }
+    }
+}
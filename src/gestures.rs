@@ -0,0 +1,650 @@
+///
+/// This module contains the gestures that are built on top of
+/// the movement primitives of the mouse and touch managers
+///
+use crate::cancel::CancellationToken;
+use crate::common::{MouseButton, MouseController};
+use crate::error::Error;
+use crate::touch::TouchActions;
+use std::{
+    f64::consts::PI,
+    time::{Duration, Instant},
+};
+
+/// The interval between the steps of the gestures, 125 moves per second
+const GESTURE_INTERVAL: Duration = Duration::from_millis(8);
+/// The distance between the consecutive points on a circle
+const CIRCLE_STEP: f64 = 5.;
+/// The least number of the points on a revolution, so small circles stay round
+const MIN_CIRCLE_STEPS: usize = 16;
+/// The distance of each finger from the center at the start of a pinch
+const PINCH_RADIUS: f64 = 100.;
+/// The number of the steps of a pinch
+const PINCH_STEPS: usize = 30;
+
+/// Swipe from the `from` coordinates to the `to` coordinates over the given
+/// duration, by holding the left button and moving at a constant speed
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::gestures::swipe;
+/// use mouce::Mouse;
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let duration = Duration::from_millis(300);
+/// assert_eq!(swipe(manager.as_mut(), (1500, 500), (300, 500), duration), Ok(()));
+/// ```
+pub fn swipe<M: MouseController + ?Sized>(
+    manager: &mut M,
+    from: (i32, i32),
+    to: (i32, i32),
+    duration: Duration,
+) -> Result<(), Error> {
+    swipe_cancellable(manager, from, to, duration, &CancellationToken::new())
+}
+
+/// Swipe like `swipe`, but stop between the steps once the given token is
+/// cancelled and fail with `Error::Cancelled`, the button is released either way
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::cancel::CancellationToken;
+/// use mouce::gestures::swipe_cancellable;
+/// use mouce::Mouse;
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let token = CancellationToken::new();
+/// let duration = Duration::from_millis(300);
+/// swipe_cancellable(manager.as_mut(), (1500, 500), (300, 500), duration, &token).unwrap();
+/// ```
+pub fn swipe_cancellable<M: MouseController + ?Sized>(
+    manager: &mut M,
+    from: (i32, i32),
+    to: (i32, i32),
+    duration: Duration,
+    token: &CancellationToken,
+) -> Result<(), Error> {
+    let steps = (duration.as_millis() / GESTURE_INTERVAL.as_millis()).max(1) as usize;
+    let path = line_path(from, to, steps);
+
+    token.check()?;
+    let (x, y) = to_position(from);
+    manager.move_to(x, y)?;
+    manager.press_button(&MouseButton::Left)?;
+    let swiped = path.into_iter().try_for_each(|point| {
+        token.sleep(GESTURE_INTERVAL)?;
+        let (x, y) = to_position(point);
+        manager.move_to(x, y)
+    });
+    // Do not leave the button pressed, also when the swipe fails or is cancelled
+    let released = manager.release_button(&MouseButton::Left);
+    swiped.and(released)
+}
+
+/// Move the mouse around a circle with the given center and radius, starting from
+/// its rightmost point, e.g. to scroll the circular scroll areas of the touchpads
+///
+/// Negative revolutions go counterclockwise, a fraction of a revolution moves on an arc,
+/// the revolutions that are NaN or infinite are rejected with an error
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::gestures::circle;
+/// use mouce::Mouse;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// assert_eq!(circle(manager.as_mut(), (960, 540), 200, 2.), Ok(()));
+/// ```
+pub fn circle<M: MouseController + ?Sized>(
+    manager: &mut M,
+    center: (i32, i32),
+    radius: u32,
+    revolutions: f64,
+) -> Result<(), Error> {
+    circle_cancellable(
+        manager,
+        center,
+        radius,
+        revolutions,
+        &CancellationToken::new(),
+    )
+}
+
+/// Move around a circle like `circle`, but stop between the steps once the
+/// given token is cancelled and fail with `Error::Cancelled`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::cancel::CancellationToken;
+/// use mouce::gestures::circle_cancellable;
+/// use mouce::Mouse;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let token = CancellationToken::new();
+/// circle_cancellable(manager.as_mut(), (960, 540), 200, 2., &token).unwrap();
+/// ```
+pub fn circle_cancellable<M: MouseController + ?Sized>(
+    manager: &mut M,
+    center: (i32, i32),
+    radius: u32,
+    revolutions: f64,
+    token: &CancellationToken,
+) -> Result<(), Error> {
+    let path = circle_path(center, radius, revolutions)?;
+    token.check()?;
+    for (i, point) in path.into_iter().enumerate() {
+        if i != 0 {
+            token.sleep(GESTURE_INTERVAL)?;
+        }
+        let (x, y) = to_position(point);
+        manager.move_to(x, y)?;
+    }
+    Ok(())
+}
+
+/// Move the mouse along the lines between the given points over the given duration,
+/// at a constant speed along the whole path
+///
+/// The moves are scheduled `8ms` apart from the start, so the path takes the given
+/// duration even when the moves themselves take a while
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::gestures::move_along_path;
+/// use mouce::Mouse;
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let points = [(100, 100), (500, 100), (500, 400)];
+/// let duration = Duration::from_millis(500);
+/// assert_eq!(move_along_path(manager.as_mut(), &points, duration), Ok(()));
+/// ```
+pub fn move_along_path<M: MouseController + ?Sized>(
+    manager: &mut M,
+    points: &[(i32, i32)],
+    duration: Duration,
+) -> Result<(), Error> {
+    move_along_path_cancellable(manager, points, duration, &CancellationToken::new())
+}
+
+/// Move along the path like `move_along_path`, but stop between the moves once
+/// the given token is cancelled and fail with `Error::Cancelled`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::cancel::CancellationToken;
+/// use mouce::gestures::move_along_path_cancellable;
+/// use mouce::Mouse;
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let token = CancellationToken::new();
+/// let points = [(100, 100), (500, 100), (500, 400)];
+/// let duration = Duration::from_millis(500);
+/// move_along_path_cancellable(manager.as_mut(), &points, duration, &token).unwrap();
+/// ```
+pub fn move_along_path_cancellable<M: MouseController + ?Sized>(
+    manager: &mut M,
+    points: &[(i32, i32)],
+    duration: Duration,
+    token: &CancellationToken,
+) -> Result<(), Error> {
+    let start = *points
+        .first()
+        .ok_or_else(|| Error::CustomError(String::from("the path has no points")))?;
+    follow(
+        manager,
+        start,
+        polyline_path(points, gesture_steps(duration)),
+        token,
+    )
+}
+
+/// Move the mouse along the cubic bezier curve from `p0` to `p3` over the given
+/// duration, `p1` and `p2` are the control points that the curve bends towards
+///
+/// The curve is sampled at even intervals of its parameter, so the mouse slows
+/// down where the curve bends the most, like a hand does
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::gestures::move_bezier;
+/// use mouce::Mouse;
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let duration = Duration::from_millis(400);
+/// assert_eq!(
+///     move_bezier(manager.as_mut(), (100, 800), (300, 200), (900, 100), (1200, 500), duration),
+///     Ok(())
+/// );
+/// ```
+pub fn move_bezier<M: MouseController + ?Sized>(
+    manager: &mut M,
+    p0: (i32, i32),
+    p1: (i32, i32),
+    p2: (i32, i32),
+    p3: (i32, i32),
+    duration: Duration,
+) -> Result<(), Error> {
+    move_bezier_cancellable(manager, p0, p1, p2, p3, duration, &CancellationToken::new())
+}
+
+/// Move along the curve like `move_bezier`, but stop between the moves once
+/// the given token is cancelled and fail with `Error::Cancelled`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::cancel::CancellationToken;
+/// use mouce::gestures::move_bezier_cancellable;
+/// use mouce::Mouse;
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let token = CancellationToken::new();
+/// let duration = Duration::from_millis(400);
+/// move_bezier_cancellable(
+///     manager.as_mut(),
+///     (100, 800),
+///     (300, 200),
+///     (900, 100),
+///     (1200, 500),
+///     duration,
+///     &token,
+/// )
+/// .unwrap();
+/// ```
+pub fn move_bezier_cancellable<M: MouseController + ?Sized>(
+    manager: &mut M,
+    p0: (i32, i32),
+    p1: (i32, i32),
+    p2: (i32, i32),
+    p3: (i32, i32),
+    duration: Duration,
+    token: &CancellationToken,
+) -> Result<(), Error> {
+    follow(
+        manager,
+        p0,
+        bezier_path([p0, p1, p2, p3], gesture_steps(duration)),
+        token,
+    )
+}
+
+/// Pinch with two fingers around the given center, the fingers move
+/// apart to zoom in when the scale is above 1, and together below 1
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// use mouce::gestures::pinch;
+/// use mouce::nix::UInputTouchManager;
+///
+/// let mut manager = UInputTouchManager::new((0, 1920), (0, 1080)).unwrap();
+/// // Zoom in to double the size
+/// assert_eq!(pinch(&mut manager, (960, 540), 2.), Ok(()));
+/// # }
+/// ```
+pub fn pinch(manager: &mut dyn TouchActions, center: (i32, i32), scale: f64) -> Result<(), Error> {
+    pinch_cancellable(manager, center, scale, &CancellationToken::new())
+}
+
+/// Pinch like `pinch`, but stop between the steps once the given token is
+/// cancelled and fail with `Error::Cancelled`, the fingers are lifted either way
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[cfg(target_os = "linux")]
+/// # {
+/// use mouce::cancel::CancellationToken;
+/// use mouce::gestures::pinch_cancellable;
+/// use mouce::nix::UInputTouchManager;
+///
+/// let mut manager = UInputTouchManager::new((0, 1920), (0, 1080)).unwrap();
+/// let token = CancellationToken::new();
+/// pinch_cancellable(&mut manager, (960, 540), 2., &token).unwrap();
+/// # }
+/// ```
+pub fn pinch_cancellable(
+    manager: &mut dyn TouchActions,
+    center: (i32, i32),
+    scale: f64,
+    token: &CancellationToken,
+) -> Result<(), Error> {
+    if scale.is_nan() || scale <= 0. {
+        return Err(Error::CustomError(format!(
+            "the scale of a pinch must be positive, got {}",
+            scale
+        )));
+    }
+
+    token.check()?;
+    let fingers = pinch_path(center, scale);
+    let (first, second) = fingers[0];
+    manager.touch_down(0, first.0, first.1)?;
+    if let Err(err) = manager.touch_down(1, second.0, second.1) {
+        let _ = manager.touch_up(0);
+        return Err(err);
+    }
+
+    let moved = fingers[1..].iter().try_for_each(|(first, second)| {
+        token.sleep(GESTURE_INTERVAL)?;
+        manager.touch_move(0, first.0, first.1)?;
+        manager.touch_move(1, second.0, second.1)
+    });
+    // Lift the fingers even if they could not be moved
+    let lifted = manager.touch_up(0).and(manager.touch_up(1));
+    moved.and(lifted)
+}
+
+/// Move to the start and then to the points of the path, one at every `GESTURE_INTERVAL`
+///
+/// The moves are paced against their deadlines, so the time that the moves
+/// take does not add up over the path, the waits stop once the token is cancelled
+fn follow<M: MouseController + ?Sized>(
+    manager: &mut M,
+    start: (i32, i32),
+    path: Vec<(i32, i32)>,
+    token: &CancellationToken,
+) -> Result<(), Error> {
+    token.check()?;
+    let (x, y) = to_position(start);
+    manager.move_to(x, y)?;
+    let started = Instant::now();
+    for (i, point) in path.into_iter().enumerate() {
+        let deadline = started + GESTURE_INTERVAL * (i as u32 + 1);
+        let now = Instant::now();
+        if deadline > now {
+            token.sleep(deadline - now)?;
+        }
+        let (x, y) = to_position(point);
+        manager.move_to(x, y)?;
+    }
+    Ok(())
+}
+
+/// The number of the steps of a gesture that takes the given duration
+fn gesture_steps(duration: Duration) -> usize {
+    (duration.as_millis() / GESTURE_INTERVAL.as_millis()).max(1) as usize
+}
+
+/// The points of a line after the start, the last one is the end
+fn line_path(from: (i32, i32), to: (i32, i32), steps: usize) -> Vec<(i32, i32)> {
+    let (delta_x, delta_y) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+    (1..=steps)
+        .map(|step| {
+            let progress = step as f64 / steps as f64;
+            (
+                (from.0 as f64 + delta_x * progress).round() as i32,
+                (from.1 as f64 + delta_y * progress).round() as i32,
+            )
+        })
+        .collect()
+}
+
+/// The points along the lines between the given points after the first one, evenly
+/// spaced by the distance on the lines, the last one is the last of the given points
+fn polyline_path(points: &[(i32, i32)], steps: usize) -> Vec<(i32, i32)> {
+    let lengths: Vec<f64> = points
+        .windows(2)
+        .map(|line| {
+            let (delta_x, delta_y) = (
+                (line[1].0 - line[0].0) as f64,
+                (line[1].1 - line[0].1) as f64,
+            );
+            delta_x.hypot(delta_y)
+        })
+        .collect();
+    let total: f64 = lengths.iter().sum();
+    let last = points[points.len() - 1];
+    if total == 0. {
+        return vec![last; steps];
+    }
+
+    (1..=steps)
+        .map(|step| {
+            let mut distance = total * step as f64 / steps as f64;
+            for (line, length) in points.windows(2).zip(lengths.iter()) {
+                if distance <= *length && *length > 0. {
+                    let progress = distance / length;
+                    return (
+                        (line[0].0 as f64 + (line[1].0 - line[0].0) as f64 * progress).round()
+                            as i32,
+                        (line[0].1 as f64 + (line[1].1 - line[0].1) as f64 * progress).round()
+                            as i32,
+                    );
+                }
+                distance -= length;
+            }
+            last
+        })
+        .collect()
+}
+
+/// The points of a cubic bezier curve after its start, the last one is its end
+fn bezier_path(points: [(i32, i32); 4], steps: usize) -> Vec<(i32, i32)> {
+    let bezier = |p: [f64; 4], t: f64| {
+        (1. - t).powi(3) * p[0]
+            + 3. * (1. - t).powi(2) * t * p[1]
+            + 3. * (1. - t) * t * t * p[2]
+            + t.powi(3) * p[3]
+    };
+    let xs = points.map(|point| point.0 as f64);
+    let ys = points.map(|point| point.1 as f64);
+    (1..=steps)
+        .map(|step| {
+            let t = step as f64 / steps as f64;
+            (bezier(xs, t).round() as i32, bezier(ys, t).round() as i32)
+        })
+        .collect()
+}
+
+/// The points on the circle from the start to the end of the revolutions,
+/// clockwise on the screen since the y axis points down
+fn circle_path(
+    center: (i32, i32),
+    radius: u32,
+    revolutions: f64,
+) -> Result<Vec<(i32, i32)>, Error> {
+    if !revolutions.is_finite() {
+        return Err(Error::CustomError(format!(
+            "the revolutions of a circle must be finite, got {}",
+            revolutions
+        )));
+    }
+    let radius = radius as f64;
+    let steps_per_revolution =
+        ((2. * PI * radius / CIRCLE_STEP).ceil() as usize).max(MIN_CIRCLE_STEPS);
+    let steps = (steps_per_revolution as f64 * revolutions.abs()).ceil() as usize;
+    let angle = 2. * PI * revolutions;
+
+    Ok((0..=steps)
+        .map(|step| {
+            let theta = if steps == 0 {
+                0.
+            } else {
+                angle * step as f64 / steps as f64
+            };
+            (
+                (center.0 as f64 + radius * theta.cos()).round() as i32,
+                (center.1 as f64 + radius * theta.sin()).round() as i32,
+            )
+        })
+        .collect())
+}
+
+/// The positions of the two fingers on each step of a horizontal pinch
+fn pinch_path(center: (i32, i32), scale: f64) -> Vec<((i32, i32), (i32, i32))> {
+    (0..=PINCH_STEPS)
+        .map(|step| {
+            let progress = step as f64 / PINCH_STEPS as f64;
+            let distance = (PINCH_RADIUS * (1. + (scale - 1.) * progress)).round() as i32;
+            (
+                (center.0 - distance, center.1),
+                (center.0 + distance, center.1),
+            )
+        })
+        .collect()
+}
+
+/// Clamp the given point to the screen, as the mouse can not be moved to negative coordinates
+fn to_position(point: (i32, i32)) -> (usize, usize) {
+    (point.0.max(0) as usize, point.1.max(0) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_paths() {
+        assert_eq!(
+            line_path((0, 0), (100, -50), 4),
+            vec![(25, -13), (50, -25), (75, -38), (100, -50)]
+        );
+        assert_eq!(line_path((10, 10), (20, 20), 1), vec![(20, 20)]);
+    }
+
+    #[test]
+    fn polyline_paths() {
+        assert_eq!(
+            polyline_path(&[(0, 0), (100, 0), (100, 100)], 4),
+            vec![(50, 0), (100, 0), (100, 50), (100, 100)]
+        );
+        // The lines without a length are skipped
+        assert_eq!(
+            polyline_path(&[(0, 0), (0, 0), (0, 30)], 3),
+            vec![(0, 10), (0, 20), (0, 30)]
+        );
+        assert_eq!(polyline_path(&[(5, 5)], 2), vec![(5, 5), (5, 5)]);
+    }
+
+    #[test]
+    fn bezier_paths() {
+        let path = bezier_path([(0, 0), (0, 100), (100, 100), (100, 0)], 4);
+        assert_eq!(path, vec![(16, 56), (50, 75), (84, 56), (100, 0)]);
+        // A curve with the control points on the line is the line itself
+        assert_eq!(
+            bezier_path([(0, 0), (10, 10), (20, 20), (30, 30)], 3),
+            vec![(10, 10), (20, 20), (30, 30)]
+        );
+    }
+
+    #[test]
+    fn circle_paths() {
+        let path = circle_path((100, 100), 10, 1.).unwrap();
+        assert_eq!(path.len(), MIN_CIRCLE_STEPS + 1);
+        assert_eq!(path[0], (110, 100));
+        // A quarter of a revolution goes down on the screen
+        assert_eq!(path[MIN_CIRCLE_STEPS / 4], (100, 110));
+        assert_eq!(path[MIN_CIRCLE_STEPS], (110, 100));
+        for (x, y) in path {
+            let distance = (((x - 100).pow(2) + (y - 100).pow(2)) as f64).sqrt();
+            assert!((distance - 10.).abs() <= 1.);
+        }
+
+        let path = circle_path((100, 100), 10, -0.25).unwrap();
+        assert_eq!(path.last(), Some(&(100, 90)));
+        assert_eq!(circle_path((100, 100), 10, 0.), Ok(vec![(110, 100)]));
+        // The revolutions that would never end are rejected before any move
+        assert!(circle_path((100, 100), 10, f64::NAN).is_err());
+        assert!(circle_path((100, 100), 10, f64::INFINITY).is_err());
+        assert!(circle_path((100, 100), 10, f64::NEG_INFINITY).is_err());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn cancel_swipe() {
+        use crate::mock::{MockCall, MockMouseManager};
+
+        let mut manager = MockMouseManager::new();
+        let token = CancellationToken::new();
+        let stop = token.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            stop.cancel();
+        });
+        // The swipe would take seconds, it stops with the button released
+        assert_eq!(
+            swipe_cancellable(
+                &mut manager,
+                (0, 0),
+                (1000, 0),
+                Duration::from_secs(5),
+                &token
+            ),
+            Err(Error::Cancelled)
+        );
+        canceller.join().unwrap();
+        let calls = manager.calls();
+        assert_eq!(calls[1], MockCall::Press(MouseButton::Left));
+        assert_eq!(calls.last(), Some(&MockCall::Release(MouseButton::Left)));
+
+        manager.clear_calls();
+        assert_eq!(
+            circle_cancellable(&mut manager, (100, 100), 10, 1., &token),
+            Err(Error::Cancelled)
+        );
+        assert_eq!(
+            move_bezier_cancellable(
+                &mut manager,
+                (0, 0),
+                (0, 10),
+                (10, 10),
+                (10, 0),
+                Duration::ZERO,
+                &token
+            ),
+            Err(Error::Cancelled)
+        );
+        assert!(manager.calls().is_empty());
+    }
+
+    #[test]
+    fn pinch_paths() {
+        let path = pinch_path((500, 300), 2.);
+        assert_eq!(path.len(), PINCH_STEPS + 1);
+        assert_eq!(path[0], ((400, 300), (600, 300)));
+        assert_eq!(path[PINCH_STEPS], ((300, 300), (700, 300)));
+
+        let path = pinch_path((500, 300), 0.5);
+        assert_eq!(path[PINCH_STEPS], ((450, 300), (550, 300)));
+    }
+}
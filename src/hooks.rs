@@ -0,0 +1,578 @@
+///
+/// This module contains the list of the callbacks that the listeners of
+/// the managers invoke, in the order of their priorities
+///
+/// The callbacks of the higher priorities are invoked first, and the callbacks
+/// of the same priority in the order they are attached. A callback that is
+/// attached with `hook_with_priority` can stop the event from reaching the
+/// callbacks of the lower priorities
+///
+/// A callback that panics does not stop the others, the panic is caught and
+/// reported to the handler of `on_callback_panic`
+///
+use crate::common::{
+    CallbackId, CallbackPanic, EventAction, EventMask, MouseEvent, MouseEventExt, Propagation,
+};
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+/// The priority of the callbacks that are not attached with `hook_with_priority`
+pub(crate) const DEFAULT_PRIORITY: i32 = 0;
+
+/// Locks the mutexes of the managers even if a callback panicked while holding
+/// the lock, so that one panicking callback does not break the manager
+pub(crate) trait LockUnpoisoned<T> {
+    fn lock_unpoisoned(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockUnpoisoned<T> for Mutex<T> {
+    fn lock_unpoisoned(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The events that the callbacks receive, the managers that report
+/// the devices of the events pass `MouseEventExt`s to their callbacks
+pub(crate) trait HookEvent {
+    fn mouse_event(&self) -> &MouseEvent;
+}
+
+impl HookEvent for MouseEvent {
+    fn mouse_event(&self) -> &MouseEvent {
+        self
+    }
+}
+
+impl HookEvent for MouseEventExt {
+    fn mouse_event(&self) -> &MouseEvent {
+        &self.event
+    }
+}
+
+/// A callback in the list
+pub(crate) enum Handler<E> {
+    /// Decides whether the event is passed through to the system
+    /// while the devices are grabbed
+    Action(Box<dyn Fn(&E) -> EventAction + Send + Sync>),
+    /// Decides whether the callbacks of the lower priorities receive the event
+    Propagate(Box<dyn Fn(&E) -> Propagation + Send + Sync>),
+}
+
+struct Hook<E> {
+    id: CallbackId,
+    priority: i32,
+    /// The event types that the callback is invoked for
+    mask: EventMask,
+    /// The callbacks are `Sync`, so the snapshots share them between the threads
+    /// and invoke them without taking a lock
+    handler: Handler<E>,
+    /// Whether the callback is skipped until it is resumed
+    paused: AtomicBool,
+}
+
+/// The callbacks in the order they are invoked
+type Snapshot<E> = Arc<[Arc<Hook<E>>]>;
+
+/// The handler of `on_callback_panic`
+struct PanicHandler {
+    handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    /// Whether the panicking callbacks are removed
+    unhook: bool,
+}
+
+/// Catches the panics of the callbacks of a manager and reports them to the handler
+/// of `on_callback_panic`, the clones share the same handler
+#[derive(Clone, Default)]
+pub(crate) struct PanicReporter {
+    handler: Arc<Mutex<Option<PanicHandler>>>,
+}
+
+impl PanicReporter {
+    pub(crate) fn set(&self, handler: Box<dyn Fn(&CallbackPanic) + Send>, unhook: bool) {
+        *self.handler.lock_unpoisoned() = Some(PanicHandler { handler, unhook });
+    }
+
+    /// Invoke the given callback and catch its panic, which is reported to the handler
+    ///
+    /// Fails with whether the callback is to be removed after the panic, the
+    /// caller removes it
+    pub(crate) fn catch<T>(
+        &self,
+        callback_id: CallbackId,
+        callback: impl FnOnce() -> T,
+    ) -> Result<T, bool> {
+        let payload = match panic::catch_unwind(AssertUnwindSafe(callback)) {
+            Ok(result) => return Ok(result),
+            Err(payload) => payload,
+        };
+        if payload.is::<Unhook>() {
+            return Err(true);
+        }
+        let handler = self.handler.lock_unpoisoned();
+        let Some(handler) = &*handler else {
+            return Err(false);
+        };
+        let report = CallbackPanic {
+            callback_id,
+            message: panic_message(payload.as_ref()),
+            unhooked: handler.unhook,
+        };
+        // A panicking handler is ignored as well, it must not stop the listener
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| (handler.handler)(&report)));
+        Err(handler.unhook)
+    }
+}
+
+/// The payload that a callback unwinds with to remove itself, it is not reported as a panic
+struct Unhook;
+
+/// Remove the running callback from its list, e.g. once the receiver that it feeds is
+/// dropped. The browsers can not catch the unwinding, so the callback stays there
+pub(crate) fn unhook_current() {
+    #[cfg(not(target_arch = "wasm32"))]
+    panic::resume_unwind(Box::new(Unhook));
+}
+
+/// The callbacks that are invoked outside of `Hooks`, e.g. the raw and the restart
+/// callbacks, their panics are reported with the reporter of the hooked callbacks
+pub(crate) struct CallbackList<F: ?Sized> {
+    callbacks: CallbackEntries<F>,
+    panics: PanicReporter,
+}
+
+impl<F: ?Sized> CallbackList<F> {
+    pub(crate) fn new(panics: PanicReporter) -> Self {
+        CallbackList {
+            callbacks: Mutex::new(Vec::new()),
+            panics,
+        }
+    }
+
+    pub(crate) fn push(&self, id: CallbackId, callback: Box<F>) {
+        self.callbacks
+            .lock_unpoisoned()
+            .push((id, Arc::new(Mutex::new(callback))));
+    }
+
+    /// Remove the callback with the given id, returns whether it is found
+    pub(crate) fn remove(&self, id: CallbackId) -> bool {
+        let mut callbacks = self.callbacks.lock_unpoisoned();
+        let count = callbacks.len();
+        callbacks.retain(|(callback_id, _)| *callback_id != id);
+        callbacks.len() != count
+    }
+
+    pub(crate) fn clear(&self) {
+        self.callbacks.lock_unpoisoned().clear();
+    }
+
+    /// Invoke every callback with the given function
+    pub(crate) fn invoke(&self, call: impl Fn(&F)) {
+        invoke_all(&self.callbacks, &self.panics, call);
+    }
+}
+
+/// A list of the callbacks that are invoked outside of `Hooks`, every callback is
+/// shared so the list is not locked while they run
+pub(crate) type CallbackEntries<F> = Mutex<Vec<(CallbackId, Arc<Mutex<Box<F>>>)>>;
+
+/// Invoke the callbacks of the given list with the given function, the panicking
+/// callbacks are removed if the handler of `on_callback_panic` says so
+///
+/// The list is copied first, so the callbacks may attach or remove callbacks
+pub(crate) fn invoke_all<F: ?Sized>(
+    callbacks: &CallbackEntries<F>,
+    panics: &PanicReporter,
+    call: impl Fn(&F),
+) {
+    let snapshot = callbacks.lock_unpoisoned().clone();
+    let removed: Vec<CallbackId> = snapshot
+        .iter()
+        .filter(|(id, callback)| {
+            panics.catch(*id, || call(&**callback.lock_unpoisoned())) == Err(true)
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    if !removed.is_empty() {
+        callbacks
+            .lock_unpoisoned()
+            .retain(|(id, _)| !removed.contains(id));
+    }
+}
+
+/// The callbacks of a listener, sorted in the order they are invoked
+///
+/// Every change copies the list and swaps the copy in, so the listener threads
+/// dispatch the events from a snapshot of the list without locking it, and
+/// attaching a callback never waits for a running callback
+pub(crate) struct Hooks<E> {
+    hooks: Mutex<Snapshot<E>>,
+    /// Increased on every change, the dispatchers only take a new snapshot once it changes
+    generation: AtomicUsize,
+    panics: PanicReporter,
+}
+
+impl<E: HookEvent> Hooks<E> {
+    pub(crate) fn new() -> Self {
+        Hooks {
+            hooks: Mutex::new(Arc::from(Vec::new())),
+            generation: AtomicUsize::new(0),
+            panics: PanicReporter::default(),
+        }
+    }
+
+    /// Add the given callback after the callbacks of the same or higher priorities
+    pub(crate) fn insert(
+        &self,
+        id: CallbackId,
+        priority: i32,
+        mask: EventMask,
+        handler: Handler<E>,
+    ) {
+        self.update(|hooks| {
+            let index = hooks
+                .iter()
+                .position(|hook| hook.priority < priority)
+                .unwrap_or(hooks.len());
+            hooks.insert(
+                index,
+                Arc::new(Hook {
+                    id,
+                    priority,
+                    mask,
+                    handler,
+                    paused: AtomicBool::new(false),
+                }),
+            );
+        });
+    }
+
+    /// Remove the callback with the given id, returns whether it is found
+    pub(crate) fn remove(&self, id: CallbackId) -> bool {
+        let mut found = false;
+        self.update(|hooks| {
+            let count = hooks.len();
+            hooks.retain(|hook| hook.id != id);
+            found = hooks.len() != count;
+        });
+        found
+    }
+
+    /// Pause or resume the callback with the given id, returns whether it is found
+    pub(crate) fn set_paused(&self, id: CallbackId, paused: bool) -> bool {
+        match self.snapshot().iter().find(|hook| hook.id == id) {
+            // The flag is shared with the snapshots, so the list is not copied
+            Some(hook) => {
+                hook.paused.store(paused, Ordering::Release);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        self.update(|hooks| hooks.clear());
+    }
+
+    /// Whether no callback is attached
+    pub(crate) fn is_empty(&self) -> bool {
+        self.snapshot().is_empty()
+    }
+
+    /// Whether the callback with the given id is attached, the panicking callbacks
+    /// and the ones that call `unhook_current` are removed without the manager
+    pub(crate) fn contains(&self, id: CallbackId) -> bool {
+        self.snapshot().iter().any(|hook| hook.id == id)
+    }
+
+    /// Report the panics of the callbacks to the given handler instead of only
+    /// skipping them, and remove the panicking callbacks if `unhook` is set
+    pub(crate) fn set_panic_handler(
+        &self,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+        unhook: bool,
+    ) {
+        self.panics.set(handler, unhook);
+    }
+
+    /// The reporter of the panics, to share it with the other callbacks of the manager
+    pub(crate) fn panics(&self) -> PanicReporter {
+        self.panics.clone()
+    }
+
+    /// Invoke the callbacks that subscribe to the type of the given event in order,
+    /// until one of them stops the event, the paused callbacks are skipped
+    ///
+    /// The event is consumed if any of the invoked callbacks consumes it
+    pub(crate) fn dispatch(&self, event: &E) -> EventAction {
+        self.dispatch_snapshot(&self.snapshot(), event).0
+    }
+
+    /// Get a dispatcher for a listener thread, which keeps the snapshot of
+    /// the callbacks until they change
+    pub(crate) fn dispatcher(self: &Arc<Self>) -> Dispatcher<E> {
+        let generation = self.generation.load(Ordering::Acquire);
+        Dispatcher {
+            hooks: self.clone(),
+            snapshot: self.snapshot(),
+            generation,
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot<E> {
+        self.hooks.lock_unpoisoned().clone()
+    }
+
+    /// Swap in a changed copy of the list
+    fn update(&self, change: impl FnOnce(&mut Vec<Arc<Hook<E>>>)) {
+        let mut hooks = self.hooks.lock_unpoisoned();
+        let mut copy = hooks.to_vec();
+        change(&mut copy);
+        *hooks = Arc::from(copy);
+        // Increased while the lock is held, so the dispatchers that see the
+        // new generation also see the new list
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    fn dispatch_snapshot(&self, hooks: &[Arc<Hook<E>>], event: &E) -> (EventAction, usize) {
+        let mut action = EventAction::PassThrough;
+        let mut invoked = 0;
+        for hook in hooks.iter() {
+            if hook.paused.load(Ordering::Acquire) || !hook.mask.matches(event.mouse_event()) {
+                continue;
+            }
+            invoked += 1;
+            let result = self.panics.catch(hook.id, || match &hook.handler {
+                Handler::Action(callback) => (callback(event), Propagation::Continue),
+                Handler::Propagate(callback) => (EventAction::PassThrough, callback(event)),
+            });
+            match result {
+                Ok((callback_action, propagation)) => {
+                    if callback_action == EventAction::Consume {
+                        action = EventAction::Consume;
+                    }
+                    if propagation == Propagation::Stop {
+                        break;
+                    }
+                }
+                // The event goes on to the other callbacks as if the callback ignored it
+                Err(unhook) => {
+                    if unhook {
+                        self.remove(hook.id);
+                    }
+                }
+            }
+        }
+        (action, invoked)
+    }
+}
+
+/// The message of a panic, the payloads of `panic!` are either `&str` or `String`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("the callback panicked")
+    }
+}
+
+impl<E: HookEvent> Default for Hooks<E> {
+    fn default() -> Self {
+        Hooks::new()
+    }
+}
+
+/// Dispatches the events of a listener thread from a snapshot of the callbacks,
+/// checking an atomic counter for the changes instead of locking the list
+pub(crate) struct Dispatcher<E> {
+    hooks: Arc<Hooks<E>>,
+    snapshot: Snapshot<E>,
+    generation: usize,
+}
+
+impl<E: HookEvent> Dispatcher<E> {
+    /// Invoke the callbacks like `Hooks::dispatch`, along with how many are invoked
+    pub(crate) fn dispatch(&mut self, event: &E) -> (EventAction, usize) {
+        let generation = self.hooks.generation.load(Ordering::Acquire);
+        if generation != self.generation {
+            self.snapshot = self.hooks.snapshot();
+            self.generation = generation;
+        }
+        self.hooks.dispatch_snapshot(&self.snapshot, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallbackList, Handler, Hooks, PanicReporter, DEFAULT_PRIORITY};
+    use crate::common::{EventAction, EventMask, MouseButton, MouseEvent, Propagation};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn dispatch_in_priority_order() {
+        let hooks = Hooks::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let listen = |name: &'static str, action: EventAction| {
+            let order = order.clone();
+            Handler::Action(Box::new(move |_: &MouseEvent| {
+                order.lock().unwrap().push(name);
+                action
+            }))
+        };
+        hooks.insert(
+            0,
+            DEFAULT_PRIORITY,
+            EventMask::ALL,
+            listen("first", EventAction::PassThrough),
+        );
+        hooks.insert(
+            1,
+            DEFAULT_PRIORITY,
+            EventMask::ALL,
+            listen("second", EventAction::Consume),
+        );
+        hooks.insert(
+            2,
+            10,
+            EventMask::ALL,
+            listen("high", EventAction::PassThrough),
+        );
+        hooks.insert(
+            3,
+            -10,
+            EventMask::PRESS,
+            listen("low", EventAction::PassThrough),
+        );
+
+        let release = MouseEvent::Release(MouseButton::Left);
+        assert_eq!(hooks.dispatch(&release), EventAction::Consume);
+        assert_eq!(*order.lock().unwrap(), vec!["high", "first", "second"]);
+
+        // A callback that handles the presses before the others
+        let stop = order.clone();
+        hooks.insert(
+            4,
+            5,
+            EventMask::ALL,
+            Handler::Propagate(Box::new(move |event: &MouseEvent| {
+                stop.lock().unwrap().push("stop");
+                match event {
+                    MouseEvent::Press(_) => Propagation::Stop,
+                    _ => Propagation::Continue,
+                }
+            })),
+        );
+        order.lock().unwrap().clear();
+        let press = MouseEvent::Press(MouseButton::Left);
+        assert_eq!(hooks.dispatch(&press), EventAction::PassThrough);
+        assert_eq!(*order.lock().unwrap(), vec!["high", "stop"]);
+
+        assert!(hooks.remove(4));
+        assert!(!hooks.remove(4));
+        order.lock().unwrap().clear();
+        assert_eq!(hooks.dispatch(&press), EventAction::Consume);
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["high", "first", "second", "low"]
+        );
+    }
+
+    #[test]
+    fn skip_paused() {
+        let hooks = Hooks::new();
+        let received = Arc::new(Mutex::new(0));
+        let counter = received.clone();
+        hooks.insert(
+            0,
+            DEFAULT_PRIORITY,
+            EventMask::ALL,
+            Handler::Action(Box::new(move |_: &MouseEvent| {
+                *counter.lock().unwrap() += 1;
+                EventAction::Consume
+            })),
+        );
+        let press = MouseEvent::Press(MouseButton::Left);
+
+        assert!(hooks.set_paused(0, true));
+        assert_eq!(hooks.dispatch(&press), EventAction::PassThrough);
+        assert_eq!(*received.lock().unwrap(), 0);
+
+        assert!(hooks.set_paused(0, false));
+        assert_eq!(hooks.dispatch(&press), EventAction::Consume);
+        assert_eq!(*received.lock().unwrap(), 1);
+        assert!(!hooks.set_paused(1, true));
+    }
+
+    #[test]
+    fn dispatcher_follows_changes() {
+        let hooks = Arc::new(Hooks::new());
+        let mut dispatcher = hooks.dispatcher();
+        let press = MouseEvent::Press(MouseButton::Left);
+        assert_eq!(dispatcher.dispatch(&press).0, EventAction::PassThrough);
+
+        hooks.insert(
+            0,
+            DEFAULT_PRIORITY,
+            EventMask::ALL,
+            Handler::Action(Box::new(|_: &MouseEvent| EventAction::Consume)),
+        );
+        assert_eq!(dispatcher.dispatch(&press), (EventAction::Consume, 1));
+        hooks.set_paused(0, true);
+        assert_eq!(dispatcher.dispatch(&press), (EventAction::PassThrough, 0));
+        hooks.set_paused(0, false);
+        assert_eq!(dispatcher.dispatch(&press).0, EventAction::Consume);
+        assert!(hooks.contains(0));
+        hooks.clear();
+        assert_eq!(dispatcher.dispatch(&press).0, EventAction::PassThrough);
+        assert!(!hooks.contains(0) && hooks.is_empty());
+    }
+
+    #[test]
+    fn remove_panicking_callbacks() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reported = reports.clone();
+        let panics = PanicReporter::default();
+        panics.set(
+            Box::new(move |report| reported.lock().unwrap().push(report.callback_id)),
+            true,
+        );
+        let list: CallbackList<dyn Fn() + Send> = CallbackList::new(panics);
+        let invoked = Arc::new(Mutex::new(0));
+        let counter = invoked.clone();
+        list.push(0, Box::new(|| panic!("the callback failed")));
+        list.push(1, Box::new(move || *counter.lock().unwrap() += 1));
+
+        list.invoke(|callback| callback());
+        list.invoke(|callback| callback());
+        assert_eq!(*reports.lock().unwrap(), vec![0]);
+        assert_eq!(*invoked.lock().unwrap(), 2);
+        assert!(!list.remove(0));
+        assert!(list.remove(1));
+    }
+
+    #[test]
+    fn change_the_list_from_a_callback() {
+        let list: Arc<CallbackList<dyn Fn() + Send>> =
+            Arc::new(CallbackList::new(PanicReporter::default()));
+        let shared = list.clone();
+        // The list is not locked while the callbacks run
+        list.push(
+            0,
+            Box::new(move || {
+                shared.remove(0);
+                shared.push(1, Box::new(|| {}));
+            }),
+        );
+        list.invoke(|callback| callback());
+        assert!(!list.remove(0));
+        assert!(list.remove(1));
+    }
+}
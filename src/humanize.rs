@@ -0,0 +1,700 @@
+///
+/// This module contains a wrapper of the mouse managers that moves the mouse
+/// like a hand does, instead of jumping to the target at once
+///
+/// The moves take a while that depends on their distance, their speed varies
+/// from move to move, the path trembles a little and the long moves overshoot
+/// the target before correcting back to it
+///
+use crate::cancel::CancellationToken;
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, CoordinateSpace, Easing,
+    EventAction, EventMask, EventWithTime, ListenerStats, MotionProfile, MouseActions, MouseButton,
+    MouseController, MouseEvent, MouseEventExt, MouseListener, Propagation, ScrollDirection,
+    ScrollPhase,
+};
+use crate::devices::MouseDeviceInfo;
+use crate::display::Rect;
+use crate::error::Error;
+use crate::keyboard::Modifiers;
+use std::{
+    cell::Cell,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The interval between the steps of a humanized move
+const HUMAN_INTERVAL: Duration = Duration::from_millis(8);
+/// The moves that are shorter than this many pixels do not overshoot
+const OVERSHOOT_MIN_DISTANCE: f64 = 50.;
+
+/// How the moves of a `Humanize` deviate from the straight and instant moves
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::humanize::HumanProfile;
+///
+/// // A slow and shaky hand
+/// let profile = HumanProfile::new().speed(600.).jitter(3.);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HumanProfile {
+    speed: f64,
+    speed_variance: f64,
+    overshoot: f64,
+    jitter: f64,
+}
+
+impl HumanProfile {
+    /// The default profile, 1200 pixels per second varied by 25%, overshooting
+    /// by 5% of the distance and trembling by 1.5 pixels
+    pub fn new() -> Self {
+        HumanProfile {
+            speed: 1200.,
+            speed_variance: 0.25,
+            overshoot: 0.05,
+            jitter: 1.5,
+        }
+    }
+
+    /// The average speed of the moves in pixels per second
+    pub fn speed(mut self, pixels_per_second: f64) -> Self {
+        self.speed = pixels_per_second.max(1.);
+        self
+    }
+
+    /// How much the speed of each move varies from the average, as a fraction of it
+    pub fn speed_variance(mut self, variance: f64) -> Self {
+        self.speed_variance = variance.clamp(0., 0.9);
+        self
+    }
+
+    /// How far the long moves go past the target at most, as a fraction of their
+    /// distance, before correcting back. Zero disables the overshoots
+    pub fn overshoot(mut self, fraction: f64) -> Self {
+        self.overshoot = fraction.max(0.);
+        self
+    }
+
+    /// How many pixels the intermediate points deviate from the path at most,
+    /// the target itself is always reached exactly
+    pub fn jitter(mut self, pixels: f64) -> Self {
+        self.jitter = pixels.max(0.);
+        self
+    }
+}
+
+impl Default for HumanProfile {
+    fn default() -> Self {
+        HumanProfile::new()
+    }
+}
+
+/// A small xorshift generator, the moves only need to look random
+struct Rng(Cell<u64>);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state of xorshift must not be zero
+        Rng(Cell::new(seed | 1))
+    }
+
+    fn next(&self) -> u64 {
+        let mut state = self.0.get();
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        self.0.set(state);
+        state
+    }
+
+    /// A number between 0 and 1
+    fn unit(&self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number between -1 and 1
+    fn signed(&self) -> f64 {
+        self.unit() * 2. - 1.
+    }
+}
+
+/// A mouse manager that moves like a hand with `move_to` and `move_relative`,
+/// the other actions are passed to the wrapped manager as they are
+///
+/// `move_to` needs the current position of the mouse, so it moves at once where
+/// `get_position` is not supported. `move_to_smooth` trembles and overshoots
+/// like `move_to` but takes the duration and the easing that it is asked for,
+/// `drag_to` keeps the timing that it is asked for
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::humanize::{HumanProfile, Humanize};
+/// use mouce::common::MouseButton;
+/// use mouce::{Mouse, MouseController};
+///
+/// # #[cfg(target_os = "linux")]
+/// let manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let manager = Mouse::new().unwrap();
+/// let mut mouse = Humanize::new(manager, HumanProfile::new());
+/// mouse.move_to(960, 540).unwrap();
+/// mouse.click_at(100, 100, &MouseButton::Left).unwrap();
+/// ```
+pub struct Humanize {
+    manager: Box<dyn MouseActions + Send>,
+    profile: HumanProfile,
+    rng: Rng,
+}
+
+impl Humanize {
+    /// Wrap the given manager, e.g. the one that `Mouse::new` creates
+    pub fn new(manager: Box<dyn MouseActions + Send>, profile: HumanProfile) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        Humanize::with_seed(manager, profile, seed)
+    }
+
+    /// Wrap the given manager with a fixed seed, so the same calls make the same moves
+    pub fn with_seed(
+        manager: Box<dyn MouseActions + Send>,
+        profile: HumanProfile,
+        seed: u64,
+    ) -> Self {
+        Humanize {
+            manager,
+            profile,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Get the profile of the moves
+    pub fn profile(&self) -> HumanProfile {
+        self.profile
+    }
+
+    /// Set the profile of the next moves
+    pub fn set_profile(&mut self, profile: HumanProfile) {
+        self.profile = profile;
+    }
+
+    /// Take the wrapped manager back
+    pub fn into_inner(self) -> Box<dyn MouseActions + Send> {
+        self.manager
+    }
+}
+
+/// The points of a humanized move by the given offset, relative to its start,
+/// the last one is the offset itself
+fn human_path(offset: (i32, i32), profile: &HumanProfile, rng: &Rng) -> Vec<(i32, i32)> {
+    let distance = (offset.0 as f64).hypot(offset.1 as f64);
+    let speed = profile.speed * (1. + profile.speed_variance * rng.signed());
+    let steps = ((distance / speed) / HUMAN_INTERVAL.as_secs_f64())
+        .ceil()
+        .max(1.) as usize;
+    human_stroke(offset, profile, rng, steps, &Easing::EaseInOut)
+}
+
+/// The points of a humanized move by the given offset that takes the given number
+/// of steps to the target, and follows the given easing on the way there
+fn human_stroke(
+    offset: (i32, i32),
+    profile: &HumanProfile,
+    rng: &Rng,
+    steps: usize,
+    easing: &Easing,
+) -> Vec<(i32, i32)> {
+    let (delta_x, delta_y) = (offset.0 as f64, offset.1 as f64);
+    let distance = delta_x.hypot(delta_y);
+    if distance == 0. {
+        return vec![offset];
+    }
+    let (direction_x, direction_y) = (delta_x / distance, delta_y / distance);
+    let steps = steps.max(1);
+
+    // Aim past the target on the long moves
+    let overshoot = if profile.overshoot > 0. && distance >= OVERSHOOT_MIN_DISTANCE {
+        distance * profile.overshoot * (0.5 + rng.unit() / 2.)
+    } else {
+        0.
+    };
+    let aim = (
+        delta_x + direction_x * overshoot,
+        delta_y + direction_y * overshoot,
+    );
+
+    let mut path = Vec::with_capacity(steps + 1);
+    for step in 1..=steps {
+        let progress = easing.apply(step as f64 / steps as f64);
+        // Tremble across the path, but not at the end of the stroke
+        let tremble = if step == steps {
+            0.
+        } else {
+            profile.jitter * rng.signed()
+        };
+        path.push((
+            (aim.0 * progress - direction_y * tremble).round() as i32,
+            (aim.1 * progress + direction_x * tremble).round() as i32,
+        ));
+    }
+
+    if overshoot > 0. {
+        let steps = (steps / 4).max(2);
+        for step in 1..=steps {
+            let progress = Easing::EaseInOut.apply(step as f64 / steps as f64);
+            path.push((
+                (aim.0 + (delta_x - aim.0) * progress).round() as i32,
+                (aim.1 + (delta_y - aim.1) * progress).round() as i32,
+            ));
+        }
+    }
+
+    if let Some(last) = path.last_mut() {
+        *last = offset;
+    }
+    path
+}
+
+/// Implement the actions for both receivers of `MouseController`,
+/// the platforms differ in whether the actions take `&mut self`
+macro_rules! humanize_actions {
+    ($($mutability:tt)?) => {
+        impl Humanize {
+            /// Move like `move_to`, but stop between the steps once the given
+            /// token is cancelled and fail with `Error::Cancelled`
+            pub fn move_to_cancellable(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                token.check()?;
+                let start = match self.manager.get_position() {
+                    Ok(start) => start,
+                    Err(_) => return self.manager.move_to(x, y),
+                };
+                let offset = (x as i32 - start.0, y as i32 - start.1);
+                let path = human_path(offset, &self.profile, &self.rng);
+                self.follow(start, &path, HUMAN_INTERVAL, token)
+            }
+
+            /// Move like `move_relative`, but stop between the steps once the
+            /// given token is cancelled and fail with `Error::Cancelled`
+            pub fn move_relative_cancellable(
+                &$($mutability)? self,
+                x_offset: i32,
+                y_offset: i32,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                token.check()?;
+                let path = human_path((x_offset, y_offset), &self.profile, &self.rng);
+                let mut previous = (0, 0);
+                for (i, point) in path.iter().enumerate() {
+                    if i != 0 {
+                        token.sleep(HUMAN_INTERVAL)?;
+                    }
+                    let delta = (point.0 - previous.0, point.1 - previous.1);
+                    if delta != (0, 0) {
+                        self.manager.move_relative(delta.0, delta.1)?;
+                    }
+                    previous = *point;
+                }
+                Ok(())
+            }
+
+            /// Move to the points of the path relative to the start, `interval` apart
+            fn follow(
+                &$($mutability)? self,
+                start: (i32, i32),
+                path: &[(i32, i32)],
+                interval: Duration,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                for (i, point) in path.iter().enumerate() {
+                    if i != 0 {
+                        token.sleep(interval)?;
+                    }
+                    self.manager.move_to(
+                        (start.0 + point.0).max(0) as usize,
+                        (start.1 + point.1).max(0) as usize,
+                    )?;
+                }
+                Ok(())
+            }
+        }
+
+        impl MouseController for Humanize {
+            fn move_to(&$($mutability)? self, x: usize, y: usize) -> Result<(), Error> {
+                self.move_to_cancellable(x, y, &CancellationToken::new())
+            }
+
+            fn move_relative(
+                &$($mutability)? self,
+                x_offset: i32,
+                y_offset: i32,
+            ) -> Result<(), Error> {
+                self.move_relative_cancellable(x_offset, y_offset, &CancellationToken::new())
+            }
+
+            fn move_to_smooth(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                duration: Duration,
+                easing: &Easing,
+            ) -> Result<(), Error> {
+                self.move_to_smooth_cancellable(x, y, duration, easing, &CancellationToken::new())
+            }
+
+            fn move_to_smooth_cancellable(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                duration: Duration,
+                easing: &Easing,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                token.check()?;
+                let start = match self.manager.get_position() {
+                    Ok(start) => start,
+                    Err(_) => {
+                        return self
+                            .manager
+                            .move_to_smooth_cancellable(x, y, duration, easing, token)
+                    }
+                };
+                let offset = (x as i32 - start.0, y as i32 - start.1);
+                let steps = (duration.as_secs_f64() / HUMAN_INTERVAL.as_secs_f64()).ceil() as usize;
+                let path = human_stroke(offset, &self.profile, &self.rng, steps, easing);
+                // The correction of an overshoot fits in the duration as well
+                let interval = duration / path.len() as u32;
+                self.follow(start, &path, interval, token)
+            }
+
+            fn move_to_virtual(&$($mutability)? self, x: i32, y: i32) -> Result<(), Error> {
+                // The managers that know the layout reach the monitors at negative positions
+                self.manager.move_to_virtual(x, y)
+            }
+
+            fn get_position(&self) -> Result<(i32, i32), Error> {
+                self.manager.get_position()
+            }
+
+            fn backend(&self) -> Backend {
+                self.manager.backend()
+            }
+
+            fn supports(&self, capability: Capability) -> bool {
+                self.manager.supports(capability)
+            }
+
+            fn get_button_state(&self) -> Result<ButtonState, Error> {
+                self.manager.get_button_state()
+            }
+
+            fn set_coordinate_space(&mut self, space: CoordinateSpace) -> Result<(), Error> {
+                self.manager.set_coordinate_space(space)
+            }
+
+            fn set_motion_profile(&mut self, profile: MotionProfile) -> Result<(), Error> {
+                self.manager.set_motion_profile(profile)
+            }
+
+            fn press_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.manager.press_button(button)
+            }
+
+            fn release_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.manager.release_button(button)
+            }
+
+            fn click_with_modifiers(
+                &$($mutability)? self,
+                button: &MouseButton,
+                modifiers: Modifiers,
+            ) -> Result<(), Error> {
+                // The manager keeps its keyboard between the clicks
+                self.manager.click_with_modifiers(button, modifiers)
+            }
+
+            fn drag_to(
+                &$($mutability)? self,
+                button: &MouseButton,
+                from: (i32, i32),
+                to: (i32, i32),
+                step_delay: Duration,
+            ) -> Result<(), Error> {
+                self.manager.drag_to(button, from, to, step_delay)
+            }
+
+            fn drag_to_cancellable(
+                &$($mutability)? self,
+                button: &MouseButton,
+                from: (i32, i32),
+                to: (i32, i32),
+                step_delay: Duration,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                self.manager.drag_to_cancellable(button, from, to, step_delay, token)
+            }
+
+            fn scroll_wheel(
+                &$($mutability)? self,
+                direction: &ScrollDirection,
+            ) -> Result<(), Error> {
+                self.manager.scroll_wheel(direction)
+            }
+
+            fn scroll(&$($mutability)? self, dx: i32, dy: i32) -> Result<(), Error> {
+                self.manager.scroll(dx, dy)
+            }
+
+            fn scroll_wheel_hi_res(&$($mutability)? self, dx: i32, dy: i32) -> Result<(), Error> {
+                self.manager.scroll_wheel_hi_res(dx, dy)
+            }
+
+            fn scroll_smooth(
+                &$($mutability)? self,
+                dx: f64,
+                dy: f64,
+                phase: Option<ScrollPhase>,
+            ) -> Result<(), Error> {
+                self.manager.scroll_smooth(dx, dy, phase)
+            }
+
+            fn confine_cursor(&$($mutability)? self, rect: Rect) -> Result<(), Error> {
+                self.manager.confine_cursor(rect)
+            }
+
+            fn release_cursor(&$($mutability)? self) -> Result<(), Error> {
+                self.manager.release_cursor()
+            }
+
+            fn set_cursor_visible(&$($mutability)? self, visible: bool) -> Result<(), Error> {
+                self.manager.set_cursor_visible(visible)
+            }
+        }
+
+        impl MouseListener for Humanize {
+            fn hook(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.hook(callback)
+            }
+
+            fn hook_with_time(
+                &mut self,
+                callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.hook_with_time(callback)
+            }
+
+            fn hook_ext(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.hook_ext(callback)
+            }
+
+            fn hook_filtered(
+                &mut self,
+                mask: EventMask,
+                callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.hook_filtered(mask, callback)
+            }
+
+            fn hook_grab(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEvent) -> EventAction + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.hook_grab(callback)
+            }
+
+            fn hook_with_priority(
+                &mut self,
+                priority: i32,
+                callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.hook_with_priority(priority, callback)
+            }
+
+            fn hook_device(
+                &mut self,
+                device: &MouseDeviceInfo,
+                callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.hook_device(device, callback)
+            }
+
+            fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+                self.manager.inject_event(event)
+            }
+
+            fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                self.manager.unhook(callback_id)
+            }
+
+            fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                self.manager.pause_hook(callback_id)
+            }
+
+            fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                self.manager.resume_hook(callback_id)
+            }
+
+            fn unhook_all(&mut self) -> Result<(), Error> {
+                self.manager.unhook_all()
+            }
+
+            fn stop_listening(&mut self) -> Result<(), Error> {
+                self.manager.stop_listening()
+            }
+
+            fn listener_stats(&self) -> Result<ListenerStats, Error> {
+                self.manager.listener_stats()
+            }
+
+            fn on_listener_restarted(
+                &mut self,
+                callback: Box<dyn Fn() + Send>,
+            ) -> Result<CallbackId, Error> {
+                self.manager.on_listener_restarted(callback)
+            }
+
+            fn on_callback_panic(
+                &mut self,
+                unhook: bool,
+                handler: Box<dyn Fn(&CallbackPanic) + Send>,
+            ) -> Result<(), Error> {
+                self.manager.on_callback_panic(unhook, handler)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+humanize_actions!(mut);
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+humanize_actions!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reach_the_target() {
+        let rng = Rng::new(7);
+        let profile = HumanProfile::new().jitter(4.);
+        for offset in [(0, 0), (3, -2), (400, 0), (-250, 600)] {
+            let path = human_path(offset, &profile, &rng);
+            assert_eq!(path.last(), Some(&offset));
+        }
+    }
+
+    #[test]
+    fn steady_paths() {
+        let rng = Rng::new(7);
+        let profile = HumanProfile::new()
+            .speed(1000.)
+            .speed_variance(0.)
+            .overshoot(0.)
+            .jitter(0.);
+        // 40 pixels at 1000 pixels per second take 5 steps
+        let path = human_path((40, 0), &profile, &rng);
+        assert_eq!(path.len(), 5);
+        assert!(path.iter().all(|point| point.1 == 0));
+        assert!(path.windows(2).all(|points| points[0].0 <= points[1].0));
+    }
+
+    #[test]
+    fn overshoot_long_moves() {
+        let rng = Rng::new(7);
+        let profile = HumanProfile::new().overshoot(0.2).jitter(0.);
+        let path = human_path((0, 500), &profile, &rng);
+        assert!(path.iter().any(|point| point.1 > 500));
+        assert_eq!(path.last(), Some(&(0, 500)));
+
+        let path = human_path((0, 20), &profile, &rng);
+        assert!(path.iter().all(|point| point.1 <= 20));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn wrap_a_manager() {
+        use crate::mock::MockMouseManager;
+
+        let mut mouse = Humanize::with_seed(
+            Box::new(MockMouseManager::new()),
+            HumanProfile::new().speed(10000.),
+            3,
+        );
+        mouse.move_to(200, 100).unwrap();
+        assert_eq!(mouse.get_position(), Ok((200, 100)));
+        mouse.move_relative(-50, 25).unwrap();
+        assert_eq!(mouse.get_position(), Ok((150, 125)));
+        assert_eq!(mouse.backend(), Backend::Mock);
+    }
+
+    #[test]
+    fn timed_strokes() {
+        let rng = Rng::new(7);
+        let profile = HumanProfile::new().overshoot(0.2).jitter(0.);
+        // The stroke takes the given steps, then corrects the overshoot
+        let path = human_stroke((0, 400), &profile, &rng, 10, &Easing::Linear);
+        assert_eq!(path.len(), 10 + 2);
+        assert!(path[9].1 > 400);
+        assert_eq!(path.last(), Some(&(0, 400)));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn humanize_smooth_moves() {
+        use crate::mock::MockMouseManager;
+
+        let mut mouse = Humanize::with_seed(
+            Box::new(MockMouseManager::new()),
+            HumanProfile::new().jitter(3.),
+            3,
+        );
+        mouse
+            .move_to_smooth(0, 400, Duration::from_millis(40), &Easing::Linear)
+            .unwrap();
+        assert_eq!(mouse.get_position(), Ok((0, 400)));
+
+        // The moves that start with a cancelled token do nothing
+        let token = CancellationToken::new();
+        token.cancel();
+        assert_eq!(
+            mouse.move_to_cancellable(100, 100, &token),
+            Err(Error::Cancelled)
+        );
+        assert_eq!(
+            mouse.move_relative_cancellable(100, 100, &token),
+            Err(Error::Cancelled)
+        );
+        assert_eq!(
+            mouse.move_to_smooth_cancellable(100, 100, Duration::ZERO, &Easing::Linear, &token),
+            Err(Error::Cancelled)
+        );
+        assert_eq!(mouse.get_position(), Ok((0, 400)));
+    }
+}
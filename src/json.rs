@@ -0,0 +1,444 @@
+///
+/// This module contains a minimal JSON reader and writer
+/// that is used to store the mouse events
+///
+use crate::common::{MouseButton, MouseEvent, ScrollDirection, ScrollPhase};
+use std::{
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Get the value of the given key if this is an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the given JSON text, the error contains the byte position of the failure
+pub fn parse(input: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        bytes: input.as_bytes(),
+        position: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position != parser.bytes.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Quote and escape the given string
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Write the fields that describe the given event, without the surrounding braces
+/// so that the callers can add their own fields, e.g. `"type":"press","button":"left"`
+pub fn event_fields(event: &MouseEvent) -> String {
+    match event {
+        MouseEvent::RelativeMove(x, y) => {
+            format!("\"type\":\"relative_move\",\"x\":{},\"y\":{}", x, y)
+        }
+        MouseEvent::AbsoluteMove(x, y) => {
+            format!("\"type\":\"absolute_move\",\"x\":{},\"y\":{}", x, y)
+        }
+        MouseEvent::Press(button) => format!(
+            "\"type\":\"press\",\"button\":{}",
+            quote(&button.to_string())
+        ),
+        MouseEvent::Release(button) => {
+            format!(
+                "\"type\":\"release\",\"button\":{}",
+                quote(&button.to_string())
+            )
+        }
+        MouseEvent::Scroll(direction) => {
+            format!(
+                "\"type\":\"scroll\",\"direction\":{}",
+                quote(&direction.to_string())
+            )
+        }
+        MouseEvent::ScrollHiRes { dx, dy } => {
+            format!("\"type\":\"scroll_hi_res\",\"dx\":{},\"dy\":{}", dx, dy)
+        }
+        MouseEvent::Scroll2D { dx, dy } => {
+            format!("\"type\":\"scroll_2d\",\"dx\":{},\"dy\":{}", dx, dy)
+        }
+        MouseEvent::ScrollPhase(phase) => {
+            format!(
+                "\"type\":\"scroll_phase\",\"phase\":{}",
+                quote(&phase.to_string())
+            )
+        }
+        MouseEvent::DeviceDisconnected(device_id) => {
+            format!(
+                "\"type\":\"device_disconnected\",\"device_id\":{}",
+                quote(device_id)
+            )
+        }
+        MouseEvent::Click(button) => format!(
+            "\"type\":\"click\",\"button\":{}",
+            quote(&button.to_string())
+        ),
+        MouseEvent::DoubleClick(button) => format!(
+            "\"type\":\"double_click\",\"button\":{}",
+            quote(&button.to_string())
+        ),
+        MouseEvent::TripleClick(button) => format!(
+            "\"type\":\"triple_click\",\"button\":{}",
+            quote(&button.to_string())
+        ),
+    }
+}
+
+/// Write a line of `hook_json`, the time of the event in microseconds since the
+/// unix epoch, the id and the name of its device if they are known and its fields
+pub fn event_line(event: &MouseEvent, time: SystemTime, device: Option<(&str, &str)>) -> String {
+    let time = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_micros());
+    let (device_id, device_name) = match device {
+        Some((id, name)) => (quote(id), quote(name)),
+        None => ("null".to_string(), "null".to_string()),
+    };
+    format!(
+        "{{\"time\":{},\"device_id\":{},\"device_name\":{},{}}}",
+        time,
+        device_id,
+        device_name,
+        event_fields(event)
+    )
+}
+
+/// Construct the event from an object that has the fields written by `event_fields`
+pub fn event_from_object(object: &Value) -> Result<MouseEvent, String> {
+    let field = |key: &str| object.get(key).ok_or(format!("missing field `{}`", key));
+    let number = |key: &str| {
+        field(key)?
+            .as_f64()
+            .map(|value| value as i32)
+            .ok_or(format!("field `{}` is not a number", key))
+    };
+    let string = |key: &str| {
+        field(key)?
+            .as_str()
+            .ok_or(format!("field `{}` is not a string", key))
+    };
+
+    let button = |name: &str| MouseButton::from_str(name).map_err(|err| err.to_string());
+
+    match string("type")? {
+        "relative_move" => Ok(MouseEvent::RelativeMove(number("x")?, number("y")?)),
+        "absolute_move" => Ok(MouseEvent::AbsoluteMove(number("x")?, number("y")?)),
+        "press" => Ok(MouseEvent::Press(button(string("button")?)?)),
+        "release" => Ok(MouseEvent::Release(button(string("button")?)?)),
+        "scroll" => Ok(MouseEvent::Scroll(
+            ScrollDirection::from_str(string("direction")?).map_err(|err| err.to_string())?,
+        )),
+        "scroll_hi_res" => Ok(MouseEvent::ScrollHiRes {
+            dx: number("dx")?,
+            dy: number("dy")?,
+        }),
+        "scroll_2d" => Ok(MouseEvent::Scroll2D {
+            dx: number("dx")?,
+            dy: number("dy")?,
+        }),
+        "scroll_phase" => Ok(MouseEvent::ScrollPhase(
+            ScrollPhase::from_str(string("phase")?).map_err(|err| err.to_string())?,
+        )),
+        "device_disconnected" => Ok(MouseEvent::DeviceDisconnected(
+            string("device_id")?.to_string(),
+        )),
+        "click" => Ok(MouseEvent::Click(button(string("button")?)?)),
+        "double_click" => Ok(MouseEvent::DoubleClick(button(string("button")?)?)),
+        "triple_click" => Ok(MouseEvent::TripleClick(button(string("button")?)?)),
+        other => Err(format!("unknown event type `{}`", other)),
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", byte as char)))
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&b'}') {
+            self.position += 1;
+            return Ok(Value::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(b':')?;
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(fields));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&b']') {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut value = String::new();
+        loop {
+            let start = self.position;
+            // Copy the unescaped part as a whole to keep the utf-8 characters intact
+            while let Some(byte) = self.bytes.get(self.position) {
+                if *byte == b'"' || *byte == b'\\' {
+                    break;
+                }
+                self.position += 1;
+            }
+            value.push_str(
+                std::str::from_utf8(&self.bytes[start..self.position])
+                    .map_err(|_| self.error("invalid utf-8"))?,
+            );
+
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(value);
+                }
+                Some(b'\\') => {
+                    let escaped = match self.bytes.get(self.position + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'u') => {
+                            let code = self
+                                .bytes
+                                .get(self.position + 2..self.position + 6)
+                                .and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.position += 4;
+                            code
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    value.push(escaped);
+                    self.position += 2;
+                }
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') =
+            self.bytes.get(self.position)
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{event_fields, event_from_object, event_line, parse, quote, Value};
+    use crate::common::{MouseButton, MouseEvent, ScrollDirection};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn parse_values() {
+        let value = parse(r#" {"a": [1, -2.5, true, null], "b": "x\"Ay"} "#).unwrap();
+        assert_eq!(
+            value,
+            Value::Object(vec![
+                (
+                    "a".to_string(),
+                    Value::Array(vec![
+                        Value::Number(1.),
+                        Value::Number(-2.5),
+                        Value::Bool(true),
+                        Value::Null,
+                    ])
+                ),
+                ("b".to_string(), Value::String("x\"Ay".to_string())),
+            ])
+        );
+        assert!(parse("{\"a\": }").is_err());
+        assert!(parse("[1, 2").is_err());
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        let text = "a \"quoted\"\n\\ text";
+        assert_eq!(
+            parse(&quote(text)).unwrap(),
+            Value::String(text.to_string())
+        );
+    }
+
+    #[test]
+    fn event_round_trip() {
+        let events = [
+            MouseEvent::RelativeMove(-3, 7),
+            MouseEvent::AbsoluteMove(100, 200),
+            MouseEvent::Press(MouseButton::Left),
+            MouseEvent::Release(MouseButton::Side),
+            MouseEvent::Scroll(ScrollDirection::Down),
+        ];
+        for event in events {
+            let object = parse(&format!("{{{}}}", event_fields(&event))).unwrap();
+            assert_eq!(event_from_object(&object).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn event_lines() {
+        let time = UNIX_EPOCH + Duration::from_micros(1_500_000);
+        let event = MouseEvent::Press(MouseButton::Left);
+        assert_eq!(
+            event_line(&event, time, Some(("/dev/input/event3", "USB \"Mouse\""))),
+            r#"{"time":1500000,"device_id":"/dev/input/event3","device_name":"USB \"Mouse\"","type":"press","button":"left"}"#
+        );
+        let line = event_line(&MouseEvent::RelativeMove(-2, 3), time, None);
+        let object = parse(&line).unwrap();
+        assert_eq!(object.get("device_id"), Some(&Value::Null));
+        assert_eq!(
+            event_from_object(&object).unwrap(),
+            MouseEvent::RelativeMove(-2, 3)
+        );
+    }
+}
@@ -0,0 +1,430 @@
+///
+/// This module contains the keyboard actions that are
+/// shared by the keyboard managers of all the platforms
+///
+use crate::cancel::CancellationToken;
+use crate::error::Error;
+use crate::hooks::LockUnpoisoned;
+use std::{
+    ops::{BitOr, BitOrAssign},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The interval between the keys of `type_string`
+const TYPING_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The keys of a US layout keyboard
+///
+/// `Char` is used for the keys that type a character without shift,
+/// the lowercase letters, the digits and the punctuation e.g. `Key::Char('a')`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+    Space,
+    Shift,
+    Control,
+    Alt,
+    Meta,
+    CapsLock,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// The function keys from F1 to F12
+    F(u8),
+}
+
+/// A set of the modifier keys, e.g. to hold them during `click_with_modifiers`
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::keyboard::{Key, Modifiers};
+///
+/// let modifiers = Modifiers::CONTROL | Modifiers::SHIFT;
+/// assert!(modifiers.contains(Modifiers::SHIFT));
+/// assert_eq!(modifiers.keys(), vec![Key::Shift, Key::Control]);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const SHIFT: Modifiers = Modifiers(1 << 0);
+    pub const CONTROL: Modifiers = Modifiers(1 << 1);
+    pub const ALT: Modifiers = Modifiers(1 << 2);
+    /// The command key on macos and the windows key on the others
+    pub const META: Modifiers = Modifiers(1 << 3);
+
+    /// A set that does not contain any modifier
+    pub const fn empty() -> Self {
+        Modifiers(0)
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all the modifiers of `other` are in this set
+    pub const fn contains(&self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Get the keys of the modifiers in this set, in the order they are pressed
+    pub fn keys(&self) -> Vec<Key> {
+        [
+            (Modifiers::SHIFT, Key::Shift),
+            (Modifiers::CONTROL, Key::Control),
+            (Modifiers::ALT, Key::Alt),
+            (Modifiers::META, Key::Meta),
+        ]
+        .into_iter()
+        .filter(|(modifier, _)| self.contains(*modifier))
+        .map(|(_, key)| key)
+        .collect()
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, rhs: Modifiers) -> Modifiers {
+        Modifiers(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, rhs: Modifiers) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The keyboard manager that holds the modifier keys of `click_with_modifiers`, it is
+/// created on the first use and kept by the mouse manager, so that every click does
+/// not connect to the X server or create a uinput device again
+#[derive(Clone, Default)]
+pub(crate) struct ModifierKeys {
+    keyboard: Arc<Mutex<Option<Box<dyn KeyboardActions + Send>>>>,
+}
+
+impl ModifierKeys {
+    /// Run the given action while the given modifiers are held with the keyboard
+    /// manager of the platform, the modifiers are released even if the action fails
+    pub(crate) fn hold(
+        &self,
+        modifiers: Modifiers,
+        action: impl FnOnce() -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.hold_with(crate::Keyboard::new, modifiers, action)
+    }
+
+    /// Like `hold`, the keyboard manager is created with the given function
+    fn hold_with(
+        &self,
+        create: impl FnOnce() -> Result<Box<dyn KeyboardActions + Send>, Error>,
+        modifiers: Modifiers,
+        action: impl FnOnce() -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if modifiers.is_empty() {
+            return action();
+        }
+        let mut keyboard = self.keyboard.lock_unpoisoned();
+        let keyboard = match &mut *keyboard {
+            Some(keyboard) => keyboard,
+            empty => empty.insert(create()?),
+        };
+        with_keys_held(keyboard.as_mut(), &modifiers.keys(), |_| action())
+    }
+}
+
+/// Run the given action while the given keys are held, the keys that are
+/// pressed are released in the reverse order even if the action fails
+fn with_keys_held<K: KeyboardActions + ?Sized>(
+    keyboard: &mut K,
+    keys: &[Key],
+    action: impl FnOnce(&mut K) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut pressed = Vec::new();
+    let mut result = Ok(());
+    for key in keys {
+        result = keyboard.press_key(key);
+        if result.is_err() {
+            break;
+        }
+        pressed.push(key);
+    }
+    if result.is_ok() {
+        result = action(keyboard);
+    }
+    for key in pressed.into_iter().rev() {
+        let released = keyboard.release_key(key);
+        if result.is_ok() {
+            result = released;
+        }
+    }
+    result
+}
+
+/// The characters that are typed with shift, along with the characters of the same keys
+const SHIFTED: [(char, char); 21] = [
+    ('!', '1'),
+    ('@', '2'),
+    ('#', '3'),
+    ('$', '4'),
+    ('%', '5'),
+    ('^', '6'),
+    ('&', '7'),
+    ('*', '8'),
+    ('(', '9'),
+    (')', '0'),
+    ('_', '-'),
+    ('+', '='),
+    ('{', '['),
+    ('}', ']'),
+    ('|', '\\'),
+    (':', ';'),
+    ('"', '\''),
+    ('~', '`'),
+    ('<', ','),
+    ('>', '.'),
+    ('?', '/'),
+];
+
+impl Key {
+    /// Get the key that types the given character on a US layout keyboard,
+    /// and whether shift needs to be held while typing it
+    pub fn from_char(ch: char) -> Option<(Key, bool)> {
+        match ch {
+            '\n' => Some((Key::Enter, false)),
+            '\t' => Some((Key::Tab, false)),
+            ' ' => Some((Key::Space, false)),
+            'A'..='Z' => Some((Key::Char(ch.to_ascii_lowercase()), true)),
+            'a'..='z' | '0'..='9' => Some((Key::Char(ch), false)),
+            _ => {
+                if SHIFTED.iter().any(|(_, base)| *base == ch) {
+                    return Some((Key::Char(ch), false));
+                }
+                SHIFTED
+                    .iter()
+                    .find(|(shifted, _)| *shifted == ch)
+                    .map(|(_, base)| (Key::Char(*base), true))
+            }
+        }
+    }
+}
+
+pub trait KeyboardActions {
+    /// Press the given key
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::keyboard::Key;
+    /// use mouce::Keyboard;
+    ///
+    /// let mut manager = Keyboard::new().unwrap();
+    /// assert_eq!(manager.press_key(&Key::Shift), Ok(()));
+    /// assert_eq!(manager.release_key(&Key::Shift), Ok(()));
+    /// ```
+    fn press_key(&mut self, key: &Key) -> Result<(), Error>;
+    /// Release the given key
+    fn release_key(&mut self, key: &Key) -> Result<(), Error>;
+    /// Press and release the given key
+    fn click_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.press_key(key)?;
+        self.release_key(key)
+    }
+    /// Type the given text as if it is typed on a US layout keyboard
+    ///
+    /// Returns an error without typing anything if the text contains
+    /// a character that can not be typed e.g. a non-ASCII character
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::Keyboard;
+    ///
+    /// let mut manager = Keyboard::new().unwrap();
+    /// assert_eq!(manager.type_string("Hello, world!\n"), Ok(()));
+    /// ```
+    fn type_string(&mut self, text: &str) -> Result<(), Error> {
+        self.type_string_cancellable(text, &CancellationToken::new())
+    }
+    /// Type like `type_string`, but stop between the keys once the given
+    /// token is cancelled and fail with `Error::Cancelled`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::cancel::CancellationToken;
+    /// use mouce::Keyboard;
+    ///
+    /// let mut manager = Keyboard::new().unwrap();
+    /// let token = CancellationToken::new();
+    /// manager.type_string_cancellable("Hello, world!\n", &token).unwrap();
+    /// ```
+    fn type_string_cancellable(
+        &mut self,
+        text: &str,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        let keys = text
+            .chars()
+            .map(|ch| {
+                Key::from_char(ch)
+                    .ok_or_else(|| Error::CustomError(format!("{:?} can not be typed", ch)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        token.check()?;
+        for (key, shift) in keys {
+            // Shift is released even if the key fails, so it is not left pressed
+            let shifted: &[Key] = if shift { &[Key::Shift] } else { &[] };
+            with_keys_held(self, shifted, |keyboard| keyboard.click_key(&key))?;
+            token.sleep(TYPING_INTERVAL)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, KeyboardActions, ModifierKeys, Modifiers};
+    use crate::error::Error;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the pressed and the released keys, and fails to press the given key
+    struct Recorder {
+        keys: Arc<Mutex<Vec<(Key, bool)>>>,
+        failing: Option<Key>,
+    }
+
+    impl KeyboardActions for Recorder {
+        fn press_key(&mut self, key: &Key) -> Result<(), Error> {
+            if self.failing == Some(*key) {
+                return Err(Error::WriteFailed);
+            }
+            self.keys.lock().unwrap().push((*key, true));
+            Ok(())
+        }
+
+        fn release_key(&mut self, key: &Key) -> Result<(), Error> {
+            self.keys.lock().unwrap().push((*key, false));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn keys_of_characters() {
+        assert_eq!(Key::from_char('a'), Some((Key::Char('a'), false)));
+        assert_eq!(Key::from_char('A'), Some((Key::Char('a'), true)));
+        assert_eq!(Key::from_char('7'), Some((Key::Char('7'), false)));
+        assert_eq!(Key::from_char('&'), Some((Key::Char('7'), true)));
+        assert_eq!(Key::from_char('/'), Some((Key::Char('/'), false)));
+        assert_eq!(Key::from_char('?'), Some((Key::Char('/'), true)));
+        assert_eq!(Key::from_char('\n'), Some((Key::Enter, false)));
+        assert_eq!(Key::from_char('ö'), None);
+    }
+
+    #[test]
+    fn modifier_keys() {
+        assert!(Modifiers::empty().keys().is_empty());
+        assert!(Modifiers::default().is_empty());
+        let mut modifiers = Modifiers::META | Modifiers::SHIFT;
+        assert_eq!(modifiers.keys(), vec![Key::Shift, Key::Meta]);
+        modifiers |= Modifiers::ALT;
+        assert!(modifiers.contains(Modifiers::ALT | Modifiers::META));
+        assert!(!modifiers.contains(Modifiers::CONTROL));
+        assert_eq!(modifiers.keys(), vec![Key::Shift, Key::Alt, Key::Meta]);
+    }
+
+    #[test]
+    fn hold_modifiers() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let created = Arc::new(Mutex::new(0));
+        let create = |failing: Option<Key>| {
+            let (keys, created) = (keys.clone(), created.clone());
+            move || {
+                *created.lock().unwrap() += 1;
+                Ok(Box::new(Recorder { keys, failing }) as Box<dyn KeyboardActions + Send>)
+            }
+        };
+        let modifiers = ModifierKeys::default();
+        let held = Modifiers::CONTROL | Modifiers::SHIFT;
+
+        let clicked = modifiers.hold_with(create(None), held, || {
+            keys.lock().unwrap().push((Key::Space, true));
+            Ok(())
+        });
+        assert_eq!(clicked, Ok(()));
+        assert_eq!(
+            *keys.lock().unwrap(),
+            vec![
+                (Key::Shift, true),
+                (Key::Control, true),
+                (Key::Space, true),
+                (Key::Control, false),
+                (Key::Shift, false),
+            ]
+        );
+
+        // The keyboard is reused, and the modifiers are released when the action fails
+        keys.lock().unwrap().clear();
+        let clicked = modifiers.hold_with(create(None), held, || Err(Error::WriteFailed));
+        assert_eq!(clicked, Err(Error::WriteFailed));
+        assert_eq!(*created.lock().unwrap(), 1);
+        assert_eq!(
+            *keys.lock().unwrap(),
+            vec![
+                (Key::Shift, true),
+                (Key::Control, true),
+                (Key::Control, false),
+                (Key::Shift, false),
+            ]
+        );
+
+        // The keys that are pressed before a press fails are released, without the action
+        keys.lock().unwrap().clear();
+        let clicked = ModifierKeys::default().hold_with(create(Some(Key::Control)), held, || {
+            panic!("the action runs without the modifiers")
+        });
+        assert_eq!(clicked, Err(Error::WriteFailed));
+        assert_eq!(
+            *keys.lock().unwrap(),
+            vec![(Key::Shift, true), (Key::Shift, false)]
+        );
+    }
+
+    #[test]
+    fn release_shift_of_failing_keys() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let mut keyboard = Recorder {
+            keys: keys.clone(),
+            failing: Some(Key::Char('b')),
+        };
+        assert_eq!(keyboard.type_string("aB"), Err(Error::WriteFailed));
+        assert_eq!(
+            *keys.lock().unwrap(),
+            vec![
+                (Key::Char('a'), true),
+                (Key::Char('a'), false),
+                (Key::Shift, true),
+                (Key::Shift, false),
+            ]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 #[cfg(any(
+    target_os = "android",
     target_os = "linux",
     target_os = "dragonfly",
     target_os = "freebsd",
@@ -7,6 +8,16 @@
 ))]
 pub mod nix;
 #[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use crate::nix::NixKeyboardManager as Keyboard;
+#[cfg(any(
+    target_os = "android",
     target_os = "linux",
     target_os = "dragonfly",
     target_os = "freebsd",
@@ -18,16 +29,70 @@ pub use crate::nix::NixMouseManager as Mouse;
 #[cfg(target_vendor = "apple")]
 pub mod darwin;
 #[cfg(target_vendor = "apple")]
+pub use crate::darwin::DarwinKeyboardManager as Keyboard;
+#[cfg(target_vendor = "apple")]
 pub use crate::darwin::DarwinMouseManager as Mouse;
 
 #[cfg(target_os = "windows")]
 pub mod windows;
 #[cfg(target_os = "windows")]
+pub use crate::windows::WindowsKeyboardManager as Keyboard;
+#[cfg(target_os = "windows")]
 pub use crate::windows::WindowsMouseManager as Mouse;
 
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+#[cfg(target_arch = "wasm32")]
+pub use crate::wasm::WasmKeyboardManager as Keyboard;
+#[cfg(target_arch = "wasm32")]
+pub use crate::wasm::WasmMouseManager as Mouse;
+
+pub mod cancel;
 pub mod common;
+pub mod cursor;
+pub mod detect;
+pub mod devices;
+pub mod diagnostics;
+pub mod display;
+pub mod error;
+mod evemu;
+pub mod executor;
+pub mod ffi;
+pub mod gestures;
+mod hooks;
+pub mod humanize;
+mod json;
+pub mod keyboard;
+pub mod limit;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod position;
+pub mod recorder;
+pub mod remap;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod scheduler;
+pub mod script;
+#[cfg(target_os = "linux")]
+pub mod setup;
+pub mod shared;
+#[cfg(feature = "stream")]
+pub mod stream;
+pub mod subscription;
+pub mod touch;
+mod trace;
+pub mod transforms;
+#[cfg(feature = "winit")]
+pub mod winit_adapter;
+#[cfg(feature = "serde")]
+mod wire;
+pub mod zones;
 
-pub use common::MouseActions;
+pub use common::{MouseActions, MouseController, MouseListener};
+pub use error::Error;
+pub use keyboard::KeyboardActions;
+pub use shared::SharedMouse;
+pub use touch::TouchActions;
 
 #[cfg(test)]
 mod tests {
@@ -37,6 +102,7 @@ mod tests {
     fn supported_platform() {
         // Mouse should be visible if the current platform is supported
         #[cfg(any(
+            target_os = "android",
             target_os = "linux",
             target_os = "dragonfly",
             target_os = "freebsd",
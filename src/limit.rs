@@ -0,0 +1,299 @@
+///
+/// This module contains the rate limits of the callbacks, so that e.g. a UI
+/// is not flooded with the thousands of moves per second of the gaming mice
+///
+/// The limited events are merged while they are held back, the relative moves
+/// and the scrolls are summed up and the absolute moves keep the last position,
+/// so the limited callbacks still add up to the same movement
+///
+use crate::common::{EventMask, MouseEvent};
+use crate::executor::{self, Panicked};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Limits how often a callback receives the events of the types in the mask,
+/// the other events are delivered as they come
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::common::EventMask;
+/// use mouce::limit::RateLimit;
+/// use mouce::{Mouse, MouseListener};
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// // At most 60 moves per second
+/// let limit = RateLimit::new(EventMask::MOVE_REL).throttle(Duration::from_millis(16));
+/// manager
+///     .hook_limited(limit, Box::new(|e| println!("{:?}", e)))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    mask: EventMask,
+    throttle: Option<Duration>,
+    debounce: Option<Duration>,
+}
+
+impl RateLimit {
+    /// Limit the events of the types in the given mask, without any limits yet
+    pub fn new(mask: EventMask) -> Self {
+        RateLimit {
+            mask,
+            throttle: None,
+            debounce: None,
+        }
+    }
+
+    /// Deliver the limited events at most once in the given interval,
+    /// the first event is delivered right away
+    pub fn throttle(mut self, interval: Duration) -> Self {
+        self.throttle = Some(interval);
+        self
+    }
+
+    /// Deliver the limited events only once they stop for the given time,
+    /// e.g. to react when the mouse comes to rest
+    pub fn debounce(mut self, quiet: Duration) -> Self {
+        self.debounce = Some(quiet);
+        self
+    }
+}
+
+impl Default for RateLimit {
+    /// Limit the moves
+    fn default() -> Self {
+        RateLimit::new(EventMask::MOVE_REL | EventMask::MOVE_ABS)
+    }
+}
+
+/// Wrap the given callback so that it receives the events with the given limit
+///
+/// The held back events need to be delivered when their time comes even if there
+/// are no more events, so the wrapped callback runs on its own thread, which exits
+/// once the wrapper is dropped, e.g. when the callback is unhooked. Its panics are
+/// raised again on the dispatch thread like the ones of `executor::with_policy`
+pub(crate) fn limited(
+    limit: RateLimit,
+    callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+) -> Box<dyn Fn(&MouseEvent) + Send + Sync> {
+    let (tx, rx) = mpsc::channel::<MouseEvent>();
+    let panicked = Panicked::default();
+    let caught = panicked.clone();
+    thread::spawn(move || {
+        let mut limiter = Limiter::new(limit);
+        loop {
+            let received = match limiter.deadline() {
+                Some(deadline) => {
+                    rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let events = match received {
+                Ok(event) => limiter.offer(event, Instant::now()),
+                Err(RecvTimeoutError::Timeout) => limiter.ready(Instant::now()),
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            for event in events.iter() {
+                executor::catch(&caught, || callback(event));
+            }
+        }
+    });
+
+    Box::new(move |event| {
+        let _ = tx.send(event.clone());
+        executor::raise(&panicked);
+    })
+}
+
+/// Decides when the events of a limited callback are delivered
+struct Limiter {
+    limit: RateLimit,
+    /// The limited events since the last delivery, merged into one
+    pending: Option<MouseEvent>,
+    /// When the limited events were delivered last time
+    delivered: Option<Instant>,
+    /// When the last limited event was received
+    received: Option<Instant>,
+}
+
+impl Limiter {
+    fn new(limit: RateLimit) -> Self {
+        Limiter {
+            limit,
+            pending: None,
+            delivered: None,
+            received: None,
+        }
+    }
+
+    /// Take the given event, and get the events that are to be delivered now
+    fn offer(&mut self, event: MouseEvent, now: Instant) -> Vec<MouseEvent> {
+        if !self.limit.mask.matches(&event) {
+            // Keep the order of the events, the held back ones happened earlier
+            let mut events: Vec<MouseEvent> = self.pending.take().into_iter().collect();
+            if !events.is_empty() {
+                self.delivered = Some(now);
+            }
+            events.push(event);
+            return events;
+        }
+
+        let mut events = Vec::new();
+        self.pending = match (self.pending.take(), event) {
+            (None, event) => Some(event),
+            (Some(pending), event) => match merge(&pending, &event) {
+                Some(merged) => Some(merged),
+                None => {
+                    // The events of another type can not be merged, so the
+                    // held back ones are delivered first
+                    events.push(pending);
+                    self.delivered = Some(now);
+                    Some(event)
+                }
+            },
+        };
+        self.received = Some(now);
+        events.extend(self.ready(now));
+        events
+    }
+
+    /// When the held back events are to be delivered, if there are any
+    fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref()?;
+        let throttled = match (self.limit.throttle, self.delivered) {
+            (Some(interval), Some(delivered)) => Some(delivered + interval),
+            _ => None,
+        };
+        let debounced = match (self.limit.debounce, self.received) {
+            (Some(quiet), Some(received)) => Some(received + quiet),
+            _ => None,
+        };
+        // Both of the limits are satisfied at the later one
+        match (throttled, debounced) {
+            (Some(throttled), Some(debounced)) => Some(throttled.max(debounced)),
+            (Some(deadline), None) | (None, Some(deadline)) => Some(deadline),
+            // Neither of the limits holds the events back
+            (None, None) => self.received,
+        }
+    }
+
+    /// Get the held back events if their time has come
+    fn ready(&mut self, now: Instant) -> Vec<MouseEvent> {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => {
+                self.delivered = Some(now);
+                self.pending.take().into_iter().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Merge the given events of the same type into one, if they can be merged
+fn merge(earlier: &MouseEvent, later: &MouseEvent) -> Option<MouseEvent> {
+    match (earlier, later) {
+        (MouseEvent::RelativeMove(x1, y1), MouseEvent::RelativeMove(x2, y2)) => {
+            Some(MouseEvent::RelativeMove(x1 + x2, y1 + y2))
+        }
+        (MouseEvent::AbsoluteMove(..), MouseEvent::AbsoluteMove(..)) => Some(later.clone()),
+        (
+            MouseEvent::ScrollHiRes { dx: x1, dy: y1 },
+            MouseEvent::ScrollHiRes { dx: x2, dy: y2 },
+        ) => Some(MouseEvent::ScrollHiRes {
+            dx: x1 + x2,
+            dy: y1 + y2,
+        }),
+        (MouseEvent::Scroll2D { dx: x1, dy: y1 }, MouseEvent::Scroll2D { dx: x2, dy: y2 }) => {
+            Some(MouseEvent::Scroll2D {
+                dx: x1 + x2,
+                dy: y1 + y2,
+            })
+        }
+        // Every press, release and detent matters on its own
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::MouseButton;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn throttle_moves() {
+        let start = Instant::now();
+        let mut limiter = Limiter::new(RateLimit::default().throttle(10 * MS));
+
+        // The first move is delivered right away
+        assert_eq!(
+            limiter.offer(MouseEvent::RelativeMove(1, 0), start),
+            vec![MouseEvent::RelativeMove(1, 0)]
+        );
+        // The moves within the interval are summed up
+        assert_eq!(
+            limiter.offer(MouseEvent::RelativeMove(2, 1), start + MS),
+            vec![]
+        );
+        assert_eq!(
+            limiter.offer(MouseEvent::RelativeMove(3, 1), start + 2 * MS),
+            vec![]
+        );
+        assert_eq!(limiter.deadline(), Some(start + 10 * MS));
+        assert_eq!(limiter.ready(start + 9 * MS), vec![]);
+        assert_eq!(
+            limiter.ready(start + 10 * MS),
+            vec![MouseEvent::RelativeMove(5, 2)]
+        );
+        assert_eq!(limiter.deadline(), None);
+    }
+
+    #[test]
+    fn debounce_moves() {
+        let start = Instant::now();
+        let mut limiter = Limiter::new(RateLimit::default().debounce(10 * MS));
+
+        assert_eq!(limiter.offer(MouseEvent::AbsoluteMove(1, 1), start), vec![]);
+        assert_eq!(
+            limiter.offer(MouseEvent::AbsoluteMove(5, 5), start + 8 * MS),
+            vec![]
+        );
+        // The quiet time restarts with every move
+        assert_eq!(limiter.ready(start + 12 * MS), vec![]);
+        assert_eq!(
+            limiter.ready(start + 18 * MS),
+            vec![MouseEvent::AbsoluteMove(5, 5)]
+        );
+    }
+
+    #[test]
+    fn keep_the_order() {
+        let start = Instant::now();
+        let mut limiter = Limiter::new(RateLimit::default().debounce(10 * MS));
+
+        assert_eq!(limiter.offer(MouseEvent::RelativeMove(1, 1), start), vec![]);
+        // The held back move happened before the press
+        assert_eq!(
+            limiter.offer(MouseEvent::Press(MouseButton::Left), start + MS),
+            vec![
+                MouseEvent::RelativeMove(1, 1),
+                MouseEvent::Press(MouseButton::Left)
+            ]
+        );
+        // The moves of different types are not merged
+        assert_eq!(limiter.offer(MouseEvent::RelativeMove(1, 1), start), vec![]);
+        assert_eq!(
+            limiter.offer(MouseEvent::AbsoluteMove(3, 3), start + MS),
+            vec![MouseEvent::RelativeMove(1, 1)]
+        );
+    }
+}
@@ -0,0 +1,737 @@
+///
+/// This module contains a mouse manager that does not touch any device,
+/// so that the code that drives the mouse can be tested without a display
+///
+/// The manager logs the actions instead of simulating them, and the
+/// events are only delivered to the callbacks when they are injected
+///
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, EventAction, EventMask,
+    MouseButton, MouseController, MouseEvent, MouseListener, Propagation, ScrollDirection,
+};
+use crate::error::Error;
+use crate::hooks::{Dispatcher, Handler, Hooks, LockUnpoisoned, DEFAULT_PRIORITY};
+use crate::keyboard::{Key, Modifiers};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// An action that is received by a `MockMouseManager`
+///
+/// The default methods of `MouseController` are logged as the actions that they
+/// are made of, e.g. `click_button` is logged as a press and a release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    MoveTo(usize, usize),
+    MoveRelative(i32, i32),
+    Press(MouseButton),
+    Release(MouseButton),
+    ScrollWheel(ScrollDirection),
+    ScrollHiRes(i32, i32),
+    /// The modifier keys held by `click_with_modifiers`
+    PressKey(Key),
+    ReleaseKey(Key),
+}
+
+/// A mouse manager that logs the actions and delivers the injected events
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::common::{MouseButton, MouseEvent};
+/// use mouce::mock::{MockCall, MockMouseManager};
+/// use mouce::{MouseController, MouseListener};
+/// use std::sync::{Arc, Mutex};
+///
+/// let mut manager = MockMouseManager::new();
+/// manager.click_button(&MouseButton::Left).unwrap();
+/// assert_eq!(
+///     manager.calls(),
+///     vec![
+///         MockCall::Press(MouseButton::Left),
+///         MockCall::Release(MouseButton::Left)
+///     ]
+/// );
+///
+/// let received = Arc::new(Mutex::new(Vec::new()));
+/// let events = received.clone();
+/// manager
+///     .hook(Box::new(move |e| events.lock().unwrap().push(e.clone())))
+///     .unwrap();
+/// manager.inject(&MouseEvent::RelativeMove(1, 2));
+/// assert_eq!(*received.lock().unwrap(), vec![MouseEvent::RelativeMove(1, 2)]);
+/// ```
+pub struct MockMouseManager {
+    calls: Mutex<Vec<MockCall>>,
+    position: Mutex<(i32, i32)>,
+    /// The buttons that are pressed and not released yet
+    pressed: Mutex<Vec<MouseButton>>,
+    callbacks: Arc<Hooks<MouseEvent>>,
+    callback_counter: CallbackId,
+    listening: Arc<AtomicBool>,
+}
+
+impl Default for MockMouseManager {
+    fn default() -> Self {
+        MockMouseManager {
+            calls: Mutex::new(Vec::new()),
+            position: Mutex::new((0, 0)),
+            pressed: Mutex::new(Vec::new()),
+            callbacks: Arc::default(),
+            callback_counter: 0,
+            listening: Arc::default(),
+        }
+    }
+}
+
+impl MockMouseManager {
+    /// Create a manager with an empty log, with the mouse at (0, 0)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the actions that are received so far, in order
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock_unpoisoned().clone()
+    }
+
+    /// Clear the log of the received actions
+    pub fn clear_calls(&self) {
+        self.calls.lock_unpoisoned().clear();
+    }
+
+    /// Set the position that `get_position` reports, without logging an action
+    pub fn set_position(&self, x: i32, y: i32) {
+        *self.position.lock_unpoisoned() = (x, y);
+    }
+
+    /// Deliver the given event to the attached callbacks, as if a mouse reported it
+    ///
+    /// Returns `EventAction::Consume` if a grabbing callback consumed the event, the
+    /// events are not delivered after the listener is stopped until `hook` is called again
+    pub fn inject(&self, event: &MouseEvent) -> EventAction {
+        if !self.listening.load(Ordering::Acquire) {
+            return EventAction::PassThrough;
+        }
+        self.callbacks.dispatch(event)
+    }
+
+    /// Get a listener that delivers the injected events like the dispatch thread of
+    /// a real manager, from a snapshot of the callbacks that is kept until they change
+    pub fn listener(&self) -> MockListener {
+        MockListener {
+            dispatcher: self.callbacks.dispatcher(),
+            listening: self.listening.clone(),
+        }
+    }
+
+    fn log(&self, call: MockCall) -> Result<(), Error> {
+        self.calls.lock_unpoisoned().push(call);
+        Ok(())
+    }
+
+    fn add_callback(
+        &mut self,
+        priority: i32,
+        handler: Handler<MouseEvent>,
+    ) -> Result<CallbackId, Error> {
+        let id = self.callback_counter;
+        self.callbacks.insert(id, priority, EventMask::ALL, handler);
+        self.callback_counter += 1;
+        self.listening.store(true, Ordering::Release);
+        Ok(id)
+    }
+}
+
+/// Delivers the injected events of a `MockMouseManager` on the thread that owns it
+pub struct MockListener {
+    dispatcher: Dispatcher<MouseEvent>,
+    listening: Arc<AtomicBool>,
+}
+
+impl MockListener {
+    /// Deliver the given event like `MockMouseManager::inject`
+    pub fn inject(&mut self, event: &MouseEvent) -> EventAction {
+        if !self.listening.load(Ordering::Acquire) {
+            return EventAction::PassThrough;
+        }
+        self.dispatcher.dispatch(event).0
+    }
+}
+
+/// Implement the actions for both receivers of `MouseController`,
+/// the platforms differ in whether the actions take `&mut self`
+macro_rules! mock_mouse_actions {
+    ($($mutability:tt)?) => {
+        impl MouseController for MockMouseManager {
+            fn move_to(&$($mutability)? self, x: usize, y: usize) -> Result<(), Error> {
+                *self.position.lock_unpoisoned() = (x as i32, y as i32);
+                self.log(MockCall::MoveTo(x, y))
+            }
+
+            fn move_relative(
+                &$($mutability)? self,
+                x_offset: i32,
+                y_offset: i32,
+            ) -> Result<(), Error> {
+                let mut position = self.position.lock_unpoisoned();
+                *position = (position.0 + x_offset, position.1 + y_offset);
+                self.log(MockCall::MoveRelative(x_offset, y_offset))
+            }
+
+            fn get_position(&self) -> Result<(i32, i32), Error> {
+                Ok(*self.position.lock_unpoisoned())
+            }
+
+            fn backend(&self) -> Backend {
+                Backend::Mock
+            }
+
+            fn supports(&self, capability: Capability) -> bool {
+                matches!(
+                    capability,
+                    Capability::GetPosition
+                        | Capability::ButtonState
+                        | Capability::HiResScroll
+                        | Capability::GrabEvents
+                        | Capability::InjectEvent
+                        | Capability::PauseHook
+                        | Capability::CallbackPanics
+                )
+            }
+
+            fn get_button_state(&self) -> Result<ButtonState, Error> {
+                Ok(ButtonState {
+                    pressed: self.pressed.lock_unpoisoned().clone(),
+                })
+            }
+
+            fn press_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                let mut pressed = self.pressed.lock_unpoisoned();
+                if !pressed.contains(button) {
+                    pressed.push(button.clone());
+                }
+                self.log(MockCall::Press(button.clone()))
+            }
+
+            fn release_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.pressed.lock_unpoisoned().retain(|pressed| pressed != button);
+                self.log(MockCall::Release(button.clone()))
+            }
+
+            fn scroll_wheel(
+                &$($mutability)? self,
+                direction: &ScrollDirection,
+            ) -> Result<(), Error> {
+                self.log(MockCall::ScrollWheel(direction.clone()))
+            }
+
+            fn scroll_wheel_hi_res(&$($mutability)? self, dx: i32, dy: i32) -> Result<(), Error> {
+                self.log(MockCall::ScrollHiRes(dx, dy))
+            }
+
+            fn click_with_modifiers(
+                &$($mutability)? self,
+                button: &MouseButton,
+                modifiers: Modifiers,
+            ) -> Result<(), Error> {
+                // The keys are logged instead of pressed with the keyboard of the platform
+                let keys = modifiers.keys();
+                for key in &keys {
+                    self.log(MockCall::PressKey(*key))?;
+                }
+                self.click_button(button)?;
+                for key in keys.iter().rev() {
+                    self.log(MockCall::ReleaseKey(*key))?;
+                }
+                Ok(())
+            }
+        }
+
+        impl MouseListener for MockMouseManager {
+            fn hook(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.add_callback(
+                    DEFAULT_PRIORITY,
+                    Handler::Action(Box::new(move |event| {
+                        callback(event);
+                        EventAction::PassThrough
+                    })),
+                )
+            }
+
+            fn hook_grab(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEvent) -> EventAction + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.add_callback(DEFAULT_PRIORITY, Handler::Action(callback))
+            }
+
+            fn hook_with_priority(
+                &mut self,
+                priority: i32,
+                callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.add_callback(priority, Handler::Propagate(callback))
+            }
+
+            fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+                Ok(self.inject(&event))
+            }
+
+            fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                if self.callbacks.remove(callback_id) {
+                    Ok(())
+                } else {
+                    Err(Error::CallbackNotFound)
+                }
+            }
+
+            fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                if self.callbacks.set_paused(callback_id, true) {
+                    Ok(())
+                } else {
+                    Err(Error::CallbackNotFound)
+                }
+            }
+
+            fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                if self.callbacks.set_paused(callback_id, false) {
+                    Ok(())
+                } else {
+                    Err(Error::CallbackNotFound)
+                }
+            }
+
+            fn on_callback_panic(
+                &mut self,
+                unhook: bool,
+                handler: Box<dyn Fn(&CallbackPanic) + Send>,
+            ) -> Result<(), Error> {
+                self.callbacks.set_panic_handler(handler, unhook);
+                Ok(())
+            }
+
+            fn unhook_all(&mut self) -> Result<(), Error> {
+                self.callbacks.clear();
+                Ok(())
+            }
+
+            fn stop_listening(&mut self) -> Result<(), Error> {
+                self.listening.store(false, Ordering::Release);
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mock_mouse_actions!(mut);
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+mock_mouse_actions!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancellationToken;
+    use crate::common::HoldButton;
+    use crate::display::Rect;
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+    use std::{io, panic, time::Duration};
+
+    #[test]
+    fn log_actions() {
+        let mut manager = MockMouseManager::new();
+        manager.move_to(10, 20).unwrap();
+        manager.move_relative(5, -5).unwrap();
+        assert_eq!(manager.get_position(), Ok((15, 15)));
+        manager.scroll(0, -2).unwrap();
+        assert_eq!(
+            manager.calls(),
+            vec![
+                MockCall::MoveTo(10, 20),
+                MockCall::MoveRelative(5, -5),
+                MockCall::ScrollWheel(ScrollDirection::Down),
+                MockCall::ScrollWheel(ScrollDirection::Down),
+            ]
+        );
+
+        manager.clear_calls();
+        assert!(manager.calls().is_empty());
+    }
+
+    #[test]
+    fn click_at() {
+        let mut manager = MockMouseManager::new();
+        manager
+            .click_at_with_settle(30, 40, &MouseButton::Right, Duration::ZERO)
+            .unwrap();
+        assert_eq!(
+            manager.calls(),
+            vec![
+                MockCall::MoveTo(30, 40),
+                MockCall::Press(MouseButton::Right),
+                MockCall::Release(MouseButton::Right),
+            ]
+        );
+    }
+
+    #[test]
+    fn scroll_detents() {
+        let mut manager = MockMouseManager::new();
+        manager
+            .scroll_n(&ScrollDirection::Down, 3, Duration::ZERO)
+            .unwrap();
+        assert_eq!(
+            manager.calls(),
+            vec![MockCall::ScrollWheel(ScrollDirection::Down); 3]
+        );
+        manager.clear_calls();
+        manager
+            .scroll_n(&ScrollDirection::Up, 0, Duration::ZERO)
+            .unwrap();
+        assert!(manager.calls().is_empty());
+    }
+
+    #[test]
+    fn cancel_drag() {
+        let mut manager = MockMouseManager::new();
+        let token = CancellationToken::new();
+        let stop = token.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            stop.cancel();
+        });
+        // The drag would take seconds, it stops with the button released
+        let step_delay = Duration::from_millis(5);
+        assert_eq!(
+            manager.drag_to_cancellable(&MouseButton::Left, (0, 0), (5000, 0), step_delay, &token),
+            Err(Error::Cancelled)
+        );
+        canceller.join().unwrap();
+        let calls = manager.calls();
+        assert_eq!(calls[1], MockCall::Press(MouseButton::Left));
+        assert_eq!(calls.last(), Some(&MockCall::Release(MouseButton::Left)));
+        assert!(manager.get_button_state().unwrap().pressed.is_empty());
+
+        // The actions that start with a cancelled token do nothing
+        manager.clear_calls();
+        assert_eq!(
+            manager.scroll_n_cancellable(&ScrollDirection::Up, 3, Duration::ZERO, &token),
+            Err(Error::Cancelled)
+        );
+        assert_eq!(
+            manager.multi_click_cancellable(&MouseButton::Left, 2, Duration::ZERO, &token),
+            Err(Error::Cancelled)
+        );
+        assert_eq!(
+            manager.click_at_with_settle_cancellable(
+                10,
+                20,
+                &MouseButton::Left,
+                Duration::ZERO,
+                &token
+            ),
+            Err(Error::Cancelled)
+        );
+        assert!(manager.calls().is_empty());
+    }
+
+    #[test]
+    fn click_with_modifiers() {
+        let mut manager = MockMouseManager::new();
+        manager
+            .click_with_modifiers(&MouseButton::Left, Modifiers::CONTROL | Modifiers::SHIFT)
+            .unwrap();
+        assert_eq!(
+            manager.calls(),
+            vec![
+                MockCall::PressKey(Key::Shift),
+                MockCall::PressKey(Key::Control),
+                MockCall::Press(MouseButton::Left),
+                MockCall::Release(MouseButton::Left),
+                MockCall::ReleaseKey(Key::Control),
+                MockCall::ReleaseKey(Key::Shift),
+            ]
+        );
+    }
+
+    #[test]
+    fn release_held_buttons() {
+        let mut manager = MockMouseManager::new();
+        let mut held = manager.hold_button(&MouseButton::Left).unwrap();
+        held.move_relative(5, 0).unwrap();
+        assert!(held
+            .get_button_state()
+            .unwrap()
+            .is_pressed(&MouseButton::Left));
+        drop(held);
+        assert_eq!(manager.get_button_state(), Ok(ButtonState::default()));
+        manager
+            .hold_button(&MouseButton::Right)
+            .unwrap()
+            .release()
+            .unwrap();
+        assert_eq!(
+            manager.calls(),
+            vec![
+                MockCall::Press(MouseButton::Left),
+                MockCall::MoveRelative(5, 0),
+                MockCall::Release(MouseButton::Left),
+                MockCall::Press(MouseButton::Right),
+                MockCall::Release(MouseButton::Right),
+            ]
+        );
+
+        // The button is released while unwinding
+        manager.clear_calls();
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _held = manager.hold_button(&MouseButton::Middle).unwrap();
+            panic!("the automation failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            manager.calls(),
+            vec![
+                MockCall::Press(MouseButton::Middle),
+                MockCall::Release(MouseButton::Middle),
+            ]
+        );
+    }
+
+    #[test]
+    fn inject_events() {
+        let mut manager = MockMouseManager::new();
+        let presses = Arc::new(AtomicUsize::new(0));
+        let counter = presses.clone();
+        let id = manager
+            .hook_filtered(
+                EventMask::PRESS,
+                Box::new(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }),
+            )
+            .unwrap();
+        manager
+            .hook_grab(Box::new(|e| match e {
+                MouseEvent::Press(MouseButton::Middle) => EventAction::Consume,
+                _ => EventAction::PassThrough,
+            }))
+            .unwrap();
+
+        assert_eq!(
+            manager.inject(&MouseEvent::Press(MouseButton::Middle)),
+            EventAction::Consume
+        );
+        assert_eq!(
+            manager.inject(&MouseEvent::RelativeMove(1, 1)),
+            EventAction::PassThrough
+        );
+        assert_eq!(presses.load(Ordering::SeqCst), 1);
+
+        manager.stop_listening().unwrap();
+        manager.inject(&MouseEvent::Press(MouseButton::Left));
+        assert_eq!(presses.load(Ordering::SeqCst), 1);
+
+        assert_eq!(manager.unhook(id), Ok(()));
+        assert_eq!(manager.unhook(id), Err(Error::CallbackNotFound));
+    }
+
+    #[test]
+    fn listener_follows_hooks() {
+        let mut manager = MockMouseManager::new();
+        let presses = Arc::new(AtomicUsize::new(0));
+        let counter = presses.clone();
+        let id = manager
+            .hook(Box::new(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }))
+            .unwrap();
+        let mut listener = manager.listener();
+        listener.inject(&MouseEvent::Press(MouseButton::Left));
+        assert_eq!(presses.load(Ordering::SeqCst), 1);
+
+        // The listener takes the changes of the callbacks
+        manager.unhook(id).unwrap();
+        listener.inject(&MouseEvent::Press(MouseButton::Left));
+        assert_eq!(presses.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn supported_actions() {
+        let mut manager = MockMouseManager::new();
+        assert!(manager.supports(Capability::InjectEvent));
+        assert!(!manager.supports(Capability::ConfineCursor));
+        assert_eq!(
+            manager.confine_cursor(Rect::new(0, 0, 10, 10)),
+            Err(Error::NotImplemented)
+        );
+    }
+
+    #[test]
+    fn survive_panicking_callbacks() {
+        let mut manager = MockMouseManager::new();
+        let id = manager
+            .hook(Box::new(|e| {
+                if e == &MouseEvent::Press(MouseButton::Left) {
+                    panic!("the callback failed");
+                }
+            }))
+            .unwrap();
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        manager
+            .hook(Box::new(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }))
+            .unwrap();
+
+        // The panic does not stop the other callbacks
+        assert_eq!(
+            manager.inject(&MouseEvent::Press(MouseButton::Left)),
+            EventAction::PassThrough
+        );
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reported = reports.clone();
+        manager
+            .on_callback_panic(
+                true,
+                Box::new(move |report| reported.lock().unwrap().push(report.clone())),
+            )
+            .unwrap();
+        manager.inject(&MouseEvent::Press(MouseButton::Left));
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![CallbackPanic {
+                callback_id: id,
+                message: String::from("the callback failed"),
+                unhooked: true,
+            }]
+        );
+        assert_eq!(manager.unhook(id), Err(Error::CallbackNotFound));
+        manager.inject(&MouseEvent::Press(MouseButton::Left));
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+        assert_eq!(reports.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn hook_priorities() {
+        let mut manager = MockMouseManager::new();
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        manager
+            .hook(Box::new(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }))
+            .unwrap();
+        // Handles the scrolls before the other callback
+        manager
+            .hook_with_priority(
+                1,
+                Box::new(|e| match e {
+                    MouseEvent::Scroll(_) => Propagation::Stop,
+                    _ => Propagation::Continue,
+                }),
+            )
+            .unwrap();
+
+        manager.inject(&MouseEvent::Scroll(ScrollDirection::Up));
+        assert_eq!(received.load(Ordering::SeqCst), 0);
+        manager.inject(&MouseEvent::Press(MouseButton::Left));
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn pause_hooks() {
+        let mut manager = MockMouseManager::new();
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        let id = manager
+            .hook(Box::new(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }))
+            .unwrap();
+
+        assert_eq!(manager.pause_hook(id), Ok(()));
+        manager.inject(&MouseEvent::RelativeMove(1, 0));
+        assert_eq!(received.load(Ordering::SeqCst), 0);
+
+        assert_eq!(manager.resume_hook(id), Ok(()));
+        manager.inject(&MouseEvent::RelativeMove(1, 0));
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+        assert_eq!(manager.pause_hook(id + 1), Err(Error::CallbackNotFound));
+    }
+
+    /// A writer that fails once it is told to
+    struct Lines {
+        written: Arc<Mutex<Vec<u8>>>,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl io::Write for Lines {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_json_lines() {
+        let mut manager = MockMouseManager::new();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let failing = Arc::new(AtomicBool::new(false));
+        let id = manager
+            .hook_json(Box::new(Lines {
+                written: written.clone(),
+                failing: failing.clone(),
+            }))
+            .unwrap();
+
+        manager.inject(&MouseEvent::Press(MouseButton::Left));
+        for _ in 0..100 {
+            if !written.lock().unwrap().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let line = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert!(line.ends_with("\"type\":\"press\",\"button\":\"left\"}\n"));
+
+        // The writer stops at its first error, and the callback is unhooked
+        failing.store(true, Ordering::SeqCst);
+        for _ in 0..20 {
+            manager.inject(&MouseEvent::RelativeMove(1, 0));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(manager.unhook(id), Err(Error::CallbackNotFound));
+    }
+}
@@ -0,0 +1,92 @@
+///
+/// This module contains the parts of the unix-like backend that differ on Android
+///
+/// Android has no X server, so this module answers the queries of the x11 module
+/// instead: the size of the screen is read from `wm size`, the others are not
+/// available. The event files are not linked under /dev/input/by-id either, so
+/// the mice are probed among all the event files under /dev/input
+///
+/// SELinux only lets root open /dev/uinput and the event files, so the managers
+/// work on the rooted devices, e.g. in a process that is started with `su`
+///
+use crate::nix::devices::{device_name, is_mouse, EVENT_FILES};
+use glob::glob;
+use std::{fs::File, process::Command};
+
+/// The directory that the hotplug watcher watches for the event files
+pub const MICE_DIR: &str = "/dev/input";
+/// The names of the virtual devices that the managers create, e.g. the passthrough
+/// mouse, they are not linked under by-id on the other systems either
+const OWN_DEVICE_PREFIX: &str = "mouce-library-";
+
+/// Check whether the given file name under MICE_DIR is an event file,
+/// the devices are only known to be mice once they are opened
+pub fn is_mouse_file(name: &str) -> bool {
+    name.starts_with("event")
+}
+
+/// Check whether the device of the given event file is a mouse that the listener reads
+pub fn pointer_device(event: &File) -> bool {
+    is_mouse(event) && !device_name(event).is_some_and(|name| name.starts_with(OWN_DEVICE_PREFIX))
+}
+
+/// List the paths of the event files of the mice
+pub fn mouse_paths() -> Vec<String> {
+    glob(EVENT_FILES)
+        .expect("Failed to read glob pattern")
+        .flatten()
+        .filter(|path| {
+            File::options()
+                .read(true)
+                .open(path)
+                .is_ok_and(|event| pointer_device(&event))
+        })
+        .map(|path| path.display().to_string())
+        .collect()
+}
+
+/// Get the size of the display from the window manager
+pub fn query_screen_size() -> Option<(i32, i32)> {
+    let output = Command::new("wm").arg("size").output().ok()?;
+    parse_wm_size(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// There are no X resources, the scale factor falls back to its variables
+pub fn query_resources() -> Option<String> {
+    None
+}
+
+/// The position of the pointer is not reported to the other processes
+pub fn query_pointer_position() -> Option<(i32, i32)> {
+    None
+}
+
+/// Parse the output of `wm size`, the override size that e.g. `wm size 720x1280`
+/// sets takes precedence over the physical size of the display
+fn parse_wm_size(output: &str) -> Option<(i32, i32)> {
+    let size = |prefix: &str| {
+        output.lines().find_map(|line| {
+            let (width, height) = line.strip_prefix(prefix)?.trim().split_once('x')?;
+            Some((width.parse().ok()?, height.parse().ok()?))
+        })
+    };
+    size("Override size:").or_else(|| size("Physical size:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_wm_size;
+
+    #[test]
+    fn wm_sizes() {
+        assert_eq!(
+            parse_wm_size("Physical size: 1080x2400\n"),
+            Some((1080, 2400))
+        );
+        assert_eq!(
+            parse_wm_size("Physical size: 1080x2400\nOverride size: 720x1600\n"),
+            Some((720, 1600))
+        );
+        assert_eq!(parse_wm_size("Error: no display\n"), None);
+    }
+}
@@ -0,0 +1,152 @@
+///
+/// This module contains the calibration of the relative moves of uinput,
+/// i.e. how many pixels the cursor moves for a unit of the virtual mouse
+///
+/// The ratio depends on the acceleration profile and the speed that the
+/// compositor or the X server applies to the mouse, so it is measured by
+/// moving the device and reading the cursor position back
+///
+use crate::error::Error;
+use crate::json;
+use std::{fs, path::Path};
+
+/// The pixels that the cursor moves for a unit of the relative axes of the virtual mouse
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::nix::{Calibration, UInputMouseManager};
+///
+/// let mut manager = UInputMouseManager::new((0, 1920), (0, 1080)).unwrap();
+/// let calibration = match Calibration::load("mouse.json") {
+///     Ok(calibration) => calibration,
+///     Err(_) => {
+///         let calibration = manager.calibrate().unwrap();
+///         calibration.save("mouse.json").unwrap();
+///         calibration
+///     }
+/// };
+/// manager.set_calibration(calibration);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Default for Calibration {
+    /// The ratio of the default flat and adaptive profiles of libinput,
+    /// which is also what the other uinput tools e.g. `ydotool` see
+    fn default() -> Self {
+        Calibration { x: 2., y: 2. }
+    }
+}
+
+impl Calibration {
+    /// Construct the calibration from the given pixels per unit of each axis
+    pub fn new(x: f64, y: f64) -> Result<Self, Error> {
+        if !(x.is_finite() && x > 0. && y.is_finite() && y > 0.) {
+            return Err(Error::CustomError(format!(
+                "invalid calibration: {}, {}",
+                x, y
+            )));
+        }
+        Ok(Calibration { x, y })
+    }
+
+    /// Construct the calibration from the cursor delta that the given units moved it
+    pub(crate) fn from_delta(units: (i32, i32), delta: (i32, i32)) -> Result<Self, Error> {
+        if delta.0 == 0 || delta.1 == 0 {
+            return Err(Error::CustomError(
+                "the cursor did not move while calibrating".to_string(),
+            ));
+        }
+        Self::new(
+            delta.0 as f64 / units.0 as f64,
+            delta.1 as f64 / units.1 as f64,
+        )
+    }
+
+    /// Convert the given offset in pixels to the units of the relative axes,
+    /// the offsets that are not zero move the device by a unit at least
+    pub(crate) fn units(&self, x: i32, y: i32) -> (i32, i32) {
+        let units = |pixels: i32, ratio: f64| {
+            let units = (pixels as f64 / ratio).round() as i32;
+            if units == 0 {
+                pixels.signum()
+            } else {
+                units
+            }
+        };
+        (units(x, self.x), units(y, self.y))
+    }
+
+    /// Serialize the calibration to JSON
+    pub fn to_json(&self) -> String {
+        format!("{{\"x\":{},\"y\":{}}}", self.x, self.y)
+    }
+
+    /// Deserialize a calibration from the JSON written by `to_json`
+    pub fn from_json(input: &str) -> Result<Self, Error> {
+        let invalid =
+            |message: String| Error::CustomError(format!("invalid calibration: {}", message));
+
+        let value = json::parse(input).map_err(invalid)?;
+        let axis = |name: &str| {
+            value
+                .get(name)
+                .and_then(|value| value.as_f64())
+                .ok_or_else(|| invalid(format!("missing `{}` number", name)))
+        };
+        Self::new(axis("x")?, axis("y")?)
+    }
+
+    /// Save the calibration to the given file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    /// Load a calibration that is saved by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_units() {
+        let calibration = Calibration::default();
+        assert_eq!(calibration.units(10, -10), (5, -5));
+        // The small moves are not lost
+        assert_eq!(calibration.units(1, -1), (1, -1));
+        assert_eq!(calibration.units(0, 0), (0, 0));
+
+        let calibration = Calibration::new(1.5, 0.5).unwrap();
+        assert_eq!(calibration.units(30, 30), (20, 60));
+        assert!(Calibration::new(0., 1.).is_err());
+    }
+
+    #[test]
+    fn measure_delta() {
+        let calibration = Calibration::from_delta((100, -100), (150, -200)).unwrap();
+        assert_eq!((calibration.x, calibration.y), (1.5, 2.));
+        assert!(Calibration::from_delta((100, 100), (0, 10)).is_err());
+        // The cursor moved against the device
+        assert!(Calibration::from_delta((100, 100), (-10, 10)).is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let calibration = Calibration::new(1.25, 2.).unwrap();
+        assert_eq!(
+            Calibration::from_json(&calibration.to_json()),
+            Ok(calibration)
+        );
+        assert!(Calibration::from_json("{\"x\": 1}").is_err());
+        assert!(Calibration::from_json("{\"x\": 1, \"y\": -1}").is_err());
+    }
+}
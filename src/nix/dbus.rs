@@ -0,0 +1,641 @@
+///
+/// This module contains a minimal client of the D-Bus session bus
+/// for the unix-like systems, on top of libdbus
+///
+/// Only the parts that the portals need are covered: the method calls,
+/// waiting for and polling the signals, and the values that the portals
+/// send and receive
+///
+use std::{
+    ffi::{CStr, CString},
+    io::{Error, ErrorKind, Result},
+    os::{
+        raw::{c_char, c_int, c_uint, c_void},
+        unix::io::RawFd,
+    },
+    ptr::null_mut,
+    time::{Duration, Instant},
+};
+
+/// A D-Bus value, the containers hold their values in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    I32(i32),
+    U32(u32),
+    F64(f64),
+    Str(String),
+    Path(String),
+    /// A file descriptor, the receiver owns the ones that are read from a message
+    Fd(c_int),
+    /// An array with the signature of its elements, which is needed when it is empty
+    Array(String, Vec<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+    Variant(Box<Value>),
+}
+
+impl Value {
+    /// Create an `a{sv}` dictionary from the given entries
+    pub fn dict(entries: Vec<(&str, Value)>) -> Value {
+        Value::Array(
+            "{sv}".to_string(),
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    Value::DictEntry(
+                        Box::new(Value::Str(key.to_string())),
+                        Box::new(Value::Variant(Box::new(value))),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Get the value of the given key of an `a{sv}` dictionary
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Array(_, entries) => entries.iter().find_map(|entry| match entry {
+                Value::DictEntry(k, v) if **k == Value::Str(key.to_string()) => match &**v {
+                    Value::Variant(v) => Some(&**v),
+                    v => Some(v),
+                },
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Get the D-Bus signature of this value
+    pub fn signature(&self) -> String {
+        match self {
+            Value::Bool(_) => "b".to_string(),
+            Value::I32(_) => "i".to_string(),
+            Value::U32(_) => "u".to_string(),
+            Value::F64(_) => "d".to_string(),
+            Value::Str(_) => "s".to_string(),
+            Value::Path(_) => "o".to_string(),
+            Value::Fd(_) => "h".to_string(),
+            Value::Array(signature, _) => format!("a{}", signature),
+            Value::Struct(values) => format!(
+                "({})",
+                values.iter().map(Value::signature).collect::<String>()
+            ),
+            Value::DictEntry(key, value) => {
+                format!("{{{}{}}}", key.signature(), value.signature())
+            }
+            Value::Variant(_) => "v".to_string(),
+        }
+    }
+}
+
+/// A private connection to the session bus, which is closed on drop
+pub struct Connection(*mut DBusConnection);
+
+unsafe impl Send for Connection {}
+
+impl Connection {
+    /// Connect to the session bus
+    pub fn session() -> Result<Self> {
+        let mut error = DBusError::new();
+        let connection = unsafe { dbus_bus_get_private(DBUS_BUS_SESSION, &mut error) };
+        if connection.is_null() {
+            return Err(error.into_io_error("failed to connect to the session bus"));
+        }
+        unsafe {
+            // The process must not exit when the bus goes away
+            dbus_connection_set_exit_on_disconnect(connection, 0);
+        }
+        Ok(Connection(connection))
+    }
+
+    /// Get the name that the bus assigned to this connection, e.g. `:1.42`
+    pub fn unique_name(&self) -> String {
+        unsafe {
+            let name = dbus_bus_get_unique_name(self.0);
+            if name.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(name).to_string_lossy().into_owned()
+            }
+        }
+    }
+
+    /// Receive the signals that match the given rule
+    pub fn add_match(&self, rule: &str) -> Result<()> {
+        let rule = to_cstring(rule)?;
+        let mut error = DBusError::new();
+        unsafe {
+            dbus_bus_add_match(self.0, rule.as_ptr(), &mut error);
+        }
+        if error.is_set() {
+            return Err(error.into_io_error("failed to add the match rule"));
+        }
+        Ok(())
+    }
+
+    /// Call the given method and block until it returns, returns the values of the reply
+    pub fn call(
+        &self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: &[Value],
+    ) -> Result<Vec<Value>> {
+        let (destination, path, interface, method) = (
+            to_cstring(destination)?,
+            to_cstring(path)?,
+            to_cstring(interface)?,
+            to_cstring(method)?,
+        );
+
+        unsafe {
+            let message = dbus_message_new_method_call(
+                destination.as_ptr(),
+                path.as_ptr(),
+                interface.as_ptr(),
+                method.as_ptr(),
+            );
+            if message.is_null() {
+                return Err(Error::new(
+                    ErrorKind::OutOfMemory,
+                    "failed to create message",
+                ));
+            }
+            let message = Message(message);
+
+            let mut iter = DBusMessageIter::new();
+            dbus_message_iter_init_append(message.0, &mut iter);
+            for arg in args {
+                append(&mut iter, arg)?;
+            }
+
+            let mut error = DBusError::new();
+            let reply = dbus_connection_send_with_reply_and_block(
+                self.0,
+                message.0,
+                DBUS_TIMEOUT_USE_DEFAULT,
+                &mut error,
+            );
+            if reply.is_null() {
+                return Err(error.into_io_error("the method call failed"));
+            }
+            Ok(read_all(&Message(reply)))
+        }
+    }
+
+    /// Block until a signal of the given member is received from the given object,
+    /// returns the values of the signal
+    ///
+    /// The signal must be matched by a rule that is added with `add_match`
+    pub fn wait_signal(
+        &self,
+        path: &str,
+        interface: &str,
+        member: &str,
+        timeout: Duration,
+    ) -> Result<Vec<Value>> {
+        let (path, interface, member) = (
+            to_cstring(path)?,
+            to_cstring(interface)?,
+            to_cstring(member)?,
+        );
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            unsafe {
+                loop {
+                    let message = dbus_connection_pop_message(self.0);
+                    if message.is_null() {
+                        break;
+                    }
+                    let message = Message(message);
+                    let message_path = dbus_message_get_path(message.0);
+                    if dbus_message_is_signal(message.0, interface.as_ptr(), member.as_ptr()) != 0
+                        && !message_path.is_null()
+                        && CStr::from_ptr(message_path) == path.as_c_str()
+                    {
+                        return Ok(read_all(&message));
+                    }
+                }
+
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(Error::new(
+                        ErrorKind::TimedOut,
+                        "timed out while waiting for the signal",
+                    ));
+                }
+                if dbus_connection_read_write(self.0, remaining.as_millis() as c_int) == 0 {
+                    return Err(Error::new(
+                        ErrorKind::ConnectionAborted,
+                        "the connection to the session bus is closed",
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Get the fd of the connection, it becomes readable when a message arrives
+    pub fn fd(&self) -> Option<RawFd> {
+        let mut fd = -1;
+        match unsafe { dbus_connection_get_unix_fd(self.0, &mut fd) } {
+            0 => None,
+            _ => Some(fd),
+        }
+    }
+
+    /// Read the messages that arrived without blocking, returns the members and
+    /// the values of the signals of the given interface, the other messages are dropped
+    ///
+    /// The signals must be matched by a rule that is added with `add_match`
+    pub fn pop_signals(&self, interface: &str) -> Result<Vec<(String, Vec<Value>)>> {
+        let interface = to_cstring(interface)?;
+        let mut signals = Vec::new();
+        unsafe {
+            if dbus_connection_read_write(self.0, 0) == 0 {
+                return Err(Error::new(
+                    ErrorKind::ConnectionAborted,
+                    "the connection to the session bus is closed",
+                ));
+            }
+            loop {
+                let message = dbus_connection_pop_message(self.0);
+                if message.is_null() {
+                    break;
+                }
+                let message = Message(message);
+                let message_interface = dbus_message_get_interface(message.0);
+                let member = dbus_message_get_member(message.0);
+                if dbus_message_get_type(message.0) == DBUS_MESSAGE_TYPE_SIGNAL
+                    && !message_interface.is_null()
+                    && !member.is_null()
+                    && CStr::from_ptr(message_interface) == interface.as_c_str()
+                {
+                    let member = CStr::from_ptr(member).to_string_lossy().into_owned();
+                    signals.push((member, read_all(&message)));
+                }
+            }
+        }
+        Ok(signals)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            dbus_connection_close(self.0);
+            dbus_connection_unref(self.0);
+        }
+    }
+}
+
+/// Owns a message and releases it on drop
+struct Message(*mut DBusMessage);
+
+impl Drop for Message {
+    fn drop(&mut self) {
+        unsafe {
+            dbus_message_unref(self.0);
+        }
+    }
+}
+
+/// Append the given value to the message of the given iterator
+unsafe fn append(iter: &mut DBusMessageIter, value: &Value) -> Result<()> {
+    let appended = match value {
+        Value::Bool(value) => {
+            let value: c_uint = (*value).into();
+            append_basic(iter, DBUS_TYPE_BOOLEAN, &value)
+        }
+        Value::I32(value) => append_basic(iter, DBUS_TYPE_INT32, value),
+        Value::U32(value) => append_basic(iter, DBUS_TYPE_UINT32, value),
+        Value::F64(value) => append_basic(iter, DBUS_TYPE_DOUBLE, value),
+        Value::Str(value) => append_string(iter, DBUS_TYPE_STRING, value)?,
+        Value::Path(value) => append_string(iter, DBUS_TYPE_OBJECT_PATH, value)?,
+        Value::Fd(value) => append_basic(iter, DBUS_TYPE_UNIX_FD, value),
+        Value::Array(signature, values) => {
+            append_container(iter, DBUS_TYPE_ARRAY, Some(signature), values)?
+        }
+        Value::Struct(values) => append_container(iter, DBUS_TYPE_STRUCT, None, values)?,
+        Value::DictEntry(key, value) => append_container(
+            iter,
+            DBUS_TYPE_DICT_ENTRY,
+            None,
+            &[(**key).clone(), (**value).clone()],
+        )?,
+        Value::Variant(value) => append_container(
+            iter,
+            DBUS_TYPE_VARIANT,
+            Some(&value.signature()),
+            std::slice::from_ref(&**value),
+        )?,
+    };
+
+    if appended {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::OutOfMemory,
+            "failed to append the value",
+        ))
+    }
+}
+
+unsafe fn append_basic<T>(iter: &mut DBusMessageIter, r#type: c_int, value: &T) -> bool {
+    dbus_message_iter_append_basic(iter, r#type, value as *const T as *const c_void) != 0
+}
+
+/// The strings are appended as pointers to their C strings
+unsafe fn append_string(iter: &mut DBusMessageIter, r#type: c_int, value: &str) -> Result<bool> {
+    let string = to_cstring(value)?;
+    Ok(append_basic(iter, r#type, &string.as_ptr()))
+}
+
+unsafe fn append_container(
+    iter: &mut DBusMessageIter,
+    r#type: c_int,
+    signature: Option<&str>,
+    values: &[Value],
+) -> Result<bool> {
+    let signature = signature.map(to_cstring).transpose()?;
+    let mut sub = DBusMessageIter::new();
+    if dbus_message_iter_open_container(
+        iter,
+        r#type,
+        signature
+            .as_ref()
+            .map_or(null_mut(), |s| s.as_ptr() as *mut c_char),
+        &mut sub,
+    ) == 0
+    {
+        return Ok(false);
+    }
+    for value in values {
+        if let Err(err) = append(&mut sub, value) {
+            dbus_message_iter_abandon_container(iter, &mut sub);
+            return Err(err);
+        }
+    }
+    Ok(dbus_message_iter_close_container(iter, &mut sub) != 0)
+}
+
+/// Read the values of the given message
+unsafe fn read_all(message: &Message) -> Vec<Value> {
+    let mut iter = DBusMessageIter::new();
+    if dbus_message_iter_init(message.0, &mut iter) == 0 {
+        // The message has no values
+        return Vec::new();
+    }
+    read_values(&mut iter)
+}
+
+/// Read the values from the given iterator until its end
+unsafe fn read_values(iter: &mut DBusMessageIter) -> Vec<Value> {
+    let mut values = Vec::new();
+    loop {
+        if let Some(value) = read(iter) {
+            values.push(value);
+        }
+        if dbus_message_iter_next(iter) == 0 {
+            return values;
+        }
+    }
+}
+
+/// Read the current value of the given iterator, the unknown types are skipped
+unsafe fn read(iter: &mut DBusMessageIter) -> Option<Value> {
+    let r#type = dbus_message_iter_get_arg_type(iter);
+    let value = match r#type {
+        DBUS_TYPE_BOOLEAN => Value::Bool(read_basic::<c_uint>(iter) != 0),
+        DBUS_TYPE_INT32 => Value::I32(read_basic(iter)),
+        DBUS_TYPE_UINT32 => Value::U32(read_basic(iter)),
+        DBUS_TYPE_DOUBLE => Value::F64(read_basic(iter)),
+        DBUS_TYPE_UNIX_FD => Value::Fd(read_basic(iter)),
+        DBUS_TYPE_STRING | DBUS_TYPE_OBJECT_PATH => {
+            let string: *const c_char = read_basic(iter);
+            let string = CStr::from_ptr(string).to_string_lossy().into_owned();
+            if r#type == DBUS_TYPE_STRING {
+                Value::Str(string)
+            } else {
+                Value::Path(string)
+            }
+        }
+        DBUS_TYPE_ARRAY | DBUS_TYPE_STRUCT | DBUS_TYPE_DICT_ENTRY | DBUS_TYPE_VARIANT => {
+            let mut sub = DBusMessageIter::new();
+            dbus_message_iter_recurse(iter, &mut sub);
+            let values = if dbus_message_iter_get_arg_type(&mut sub) == DBUS_TYPE_INVALID {
+                Vec::new()
+            } else {
+                read_values(&mut sub)
+            };
+            match r#type {
+                DBUS_TYPE_ARRAY => {
+                    // The signature of the array itself, without the leading `a`
+                    let signature = dbus_message_iter_get_signature(iter);
+                    let element = CStr::from_ptr(signature).to_string_lossy()[1..].to_string();
+                    dbus_free(signature as *mut c_void);
+                    Value::Array(element, values)
+                }
+                DBUS_TYPE_STRUCT => Value::Struct(values),
+                DBUS_TYPE_DICT_ENTRY => {
+                    let mut values = values.into_iter();
+                    Value::DictEntry(Box::new(values.next()?), Box::new(values.next()?))
+                }
+                _ => Value::Variant(Box::new(values.into_iter().next()?)),
+            }
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+unsafe fn read_basic<T: Default>(iter: &mut DBusMessageIter) -> T {
+    let mut value = T::default();
+    dbus_message_iter_get_basic(iter, &mut value as *mut T as *mut c_void);
+    value
+}
+
+fn to_cstring(string: &str) -> Result<CString> {
+    CString::new(string).map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid string"))
+}
+
+impl DBusError {
+    fn new() -> Self {
+        let mut error = DBusError {
+            name: null_mut(),
+            message: null_mut(),
+            dummy: 0,
+            padding: null_mut(),
+        };
+        unsafe {
+            dbus_error_init(&mut error);
+        }
+        error
+    }
+
+    fn is_set(&self) -> bool {
+        unsafe { dbus_error_is_set(self) != 0 }
+    }
+
+    /// Convert the error to an io error, using the given message if no error is set
+    fn into_io_error(mut self, message: &str) -> Error {
+        let error = if self.is_set() && !self.message.is_null() {
+            let details = unsafe { CStr::from_ptr(self.message).to_string_lossy() };
+            Error::other(format!("{}: {}", message, details))
+        } else {
+            Error::other(message)
+        };
+        unsafe {
+            dbus_error_free(&mut self);
+        }
+        error
+    }
+}
+
+impl DBusMessageIter {
+    fn new() -> Self {
+        DBusMessageIter {
+            dummy1: null_mut(),
+            dummy2: null_mut(),
+            dummy3: 0,
+            dummy4: [0; 8],
+            pad1: 0,
+            pad2: null_mut(),
+            pad3: null_mut(),
+        }
+    }
+}
+
+/// libdbus definitions
+const DBUS_BUS_SESSION: c_int = 0;
+const DBUS_TIMEOUT_USE_DEFAULT: c_int = -1;
+const DBUS_MESSAGE_TYPE_SIGNAL: c_int = 4;
+const DBUS_TYPE_INVALID: c_int = 0;
+const DBUS_TYPE_BOOLEAN: c_int = b'b' as c_int;
+const DBUS_TYPE_INT32: c_int = b'i' as c_int;
+const DBUS_TYPE_UINT32: c_int = b'u' as c_int;
+const DBUS_TYPE_DOUBLE: c_int = b'd' as c_int;
+const DBUS_TYPE_STRING: c_int = b's' as c_int;
+const DBUS_TYPE_OBJECT_PATH: c_int = b'o' as c_int;
+const DBUS_TYPE_UNIX_FD: c_int = b'h' as c_int;
+const DBUS_TYPE_ARRAY: c_int = b'a' as c_int;
+const DBUS_TYPE_STRUCT: c_int = b'r' as c_int;
+const DBUS_TYPE_DICT_ENTRY: c_int = b'e' as c_int;
+const DBUS_TYPE_VARIANT: c_int = b'v' as c_int;
+
+/// libdbus types
+enum DBusConnection {}
+enum DBusMessage {}
+
+#[repr(C)]
+struct DBusError {
+    name: *const c_char,
+    message: *const c_char,
+    /// The bit fields of the error
+    dummy: c_uint,
+    padding: *mut c_void,
+}
+
+#[repr(C)]
+struct DBusMessageIter {
+    dummy1: *mut c_void,
+    dummy2: *mut c_void,
+    dummy3: u32,
+    dummy4: [c_int; 8],
+    pad1: c_int,
+    pad2: *mut c_void,
+    pad3: *mut c_void,
+}
+
+#[link(name = "dbus-1")]
+extern "C" {
+    fn dbus_error_init(error: *mut DBusError);
+    fn dbus_error_free(error: *mut DBusError);
+    fn dbus_error_is_set(error: *const DBusError) -> c_uint;
+    fn dbus_free(memory: *mut c_void);
+    fn dbus_bus_get_private(r#type: c_int, error: *mut DBusError) -> *mut DBusConnection;
+    fn dbus_bus_get_unique_name(connection: *mut DBusConnection) -> *const c_char;
+    fn dbus_bus_add_match(
+        connection: *mut DBusConnection,
+        rule: *const c_char,
+        error: *mut DBusError,
+    );
+    fn dbus_connection_set_exit_on_disconnect(connection: *mut DBusConnection, exit: c_uint);
+    fn dbus_connection_close(connection: *mut DBusConnection);
+    fn dbus_connection_unref(connection: *mut DBusConnection);
+    fn dbus_connection_send_with_reply_and_block(
+        connection: *mut DBusConnection,
+        message: *mut DBusMessage,
+        timeout_milliseconds: c_int,
+        error: *mut DBusError,
+    ) -> *mut DBusMessage;
+    fn dbus_connection_read_write(
+        connection: *mut DBusConnection,
+        timeout_milliseconds: c_int,
+    ) -> c_uint;
+    fn dbus_connection_pop_message(connection: *mut DBusConnection) -> *mut DBusMessage;
+    fn dbus_connection_get_unix_fd(connection: *mut DBusConnection, fd: *mut c_int) -> c_uint;
+    fn dbus_message_new_method_call(
+        destination: *const c_char,
+        path: *const c_char,
+        interface: *const c_char,
+        method: *const c_char,
+    ) -> *mut DBusMessage;
+    fn dbus_message_unref(message: *mut DBusMessage);
+    fn dbus_message_is_signal(
+        message: *mut DBusMessage,
+        interface: *const c_char,
+        signal_name: *const c_char,
+    ) -> c_uint;
+    fn dbus_message_get_path(message: *mut DBusMessage) -> *const c_char;
+    fn dbus_message_get_interface(message: *mut DBusMessage) -> *const c_char;
+    fn dbus_message_get_member(message: *mut DBusMessage) -> *const c_char;
+    fn dbus_message_get_type(message: *mut DBusMessage) -> c_int;
+    fn dbus_message_iter_init(message: *mut DBusMessage, iter: *mut DBusMessageIter) -> c_uint;
+    fn dbus_message_iter_init_append(message: *mut DBusMessage, iter: *mut DBusMessageIter);
+    fn dbus_message_iter_append_basic(
+        iter: *mut DBusMessageIter,
+        r#type: c_int,
+        value: *const c_void,
+    ) -> c_uint;
+    fn dbus_message_iter_open_container(
+        iter: *mut DBusMessageIter,
+        r#type: c_int,
+        contained_signature: *const c_char,
+        sub: *mut DBusMessageIter,
+    ) -> c_uint;
+    fn dbus_message_iter_close_container(
+        iter: *mut DBusMessageIter,
+        sub: *mut DBusMessageIter,
+    ) -> c_uint;
+    fn dbus_message_iter_abandon_container(iter: *mut DBusMessageIter, sub: *mut DBusMessageIter);
+    fn dbus_message_iter_get_arg_type(iter: *mut DBusMessageIter) -> c_int;
+    fn dbus_message_iter_get_basic(iter: *mut DBusMessageIter, value: *mut c_void);
+    fn dbus_message_iter_get_signature(iter: *mut DBusMessageIter) -> *mut c_char;
+    fn dbus_message_iter_recurse(iter: *mut DBusMessageIter, sub: *mut DBusMessageIter);
+    fn dbus_message_iter_next(iter: *mut DBusMessageIter) -> c_uint;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+
+    #[test]
+    fn value_signatures() {
+        let options = Value::dict(vec![
+            ("handle_token", Value::Str("mouce".to_string())),
+            ("capabilities", Value::U32(2)),
+        ]);
+        assert_eq!(options.signature(), "a{sv}");
+        assert_eq!(options.get("capabilities"), Some(&Value::U32(2)));
+        assert_eq!(options.get("missing"), None);
+
+        let position = Value::Struct(vec![Value::I32(0); 4]);
+        assert_eq!(position.signature(), "(iiii)");
+        let barriers = Value::Array("a{sv}".to_string(), Vec::new());
+        assert_eq!(barriers.signature(), "aa{sv}");
+        assert_eq!(Value::F64(0.5).signature(), "d");
+    }
+}
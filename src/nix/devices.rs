@@ -0,0 +1,274 @@
+///
+/// This module contains the enumeration of the mouse devices
+/// for the unix-like systems
+///
+/// The capabilities of the devices are read from their event
+/// files under /dev/input with the evdev ioctls
+///
+use crate::common::{ButtonState, MouseButton};
+use crate::devices::MouseDeviceInfo;
+use crate::error::Error;
+use crate::nix::uinput::{
+    InputAbsinfo, InputId, BTN_BACK, BTN_EXTRA, BTN_FORWARD, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT,
+    BTN_SIDE, BTN_TASK, EV_KEY, EV_REL, REL_HWHEEL, REL_WHEEL, REL_X, REL_Y,
+};
+use glob::glob;
+use std::{
+    fs::File,
+    io::{self, ErrorKind},
+    mem::size_of,
+    os::{
+        raw::{c_int, c_uint, c_ulong},
+        unix::io::AsRawFd,
+    },
+};
+
+/// The event files of all the input devices
+pub const EVENT_FILES: &str = "/dev/input/event*";
+
+/// List the mouse devices, the devices that report relative
+/// movements and have a left button are considered as a mouse
+pub fn list_mice() -> Result<Vec<MouseDeviceInfo>, Error> {
+    let mut mice = Vec::new();
+    let mut permission_denied = false;
+
+    for file in glob(EVENT_FILES).expect("Failed to read glob pattern") {
+        let path = match file {
+            Ok(path) => path.display().to_string(),
+            Err(_) => continue,
+        };
+        let event = match File::options().read(true).open(&path) {
+            Ok(event) => event,
+            Err(err) => {
+                permission_denied |= err.kind() == ErrorKind::PermissionDenied;
+                continue;
+            }
+        };
+
+        // Skip the devices that are disconnected in the meantime
+        if let Ok(Some(mouse)) = read_device_info(&event, path) {
+            mice.push(mouse);
+        }
+    }
+
+    // Most likely none of the devices is readable without the user privileges
+    if mice.is_empty() && permission_denied {
+        return Err(Error::PermissionDenied);
+    }
+    Ok(mice)
+}
+
+/// Get the buttons that are held down on any of the mice
+pub fn button_state() -> Result<ButtonState, Error> {
+    let mut held = [0u8; KEY_BITS_SIZE];
+    let mut read = false;
+    let mut permission_denied = false;
+
+    for path in glob(EVENT_FILES)
+        .expect("Failed to read glob pattern")
+        .flatten()
+    {
+        let event = match File::options().read(true).open(&path) {
+            Ok(event) => event,
+            Err(err) => {
+                permission_denied |= err.kind() == ErrorKind::PermissionDenied;
+                continue;
+            }
+        };
+        if !is_mouse(&event) {
+            continue;
+        }
+
+        let mut bits = [0u8; KEY_BITS_SIZE];
+        let request = eviocgkey(bits.len());
+        if unsafe { ioctl(event.as_raw_fd(), request, bits.as_mut_ptr()) } < 0 {
+            continue;
+        }
+        for (held, bits) in held.iter_mut().zip(bits) {
+            *held |= bits;
+        }
+        read = true;
+    }
+
+    if !read && permission_denied {
+        return Err(Error::PermissionDenied);
+    }
+    Ok(pressed_buttons(&held))
+}
+
+/// Get the buttons whose bits are set in the given key state
+fn pressed_buttons(bits: &[u8]) -> ButtonState {
+    let pressed = [
+        (BTN_LEFT, MouseButton::Left),
+        (BTN_RIGHT, MouseButton::Right),
+        (BTN_MIDDLE, MouseButton::Middle),
+        (BTN_SIDE, MouseButton::Side),
+        (BTN_EXTRA, MouseButton::Extra),
+        (BTN_FORWARD, MouseButton::Forward),
+        (BTN_BACK, MouseButton::Back),
+        (BTN_TASK, MouseButton::Task),
+    ]
+    .into_iter()
+    .filter(|(code, _)| bits[*code as usize / 8] & (1 << (code % 8)) != 0)
+    .map(|(_, button)| button)
+    .collect();
+    ButtonState { pressed }
+}
+
+/// Check whether the device of the given event file is a mouse, like `list_mice`
+pub fn is_mouse(event: &File) -> bool {
+    matches!(read_device_info(event, String::new()), Ok(Some(_)))
+}
+
+/// Read the information of the given event file, returns None if it is not a mouse
+fn read_device_info(event: &File, path: String) -> io::Result<Option<MouseDeviceInfo>> {
+    let rel_bits = event_bits(event, EV_REL)?;
+    let key_bits = event_bits(event, EV_KEY)?;
+    let has = |bits: &[u8], code: c_int| bits[code as usize / 8] & (1 << (code % 8)) != 0;
+
+    if !has(&rel_bits, REL_X as c_int)
+        || !has(&rel_bits, REL_Y as c_int)
+        || !has(&key_bits, BTN_LEFT)
+    {
+        return Ok(None);
+    }
+
+    let mut id = InputId {
+        bustype: 0,
+        vendor: 0,
+        product: 0,
+        version: 0,
+    };
+    if unsafe { ioctl(event.as_raw_fd(), EVIOCGID, &mut id) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Some(MouseDeviceInfo {
+        name: device_name(event).unwrap_or_default(),
+        vendor_id: id.vendor,
+        product_id: id.product,
+        path,
+        wheel: has(&rel_bits, REL_WHEEL as c_int),
+        hwheel: has(&rel_bits, REL_HWHEEL as c_int),
+        extra_buttons: [BTN_SIDE, BTN_EXTRA, BTN_FORWARD, BTN_BACK, BTN_TASK]
+            .iter()
+            .any(|code| has(&key_bits, *code)),
+    }))
+}
+
+/// Get the name that the device of the given event file reports
+pub fn device_name(event: &File) -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let request = eviocgname(buffer.len());
+    if unsafe { ioctl(event.as_raw_fd(), request, buffer.as_mut_ptr()) } < 0 {
+        return None;
+    }
+
+    let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    Some(String::from_utf8_lossy(&buffer[..len]).into_owned())
+}
+
+/// Get the buttons that the device of the given event file has
+pub fn device_buttons(event: &File) -> io::Result<Vec<MouseButton>> {
+    Ok(pressed_buttons(&event_bits(event, EV_KEY)?).pressed)
+}
+
+/// Get the minimum and the maximum values of the given absolute axis,
+/// None if the device does not report the axis
+pub fn abs_range(event: &File, code: c_uint) -> Option<(i32, i32)> {
+    abs_info(event, code)
+        .filter(|absinfo| absinfo.minimum < absinfo.maximum)
+        .map(|absinfo| (absinfo.minimum, absinfo.maximum))
+}
+
+/// Get the last value of the given absolute axis, None if the device does not report the axis
+pub fn abs_value(event: &File, code: c_uint) -> Option<i32> {
+    abs_info(event, code)
+        .filter(|absinfo| absinfo.minimum < absinfo.maximum)
+        .map(|absinfo| absinfo.value)
+}
+
+/// Get the state of the given absolute axis with EVIOCGABS
+fn abs_info(event: &File, code: c_uint) -> Option<InputAbsinfo> {
+    let mut absinfo = InputAbsinfo {
+        value: 0,
+        minimum: 0,
+        maximum: 0,
+        fuzz: 0,
+        flat: 0,
+        resolution: 0,
+    };
+    let request = eviocgabs(code);
+    if unsafe { ioctl(event.as_raw_fd(), request, &mut absinfo) } < 0 {
+        return None;
+    }
+    Some(absinfo)
+}
+
+/// Get the bitmask of the codes that the device supports for the given event type
+fn event_bits(event: &File, r#type: c_int) -> io::Result<[u8; KEY_BITS_SIZE]> {
+    let mut bits = [0u8; KEY_BITS_SIZE];
+    let request = eviocgbit(r#type, bits.len());
+    if unsafe { ioctl(event.as_raw_fd(), request, bits.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(bits)
+}
+
+/// evdev ioctl definitions
+const IOC_READ: c_ulong = 2;
+const EVIOCGID: c_ulong = ioc_read(0x02, 8);
+/// (KEY_MAX + 1) / 8, large enough for the bits of all the event types
+const KEY_BITS_SIZE: usize = 96;
+
+const fn ioc_read(nr: c_ulong, size: usize) -> c_ulong {
+    (IOC_READ << 30) | ((size as c_ulong) << 16) | ((b'E' as c_ulong) << 8) | nr
+}
+
+const fn eviocgname(len: usize) -> c_ulong {
+    ioc_read(0x06, len)
+}
+
+const fn eviocgabs(code: c_uint) -> c_ulong {
+    ioc_read(0x40 + code as c_ulong, size_of::<InputAbsinfo>())
+}
+
+const fn eviocgkey(len: usize) -> c_ulong {
+    ioc_read(0x18, len)
+}
+
+const fn eviocgbit(r#type: c_int, len: usize) -> c_ulong {
+    ioc_read(0x20 + r#type as c_ulong, len)
+}
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eviocgabs, eviocgbit, eviocgkey, eviocgname, pressed_buttons, EVIOCGID};
+    use crate::common::MouseButton;
+    use crate::nix::uinput::{ABS_Y, BTN_LEFT, BTN_SIDE, EV_KEY};
+
+    #[test]
+    fn ioctl_requests() {
+        // The values of the macros in linux/input.h
+        assert_eq!(EVIOCGID, 0x80084502);
+        assert_eq!(eviocgname(256), 0x81004506);
+        assert_eq!(eviocgbit(EV_KEY, 96), 0x80604521);
+        assert_eq!(eviocgabs(ABS_Y), 0x80184541);
+        assert_eq!(eviocgkey(96), 0x80604518);
+    }
+
+    #[test]
+    fn pressed_bits() {
+        let mut bits = [0u8; 96];
+        for code in [BTN_LEFT, BTN_SIDE] {
+            bits[code as usize / 8] |= 1 << (code % 8);
+        }
+        let state = pressed_buttons(&bits);
+        assert_eq!(state.pressed, vec![MouseButton::Left, MouseButton::Side]);
+        assert!(!state.is_pressed(&MouseButton::Right));
+    }
+}
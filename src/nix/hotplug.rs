@@ -0,0 +1,162 @@
+///
+/// This module contains the hotplug watcher for the nix event listener
+///
+/// The watcher uses inotify to get notified whenever a mouse event file
+/// appears under /dev/input/by-id (/dev/input on Android), so the mice that are connected after
+/// the listener is started are read as well. Its fd is polled by the
+/// reader thread of the listener together with the fds of the mice
+///
+use crate::nix::{is_mouse_file, MICE_DIR};
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{Error, Read, Result},
+    os::{
+        raw::{c_char, c_int, c_uint},
+        unix::io::{AsRawFd, FromRawFd, RawFd},
+    },
+    path::Path,
+    time::Duration,
+};
+
+/// How many times to try opening a newly connected device
+pub const OPEN_ATTEMPTS: usize = 10;
+/// udev may not have set the permissions of a new device file yet,
+/// wait a bit between the attempts to open it
+pub const OPEN_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// A change of the mouse event files under MICE_DIR
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Connected(String),
+    Disconnected(String),
+}
+
+/// Watches MICE_DIR for the mice that are connected or disconnected
+pub struct HotplugWatcher {
+    inotify: File,
+}
+
+impl HotplugWatcher {
+    /// Start watching MICE_DIR, returns None if there is nothing to watch
+    pub fn new() -> Result<Option<Self>> {
+        // The directory is created by udev with the first input device,
+        // there is nothing to watch if it does not exist yet
+        if !Path::new(MICE_DIR).exists() {
+            return Ok(None);
+        }
+
+        let fd = unsafe { inotify_init1(IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        // The file takes the ownership of the fd and closes it on drop
+        let inotify = unsafe { File::from_raw_fd(fd) };
+
+        let dir = CString::new(MICE_DIR).unwrap();
+        // udev creates the symlinks with a temporary name and then renames them
+        let mask = IN_CREATE | IN_MOVED_TO | IN_DELETE;
+        if unsafe { inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(Some(HotplugWatcher { inotify }))
+    }
+
+    /// Read the changes of the mouse event files, once the fd is readable
+    pub fn read_changes(&mut self) -> Result<Vec<Change>> {
+        let mut buffer = [0u8; 4096];
+        let len = self.inotify.read(&mut buffer)?;
+        Ok(parse_events(&buffer[..len])
+            .into_iter()
+            .filter(|(_, name)| is_mouse_file(name))
+            .map(|(mask, name)| {
+                let path = format!("{}/{}", MICE_DIR, name);
+                if mask & IN_DELETE != 0 {
+                    Change::Disconnected(path)
+                } else {
+                    Change::Connected(path)
+                }
+            })
+            .collect())
+    }
+}
+
+impl AsRawFd for HotplugWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
+
+/// Parse the `mask` and `name` fields of the inotify events in the given buffer
+fn parse_events(buffer: &[u8]) -> Vec<(u32, String)> {
+    let mut events = Vec::new();
+    let mut offset = 0;
+
+    // struct inotify_event { int wd; uint32_t mask; uint32_t cookie; uint32_t len; char name[]; }
+    while offset + EVENT_HEADER_SIZE <= buffer.len() {
+        let field = |index: usize| {
+            let start = offset + index * 4;
+            u32::from_ne_bytes(buffer[start..start + 4].try_into().unwrap())
+        };
+        let mask = field(1);
+        let name_len = field(3) as usize;
+
+        let name_start = offset + EVENT_HEADER_SIZE;
+        let name_end = (name_start + name_len).min(buffer.len());
+        // The name is null terminated and may be padded with more null bytes
+        let name: Vec<u8> = buffer[name_start..name_end]
+            .iter()
+            .take_while(|byte| **byte != 0)
+            .cloned()
+            .collect();
+        events.push((mask, String::from_utf8_lossy(&name).to_string()));
+
+        offset = name_start + name_len;
+    }
+
+    events
+}
+
+/// inotify definitions
+const EVENT_HEADER_SIZE: usize = 16;
+const IN_CLOEXEC: c_int = 0o2000000;
+const IN_MOVED_TO: u32 = 0x00000080;
+const IN_CREATE: u32 = 0x00000100;
+const IN_DELETE: u32 = 0x00000200;
+
+extern "C" {
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: c_uint) -> c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_events, IN_CREATE, IN_DELETE};
+
+    fn event(mask: u32, name: &str, name_len: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for field in [1, mask, 0, name_len as u32] {
+            bytes.extend_from_slice(&field.to_ne_bytes());
+        }
+        let mut name = name.as_bytes().to_vec();
+        name.resize(name_len, 0);
+        bytes.extend(name);
+        bytes
+    }
+
+    #[test]
+    fn parse_multiple_events() {
+        let mut buffer = event(IN_CREATE, "usb-mouse-event-mouse", 32);
+        buffer.extend(event(IN_DELETE, "usb-keyboard-event-kbd", 32));
+
+        let events = parse_events(&buffer);
+        assert_eq!(
+            events,
+            vec![
+                (IN_CREATE, "usb-mouse-event-mouse".to_string()),
+                (IN_DELETE, "usb-keyboard-event-kbd".to_string()),
+            ]
+        );
+    }
+}
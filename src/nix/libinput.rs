@@ -0,0 +1,355 @@
+///
+/// This module contains the libinput based event listener
+/// for the unix-like systems
+///
+/// libinput takes care of the device enumeration through udev and only
+/// reports the pointer events of the devices that are assigned to the
+/// given seat, so the listener does not need to glob the event files
+/// under /dev/input by itself
+///
+use crate::common::{MouseEvent, MouseEventExt, ScrollDirection};
+use crate::nix::listener::Listener;
+use crate::nix::{Callbacks, Ranges};
+use std::{
+    env,
+    ffi::{CStr, CString},
+    io::{Error, ErrorKind, Result},
+    os::raw::{c_char, c_double, c_int, c_uint, c_void},
+    ptr::null_mut,
+    time::SystemTime,
+};
+
+/// The seat that is used when `XDG_SEAT` is not set
+const DEFAULT_SEAT: &str = "seat0";
+/// The rotation of the mouse wheels per detent that libinput assumes
+const DEGREES_PER_DETENT: c_double = 15.;
+
+/// Start the libinput event listener
+///
+/// The seat is read from the `XDG_SEAT` environment variable, if it is
+/// not set the default seat (`seat0`) is used
+pub fn start_libinput_listener(callbacks: &Callbacks, ranges: Ranges) -> Result<Listener> {
+    let listener = Listener::new()?;
+    let seat = env::var("XDG_SEAT").unwrap_or_else(|_| DEFAULT_SEAT.to_string());
+    let seat =
+        CString::new(seat).map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid seat name"))?;
+
+    let context = unsafe {
+        let udev = udev_new();
+        if udev.is_null() {
+            return Err(Error::other("failed to create udev context"));
+        }
+
+        let libinput = libinput_udev_create_context(&INTERFACE, null_mut(), udev);
+        // libinput keeps its own reference to the udev context
+        udev_unref(udev);
+        if libinput.is_null() {
+            return Err(Error::other("failed to create libinput context"));
+        }
+
+        if libinput_udev_assign_seat(libinput, seat.as_ptr()) != 0 {
+            libinput_unref(libinput);
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "failed to assign the seat to the libinput context",
+            ));
+        }
+
+        Context(libinput)
+    };
+
+    let mut dispatcher = callbacks.dispatcher();
+    let handle = listener.handle();
+    let mut counter = handle.counter();
+    // Create a thread for reading and handling the libinput events
+    listener.handle().spawn(move || {
+        let context = context;
+        let fd = unsafe { libinput_get_fd(context.0) };
+
+        while handle.wait(fd) {
+            unsafe {
+                libinput_dispatch(context.0);
+                loop {
+                    let event = libinput_get_event(context.0);
+                    if event.is_null() {
+                        break;
+                    }
+
+                    // The axis events also report the combined scroll of both axes
+                    let mouse_events: Vec<MouseEvent> = to_mouse_event(event, ranges)
+                        .into_iter()
+                        .chain(to_scroll_2d(event))
+                        .collect();
+                    let device = libinput_event_get_device(event);
+                    let (device_id, device_name) = (
+                        format!(
+                            "/dev/input/{}",
+                            to_string(libinput_device_get_sysname(device))
+                        ),
+                        to_string(libinput_device_get_name(device)),
+                    );
+                    libinput_event_destroy(event);
+
+                    // libinput uses a monotonic clock for the event times,
+                    // so stamp the events with the time they are received
+                    let time = SystemTime::now();
+                    for mouse_event in mouse_events {
+                        let event = MouseEventExt {
+                            device_id: device_id.clone(),
+                            device_name: device_name.clone(),
+                            event: mouse_event,
+                            time,
+                        };
+
+                        super::dispatch(&mut dispatcher, &mut counter, &event);
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(listener)
+}
+
+/// Construct the library's MouseEvent from the given libinput event
+///
+/// The absolute positions are scaled to the given ranges
+unsafe fn to_mouse_event(event: *mut LibinputEvent, ranges: Ranges) -> Option<MouseEvent> {
+    let r#type = libinput_event_get_type(event);
+    match r#type {
+        LIBINPUT_EVENT_POINTER_MOTION
+        | LIBINPUT_EVENT_POINTER_MOTION_ABSOLUTE
+        | LIBINPUT_EVENT_POINTER_BUTTON
+        | LIBINPUT_EVENT_POINTER_AXIS
+        | LIBINPUT_EVENT_POINTER_SCROLL_WHEEL => {}
+        // Ignore the non pointer events
+        _ => return None,
+    }
+
+    let pointer = libinput_event_get_pointer_event(event);
+    match r#type {
+        LIBINPUT_EVENT_POINTER_MOTION => Some(MouseEvent::RelativeMove(
+            libinput_event_pointer_get_dx(pointer) as i32,
+            libinput_event_pointer_get_dy(pointer) as i32,
+        )),
+        LIBINPUT_EVENT_POINTER_MOTION_ABSOLUTE => {
+            let ((min_x, max_x), (min_y, max_y)) = ranges;
+            Some(MouseEvent::AbsoluteMove(
+                min_x
+                    + libinput_event_pointer_get_absolute_x_transformed(
+                        pointer,
+                        (max_x - min_x) as c_uint,
+                    ) as i32,
+                min_y
+                    + libinput_event_pointer_get_absolute_y_transformed(
+                        pointer,
+                        (max_y - min_y) as c_uint,
+                    ) as i32,
+            ))
+        }
+        LIBINPUT_EVENT_POINTER_BUTTON => {
+            let button = super::map_button(libinput_event_pointer_get_button(pointer) as c_int)?;
+            if libinput_event_pointer_get_button_state(pointer) == LIBINPUT_BUTTON_STATE_PRESSED {
+                Some(MouseEvent::Press(button))
+            } else {
+                Some(MouseEvent::Release(button))
+            }
+        }
+        LIBINPUT_EVENT_POINTER_AXIS => {
+            // Unlike evdev, libinput reports positive values when
+            // scrolling down and right
+            if libinput_event_pointer_has_axis(pointer, LIBINPUT_POINTER_AXIS_SCROLL_VERTICAL) != 0
+            {
+                let value = libinput_event_pointer_get_axis_value(
+                    pointer,
+                    LIBINPUT_POINTER_AXIS_SCROLL_VERTICAL,
+                );
+                Some(MouseEvent::Scroll(if value < 0. {
+                    ScrollDirection::Up
+                } else {
+                    ScrollDirection::Down
+                }))
+            } else if libinput_event_pointer_has_axis(
+                pointer,
+                LIBINPUT_POINTER_AXIS_SCROLL_HORIZONTAL,
+            ) != 0
+            {
+                let value = libinput_event_pointer_get_axis_value(
+                    pointer,
+                    LIBINPUT_POINTER_AXIS_SCROLL_HORIZONTAL,
+                );
+                Some(MouseEvent::Scroll(if value < 0. {
+                    ScrollDirection::Left
+                } else {
+                    ScrollDirection::Right
+                }))
+            } else {
+                None
+            }
+        }
+        LIBINPUT_EVENT_POINTER_SCROLL_WHEEL => {
+            // The wheel events are reported in the units of 1/120 of a detent,
+            // the vertical axis is flipped to match the direction of evdev
+            let v120 = |axis| {
+                if libinput_event_pointer_has_axis(pointer, axis) != 0 {
+                    libinput_event_pointer_get_scroll_value_v120(pointer, axis) as i32
+                } else {
+                    0
+                }
+            };
+            Some(MouseEvent::ScrollHiRes {
+                dx: v120(LIBINPUT_POINTER_AXIS_SCROLL_HORIZONTAL),
+                dy: -v120(LIBINPUT_POINTER_AXIS_SCROLL_VERTICAL),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Construct the combined scroll of both axes from the given libinput event
+///
+/// The axis values are in degrees of the wheel rotation, which
+/// libinput reports as 15 degrees per detent for the most mice
+unsafe fn to_scroll_2d(event: *mut LibinputEvent) -> Option<MouseEvent> {
+    if libinput_event_get_type(event) != LIBINPUT_EVENT_POINTER_AXIS {
+        return None;
+    }
+
+    let pointer = libinput_event_get_pointer_event(event);
+    let detents = |axis| {
+        if libinput_event_pointer_has_axis(pointer, axis) != 0 {
+            (libinput_event_pointer_get_axis_value(pointer, axis) / DEGREES_PER_DETENT).round()
+                as i32
+        } else {
+            0
+        }
+    };
+    // The vertical axis is flipped to match the direction of evdev
+    let (dx, dy) = (
+        detents(LIBINPUT_POINTER_AXIS_SCROLL_HORIZONTAL),
+        -detents(LIBINPUT_POINTER_AXIS_SCROLL_VERTICAL),
+    );
+    if dx == 0 && dy == 0 {
+        return None;
+    }
+    Some(MouseEvent::Scroll2D { dx, dy })
+}
+
+/// Copy the given C string that is owned by libinput
+unsafe fn to_string(string: *const c_char) -> String {
+    if string.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(string).to_string_lossy().into_owned()
+}
+
+/// Owns the libinput context, so that it can be moved into the listener thread
+struct Context(*mut Libinput);
+
+unsafe impl Send for Context {}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe {
+            libinput_unref(self.0);
+        }
+    }
+}
+
+/// libinput asks for the device files through these functions, so
+/// it is possible to open them without the help of a session manager
+static INTERFACE: LibinputInterface = LibinputInterface {
+    open_restricted: Some(open_restricted),
+    close_restricted: Some(close_restricted),
+};
+
+unsafe extern "C" fn open_restricted(path: *const c_char, flags: c_int, _: *mut c_void) -> c_int {
+    let fd = open(path, flags);
+    if fd < 0 {
+        // libinput expects a negative errno on failure
+        -Error::last_os_error().raw_os_error().unwrap_or(0)
+    } else {
+        fd
+    }
+}
+
+unsafe extern "C" fn close_restricted(fd: c_int, _: *mut c_void) {
+    close(fd);
+}
+
+/// libinput definitions
+const LIBINPUT_EVENT_POINTER_MOTION: c_int = 400;
+const LIBINPUT_EVENT_POINTER_MOTION_ABSOLUTE: c_int = 401;
+const LIBINPUT_EVENT_POINTER_BUTTON: c_int = 402;
+const LIBINPUT_EVENT_POINTER_AXIS: c_int = 403;
+const LIBINPUT_EVENT_POINTER_SCROLL_WHEEL: c_int = 404;
+const LIBINPUT_BUTTON_STATE_PRESSED: c_int = 1;
+const LIBINPUT_POINTER_AXIS_SCROLL_VERTICAL: c_int = 0;
+const LIBINPUT_POINTER_AXIS_SCROLL_HORIZONTAL: c_int = 1;
+
+/// libinput types
+enum Udev {}
+enum Libinput {}
+enum LibinputEvent {}
+enum LibinputEventPointer {}
+enum LibinputDevice {}
+
+#[repr(C)]
+struct LibinputInterface {
+    open_restricted: Option<
+        unsafe extern "C" fn(path: *const c_char, flags: c_int, user_data: *mut c_void) -> c_int,
+    >,
+    close_restricted: Option<unsafe extern "C" fn(fd: c_int, user_data: *mut c_void)>,
+}
+
+#[link(name = "udev")]
+extern "C" {
+    fn udev_new() -> *mut Udev;
+    fn udev_unref(udev: *mut Udev) -> *mut Udev;
+}
+
+#[link(name = "input")]
+extern "C" {
+    fn libinput_udev_create_context(
+        interface: *const LibinputInterface,
+        user_data: *mut c_void,
+        udev: *mut Udev,
+    ) -> *mut Libinput;
+    fn libinput_udev_assign_seat(libinput: *mut Libinput, seat_id: *const c_char) -> c_int;
+    fn libinput_unref(libinput: *mut Libinput) -> *mut Libinput;
+    fn libinput_get_fd(libinput: *mut Libinput) -> c_int;
+    fn libinput_dispatch(libinput: *mut Libinput) -> c_int;
+    fn libinput_get_event(libinput: *mut Libinput) -> *mut LibinputEvent;
+    fn libinput_event_get_type(event: *mut LibinputEvent) -> c_int;
+    fn libinput_event_destroy(event: *mut LibinputEvent);
+    fn libinput_event_get_device(event: *mut LibinputEvent) -> *mut LibinputDevice;
+    fn libinput_device_get_name(device: *mut LibinputDevice) -> *const c_char;
+    fn libinput_device_get_sysname(device: *mut LibinputDevice) -> *const c_char;
+    fn libinput_event_get_pointer_event(event: *mut LibinputEvent) -> *mut LibinputEventPointer;
+    fn libinput_event_pointer_get_dx(event: *mut LibinputEventPointer) -> c_double;
+    fn libinput_event_pointer_get_dy(event: *mut LibinputEventPointer) -> c_double;
+    fn libinput_event_pointer_get_absolute_x_transformed(
+        event: *mut LibinputEventPointer,
+        width: c_uint,
+    ) -> c_double;
+    fn libinput_event_pointer_get_absolute_y_transformed(
+        event: *mut LibinputEventPointer,
+        height: c_uint,
+    ) -> c_double;
+    fn libinput_event_pointer_get_button(event: *mut LibinputEventPointer) -> c_uint;
+    fn libinput_event_pointer_get_button_state(event: *mut LibinputEventPointer) -> c_int;
+    fn libinput_event_pointer_has_axis(event: *mut LibinputEventPointer, axis: c_int) -> c_int;
+    fn libinput_event_pointer_get_axis_value(
+        event: *mut LibinputEventPointer,
+        axis: c_int,
+    ) -> c_double;
+    fn libinput_event_pointer_get_scroll_value_v120(
+        event: *mut LibinputEventPointer,
+        axis: c_int,
+    ) -> c_double;
+}
+
+extern "C" {
+    fn open(path: *const c_char, flags: c_int, ...) -> c_int;
+    fn close(fd: c_int) -> c_int;
+}
@@ -0,0 +1,342 @@
+///
+/// This module contains the thread management of the nix event listeners
+///
+/// Every thread of a listener waits on its file descriptors together with
+/// the read end of a pipe, e.g. the evdev listener waits on all the mice in a
+/// single thread. Stopping the listener writes to the pipe, which
+/// wakes up all the threads so they can exit, and then joins them
+///
+use crate::common::{ListenerStats, MouseEventExt};
+use crate::hooks::LockUnpoisoned;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    mem,
+    os::{
+        raw::{c_int, c_short, c_ulong},
+        unix::io::{AsRawFd, FromRawFd, RawFd},
+    },
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+struct Inner {
+    stop_rx: File,
+    stop_tx: File,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+    counters: Counters,
+}
+
+/// The counters of a listener that its threads update, see `ListenerStats`
+#[derive(Default)]
+pub struct Counters {
+    pub events_received: AtomicU64,
+    pub events_dropped: AtomicU64,
+    pub callbacks_invoked: AtomicU64,
+    pub restarts: AtomicU64,
+    /// The dispatching threads keep their own handles to the counts, so the
+    /// map is only locked for the first event of a device
+    per_device_counts: Mutex<HashMap<String, Arc<AtomicU64>>>,
+    /// Microseconds since the epoch, zero until the first event
+    last_event_time: AtomicU64,
+}
+
+/// Counts the events of a dispatching thread in the counters of its listener
+pub struct EventCounter {
+    listener: ListenerHandle,
+    devices: HashMap<String, Arc<AtomicU64>>,
+}
+
+impl EventCounter {
+    /// Count the given event that is delivered to the given number of callbacks
+    pub fn record(&mut self, event: &MouseEventExt, invoked: usize) {
+        let counters = self.listener.counters();
+        counters.events_received.fetch_add(1, Ordering::Relaxed);
+        counters
+            .callbacks_invoked
+            .fetch_add(invoked as u64, Ordering::Relaxed);
+        // The id is only copied for the first event of a device
+        let count = match self.devices.get(&event.device_id) {
+            Some(count) => count,
+            None => {
+                let count = counters
+                    .per_device_counts
+                    .lock_unpoisoned()
+                    .entry(event.device_id.clone())
+                    .or_default()
+                    .clone();
+                self.devices.entry(event.device_id.clone()).or_insert(count)
+            }
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        let micros = event
+            .time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |time| time.as_micros() as u64);
+        counters.last_event_time.store(micros, Ordering::Relaxed);
+    }
+}
+
+/// A running listener, its threads are stopped and joined on drop
+pub struct Listener {
+    handle: ListenerHandle,
+}
+
+impl Listener {
+    pub fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // The files take the ownership of the fds and close them on drop
+        let (stop_rx, stop_tx) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        Ok(Listener {
+            handle: ListenerHandle(Arc::new(Inner {
+                stop_rx,
+                stop_tx,
+                threads: Mutex::new(Vec::new()),
+                counters: Counters::default(),
+            })),
+        })
+    }
+
+    /// Get a handle that can be moved into the listener threads
+    pub fn handle(&self) -> ListenerHandle {
+        self.handle.clone()
+    }
+
+    /// Get the statistics of the listener since it is started
+    pub fn stats(&self) -> ListenerStats {
+        let counters = self.handle.counters();
+        ListenerStats {
+            events_received: counters.events_received.load(Ordering::Relaxed),
+            events_dropped: counters.events_dropped.load(Ordering::Relaxed),
+            callbacks_invoked: counters.callbacks_invoked.load(Ordering::Relaxed),
+            per_device_counts: counters
+                .per_device_counts
+                .lock_unpoisoned()
+                .iter()
+                .map(|(device, count)| (device.clone(), count.load(Ordering::Relaxed)))
+                .collect(),
+            last_event_time: match counters.last_event_time.load(Ordering::Relaxed) {
+                0 => None,
+                micros => Some(SystemTime::UNIX_EPOCH + Duration::from_micros(micros)),
+            },
+            // A thread that exits early e.g. after a read failure leaves the listener dead
+            running: self
+                .handle
+                .0
+                .threads
+                .lock_unpoisoned()
+                .iter()
+                .any(|thread| !thread.is_finished()),
+            restarts: counters.restarts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        // Nobody reads from the pipe, so it stays readable
+        // and every thread notices that the listener is stopped
+        let _ = (&self.handle.0.stop_tx).write_all(&[1]);
+
+        // Threads may spawn new threads (e.g. the hotplug watcher)
+        // while the others are being joined, so repeat until none is left
+        loop {
+            let threads: Vec<JoinHandle<()>> =
+                self.handle.0.threads.lock_unpoisoned().drain(..).collect();
+            if threads.is_empty() {
+                break;
+            }
+
+            for thread in threads {
+                // Avoid a deadlock when the listener is stopped from a callback
+                if thread.thread().id() != thread::current().id() {
+                    let _ = thread.join();
+                }
+            }
+        }
+    }
+}
+
+/// A cloneable handle of a listener that is used by its threads
+#[derive(Clone)]
+pub struct ListenerHandle(Arc<Inner>);
+
+impl ListenerHandle {
+    pub fn counters(&self) -> &Counters {
+        &self.0.counters
+    }
+
+    /// Get a counter for the thread that dispatches the events
+    pub fn counter(&self) -> EventCounter {
+        EventCounter {
+            listener: self.clone(),
+            devices: HashMap::new(),
+        }
+    }
+
+    /// Spawn a new thread that belongs to this listener
+    pub fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let thread = thread::spawn(f);
+        let mut threads = self.0.threads.lock_unpoisoned();
+        // Reap the threads that are already finished e.g. the readers of the disconnected devices
+        let (finished, running) = mem::take(&mut *threads)
+            .into_iter()
+            .partition::<Vec<_>, _>(|thread| thread.is_finished());
+        *threads = running;
+        threads.push(thread);
+        drop(threads);
+        for thread in finished {
+            let _ = thread.join();
+        }
+    }
+
+    /// Block until the given fd is readable
+    ///
+    /// Returns false if the listener is stopped in the meantime
+    #[cfg_attr(not(any(feature = "libinput", feature = "xinput2")), allow(dead_code))]
+    pub fn wait(&self, fd: RawFd) -> bool {
+        self.wait_any(&[fd], None).is_some()
+    }
+
+    /// Block until any of the given fds is readable or the timeout elapses
+    ///
+    /// Returns whether each of the fds is ready, or None if the listener is stopped
+    /// in the meantime. The fds that are closed or invalid are ready as well,
+    /// the following read reports the error to the caller
+    pub fn wait_any(&self, fds: &[RawFd], timeout: Option<Duration>) -> Option<Vec<bool>> {
+        let mut poll_fds: Vec<PollFd> = fds
+            .iter()
+            .chain([self.0.stop_rx.as_raw_fd()].iter())
+            .map(|fd| PollFd {
+                fd: *fd,
+                events: POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let timeout = timeout.map_or(-1, |timeout| {
+            timeout.as_millis().min(c_int::MAX as u128) as c_int
+        });
+
+        loop {
+            if unsafe { poll(poll_fds.as_mut_ptr(), poll_fds.len() as c_ulong, timeout) } < 0 {
+                // Interrupted by a signal
+                continue;
+            }
+
+            let stop = poll_fds.pop().unwrap();
+            if stop.revents != 0 {
+                return None;
+            }
+            return Some(poll_fds.iter().map(|fd| fd.revents != 0).collect());
+        }
+    }
+}
+
+/// poll definitions
+const POLLIN: c_short = 0x001;
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+extern "C" {
+    fn pipe(fds: *mut c_int) -> c_int;
+    fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Listener;
+    use crate::common::{MouseEvent, MouseEventExt};
+    use std::{
+        io::{self, Write},
+        os::unix::io::AsRawFd,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn stats_count_events() {
+        let listener = Listener::new().unwrap();
+        listener.handle().spawn(|| {});
+        let event = |device_id: &str| MouseEventExt {
+            device_id: device_id.to_string(),
+            device_name: String::new(),
+            event: MouseEvent::RelativeMove(1, 0),
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+        };
+        let mut counter = listener.handle().counter();
+        counter.record(&event("mouse0"), 2);
+        counter.record(&event("mouse1"), 0);
+        // The counts are shared with the listener as they are recorded
+        assert_eq!(listener.stats().per_device_counts["mouse1"], 1);
+        counter.record(&event("mouse0"), 1);
+
+        let stats = listener.stats();
+        assert_eq!(stats.events_received, 3);
+        assert_eq!(stats.callbacks_invoked, 3);
+        assert_eq!(stats.per_device_counts["mouse0"], 2);
+        assert_eq!(stats.per_device_counts["mouse1"], 1);
+        assert_eq!(stats.last_event_time, Some(event("").time));
+        // The only thread exits right away
+        thread::sleep(Duration::from_millis(20));
+        assert!(!listener.stats().running);
+    }
+
+    #[test]
+    fn drop_joins_waiting_threads() {
+        let (reader, _writer) = io::pipe().unwrap();
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let listener = Listener::new().unwrap();
+        let handle = listener.handle();
+        let thread_stopped = stopped.clone();
+        listener.handle().spawn(move || {
+            // Nothing is written to the pipe, so this only returns on stop
+            assert!(!handle.wait(reader.as_raw_fd()));
+            thread_stopped.store(true, Ordering::SeqCst);
+        });
+
+        drop(listener);
+        assert!(stopped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn wait_any_reports_ready_fds() {
+        let (idle, _idle_writer) = io::pipe().unwrap();
+        let (ready, mut ready_writer) = io::pipe().unwrap();
+        ready_writer.write_all(&[1]).unwrap();
+
+        let listener = Listener::new().unwrap();
+        let handle = listener.handle();
+        let fds = [idle.as_raw_fd(), ready.as_raw_fd()];
+        assert_eq!(handle.wait_any(&fds, None), Some(vec![false, true]));
+        // Nothing is ready before the timeout
+        assert_eq!(
+            handle.wait_any(&fds[..1], Some(Duration::from_millis(10))),
+            Some(vec![false])
+        );
+
+        drop(listener);
+        assert_eq!(handle.wait_any(&fds, None), None);
+    }
+}
@@ -1,166 +1,1374 @@
 ///
-/// This module contains the mouse action functions
-/// for the unix-like systems
+/// This module contains the mouse and keyboard action
+/// functions for the unix-like systems
 ///
-use crate::common::{CallbackId, MouseActions, MouseButton, MouseEvent, ScrollDirection};
+use crate::common::{
+    Backend, EventAction, MouseActions, MouseButton, MouseEvent, MouseEventExt, ScrollDirection,
+};
+use crate::error::Error;
+use crate::hooks::{CallbackList, Dispatcher, Hooks};
+use crate::keyboard::KeyboardActions;
 use crate::nix::uinput::{
-    InputEvent, TimeVal, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT, EV_KEY, EV_REL, REL_HWHEEL, REL_WHEEL,
-    REL_X, REL_Y,
+    InputEvent, TimeVal, ABS_X, ABS_Y, BTN_BACK, BTN_EXTRA, BTN_FORWARD, BTN_LEFT, BTN_MIDDLE,
+    BTN_RIGHT, BTN_SIDE, BTN_TASK, EV_ABS, EV_KEY, EV_REL, EV_SYN, REL_HWHEEL, REL_HWHEEL_HI_RES,
+    REL_WHEEL, REL_WHEEL_HI_RES, REL_X, REL_Y,
 };
+use crate::trace;
+#[cfg(not(target_os = "android"))]
 use glob::glob;
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::Result,
-    mem::size_of,
-    os::unix::io::AsRawFd,
-    process::Command,
-    str::from_utf8,
-    sync::{mpsc, Arc, Mutex},
-    thread,
+    collections::{HashMap, VecDeque},
+    env,
+    fs::{self, File},
+    io,
+    mem::{self, size_of},
+    os::unix::io::{AsRawFd, RawFd},
+    path::Path,
+    str::FromStr,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant, SystemTime},
 };
 
+#[cfg(target_os = "android")]
+mod android;
+mod calibration;
+#[cfg(feature = "portal")]
+mod dbus;
+mod devices;
+mod hotplug;
+#[cfg(feature = "libinput")]
+mod libinput;
+mod listener;
+mod poll;
+#[cfg(feature = "portal")]
+mod portal;
+mod queue;
+#[cfg(feature = "remote-desktop")]
+mod remote_desktop;
+mod screen;
 mod uinput;
+#[cfg(feature = "wayland")]
+mod wayland;
+#[cfg(not(target_os = "android"))]
 mod x11;
+#[cfg(feature = "xcursor")]
+mod xcursor;
+#[cfg(feature = "xinput2")]
+mod xinput2;
+#[cfg(feature = "xrandr")]
+mod xrandr;
+
+// There is no X server on Android, its module answers the queries of X11 instead
+#[cfg(target_os = "android")]
+use android as x11;
+#[cfg(target_os = "android")]
+use android::{is_mouse_file, mouse_paths, pointer_device, MICE_DIR};
+pub use calibration::Calibration;
+use devices::{abs_range, abs_value, device_buttons, device_name, EVENT_FILES};
+pub(crate) use devices::{button_state, list_mice};
+use hotplug::{Change, HotplugWatcher};
+use listener::{EventCounter, Listener, ListenerHandle};
+pub use poll::PollHandle;
+use queue::{Coalesce, QueueSender};
+pub use queue::{OverflowPolicy, QueueOptions, DEFAULT_QUEUE_CAPACITY};
+#[cfg(feature = "remote-desktop")]
+pub use remote_desktop::{sandboxed, RemoteDesktopMouseManager};
+pub(crate) use screen::{double_click_time, monitors};
+pub use uinput::{
+    Transaction, UInputKeyboardManager, UInputMouseManager, UInputMouseManagerBuilder,
+    UInputTouchManager,
+};
+#[cfg(not(target_os = "android"))]
+pub(crate) use x11::CursorNames;
+#[cfg(not(target_os = "android"))]
+pub use x11::{X11KeyboardManager, X11MouseManager};
+#[cfg(feature = "xcursor")]
+pub(crate) use xcursor::{reset_cursor, set_cursor_image};
+
+/// A callback of the listener, it decides whether the event is
+/// passed through to the system while the devices are grabbed
+type Callback = Box<dyn Fn(&MouseEventExt) -> EventAction + Send + Sync>;
+
+/// The callbacks that are shared between the managers and the listener threads,
+/// every callback is only invoked for the event types in its mask
+type Callbacks = Arc<Hooks<MouseEventExt>>;
+
+/// An event of a mouse as the kernel reports it, for the event
+/// codes that `MouseEvent` does not model e.g. `BTN_TOUCH` or `REL_DIAL`,
+/// see `UInputMouseManager::hook_raw`
+///
+/// The types and the codes are listed in linux/input-event-codes.h
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawInputEvent {
+    /// The event file of the device e.g. /dev/input/event5
+    pub device_id: String,
+    pub r#type: u16,
+    pub code: u16,
+    pub value: i32,
+    /// The time that the kernel stamped the event with
+    pub time: SystemTime,
+}
+
+impl From<(&Device, &InputEvent)> for RawInputEvent {
+    fn from((device, received): (&Device, &InputEvent)) -> Self {
+        RawInputEvent {
+            device_id: device.path.clone(),
+            r#type: received.r#type,
+            code: received.code,
+            value: received.value,
+            time: SystemTime::UNIX_EPOCH
+                + Duration::new(received.time.tv_sec, received.time.tv_usec as u32 * 1000),
+        }
+    }
+}
+
+/// A callback of the raw listener
+type RawCallback = Box<dyn Fn(&RawInputEvent) + Send + Sync>;
+
+/// The raw callbacks that are shared between the managers and the raw listener threads
+type RawCallbacks = Arc<CallbackList<dyn Fn(&RawInputEvent) + Send + Sync>>;
+
+/// The callbacks of `on_listener_restarted`, they are invoked by the reader threads
+type RestartCallbacks = Arc<CallbackList<dyn Fn() + Send>>;
+
+/// A device of the reader thread, it is sent along with the events of the device
+struct Device {
+    path: String,
+    name: String,
+    /// The ranges of the absolute axes, for the devices
+    /// that report absolute positions e.g. the tablets
+    abs_x: Option<(i32, i32)>,
+    abs_y: Option<(i32, i32)>,
+}
 
-pub use uinput::UInputMouseManager;
-pub use x11::X11MouseManager;
+/// The ranges of the screen coordinates that the absolute positions are scaled to
+type Ranges = ((i32, i32), (i32, i32));
+
+/// The events of a device since its last sync event, that are
+/// delivered together once the frame is complete
+#[derive(Default)]
+struct Frame {
+    /// The relative motion of both axes
+    motion: Option<(i32, i32)>,
+    /// The wheel detents of both axes
+    scroll: Option<(i32, i32)>,
+    /// Whether the absolute position has changed
+    moved: bool,
+    /// The last absolute position in the units of the device,
+    /// it is kept across the frames as only the changed axes are reported
+    position: (i32, i32),
+    /// The raw motion events that are re-emitted unless the move is consumed
+    held: Vec<InputEvent>,
+}
+
+/// The channel that the reader thread sends the events of the devices to,
+/// `None` is sent once a device is disconnected or failed
+type EventSender = QueueSender<QueuedEvent>;
+
+/// An event of a device, or `None` once the device is disconnected
+type QueuedEvent = (Arc<Device>, Option<InputEvent>);
+
+impl Coalesce for QueuedEvent {
+    /// The relative moves of a device are added to its last queued move of the same axis,
+    /// and the absolute positions replace it. The search stops at the other events
+    /// of the device, so the moves are not merged across the button events
+    fn coalesce(queued: &mut VecDeque<Self>, (device, newer): &Self) -> bool {
+        let Some(newer) = newer.filter(is_move) else {
+            return false;
+        };
+        for (queued_device, queued_event) in queued.iter_mut().rev() {
+            if !Arc::ptr_eq(queued_device, device) {
+                continue;
+            }
+            let Some(queued_event) = queued_event else {
+                return false;
+            };
+            if queued_event.r#type == newer.r#type && queued_event.code == newer.code {
+                if newer.r#type as i32 == EV_REL {
+                    queued_event.value = queued_event.value.saturating_add(newer.value);
+                } else {
+                    queued_event.value = newer.value;
+                }
+                return true;
+            }
+            if !is_move(queued_event) && queued_event.r#type as i32 != EV_SYN {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn is_move(&self) -> bool {
+        self.1.as_ref().is_some_and(is_move)
+    }
+
+    /// The buttons, and the disconnects that close the devices
+    fn is_essential(&self) -> bool {
+        self.1.is_none_or(|event| event.r#type as i32 == EV_KEY)
+    }
+}
+
+/// Whether the given event moves the pointer on either axis
+fn is_move(event: &InputEvent) -> bool {
+    let code = event.code as u32;
+    match event.r#type as i32 {
+        EV_REL => code == REL_X || code == REL_Y,
+        EV_ABS => code == ABS_X || code == ABS_Y,
+        _ => false,
+    }
+}
+
+/// A mouse event file that is read by the listener
+struct Reader {
+    /// The path under BY_ID_DIR that the file is opened from
+    path: String,
+    file: File,
+    device: Arc<Device>,
+}
+
+/// How many events are read from a device at once
+const READ_EVENTS: usize = 64;
+
+/// The initial events of the read buffers
+const EMPTY_EVENT: InputEvent = InputEvent {
+    time: TimeVal {
+        tv_sec: 0,
+        tv_usec: 0,
+    },
+    r#type: 0,
+    code: 0,
+    value: 0,
+};
+
+/// The directory that contains the symlinks to the input event files
+const BY_ID_DIR: &str = "/dev/input/by-id";
+/// The suffix of the mouse event file names under BY_ID_DIR
+#[cfg(not(target_os = "android"))]
+const MOUSE_EVENT_SUFFIX: &str = "-event-mouse";
+/// The directory that the hotplug watcher watches for the mouse event files
+#[cfg(not(target_os = "android"))]
+const MICE_DIR: &str = BY_ID_DIR;
+
+/// Check whether the given file name under MICE_DIR is a mouse event file
+#[cfg(not(target_os = "android"))]
+fn is_mouse_file(name: &str) -> bool {
+    name.ends_with(MOUSE_EVENT_SUFFIX)
+}
+
+/// Identify the display manager of the session e.g. x11 or wayland,
+/// from the environment that the session sets up for its programs
+pub(crate) fn display_manager() -> String {
+    let var = |name| env::var(name).ok().filter(|value| !value.is_empty());
+    session_type(
+        var("XDG_SESSION_TYPE"),
+        var("WAYLAND_DISPLAY").is_some(),
+        var("DISPLAY").is_some(),
+    )
+}
+
+/// Decide the display manager from $XDG_SESSION_TYPE, falling back to the display
+/// variables when it is not a graphical session type (e.g. tty over ssh or a
+/// session that is started by hand). Wayland is checked first as XWayland sets $DISPLAY too
+fn session_type(xdg_session_type: Option<String>, wayland: bool, x11: bool) -> String {
+    match xdg_session_type.as_deref() {
+        Some("x11") | Some("wayland") => xdg_session_type.unwrap(),
+        _ if wayland => "wayland".to_string(),
+        _ if x11 => "x11".to_string(),
+        _ => xdg_session_type.unwrap_or_default(),
+    }
+}
+
+/// Whether the RemoteDesktop portal should be tried first
+#[cfg(feature = "remote-desktop")]
+fn in_sandbox() -> bool {
+    remote_desktop::sandboxed()
+}
+
+#[cfg(not(feature = "remote-desktop"))]
+fn in_sandbox() -> bool {
+    false
+}
+
+/// The environment variable that overrides the backend that `NixMouseManager::new` picks
+const BACKEND_ENV: &str = "MOUCE_BACKEND";
+
+/// The backends that `NixMouseManager` can create the managers with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendPreference {
+    /// Pick the backend that fits the session, falling back to the next one
+    /// if it fails to start: X11 and then uinput on the X11 sessions, uinput
+    /// on the Wayland sessions as XTest would only reach the XWayland clients.
+    /// With the `remote-desktop` feature the sandboxed applications try the
+    /// RemoteDesktop portal first
+    Auto,
+    X11,
+    UInput,
+    /// The RemoteDesktop portal, which needs the `remote-desktop` feature
+    Portal,
+}
+
+impl BackendPreference {
+    /// Get the preference from `$MOUCE_BACKEND`, `Auto` if it is not set
+    pub fn from_env() -> Result<Self, Error> {
+        match env::var(BACKEND_ENV) {
+            Ok(value) if !value.is_empty() => value.parse().map_err(|_| {
+                Error::CustomError(format!(
+                    "{} is not a backend, {} must be one of auto, x11, uinput and portal",
+                    value, BACKEND_ENV
+                ))
+            }),
+            _ => Ok(BackendPreference::Auto),
+        }
+    }
+
+    /// The backends to try in order on the given session, the portal
+    /// is tried first by the applications that run in a sandbox
+    fn chain(&self, session: &str, sandboxed: bool) -> Vec<Backend> {
+        match self {
+            BackendPreference::X11 => vec![Backend::X11],
+            BackendPreference::UInput => vec![Backend::UInput],
+            BackendPreference::Portal => vec![Backend::Portal],
+            BackendPreference::Auto if sandboxed => {
+                let mut chain = BackendPreference::Auto.chain(session, false);
+                chain.insert(0, Backend::Portal);
+                chain
+            }
+            BackendPreference::Auto if session == "x11" => vec![Backend::X11, Backend::UInput],
+            // If the display manager is unknown default to uinput
+            BackendPreference::Auto => vec![Backend::UInput],
+        }
+    }
+}
+
+impl FromStr for BackendPreference {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(BackendPreference::Auto),
+            "x11" => Ok(BackendPreference::X11),
+            "uinput" => Ok(BackendPreference::UInput),
+            "portal" => Ok(BackendPreference::Portal),
+            _ => Err(Error::CustomError(format!("{} is not a backend", s))),
+        }
+    }
+}
 
 pub struct NixMouseManager {}
 
 impl NixMouseManager {
+    /// Create the manager with the backend that fits the session, or the one that
+    /// `$MOUCE_BACKEND` asks for (`auto`, `x11`, `uinput` or `portal`)
+    ///
     /// rng_x and rng_y is used by uinput mouse.
     /// As for x11, the params can be (0, 0), (0, 0)
-    pub fn new(rng_x: (i32, i32), rng_y: (i32, i32)) -> Result<Box<dyn MouseActions>> {
-        // Try to identify the display manager using loginctl, if it fails
-        // read the environment variable $XDG_SESSION_TYPE
-        let output = Command::new("sh")
-            .arg("-c")
-            .arg("loginctl show-session $(loginctl | awk '/tty/ {print $1}') -p Type | awk -F= '{print $2}'")
-            .output()
-            .unwrap_or(
-                Command::new("sh")
-                    .arg("-c")
-                    .arg("echo $XDG_SESSION_TYPE")
-                    .output().unwrap()
-                );
-
-        let display_manager = from_utf8(&output.stdout).unwrap().trim();
-
-        match display_manager {
-            "x11" => Ok(Box::new(x11::X11MouseManager::new())),
-            // If the display manager is unknown default to uinput
-            _ => Ok(Box::new(uinput::UInputMouseManager::new(rng_x, rng_y)?)),
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        rng_x: (i32, i32),
+        rng_y: (i32, i32),
+    ) -> Result<Box<dyn MouseActions + Send>, Error> {
+        Self::with_preference(BackendPreference::from_env()?, rng_x, rng_y)
+    }
+
+    /// Create the manager with the given backend preference, `$MOUCE_BACKEND` is not used
+    ///
+    /// Returns the error of the last backend that is tried if none of them starts
+    pub fn with_preference(
+        preference: BackendPreference,
+        rng_x: (i32, i32),
+        rng_y: (i32, i32),
+    ) -> Result<Box<dyn MouseActions + Send>, Error> {
+        let _span = trace::span!("select_backend");
+        let session = display_manager();
+        trace::debug!(
+            "selecting the backend of {:?} for the {} session",
+            preference,
+            session
+        );
+
+        let mut result = Err(Error::NotImplemented);
+        for backend in preference.chain(&session, in_sandbox()) {
+            result = match backend {
+                #[cfg(not(target_os = "android"))]
+                Backend::X11 => x11::X11MouseManager::try_new()
+                    .map(|manager| Box::new(manager) as Box<dyn MouseActions + Send>),
+                #[cfg(target_os = "android")]
+                Backend::X11 => Err(Error::Unsupported(Backend::X11)),
+                #[cfg(feature = "remote-desktop")]
+                Backend::Portal => remote_desktop::RemoteDesktopMouseManager::new()
+                    .map(|manager| Box::new(manager) as Box<dyn MouseActions + Send>),
+                #[cfg(not(feature = "remote-desktop"))]
+                Backend::Portal => Err(Error::Unsupported(Backend::Portal)),
+                _ => uinput::UInputMouseManager::new(rng_x, rng_y)
+                    .map(|manager| Box::new(manager) as Box<dyn MouseActions + Send>),
+            };
+            match &result {
+                Ok(_) => {
+                    trace::debug!("selected the {} backend", backend);
+                    break;
+                }
+                Err(err) => trace::debug!("the {} backend can not be used: {}", backend, err),
+            }
         }
+        result
     }
 
+    #[cfg(not(target_os = "android"))]
     pub fn new_x11() -> X11MouseManager {
         x11::X11MouseManager::new()
     }
 
-    pub fn new_uinput(rng_x: (i32, i32), rng_y: (i32, i32)) -> Result<UInputMouseManager> {
-        Ok(uinput::UInputMouseManager::new(rng_x, rng_y)?)
+    pub fn new_uinput(rng_x: (i32, i32), rng_y: (i32, i32)) -> Result<UInputMouseManager, Error> {
+        uinput::UInputMouseManager::new(rng_x, rng_y)
     }
 }
 
-/// Start the event listener for nix systems
-fn start_nix_listener(
-    callbacks: &Arc<Mutex<HashMap<CallbackId, Box<dyn Fn(&MouseEvent) + Send>>>>,
-) -> Result<()> {
-    let (tx, rx) = mpsc::channel();
-
-    // Read all the mouse events listed under /dev/input/by-id
-    // by-id directory is a collection of symlinks to /dev/input/event*
-    // I am only interested in the ones that end with `-event-mouse`
-    for file in glob("/dev/input/by-id/*-event-mouse").expect("Failed to read glob pattern") {
-        let path = file
-            .expect("Failed because of an IO error")
-            .display()
-            .to_string();
-
-        let event = File::options().read(true).open(path)?;
-
-        // Create a thread for this mouse-event file
-        let tx = tx.clone();
-        thread::spawn(move || loop {
-            let mut buffer = InputEvent {
-                time: TimeVal {
-                    tv_sec: 0,
-                    tv_usec: 0,
-                },
-                r#type: 0,
-                code: 0,
-                value: 0,
+pub struct NixKeyboardManager {}
+
+impl NixKeyboardManager {
+    /// Uses X11 on the X11 sessions and uinput on the others, like `NixMouseManager`,
+    /// which can also be overridden with `$MOUCE_BACKEND`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> Result<Box<dyn KeyboardActions + Send>, Error> {
+        let mut result = Err(Error::NotImplemented);
+        // The portal only controls the mouse
+        for backend in BackendPreference::from_env()?.chain(&display_manager(), false) {
+            result = match backend {
+                #[cfg(not(target_os = "android"))]
+                Backend::X11 => x11::X11KeyboardManager::new()
+                    .map(|manager| Box::new(manager) as Box<dyn KeyboardActions + Send>),
+                #[cfg(target_os = "android")]
+                Backend::X11 => Err(Error::Unsupported(Backend::X11)),
+                Backend::Portal => Err(Error::Unsupported(Backend::Portal)),
+                _ => uinput::UInputKeyboardManager::new()
+                    .map(|manager| Box::new(manager) as Box<dyn KeyboardActions + Send>),
             };
-            unsafe {
-                read(event.as_raw_fd(), &mut buffer, size_of::<InputEvent>());
+            if result.is_ok() {
+                break;
             }
-            tx.send(buffer).unwrap();
-        });
+        }
+        result
     }
 
-    let callbacks = callbacks.clone();
-    // Create a thread for handling the callbacks
-    thread::spawn(move || {
-        for received in rx {
-            // Construct the library's MouseEvent
-            let r#type = received.r#type as i32;
-            let code = received.code as i32;
-            let val = received.value as i32;
-
-            let mouse_event = if r#type == EV_KEY {
-                let button = if code == BTN_LEFT {
-                    MouseButton::Left
-                } else if code == BTN_RIGHT {
-                    MouseButton::Right
-                } else if code == BTN_MIDDLE {
-                    MouseButton::Middle
-                } else {
-                    // Ignore the unknown mouse buttons
-                    continue;
-                };
+    #[cfg(not(target_os = "android"))]
+    pub fn new_x11() -> Result<X11KeyboardManager, Error> {
+        x11::X11KeyboardManager::new()
+    }
 
-                if received.value == 1 {
-                    MouseEvent::Press(button)
-                } else {
-                    MouseEvent::Release(button)
-                }
-            } else if r#type == EV_REL {
-                let code = received.code as u32;
-                if code == REL_WHEEL {
-                    MouseEvent::Scroll(if received.value > 0 {
-                        ScrollDirection::Up
-                    } else {
-                        ScrollDirection::Down
-                    })
-                } else if code == REL_HWHEEL {
-                    MouseEvent::Scroll(if received.value > 0 {
-                        ScrollDirection::Right
+    pub fn new_uinput() -> Result<UInputKeyboardManager, Error> {
+        uinput::UInputKeyboardManager::new()
+    }
+}
+
+/// Start the event listener for nix systems
+///
+/// Uses libinput if the `libinput` feature is enabled, otherwise
+/// reads the mouse event files under /dev/input directly. Grabbing
+/// the devices always reads the event files, as the raw events need
+/// to be passed through to the system
+///
+/// With the `portal` feature the Wayland sessions receive the events
+/// from the InputCapture portal first, falling back to the others if
+/// the portal is not available
+///
+/// The positions of the absolute devices are scaled to the given ranges
+fn start_nix_listener(
+    callbacks: &Callbacks,
+    restarted: &RestartCallbacks,
+    grab: bool,
+    ranges: Ranges,
+    queue: QueueOptions,
+) -> io::Result<Listener> {
+    // The portal captures the pointer, so it is only used when the mice can not be read
+    #[cfg(feature = "portal")]
+    if !grab && display_manager() == "wayland" && !mice_readable() {
+        if let Ok(listener) = portal::start_portal_listener(callbacks) {
+            return Ok(listener);
+        }
+    }
+    #[cfg(feature = "libinput")]
+    if !grab {
+        return libinput::start_libinput_listener(callbacks, ranges);
+    }
+    start_evdev_listener(callbacks, restarted, grab, ranges, queue)
+}
+
+/// Whether `start_nix_listener` starts the listener that reads the event files,
+/// only that one opens the mice again once they are lost and queues the events
+/// with the `QueueOptions`, the others invoke the callbacks as the events come
+fn reads_event_files(grab: bool) -> bool {
+    if grab {
+        return true;
+    }
+    #[cfg(feature = "portal")]
+    if display_manager() == "wayland" && !mice_readable() {
+        return false;
+    }
+    !cfg!(feature = "libinput")
+}
+
+/// Scale the given value from the range of the device to the range of the screen
+fn scale(value: i32, from: (i32, i32), to: (i32, i32)) -> i32 {
+    let offset = (value.clamp(from.0, from.1) - from.0) as i64;
+    to.0 + (offset * (to.1 - to.0) as i64 / (from.1 - from.0) as i64) as i32
+}
+
+/// Invoke the callbacks that subscribe to the type of the given event
+///
+/// The event is consumed if any of the callbacks consumes it, and it is
+/// counted in the statistics of the given listener
+fn dispatch(
+    dispatcher: &mut Dispatcher<MouseEventExt>,
+    counter: &mut EventCounter,
+    event: &MouseEventExt,
+) -> EventAction {
+    trace::debug!("{:?} from {}", event.event, event.device_id);
+    let (action, invoked) = dispatcher.dispatch(event);
+    counter.record(event, invoked);
+    action
+}
+
+/// Dispatch the given event as if a device without a name reported it now
+fn inject(callbacks: &Callbacks, event: MouseEvent) -> EventAction {
+    let event = MouseEventExt {
+        device_id: String::new(),
+        device_name: String::new(),
+        event,
+        time: SystemTime::now(),
+    };
+    trace::debug!("{:?} is injected", event.event);
+    callbacks.dispatch(&event)
+}
+
+/// Map the given evdev button code to the library's MouseButton
+fn map_button(code: i32) -> Option<MouseButton> {
+    match code {
+        BTN_LEFT => Some(MouseButton::Left),
+        BTN_RIGHT => Some(MouseButton::Right),
+        BTN_MIDDLE => Some(MouseButton::Middle),
+        BTN_SIDE => Some(MouseButton::Side),
+        BTN_EXTRA => Some(MouseButton::Extra),
+        BTN_FORWARD => Some(MouseButton::Forward),
+        BTN_BACK => Some(MouseButton::Back),
+        BTN_TASK => Some(MouseButton::Task),
+        _ => None,
+    }
+}
+
+/// Start the event listener that reads the raw event files under /dev/input
+///
+/// If `grab` is set, the devices are grabbed so that their events are
+/// not delivered to the rest of the system, and the events that are
+/// not consumed by the callbacks are re-emitted from a virtual mouse
+fn start_evdev_listener(
+    callbacks: &Callbacks,
+    restarted: &RestartCallbacks,
+    grab: bool,
+    ranges: Ranges,
+    queue: QueueOptions,
+) -> io::Result<Listener> {
+    let listener = Listener::new()?;
+    let passthrough = if grab {
+        Some(uinput::create_passthrough_device()?)
+    } else {
+        None
+    };
+    let (tx, rx) = queue::bounded(queue, &listener.handle());
+
+    // Keep watching the directory for the mice that are connected later on
+    let hotplug = HotplugWatcher::new()?;
+    spawn_reader(
+        Mice::new(open_mice(grab)?, hotplug, grab),
+        tx,
+        &listener.handle(),
+        restarted,
+    );
+
+    let mut dispatcher = callbacks.dispatcher();
+    let mut counter = listener.handle().counter();
+    // Create a thread for handling the callbacks, it exits once
+    // the reader thread is stopped and the channel is closed
+    listener.handle().spawn(move || {
+        let mut frames = Frames::new(ranges, passthrough);
+        for queued in rx {
+            frames.handle(queued, &mut |event| {
+                dispatch(&mut dispatcher, &mut counter, event)
+            });
+        }
+    });
+
+    Ok(listener)
+}
+
+/// Assembles the events of the mice into the `MouseEvent`s, which are
+/// delivered once the frames of the devices are complete
+struct Frames {
+    /// The pending frame of every device until its next sync event, by the address
+    /// of the device, which stays the same until the device is disconnected
+    frames: HashMap<usize, Frame>,
+    ranges: Ranges,
+    /// The virtual mouse that re-emits the events which are not consumed,
+    /// while the devices are grabbed
+    passthrough: Option<File>,
+}
+
+impl Frames {
+    fn new(ranges: Ranges, passthrough: Option<File>) -> Self {
+        Frames {
+            frames: HashMap::new(),
+            ranges,
+            passthrough,
+        }
+    }
+
+    /// Handle the given event of a device, the `MouseEvent`s are passed to `dispatch`
+    /// which decides whether they are consumed
+    fn handle(
+        &mut self,
+        (device, received): QueuedEvent,
+        dispatch: &mut impl FnMut(&MouseEventExt) -> EventAction,
+    ) {
+        let received = match received {
+            Some(received) => received,
+            None => {
+                // Drop the pending frame, the rest of it will never arrive
+                self.frames.remove(&(Arc::as_ptr(&device) as usize));
+                dispatch(&MouseEventExt {
+                    device_id: device.path.clone(),
+                    device_name: device.name.clone(),
+                    event: MouseEvent::DeviceDisconnected(device.path.clone()),
+                    time: SystemTime::now(),
+                });
+                return;
+            }
+        };
+        // The kernel stamps the events with the wall clock time
+        let time = SystemTime::UNIX_EPOCH
+            + Duration::new(received.time.tv_sec, received.time.tv_usec as u32 * 1000);
+        let to_event = |mouse_event| MouseEventExt {
+            device_id: device.path.clone(),
+            device_name: device.name.clone(),
+            event: mouse_event,
+            time,
+        };
+        let frame = self
+            .frames
+            .entry(Arc::as_ptr(&device) as usize)
+            .or_default();
+
+        // The absolute positions are also delivered once the frame is complete
+        if received.r#type as i32 == EV_ABS
+            && (received.code as u32 == ABS_X || received.code as u32 == ABS_Y)
+            && device.abs_x.is_some()
+            && device.abs_y.is_some()
+        {
+            if received.code as u32 == ABS_X {
+                frame.position.0 = received.value;
+            } else {
+                frame.position.1 = received.value;
+            }
+            frame.moved = true;
+            frame.held.push(received);
+            return;
+        }
+
+        let action = match to_mouse_event(&received) {
+            // The motion of both axes is delivered as a single move once the
+            // frame is complete, so the raw events are held back until then
+            Some(MouseEvent::RelativeMove(x, y)) => {
+                let motion = frame.motion.get_or_insert((0, 0));
+                *motion = (motion.0 + x, motion.1 + y);
+                frame.held.push(received);
+                return;
+            }
+            Some(mouse_event) => {
+                if let MouseEvent::Scroll(_) = mouse_event {
+                    let scroll = frame.scroll.get_or_insert((0, 0));
+                    if received.code as u32 == REL_WHEEL {
+                        scroll.1 += received.value;
                     } else {
-                        ScrollDirection::Left
-                    })
-                } else if code == REL_X {
-                    MouseEvent::RelativeMove(val, 0)
-                } else if code == REL_Y {
-                    MouseEvent::RelativeMove(0, val)
-                } else {
-                    continue;
+                        scroll.0 += received.value;
+                    }
+                }
+                dispatch(&to_event(mouse_event))
+            }
+            None => {
+                if received.r#type as i32 == EV_SYN {
+                    let relative = frame
+                        .motion
+                        .take()
+                        .map(|(x, y)| MouseEvent::RelativeMove(x, y));
+                    let absolute = match (mem::take(&mut frame.moved), device.abs_x, device.abs_y) {
+                        (true, Some(abs_x), Some(abs_y)) => Some(MouseEvent::AbsoluteMove(
+                            scale(frame.position.0, abs_x, self.ranges.0),
+                            scale(frame.position.1, abs_y, self.ranges.1),
+                        )),
+                        _ => None,
+                    };
+
+                    // The held events are consumed along with any of the moves
+                    let mut action = EventAction::PassThrough;
+                    for mouse_event in [relative, absolute].into_iter().flatten() {
+                        if dispatch(&to_event(mouse_event)) == EventAction::Consume {
+                            action = EventAction::Consume;
+                        }
+                    }
+                    // The buffer of the held events is kept for the next frame
+                    if let (Some(passthrough), EventAction::PassThrough) =
+                        (&self.passthrough, action)
+                    {
+                        for held in frame.held.iter() {
+                            let _ = uinput::write_event(passthrough, held);
+                        }
+                    }
+                    frame.held.clear();
+                    // Both axes of a scroll are reported in the same frame, the consume
+                    // decision for the combined scroll is made by its `Scroll` events
+                    if let Some((dx, dy)) = frame.scroll.take() {
+                        dispatch(&to_event(MouseEvent::Scroll2D { dx, dy }));
+                    }
                 }
+                // The unknown events (e.g. the sync events) are always passed through
+                EventAction::PassThrough
+            }
+        };
+
+        // Re-emit the events of the grabbed devices that are not consumed
+        if let (Some(passthrough), EventAction::PassThrough) = (&self.passthrough, action) {
+            let _ = uinput::write_event(passthrough, &received);
+        }
+    }
+}
+
+/// Start a listener that reports the events of the mice as they are read,
+/// without constructing the `MouseEvent`s, to the given raw callbacks
+///
+/// The devices are never grabbed and the sync events are reported as well
+fn start_raw_listener(
+    callbacks: &RawCallbacks,
+    restarted: &RestartCallbacks,
+    queue: QueueOptions,
+) -> io::Result<Listener> {
+    let listener = Listener::new()?;
+    let (tx, rx) = queue::bounded(queue, &listener.handle());
+    let hotplug = HotplugWatcher::new()?;
+    spawn_reader(
+        Mice::new(open_mice(false)?, hotplug, false),
+        tx,
+        &listener.handle(),
+        restarted,
+    );
+
+    let callbacks = callbacks.clone();
+    listener.handle().spawn(move || {
+        // The disconnected devices are not reported, they have no raw event
+        for (device, received) in rx {
+            if let Some(received) = received {
+                let event = RawInputEvent::from((device.as_ref(), &received));
+                callbacks.invoke(|callback| callback(&event));
+            }
+        }
+    });
+
+    Ok(listener)
+}
+
+/// Open all the mice that are listed under /dev/input/by-id
+///
+/// The by-id directory is a collection of symlinks to /dev/input/event*,
+/// only the ones that end with `-event-mouse` are mice
+fn open_mice(grab: bool) -> io::Result<Vec<Reader>> {
+    mouse_paths()
+        .into_iter()
+        .map(|path| open_device(path, grab))
+        .collect()
+}
+
+/// Whether the mouse event files can be opened, or there are none to open yet
+#[cfg(feature = "portal")]
+fn mice_readable() -> bool {
+    let paths = mouse_paths();
+    paths.is_empty() || paths.iter().any(|path| File::open(path).is_ok())
+}
+
+/// List the paths of the mouse event files under BY_ID_DIR
+#[cfg(not(target_os = "android"))]
+fn mouse_paths() -> Vec<String> {
+    glob(&format!("{}/*{}", BY_ID_DIR, MOUSE_EVENT_SUFFIX))
+        .expect("Failed to read glob pattern")
+        .map(|file| {
+            file.expect("Failed because of an IO error")
+                .display()
+                .to_string()
+        })
+        .collect()
+}
+
+/// Construct the library's MouseEvent from the given raw event
+fn to_mouse_event(received: &InputEvent) -> Option<MouseEvent> {
+    let r#type = received.r#type as i32;
+    let code = received.code as i32;
+    let val = received.value;
+
+    if r#type == EV_KEY {
+        // Ignore the unknown mouse buttons
+        let button = map_button(code)?;
+        if received.value == 1 {
+            Some(MouseEvent::Press(button))
+        } else {
+            Some(MouseEvent::Release(button))
+        }
+    } else if r#type == EV_REL {
+        let code = received.code as u32;
+        if code == REL_WHEEL {
+            Some(MouseEvent::Scroll(if received.value > 0 {
+                ScrollDirection::Up
             } else {
-                // Ignore other unknown events
+                ScrollDirection::Down
+            }))
+        } else if code == REL_HWHEEL {
+            Some(MouseEvent::Scroll(if received.value > 0 {
+                ScrollDirection::Right
+            } else {
+                ScrollDirection::Left
+            }))
+        } else if code == REL_WHEEL_HI_RES {
+            Some(MouseEvent::ScrollHiRes { dx: 0, dy: val })
+        } else if code == REL_HWHEEL_HI_RES {
+            Some(MouseEvent::ScrollHiRes { dx: val, dy: 0 })
+        } else if code == REL_X {
+            Some(MouseEvent::RelativeMove(val, 0))
+        } else if code == REL_Y {
+            Some(MouseEvent::RelativeMove(0, val))
+        } else {
+            None
+        }
+    } else {
+        // Ignore other unknown events
+        None
+    }
+}
+
+/// Open the given mouse event file, and grab the device if `grab` is set
+///
+/// The grab is released once the file is closed
+fn open_device(path: String, grab: bool) -> io::Result<Reader> {
+    let file = File::options().read(true).open(&path)?;
+    // Every event file is watched on Android, so the new devices may not be mice
+    #[cfg(target_os = "android")]
+    if !pointer_device(&file) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a mouse"));
+    }
+    if grab {
+        if let Err(err) = uinput::grab_device(&file) {
+            trace::warning!("failed to grab {}: {}", path, err);
+            return Err(err);
+        }
+    }
+
+    let device = Arc::new(Device {
+        // Fall back to the file name if the device does not report a name
+        name: device_name(&file).unwrap_or_else(|| {
+            path.trim_start_matches(BY_ID_DIR)
+                .trim_start_matches('/')
+                .to_string()
+        }),
+        // Report the event file that the symlink points to, the same
+        // path that is listed by `list_mice` and used by libinput
+        path: fs::canonicalize(&path)
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| path.clone()),
+        abs_x: abs_range(&file, ABS_X),
+        abs_y: abs_range(&file, ABS_Y),
+    });
+
+    trace::debug!("reading {} ({})", device.path, device.name);
+    Ok(Reader { path, file, device })
+}
+
+/// Read the pending events of the given device, once its file is readable
+///
+/// Fails once the device is disconnected (ENODEV) or reached the end of its file
+fn read_events<'a>(
+    file: &File,
+    events: &'a mut [InputEvent; READ_EVENTS],
+) -> io::Result<&'a [InputEvent]> {
+    let read_bytes = unsafe {
+        read(
+            file.as_raw_fd(),
+            events.as_mut_ptr(),
+            size_of::<InputEvent>() * READ_EVENTS,
+        )
+    };
+
+    if read_bytes < 0 {
+        let err = io::Error::last_os_error();
+        // Interrupted by a signal or woken up without an event, try again later
+        return match err.kind() {
+            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock => Ok(&[]),
+            _ => Err(err),
+        };
+    }
+    if read_bytes == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    // The kernel only returns whole events
+    Ok(&events[..read_bytes as usize / size_of::<InputEvent>()])
+}
+
+/// The mice that a reader follows, together with the hotplug watcher
+/// and the mice that are connected but could not be opened yet
+///
+/// The devices fail to be read after the system resumes from suspend while their
+/// files stay, such devices are opened again along with the rest of the mice
+struct Mice {
+    readers: Vec<Reader>,
+    hotplug: Option<HotplugWatcher>,
+    /// The connected mice that could not be opened yet, with their failed attempts
+    pending: Vec<(String, usize)>,
+    retry_at: Option<Instant>,
+    /// The mice that failed to be read while their files exist, and whether
+    /// the mice should be listed again on the next attempt to open them
+    lost: Vec<String>,
+    rescan: bool,
+    /// Whether the newly connected devices are grabbed as well
+    grab: bool,
+}
+
+impl Mice {
+    fn new(readers: Vec<Reader>, hotplug: Option<HotplugWatcher>, grab: bool) -> Self {
+        Mice {
+            readers,
+            hotplug,
+            pending: Vec::new(),
+            retry_at: None,
+            lost: Vec::new(),
+            rescan: false,
+            grab,
+        }
+    }
+
+    /// Collect the fds of the mice followed by the one of the hotplug watcher
+    fn fds(&self, fds: &mut Vec<RawFd>) {
+        fds.clear();
+        fds.extend(self.readers.iter().map(|reader| reader.file.as_raw_fd()));
+        fds.extend(self.hotplug.as_ref().map(|hotplug| hotplug.as_raw_fd()));
+    }
+
+    /// How long until the pending mice are tried to be opened again
+    fn timeout(&self) -> Option<Duration> {
+        self.retry_at
+            .map(|retry_at| retry_at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Read the mice and the hotplug watcher that are ready, by the order of `fds`,
+    /// and pass their events to `send`. The disconnects and the read failures are
+    /// sent as `None`
+    ///
+    /// Returns false as soon as `send` fails, the rest of the events are not read
+    fn read_ready(
+        &mut self,
+        ready: &[bool],
+        events: &mut [InputEvent; READ_EVENTS],
+        send: &mut impl FnMut(QueuedEvent) -> bool,
+    ) -> bool {
+        let readers = &mut self.readers;
+        let hotplug_ready = ready.len() > readers.len() && ready[readers.len()];
+
+        // Iterate backwards so that the failed readers can be removed
+        for index in (0..readers.len().min(ready.len())).rev() {
+            if !ready[index] {
                 continue;
-            };
+            }
+            match read_events(&readers[index].file, events) {
+                Ok(events) => {
+                    for event in events {
+                        if !send((readers[index].device.clone(), Some(*event))) {
+                            return false;
+                        }
+                    }
+                }
+                Err(err) => {
+                    let reader = readers.remove(index);
+                    trace::debug!("stopped reading {}: {}", reader.device.path, err);
+                    // An unplugged mouse loses its file as well, which udev removes
+                    if Path::new(&reader.path).exists() {
+                        if !self
+                            .pending
+                            .iter()
+                            .any(|(pending, _)| *pending == reader.path)
+                        {
+                            self.pending.push((reader.path.clone(), 0));
+                        }
+                        self.lost.push(reader.path);
+                        self.rescan = true;
+                        self.retry_at = Some(Instant::now() + hotplug::OPEN_RETRY_DELAY);
+                    }
+                    if !send((reader.device, None)) {
+                        return false;
+                    }
+                }
+            }
+        }
 
-            // Invoke all given callbacks with the constructed mouse event
-            for callback in callbacks.lock().unwrap().values() {
-                callback(&mouse_event);
+        if let (Some(watcher), true) = (&mut self.hotplug, hotplug_ready) {
+            match watcher.read_changes() {
+                Ok(changes) => {
+                    for change in changes {
+                        match change {
+                            // The device is removed once its file fails to be read
+                            Change::Disconnected(path) => {
+                                self.pending.retain(|(pending, _)| *pending != path);
+                            }
+                            Change::Connected(path) => {
+                                let connected = readers.iter().any(|reader| reader.path == path)
+                                    || self.pending.iter().any(|(pending, _)| *pending == path);
+                                if !connected {
+                                    self.pending.push((path, 0));
+                                    self.retry_at = Some(Instant::now());
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(err) => {
+                    trace::warning!("stopped watching for the new mice: {}", err);
+                    self.hotplug = None;
+                }
             }
         }
-    });
+        true
+    }
+
+    /// Try to open the connected mice once it is time to, udev may not have set
+    /// their permissions yet. Returns whether any of the lost mice is back
+    fn retry(&mut self) -> bool {
+        match self.retry_at {
+            Some(retry_at) if retry_at <= Instant::now() => {}
+            _ => return false,
+        }
+        if mem::take(&mut self.rescan) {
+            // The mice may come back under new names, or while nothing watched them
+            if self.hotplug.is_none() {
+                self.hotplug = HotplugWatcher::new().ok().flatten();
+            }
+            for path in mouse_paths() {
+                let known = self.readers.iter().any(|reader| reader.path == path)
+                    || self.pending.iter().any(|(pending, _)| *pending == path);
+                if !known {
+                    self.pending.push((path, 0));
+                }
+            }
+        }
+        let mut reopened = false;
+        let (readers, lost, grab) = (&mut self.readers, &mut self.lost, self.grab);
+        self.pending
+            .retain_mut(|(path, attempts)| match open_device(path.clone(), grab) {
+                Ok(reader) => {
+                    let count = lost.len();
+                    lost.retain(|lost| *lost != reader.path);
+                    reopened |= lost.len() != count;
+                    readers.push(reader);
+                    false
+                }
+                // The devices that are not mice are not tried again
+                Err(err) if err.kind() == io::ErrorKind::InvalidInput => false,
+                Err(_) => {
+                    *attempts += 1;
+                    *attempts < hotplug::OPEN_ATTEMPTS
+                }
+            });
+        self.retry_at = if self.pending.is_empty() {
+            None
+        } else {
+            Some(Instant::now() + hotplug::OPEN_RETRY_DELAY)
+        };
+        reopened
+    }
+}
+
+/// Count the restart of the given listener and invoke the `restarted` callbacks,
+/// once the lost mice are back
+fn notify_restarted(listener: &ListenerHandle, restarted: &RestartCallbacks) {
+    trace::debug!("reopened the lost mice");
+    listener.counters().restarts.fetch_add(1, Ordering::Relaxed);
+    restarted.invoke(|callback| callback());
+}
+
+/// Create the thread that reads the events of all the given mice
+///
+/// The thread polls the event files together with the hotplug watcher, so a single
+/// thread serves any number of devices. It exits once the listener is stopped,
+/// the disconnects and the read failures are reported as `DeviceDisconnected`
+///
+/// The lost mice that are opened again are reported to the `restarted` callbacks
+fn spawn_reader(
+    mut mice: Mice,
+    tx: EventSender,
+    listener: &ListenerHandle,
+    restarted: &RestartCallbacks,
+) {
+    let handle = listener.clone();
+    let restarted = restarted.clone();
+    listener.spawn(move || {
+        // The buffers are reused for every read
+        let mut fds: Vec<RawFd> = Vec::new();
+        let mut events = [EMPTY_EVENT; READ_EVENTS];
 
-    Ok(())
+        loop {
+            mice.fds(&mut fds);
+            let ready = match handle.wait_any(&fds, mice.timeout()) {
+                Some(ready) => ready,
+                None => break,
+            };
+            if !mice.read_ready(&ready, &mut events, &mut |queued| tx.send(queued).is_ok()) {
+                return;
+            }
+            if mice.retry() {
+                notify_restarted(&handle, &restarted);
+            }
+        }
+    });
 }
 
 extern "C" {
-    fn read(fd: i32, buf: *mut InputEvent, count: usize) -> i32;
+    fn read(fd: i32, buf: *mut InputEvent, count: usize) -> isize;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        inject, read_events, reads_event_files, scale, session_type, to_mouse_event,
+        BackendPreference, Callbacks, Device, Frames, InputEvent, Mice, RawInputEvent, Reader,
+        EMPTY_EVENT, READ_EVENTS,
+    };
+    use crate::common::{Backend, EventAction, EventMask, MouseButton, MouseEvent};
+    use crate::hooks::{Handler, DEFAULT_PRIORITY};
+    use std::{
+        fs::File,
+        io::{self, Write},
+        mem::{self, size_of},
+        os::fd::OwnedFd,
+        slice,
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn restart_only_the_event_files() {
+        // The grabbed devices are always read from their event files
+        assert!(reads_event_files(true));
+        // libinput follows the devices itself, so it never restarts
+        if cfg!(feature = "libinput") {
+            assert!(!reads_event_files(false));
+        }
+    }
+
+    #[test]
+    fn read_pending_events() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        let reader = File::from(OwnedFd::from(reader));
+        let events = [(2, 0, 5), (2, 1, -3), (0, 0, 0)];
+        for (r#type, code, value) in events {
+            let mut event: InputEvent = unsafe { mem::zeroed() };
+            event.r#type = r#type;
+            event.code = code;
+            event.value = value;
+            let bytes = unsafe {
+                slice::from_raw_parts(&event as *const _ as *const u8, size_of::<InputEvent>())
+            };
+            writer.write_all(bytes).unwrap();
+        }
+
+        let mut buffer = [EMPTY_EVENT; READ_EVENTS];
+        let read: Vec<_> = read_events(&reader, &mut buffer)
+            .unwrap()
+            .iter()
+            .map(|event| (event.r#type, event.code, event.value))
+            .collect();
+        assert_eq!(read, events);
+        // The end of the file is reported like a disconnected device
+        drop(writer);
+        assert!(read_events(&reader, &mut buffer).is_err());
+    }
+
+    #[test]
+    fn report_disconnected_devices() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        let device = Arc::new(Device {
+            path: "/dev/input/event9".to_string(),
+            name: "mouse".to_string(),
+            abs_x: None,
+            abs_y: None,
+        });
+        let mut mice = Mice::new(
+            vec![Reader {
+                path: "/dev/input/by-id/mouce-test-unplugged-mouse".to_string(),
+                file: File::from(OwnedFd::from(reader)),
+                device: device.clone(),
+            }],
+            None,
+            false,
+        );
+        // A move of the frame that is cut off by the disconnect
+        let mut event: InputEvent = unsafe { mem::zeroed() };
+        event.r#type = 2;
+        event.value = 5;
+        let bytes = unsafe {
+            slice::from_raw_parts(&event as *const _ as *const u8, size_of::<InputEvent>())
+        };
+        writer.write_all(bytes).unwrap();
+        drop(writer);
+
+        let mut queued = Vec::new();
+        let mut buffer = [EMPTY_EVENT; READ_EVENTS];
+        for _ in 0..2 {
+            assert!(mice.read_ready(&[true], &mut buffer, &mut |event| {
+                queued.push(event);
+                true
+            }));
+        }
+        // The reader is removed, and is not reopened as its file is gone
+        assert!(mice.readers.is_empty());
+        assert!(mice.pending.is_empty());
+        assert_eq!(queued.len(), 2);
+        assert!(queued[1].1.is_none());
+
+        let mut frames = Frames::new(((0, 1919), (0, 1079)), None);
+        let mut delivered = Vec::new();
+        for event in queued {
+            frames.handle(event, &mut |event| {
+                delivered.push(event.clone());
+                EventAction::PassThrough
+            });
+        }
+        // The pending move is dropped with the frame
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].device_id, "/dev/input/event9");
+        assert_eq!(
+            delivered[0].event,
+            MouseEvent::DeviceDisconnected("/dev/input/event9".to_string())
+        );
+    }
+
+    #[test]
+    fn inject_events() {
+        let callbacks: Callbacks = Arc::default();
+        let presses = Arc::new(Mutex::new(Vec::new()));
+        let received = presses.clone();
+        callbacks.insert(
+            0,
+            DEFAULT_PRIORITY,
+            EventMask::PRESS,
+            Handler::Action(Box::new(move |event| {
+                received.lock().unwrap().push(event.event.clone());
+                EventAction::Consume
+            })),
+        );
+
+        let press = MouseEvent::Press(MouseButton::Left);
+        assert_eq!(inject(&callbacks, press.clone()), EventAction::Consume);
+        // The other types are not delivered to the callback
+        let release = MouseEvent::Release(MouseButton::Left);
+        assert_eq!(inject(&callbacks, release), EventAction::PassThrough);
+        assert_eq!(*presses.lock().unwrap(), vec![press]);
+    }
+
+    #[test]
+    fn extra_buttons() {
+        let mut received: InputEvent = unsafe { mem::zeroed() };
+        received.r#type = 1;
+        // BTN_SIDE
+        received.code = 0x113;
+        received.value = 1;
+        assert_eq!(
+            to_mouse_event(&received),
+            Some(MouseEvent::Press(MouseButton::Side))
+        );
+        // BTN_TASK
+        received.code = 0x117;
+        received.value = 0;
+        assert_eq!(
+            to_mouse_event(&received),
+            Some(MouseEvent::Release(MouseButton::Task))
+        );
+        // BTN_TOUCH is not a mouse button
+        received.code = 0x14a;
+        assert_eq!(to_mouse_event(&received), None);
+    }
+
+    #[test]
+    fn raw_events() {
+        let device = Device {
+            path: "/dev/input/event5".to_string(),
+            name: "mouse".to_string(),
+            abs_x: None,
+            abs_y: None,
+        };
+        let mut received: InputEvent = unsafe { mem::zeroed() };
+        received.time.tv_sec = 2;
+        received.time.tv_usec = 500;
+        received.r#type = 1;
+        received.code = 0x14a;
+        received.value = 1;
+        assert_eq!(
+            RawInputEvent::from((&device, &received)),
+            RawInputEvent {
+                device_id: "/dev/input/event5".to_string(),
+                r#type: 1,
+                code: 0x14a,
+                value: 1,
+                time: SystemTime::UNIX_EPOCH + Duration::new(2, 500_000),
+            }
+        );
+    }
+
+    #[test]
+    fn scale_absolute_positions() {
+        assert_eq!(scale(0, (0, 32767), (0, 1919)), 0);
+        assert_eq!(scale(32767, (0, 32767), (0, 1919)), 1919);
+        assert_eq!(scale(16384, (0, 32767), (0, 1919)), 959);
+        // The values out of the range of the device are clamped
+        assert_eq!(scale(-5, (0, 100), (10, 20)), 10);
+        assert_eq!(scale(150, (0, 100), (10, 20)), 20);
+    }
+
+    #[test]
+    fn session_types() {
+        let x11 = Some("x11".to_string());
+        let tty = Some("tty".to_string());
+        assert_eq!(session_type(x11, true, true), "x11");
+        assert_eq!(session_type(tty.clone(), true, true), "wayland");
+        assert_eq!(session_type(None, false, true), "x11");
+        assert_eq!(session_type(tty, false, false), "tty");
+        assert_eq!(session_type(None, false, false), "");
+    }
+
+    #[test]
+    fn backend_chains() {
+        assert_eq!(
+            BackendPreference::Auto.chain("x11", false),
+            vec![Backend::X11, Backend::UInput]
+        );
+        assert_eq!(
+            BackendPreference::Auto.chain("wayland", false),
+            vec![Backend::UInput]
+        );
+        assert_eq!(
+            BackendPreference::Auto.chain("", false),
+            vec![Backend::UInput]
+        );
+        assert_eq!(
+            BackendPreference::X11.chain("wayland", true),
+            vec![Backend::X11]
+        );
+        // The sandboxed applications try the portal first
+        assert_eq!(
+            BackendPreference::Auto.chain("wayland", true),
+            vec![Backend::Portal, Backend::UInput]
+        );
+        assert_eq!("uinput".parse(), Ok(BackendPreference::UInput));
+        assert_eq!("portal".parse(), Ok(BackendPreference::Portal));
+        assert!("wayland".parse::<BackendPreference>().is_err());
+    }
 }
@@ -0,0 +1,410 @@
+///
+/// This module contains the poll handle, a listener without threads
+/// for the applications that run their own event loop e.g. mio or calloop
+///
+/// The handle owns an epoll fd that becomes readable whenever any of the
+/// mice or the hotplug watcher has something to read, the application
+/// registers it in its loop and calls `dispatch_pending` once it is readable,
+/// which invokes the callbacks on the thread of the loop
+///
+use crate::common::{
+    CallbackId, CallbackPanic, EventAction, EventMask, EventWithTime, ListenerStats, MouseEvent,
+    MouseEventExt, MouseListener, Propagation,
+};
+use crate::error::Error;
+use crate::hooks::{CallbackList, Dispatcher, Handler, DEFAULT_PRIORITY};
+use crate::nix::listener::{EventCounter, Listener};
+use crate::nix::uinput::InputEvent;
+use crate::nix::{
+    dispatch, notify_restarted, open_mice, Callbacks, Frames, HotplugWatcher, Mice, Ranges,
+    RestartCallbacks, EMPTY_EVENT, READ_EVENTS,
+};
+use std::{
+    fs::File,
+    io,
+    os::{
+        raw::c_int,
+        unix::io::{AsRawFd, FromRawFd, RawFd},
+    },
+    sync::Arc,
+    time::Duration,
+};
+
+/// Reads the mice only when `dispatch_pending` is called, instead of
+/// spawning the threads of a listener
+///
+/// The devices are never grabbed, so every event is passed through to the system
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::common::MouseListener;
+/// use mouce::nix::PollHandle;
+/// use std::os::unix::io::AsRawFd;
+///
+/// let mut handle = PollHandle::new((0, 1920), (0, 1080)).unwrap();
+/// handle
+///     .hook(Box::new(|e| println!("New event: {:?}", e)))
+///     .unwrap();
+/// // Register the fd in the event loop, and dispatch once it is readable
+/// let _fd = handle.as_raw_fd();
+/// handle.dispatch_pending().unwrap();
+/// ```
+pub struct PollHandle {
+    mice: Mice,
+    frames: Frames,
+    callbacks: Callbacks,
+    dispatcher: Dispatcher<MouseEventExt>,
+    /// Kept between the dispatches, so a device is only added to the statistics once
+    counter: EventCounter,
+    restart_callbacks: RestartCallbacks,
+    callback_counter: CallbackId,
+    /// The listener has no threads, it counts the events and tells which fds are ready
+    listener: Listener,
+    epoll: File,
+    /// The buffers are reused for every dispatch
+    fds: Vec<RawFd>,
+    events: [InputEvent; READ_EVENTS],
+}
+
+impl PollHandle {
+    /// Open all the mice and start watching for the new ones, the positions
+    /// of the absolute devices are scaled to the given ranges
+    pub fn new(rng_x: (i32, i32), rng_y: (i32, i32)) -> Result<Self, Error> {
+        let mice = Mice::new(open_mice(false)?, HotplugWatcher::new()?, false);
+        Self::with_mice(mice, (rng_x, rng_y))
+    }
+
+    /// Create the handle that reads the given mice
+    fn with_mice(mice: Mice, ranges: Ranges) -> Result<Self, Error> {
+        let fd = unsafe { epoll_create1(EPOLL_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // The file takes the ownership of the fd and closes it on drop
+        let epoll = unsafe { File::from_raw_fd(fd) };
+        let callbacks = Callbacks::default();
+        let listener = Listener::new()?;
+        let mut handle = PollHandle {
+            mice,
+            frames: Frames::new(ranges, None),
+            dispatcher: callbacks.dispatcher(),
+            restart_callbacks: Arc::new(CallbackList::new(callbacks.panics())),
+            callbacks,
+            callback_counter: 0,
+            counter: listener.handle().counter(),
+            listener,
+            epoll,
+            fds: Vec::new(),
+            events: [EMPTY_EVENT; READ_EVENTS],
+        };
+        handle.register()?;
+        Ok(handle)
+    }
+
+    /// Read the events that are pending on the mice without blocking, and invoke
+    /// the callbacks for them. Also opens the mice that are connected meanwhile
+    ///
+    /// Returns how many events are read from the devices
+    pub fn dispatch_pending(&mut self) -> Result<usize, Error> {
+        self.mice.fds(&mut self.fds);
+        let handle = self.listener.handle();
+        let ready = handle
+            .wait_any(&self.fds, Some(Duration::ZERO))
+            .unwrap_or_default();
+
+        let mut read = 0;
+        let (frames, dispatcher, counter) =
+            (&mut self.frames, &mut self.dispatcher, &mut self.counter);
+        self.mice
+            .read_ready(&ready, &mut self.events, &mut |queued| {
+                read += queued.1.is_some() as usize;
+                frames.handle(queued, &mut |event| dispatch(dispatcher, counter, event));
+                true
+            });
+        if self.mice.retry() {
+            notify_restarted(&handle, &self.restart_callbacks);
+        }
+        self.register()?;
+        Ok(read)
+    }
+
+    /// How long until `dispatch_pending` should be called even though the fd
+    /// is not readable, while the newly connected mice can not be opened yet
+    ///
+    /// Returns None if there is nothing to wait for
+    pub fn timeout(&self) -> Option<Duration> {
+        self.mice.timeout()
+    }
+
+    /// Add the fds of the mice to the epoll set, the fds that are closed
+    /// are removed from the set by the kernel
+    fn register(&mut self) -> io::Result<()> {
+        self.mice.fds(&mut self.fds);
+        for fd in &self.fds {
+            let mut event = EpollEvent {
+                events: EPOLLIN,
+                data: *fd as u64,
+            };
+            let added =
+                unsafe { epoll_ctl(self.epoll.as_raw_fd(), EPOLL_CTL_ADD, *fd, &mut event) };
+            if added < 0 {
+                let err = io::Error::last_os_error();
+                // The fds that stay open are registered already
+                if err.raw_os_error() != Some(EEXIST) {
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_callback(
+        &mut self,
+        priority: i32,
+        mask: EventMask,
+        handler: Handler<MouseEventExt>,
+    ) -> Result<CallbackId, Error> {
+        let id = self.callback_counter;
+        self.callbacks.insert(id, priority, mask, handler);
+        self.callback_counter += 1;
+        Ok(id)
+    }
+}
+
+impl AsRawFd for PollHandle {
+    /// The epoll fd stays the same while the mice come and go
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll.as_raw_fd()
+    }
+}
+
+impl MouseListener for PollHandle {
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_with_time(Box::new(move |event| callback(&event.event)))
+    }
+
+    fn hook_with_time(
+        &mut self,
+        callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_ext(Box::new(move |event| {
+            callback(&EventWithTime {
+                event: event.event.clone(),
+                time: event.time,
+            })
+        }))
+    }
+
+    fn hook_ext(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            DEFAULT_PRIORITY,
+            EventMask::ALL,
+            Handler::Action(Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            })),
+        )
+    }
+
+    fn hook_filtered(
+        &mut self,
+        mask: EventMask,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            DEFAULT_PRIORITY,
+            mask,
+            Handler::Action(Box::new(move |event| {
+                callback(&event.event);
+                EventAction::PassThrough
+            })),
+        )
+    }
+
+    fn hook_with_priority(
+        &mut self,
+        priority: i32,
+        callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            priority,
+            EventMask::ALL,
+            Handler::Propagate(Box::new(move |event| callback(&event.event))),
+        )
+    }
+
+    fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+        Ok(super::inject(&self.callbacks, event))
+    }
+
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.remove(callback_id) {
+            return Ok(());
+        }
+        if self.restart_callbacks.remove(callback_id) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, true) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, false) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn on_callback_panic(
+        &mut self,
+        unhook: bool,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
+        self.callbacks.set_panic_handler(handler, unhook);
+        Ok(())
+    }
+
+    fn unhook_all(&mut self) -> Result<(), Error> {
+        self.callbacks.clear();
+        self.restart_callbacks.clear();
+        Ok(())
+    }
+
+    fn stop_listening(&mut self) -> Result<(), Error> {
+        // Closing the files removes them from the epoll set as well
+        self.mice = Mice::new(Vec::new(), None, false);
+        Ok(())
+    }
+
+    fn listener_stats(&self) -> Result<ListenerStats, Error> {
+        Ok(ListenerStats {
+            // The handle listens as long as it follows any mouse
+            running: !self.mice.readers.is_empty() || self.mice.hotplug.is_some(),
+            ..self.listener.stats()
+        })
+    }
+
+    fn on_listener_restarted(
+        &mut self,
+        callback: Box<dyn Fn() + Send>,
+    ) -> Result<CallbackId, Error> {
+        let id = self.callback_counter;
+        self.restart_callbacks.push(id, callback);
+        self.callback_counter += 1;
+        Ok(id)
+    }
+}
+
+/// epoll definitions
+const EPOLL_CLOEXEC: c_int = 0o2000000;
+const EPOLL_CTL_ADD: c_int = 1;
+const EPOLLIN: u32 = 0x001;
+const EEXIST: i32 = 17;
+
+/// The event of epoll_ctl, which is packed on x86_64
+#[cfg_attr(target_arch = "x86_64", repr(C, packed))]
+#[cfg_attr(not(target_arch = "x86_64"), repr(C))]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+extern "C" {
+    fn epoll_create1(flags: c_int) -> c_int;
+    fn epoll_ctl(epfd: c_int, op: c_int, fd: c_int, event: *mut EpollEvent) -> c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EpollEvent, PollHandle, EPOLLIN};
+    use crate::common::{MouseButton, MouseEvent, MouseListener};
+    use crate::nix::uinput::InputEvent;
+    use crate::nix::{Device, Mice, Reader};
+    use std::{
+        fs::File,
+        io::{self, Write},
+        mem::{self, size_of},
+        os::fd::OwnedFd,
+        slice,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn dispatch_pending_events() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        let mice = Mice::new(
+            vec![Reader {
+                path: "/dev/input/by-id/mouce-test-poll-mouse".to_string(),
+                file: File::from(OwnedFd::from(reader)),
+                device: Arc::new(Device {
+                    path: "/dev/input/event9".to_string(),
+                    name: "mouse".to_string(),
+                    abs_x: None,
+                    abs_y: None,
+                }),
+            }],
+            None,
+            false,
+        );
+        let mut handle = PollHandle::with_mice(mice, ((0, 1919), (0, 1079))).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let collected = received.clone();
+        handle
+            .hook(Box::new(move |event| {
+                collected.lock().unwrap().push(event.clone())
+            }))
+            .unwrap();
+
+        // Nothing is read or delivered until the device has events
+        assert_eq!(handle.dispatch_pending(), Ok(0));
+        // A move of both axes and a press, each completed by a sync
+        for (r#type, code, value) in [(2, 0, 5), (2, 1, -3), (0, 0, 0), (1, 0x110, 1), (0, 0, 0)] {
+            let mut event: InputEvent = unsafe { mem::zeroed() };
+            event.r#type = r#type;
+            event.code = code;
+            event.value = value;
+            let bytes = unsafe {
+                slice::from_raw_parts(&event as *const _ as *const u8, size_of::<InputEvent>())
+            };
+            writer.write_all(bytes).unwrap();
+        }
+        assert!(received.lock().unwrap().is_empty());
+
+        assert_eq!(handle.dispatch_pending(), Ok(5));
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                MouseEvent::RelativeMove(5, -3),
+                MouseEvent::Press(MouseButton::Left)
+            ]
+        );
+    }
+
+    #[test]
+    fn epoll_event_layout() {
+        // The kernel reads the data right after the events on x86_64
+        let expected = if cfg!(target_arch = "x86_64") { 12 } else { 16 };
+        assert_eq!(size_of::<EpollEvent>(), expected);
+        let event = EpollEvent {
+            events: EPOLLIN,
+            data: 7,
+        };
+        assert_eq!({ event.data }, 7);
+    }
+}
@@ -0,0 +1,612 @@
+///
+/// This module contains the event listener for the Wayland sessions
+/// that receives the events through the InputCapture portal and libei
+///
+/// The compositors do not let the clients see the global input events,
+/// so the portal is asked to capture the pointer when it is pushed
+/// against the edges of the screens. While the input is captured, the
+/// compositor sends the events to the listener over libei instead of
+/// moving the pointer, until it releases the capture (e.g. with its
+/// shortcut to release the captured input) or the listener is stopped
+///
+/// The portal may ask the user for the permission when the listener starts
+///
+use crate::common::{MouseEvent, MouseEventExt, ScrollDirection};
+use crate::nix::dbus::{Connection, Value};
+use crate::nix::listener::Listener;
+use crate::nix::Callbacks;
+use std::{
+    ffi::CStr,
+    io::{Error, ErrorKind, Result},
+    os::{
+        fd::{FromRawFd, OwnedFd, RawFd},
+        raw::{c_char, c_double, c_int, c_uint, c_void},
+    },
+    process,
+    ptr::null_mut,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
+pub(super) const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+pub(super) const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const INPUT_CAPTURE_INTERFACE: &str = "org.freedesktop.portal.InputCapture";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+/// The capability of the InputCapture sessions to capture the pointer
+const CAPABILITY_POINTER: u32 = 2;
+/// How long to wait for the response of a request, which may need the user to allow it
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+/// The number of the scroll units of libei per detent of the wheel
+const V120_PER_DETENT: i32 = 120;
+
+/// The counter of the tokens of the requests, so that every request has its own path
+static TOKEN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Start the event listener that receives the events from the InputCapture portal
+pub fn start_portal_listener(callbacks: &Callbacks) -> Result<Listener> {
+    let listener = Listener::new()?;
+    let connection = Connection::session()?;
+    let session = create_session(&connection)?;
+
+    let context = unsafe {
+        let fd = connect_to_eis(&connection, &session)?;
+        let ei = ei_new_receiver(null_mut());
+        if ei.is_null() {
+            drop(OwnedFd::from_raw_fd(fd));
+            return Err(Error::other("failed to create libei context"));
+        }
+        // The context owns the fd from now on
+        let context = Context(ei);
+        ei_configure_name(ei, c"mouce".as_ptr());
+        if ei_setup_backend_fd(ei, fd) < 0 {
+            return Err(Error::new(
+                ErrorKind::ConnectionRefused,
+                "failed to connect to the EIS server of the portal",
+            ));
+        }
+        context
+    };
+
+    // Follow the activations of the capture, so that it can be released on stop
+    connection.add_match(&format!(
+        "type='signal',interface='{}',path='{}'",
+        INPUT_CAPTURE_INTERFACE, PORTAL_PATH
+    ))?;
+    // The capture is only enabled after the events can be received
+    connection.call(
+        PORTAL_DESTINATION,
+        PORTAL_PATH,
+        INPUT_CAPTURE_INTERFACE,
+        "Enable",
+        &[Value::Path(session.clone()), Value::dict(Vec::new())],
+    )?;
+
+    let mut dispatcher = callbacks.dispatcher();
+    let callbacks = callbacks.clone();
+    let handle = listener.handle();
+    let mut counter = handle.counter();
+    // Create a thread for reading and handling the libei events
+    listener.handle().spawn(move || {
+        let context = context;
+        let fds: Vec<RawFd> = [Some(unsafe { ei_get_fd(context.0) }), connection.fd()]
+            .into_iter()
+            .flatten()
+            .collect();
+        // The id of the activation while the pointer is captured
+        let mut activation = None;
+
+        while let Some(ready) = handle.wait_any(&fds, None) {
+            if ready.get(1) == Some(&true) {
+                match connection.pop_signals(INPUT_CAPTURE_INTERFACE) {
+                    Ok(signals) => {
+                        for (member, values) in signals {
+                            activation = follow_activation(&session, activation, &member, &values);
+                        }
+                    }
+                    // The session is closed along with the connection to the bus
+                    Err(_) => return,
+                }
+            }
+            if !ready[0] {
+                continue;
+            }
+            unsafe {
+                ei_dispatch(context.0);
+                loop {
+                    let event = ei_get_event(context.0);
+                    if event.is_null() {
+                        break;
+                    }
+
+                    let r#type = ei_event_get_type(event);
+                    match r#type {
+                        EI_EVENT_SEAT_ADDED => {
+                            ei_seat_bind_capabilities(
+                                ei_event_get_seat(event),
+                                EI_DEVICE_CAP_POINTER,
+                                EI_DEVICE_CAP_POINTER_ABSOLUTE,
+                                EI_DEVICE_CAP_BUTTON,
+                                EI_DEVICE_CAP_SCROLL,
+                                null_mut::<c_void>(),
+                            );
+                        }
+                        EI_EVENT_DISCONNECT => {
+                            // The portal closed the session
+                            ei_event_unref(event);
+                            return;
+                        }
+                        _ => {}
+                    }
+
+                    let mouse_events = to_mouse_events(event);
+                    let device_name = if mouse_events.is_empty() {
+                        String::new()
+                    } else {
+                        to_string(ei_device_get_name(ei_event_get_device(event)))
+                    };
+                    ei_event_unref(event);
+
+                    // libei uses a monotonic clock for the event times,
+                    // so stamp the events with the time they are received
+                    let time = SystemTime::now();
+                    for mouse_event in mouse_events {
+                        let event = MouseEventExt {
+                            device_id: "portal".to_string(),
+                            device_name: device_name.clone(),
+                            event: mouse_event,
+                            time,
+                        };
+
+                        super::dispatch(&mut dispatcher, &mut counter, &event);
+                    }
+                }
+            }
+
+            // Nobody receives the captured events once all the callbacks are unhooked
+            if let Some(activation_id) = activation {
+                if callbacks.is_empty() && release(&connection, &session, activation_id).is_ok() {
+                    activation = None;
+                }
+            }
+        }
+
+        // The listener is stopped, give the pointer back to the compositor
+        // before the session is closed along with the connection
+        if let Some(activation_id) = activation {
+            let _ = release(&connection, &session, activation_id);
+        }
+        let _ = connection.call(
+            PORTAL_DESTINATION,
+            PORTAL_PATH,
+            INPUT_CAPTURE_INTERFACE,
+            "Disable",
+            &[Value::Path(session), Value::dict(Vec::new())],
+        );
+    });
+
+    Ok(listener)
+}
+
+/// Release the capture of the given activation, so the compositor moves the pointer again
+fn release(connection: &Connection, session: &str, activation_id: u32) -> Result<()> {
+    connection.call(
+        PORTAL_DESTINATION,
+        PORTAL_PATH,
+        INPUT_CAPTURE_INTERFACE,
+        "Release",
+        &[
+            Value::Path(session.to_string()),
+            Value::dict(vec![("activation_id", Value::U32(activation_id))]),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Follow the capture of the given session through the given signal of the portal,
+/// returns the id of the activation while the pointer is captured
+fn follow_activation(
+    session: &str,
+    activation: Option<u32>,
+    member: &str,
+    values: &[Value],
+) -> Option<u32> {
+    match values.first() {
+        Some(Value::Path(path)) | Some(Value::Str(path)) if path == session => {}
+        _ => return activation,
+    }
+    match member {
+        "Activated" => match values
+            .get(1)
+            .and_then(|options| options.get("activation_id"))
+        {
+            Some(Value::U32(activation_id)) => Some(*activation_id),
+            _ => activation,
+        },
+        "Deactivated" | "Disabled" => None,
+        _ => activation,
+    }
+}
+
+/// Create an InputCapture session that captures the pointer on the edges of the screens,
+/// returns the handle of the session
+fn create_session(connection: &Connection) -> Result<String> {
+    let results = request(
+        connection,
+        INPUT_CAPTURE_INTERFACE,
+        "CreateSession",
+        |token| {
+            vec![
+                Value::Str(String::new()),
+                Value::dict(vec![
+                    ("handle_token", Value::Str(token.to_string())),
+                    ("session_handle_token", Value::Str(token.to_string())),
+                    ("capabilities", Value::U32(CAPABILITY_POINTER)),
+                ]),
+            ]
+        },
+    )?;
+    let session = match results.get("session_handle") {
+        Some(Value::Path(session)) | Some(Value::Str(session)) => session.clone(),
+        _ => return Err(Error::other("the portal did not create a session")),
+    };
+
+    let results = request(connection, INPUT_CAPTURE_INTERFACE, "GetZones", |token| {
+        vec![
+            Value::Path(session.clone()),
+            Value::dict(vec![("handle_token", Value::Str(token.to_string()))]),
+        ]
+    })?;
+    let zones: Vec<Zone> = match results.get("zones") {
+        Some(Value::Array(_, zones)) => zones.iter().filter_map(to_zone).collect(),
+        _ => Vec::new(),
+    };
+    let zone_set = match results.get("zone_set") {
+        Some(Value::U32(zone_set)) => *zone_set,
+        _ => return Err(Error::other("the portal did not report the zones")),
+    };
+
+    let barriers = barriers(&zones);
+    let results = request(
+        connection,
+        INPUT_CAPTURE_INTERFACE,
+        "SetPointerBarriers",
+        |token| {
+            vec![
+                Value::Path(session.clone()),
+                Value::dict(vec![("handle_token", Value::Str(token.to_string()))]),
+                Value::Array(
+                    "a{sv}".to_string(),
+                    barriers
+                        .iter()
+                        .map(|(id, (x1, y1, x2, y2))| {
+                            Value::dict(vec![
+                                ("barrier_id", Value::U32(*id)),
+                                (
+                                    "position",
+                                    Value::Struct(vec![
+                                        Value::I32(*x1),
+                                        Value::I32(*y1),
+                                        Value::I32(*x2),
+                                        Value::I32(*y2),
+                                    ]),
+                                ),
+                            ])
+                        })
+                        .collect(),
+                ),
+                Value::U32(zone_set),
+            ]
+        },
+    )?;
+    // The edges between the screens are rejected, as the pointer crosses them
+    let failed = match results.get("failed_barriers") {
+        Some(Value::Array(_, failed)) => failed.len(),
+        _ => 0,
+    };
+    if failed >= barriers.len() {
+        return Err(Error::other("the portal rejected all the pointer barriers"));
+    }
+
+    Ok(session)
+}
+
+/// Get the fd of the EIS server of the given session
+fn connect_to_eis(connection: &Connection, session: &str) -> Result<c_int> {
+    let reply = connection.call(
+        PORTAL_DESTINATION,
+        PORTAL_PATH,
+        INPUT_CAPTURE_INTERFACE,
+        "ConnectToEIS",
+        &[Value::Path(session.to_string()), Value::dict(Vec::new())],
+    )?;
+    match reply.first() {
+        Some(Value::Fd(fd)) => Ok(*fd),
+        _ => Err(Error::other("the portal did not return the EIS fd")),
+    }
+}
+
+/// Call the given method of a portal interface that responds through
+/// a request object, and wait for its response
+///
+/// The arguments are created from the token of the request, returns the results
+pub(super) fn request<F>(
+    connection: &Connection,
+    interface: &str,
+    method: &str,
+    args: F,
+) -> Result<Value>
+where
+    F: FnOnce(&str) -> Vec<Value>,
+{
+    let token = format!(
+        "mouce_{}_{}",
+        process::id(),
+        TOKEN_COUNTER.fetch_add(1, Ordering::SeqCst)
+    );
+    // Listen to the response before the call, so that it can not be missed
+    let path = request_path(&connection.unique_name(), &token);
+    connection.add_match(&format!(
+        "type='signal',interface='{}',member='Response',path='{}'",
+        REQUEST_INTERFACE, path
+    ))?;
+    connection.call(
+        PORTAL_DESTINATION,
+        PORTAL_PATH,
+        interface,
+        method,
+        &args(&token),
+    )?;
+
+    let response = connection.wait_signal(&path, REQUEST_INTERFACE, "Response", REQUEST_TIMEOUT)?;
+    match (response.first(), response.get(1)) {
+        (Some(Value::U32(0)), Some(results)) => Ok(results.clone()),
+        (Some(Value::U32(1)), _) => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("the request {} is cancelled", method),
+        )),
+        _ => Err(Error::other(format!("the request {} failed", method))),
+    }
+}
+
+/// Get the path of the request object that the portal creates for the given token,
+/// e.g. `:1.42` and `token` gives `/org/freedesktop/portal/desktop/request/1_42/token`
+fn request_path(unique_name: &str, token: &str) -> String {
+    let sender = unique_name.trim_start_matches(':').replace('.', "_");
+    format!("{}/request/{}/{}", PORTAL_PATH, sender, token)
+}
+
+/// A zone of the screens, in the order of the portal: width, height, x and y
+type Zone = (u32, u32, i32, i32);
+
+fn to_zone(value: &Value) -> Option<Zone> {
+    match value {
+        Value::Struct(values) => match values.as_slice() {
+            [Value::U32(width), Value::U32(height), Value::I32(x), Value::I32(y)] => {
+                Some((*width, *height, *x, *y))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Create the barriers on the four edges of every zone, with their ids
+///
+/// The barriers of the right and the bottom edges are placed on the
+/// pixels after the zone, which is what the portal expects
+fn barriers(zones: &[Zone]) -> Vec<(u32, (i32, i32, i32, i32))> {
+    zones
+        .iter()
+        .flat_map(|(width, height, x, y)| {
+            let (right, bottom) = (x + *width as i32, y + *height as i32);
+            [
+                (*x, *y, *x, bottom - 1),
+                (right, *y, right, bottom - 1),
+                (*x, *y, right - 1, *y),
+                (*x, bottom, right - 1, bottom),
+            ]
+        })
+        .enumerate()
+        // The id 0 is not a valid barrier id
+        .map(|(i, position)| (i as u32 + 1, position))
+        .collect()
+}
+
+/// Construct the library's MouseEvents from the given libei event
+unsafe fn to_mouse_events(event: *mut EiEvent) -> Vec<MouseEvent> {
+    match ei_event_get_type(event) {
+        EI_EVENT_POINTER_MOTION => vec![MouseEvent::RelativeMove(
+            ei_event_pointer_get_dx(event) as i32,
+            ei_event_pointer_get_dy(event) as i32,
+        )],
+        EI_EVENT_POINTER_MOTION_ABSOLUTE => vec![MouseEvent::AbsoluteMove(
+            ei_event_pointer_get_absolute_x(event) as i32,
+            ei_event_pointer_get_absolute_y(event) as i32,
+        )],
+        EI_EVENT_BUTTON_BUTTON => {
+            match super::map_button(ei_event_button_get_button(event) as c_int) {
+                Some(button) if ei_event_button_get_is_press(event) => {
+                    vec![MouseEvent::Press(button)]
+                }
+                Some(button) => vec![MouseEvent::Release(button)],
+                None => Vec::new(),
+            }
+        }
+        EI_EVENT_SCROLL_DISCRETE => scroll_events(
+            ei_event_scroll_get_discrete_dx(event),
+            ei_event_scroll_get_discrete_dy(event),
+        ),
+        _ => Vec::new(),
+    }
+}
+
+/// Construct the scroll events from the given discrete scroll of libei, with
+/// a `Scroll` for every whole detent
+///
+/// Like libinput, libei reports positive values when scrolling down and right,
+/// so the vertical axis is flipped to match the direction of evdev
+fn scroll_events(dx: i32, dy: i32) -> Vec<MouseEvent> {
+    let mut events = Vec::new();
+    // A fast scroll may be reported as several detents at once
+    if dy.abs() >= V120_PER_DETENT {
+        let direction = if dy < 0 {
+            ScrollDirection::Up
+        } else {
+            ScrollDirection::Down
+        };
+        events
+            .extend((0..dy.abs() / V120_PER_DETENT).map(|_| MouseEvent::Scroll(direction.clone())));
+    } else if dx.abs() >= V120_PER_DETENT {
+        let direction = if dx < 0 {
+            ScrollDirection::Left
+        } else {
+            ScrollDirection::Right
+        };
+        events
+            .extend((0..dx.abs() / V120_PER_DETENT).map(|_| MouseEvent::Scroll(direction.clone())));
+    }
+    if dx != 0 || dy != 0 {
+        events.push(MouseEvent::ScrollHiRes { dx, dy: -dy });
+    }
+    events
+}
+
+/// Copy the given C string that is owned by libei
+unsafe fn to_string(string: *const c_char) -> String {
+    if string.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(string).to_string_lossy().into_owned()
+}
+
+/// Owns the libei context, so that it can be moved into the listener thread
+struct Context(*mut Ei);
+
+unsafe impl Send for Context {}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe {
+            ei_unref(self.0);
+        }
+    }
+}
+
+/// libei definitions
+const EI_EVENT_DISCONNECT: c_int = 2;
+const EI_EVENT_SEAT_ADDED: c_int = 3;
+const EI_EVENT_POINTER_MOTION: c_int = 300;
+const EI_EVENT_POINTER_MOTION_ABSOLUTE: c_int = 400;
+const EI_EVENT_BUTTON_BUTTON: c_int = 500;
+const EI_EVENT_SCROLL_DISCRETE: c_int = 603;
+const EI_DEVICE_CAP_POINTER: c_uint = 1 << 0;
+const EI_DEVICE_CAP_POINTER_ABSOLUTE: c_uint = 1 << 1;
+const EI_DEVICE_CAP_SCROLL: c_uint = 1 << 4;
+const EI_DEVICE_CAP_BUTTON: c_uint = 1 << 5;
+
+/// libei types
+enum Ei {}
+enum EiEvent {}
+enum EiSeat {}
+enum EiDevice {}
+
+#[link(name = "ei")]
+extern "C" {
+    fn ei_new_receiver(user_data: *mut c_void) -> *mut Ei;
+    fn ei_unref(ei: *mut Ei) -> *mut Ei;
+    fn ei_configure_name(ei: *mut Ei, name: *const c_char);
+    fn ei_setup_backend_fd(ei: *mut Ei, fd: c_int) -> c_int;
+    fn ei_get_fd(ei: *mut Ei) -> c_int;
+    fn ei_dispatch(ei: *mut Ei);
+    fn ei_get_event(ei: *mut Ei) -> *mut EiEvent;
+    fn ei_event_unref(event: *mut EiEvent) -> *mut EiEvent;
+    fn ei_event_get_type(event: *mut EiEvent) -> c_int;
+    fn ei_event_get_seat(event: *mut EiEvent) -> *mut EiSeat;
+    fn ei_event_get_device(event: *mut EiEvent) -> *mut EiDevice;
+    fn ei_device_get_name(device: *mut EiDevice) -> *const c_char;
+    /// The capabilities are terminated with a null pointer
+    fn ei_seat_bind_capabilities(seat: *mut EiSeat, ...);
+    fn ei_event_pointer_get_dx(event: *mut EiEvent) -> c_double;
+    fn ei_event_pointer_get_dy(event: *mut EiEvent) -> c_double;
+    fn ei_event_pointer_get_absolute_x(event: *mut EiEvent) -> c_double;
+    fn ei_event_pointer_get_absolute_y(event: *mut EiEvent) -> c_double;
+    fn ei_event_button_get_button(event: *mut EiEvent) -> u32;
+    fn ei_event_button_get_is_press(event: *mut EiEvent) -> bool;
+    fn ei_event_scroll_get_discrete_dx(event: *mut EiEvent) -> i32;
+    fn ei_event_scroll_get_discrete_dy(event: *mut EiEvent) -> i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_paths() {
+        assert_eq!(
+            request_path(":1.42", "mouce_1_0"),
+            "/org/freedesktop/portal/desktop/request/1_42/mouce_1_0"
+        );
+    }
+
+    #[test]
+    fn zone_barriers() {
+        let barriers = barriers(&[(1920, 1080, 0, 0)]);
+        assert_eq!(
+            barriers,
+            vec![
+                (1, (0, 0, 0, 1079)),
+                (2, (1920, 0, 1920, 1079)),
+                (3, (0, 0, 1919, 0)),
+                (4, (0, 1080, 1919, 1080)),
+            ]
+        );
+    }
+
+    #[test]
+    fn discrete_scrolls() {
+        assert_eq!(
+            scroll_events(0, 120),
+            vec![
+                MouseEvent::Scroll(ScrollDirection::Down),
+                MouseEvent::ScrollHiRes { dx: 0, dy: -120 }
+            ]
+        );
+        // A part of a detent is only reported as a high resolution scroll
+        assert_eq!(
+            scroll_events(-60, 0),
+            vec![MouseEvent::ScrollHiRes { dx: -60, dy: 0 }]
+        );
+        assert_eq!(scroll_events(0, 0), Vec::new());
+        assert_eq!(
+            scroll_events(0, -360),
+            vec![
+                MouseEvent::Scroll(ScrollDirection::Up),
+                MouseEvent::Scroll(ScrollDirection::Up),
+                MouseEvent::Scroll(ScrollDirection::Up),
+                MouseEvent::ScrollHiRes { dx: 0, dy: 360 }
+            ]
+        );
+    }
+
+    #[test]
+    fn follow_activations() {
+        let session = "/org/freedesktop/portal/desktop/session/1_42/mouce";
+        let activated = |session: &str, activation_id| {
+            vec![
+                Value::Path(session.to_string()),
+                Value::dict(vec![("activation_id", Value::U32(activation_id))]),
+            ]
+        };
+        let activation = follow_activation(session, None, "Activated", &activated(session, 3));
+        assert_eq!(activation, Some(3));
+        // The signals of the other sessions are ignored
+        assert_eq!(
+            follow_activation(session, activation, "Deactivated", &activated("/other", 3)),
+            Some(3)
+        );
+        assert_eq!(
+            follow_activation(session, activation, "Deactivated", &activated(session, 3)),
+            None
+        );
+    }
+}
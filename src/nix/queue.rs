@@ -0,0 +1,323 @@
+///
+/// This module contains the bounded queue between the thread that reads the
+/// mice and the thread that invokes the callbacks
+///
+/// A callback that stalls would otherwise let the events pile up without a
+/// limit, once the queue is full the new events are handled by its
+/// `OverflowPolicy` and the dropped ones are counted in the listener statistics
+///
+use crate::hooks::LockUnpoisoned;
+use crate::nix::listener::ListenerHandle;
+use std::{
+    collections::VecDeque,
+    sync::{atomic::Ordering, Arc, Condvar, Mutex, MutexGuard},
+};
+
+/// How many events the queue of a listener holds by default
+pub const DEFAULT_QUEUE_CAPACITY: usize = 4096;
+
+/// What the listener does with the events that arrive while its queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest event in the queue to make room for the new one
+    DropOldest,
+    /// Drop the new event
+    DropNewest,
+    /// Add the new move to the last queued move of the same device and axis, so
+    /// the motion is delayed instead of lost. The button events are never dropped,
+    /// the oldest queued move is evicted to make room for them, or the queue grows
+    /// past its capacity if it holds only buttons. The other events are dropped like
+    /// `DropNewest`
+    CoalesceMoves,
+    /// Wait until the callbacks make room, the devices are not read meanwhile
+    Block,
+}
+
+/// The queue of the events that are read from the mice, see `set_event_queue`
+/// of the managers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueOptions {
+    /// How many events the queue holds, at least one
+    pub capacity: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        QueueOptions {
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            policy: OverflowPolicy::CoalesceMoves,
+        }
+    }
+}
+
+/// The events that `OverflowPolicy::CoalesceMoves` can merge
+pub(crate) trait Coalesce: Sized {
+    /// Merge the given event into the queued ones, returns whether it is merged
+    fn coalesce(queued: &mut VecDeque<Self>, newer: &Self) -> bool;
+    /// Whether the event is a move, which is evicted first to make room
+    fn is_move(&self) -> bool;
+    /// Whether the event must not be dropped, e.g. the press of a button
+    fn is_essential(&self) -> bool;
+}
+
+struct State<T> {
+    events: VecDeque<T>,
+    /// Whether the sender and the receiver are still alive
+    sending: bool,
+    receiving: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// Notified whenever an event is queued or taken, or either side is dropped
+    changed: Condvar,
+    options: QueueOptions,
+    listener: ListenerHandle,
+}
+
+impl<T> Shared<T> {
+    fn wait<'a>(&self, state: MutexGuard<'a, State<T>>) -> MutexGuard<'a, State<T>> {
+        self.changed
+            .wait(state)
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn drop_event(&self) {
+        self.listener
+            .counters()
+            .events_dropped
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The side of the queue that the reader thread sends the events to
+pub(crate) struct QueueSender<T>(Arc<Shared<T>>);
+
+/// The side of the queue that the dispatching thread iterates over,
+/// the iteration ends once the sender is dropped and the queue is empty
+pub(crate) struct QueueReceiver<T>(Arc<Shared<T>>);
+
+/// Create a queue with the given options, the dropped events are counted
+/// in the statistics of the given listener
+pub(crate) fn bounded<T: Coalesce>(
+    options: QueueOptions,
+    listener: &ListenerHandle,
+) -> (QueueSender<T>, QueueReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            events: VecDeque::new(),
+            sending: true,
+            receiving: true,
+        }),
+        changed: Condvar::new(),
+        options: QueueOptions {
+            capacity: options.capacity.max(1),
+            policy: options.policy,
+        },
+        listener: listener.clone(),
+    });
+    (QueueSender(shared.clone()), QueueReceiver(shared))
+}
+
+impl<T: Coalesce> QueueSender<T> {
+    /// Queue the given event, fails with the event once the receiver is dropped
+    pub(crate) fn send(&self, event: T) -> Result<(), T> {
+        let shared = &self.0;
+        let mut state = shared.state.lock_unpoisoned();
+        loop {
+            if !state.receiving {
+                return Err(event);
+            }
+            if state.events.len() < shared.options.capacity {
+                break;
+            }
+            match shared.options.policy {
+                OverflowPolicy::DropOldest => {
+                    state.events.pop_front();
+                    shared.drop_event();
+                    break;
+                }
+                OverflowPolicy::DropNewest => {
+                    shared.drop_event();
+                    return Ok(());
+                }
+                OverflowPolicy::CoalesceMoves => {
+                    if T::coalesce(&mut state.events, &event) {
+                        return Ok(());
+                    }
+                    if !event.is_essential() {
+                        shared.drop_event();
+                        return Ok(());
+                    }
+                    // Make room by dropping the oldest queued move, or else any
+                    // other queued event that is not essential either
+                    let evicted = state.events.iter().position(T::is_move).or_else(|| {
+                        state
+                            .events
+                            .iter()
+                            .position(|queued| !queued.is_essential())
+                    });
+                    if let Some(index) = evicted {
+                        state.events.remove(index);
+                        shared.drop_event();
+                    }
+                    break;
+                }
+                OverflowPolicy::Block => state = shared.wait(state),
+            }
+        }
+        state.events.push_back(event);
+        drop(state);
+        shared.changed.notify_all();
+        Ok(())
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        self.0.state.lock_unpoisoned().sending = false;
+        self.0.changed.notify_all();
+    }
+}
+
+impl<T> Iterator for QueueReceiver<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let shared = &self.0;
+        let mut state = shared.state.lock_unpoisoned();
+        loop {
+            if let Some(event) = state.events.pop_front() {
+                drop(state);
+                // Wake up the sender that waits for the room
+                shared.changed.notify_all();
+                return Some(event);
+            }
+            if !state.sending {
+                return None;
+            }
+            state = shared.wait(state);
+        }
+    }
+}
+
+impl<T> Drop for QueueReceiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock_unpoisoned();
+        state.receiving = false;
+        state.events.clear();
+        drop(state);
+        self.0.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bounded, Coalesce, OverflowPolicy, QueueOptions};
+    use crate::nix::listener::Listener;
+    use std::{collections::VecDeque, sync::atomic::Ordering, thread, time::Duration};
+
+    /// Moves are positive, the buttons are negative and the syncs are zero
+    impl Coalesce for i32 {
+        fn coalesce(queued: &mut VecDeque<Self>, newer: &Self) -> bool {
+            match queued.back_mut() {
+                Some(last) if *last > 0 && *newer > 0 => {
+                    *last += newer;
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        fn is_move(&self) -> bool {
+            *self > 0
+        }
+
+        fn is_essential(&self) -> bool {
+            *self < 0
+        }
+    }
+
+    fn send_all(policy: OverflowPolicy, events: &[i32]) -> (Vec<i32>, u64) {
+        let listener = Listener::new().unwrap();
+        let (tx, rx) = bounded(
+            QueueOptions {
+                capacity: 2,
+                policy,
+            },
+            &listener.handle(),
+        );
+        for event in events {
+            tx.send(*event).unwrap();
+        }
+        drop(tx);
+        let dropped = listener
+            .handle()
+            .counters()
+            .events_dropped
+            .load(Ordering::Relaxed);
+        (rx.collect(), dropped)
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let events = [-1, 2, 3, -4];
+        assert_eq!(
+            send_all(OverflowPolicy::DropOldest, &events),
+            (vec![3, -4], 2)
+        );
+        assert_eq!(
+            send_all(OverflowPolicy::DropNewest, &events),
+            (vec![-1, 2], 2)
+        );
+        // The buttons evict the moves, and then the other events
+        assert_eq!(
+            send_all(OverflowPolicy::CoalesceMoves, &events),
+            (vec![-1, -4], 1)
+        );
+        assert_eq!(
+            send_all(OverflowPolicy::CoalesceMoves, &[0, 2, -1]),
+            (vec![0, -1], 1)
+        );
+        assert_eq!(
+            send_all(OverflowPolicy::CoalesceMoves, &[0, 0, -1]),
+            (vec![0, -1], 1)
+        );
+        assert_eq!(
+            send_all(OverflowPolicy::CoalesceMoves, &[2, 0, 0, 3]),
+            (vec![2, 0], 2)
+        );
+        // The queue grows rather than dropping a button
+        assert_eq!(
+            send_all(OverflowPolicy::CoalesceMoves, &[-1, -2, -3]),
+            (vec![-1, -2, -3], 0)
+        );
+    }
+
+    #[test]
+    fn block_until_received() {
+        let listener = Listener::new().unwrap();
+        let (tx, mut rx) = bounded(
+            QueueOptions {
+                capacity: 1,
+                policy: OverflowPolicy::Block,
+            },
+            &listener.handle(),
+        );
+        let sender = thread::spawn(move || {
+            for event in 0..3 {
+                tx.send(event).unwrap();
+            }
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rx.next(), Some(0));
+        assert_eq!(rx.by_ref().collect::<Vec<_>>(), vec![1, 2]);
+        sender.join().unwrap();
+
+        // The sender fails once the receiver is gone
+        let (tx, rx) = bounded::<i32>(QueueOptions::default(), &listener.handle());
+        drop(rx);
+        assert_eq!(tx.send(1), Err(1));
+    }
+}
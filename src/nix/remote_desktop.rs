@@ -0,0 +1,388 @@
+///
+/// This module contains the mouse manager for the sandboxed applications e.g.
+/// Flatpak and Snap, which can not open /dev/uinput or the files under /dev/input
+///
+/// The actions are sent to the compositor through the RemoteDesktop portal,
+/// and the events are received through the InputCapture portal like the
+/// listener of the `portal` feature. The portal asks the user for the
+/// permission once the manager is created
+///
+use crate::common::{
+    Backend, CallbackId, CallbackPanic, Capability, EventAction, EventMask, EventWithTime,
+    ListenerStats, MouseButton, MouseController, MouseEvent, MouseEventExt, MouseListener,
+    Propagation, ScrollDirection, ScrollPhase,
+};
+use crate::error::Error;
+use crate::hooks::{Handler, DEFAULT_PRIORITY};
+use crate::keyboard::{ModifierKeys, Modifiers};
+use crate::nix::dbus::{Connection, Value};
+use crate::nix::listener::Listener;
+use crate::nix::portal::{self, PORTAL_DESTINATION, PORTAL_PATH};
+use crate::nix::uinput::button_code;
+use std::{env, path::Path, sync::Arc};
+
+const REMOTE_DESKTOP_INTERFACE: &str = "org.freedesktop.portal.RemoteDesktop";
+/// The device type of the RemoteDesktop sessions to control the pointer
+const DEVICE_POINTER: u32 = 2;
+/// The axes of `NotifyPointerAxisDiscrete`
+const AXIS_VERTICAL: u32 = 0;
+const AXIS_HORIZONTAL: u32 = 1;
+
+/// Check whether the process runs in a sandbox that hides the devices,
+/// where the portals are the only way to control the mouse
+pub fn sandboxed() -> bool {
+    Path::new("/.flatpak-info").exists() || env::var_os("SNAP").is_some()
+}
+
+/// Controls the mouse through the RemoteDesktop portal
+///
+/// The portal can only move the pointer relatively, without a screen cast
+/// stream there are no coordinates to move it to, so `move_to` and
+/// `get_position` return `Error::NotImplemented`
+pub struct RemoteDesktopMouseManager {
+    /// The session is closed when the connection to the bus is closed
+    connection: Connection,
+    session: String,
+    callbacks: super::Callbacks,
+    callback_counter: CallbackId,
+    listener: Option<Listener>,
+    /// The keyboard manager that holds the modifiers of `click_with_modifiers`
+    modifier_keys: ModifierKeys,
+}
+
+impl RemoteDesktopMouseManager {
+    /// Start a RemoteDesktop session that controls the pointer,
+    /// which waits for the user to allow it
+    pub fn new() -> Result<Self, Error> {
+        let connection = Connection::session()?;
+        let session = create_session(&connection)?;
+        Ok(RemoteDesktopMouseManager {
+            connection,
+            session,
+            callbacks: Arc::default(),
+            callback_counter: 0,
+            listener: None,
+            modifier_keys: ModifierKeys::default(),
+        })
+    }
+
+    /// Call the given notify method of the session with the given arguments,
+    /// which follow the session and the empty options
+    fn notify(&self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        self.notify_with_options(method, Vec::new(), args)
+    }
+
+    fn notify_with_options(
+        &self,
+        method: &str,
+        options: Vec<(&str, Value)>,
+        args: Vec<Value>,
+    ) -> Result<(), Error> {
+        let mut values = vec![Value::Path(self.session.clone()), Value::dict(options)];
+        values.extend(args);
+        self.connection.call(
+            PORTAL_DESTINATION,
+            PORTAL_PATH,
+            REMOTE_DESKTOP_INTERFACE,
+            method,
+            &values,
+        )?;
+        Ok(())
+    }
+
+    fn button_event(&self, button: &MouseButton, is_press: bool) -> Result<(), Error> {
+        self.notify(
+            "NotifyPointerButton",
+            vec![Value::I32(button_code(button)), Value::U32(is_press.into())],
+        )
+    }
+
+    fn add_callback(
+        &mut self,
+        priority: i32,
+        mask: EventMask,
+        handler: Handler<MouseEventExt>,
+    ) -> Result<CallbackId, Error> {
+        if self.listener.is_none() {
+            self.listener = Some(portal::start_portal_listener(&self.callbacks)?);
+        }
+
+        let id = self.callback_counter;
+        self.callbacks.insert(id, priority, mask, handler);
+        self.callback_counter += 1;
+        Ok(id)
+    }
+}
+
+/// Create a RemoteDesktop session for the pointer and start it, returns the handle of the session
+fn create_session(connection: &Connection) -> Result<String, Error> {
+    let results = portal::request(
+        connection,
+        REMOTE_DESKTOP_INTERFACE,
+        "CreateSession",
+        |token| {
+            vec![Value::dict(vec![
+                ("handle_token", Value::Str(token.to_string())),
+                ("session_handle_token", Value::Str(token.to_string())),
+            ])]
+        },
+    )?;
+    let session = match results.get("session_handle") {
+        Some(Value::Path(session)) | Some(Value::Str(session)) => session.clone(),
+        _ => {
+            return Err(Error::CustomError(
+                "the portal did not create a session".to_string(),
+            ))
+        }
+    };
+
+    portal::request(
+        connection,
+        REMOTE_DESKTOP_INTERFACE,
+        "SelectDevices",
+        |token| {
+            vec![
+                Value::Path(session.clone()),
+                Value::dict(vec![
+                    ("handle_token", Value::Str(token.to_string())),
+                    ("types", Value::U32(DEVICE_POINTER)),
+                ]),
+            ]
+        },
+    )?;
+    // The user is asked for the permission here
+    let results = portal::request(connection, REMOTE_DESKTOP_INTERFACE, "Start", |token| {
+        vec![
+            Value::Path(session.clone()),
+            Value::Str(String::new()),
+            Value::dict(vec![("handle_token", Value::Str(token.to_string()))]),
+        ]
+    })?;
+    match results.get("devices") {
+        Some(Value::U32(devices)) if devices & DEVICE_POINTER != 0 => Ok(session),
+        _ => Err(Error::PermissionDenied),
+    }
+}
+
+/// Get the steps of `NotifyPointerAxisDiscrete` for the given detents, the
+/// portal scrolls down and right for the positive steps like libinput
+fn discrete_steps(dx: i32, dy: i32) -> [(u32, i32); 2] {
+    [(AXIS_VERTICAL, -dy), (AXIS_HORIZONTAL, dx)]
+}
+
+impl MouseController for RemoteDesktopMouseManager {
+    fn move_to(&mut self, _x: usize, _y: usize) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn move_relative(&mut self, x_offset: i32, y_offset: i32) -> Result<(), Error> {
+        self.notify(
+            "NotifyPointerMotion",
+            vec![Value::F64(x_offset.into()), Value::F64(y_offset.into())],
+        )
+    }
+
+    fn get_position(&self) -> Result<(i32, i32), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Portal
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::SmoothScroll
+                | Capability::InjectEvent
+                | Capability::PauseHook
+                | Capability::CallbackPanics
+                | Capability::ListenerStats
+        )
+    }
+
+    fn press_button(&mut self, button: &MouseButton) -> Result<(), Error> {
+        self.button_event(button, true)
+    }
+
+    fn release_button(&mut self, button: &MouseButton) -> Result<(), Error> {
+        self.button_event(button, false)
+    }
+
+    fn click_with_modifiers(
+        &mut self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        let modifier_keys = self.modifier_keys.clone();
+        modifier_keys.hold(modifiers, || self.click_button(button))
+    }
+
+    fn scroll_wheel(&mut self, direction: &ScrollDirection) -> Result<(), Error> {
+        let (dx, dy) = match direction {
+            ScrollDirection::Up => (0, 1),
+            ScrollDirection::Down => (0, -1),
+            ScrollDirection::Left => (-1, 0),
+            ScrollDirection::Right => (1, 0),
+        };
+        self.scroll(dx, dy)
+    }
+
+    fn scroll(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        for (axis, steps) in discrete_steps(dx, dy) {
+            if steps != 0 {
+                self.notify(
+                    "NotifyPointerAxisDiscrete",
+                    vec![Value::U32(axis), Value::I32(steps)],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn scroll_smooth(&mut self, dx: f64, dy: f64, phase: Option<ScrollPhase>) -> Result<(), Error> {
+        // The end of a gesture lets the compositor start the kinetic scrolling
+        let finish = matches!(phase, Some(ScrollPhase::Ended));
+        self.notify_with_options(
+            "NotifyPointerAxis",
+            vec![("finish", Value::Bool(finish))],
+            vec![Value::F64(dx), Value::F64(-dy)],
+        )
+    }
+}
+
+impl MouseListener for RemoteDesktopMouseManager {
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_with_time(Box::new(move |event| callback(&event.event)))
+    }
+
+    fn hook_with_time(
+        &mut self,
+        callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_ext(Box::new(move |event| {
+            callback(&EventWithTime {
+                event: event.event.clone(),
+                time: event.time,
+            })
+        }))
+    }
+
+    fn hook_ext(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            DEFAULT_PRIORITY,
+            EventMask::ALL,
+            Handler::Action(Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            })),
+        )
+    }
+
+    fn hook_filtered(
+        &mut self,
+        mask: EventMask,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            DEFAULT_PRIORITY,
+            mask,
+            Handler::Action(Box::new(move |event| {
+                callback(&event.event);
+                EventAction::PassThrough
+            })),
+        )
+    }
+
+    fn hook_with_priority(
+        &mut self,
+        priority: i32,
+        callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            priority,
+            EventMask::ALL,
+            Handler::Propagate(Box::new(move |event| callback(&event.event))),
+        )
+    }
+
+    fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+        Ok(super::inject(&self.callbacks, event))
+    }
+
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.remove(callback_id) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, true) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, false) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn on_callback_panic(
+        &mut self,
+        unhook: bool,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
+        self.callbacks.set_panic_handler(handler, unhook);
+        Ok(())
+    }
+
+    fn unhook_all(&mut self) -> Result<(), Error> {
+        self.callbacks.clear();
+        Ok(())
+    }
+
+    fn stop_listening(&mut self) -> Result<(), Error> {
+        // Dropping the listener stops and joins its thread
+        self.listener = None;
+        Ok(())
+    }
+
+    fn listener_stats(&self) -> Result<ListenerStats, Error> {
+        Ok(self
+            .listener
+            .as_ref()
+            .map(Listener::stats)
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{discrete_steps, AXIS_HORIZONTAL, AXIS_VERTICAL};
+
+    #[test]
+    fn scroll_steps() {
+        // Scrolling up is a negative step on the vertical axis of the portal
+        assert_eq!(
+            discrete_steps(0, 2),
+            [(AXIS_VERTICAL, -2), (AXIS_HORIZONTAL, 0)]
+        );
+        assert_eq!(
+            discrete_steps(-1, 0),
+            [(AXIS_VERTICAL, 0), (AXIS_HORIZONTAL, -1)]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+///
+/// This module contains the detection of the screen size and the scale
+/// factor of the desktop, for the managers that need to map the pixels
+/// to the units of their devices e.g. uinput, and the other settings of
+/// the desktop that are read from the X resources
+///
+use crate::display::Monitor;
+use crate::nix::x11;
+use glob::glob;
+use std::{env, fs, time::Duration};
+
+/// The DPI of the unscaled desktops
+const BASE_DPI: f64 = 96.;
+
+/// Get the size of the whole screen in pixels, i.e. the area that the
+/// absolute devices are mapped onto, None if it can not be detected
+///
+/// The X server (also XWayland) reports the size of the whole layout, otherwise
+/// the mode of the only connected output is used, as the layout of several
+/// outputs is only known by the compositor
+pub fn screen_size() -> Option<(i32, i32)> {
+    x11::query_screen_size().or_else(drm_screen_size)
+}
+
+/// Get the size of the area that the monitors span, i.e. the area that the
+/// absolute devices are mapped onto, None if it can not be detected
+pub fn layout_size() -> Option<(i32, i32)> {
+    bounding_size(&monitors())
+}
+
+/// Get the scale factor of the desktop, 1 if it is not scaled
+///
+/// Uses the `Xft.dpi` resource that the desktops set for the X clients,
+/// and falls back to the scale variables of GTK and Qt
+pub fn scale_factor() -> f64 {
+    x11::query_resources()
+        .and_then(|resources| parse_xft_dpi(&resources))
+        .map(|dpi| dpi / BASE_DPI)
+        .or_else(|| parse_scale(env::var("GDK_SCALE").ok()?.as_str()))
+        .or_else(|| parse_scale(env::var("QT_SCALE_FACTOR").ok()?.as_str()))
+        .unwrap_or(1.)
+}
+
+/// Get the double click time of the desktop from the `multiClickTime`
+/// resource, None if the desktop does not set it
+pub fn double_click_time() -> Option<Duration> {
+    x11::query_resources().and_then(|resources| parse_multi_click_time(&resources))
+}
+
+/// Get the monitors of the session
+///
+/// Wayland sessions read the outputs of the compositor with the `wayland` feature,
+/// X11 (also XWayland) reads the monitors of RandR with the `xrandr` feature,
+/// otherwise the whole screen is reported as a single monitor
+pub fn monitors() -> Vec<Monitor> {
+    #[cfg(feature = "wayland")]
+    if super::display_manager() == "wayland" {
+        if let Some(monitors) = super::wayland::monitors() {
+            return monitors;
+        }
+    }
+    #[cfg(feature = "xrandr")]
+    if let Some(monitors) = super::xrandr::monitors(scale_factor()) {
+        return monitors;
+    }
+    screen_size()
+        .map(|(width, height)| {
+            vec![Monitor {
+                id: 0,
+                name: String::new(),
+                x: 0,
+                y: 0,
+                width,
+                height,
+                scale: scale_factor(),
+                primary: true,
+            }]
+        })
+        .unwrap_or_default()
+}
+
+/// Get the size of the smallest area that covers the given monitors, the
+/// monitors may be placed at negative positions left of or above the primary one
+fn bounding_size(monitors: &[Monitor]) -> Option<(i32, i32)> {
+    let left = monitors.iter().map(|monitor| monitor.x).min()?;
+    let top = monitors.iter().map(|monitor| monitor.y).min()?;
+    let right = monitors
+        .iter()
+        .map(|monitor| monitor.x + monitor.width)
+        .max()?;
+    let bottom = monitors
+        .iter()
+        .map(|monitor| monitor.y + monitor.height)
+        .max()?;
+    Some((right - left, bottom - top))
+}
+
+/// Get the mode of the only connected output from the kernel
+fn drm_screen_size() -> Option<(i32, i32)> {
+    let mut connected = glob("/sys/class/drm/card*-*/status")
+        .expect("Failed to read glob pattern")
+        .flatten()
+        .filter(|status| {
+            fs::read_to_string(status).is_ok_and(|status| status.trim() == "connected")
+        });
+    let status = connected.next()?;
+    if connected.next().is_some() {
+        return None;
+    }
+    // The preferred mode is listed first, which the compositors use by default
+    let modes = fs::read_to_string(status.with_file_name("modes")).ok()?;
+    parse_mode(modes.lines().next()?)
+}
+
+/// Parse a mode of a DRM connector e.g. `1920x1080`, the interlaced
+/// modes have an `i` suffix
+fn parse_mode(mode: &str) -> Option<(i32, i32)> {
+    let (width, height) = mode.trim().split_once('x')?;
+    let height = height.trim_end_matches(|c: char| !c.is_ascii_digit());
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// Find the `Xft.dpi` value in the given resources of the X server
+fn parse_xft_dpi(resources: &str) -> Option<f64> {
+    resources.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim() != "Xft.dpi" {
+            return None;
+        }
+        value.trim().parse().ok().filter(|dpi: &f64| *dpi > 0.)
+    })
+}
+
+/// Parse the `multiClickTime` resource of the X toolkit in milliseconds,
+/// either for all the clients e.g. `*multiClickTime` or for one of them
+fn parse_multi_click_time(resources: &str) -> Option<Duration> {
+    resources.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().ends_with("multiClickTime") {
+            return None;
+        }
+        let millis: u64 = value.trim().parse().ok()?;
+        Some(Duration::from_millis(millis)).filter(|time| !time.is_zero())
+    })
+}
+
+/// Parse a positive scale factor
+fn parse_scale(scale: &str) -> Option<f64> {
+    scale.trim().parse().ok().filter(|scale: &f64| *scale > 0.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_sizes() {
+        let monitor = |x, width, height| Monitor {
+            id: 0,
+            name: String::new(),
+            x,
+            y: 0,
+            width,
+            height,
+            scale: 1.,
+            primary: false,
+        };
+        assert_eq!(
+            bounding_size(&[monitor(0, 1920, 1080), monitor(1920, 2560, 1440)]),
+            Some((4480, 1440))
+        );
+        // A monitor left of the primary one
+        assert_eq!(
+            bounding_size(&[monitor(-1280, 1280, 1024), monitor(0, 1920, 1080)]),
+            Some((3200, 1080))
+        );
+        let above = Monitor {
+            y: -1440,
+            ..monitor(0, 2560, 1440)
+        };
+        assert_eq!(
+            bounding_size(&[above, monitor(0, 1920, 1080)]),
+            Some((2560, 2520))
+        );
+        assert_eq!(bounding_size(&[]), None);
+    }
+
+    #[test]
+    fn parse_modes() {
+        assert_eq!(parse_mode("1920x1080\n"), Some((1920, 1080)));
+        assert_eq!(parse_mode("1920x1080i"), Some((1920, 1080)));
+        assert_eq!(parse_mode("preferred"), None);
+    }
+
+    #[test]
+    fn parse_resources() {
+        let resources = "Xcursor.size:\t24\nXft.antialias:\t1\nXft.dpi:\t144\n";
+        assert_eq!(parse_xft_dpi(resources), Some(144.));
+        assert_eq!(parse_xft_dpi("Xcursor.size:\t24\n"), None);
+        assert_eq!(parse_scale("2"), Some(2.));
+        assert_eq!(parse_scale("0"), None);
+        assert_eq!(
+            parse_multi_click_time("Xft.dpi:\t96\n*multiClickTime:\t400\n"),
+            Some(Duration::from_millis(400))
+        );
+        assert_eq!(parse_multi_click_time("XTerm*multiClickTime: 0\n"), None);
+    }
+}
@@ -1,52 +1,388 @@
 ///
-/// This module contains the mouse action functions
-/// for the li&nux systems that uses uinput
+/// This module contains the mouse, keyboard and touch action
+/// functions for the li&nux systems that uses uinput
 ///
-/// - Unsupported mouse actions
-///     - get_position is not available on uinput
+/// - Limited mouse actions
+///     - uinput does not report the position of the mouse, so get_position
+///       reads it from X11 if possible, otherwise it returns the position
+///       that is tracked by the manager itself
 ///
-use crate::common::{CallbackId, MouseActions, MouseButton, MouseEvent, ScrollDirection};
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, CoordinateSpace, EventAction,
+    EventMask, EventWithTime, ListenerStats, Motion, MotionProfile, MouseButton, MouseController,
+    MouseEvent, MouseEventExt, MouseListener, Propagation, ScrollDirection,
+};
+use crate::error::Error;
+use crate::hooks::{CallbackList, Handler, DEFAULT_PRIORITY};
+use crate::keyboard::{Key, KeyboardActions, ModifierKeys, Modifiers};
+use crate::nix::calibration::Calibration;
+use crate::nix::listener::Listener;
+use crate::nix::queue::QueueOptions;
+use crate::nix::RawInputEvent;
+use crate::touch::TouchActions;
+use crate::trace;
+use glob::glob;
 use std::{
-    collections::HashMap,
     fs::File,
-    io::{Error, ErrorKind, Result},
-    mem::size_of,
+    io,
+    mem::{size_of, size_of_val},
     os::{
-        raw::{c_char, c_int, c_long, c_uint, c_ulong, c_ushort},
+        raw::{c_char, c_int, c_long, c_uint, c_ulong, c_ushort, c_void},
         unix::{fs::OpenOptionsExt, io::AsRawFd},
     },
-    sync::{Arc, Mutex},
+    path::Path,
+    sync::Arc,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 const UINPUT_MAX_NAME_SIZE: usize = 80;
+/// The directory that the kernel lists the uinput devices under
+const SYS_VIRTUAL_INPUT_DIR: &str = "/sys/devices/virtual/input";
+/// The interval between the checks for the event file of a created device
+const SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// The moves of the calibration, the steps are small and spaced like the
+/// reports of a real mouse so that the usual acceleration is measured
+const CALIBRATION_STEP: i32 = 4;
+const CALIBRATION_STEPS: i32 = 25;
+const CALIBRATION_INTERVAL: Duration = Duration::from_millis(8);
+/// The time that the cursor is given to catch up before its position is read
+const CALIBRATION_SETTLE: Duration = Duration::from_millis(50);
 
 pub struct UInputMouseManager {
     uinput_file: File,
-    callbacks: Arc<Mutex<HashMap<CallbackId, Box<dyn Fn(&MouseEvent) + Send>>>>,
+    callbacks: super::Callbacks,
     callback_counter: CallbackId,
-    is_listening: bool,
+    listener: Option<Listener>,
+    /// The callbacks of `hook_raw`, they share the ids with the other callbacks
+    raw_callbacks: super::RawCallbacks,
+    raw_listener: Option<Listener>,
+    /// The callbacks of `on_listener_restarted`, they share the ids as well
+    restart_callbacks: super::RestartCallbacks,
+    /// The queue of the events that the listeners read
+    queue: QueueOptions,
+    /// Whether the device is created by another manager, so that it is not destroyed on drop
+    attached: bool,
+    /// Whether the listener grabs the devices
+    grabbing: bool,
+    /// The callbacks that grab the devices, the listener stops grabbing once they are gone
+    grab_ids: Vec<CallbackId>,
+    /// Maps the coordinates to the units of the device
+    mapping: Mapping,
+    /// The tracked position of the mouse in physical pixels, unknown
+    /// until the mouse is moved to a point
+    position: Option<(i32, i32)>,
+    /// The high resolution scroll amounts that do not add up to a whole detent yet
+    scroll_remainder: (i32, i32),
+    /// The pixels that a unit of the relative axes moves the cursor
+    calibration: Calibration,
+    /// Maps the offsets of the relative moves to the moved pixels
+    motion: Motion,
+    /// Whether the relative moves are emitted as absolute moves
+    unaccelerated: bool,
+    /// The buttons that are registered to the device
+    buttons: Vec<MouseButton>,
+    /// The virtual mouse itself, if its events are hidden from the callbacks
+    own_device: Option<Arc<OwnDevice>>,
+    /// The keyboard manager that holds the modifiers of `click_with_modifiers`
+    modifier_keys: ModifierKeys,
 }
 
-impl UInputMouseManager {
-    pub fn new(rng_x: (i32, i32), rng_y: (i32, i32)) -> Result<Self> {
-        let manager = UInputMouseManager {
-            uinput_file: File::options()
+/// Maps the coordinates of the actions to the units of the virtual mouse
+#[derive(Clone, Copy)]
+struct Mapping {
+    /// The ranges of the absolute axes of the device
+    rng_x: (i32, i32),
+    rng_y: (i32, i32),
+    /// The size of the screen in pixels, which the absolute axes span.
+    /// The pixels are used as the units of the axes if it is not known
+    screen: Option<(i32, i32)>,
+    /// The units of the coordinates, and the scale factor of the logical ones
+    space: CoordinateSpace,
+    scale: f64,
+}
+
+impl Mapping {
+    /// The ranges of the physical pixels that the mouse can be moved within
+    fn pixel_ranges(&self) -> super::Ranges {
+        match self.screen {
+            Some((width, height)) => ((0, width - 1), (0, height - 1)),
+            None => (self.rng_x, self.rng_y),
+        }
+    }
+
+    /// Convert the given coordinates to the physical pixels on the screen
+    fn pixels(&self, x: i32, y: i32) -> (i32, i32) {
+        let (x, y) = self.space.space_to_physical(x, y, self.scale);
+        let (rng_x, rng_y) = self.pixel_ranges();
+        (x.clamp(rng_x.0, rng_x.1), y.clamp(rng_y.0, rng_y.1))
+    }
+
+    /// Move the given position in physical pixels by the given offset, within the screen
+    fn offset(&self, position: (i32, i32), x_offset: i32, y_offset: i32) -> (i32, i32) {
+        let (rng_x, rng_y) = self.pixel_ranges();
+        (
+            position.0.saturating_add(x_offset).clamp(rng_x.0, rng_x.1),
+            position.1.saturating_add(y_offset).clamp(rng_y.0, rng_y.1),
+        )
+    }
+
+    /// Convert the given units of the absolute axes to the physical pixels
+    fn axis_pixels(&self, x: i32, y: i32) -> (i32, i32) {
+        if self.screen.is_none() {
+            return (x, y);
+        }
+        let (rng_x, rng_y) = self.pixel_ranges();
+        (
+            super::scale(x, self.rng_x, rng_x),
+            super::scale(y, self.rng_y, rng_y),
+        )
+    }
+
+    /// Convert the given physical pixels to the units of the absolute axes
+    fn axis_units(&self, x: i32, y: i32) -> (i32, i32) {
+        if self.screen.is_none() {
+            return (x, y);
+        }
+        let (rng_x, rng_y) = self.pixel_ranges();
+        (
+            super::scale(x, rng_x, self.rng_x),
+            super::scale(y, rng_y, self.rng_y),
+        )
+    }
+}
+
+/// Identifies the events that the virtual mouse of a manager emits,
+/// so that they are not fed back to the callbacks of the same manager
+struct OwnDevice {
+    /// The name of the device under /sys/devices/virtual/input e.g. input42
+    sysname: String,
+    /// The event file of the device, the listeners that read the event
+    /// files report it as the id of the device
+    devnode: Option<String>,
+}
+
+impl OwnDevice {
+    /// Whether the event of the device with the given id is emitted by the device,
+    /// the devices of other programs may have the same name so only the device
+    /// node tells them apart
+    fn emitted(&self, device_id: &str) -> bool {
+        match &self.devnode {
+            Some(devnode) => devnode == device_id,
+            // The event handler was not listed yet when the device was created
+            None => Path::new(device_id).file_name().is_some_and(|name| {
+                Path::new(SYS_VIRTUAL_INPUT_DIR)
+                    .join(&self.sysname)
+                    .join(name)
+                    .exists()
+            }),
+        }
+    }
+}
+
+/// Configures the identity and the capabilities of the virtual mouse
+/// that is created by `UInputMouseManager`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::common::MouseButton;
+/// use mouce::nix::UInputMouseManager;
+///
+/// let manager = UInputMouseManager::builder((0, 1920), (0, 1080))
+///     .name("my-virtual-mouse")
+///     .vendor_id(0x046d)
+///     .product_id(0xc077)
+///     .buttons(&[MouseButton::Left, MouseButton::Right])
+///     .hwheel(false)
+///     .build()
+///     .unwrap();
+/// ```
+pub struct UInputMouseManagerBuilder {
+    rng_x: (i32, i32),
+    rng_y: (i32, i32),
+    name: String,
+    bus_type: u16,
+    vendor_id: u16,
+    product_id: u16,
+    version: u16,
+    buttons: Vec<MouseButton>,
+    wheel: bool,
+    hwheel: bool,
+    settle: Settle,
+    ignore_own_events: bool,
+    /// The size of the screen, detected if it is not set
+    screen_size: Option<(i32, i32)>,
+    calibration: Calibration,
+    unaccelerated: bool,
+    /// The additional event codes that the device is enabled for, by their types
+    codes: Vec<(u16, u16)>,
+}
+
+/// How to wait for the userspace to pick up the created device
+enum Settle {
+    /// Wait for a fixed time
+    Delay(Duration),
+    /// Wait until the event file of the device is created, at most for the given time
+    WaitForDevice(Duration),
+}
+
+impl UInputMouseManagerBuilder {
+    /// The name of the device, at most 79 bytes
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// The bus type of the device e.g. 0x03 for USB, 0x05 for Bluetooth
+    pub fn bus_type(mut self, bus_type: u16) -> Self {
+        self.bus_type = bus_type;
+        self
+    }
+
+    pub fn vendor_id(mut self, vendor_id: u16) -> Self {
+        self.vendor_id = vendor_id;
+        self
+    }
+
+    pub fn product_id(mut self, product_id: u16) -> Self {
+        self.product_id = product_id;
+        self
+    }
+
+    pub fn version(mut self, version: u16) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// The buttons that the device has, the other buttons can not be pressed,
+    /// all the buttons of `MouseButton` by default
+    pub fn buttons(mut self, buttons: &[MouseButton]) -> Self {
+        self.buttons = buttons.to_vec();
+        self
+    }
+
+    /// Whether the device has a vertical scroll wheel
+    pub fn wheel(mut self, wheel: bool) -> Self {
+        self.wheel = wheel;
+        self
+    }
+
+    /// Whether the device has a horizontal scroll wheel
+    pub fn hwheel(mut self, hwheel: bool) -> Self {
+        self.hwheel = hwheel;
+        self
+    }
+
+    /// The time to wait after creating the device, so that the userspace can
+    /// detect and initialize it before the first events are emitted
+    pub fn settle_delay(mut self, settle_delay: Duration) -> Self {
+        self.settle = Settle::Delay(settle_delay);
+        self
+    }
+
+    /// Wait until the event file of the device is created under /dev/input instead
+    /// of a fixed time, `build` fails if it is not created within the given timeout
+    ///
+    /// The file is created by udev once it has processed the device, which is
+    /// usually also when the compositors and the X server start reading it
+    pub fn wait_for_device(mut self, timeout: Duration) -> Self {
+        self.settle = Settle::WaitForDevice(timeout);
+        self
+    }
+
+    /// Whether the callbacks of the manager skip the events that its own virtual
+    /// mouse emits, so that the automations that both simulate and listen the
+    /// mouse do not react to their own actions
+    ///
+    /// The events are still passed through to the system while the devices
+    /// are grabbed. The portal listener reports the events without their
+    /// devices, so they are not skipped there
+    pub fn ignore_own_events(mut self, ignore_own_events: bool) -> Self {
+        self.ignore_own_events = ignore_own_events;
+        self
+    }
+
+    /// The size of the screen in pixels, which the absolute axes of the device span,
+    /// so that `move_to` moves the mouse in pixels whatever the ranges of the axes are
+    ///
+    /// The size is detected as in `new_auto` if it is not set, and the values
+    /// of `move_to` are written to the axes as they are if it can not be detected
+    pub fn screen_size(mut self, width: i32, height: i32) -> Self {
+        self.screen_size = Some((width, height));
+        self
+    }
+
+    /// The pixels that a unit of the relative axes moves the cursor, which
+    /// `move_relative` converts the offsets with, see `UInputMouseManager::calibrate`
+    pub fn calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    /// Whether the relative moves map 1:1 to pixels, without the pointer acceleration
+    ///
+    /// The desktops accelerate the relative axes of the mice whatever the properties
+    /// of the device are, so the relative moves are emitted as absolute moves from the
+    /// tracked position instead, like a tablet. The device is flagged with
+    /// `INPUT_PROP_POINTER` so that it is still handled as a mouse. The position is read
+    /// from X11 before every move, since the other mice may have moved the pointer. The
+    /// moves fall back to the calibrated relative units while the position is not known,
+    /// i.e. before the first `move_to` without X11
+    pub fn unaccelerated(mut self, unaccelerated: bool) -> Self {
+        self.unaccelerated = unaccelerated;
+        self
+    }
+
+    /// Enable an event code that the high level actions do not use, so that it
+    /// can be emitted with `UInputMouseManager::emit_raw` e.g. `BTN_TOUCH` or `REL_DIAL`
+    ///
+    /// The types `EV_KEY`, `EV_REL` and `EV_MSC` are supported, `build` fails for
+    /// the others. The types and the codes are listed in linux/input-event-codes.h
+    pub fn enable_code(mut self, r#type: u16, code: u16) -> Self {
+        self.codes.push((r#type, code));
+        self
+    }
+
+    /// Create the virtual mouse
+    pub fn build(self) -> Result<UInputMouseManager, Error> {
+        let _span = trace::span!("create_uinput_device");
+        if self.name.len() >= UINPUT_MAX_NAME_SIZE {
+            return Err(Error::CustomError(format!(
+                "the device name can not be longer than {} bytes",
+                UINPUT_MAX_NAME_SIZE - 1
+            )));
+        }
+        if let Some((r#type, _)) = self
+            .codes
+            .iter()
+            .find(|(r#type, _)| code_ioctl(*r#type as c_int).is_none())
+        {
+            return Err(Error::CustomError(format!(
+                "the codes of the event type {} can not be enabled",
+                r#type
+            )));
+        }
+
+        let (rng_x, rng_y) = (self.rng_x, self.rng_y);
+        let mut manager = UInputMouseManager::from_file(
+            File::options()
                 .write(true)
                 .custom_flags(O_NONBLOCK)
                 .open("/dev/uinput")?,
-            callbacks: Arc::new(Mutex::new(HashMap::new())),
-            callback_counter: 0,
-            is_listening: false,
-        };
+            (rng_x, rng_y),
+            self.screen_size,
+            self.buttons,
+        );
+        manager.calibration = self.calibration;
+        manager.unaccelerated = self.unaccelerated;
         let fd = manager.uinput_file.as_raw_fd();
         unsafe {
             // For press events (also needed for mouse movement)
             ioctl(fd, UI_SET_EVBIT, EV_KEY);
-            ioctl(fd, UI_SET_KEYBIT, BTN_LEFT);
-            ioctl(fd, UI_SET_KEYBIT, BTN_RIGHT);
-            ioctl(fd, UI_SET_KEYBIT, BTN_MIDDLE);
+            for button in manager.buttons.iter() {
+                ioctl(fd, UI_SET_KEYBIT, button_code(button));
+            }
 
             // For mouse movement
             ioctl(fd, UI_SET_EVBIT, EV_ABS);
@@ -86,81 +422,396 @@ impl UInputMouseManager {
             ioctl(fd, UI_SET_EVBIT, EV_REL);
             ioctl(fd, UI_SET_RELBIT, REL_X);
             ioctl(fd, UI_SET_RELBIT, REL_Y);
-            ioctl(fd, UI_SET_RELBIT, REL_WHEEL);
+            if self.unaccelerated {
+                ioctl(fd, UI_SET_PROPBIT, INPUT_PROP_POINTER);
+            }
+            if self.wheel {
+                ioctl(fd, UI_SET_RELBIT, REL_WHEEL);
+                ioctl(fd, UI_SET_RELBIT, REL_WHEEL_HI_RES);
+            }
+            if self.hwheel {
+                ioctl(fd, UI_SET_RELBIT, REL_HWHEEL);
+                ioctl(fd, UI_SET_RELBIT, REL_HWHEEL_HI_RES);
+            }
+            for (r#type, code) in self.codes.iter() {
+                if let Some(request) = code_ioctl(*r#type as c_int) {
+                    ioctl(fd, UI_SET_EVBIT, *r#type as c_int);
+                    ioctl(fd, request, *code as c_int);
+                }
+            }
         }
 
         let mut usetup = UInputSetup {
             id: InputId {
-                bustype: BUS_USB,
-                // Random vendor and product
-                vendor: 0x2222,
-                product: 0x3333,
-                version: 0,
+                bustype: self.bus_type,
+                vendor: self.vendor_id,
+                product: self.product_id,
+                version: self.version,
             },
             name: [0; UINPUT_MAX_NAME_SIZE],
             ff_effects_max: 0,
         };
-
-        let mut device_bytes: Vec<c_char> = "mouce-library-fake-mouse"
-            .chars()
-            .map(|ch| ch as c_char)
-            .collect();
-
-        // Fill the rest of the name buffer with empty chars
-        for _ in 0..UINPUT_MAX_NAME_SIZE - device_bytes.len() {
-            device_bytes.push('\0' as c_char);
+        // The rest of the name buffer is left as empty chars
+        for (i, byte) in self.name.bytes().enumerate() {
+            usetup.name[i] = byte as c_char;
         }
 
-        usetup.name.copy_from_slice(&device_bytes);
-
         unsafe {
-            ioctl(fd, UI_DEV_SETUP, &usetup);
-            ioctl(fd, UI_DEV_CREATE);
+            if ioctl(fd, UI_DEV_SETUP, &usetup) < 0 {
+                trace::warning!("UI_DEV_SETUP failed: {}", io::Error::last_os_error());
+            }
+            if ioctl(fd, UI_DEV_CREATE) < 0 {
+                trace::warning!("UI_DEV_CREATE failed: {}", io::Error::last_os_error());
+            }
         }
+        trace::debug!(
+            "created the virtual mouse {} ({:04x}:{:04x})",
+            self.name,
+            self.vendor_id,
+            self.product_id
+        );
 
         // On UI_DEV_CREATE the kernel will create the device node for this
         // device. We are inserting a pause here so that userspace has time
         // to detect, initialize the new device, and can start listening to
         // the event, otherwise it will not notice the event we are about to send.
-        thread::sleep(Duration::from_millis(300));
+        match self.settle {
+            Settle::Delay(delay) => thread::sleep(delay),
+            Settle::WaitForDevice(timeout) => wait_for_event_file(fd, timeout)?,
+        }
+
+        // The kernel may clamp the ranges or ignore them, e.g. the kernels before 4.5 do
+        // not know UI_ABS_SETUP, so the coordinates are mapped to the actual ranges
+        match device_ranges(fd) {
+            Some(ranges) if ranges != (rng_x, rng_y) => {
+                trace::warning!(
+                    "the virtual mouse has the ranges {:?} instead of {:?}",
+                    ranges,
+                    (rng_x, rng_y)
+                );
+                (manager.mapping.rng_x, manager.mapping.rng_y) = ranges;
+            }
+            _ => {}
+        }
+
+        if self.ignore_own_events {
+            let sysname = sysname(fd)?;
+            manager.own_device = Some(Arc::new(OwnDevice {
+                devnode: event_file(&sysname),
+                sysname,
+            }));
+        }
 
         Ok(manager)
     }
+}
 
-    /// Write the given event to the uinput file
-    fn emit(&mut self, r#type: c_int, code: c_int, value: c_int) -> Result<()> {
-        let mut event = InputEvent {
-            time: TimeVal {
-                tv_sec: 0,
-                tv_usec: 0,
+impl UInputMouseManager {
+    /// Create a virtual mouse with the default identity and capabilities,
+    /// see `builder` to configure them
+    pub fn new(rng_x: (i32, i32), rng_y: (i32, i32)) -> Result<Self, Error> {
+        Self::builder(rng_x, rng_y).build()
+    }
+
+    /// Create a virtual mouse whose absolute axes span the detected layout of the monitors,
+    /// so that `move_to` takes pixels without knowing the size of the screen
+    ///
+    /// The layout is read from the Wayland outputs with the `wayland` feature, from RandR with
+    /// the `xrandr` feature, otherwise from the X server or the only connected output of the kernel
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::nix::UInputMouseManager;
+    /// use mouce::MouseController;
+    ///
+    /// let mut manager = UInputMouseManager::new_auto().unwrap();
+    /// assert_eq!(manager.move_to(100, 100), Ok(()));
+    /// ```
+    pub fn new_auto() -> Result<Self, Error> {
+        let (width, height) = super::screen::layout_size()
+            .filter(|(width, height)| *width > 1 && *height > 1)
+            .ok_or_else(|| Error::CustomError("the screen size can not be detected".to_string()))?;
+        // A unit of the axes is a pixel
+        Self::builder((0, width - 1), (0, height - 1))
+            .screen_size(width, height)
+            .build()
+    }
+
+    /// Start configuring a virtual mouse with the given ranges of the absolute coordinates
+    pub fn builder(rng_x: (i32, i32), rng_y: (i32, i32)) -> UInputMouseManagerBuilder {
+        UInputMouseManagerBuilder {
+            rng_x,
+            rng_y,
+            name: "mouce-library-fake-mouse".to_string(),
+            bus_type: BUS_USB,
+            // Random vendor and product
+            vendor_id: 0x2222,
+            product_id: 0x3333,
+            version: 0,
+            // All the buttons that the library models, so that the extra buttons of
+            // the gaming mice can also be simulated
+            buttons: vec![
+                MouseButton::Left,
+                MouseButton::Right,
+                MouseButton::Middle,
+                MouseButton::Side,
+                MouseButton::Extra,
+                MouseButton::Forward,
+                MouseButton::Back,
+                MouseButton::Task,
+            ],
+            wheel: true,
+            hwheel: true,
+            settle: Settle::Delay(Duration::from_millis(300)),
+            ignore_own_events: false,
+            screen_size: None,
+            calibration: Calibration::default(),
+            unaccelerated: false,
+            codes: Vec::new(),
+        }
+    }
+
+    /// Attach to the virtual mouse with the given name that another manager has created,
+    /// e.g. a long-running helper, instead of creating a device of its own
+    ///
+    /// The short-lived processes do not wait for the device to be picked up by the system
+    /// this way. The events are written to the event file of the device, so it needs to be
+    /// writable. The ranges and the buttons are read from the device, and the device is left
+    /// alive once the manager is dropped. It is removed once the helper drops its manager
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::common::MouseButton;
+    /// use mouce::nix::UInputMouseManager;
+    /// use mouce::MouseController;
+    ///
+    /// // In the helper, which keeps running
+    /// let helper = UInputMouseManager::builder((0, 1919), (0, 1079))
+    ///     .name("mouce-shared-mouse")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// // In a short-lived process
+    /// let mut manager = UInputMouseManager::attach("mouce-shared-mouse").unwrap();
+    /// manager.click_button(&MouseButton::Left).unwrap();
+    /// ```
+    pub fn attach(name: &str) -> Result<Self, Error> {
+        let mut permission_denied = false;
+        for path in glob(super::EVENT_FILES)
+            .expect("Failed to read glob pattern")
+            .flatten()
+        {
+            let file = match File::options().read(true).write(true).open(&path) {
+                Ok(file) => file,
+                Err(err) => {
+                    permission_denied |= err.kind() == io::ErrorKind::PermissionDenied;
+                    continue;
+                }
+            };
+            if super::device_name(&file).as_deref() != Some(name) {
+                continue;
+            }
+
+            let ranges = super::abs_range(&file, ABS_X).zip(super::abs_range(&file, ABS_Y));
+            let ranges = ranges.ok_or_else(|| {
+                Error::CustomError(format!("the device {} has no absolute axes", name))
+            })?;
+            let buttons = super::device_buttons(&file)?;
+            trace::debug!(
+                "attached to the virtual mouse {} at {}",
+                name,
+                path.display()
+            );
+            let mut manager = UInputMouseManager::from_file(file, ranges, None, buttons);
+            manager.attached = true;
+            return Ok(manager);
+        }
+
+        if permission_denied {
+            return Err(Error::PermissionDenied);
+        }
+        Err(Error::CustomError(format!(
+            "the device {} is not found",
+            name
+        )))
+    }
+
+    /// Construct a manager that writes the events of the given device
+    /// with the default options, the screen size is detected if it is not given
+    fn from_file(
+        uinput_file: File,
+        (rng_x, rng_y): ((i32, i32), (i32, i32)),
+        screen_size: Option<(i32, i32)>,
+        buttons: Vec<MouseButton>,
+    ) -> Self {
+        let callbacks: super::Callbacks = Arc::default();
+        UInputMouseManager {
+            uinput_file,
+            callback_counter: 0,
+            listener: None,
+            raw_callbacks: Arc::new(CallbackList::new(callbacks.panics())),
+            raw_listener: None,
+            restart_callbacks: Arc::new(CallbackList::new(callbacks.panics())),
+            callbacks,
+            queue: QueueOptions::default(),
+            attached: false,
+            grabbing: false,
+            grab_ids: Vec::new(),
+            mapping: Mapping {
+                rng_x,
+                rng_y,
+                screen: screen_size
+                    .or_else(super::screen::layout_size)
+                    .filter(|(width, height)| *width > 1 && *height > 1),
+                space: CoordinateSpace::Physical,
+                scale: 1.,
             },
-            r#type: r#type as c_ushort,
-            code: code as c_ushort,
-            value,
-        };
+            // The position is read from X11 once it is needed, e.g. from XWayland
+            position: None,
+            scroll_remainder: (0, 0),
+            calibration: Calibration::default(),
+            motion: Motion::default(),
+            unaccelerated: false,
+            buttons,
+            own_device: None,
+            modifier_keys: ModifierKeys::default(),
+        }
+    }
+
+    /// Write the given event to the uinput file
+    fn emit(&mut self, r#type: c_int, code: c_int, value: c_int) -> Result<(), Error> {
+        let mut event = input_event(r#type, code, value);
         let fd = self.uinput_file.as_raw_fd();
 
         unsafe {
             let count = size_of::<InputEvent>();
             let written_bytes = write(fd, &mut event, count);
-            if written_bytes == -1 {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!("failed while trying to write to a file"),
-                ));
-            } else if written_bytes != count as c_long {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!("failed while trying to write to a file"),
-                ));
+            if written_bytes != count as c_long {
+                return Err(Error::WriteFailed);
             }
         }
 
         Ok(())
     }
 
+    /// Write an event with the given type, code and value to the device as it is,
+    /// for the event codes that the high level actions do not model
+    ///
+    /// The event is only reported once a `EV_SYN`/`SYN_REPORT` event completes its
+    /// frame, and the codes that are not enabled on the device are dropped by the
+    /// kernel, see `UInputMouseManagerBuilder::enable_code`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::nix::UInputMouseManager;
+    ///
+    /// const EV_SYN: u16 = 0x00;
+    /// const EV_REL: u16 = 0x02;
+    /// const REL_DIAL: u16 = 0x07;
+    ///
+    /// let mut manager = UInputMouseManager::builder((0, 1920), (0, 1080))
+    ///     .enable_code(EV_REL, REL_DIAL)
+    ///     .build()
+    ///     .unwrap();
+    /// manager.emit_raw(EV_REL, REL_DIAL, 1).unwrap();
+    /// manager.emit_raw(EV_SYN, 0, 0).unwrap();
+    /// ```
+    pub fn emit_raw(&mut self, r#type: u16, code: u16, value: i32) -> Result<(), Error> {
+        self.emit(r#type as c_int, code as c_int, value)
+    }
+
+    /// Attach a callback that receives the events of the mice as the kernel reports
+    /// them, including the sync events and the codes that `MouseEvent` does not model
+    ///
+    /// The raw callbacks run on a listener of their own that never grabs the devices,
+    /// they are removed with `unhook` and stopped with `stop_listening` like the others
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::nix::UInputMouseManager;
+    ///
+    /// const EV_KEY: u16 = 0x01;
+    /// const BTN_TOUCH: u16 = 0x14a;
+    ///
+    /// let mut manager = UInputMouseManager::new((0, 1920), (0, 1080)).unwrap();
+    /// manager
+    ///     .hook_raw(Box::new(|event| {
+    ///         if event.r#type == EV_KEY && event.code == BTN_TOUCH {
+    ///             println!("Touched {} on {}", event.value, event.device_id);
+    ///         }
+    ///     }))
+    ///     .unwrap();
+    /// ```
+    pub fn hook_raw(
+        &mut self,
+        callback: Box<dyn Fn(&RawInputEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        if self.raw_listener.is_none() {
+            self.raw_listener = Some(super::start_raw_listener(
+                &self.raw_callbacks,
+                &self.restart_callbacks,
+                self.queue,
+            )?);
+        }
+        let callback: super::RawCallback = match &self.own_device {
+            Some(own_device) => {
+                let own_device = own_device.clone();
+                Box::new(move |event| {
+                    if !own_device.emitted(&event.device_id) {
+                        callback(event);
+                    }
+                })
+            }
+            None => callback,
+        };
+
+        let id = self.callback_counter;
+        self.raw_callbacks.push(id, callback);
+        self.callback_counter += 1;
+        Ok(id)
+    }
+
+    /// Start a transaction that queues the mouse actions and emits them all at once
+    ///
+    /// Every action emits its events and waits for uinput to process them, which is
+    /// slow for the long sequences e.g. drag paths. The actions of a transaction are
+    /// written with a single system call and waited for only once on `commit`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::common::MouseButton;
+    /// use mouce::nix::NixMouseManager;
+    ///
+    /// let mut manager = NixMouseManager::new_uinput((0, 1920), (0, 1080)).unwrap();
+    /// let mut batch = manager.batch();
+    /// batch.press_button(&MouseButton::Left).unwrap();
+    /// for _ in 0..100 {
+    ///     batch.move_relative(2, 1).sync();
+    /// }
+    /// batch.release_button(&MouseButton::Left).unwrap();
+    /// assert_eq!(batch.commit(), Ok(()));
+    /// ```
+    pub fn batch(&mut self) -> Transaction<'_> {
+        // The unaccelerated moves are absolute, so they start from the actual position
+        let position = if self.unaccelerated {
+            self.current_position()
+        } else {
+            self.position
+        };
+        Transaction {
+            position,
+            manager: self,
+            events: Vec::new(),
+        }
+    }
+
     /// Syncronize the device
-    fn syncronize(&mut self) -> Result<()> {
+    fn syncronize(&mut self) -> Result<(), Error> {
         self.emit(EV_SYN, SYN_REPORT, 0)?;
         // Give uinput some time to update the mouse location,
         // otherwise it fails to move the mouse on release mode
@@ -170,53 +821,337 @@ impl UInputMouseManager {
     }
 
     /// Move the mouse relative to the current position
-    fn move_relative(&mut self, x: i32, y: i32) -> Result<()> {
-        // uinput does not move the mouse in pixels but uses `units`. I couldn't
-        // find information regarding to this uinput `unit`, but according to
-        // my findings 1 unit corresponds to exactly 2 pixels.
-        //
-        // To achieve the expected behavior; divide the parameters by 2
-        //
-        // This seems like there is a bug in this crate, but the
-        // behavior is the same on other projects that make use of
-        // uinput. e.g. `ydotool`. When you try to move your mouse,
-        // it will move 2x further pixels
-        self.emit(EV_REL, REL_X as c_int, (x as f32 / 2.).ceil() as c_int)?;
-        self.emit(EV_REL, REL_Y as c_int, (y as f32 / 2.).ceil() as c_int)?;
+    fn move_relative(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        // uinput does not move the mouse in pixels but in the units of the device,
+        // which the acceleration of the compositor or the X server scales
+        let (x, y) = self.calibration.units(x, y);
+        self.move_units(x, y)
+    }
+
+    /// Move the mouse to the given physical pixels with the absolute axes
+    fn move_to_pixels(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        let (abs_x, abs_y) = self.mapping.axis_units(x, y);
+        self.emit(EV_ABS, ABS_X as c_int, abs_x)?;
+        self.emit(EV_ABS, ABS_Y as c_int, abs_y)?;
+        self.syncronize()?;
+        self.position = Some((x, y));
+        Ok(())
+    }
+
+    /// Read the position of the pointer, which the other mice may have moved since the
+    /// last move. It is read from X11 if there is a server running, or from the absolute
+    /// axes of an attached device that the other managers move as well. Otherwise the
+    /// tracked position is the best guess
+    fn current_position(&self) -> Option<(i32, i32)> {
+        super::x11::query_pointer_position()
+            .or_else(|| {
+                if !self.attached {
+                    return None;
+                }
+                let x = super::abs_value(&self.uinput_file, ABS_X)?;
+                let y = super::abs_value(&self.uinput_file, ABS_Y)?;
+                Some(self.mapping.axis_pixels(x, y))
+            })
+            .or(self.position)
+    }
+
+    /// Move the mouse by the given units of the relative axes
+    fn move_units(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        self.emit(EV_REL, REL_X as c_int, x)?;
+        self.emit(EV_REL, REL_Y as c_int, y)?;
         self.syncronize()
     }
 
-    fn map_btn(button: &MouseButton) -> Result<c_int> {
-        match button {
-            MouseButton::Left => Ok(BTN_LEFT),
-            MouseButton::Right => Ok(BTN_RIGHT),
-            MouseButton::Middle => Ok(BTN_MIDDLE),
-            MouseButton::Side => Err(Error::new(
-                ErrorKind::InvalidInput,
-                "side button is not supported on uinput",
-            )),
-            MouseButton::Extra => Err(Error::new(
-                ErrorKind::InvalidInput,
-                "extra button is not supported on uinput",
-            )),
-            MouseButton::Forward => Err(Error::new(
-                ErrorKind::InvalidInput,
-                "forward button is not supported on uinput",
-            )),
-            MouseButton::Back => Err(Error::new(
-                ErrorKind::InvalidInput,
-                "back button is not supported on uinput",
-            )),
-            MouseButton::Task => Err(Error::new(
-                ErrorKind::InvalidInput,
-                "task button is not supported on uinput",
-            )),
+    /// The pixels that a unit of the relative axes moves the cursor
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+
+    /// Set the pixels that a unit of the relative axes moves the cursor, e.g.
+    /// a calibration that is measured by `calibrate` and loaded from a file
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    /// Set the queue between the threads that read the mice and the callbacks,
+    /// it applies to the listener that is started afterwards if it reads the
+    /// event files, see `supports(Capability::ListenerRestarts)`
+    pub fn set_event_queue(&mut self, queue: QueueOptions) {
+        self.queue = queue;
+    }
+
+    /// Measure how many pixels a unit of the relative axes moves the cursor, and use
+    /// the result for `move_relative` from now on
+    ///
+    /// The device is moved in small steps back and forth along each axis while
+    /// the cursor position is read through X11, so it needs an X server
+    /// (or XWayland while the cursor is over its windows) and a cursor that is
+    /// not near the edges of the screen. The mouse must not be moved meanwhile
+    pub fn calibrate(&mut self) -> Result<Calibration, Error> {
+        let x = self.measure_axis((CALIBRATION_STEP, 0))?;
+        let y = self.measure_axis((0, CALIBRATION_STEP))?;
+        let calibration = Calibration::from_delta(
+            (
+                CALIBRATION_STEP * CALIBRATION_STEPS,
+                CALIBRATION_STEP * CALIBRATION_STEPS,
+            ),
+            (x.0, y.1),
+        )?;
+        self.calibration = calibration;
+        Ok(calibration)
+    }
+
+    /// Move the device by the given steps and back, and return the cursor
+    /// delta of the forward moves
+    fn measure_axis(&mut self, step: (i32, i32)) -> Result<(i32, i32), Error> {
+        let position = || {
+            thread::sleep(CALIBRATION_SETTLE);
+            super::x11::query_pointer_position().ok_or_else(|| {
+                Error::CustomError("the cursor position can not be read".to_string())
+            })
+        };
+
+        let start = position()?;
+        for _ in 0..CALIBRATION_STEPS {
+            self.move_units(step.0, step.1)?;
+            thread::sleep(CALIBRATION_INTERVAL);
         }
+        let end = position()?;
+        for _ in 0..CALIBRATION_STEPS {
+            self.move_units(-step.0, -step.1)?;
+            thread::sleep(CALIBRATION_INTERVAL);
+        }
+        Ok((end.0 - start.0, end.1 - start.1))
+    }
+
+    /// Map the given button to its code, only the buttons of the device can be used
+    fn map_btn(&self, button: &MouseButton) -> Result<c_int, Error> {
+        if !self.buttons.contains(button) {
+            return Err(Error::Unsupported(Backend::UInput));
+        }
+        Ok(button_code(button))
+    }
+
+    /// Start the listener if it is not running yet and add the given callback
+    ///
+    /// A callback that needs to `grab` the devices restarts
+    /// the running listener in the grabbing mode
+    fn add_callback(
+        &mut self,
+        mask: EventMask,
+        grab: bool,
+        callback: super::Callback,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(DEFAULT_PRIORITY, mask, grab, Handler::Action(callback))
+    }
+
+    /// Start the listener if it is not running yet and add the given
+    /// handler in the order of the given priority, like `add_callback`
+    fn add_handler(
+        &mut self,
+        priority: i32,
+        mask: EventMask,
+        grab: bool,
+        handler: Handler<MouseEventExt>,
+    ) -> Result<CallbackId, Error> {
+        if grab && !self.grabbing {
+            self.listener = None;
+            self.grabbing = true;
+        }
+        self.start_listener()?;
+
+        let handler = match (&self.own_device, handler) {
+            (Some(own_device), Handler::Action(callback)) => {
+                let own_device = own_device.clone();
+                Handler::Action(Box::new(move |event: &MouseEventExt| {
+                    if own_device.emitted(&event.device_id) {
+                        EventAction::PassThrough
+                    } else {
+                        callback(event)
+                    }
+                }))
+            }
+            (Some(own_device), Handler::Propagate(callback)) => {
+                let own_device = own_device.clone();
+                Handler::Propagate(Box::new(move |event: &MouseEventExt| {
+                    if own_device.emitted(&event.device_id) {
+                        Propagation::Continue
+                    } else {
+                        callback(event)
+                    }
+                }))
+            }
+            (None, handler) => handler,
+        };
+
+        let id = self.callback_counter;
+        self.callbacks.insert(id, priority, mask, handler);
+        if grab {
+            self.grab_ids.push(id);
+        }
+        self.callback_counter += 1;
+        Ok(id)
+    }
+
+    /// Start the listener if it is not running yet
+    fn start_listener(&mut self) -> Result<(), Error> {
+        if self.listener.is_none() {
+            self.listener = Some(super::start_nix_listener(
+                &self.callbacks,
+                &self.restart_callbacks,
+                self.grabbing,
+                self.mapping.pixel_ranges(),
+                self.queue,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Restart the listener without grabbing the devices once the callbacks that grab
+    /// them are all unhooked, so that the events reach the system again
+    fn release_grab(&mut self) -> Result<(), Error> {
+        let callbacks = &self.callbacks;
+        self.grab_ids.retain(|id| callbacks.contains(*id));
+        if !self.grabbing || !self.grab_ids.is_empty() {
+            return Ok(());
+        }
+        self.grabbing = false;
+        self.listener = None;
+        if self.callbacks.is_empty() {
+            return Ok(());
+        }
+        self.start_listener()
+    }
+}
+
+/// The queued mouse actions of a uinput manager, see `UInputMouseManager::batch`
+///
+/// The queued events are reported as a single frame unless they are separated by
+/// `sync`, all the events of a frame are considered simultaneous by the system.
+/// Dropping the transaction without committing discards the queued events
+pub struct Transaction<'a> {
+    manager: &'a mut UInputMouseManager,
+    events: Vec<InputEvent>,
+    /// The tracked position of the manager after the queued moves
+    position: Option<(i32, i32)>,
+}
+
+impl Transaction<'_> {
+    fn push(&mut self, r#type: c_int, code: c_int, value: c_int) -> &mut Self {
+        self.events.push(input_event(r#type, code, value));
+        self
+    }
+
+    /// End the current frame, the following events are reported separately
+    pub fn sync(&mut self) -> &mut Self {
+        // Avoid the empty frames
+        match self.events.last() {
+            Some(last) if last.r#type as c_int != EV_SYN => self.push(EV_SYN, SYN_REPORT, 0),
+            _ => self,
+        }
+    }
+
+    /// Queue moving the mouse to the given position
+    pub fn move_to(&mut self, x: usize, y: usize) -> &mut Self {
+        let mapping = self.manager.mapping;
+        let (x, y) = mapping.pixels(x as i32, y as i32);
+        self.position = Some((x, y));
+        let (x, y) = mapping.axis_units(x, y);
+        self.push(EV_ABS, ABS_X as c_int, x)
+            .push(EV_ABS, ABS_Y as c_int, y)
+    }
+
+    /// Queue moving the mouse relative to the current position
+    pub fn move_relative(&mut self, x_offset: i32, y_offset: i32) -> &mut Self {
+        let (x_offset, y_offset) = self.manager.motion.apply(x_offset, y_offset);
+        let mapping = self.manager.mapping;
+        let (x_offset, y_offset) =
+            mapping
+                .space
+                .space_to_physical(x_offset, y_offset, mapping.scale);
+        self.position = self
+            .position
+            .map(|position| mapping.offset(position, x_offset, y_offset));
+        match self.position {
+            Some((x, y)) if self.manager.unaccelerated => {
+                let (x, y) = mapping.axis_units(x, y);
+                self.push(EV_ABS, ABS_X as c_int, x)
+                    .push(EV_ABS, ABS_Y as c_int, y)
+            }
+            _ => {
+                let (x, y) = self.manager.calibration.units(x_offset, y_offset);
+                self.push(EV_REL, REL_X as c_int, x)
+                    .push(EV_REL, REL_Y as c_int, y)
+            }
+        }
+    }
+
+    /// Queue pressing the given mouse button
+    pub fn press_button(&mut self, button: &MouseButton) -> Result<&mut Self, Error> {
+        let code = self.manager.map_btn(button)?;
+        Ok(self.push(EV_KEY, code, 1))
+    }
+
+    /// Queue releasing the given mouse button
+    pub fn release_button(&mut self, button: &MouseButton) -> Result<&mut Self, Error> {
+        let code = self.manager.map_btn(button)?;
+        Ok(self.push(EV_KEY, code, 0))
+    }
+
+    /// Queue clicking the given mouse button, the press and
+    /// the release are reported in separate frames
+    pub fn click_button(&mut self, button: &MouseButton) -> Result<&mut Self, Error> {
+        self.press_button(button)?.sync();
+        self.release_button(button)
+    }
+
+    /// Queue scrolling the mouse wheel by the given number of detents
+    pub fn scroll(&mut self, dx: i32, dy: i32) -> &mut Self {
+        let (hi_res_x, hi_res_y) = hi_res_detents(dx, dy);
+        for (code, value) in [
+            (REL_WHEEL_HI_RES, hi_res_y),
+            (REL_HWHEEL_HI_RES, hi_res_x),
+            (REL_WHEEL, dy),
+            (REL_HWHEEL, dx),
+        ] {
+            if value != 0 {
+                self.push(EV_REL, code as c_int, value);
+            }
+        }
+        self
+    }
+
+    /// Emit all the queued events and synchronize the device once
+    pub fn commit(mut self) -> Result<(), Error> {
+        self.sync();
+        let fd = self.manager.uinput_file.as_raw_fd();
+        for chunk in self.events.chunks(IOV_MAX) {
+            let iov: Vec<IoVec> = chunk
+                .iter()
+                .map(|event| IoVec {
+                    iov_base: event as *const InputEvent as *mut c_void,
+                    iov_len: size_of::<InputEvent>(),
+                })
+                .collect();
+            let count = size_of_val(chunk);
+            if unsafe { writev(fd, iov.as_ptr(), iov.len() as c_int) } != count as c_long {
+                return Err(Error::WriteFailed);
+            }
+        }
+
+        // Give uinput some time to process the events, like `syncronize`
+        thread::sleep(Duration::from_millis(1));
+        self.manager.position = self.position;
+        Ok(())
     }
 }
 
 impl Drop for UInputMouseManager {
     fn drop(&mut self) {
+        // The device belongs to the manager that created it
+        if self.attached {
+            return;
+        }
         let fd = self.uinput_file.as_raw_fd();
         unsafe {
             // Destroy the device, the file is closed automatically by the File module
@@ -225,8 +1160,257 @@ impl Drop for UInputMouseManager {
     }
 }
 
-impl MouseActions for UInputMouseManager {
-    fn move_to(&mut self, x: usize, y: usize) -> Result<()> {
+/// Simulates the keyboard through a virtual uinput keyboard
+pub struct UInputKeyboardManager {
+    uinput_file: File,
+}
+
+impl UInputKeyboardManager {
+    pub fn new() -> Result<Self, Error> {
+        let keys: Vec<c_int> = KEY_CODES.iter().map(|(_, code)| *code).collect();
+        let manager = UInputKeyboardManager {
+            uinput_file: create_device("mouce-library-fake-keyboard", 0x3335, &keys, &[])?,
+        };
+        // Give the userspace time to pick up the device, like the virtual mouse
+        thread::sleep(Duration::from_millis(300));
+        Ok(manager)
+    }
+
+    /// Emit the given key event and synchronize the device
+    fn key_event(&mut self, key: &Key, value: c_int) -> Result<(), Error> {
+        let code = KEY_CODES
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, code)| *code)
+            .ok_or(Error::Unsupported(Backend::UInput))?;
+        for event in [
+            input_event(EV_KEY, code, value),
+            input_event(EV_SYN, SYN_REPORT, 0),
+        ] {
+            write_event(&self.uinput_file, &event).map_err(|_| Error::WriteFailed)?;
+        }
+        // Give uinput some time to process the event, like `syncronize`
+        thread::sleep(Duration::from_millis(1));
+        Ok(())
+    }
+}
+
+impl KeyboardActions for UInputKeyboardManager {
+    fn press_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, 1)
+    }
+
+    fn release_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, 0)
+    }
+}
+
+/// Simulates a multitouch screen through a virtual uinput touchscreen
+pub struct UInputTouchManager {
+    uinput_file: File,
+    contacts: Contacts,
+}
+
+impl UInputTouchManager {
+    /// Create a virtual touchscreen with the given ranges of the coordinates
+    pub fn new(rng_x: (i32, i32), rng_y: (i32, i32)) -> Result<Self, Error> {
+        let file = File::options()
+            .write(true)
+            .custom_flags(O_NONBLOCK)
+            .open("/dev/uinput")?;
+        let fd = file.as_raw_fd();
+
+        let mut usetup = UInputSetup {
+            id: InputId {
+                bustype: BUS_USB,
+                // Random vendor and product
+                vendor: 0x2222,
+                product: 0x3336,
+                version: 0,
+            },
+            name: [0; UINPUT_MAX_NAME_SIZE],
+            ff_effects_max: 0,
+        };
+        for (i, byte) in "mouce-library-fake-touchscreen".bytes().enumerate() {
+            usetup.name[i] = byte as c_char;
+        }
+
+        unsafe {
+            // The contacts are at the reported positions of the screen, not relative to a cursor
+            ioctl(fd, UI_SET_PROPBIT, INPUT_PROP_DIRECT);
+
+            ioctl(fd, UI_SET_EVBIT, EV_KEY);
+            ioctl(fd, UI_SET_KEYBIT, BTN_TOUCH);
+
+            // The single touch axes are for the readers that do not know the slots
+            ioctl(fd, UI_SET_EVBIT, EV_ABS);
+            for (code, (minimum, maximum)) in [
+                (ABS_X, rng_x),
+                (ABS_Y, rng_y),
+                (ABS_MT_SLOT, (0, TOUCH_SLOTS as i32 - 1)),
+                (ABS_MT_POSITION_X, rng_x),
+                (ABS_MT_POSITION_Y, rng_y),
+                (ABS_MT_TRACKING_ID, (0, MAX_TRACKING_ID)),
+            ] {
+                ioctl(fd, UI_SET_ABSBIT, code);
+                ioctl(
+                    fd,
+                    UI_ABS_SETUP,
+                    &UinputAbsSetup {
+                        code: code as _,
+                        absinfo: InputAbsinfo {
+                            value: 0,
+                            minimum,
+                            maximum,
+                            fuzz: 0,
+                            flat: 0,
+                            resolution: 0,
+                        },
+                    },
+                );
+            }
+
+            if ioctl(fd, UI_DEV_SETUP, &usetup) < 0 || ioctl(fd, UI_DEV_CREATE) < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        // Give the userspace time to pick up the device, like the virtual mouse
+        thread::sleep(Duration::from_millis(300));
+
+        Ok(UInputTouchManager {
+            uinput_file: file,
+            contacts: Contacts::default(),
+        })
+    }
+
+    /// Create a virtual touchscreen whose coordinates are the pixels of the detected screen,
+    /// e.g. to tap on Android where the apps respond to the touches rather than a pointer
+    pub fn new_auto() -> Result<Self, Error> {
+        let (width, height) = super::screen::screen_size()
+            .filter(|(width, height)| *width > 1 && *height > 1)
+            .ok_or_else(|| Error::CustomError("the screen size can not be detected".to_string()))?;
+        Self::new((0, width - 1), (0, height - 1))
+    }
+
+    /// Emit the given events of a contact and synchronize the device
+    fn emit_frame(&mut self, mut events: Vec<InputEvent>) -> Result<(), Error> {
+        events.push(input_event(EV_SYN, SYN_REPORT, 0));
+        for event in events.iter() {
+            write_event(&self.uinput_file, event).map_err(|_| Error::WriteFailed)?;
+        }
+        // Give uinput some time to process the events, like `syncronize`
+        thread::sleep(Duration::from_millis(1));
+        Ok(())
+    }
+}
+
+impl TouchActions for UInputTouchManager {
+    fn touch_down(&mut self, slot: usize, x: i32, y: i32) -> Result<(), Error> {
+        let events = self.contacts.down(slot, x, y)?;
+        self.emit_frame(events)
+    }
+
+    fn touch_move(&mut self, slot: usize, x: i32, y: i32) -> Result<(), Error> {
+        let events = self.contacts.move_to(slot, x, y)?;
+        self.emit_frame(events)
+    }
+
+    fn touch_up(&mut self, slot: usize) -> Result<(), Error> {
+        let events = self.contacts.up(slot)?;
+        self.emit_frame(events)
+    }
+}
+
+impl Drop for UInputTouchManager {
+    fn drop(&mut self) {
+        unsafe {
+            ioctl(self.uinput_file.as_raw_fd(), UI_DEV_DESTROY as c_ulong);
+        }
+    }
+}
+
+/// The tracking ids of the contacts on the slots of the touchscreen,
+/// which build the multitouch (type B) events of the contacts
+#[derive(Default)]
+struct Contacts {
+    tracking_ids: [Option<i32>; TOUCH_SLOTS],
+    next_tracking_id: i32,
+}
+
+impl Contacts {
+    fn down(&mut self, slot: usize, x: i32, y: i32) -> Result<Vec<InputEvent>, Error> {
+        match self.tracking_ids.get(slot) {
+            None => return Err(slot_error(slot, "does not exist")),
+            Some(Some(_)) => return Err(slot_error(slot, "is already down")),
+            Some(None) => {}
+        }
+        let first = self.tracking_ids.iter().all(Option::is_none);
+        let tracking_id = self.next_tracking_id;
+        self.next_tracking_id = (tracking_id + 1) % (MAX_TRACKING_ID + 1);
+        self.tracking_ids[slot] = Some(tracking_id);
+
+        let mut events = vec![
+            input_event(EV_ABS, ABS_MT_SLOT as c_int, slot as c_int),
+            input_event(EV_ABS, ABS_MT_TRACKING_ID as c_int, tracking_id),
+        ];
+        events.extend(self.positions(slot, x, y));
+        if first {
+            events.push(input_event(EV_KEY, BTN_TOUCH, 1));
+        }
+        Ok(events)
+    }
+
+    fn move_to(&mut self, slot: usize, x: i32, y: i32) -> Result<Vec<InputEvent>, Error> {
+        self.ensure_down(slot)?;
+        let mut events = vec![input_event(EV_ABS, ABS_MT_SLOT as c_int, slot as c_int)];
+        events.extend(self.positions(slot, x, y));
+        Ok(events)
+    }
+
+    fn up(&mut self, slot: usize) -> Result<Vec<InputEvent>, Error> {
+        self.ensure_down(slot)?;
+        self.tracking_ids[slot] = None;
+
+        let mut events = vec![
+            input_event(EV_ABS, ABS_MT_SLOT as c_int, slot as c_int),
+            // A tracking id of -1 removes the contact from the slot
+            input_event(EV_ABS, ABS_MT_TRACKING_ID as c_int, -1),
+        ];
+        if self.tracking_ids.iter().all(Option::is_none) {
+            events.push(input_event(EV_KEY, BTN_TOUCH, 0));
+        }
+        Ok(events)
+    }
+
+    /// The position events of the contact on the given slot, the single touch
+    /// axes follow the contact on the lowest slot only
+    fn positions(&self, slot: usize, x: i32, y: i32) -> Vec<InputEvent> {
+        let mut events = vec![
+            input_event(EV_ABS, ABS_MT_POSITION_X as c_int, x),
+            input_event(EV_ABS, ABS_MT_POSITION_Y as c_int, y),
+        ];
+        if self.tracking_ids.iter().position(Option::is_some) == Some(slot) {
+            events.push(input_event(EV_ABS, ABS_X as c_int, x));
+            events.push(input_event(EV_ABS, ABS_Y as c_int, y));
+        }
+        events
+    }
+
+    fn ensure_down(&self, slot: usize) -> Result<(), Error> {
+        match self.tracking_ids.get(slot) {
+            None => Err(slot_error(slot, "does not exist")),
+            Some(None) => Err(slot_error(slot, "is not down")),
+            Some(Some(_)) => Ok(()),
+        }
+    }
+}
+
+fn slot_error(slot: usize, reason: &str) -> Error {
+    Error::CustomError(format!("the touch slot {} {}", slot, reason))
+}
+
+impl MouseController for UInputMouseManager {
+    fn move_to(&mut self, x: usize, y: usize) -> Result<(), Error> {
         // // For some reason, absolute mouse move events are not working on uinput
         // // (as I understand those events are intended for touch events)
         // //
@@ -235,75 +1419,566 @@ impl MouseActions for UInputMouseManager {
         //self.move_relative(i32::MIN, i32::MIN)?;
         //self.move_relative(x as i32, y as i32)
 
-        self.emit(EV_ABS, ABS_X as c_int, x as c_int)?;
-        self.emit(EV_ABS, ABS_Y as c_int, y as c_int)?;
-        self.syncronize()
+        let (x, y) = self.mapping.pixels(x as i32, y as i32);
+        self.move_to_pixels(x, y)
+    }
+
+    fn move_relative(&mut self, x_offset: i32, y_offset: i32) -> Result<(), Error> {
+        let (x_offset, y_offset) = self.motion.apply(x_offset, y_offset);
+        let mapping = self.mapping;
+        let (x_offset, y_offset) =
+            mapping
+                .space
+                .space_to_physical(x_offset, y_offset, mapping.scale);
+        // The unaccelerated moves are absolute, so they start from the actual position.
+        // The position is already in pixels, so only clamp it
+        let position = if self.unaccelerated {
+            self.current_position()
+        } else {
+            self.position
+        };
+        let position = position.map(|position| mapping.offset(position, x_offset, y_offset));
+        match position {
+            Some((x, y)) if self.unaccelerated => self.move_to_pixels(x, y),
+            _ => {
+                self.move_relative(x_offset, y_offset)?;
+                self.position = position;
+                Ok(())
+            }
+        }
+    }
+
+    fn get_position(&self) -> Result<(i32, i32), Error> {
+        // uinput does not let us get the current position of the mouse,
+        // it is not known before the first `move_to` without X11
+        let (x, y) = self.current_position().ok_or(Error::NotImplemented)?;
+        let mapping = self.mapping;
+        Ok(mapping.space.physical_to_space(x, y, mapping.scale))
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::UInput
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            // The position is only known after the first `move_to` without X11
+            Capability::GetPosition => self.current_position().is_some(),
+            Capability::ButtonState
+            | Capability::CoordinateSpace
+            | Capability::MotionProfile
+            | Capability::HiResScroll
+            | Capability::GrabEvents
+            | Capability::InjectEvent
+            | Capability::PauseHook
+            | Capability::CallbackPanics
+            | Capability::ListenerStats => true,
+            Capability::ListenerRestarts => super::reads_event_files(self.grabbing),
+            _ => false,
+        }
     }
 
-    fn move_relative(&mut self, x_offset: i32, y_offset: i32) -> Result<()> {
-        self.move_relative(x_offset, y_offset)
+    fn get_button_state(&self) -> Result<ButtonState, Error> {
+        super::button_state()
     }
 
-    fn get_position(&self) -> Result<(i32, i32)> {
-        // uinput does not let us get the current position of the mouse
-        // Err(Error::NotImplemented)
-        unimplemented!()
+    fn set_coordinate_space(&mut self, space: CoordinateSpace) -> Result<(), Error> {
+        self.mapping.scale = super::screen::scale_factor();
+        self.mapping.space = space;
+        Ok(())
+    }
+
+    fn set_motion_profile(&mut self, profile: MotionProfile) -> Result<(), Error> {
+        self.motion.set_profile(profile);
+        Ok(())
     }
 
-    fn press_button(&mut self, button: &MouseButton) -> Result<()> {
-        self.emit(EV_KEY, Self::map_btn(button)?, 1)?;
+    fn press_button(&mut self, button: &MouseButton) -> Result<(), Error> {
+        self.emit(EV_KEY, self.map_btn(button)?, 1)?;
         self.syncronize()
     }
 
-    fn release_button(&mut self, button: &MouseButton) -> Result<()> {
-        self.emit(EV_KEY, Self::map_btn(button)?, 0)?;
+    fn release_button(&mut self, button: &MouseButton) -> Result<(), Error> {
+        self.emit(EV_KEY, self.map_btn(button)?, 0)?;
         self.syncronize()
     }
 
-    fn click_button(&mut self, button: &MouseButton) -> Result<()> {
-        self.press_button(&button)?;
-        self.release_button(&button)
+    fn click_button(&mut self, button: &MouseButton) -> Result<(), Error> {
+        self.press_button(button)?;
+        self.release_button(button)
     }
 
-    fn scroll_wheel(&mut self, direction: &ScrollDirection) -> Result<()> {
-        let (code, scroll_value) = match direction {
-            ScrollDirection::Up => (REL_WHEEL, 1),
-            ScrollDirection::Down => (REL_WHEEL, -1),
-            ScrollDirection::Left => (REL_HWHEEL, -1),
-            ScrollDirection::Right => (REL_HWHEEL, 1),
+    fn click_with_modifiers(
+        &mut self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        let modifier_keys = self.modifier_keys.clone();
+        modifier_keys.hold(modifiers, || self.click_button(button))
+    }
+
+    fn scroll_wheel(&mut self, direction: &ScrollDirection) -> Result<(), Error> {
+        let (dx, dy) = match direction {
+            ScrollDirection::Up => (0, 1),
+            ScrollDirection::Down => (0, -1),
+            ScrollDirection::Left => (-1, 0),
+            ScrollDirection::Right => (1, 0),
         };
-        self.emit(EV_REL, code as c_int, scroll_value)?;
+        self.scroll(dx, dy)
+    }
+
+    fn scroll(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        let (dx, dy) = hi_res_detents(dx, dy);
+        self.scroll_wheel_hi_res(dx, dy)
+    }
+
+    fn scroll_wheel_hi_res(&mut self, dx: i32, dy: i32) -> Result<(), Error> {
+        for (code, value) in wheel_events(&mut self.scroll_remainder, dx, dy) {
+            if value != 0 {
+                self.emit(EV_REL, code as c_int, value)?;
+            }
+        }
         self.syncronize()
     }
+}
 
-    fn hook(&mut self, callback: Box<dyn Fn(&MouseEvent) + Send>) -> Result<CallbackId> {
-        if !self.is_listening {
-            super::start_nix_listener(&self.callbacks)?;
-            self.is_listening = true;
+impl MouseListener for UInputMouseManager {
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_with_time(Box::new(move |event| callback(&event.event)))
+    }
+
+    fn hook_with_time(
+        &mut self,
+        callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_ext(Box::new(move |event| {
+            callback(&EventWithTime {
+                event: event.event.clone(),
+                time: event.time,
+            })
+        }))
+    }
+
+    fn hook_ext(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            EventMask::ALL,
+            false,
+            Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            }),
+        )
+    }
+
+    fn hook_filtered(
+        &mut self,
+        mask: EventMask,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            mask,
+            false,
+            Box::new(move |event| {
+                callback(&event.event);
+                EventAction::PassThrough
+            }),
+        )
+    }
+
+    fn hook_grab(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) -> EventAction + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            EventMask::ALL,
+            true,
+            Box::new(move |event| callback(&event.event)),
+        )
+    }
+
+    fn hook_with_priority(
+        &mut self,
+        priority: i32,
+        callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(
+            priority,
+            EventMask::ALL,
+            false,
+            Handler::Propagate(Box::new(move |event| callback(&event.event))),
+        )
+    }
+
+    fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+        Ok(super::inject(&self.callbacks, event))
+    }
+
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.remove(callback_id) {
+            return self.release_grab();
         }
+        if self.raw_callbacks.remove(callback_id) {
+            return Ok(());
+        }
+        if self.restart_callbacks.remove(callback_id) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
 
+    fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, true) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, false) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn on_callback_panic(
+        &mut self,
+        unhook: bool,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
+        self.callbacks.set_panic_handler(handler, unhook);
+        Ok(())
+    }
+
+    fn unhook_all(&mut self) -> Result<(), Error> {
+        self.callbacks.clear();
+        self.raw_callbacks.clear();
+        self.restart_callbacks.clear();
+        self.release_grab()
+    }
+
+    fn stop_listening(&mut self) -> Result<(), Error> {
+        // Dropping the listeners stops and joins their threads
+        self.listener = None;
+        self.raw_listener = None;
+        Ok(())
+    }
+
+    fn listener_stats(&self) -> Result<ListenerStats, Error> {
+        Ok(self
+            .listener
+            .as_ref()
+            .map(Listener::stats)
+            .unwrap_or_default())
+    }
+
+    fn on_listener_restarted(
+        &mut self,
+        callback: Box<dyn Fn() + Send>,
+    ) -> Result<CallbackId, Error> {
         let id = self.callback_counter;
-        self.callbacks.lock().unwrap().insert(id, callback);
+        self.restart_callbacks.push(id, callback);
         self.callback_counter += 1;
         Ok(id)
     }
+}
+
+/// Create a virtual mouse that re-emits the events of the grabbed devices
+pub fn create_passthrough_device() -> io::Result<File> {
+    create_device(
+        "mouce-library-passthrough-mouse",
+        0x3334,
+        &[
+            BTN_LEFT,
+            BTN_RIGHT,
+            BTN_MIDDLE,
+            BTN_SIDE,
+            BTN_EXTRA,
+            BTN_FORWARD,
+            BTN_BACK,
+            BTN_TASK,
+        ],
+        &[
+            REL_X,
+            REL_Y,
+            REL_HWHEEL,
+            REL_WHEEL,
+            REL_WHEEL_HI_RES,
+            REL_HWHEEL_HI_RES,
+        ],
+    )
+}
+
+/// Create a virtual device with the given name and product id,
+/// that reports the given keys and relative axes
+fn create_device(name: &str, product: u16, keys: &[c_int], rels: &[c_uint]) -> io::Result<File> {
+    let file = File::options()
+        .write(true)
+        .custom_flags(O_NONBLOCK)
+        .open("/dev/uinput")?;
+    let fd = file.as_raw_fd();
+
+    let mut usetup = UInputSetup {
+        id: InputId {
+            bustype: BUS_USB,
+            // Random vendor and product
+            vendor: 0x2222,
+            product,
+            version: 0,
+        },
+        name: [0; UINPUT_MAX_NAME_SIZE],
+        ff_effects_max: 0,
+    };
+    for (i, byte) in name.bytes().enumerate() {
+        usetup.name[i] = byte as c_char;
+    }
+
+    unsafe {
+        ioctl(fd, UI_SET_EVBIT, EV_KEY);
+        for key in keys {
+            ioctl(fd, UI_SET_KEYBIT, *key);
+        }
 
-    fn unhook(&mut self, callback_id: CallbackId) -> Result<()> {
-        match self.callbacks.lock().unwrap().remove(&callback_id) {
-            Some(_) => Ok(()),
-            None => Err(Error::new(
-                ErrorKind::NotFound,
-                format!("callback id {} not found", callback_id),
-            )),
+        if !rels.is_empty() {
+            ioctl(fd, UI_SET_EVBIT, EV_REL);
+            for code in rels {
+                ioctl(fd, UI_SET_RELBIT, *code);
+            }
+        }
+
+        if ioctl(fd, UI_DEV_SETUP, &usetup) < 0 || ioctl(fd, UI_DEV_CREATE) < 0 {
+            return Err(io::Error::last_os_error());
         }
     }
 
-    fn unhook_all(&mut self) -> Result<()> {
-        self.callbacks.lock().unwrap().clear();
-        Ok(())
+    // The device is destroyed once the file is closed
+    Ok(file)
+}
+
+/// Get the name of the created uinput device under /sys/devices/virtual/input e.g. input42
+fn sysname(fd: c_int) -> Result<String, Error> {
+    let mut sysname = [0u8; 64];
+    if unsafe { ioctl(fd, ui_get_sysname(sysname.len()), sysname.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let len = sysname
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(sysname.len());
+    Ok(String::from_utf8_lossy(&sysname[..len]).into_owned())
+}
+
+/// Get the path of the event file of the given uinput device e.g. /dev/input/event7
+///
+/// The kernel lists the event handler of the device as soon as it is created,
+/// the file itself is created by udev once the device is processed
+fn event_file(sysname: &str) -> Option<String> {
+    let pattern = format!("{}/{}/event*", SYS_VIRTUAL_INPUT_DIR, sysname);
+    glob(&pattern)
+        .expect("Failed to read glob pattern")
+        .flatten()
+        .find_map(|path| path.file_name().map(|name| name.to_owned()))
+        .map(|name| Path::new("/dev/input").join(name).display().to_string())
+}
+
+/// Read the ranges of the absolute axes back from the event file of the given uinput
+/// device, None if the file can not be read e.g. the user is not in the `input` group
+fn device_ranges(fd: c_int) -> Option<super::Ranges> {
+    let path = event_file(&sysname(fd).ok()?)?;
+    let file = File::open(path).ok()?;
+    super::abs_range(&file, ABS_X).zip(super::abs_range(&file, ABS_Y))
+}
+
+/// Wait until the event file of the created uinput device exists
+fn wait_for_event_file(fd: c_int, timeout: Duration) -> Result<(), Error> {
+    let sysname = sysname(fd)?;
+
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        if event_file(&sysname).is_some_and(|path| Path::new(&path).exists()) {
+            return Ok(());
+        }
+        thread::sleep(SETTLE_POLL_INTERVAL);
+    }
+
+    Err(Error::CustomError(format!(
+        "the event file of {} is not created in {:?}",
+        sysname, timeout
+    )))
+}
+
+/// Get the high resolution amounts of the given detents, the large counts
+/// saturate instead of overflowing and scrolling the other way
+fn hi_res_detents(dx: i32, dy: i32) -> (i32, i32) {
+    (
+        dx.saturating_mul(HI_RES_PER_DETENT),
+        dy.saturating_mul(HI_RES_PER_DETENT),
+    )
+}
+
+/// Get the wheel events of the given high resolution amounts. The device reports
+/// both the high resolution and the detent events like the real mice, the detents
+/// are emitted once the amounts add up to a whole one with the given remainder
+fn wheel_events(remainder: &mut (i32, i32), dx: i32, dy: i32) -> [(c_uint, i32); 4] {
+    let (rest_x, rest_y) = (
+        remainder.0.saturating_add(dx),
+        remainder.1.saturating_add(dy),
+    );
+    *remainder = (rest_x % HI_RES_PER_DETENT, rest_y % HI_RES_PER_DETENT);
+    [
+        (REL_WHEEL_HI_RES, dy),
+        (REL_HWHEEL_HI_RES, dx),
+        (REL_WHEEL, rest_y / HI_RES_PER_DETENT),
+        (REL_HWHEEL, rest_x / HI_RES_PER_DETENT),
+    ]
+}
+
+/// Get the evdev code of the given button
+pub(crate) fn button_code(button: &MouseButton) -> c_int {
+    match button {
+        MouseButton::Left => BTN_LEFT,
+        MouseButton::Right => BTN_RIGHT,
+        MouseButton::Middle => BTN_MIDDLE,
+        MouseButton::Side => BTN_SIDE,
+        MouseButton::Extra => BTN_EXTRA,
+        MouseButton::Forward => BTN_FORWARD,
+        MouseButton::Back => BTN_BACK,
+        MouseButton::Task => BTN_TASK,
     }
 }
 
+/// Get the ioctl that enables the codes of the given event type on a device
+fn code_ioctl(r#type: c_int) -> Option<c_ulong> {
+    match r#type {
+        EV_KEY => Some(UI_SET_KEYBIT),
+        EV_REL => Some(UI_SET_RELBIT),
+        EV_MSC => Some(UI_SET_MSCBIT),
+        _ => None,
+    }
+}
+
+/// Construct a raw event with the given fields, the kernel stamps the time
+fn input_event(r#type: c_int, code: c_int, value: c_int) -> InputEvent {
+    InputEvent {
+        time: TimeVal {
+            tv_sec: 0,
+            tv_usec: 0,
+        },
+        r#type: r#type as c_ushort,
+        code: code as c_ushort,
+        value,
+    }
+}
+
+/// Write the given raw event to the given uinput file
+pub fn write_event(file: &File, event: &InputEvent) -> io::Result<()> {
+    let mut event = input_event(event.r#type as c_int, event.code as c_int, event.value);
+    let count = size_of::<InputEvent>();
+    if unsafe { write(file.as_raw_fd(), &mut event, count) } != count as c_long {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Grab the given event file, so that its events are only delivered to this process
+pub fn grab_device(file: &File) -> io::Result<()> {
+    if unsafe { ioctl(file.as_raw_fd(), EVIOCGRAB, 1 as c_int) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The evdev codes of the keys that the virtual keyboard has
+const KEY_CODES: &[(Key, c_int)] = &[
+    (Key::Escape, 1),
+    (Key::Char('1'), 2),
+    (Key::Char('2'), 3),
+    (Key::Char('3'), 4),
+    (Key::Char('4'), 5),
+    (Key::Char('5'), 6),
+    (Key::Char('6'), 7),
+    (Key::Char('7'), 8),
+    (Key::Char('8'), 9),
+    (Key::Char('9'), 10),
+    (Key::Char('0'), 11),
+    (Key::Char('-'), 12),
+    (Key::Char('='), 13),
+    (Key::Backspace, 14),
+    (Key::Tab, 15),
+    (Key::Char('q'), 16),
+    (Key::Char('w'), 17),
+    (Key::Char('e'), 18),
+    (Key::Char('r'), 19),
+    (Key::Char('t'), 20),
+    (Key::Char('y'), 21),
+    (Key::Char('u'), 22),
+    (Key::Char('i'), 23),
+    (Key::Char('o'), 24),
+    (Key::Char('p'), 25),
+    (Key::Char('['), 26),
+    (Key::Char(']'), 27),
+    (Key::Enter, 28),
+    (Key::Control, 29),
+    (Key::Char('a'), 30),
+    (Key::Char('s'), 31),
+    (Key::Char('d'), 32),
+    (Key::Char('f'), 33),
+    (Key::Char('g'), 34),
+    (Key::Char('h'), 35),
+    (Key::Char('j'), 36),
+    (Key::Char('k'), 37),
+    (Key::Char('l'), 38),
+    (Key::Char(';'), 39),
+    (Key::Char('\''), 40),
+    (Key::Char('`'), 41),
+    (Key::Shift, 42),
+    (Key::Char('\\'), 43),
+    (Key::Char('z'), 44),
+    (Key::Char('x'), 45),
+    (Key::Char('c'), 46),
+    (Key::Char('v'), 47),
+    (Key::Char('b'), 48),
+    (Key::Char('n'), 49),
+    (Key::Char('m'), 50),
+    (Key::Char(','), 51),
+    (Key::Char('.'), 52),
+    (Key::Char('/'), 53),
+    (Key::Alt, 56),
+    (Key::Space, 57),
+    (Key::CapsLock, 58),
+    (Key::F(1), 59),
+    (Key::F(2), 60),
+    (Key::F(3), 61),
+    (Key::F(4), 62),
+    (Key::F(5), 63),
+    (Key::F(6), 64),
+    (Key::F(7), 65),
+    (Key::F(8), 66),
+    (Key::F(9), 67),
+    (Key::F(10), 68),
+    (Key::F(11), 87),
+    (Key::F(12), 88),
+    (Key::Home, 102),
+    (Key::Up, 103),
+    (Key::PageUp, 104),
+    (Key::Left, 105),
+    (Key::Right, 106),
+    (Key::End, 107),
+    (Key::Down, 108),
+    (Key::PageDown, 109),
+    (Key::Insert, 110),
+    (Key::Delete, 111),
+    (Key::Meta, 125),
+];
+
 pub const O_NONBLOCK: c_int = 2048;
 
 /// ioctl and uinput definitions
@@ -312,19 +1987,32 @@ const UI_SET_EVBIT: c_ulong = 1074025828;
 const UI_SET_KEYBIT: c_ulong = 1074025829;
 const UI_SET_RELBIT: c_ulong = 1074025830;
 const UI_SET_ABSBIT: c_ulong = 1074025831;
+const UI_SET_MSCBIT: c_ulong = 1074025832;
+const UI_SET_PROPBIT: c_ulong = 1074025838;
 const UI_DEV_SETUP: c_ulong = 1079792899;
 const UI_DEV_CREATE: c_ulong = 21761;
+const fn ui_get_sysname(len: usize) -> c_ulong {
+    (2 << 30) | ((len as c_ulong) << 16) | ((b'U' as c_ulong) << 8) | 44
+}
 const UI_DEV_DESTROY: c_uint = 21762;
+const EVIOCGRAB: c_ulong = 0x40044590;
+/// The maximum number of the buffers that writev accepts
+const IOV_MAX: usize = 1024;
 
 pub const EV_KEY: c_int = 0x01;
 pub const EV_REL: c_int = 0x02;
 pub const EV_ABS: c_int = 0x03;
+pub const EV_MSC: c_int = 0x04;
 pub const REL_X: c_uint = 0x00;
 pub const REL_Y: c_uint = 0x01;
 pub const ABS_X: c_uint = 0x00;
 pub const ABS_Y: c_uint = 0x01;
 pub const REL_HWHEEL: c_uint = 0x06;
 pub const REL_WHEEL: c_uint = 0x08;
+pub const REL_WHEEL_HI_RES: c_uint = 0x0b;
+pub const REL_HWHEEL_HI_RES: c_uint = 0x0c;
+/// The high resolution scroll amount of a wheel detent
+pub const HI_RES_PER_DETENT: i32 = 120;
 pub const BTN_LEFT: c_int = 0x110;
 pub const BTN_RIGHT: c_int = 0x111;
 pub const BTN_MIDDLE: c_int = 0x112;
@@ -333,8 +2021,18 @@ pub const BTN_EXTRA: c_int = 0x114;
 pub const BTN_FORWARD: c_int = 0x115;
 pub const BTN_BACK: c_int = 0x116;
 pub const BTN_TASK: c_int = 0x117;
+pub const BTN_TOUCH: c_int = 0x14a;
+pub const ABS_MT_SLOT: c_uint = 0x2f;
+pub const ABS_MT_POSITION_X: c_uint = 0x35;
+pub const ABS_MT_POSITION_Y: c_uint = 0x36;
+pub const ABS_MT_TRACKING_ID: c_uint = 0x39;
+const INPUT_PROP_POINTER: c_int = 0x00;
+const INPUT_PROP_DIRECT: c_int = 0x01;
+/// The number of the contacts that the virtual touchscreen tracks at once
+const TOUCH_SLOTS: usize = 10;
+const MAX_TRACKING_ID: i32 = 0xffff;
 const SYN_REPORT: c_int = 0x00;
-const EV_SYN: c_int = 0x00;
+pub const EV_SYN: c_int = 0x00;
 const BUS_USB: c_ushort = 0x03;
 
 /// uinput types
@@ -346,13 +2044,20 @@ struct UInputSetup {
 }
 
 #[repr(C)]
-struct InputId {
-    bustype: c_ushort,
-    vendor: c_ushort,
-    product: c_ushort,
-    version: c_ushort,
+pub struct InputId {
+    pub bustype: c_ushort,
+    pub vendor: c_ushort,
+    pub product: c_ushort,
+    pub version: c_ushort,
 }
 
+#[repr(C)]
+struct IoVec {
+    iov_base: *mut c_void,
+    iov_len: usize,
+}
+
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct InputEvent {
     pub time: TimeVal,
@@ -361,6 +2066,7 @@ pub struct InputEvent {
     pub value: c_int,
 }
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct TimeVal {
     pub tv_sec: c_ulong,
@@ -386,4 +2092,160 @@ pub struct InputAbsinfo {
 extern "C" {
     fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
     fn write(fd: c_int, buf: *mut InputEvent, count: usize) -> c_long;
+    fn writev(fd: c_int, iov: *const IoVec, iovcnt: c_int) -> c_long;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ioctl_requests() {
+        // The value of UI_GET_SYSNAME(64) in linux/uinput.h
+        assert_eq!(ui_get_sysname(64), 0x8040552c);
+    }
+
+    #[test]
+    fn map_coordinates() {
+        let mut mapping = Mapping {
+            rng_x: (0, 32767),
+            rng_y: (0, 32767),
+            screen: Some((1920, 1080)),
+            space: CoordinateSpace::Physical,
+            scale: 2.,
+        };
+        // The axes span the whole screen
+        assert_eq!(mapping.axis_units(0, 0), (0, 0));
+        assert_eq!(mapping.axis_units(1919, 1079), (32767, 32767));
+        // The values that are read back from the axes map to the same pixels
+        assert_eq!(mapping.axis_pixels(32767, 32767), (1919, 1079));
+        assert_eq!(mapping.axis_pixels(16384, 0), (959, 0));
+        assert_eq!(mapping.pixels(5000, -3), (1919, 0));
+        assert_eq!(mapping.offset((1900, 10), 100, -20), (1919, 0));
+        assert_eq!(mapping.offset((100, 100), -50, 50), (50, 150));
+
+        mapping.space = CoordinateSpace::Logical;
+        assert_eq!(mapping.pixels(480, 270), (960, 540));
+
+        // The pixels are the units of the axes if the screen is not known
+        mapping.screen = None;
+        mapping.rng_x = (0, 1920);
+        assert_eq!(mapping.axis_units(100, 50), (100, 50));
+        assert_eq!(mapping.axis_pixels(100, 50), (100, 50));
+        assert_eq!(mapping.pixels(1000, 10), (1920, 20));
+    }
+
+    #[test]
+    fn own_events() {
+        let own_device = OwnDevice {
+            sysname: "input42".to_string(),
+            devnode: Some("/dev/input/event7".to_string()),
+        };
+        // The listeners of the event files report the path of the device
+        assert!(own_device.emitted("/dev/input/event7"));
+        assert!(!own_device.emitted("/dev/input/event3"));
+
+        // The device is looked up in sysfs if its event file is not known
+        let own_device = OwnDevice {
+            sysname: "input-that-does-not-exist".to_string(),
+            devnode: None,
+        };
+        assert!(!own_device.emitted("/dev/input/event7"));
+        assert!(!own_device.emitted(""));
+    }
+
+    #[test]
+    fn scroll_large_amounts() {
+        // The events of `scroll(i32::MAX, i32::MIN)`, which still scroll right and down
+        let mut remainder = (0, 0);
+        let (dx, dy) = hi_res_detents(i32::MAX, i32::MIN);
+        assert_eq!(
+            wheel_events(&mut remainder, dx, dy),
+            [
+                (REL_WHEEL_HI_RES, i32::MIN),
+                (REL_HWHEEL_HI_RES, i32::MAX),
+                (REL_WHEEL, i32::MIN / HI_RES_PER_DETENT),
+                (REL_HWHEEL, i32::MAX / HI_RES_PER_DETENT),
+            ]
+        );
+        assert_eq!(
+            remainder,
+            (i32::MAX % HI_RES_PER_DETENT, i32::MIN % HI_RES_PER_DETENT)
+        );
+        // The remainder does not overflow the next amounts either
+        let [_, _, (_, y), (_, x)] = wheel_events(&mut remainder, dx, dy);
+        assert!(x > 0 && y < 0);
+
+        // The amounts add up to the detents across the calls
+        let mut remainder = (0, 0);
+        assert_eq!(wheel_events(&mut remainder, 0, 60)[2], (REL_WHEEL, 0));
+        assert_eq!(wheel_events(&mut remainder, 0, 60)[2], (REL_WHEEL, 1));
+        assert_eq!(remainder, (0, 0));
+    }
+
+    fn fields(events: Vec<InputEvent>) -> Vec<(c_ushort, c_ushort, c_int)> {
+        events
+            .iter()
+            .map(|event| (event.r#type, event.code, event.value))
+            .collect()
+    }
+
+    #[test]
+    fn touch_contacts() {
+        let (abs, key) = (EV_ABS as c_ushort, EV_KEY as c_ushort);
+        let mut contacts = Contacts::default();
+
+        assert_eq!(
+            fields(contacts.down(0, 10, 20).unwrap()),
+            vec![
+                (abs, ABS_MT_SLOT as c_ushort, 0),
+                (abs, ABS_MT_TRACKING_ID as c_ushort, 0),
+                (abs, ABS_MT_POSITION_X as c_ushort, 10),
+                (abs, ABS_MT_POSITION_Y as c_ushort, 20),
+                (abs, ABS_X as c_ushort, 10),
+                (abs, ABS_Y as c_ushort, 20),
+                (key, BTN_TOUCH as c_ushort, 1),
+            ]
+        );
+        // The second finger does not move the single touch axes
+        assert_eq!(
+            fields(contacts.down(1, 30, 40).unwrap()),
+            vec![
+                (abs, ABS_MT_SLOT as c_ushort, 1),
+                (abs, ABS_MT_TRACKING_ID as c_ushort, 1),
+                (abs, ABS_MT_POSITION_X as c_ushort, 30),
+                (abs, ABS_MT_POSITION_Y as c_ushort, 40),
+            ]
+        );
+        assert!(contacts.down(1, 0, 0).is_err());
+        assert!(contacts.down(TOUCH_SLOTS, 0, 0).is_err());
+
+        assert_eq!(
+            fields(contacts.up(0).unwrap()),
+            vec![
+                (abs, ABS_MT_SLOT as c_ushort, 0),
+                (abs, ABS_MT_TRACKING_ID as c_ushort, -1),
+            ]
+        );
+        assert!(contacts.move_to(0, 0, 0).is_err());
+        // The remaining finger is on the lowest slot now
+        assert_eq!(
+            fields(contacts.move_to(1, 35, 45).unwrap()),
+            vec![
+                (abs, ABS_MT_SLOT as c_ushort, 1),
+                (abs, ABS_MT_POSITION_X as c_ushort, 35),
+                (abs, ABS_MT_POSITION_Y as c_ushort, 45),
+                (abs, ABS_X as c_ushort, 35),
+                (abs, ABS_Y as c_ushort, 45),
+            ]
+        );
+        assert_eq!(
+            fields(contacts.up(1).unwrap()),
+            vec![
+                (abs, ABS_MT_SLOT as c_ushort, 1),
+                (abs, ABS_MT_TRACKING_ID as c_ushort, -1),
+                (key, BTN_TOUCH as c_ushort, 0),
+            ]
+        );
+    }
 }
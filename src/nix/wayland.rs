@@ -0,0 +1,329 @@
+///
+/// This module contains the monitor layout of Wayland, read from
+/// the `wl_output` globals that the compositor announces
+///
+/// The positions of the outputs are in the layout of the compositor,
+/// which is in the logical (scaled) pixels, so the sizes are reported
+/// in the logical pixels as well
+///
+use crate::display::Monitor;
+use std::{
+    ffi::CStr,
+    os::raw::{c_char, c_int, c_void},
+    ptr::{null, null_mut},
+};
+
+/// The highest version of `wl_output` that is used, the 4th adds the names
+const OUTPUT_VERSION: u32 = 4;
+/// The request opcodes of `wl_display` and `wl_registry`
+const WL_DISPLAY_GET_REGISTRY: u32 = 1;
+const WL_REGISTRY_BIND: u32 = 0;
+/// The flag of the current mode of an output
+const WL_OUTPUT_MODE_CURRENT: u32 = 1;
+
+/// What the compositor reports about an output
+#[derive(Debug, Default)]
+struct Output {
+    proxy: usize,
+    name: String,
+    x: i32,
+    y: i32,
+    /// The size of the current mode in the physical pixels
+    width: i32,
+    height: i32,
+    transform: i32,
+    scale: i32,
+}
+
+/// The outputs that are bound while reading the globals
+struct Registry {
+    proxy: *mut WlProxy,
+    /// The listeners of the outputs write to the boxes, which do not
+    /// move when the list grows
+    #[allow(clippy::vec_box)]
+    outputs: Vec<Box<Output>>,
+}
+
+/// Get the outputs of the compositor of `$WAYLAND_DISPLAY`,
+/// None if it can not be connected to
+pub fn monitors() -> Option<Vec<Monitor>> {
+    unsafe {
+        let display = wl_display_connect(null());
+        if display.is_null() {
+            return None;
+        }
+
+        let mut registry = Registry {
+            proxy: wl_proxy_marshal_constructor(
+                display as *mut WlProxy,
+                WL_DISPLAY_GET_REGISTRY,
+                &wl_registry_interface,
+                null_mut::<c_void>(),
+            ),
+            outputs: Vec::new(),
+        };
+        if registry.proxy.is_null() {
+            wl_display_disconnect(display);
+            return None;
+        }
+        wl_proxy_add_listener(
+            registry.proxy,
+            &REGISTRY_LISTENER as *const RegistryListener as *const c_void,
+            &mut registry as *mut Registry as *mut c_void,
+        );
+
+        // The first roundtrip binds the outputs, the second receives their state
+        let connected = wl_display_roundtrip(display) >= 0 && wl_display_roundtrip(display) >= 0;
+
+        let monitors = registry
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(id, output)| to_monitor(id, output))
+            .collect();
+        for output in registry.outputs.iter() {
+            wl_proxy_destroy(output.proxy as *mut WlProxy);
+        }
+        wl_proxy_destroy(registry.proxy);
+        wl_display_disconnect(display);
+
+        if connected {
+            Some(monitors)
+        } else {
+            None
+        }
+    }
+}
+
+/// Construct the monitor from the state of the given output
+fn to_monitor(id: usize, output: &Output) -> Monitor {
+    // The odd transforms rotate the output by 90 or 270 degrees
+    let (width, height) = if output.transform % 2 == 1 {
+        (output.height, output.width)
+    } else {
+        (output.width, output.height)
+    };
+    let scale = output.scale.max(1);
+    Monitor {
+        id,
+        name: output.name.clone(),
+        x: output.x,
+        y: output.y,
+        width: width / scale,
+        height: height / scale,
+        scale: scale as f64,
+        // Wayland does not have a primary output
+        primary: id == 0,
+    }
+}
+
+unsafe extern "C" fn registry_global(
+    data: *mut c_void,
+    proxy: *mut WlProxy,
+    name: u32,
+    interface: *const c_char,
+    version: u32,
+) {
+    let registry = &mut *(data as *mut Registry);
+    if CStr::from_ptr(interface).to_bytes() != b"wl_output" {
+        return;
+    }
+
+    let version = version.min(OUTPUT_VERSION);
+    let output = wl_proxy_marshal_constructor_versioned(
+        proxy,
+        WL_REGISTRY_BIND,
+        &wl_output_interface,
+        version,
+        name,
+        wl_output_interface.name,
+        version,
+        null_mut::<c_void>(),
+    );
+    if output.is_null() {
+        return;
+    }
+    let mut state = Box::new(Output {
+        proxy: output as usize,
+        scale: 1,
+        ..Default::default()
+    });
+    wl_proxy_add_listener(
+        output,
+        &OUTPUT_LISTENER as *const OutputListener as *const c_void,
+        state.as_mut() as *mut Output as *mut c_void,
+    );
+    registry.outputs.push(state);
+}
+
+unsafe extern "C" fn registry_global_remove(_data: *mut c_void, _proxy: *mut WlProxy, _name: u32) {}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn output_geometry(
+    data: *mut c_void,
+    _proxy: *mut WlProxy,
+    x: c_int,
+    y: c_int,
+    _physical_width: c_int,
+    _physical_height: c_int,
+    _subpixel: c_int,
+    _make: *const c_char,
+    _model: *const c_char,
+    transform: c_int,
+) {
+    let output = &mut *(data as *mut Output);
+    output.x = x;
+    output.y = y;
+    output.transform = transform;
+}
+
+unsafe extern "C" fn output_mode(
+    data: *mut c_void,
+    _proxy: *mut WlProxy,
+    flags: u32,
+    width: c_int,
+    height: c_int,
+    _refresh: c_int,
+) {
+    if flags & WL_OUTPUT_MODE_CURRENT != 0 {
+        let output = &mut *(data as *mut Output);
+        output.width = width;
+        output.height = height;
+    }
+}
+
+unsafe extern "C" fn output_done(_data: *mut c_void, _proxy: *mut WlProxy) {}
+
+unsafe extern "C" fn output_scale(data: *mut c_void, _proxy: *mut WlProxy, factor: c_int) {
+    (*(data as *mut Output)).scale = factor;
+}
+
+unsafe extern "C" fn output_name(data: *mut c_void, _proxy: *mut WlProxy, name: *const c_char) {
+    (*(data as *mut Output)).name = CStr::from_ptr(name).to_string_lossy().into_owned();
+}
+
+unsafe extern "C" fn output_description(
+    _data: *mut c_void,
+    _proxy: *mut WlProxy,
+    _description: *const c_char,
+) {
+}
+
+static REGISTRY_LISTENER: RegistryListener = RegistryListener {
+    global: registry_global,
+    global_remove: registry_global_remove,
+};
+
+static OUTPUT_LISTENER: OutputListener = OutputListener {
+    geometry: output_geometry,
+    mode: output_mode,
+    done: output_done,
+    scale: output_scale,
+    name: output_name,
+    description: output_description,
+};
+
+/// libwayland-client types
+enum WlDisplay {}
+enum WlProxy {}
+
+#[repr(C)]
+struct WlInterface {
+    name: *const c_char,
+    version: c_int,
+    method_count: c_int,
+    methods: *const c_void,
+    event_count: c_int,
+    events: *const c_void,
+}
+
+#[repr(C)]
+struct RegistryListener {
+    global: unsafe extern "C" fn(*mut c_void, *mut WlProxy, u32, *const c_char, u32),
+    global_remove: unsafe extern "C" fn(*mut c_void, *mut WlProxy, u32),
+}
+
+#[repr(C)]
+struct OutputListener {
+    geometry: unsafe extern "C" fn(
+        *mut c_void,
+        *mut WlProxy,
+        c_int,
+        c_int,
+        c_int,
+        c_int,
+        c_int,
+        *const c_char,
+        *const c_char,
+        c_int,
+    ),
+    mode: unsafe extern "C" fn(*mut c_void, *mut WlProxy, u32, c_int, c_int, c_int),
+    done: unsafe extern "C" fn(*mut c_void, *mut WlProxy),
+    scale: unsafe extern "C" fn(*mut c_void, *mut WlProxy, c_int),
+    name: unsafe extern "C" fn(*mut c_void, *mut WlProxy, *const c_char),
+    description: unsafe extern "C" fn(*mut c_void, *mut WlProxy, *const c_char),
+}
+
+#[link(name = "wayland-client")]
+extern "C" {
+    static wl_registry_interface: WlInterface;
+    static wl_output_interface: WlInterface;
+
+    fn wl_display_connect(name: *const c_char) -> *mut WlDisplay;
+    fn wl_display_disconnect(display: *mut WlDisplay);
+    fn wl_display_roundtrip(display: *mut WlDisplay) -> c_int;
+    fn wl_proxy_marshal_constructor(
+        proxy: *mut WlProxy,
+        opcode: u32,
+        interface: *const WlInterface,
+        ...
+    ) -> *mut WlProxy;
+    fn wl_proxy_marshal_constructor_versioned(
+        proxy: *mut WlProxy,
+        opcode: u32,
+        interface: *const WlInterface,
+        version: u32,
+        ...
+    ) -> *mut WlProxy;
+    fn wl_proxy_add_listener(
+        proxy: *mut WlProxy,
+        implementation: *const c_void,
+        data: *mut c_void,
+    ) -> c_int;
+    fn wl_proxy_destroy(proxy: *mut WlProxy);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_monitors() {
+        let output = Output {
+            name: "DP-1".to_string(),
+            x: 1920,
+            width: 3840,
+            height: 2160,
+            scale: 2,
+            ..Default::default()
+        };
+        let monitor = to_monitor(1, &output);
+        assert_eq!(
+            (monitor.x, monitor.y, monitor.width, monitor.height),
+            (1920, 0, 1920, 1080)
+        );
+        assert_eq!(monitor.scale, 2.);
+        assert!(!monitor.primary);
+
+        // A portrait output
+        let output = Output {
+            width: 1920,
+            height: 1080,
+            transform: 1,
+            scale: 1,
+            ..Default::default()
+        };
+        let monitor = to_monitor(0, &output);
+        assert_eq!((monitor.width, monitor.height), (1080, 1920));
+    }
+}
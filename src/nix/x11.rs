@@ -1,51 +1,111 @@
 ///
-/// This module contains the mouse action functions
-/// for the unix-like systems that use X11
+/// This module contains the mouse and keyboard action
+/// functions for the unix-like systems that use X11
 ///
-use crate::common::{CallbackId, MouseActions, MouseButton, MouseEvent, ScrollDirection};
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, CoordinateSpace, EventAction,
+    EventMask, EventWithTime, ListenerStats, Motion, MotionProfile, MouseButton, MouseController,
+    MouseEvent, MouseEventExt, MouseListener, Propagation, ScrollDirection,
+};
+use crate::display::Rect;
+use crate::error::Error;
+use crate::hooks::{CallbackList, Handler, DEFAULT_PRIORITY};
+use crate::keyboard::{Key, KeyboardActions, ModifierKeys, Modifiers};
+use crate::nix::listener::Listener;
+use crate::nix::queue::QueueOptions;
 use std::{
-    collections::HashMap,
-    io::{Error, ErrorKind, Result},
-    os::raw::{c_char, c_int, c_uint, c_ulong},
-    sync::{Arc, Mutex},
+    ffi::CStr,
+    os::raw::{c_char, c_int, c_long, c_uchar, c_uint, c_ulong, c_void},
+    sync::Arc,
 };
 
 pub struct X11MouseManager {
     display: *mut Display,
     window: Window,
-    callbacks: Arc<Mutex<HashMap<CallbackId, Box<dyn Fn(&MouseEvent) + Send>>>>,
+    callbacks: super::Callbacks,
     callback_counter: CallbackId,
-    is_listening: bool,
+    listener: Option<Listener>,
+    /// The callbacks of `on_listener_restarted`, they share the ids with the other callbacks
+    restart_callbacks: super::RestartCallbacks,
+    /// The queue of the events that the listener reads
+    queue: QueueOptions,
+    /// Whether the listener grabs the devices
+    grabbing: bool,
+    /// The callbacks that grab the devices, the listener stops grabbing once they are gone
+    grab_ids: Vec<CallbackId>,
+    /// The units of the coordinates, and the scale factor of the logical ones
+    space: CoordinateSpace,
+    scale: f64,
+    /// Maps the offsets of the relative moves to the moved pixels
+    motion: Motion,
+    /// The pointer barriers around the rectangle of `confine_cursor`
+    barriers: Vec<PointerBarrier>,
+    /// Whether the cursor is shown, XFixes shows it once the connection is closed
+    cursor_visible: bool,
+    /// The keyboard manager that holds the modifiers of `click_with_modifiers`
+    modifier_keys: ModifierKeys,
 }
 
+// The display is only used by one thread at a time, the actions that change
+// it need `&mut self`, which the other threads can only get through a lock
+unsafe impl Send for X11MouseManager {}
+
 impl X11MouseManager {
+    /// Connect to the X server of `$DISPLAY`
+    ///
+    /// The manager does not check the connection, use `try_new` to get an error when
+    /// the X server can not be reached instead of the actions failing later
     pub fn new() -> Self {
         unsafe {
             let display = XOpenDisplay(&0);
             let window = XDefaultRootWindow(display);
+            let callbacks: super::Callbacks = Arc::default();
             X11MouseManager {
                 display,
                 window,
-                callbacks: Arc::new(Mutex::new(HashMap::new())),
                 callback_counter: 0,
-                is_listening: false,
+                listener: None,
+                restart_callbacks: Arc::new(CallbackList::new(callbacks.panics())),
+                callbacks,
+                queue: QueueOptions::default(),
+                grabbing: false,
+                grab_ids: Vec::new(),
+                space: CoordinateSpace::Physical,
+                scale: 1.,
+                motion: Motion::default(),
+                barriers: Vec::new(),
+                cursor_visible: true,
+                modifier_keys: ModifierKeys::default(),
             }
         }
     }
 
-    fn button_event(&self, button: &MouseButton, is_press: bool) -> Result<()> {
+    /// Connect to the X server of `$DISPLAY`, fails if it can not be reached
+    pub fn try_new() -> Result<Self, Error> {
+        let manager = Self::new();
+        if manager.display.is_null() {
+            return Err(Error::CustomError(
+                "failed to connect to the X server".to_string(),
+            ));
+        }
+        Ok(manager)
+    }
+
+    /// Set the queue between the threads that read the mice and the callbacks,
+    /// it applies to the listener that is started afterwards if it reads the
+    /// event files, see `supports(Capability::ListenerRestarts)`
+    pub fn set_event_queue(&mut self, queue: QueueOptions) {
+        self.queue = queue;
+    }
+
+    fn button_event(&self, button: &MouseButton, is_press: bool) -> Result<(), Error> {
         let btn = match button {
             MouseButton::Left => 1,
             MouseButton::Middle => 2,
             MouseButton::Right => 3,
             MouseButton::Back => 8,
             MouseButton::Forward => 9,
-            _ => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("Unsupported button: {:?}", button),
-                ));
-            }
+            _ => return Err(Error::Unsupported(Backend::X11)),
         };
         unsafe {
             XTestFakeButtonEvent(self.display, btn, is_press, 0);
@@ -53,18 +113,123 @@ impl X11MouseManager {
         }
         Ok(())
     }
+
+    /// Start the listener if it is not running yet and add the given callback
+    ///
+    /// A callback that needs to `grab` the devices restarts
+    /// the running listener in the grabbing mode
+    fn add_callback(
+        &mut self,
+        mask: EventMask,
+        grab: bool,
+        callback: super::Callback,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(DEFAULT_PRIORITY, mask, grab, Handler::Action(callback))
+    }
+
+    /// Start the listener if it is not running yet and add the given
+    /// handler in the order of the given priority, like `add_callback`
+    fn add_handler(
+        &mut self,
+        priority: i32,
+        mask: EventMask,
+        grab: bool,
+        handler: Handler<MouseEventExt>,
+    ) -> Result<CallbackId, Error> {
+        if grab && !self.grabbing {
+            self.listener = None;
+            self.grabbing = true;
+        }
+        self.start_listener()?;
+
+        let id = self.callback_counter;
+        self.callbacks.insert(id, priority, mask, handler);
+        if grab {
+            self.grab_ids.push(id);
+        }
+        self.callback_counter += 1;
+        Ok(id)
+    }
+
+    /// Start the listener if it is not running yet
+    fn start_listener(&mut self) -> Result<(), Error> {
+        // The raw events of XInput2 do not need the access to /dev/input,
+        // but they can not be grabbed
+        #[cfg(feature = "xinput2")]
+        if self.listener.is_none() && !self.grabbing {
+            self.listener = Some(super::xinput2::start_xinput2_listener(&self.callbacks)?);
+        }
+        if self.listener.is_none() {
+            // Scale the absolute positions to the default screen
+            let (width, height) = if self.display.is_null() {
+                (1, 1)
+            } else {
+                unsafe {
+                    let screen = XDefaultScreen(self.display);
+                    (
+                        XDisplayWidth(self.display, screen),
+                        XDisplayHeight(self.display, screen),
+                    )
+                }
+            };
+            self.listener = Some(super::start_nix_listener(
+                &self.callbacks,
+                &self.restart_callbacks,
+                self.grabbing,
+                ((0, width - 1), (0, height - 1)),
+                self.queue,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Restart the listener without grabbing the devices once the callbacks that grab
+    /// them are all unhooked, so that the events reach the system again
+    fn release_grab(&mut self) -> Result<(), Error> {
+        let callbacks = &self.callbacks;
+        self.grab_ids.retain(|id| callbacks.contains(*id));
+        if !self.grabbing || !self.grab_ids.is_empty() {
+            return Ok(());
+        }
+        self.grabbing = false;
+        self.listener = None;
+        if self.callbacks.is_empty() {
+            return Ok(());
+        }
+        self.start_listener()
+    }
+}
+
+impl Default for X11MouseManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl MouseActions for X11MouseManager {
-    fn move_to(&mut self, x: usize, y: usize) -> Result<()> {
+impl MouseController for X11MouseManager {
+    fn move_to(&mut self, x: usize, y: usize) -> Result<(), Error> {
+        let (x, y) = self.space.space_to_physical(x as i32, y as i32, self.scale);
         unsafe {
-            XWarpPointer(self.display, 0, self.window, 0, 0, 0, 0, x as i32, y as i32);
+            // Unlike warping the pointer, the fake motion goes through the input
+            // pipeline, so the applications see it as if a mouse moved it
+            let screen = XDefaultScreen(self.display);
+            XTestFakeMotionEvent(self.display, screen, x, y, 0);
             XFlush(self.display);
         }
         Ok(())
     }
 
-    fn get_position(&self) -> Result<(i32, i32)> {
+    fn move_relative(&mut self, x_offset: i32, y_offset: i32) -> Result<(), Error> {
+        let (x_offset, y_offset) = self.motion.apply(x_offset, y_offset);
+        let (x_offset, y_offset) = self.space.space_to_physical(x_offset, y_offset, self.scale);
+        unsafe {
+            XTestFakeRelativeMotionEvent(self.display, x_offset, y_offset, 0);
+            XFlush(self.display);
+        }
+        Ok(())
+    }
+
+    fn get_position(&self) -> Result<(i32, i32), Error> {
         let mut x = 0;
         let mut y = 0;
         let mut void = 0;
@@ -86,27 +251,77 @@ impl MouseActions for X11MouseManager {
             // If XQueryPointer returns False (which is an enum value that corresponds to 0)
             // that means the pointer is not on the same screen as the specified window
             if out == 0 {
-                return Err(Error::new(ErrorKind::NotFound, "X11PointerWindowMismatch"));
+                return Err(Error::X11PointerWindowMismatch);
             }
         }
 
-        Ok((x, y))
+        Ok(self.space.physical_to_space(x, y, self.scale))
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::X11
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        // XInput2 does not lose the devices, as the X server reads them
+        if capability == Capability::ListenerRestarts {
+            return (self.grabbing || !cfg!(feature = "xinput2"))
+                && super::reads_event_files(self.grabbing);
+        }
+        matches!(
+            capability,
+            Capability::GetPosition
+                | Capability::ButtonState
+                | Capability::CoordinateSpace
+                | Capability::MotionProfile
+                | Capability::ConfineCursor
+                | Capability::CursorVisibility
+                | Capability::GrabEvents
+                | Capability::InjectEvent
+                | Capability::PauseHook
+                | Capability::CallbackPanics
+                | Capability::ListenerStats
+        )
+    }
+
+    fn get_button_state(&self) -> Result<ButtonState, Error> {
+        super::button_state()
+    }
+
+    fn set_coordinate_space(&mut self, space: CoordinateSpace) -> Result<(), Error> {
+        self.scale = super::screen::scale_factor();
+        self.space = space;
+        Ok(())
     }
 
-    fn press_button(&mut self, button: &MouseButton) -> Result<()> {
+    fn set_motion_profile(&mut self, profile: MotionProfile) -> Result<(), Error> {
+        self.motion.set_profile(profile);
+        Ok(())
+    }
+
+    fn press_button(&mut self, button: &MouseButton) -> Result<(), Error> {
         self.button_event(button, true)
     }
 
-    fn release_button(&mut self, button: &MouseButton) -> Result<()> {
+    fn release_button(&mut self, button: &MouseButton) -> Result<(), Error> {
         self.button_event(button, false)
     }
 
-    fn click_button(&mut self, button: &MouseButton) -> Result<()> {
+    fn click_button(&mut self, button: &MouseButton) -> Result<(), Error> {
         self.press_button(button)?;
         self.release_button(button)
     }
 
-    fn scroll_wheel(&mut self, direction: &ScrollDirection) -> Result<()> {
+    fn click_with_modifiers(
+        &mut self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        let modifier_keys = self.modifier_keys.clone();
+        modifier_keys.hold(modifiers, || self.click_button(button))
+    }
+
+    fn scroll_wheel(&mut self, direction: &ScrollDirection) -> Result<(), Error> {
         let btn = match direction {
             ScrollDirection::Up => 4,
             ScrollDirection::Down => 5,
@@ -121,80 +336,507 @@ impl MouseActions for X11MouseManager {
         Ok(())
     }
 
-    fn hook(&mut self, callback: Box<dyn Fn(&MouseEvent) + Send>) -> Result<CallbackId> {
-        if !self.is_listening {
-            super::start_nix_listener(&self.callbacks)?;
-            self.is_listening = true;
+    fn confine_cursor(&mut self, rect: Rect) -> Result<(), Error> {
+        self.release_cursor()?;
+        unsafe {
+            // The barriers are only available to the clients that ask for XFixes 5
+            let (mut major, mut minor) = (5, 0);
+            if XFixesQueryVersion(self.display, &mut major, &mut minor) == 0 || major < 5 {
+                return Err(Error::CustomError(
+                    "the X server does not support the pointer barriers".to_string(),
+                ));
+            }
+        }
+        // The barriers only stop the pointer from crossing them
+        if let Ok((x, y)) = self.get_position() {
+            if !rect.contains(x, y) {
+                let (x, y) = rect.clamp(x, y);
+                self.move_to(x.max(0) as usize, y.max(0) as usize)?;
+            }
+        }
+
+        // Unlike a pointer grab, the barriers do not take the events from the other clients
+        let (left, top) = self.space.space_to_physical(rect.x, rect.y, self.scale);
+        let (width, height) = self
+            .space
+            .space_to_physical(rect.width, rect.height, self.scale);
+        let (right, bottom) = (left + width, top + height);
+        for (x1, y1, x2, y2, directions) in [
+            (left, top, left, bottom, BARRIER_POSITIVE_X),
+            (right, top, right, bottom, BARRIER_NEGATIVE_X),
+            (left, top, right, top, BARRIER_POSITIVE_Y),
+            (left, bottom, right, bottom, BARRIER_NEGATIVE_Y),
+        ] {
+            let barrier = unsafe {
+                XFixesCreatePointerBarrier(
+                    self.display,
+                    self.window,
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    directions,
+                    0,
+                    std::ptr::null_mut(),
+                )
+            };
+            self.barriers.push(barrier);
+        }
+        unsafe {
+            XFlush(self.display);
+        }
+        Ok(())
+    }
+
+    fn release_cursor(&mut self) -> Result<(), Error> {
+        if self.barriers.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            for barrier in self.barriers.drain(..) {
+                XFixesDestroyPointerBarrier(self.display, barrier);
+            }
+            XFlush(self.display);
+        }
+        Ok(())
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) -> Result<(), Error> {
+        // XFixes counts the hides of every client, so they are not repeated
+        if visible == self.cursor_visible {
+            return Ok(());
+        }
+        unsafe {
+            if visible {
+                XFixesShowCursor(self.display, self.window);
+            } else {
+                XFixesHideCursor(self.display, self.window);
+            }
+            XFlush(self.display);
+        }
+        self.cursor_visible = visible;
+        Ok(())
+    }
+}
+
+impl MouseListener for X11MouseManager {
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_with_time(Box::new(move |event| callback(&event.event)))
+    }
+
+    fn hook_with_time(
+        &mut self,
+        callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.hook_ext(Box::new(move |event| {
+            callback(&EventWithTime {
+                event: event.event.clone(),
+                time: event.time,
+            })
+        }))
+    }
+
+    fn hook_ext(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            EventMask::ALL,
+            false,
+            Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            }),
+        )
+    }
+
+    fn hook_filtered(
+        &mut self,
+        mask: EventMask,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            mask,
+            false,
+            Box::new(move |event| {
+                callback(&event.event);
+                EventAction::PassThrough
+            }),
+        )
+    }
+
+    fn hook_grab(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) -> EventAction + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(
+            EventMask::ALL,
+            true,
+            Box::new(move |event| callback(&event.event)),
+        )
+    }
+
+    fn hook_with_priority(
+        &mut self,
+        priority: i32,
+        callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(
+            priority,
+            EventMask::ALL,
+            false,
+            Handler::Propagate(Box::new(move |event| callback(&event.event))),
+        )
+    }
+
+    fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+        Ok(super::inject(&self.callbacks, event))
+    }
+
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.remove(callback_id) {
+            return self.release_grab();
+        }
+        if self.restart_callbacks.remove(callback_id) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
         }
+    }
+
+    fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, true) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, false) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
 
+    fn on_callback_panic(
+        &mut self,
+        unhook: bool,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
+        self.callbacks.set_panic_handler(handler, unhook);
+        Ok(())
+    }
+
+    fn unhook_all(&mut self) -> Result<(), Error> {
+        self.callbacks.clear();
+        self.restart_callbacks.clear();
+        self.release_grab()
+    }
+
+    fn stop_listening(&mut self) -> Result<(), Error> {
+        // Dropping the listener stops and joins its threads
+        self.listener = None;
+        Ok(())
+    }
+
+    fn listener_stats(&self) -> Result<ListenerStats, Error> {
+        Ok(self
+            .listener
+            .as_ref()
+            .map(Listener::stats)
+            .unwrap_or_default())
+    }
+
+    fn on_listener_restarted(
+        &mut self,
+        callback: Box<dyn Fn() + Send>,
+    ) -> Result<CallbackId, Error> {
         let id = self.callback_counter;
-        self.callbacks.lock().unwrap().insert(id, callback);
+        self.restart_callbacks.push(id, callback);
         self.callback_counter += 1;
         Ok(id)
     }
+}
+
+/// Simulates the keyboard through the XTest extension
+pub struct X11KeyboardManager {
+    display: *mut Display,
+}
+
+// The display is only used by one thread at a time, like the one of `X11MouseManager`
+unsafe impl Send for X11KeyboardManager {}
 
-    fn unhook(&mut self, callback_id: CallbackId) -> Result<()> {
-        match self.callbacks.lock().unwrap().remove(&callback_id) {
-            Some(_) => Ok(()),
-            None => Err(Error::new(
-                ErrorKind::NotFound,
-                format!("callback id {} not found", callback_id),
-            )),
+impl X11KeyboardManager {
+    pub fn new() -> Result<Self, Error> {
+        let display = unsafe { XOpenDisplay(&0) };
+        if display.is_null() {
+            return Err(Error::CustomError(
+                "failed to connect to the X server".to_string(),
+            ));
         }
+        Ok(X11KeyboardManager { display })
     }
 
-    fn unhook_all(&mut self) -> Result<()> {
-        self.callbacks.lock().unwrap().clear();
+    fn key_event(&self, key: &Key, is_press: bool) -> Result<(), Error> {
+        let keysym = match key {
+            // The keysyms of the ASCII characters are their codes
+            Key::Char(ch) if ch.is_ascii_graphic() => *ch as c_ulong,
+            Key::Enter => XK_RETURN,
+            Key::Escape => XK_ESCAPE,
+            Key::Backspace => XK_BACKSPACE,
+            Key::Tab => XK_TAB,
+            Key::Space => XK_SPACE,
+            Key::Shift => XK_SHIFT_L,
+            Key::Control => XK_CONTROL_L,
+            Key::Alt => XK_ALT_L,
+            Key::Meta => XK_SUPER_L,
+            Key::CapsLock => XK_CAPS_LOCK,
+            Key::Left => XK_LEFT,
+            Key::Right => XK_RIGHT,
+            Key::Up => XK_UP,
+            Key::Down => XK_DOWN,
+            Key::Home => XK_HOME,
+            Key::End => XK_END,
+            Key::PageUp => XK_PAGE_UP,
+            Key::PageDown => XK_PAGE_DOWN,
+            Key::Insert => XK_INSERT,
+            Key::Delete => XK_DELETE,
+            Key::F(n @ 1..=12) => XK_F1 + *n as c_ulong - 1,
+            _ => return Err(Error::Unsupported(Backend::X11)),
+        };
+        unsafe {
+            // The keysym is not mapped to any key in the current layout
+            let keycode = XKeysymToKeycode(self.display, keysym);
+            if keycode == 0 {
+                return Err(Error::Unsupported(Backend::X11));
+            }
+            XTestFakeKeyEvent(self.display, keycode as c_uint, is_press, 0);
+            XFlush(self.display);
+        }
         Ok(())
     }
 }
 
-/// Xlib type definitions
-enum _XDisplay {}
-type Display = _XDisplay;
-type Window = c_ulong;
+impl Drop for X11KeyboardManager {
+    fn drop(&mut self) {
+        unsafe {
+            XCloseDisplay(self.display);
+        }
+    }
+}
 
-#[derive(Debug)]
-#[repr(C)]
-struct XEvent {
-    r#type: c_int,
-    xbutton: XButtonEvent,
+impl KeyboardActions for X11KeyboardManager {
+    fn press_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, true)
+    }
+
+    fn release_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, false)
+    }
+}
+
+/// Get the position of the pointer from the X server if there is one running,
+/// e.g. XWayland, returns None if it is not possible to connect to it
+pub fn query_pointer_position() -> Option<(i32, i32)> {
+    unsafe {
+        let display = XOpenDisplay(&0);
+        if display.is_null() {
+            return None;
+        }
+
+        let (mut x, mut y) = (0, 0);
+        let mut void = 0;
+        let mut mask = 0;
+        let out = XQueryPointer(
+            display,
+            XDefaultRootWindow(display),
+            &mut void,
+            &mut void,
+            &mut x,
+            &mut y,
+            &mut x,
+            &mut y,
+            &mut mask,
+        );
+        XCloseDisplay(display);
+
+        if out == 0 {
+            None
+        } else {
+            Some((x, y))
+        }
+    }
+}
+
+/// Get the size of the default screen of the X server if there is one running
+pub fn query_screen_size() -> Option<(i32, i32)> {
+    unsafe {
+        let display = XOpenDisplay(&0);
+        if display.is_null() {
+            return None;
+        }
+        let screen = XDefaultScreen(display);
+        let size = (
+            XDisplayWidth(display, screen),
+            XDisplayHeight(display, screen),
+        );
+        XCloseDisplay(display);
+        Some(size)
+    }
+}
+
+/// Get the resources of the X server e.g. `Xft.dpi:\t144`, one per line
+pub fn query_resources() -> Option<String> {
+    unsafe {
+        let display = XOpenDisplay(&0);
+        if display.is_null() {
+            return None;
+        }
+        // The string is owned by the display
+        let resources = XResourceManagerString(display);
+        let resources = if resources.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(resources).to_string_lossy().into_owned())
+        };
+        XCloseDisplay(display);
+        resources
+    }
+}
+
+/// Reports the names of the cursors that the X server shows, e.g. `text` or `pointer`
+pub struct CursorNames {
+    display: *mut Display,
+    /// The type of the first event of XFixes
+    event_base: c_int,
 }
 
-#[derive(Debug)]
+impl CursorNames {
+    /// Connect to the X server and subscribe to the changes of the cursor
+    pub fn new() -> Result<Self, Error> {
+        unsafe {
+            let display = XOpenDisplay(&0);
+            if display.is_null() {
+                return Err(Error::CustomError(
+                    "failed to connect to the X server".to_string(),
+                ));
+            }
+            // The names of the cursors are reported since XFixes 2
+            let (mut event_base, mut error_base) = (0, 0);
+            let (mut major, mut minor) = (2, 0);
+            if XFixesQueryExtension(display, &mut event_base, &mut error_base) == 0
+                || XFixesQueryVersion(display, &mut major, &mut minor) == 0
+                || major < 2
+            {
+                XCloseDisplay(display);
+                return Err(Error::CustomError(
+                    "the X server does not report the cursor changes".to_string(),
+                ));
+            }
+            XFixesSelectCursorInput(
+                display,
+                XDefaultRootWindow(display),
+                XFIXES_DISPLAY_CURSOR_NOTIFY_MASK,
+            );
+            XFlush(display);
+            Ok(CursorNames {
+                display,
+                event_base,
+            })
+        }
+    }
+
+    /// Get the names of the cursors that are shown since the last call, without
+    /// waiting, the cursors without a name e.g. the ones of the applications are empty
+    pub fn changes(&mut self) -> Vec<String> {
+        let mut names = Vec::new();
+        unsafe {
+            while XPending(self.display) > 0 {
+                let mut event = [0 as c_long; 24];
+                XNextEvent(self.display, event.as_mut_ptr());
+                if event[0] as c_int != self.event_base + XFIXES_CURSOR_NOTIFY {
+                    continue;
+                }
+                let notify = &*(event.as_ptr() as *const XFixesCursorNotifyEvent);
+                names.push(atom_name(self.display, notify.cursor_name));
+            }
+        }
+        names
+    }
+}
+
+impl Drop for CursorNames {
+    fn drop(&mut self) {
+        unsafe {
+            XCloseDisplay(self.display);
+        }
+    }
+}
+
+/// Get the name of the given atom, empty for `None`
+unsafe fn atom_name(display: *mut Display, atom: c_ulong) -> String {
+    if atom == 0 {
+        return String::new();
+    }
+    let name = XGetAtomName(display, atom);
+    if name.is_null() {
+        return String::new();
+    }
+    let owned = CStr::from_ptr(name).to_string_lossy().into_owned();
+    XFree(name as *mut c_void);
+    owned
+}
+
+/// Xlib type definitions
+pub(super) enum _XDisplay {}
+pub(super) type Display = _XDisplay;
+type Window = c_ulong;
+type PointerBarrier = c_ulong;
+
+/// XFixesCursorNotifyEvent
 #[repr(C)]
-struct XButtonEvent {
+struct XFixesCursorNotifyEvent {
     r#type: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
     window: Window,
-    root: Window,
-    subwindow: Window,
-    x: c_int,
-    y: c_int,
-    x_root: c_int,
-    y_root: c_int,
-    state: c_uint,
-    button: c_uint,
-    same_screen: bool,
+    subtype: c_int,
+    cursor_serial: c_ulong,
+    timestamp: c_ulong,
+    cursor_name: c_ulong,
 }
 
+const XFIXES_CURSOR_NOTIFY: c_int = 1;
+const XFIXES_DISPLAY_CURSOR_NOTIFY_MASK: c_ulong = 1;
+
+/// The directions that the pointer can cross a barrier in
+const BARRIER_POSITIVE_X: c_int = 1 << 0;
+const BARRIER_POSITIVE_Y: c_int = 1 << 1;
+const BARRIER_NEGATIVE_X: c_int = 1 << 2;
+const BARRIER_NEGATIVE_Y: c_int = 1 << 3;
+
 // Xlib function definitions
 #[link(name = "X11")]
 extern "C" {
     fn XOpenDisplay(display: *const c_char) -> *mut Display;
+    fn XCloseDisplay(display: *mut Display) -> c_int;
     fn XDefaultRootWindow(display: *mut Display) -> Window;
-    fn XWarpPointer(
-        display: *mut Display,
-        src_w: Window,
-        dest_w: Window,
-        srx_x: c_int,
-        src_y: c_int,
-        src_width: c_uint,
-        src_height: c_uint,
-        dest_x: c_int,
-        dest_y: c_int,
-    ) -> c_int;
-
+    fn XDefaultScreen(display: *mut Display) -> c_int;
+    fn XDisplayWidth(display: *mut Display, screen_number: c_int) -> c_int;
+    fn XDisplayHeight(display: *mut Display, screen_number: c_int) -> c_int;
     fn XFlush(display: *mut Display) -> c_int;
+    fn XKeysymToKeycode(display: *mut Display, keysym: c_ulong) -> c_uchar;
+    fn XResourceManagerString(display: *mut Display) -> *mut c_char;
+    pub(crate) fn XPending(display: *mut Display) -> c_int;
+    pub(crate) fn XNextEvent(display: *mut Display, event_return: *mut c_long) -> c_int;
+    fn XGetAtomName(display: *mut Display, atom: c_ulong) -> *mut c_char;
+    fn XFree(data: *mut c_void) -> c_int;
     fn XQueryPointer(
         display: *mut Display,
         window: Window,
@@ -208,6 +850,36 @@ extern "C" {
     ) -> c_int;
 }
 
+// XFixes function definitions
+#[link(name = "Xfixes")]
+extern "C" {
+    fn XFixesQueryExtension(
+        dpy: *mut Display,
+        event_base_return: *mut c_int,
+        error_base_return: *mut c_int,
+    ) -> c_int;
+    fn XFixesQueryVersion(
+        dpy: *mut Display,
+        major_version_return: *mut c_int,
+        minor_version_return: *mut c_int,
+    ) -> c_int;
+    fn XFixesCreatePointerBarrier(
+        dpy: *mut Display,
+        w: Window,
+        x1: c_int,
+        y1: c_int,
+        x2: c_int,
+        y2: c_int,
+        directions: c_int,
+        num_devices: c_int,
+        devices: *mut c_int,
+    ) -> PointerBarrier;
+    fn XFixesDestroyPointerBarrier(dpy: *mut Display, b: PointerBarrier);
+    fn XFixesHideCursor(dpy: *mut Display, win: Window);
+    fn XFixesShowCursor(dpy: *mut Display, win: Window);
+    fn XFixesSelectCursorInput(dpy: *mut Display, win: Window, event_mask: c_ulong);
+}
+
 // XTest function definitions
 #[link(name = "Xtst")]
 extern "C" {
@@ -217,4 +889,42 @@ extern "C" {
         is_press: bool,
         delay: c_ulong,
     ) -> c_int;
+    fn XTestFakeMotionEvent(
+        dpy: *mut Display,
+        screen_number: c_int,
+        x: c_int,
+        y: c_int,
+        delay: c_ulong,
+    ) -> c_int;
+    fn XTestFakeRelativeMotionEvent(dpy: *mut Display, x: c_int, y: c_int, delay: c_ulong)
+        -> c_int;
+    fn XTestFakeKeyEvent(
+        dpy: *mut Display,
+        keycode: c_uint,
+        is_press: bool,
+        delay: c_ulong,
+    ) -> c_int;
 }
+
+// Keysym definitions
+const XK_BACKSPACE: c_ulong = 0xff08;
+const XK_TAB: c_ulong = 0xff09;
+const XK_RETURN: c_ulong = 0xff0d;
+const XK_ESCAPE: c_ulong = 0xff1b;
+const XK_SPACE: c_ulong = 0x0020;
+const XK_HOME: c_ulong = 0xff50;
+const XK_LEFT: c_ulong = 0xff51;
+const XK_UP: c_ulong = 0xff52;
+const XK_RIGHT: c_ulong = 0xff53;
+const XK_DOWN: c_ulong = 0xff54;
+const XK_PAGE_UP: c_ulong = 0xff55;
+const XK_PAGE_DOWN: c_ulong = 0xff56;
+const XK_END: c_ulong = 0xff57;
+const XK_INSERT: c_ulong = 0xff63;
+const XK_F1: c_ulong = 0xffbe;
+const XK_SHIFT_L: c_ulong = 0xffe1;
+const XK_CONTROL_L: c_ulong = 0xffe3;
+const XK_CAPS_LOCK: c_ulong = 0xffe5;
+const XK_ALT_L: c_ulong = 0xffe9;
+const XK_SUPER_L: c_ulong = 0xffeb;
+const XK_DELETE: c_ulong = 0xffff;
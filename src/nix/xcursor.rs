@@ -0,0 +1,129 @@
+///
+/// This module contains the changing of the cursor image of X11 through
+/// Xcursor, the cursors are replaced by their names with XFixes
+///
+use crate::cursor::CursorImage;
+use crate::error::Error;
+use crate::nix::x11::Display;
+use std::{
+    ffi::CString,
+    os::raw::{c_char, c_int, c_uint, c_ulong},
+    ptr::null,
+    slice,
+};
+
+/// The names of the arrow cursor in the cursor themes
+const ARROW_CURSORS: [&str; 2] = ["left_ptr", "default"];
+
+/// Replace the arrow cursors of the X server with the given image
+pub fn set_cursor_image(image: &CursorImage) -> Result<(), Error> {
+    let pixels = image.premultiplied_argb();
+    with_display(|display| unsafe {
+        for name in ARROW_CURSORS {
+            let xcursor_image = XcursorImageCreate(image.width as c_int, image.height as c_int);
+            if xcursor_image.is_null() {
+                return Err(Error::CustomError(
+                    "failed to create the cursor image".to_string(),
+                ));
+            }
+            (*xcursor_image).xhot = image.hotspot.0;
+            (*xcursor_image).yhot = image.hotspot.1;
+            slice::from_raw_parts_mut((*xcursor_image).pixels, pixels.len())
+                .copy_from_slice(&pixels);
+            let cursor = XcursorImageLoadCursor(display, xcursor_image);
+            XcursorImageDestroy(xcursor_image);
+            if cursor == 0 {
+                return Err(Error::CustomError(
+                    "failed to load the cursor image".to_string(),
+                ));
+            }
+            // The replaced cursor keeps the name, so that `reset_cursor` can find it
+            replace_cursor(display, cursor, name);
+        }
+        Ok(())
+    })
+}
+
+/// Restore the arrow cursors of the cursor theme
+pub fn reset_cursor() -> Result<(), Error> {
+    with_display(|display| unsafe {
+        let mut restored = false;
+        for name in ARROW_CURSORS {
+            let c_name = CString::new(name).unwrap();
+            let cursor = XcursorLibraryLoadCursor(display, c_name.as_ptr());
+            // A theme may have only one of the names
+            if cursor != 0 {
+                replace_cursor(display, cursor, name);
+                restored = true;
+            }
+        }
+        if !restored {
+            return Err(Error::CustomError(
+                "the cursor theme has no arrow cursor".to_string(),
+            ));
+        }
+        Ok(())
+    })
+}
+
+/// Replace the cursors with the given name by the given cursor, and free the cursor
+unsafe fn replace_cursor(display: *mut Display, cursor: Cursor, name: &str) {
+    let c_name = CString::new(name).unwrap();
+    XFixesSetCursorName(display, cursor, c_name.as_ptr());
+    XFixesChangeCursorByName(display, cursor, c_name.as_ptr());
+    XFreeCursor(display, cursor);
+}
+
+/// Run the given function with a connection to the X server, which is
+/// flushed and closed afterwards, the replaced cursors outlive the connection
+fn with_display<T>(run: impl FnOnce(*mut Display) -> Result<T, Error>) -> Result<T, Error> {
+    unsafe {
+        let display = XOpenDisplay(null());
+        if display.is_null() {
+            return Err(Error::CustomError(
+                "failed to connect to the X server".to_string(),
+            ));
+        }
+        let result = run(display);
+        XFlush(display);
+        XCloseDisplay(display);
+        result
+    }
+}
+
+/// Xlib and Xcursor types
+type Cursor = c_ulong;
+
+#[repr(C)]
+struct XcursorImage {
+    version: c_uint,
+    size: c_uint,
+    width: c_uint,
+    height: c_uint,
+    xhot: c_uint,
+    yhot: c_uint,
+    delay: c_uint,
+    pixels: *mut u32,
+}
+
+#[link(name = "X11")]
+extern "C" {
+    fn XOpenDisplay(display: *const c_char) -> *mut Display;
+    fn XCloseDisplay(display: *mut Display) -> c_int;
+    fn XFlush(display: *mut Display) -> c_int;
+    fn XFreeCursor(display: *mut Display, cursor: Cursor) -> c_int;
+}
+
+#[link(name = "Xcursor")]
+extern "C" {
+    fn XcursorImageCreate(width: c_int, height: c_int) -> *mut XcursorImage;
+    fn XcursorImageDestroy(image: *mut XcursorImage);
+    fn XcursorImageLoadCursor(display: *mut Display, image: *const XcursorImage) -> Cursor;
+    fn XcursorLibraryLoadCursor(display: *mut Display, name: *const c_char) -> Cursor;
+}
+
+#[link(name = "Xfixes")]
+extern "C" {
+    fn XFixesSetCursorName(display: *mut Display, cursor: Cursor, name: *const c_char);
+    fn XFixesChangeCursorByName(display: *mut Display, source: Cursor, name: *const c_char);
+}
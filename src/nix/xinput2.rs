@@ -0,0 +1,398 @@
+///
+/// This module contains the XInput2 based event listener
+/// for the unix-like systems that use X11
+///
+/// The raw events of XInput2 are delivered to every client that selects
+/// them on the root window, so unlike the files under /dev/input they
+/// can be received without being in the `input` group. The raw events
+/// are not affected by the pointer acceleration and are reported even
+/// when another client grabs the pointer, as of XInput 2.1
+///
+use crate::common::{MouseButton, MouseEvent, MouseEventExt, ScrollDirection};
+use crate::nix::listener::Listener;
+use crate::nix::x11::{Display, XNextEvent, XPending};
+use crate::nix::Callbacks;
+use crate::trace;
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    io::{Error, ErrorKind, Result},
+    mem::MaybeUninit,
+    os::raw::{c_char, c_double, c_int, c_long, c_uchar, c_uint, c_ulong, c_void},
+    ptr::null,
+    slice,
+    time::SystemTime,
+};
+
+/// Start the XInput2 event listener on its own connection to the display
+///
+/// XInput2 can not stop the events from reaching the other clients,
+/// so this listener is only used for the callbacks that do not grab
+pub fn start_xinput2_listener(callbacks: &Callbacks) -> Result<Listener> {
+    let listener = Listener::new()?;
+
+    let connection = unsafe {
+        let display = XOpenDisplay(null());
+        if display.is_null() {
+            return Err(Error::new(
+                ErrorKind::NotConnected,
+                "failed to open the X11 display",
+            ));
+        }
+        // Close the display on the early returns
+        let connection = Connection(display);
+
+        let name = b"XInputExtension\0";
+        let (mut opcode, mut event, mut error) = (0, 0, 0);
+        if XQueryExtension(
+            display,
+            name.as_ptr() as *const c_char,
+            &mut opcode,
+            &mut event,
+            &mut error,
+        ) == 0
+        {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "the X server does not support the XInput extension",
+            ));
+        }
+
+        // The raw events are introduced in XInput 2.0, but only the clients that
+        // announce 2.1 or later receive them while another client grabs the pointer.
+        // The server replies with the highest version that both sides support
+        let (mut major, mut minor) = (2, 2);
+        if XIQueryVersion(display, &mut major, &mut minor) != SUCCESS || major < 2 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "the X server does not support XInput 2.0",
+            ));
+        }
+        if (major, minor) < (2, 1) {
+            trace::warning!(
+                "the X server only supports XInput {}.{}, the raw events stop while the pointer is grabbed",
+                major,
+                minor
+            );
+        }
+
+        let mut mask = [0; XI_MASK_LEN];
+        for event_type in [XI_RAW_BUTTON_PRESS, XI_RAW_BUTTON_RELEASE, XI_RAW_MOTION] {
+            mask[event_type as usize / 8] |= 1 << (event_type % 8);
+        }
+        let mut event_mask = XIEventMask {
+            deviceid: XI_ALL_MASTER_DEVICES,
+            mask_len: XI_MASK_LEN as c_int,
+            mask: mask.as_mut_ptr(),
+        };
+        XISelectEvents(display, XDefaultRootWindow(display), &mut event_mask, 1);
+        XFlush(display);
+
+        (connection, opcode)
+    };
+
+    let mut dispatcher = callbacks.dispatcher();
+    let handle = listener.handle();
+    let mut counter = handle.counter();
+    // Create a thread for reading and handling the XInput2 events
+    listener.handle().spawn(move || {
+        let (connection, opcode) = connection;
+        let display = connection.0;
+        let fd = unsafe { XConnectionNumber(display) };
+        // The names of the devices are queried once for each device
+        let mut names: HashMap<c_int, String> = HashMap::new();
+
+        while handle.wait(fd) {
+            unsafe {
+                while XPending(display) > 0 {
+                    let mut event = MaybeUninit::<XEvent>::uninit();
+                    XNextEvent(display, event.as_mut_ptr() as *mut c_long);
+                    let mut event = event.assume_init();
+
+                    let cookie = &mut event.cookie;
+                    if cookie.r#type != GENERIC_EVENT
+                        || cookie.extension != opcode
+                        || XGetEventData(display, cookie) == 0
+                    {
+                        continue;
+                    }
+
+                    let raw = &*(cookie.data as *const XIRawEvent);
+                    let mouse_event = match cookie.evtype {
+                        XI_RAW_BUTTON_PRESS => to_button_event(raw.detail, true),
+                        XI_RAW_BUTTON_RELEASE => to_button_event(raw.detail, false),
+                        XI_RAW_MOTION => {
+                            let mask = slice::from_raw_parts(
+                                raw.valuators.mask,
+                                raw.valuators.mask_len as usize,
+                            );
+                            let values = slice::from_raw_parts(
+                                raw.raw_values,
+                                mask.iter().map(|m| m.count_ones() as usize).sum(),
+                            );
+                            to_motion_event(mask, values)
+                        }
+                        _ => None,
+                    };
+                    let source = raw.sourceid;
+                    XFreeEventData(display, cookie);
+
+                    let mouse_event = match mouse_event {
+                        Some(mouse_event) => mouse_event,
+                        None => continue,
+                    };
+                    let device_name = names
+                        .entry(source)
+                        .or_insert_with(|| device_name(display, source))
+                        .clone();
+                    // The X server uses its own clock for the event times,
+                    // so stamp the events with the time they are received
+                    let event = MouseEventExt {
+                        device_id: format!("xinput2:{}", source),
+                        device_name,
+                        event: mouse_event,
+                        time: SystemTime::now(),
+                    };
+
+                    super::dispatch(&mut dispatcher, &mut counter, &event);
+                }
+            }
+        }
+    });
+
+    Ok(listener)
+}
+
+/// Construct the library's MouseEvent from the given X11 button number
+///
+/// The buttons 4 to 7 are the scroll wheels, and X11 reports a press
+/// and a release for each of their detents, so only the presses are used
+fn to_button_event(button: c_int, press: bool) -> Option<MouseEvent> {
+    let scroll = |direction| {
+        if press {
+            Some(MouseEvent::Scroll(direction))
+        } else {
+            None
+        }
+    };
+    let button = match button {
+        1 => MouseButton::Left,
+        2 => MouseButton::Middle,
+        3 => MouseButton::Right,
+        4 => return scroll(ScrollDirection::Up),
+        5 => return scroll(ScrollDirection::Down),
+        6 => return scroll(ScrollDirection::Left),
+        7 => return scroll(ScrollDirection::Right),
+        8 => MouseButton::Back,
+        9 => MouseButton::Forward,
+        _ => return None,
+    };
+    if press {
+        Some(MouseEvent::Press(button))
+    } else {
+        Some(MouseEvent::Release(button))
+    }
+}
+
+/// Construct the relative move from the given valuators of a raw motion event
+///
+/// The values are only reported for the valuators that are set in the mask,
+/// the first two valuators are the horizontal and the vertical movement
+fn to_motion_event(mask: &[c_uchar], values: &[c_double]) -> Option<MouseEvent> {
+    let is_set = |valuator: usize| {
+        mask.get(valuator / 8)
+            .is_some_and(|m| m & (1 << (valuator % 8)) != 0)
+    };
+    // The index of a value is the number of the set valuators before it
+    let value = |valuator: usize| {
+        if is_set(valuator) {
+            let index = (0..valuator).filter(|v| is_set(*v)).count();
+            values.get(index).copied().unwrap_or(0.)
+        } else {
+            0.
+        }
+    };
+
+    let (x, y) = (value(0).round() as i32, value(1).round() as i32);
+    if x == 0 && y == 0 {
+        // e.g. the smooth scrolling valuators of the touchpads
+        return None;
+    }
+    Some(MouseEvent::RelativeMove(x, y))
+}
+
+/// Get the name of the given XInput device
+unsafe fn device_name(display: *mut Display, deviceid: c_int) -> String {
+    let mut count = 0;
+    let info = XIQueryDevice(display, deviceid, &mut count);
+    if info.is_null() {
+        return String::new();
+    }
+    let name = if count > 0 && !(*info).name.is_null() {
+        CStr::from_ptr((*info).name).to_string_lossy().into_owned()
+    } else {
+        String::new()
+    };
+    XIFreeDeviceInfo(info);
+    name
+}
+
+/// Owns the connection of the listener, so that it can be moved into the listener thread
+struct Connection(*mut Display);
+
+unsafe impl Send for Connection {}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            XCloseDisplay(self.0);
+        }
+    }
+}
+
+/// Xlib and XInput2 definitions
+const SUCCESS: c_int = 0;
+const GENERIC_EVENT: c_int = 35;
+const XI_ALL_MASTER_DEVICES: c_int = 1;
+const XI_RAW_BUTTON_PRESS: c_int = 15;
+const XI_RAW_BUTTON_RELEASE: c_int = 16;
+const XI_RAW_MOTION: c_int = 17;
+/// The length of the event mask that covers the selected events
+const XI_MASK_LEN: usize = (XI_RAW_MOTION as usize >> 3) + 1;
+
+/// Xlib and XInput2 types
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct XGenericEventCookie {
+    r#type: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    extension: c_int,
+    evtype: c_int,
+    cookie: c_uint,
+    data: *mut c_void,
+}
+
+#[repr(C)]
+union XEvent {
+    cookie: XGenericEventCookie,
+    pad: [c_long; 24],
+}
+
+#[repr(C)]
+struct XIEventMask {
+    deviceid: c_int,
+    mask_len: c_int,
+    mask: *mut c_uchar,
+}
+
+#[repr(C)]
+struct XIValuatorState {
+    mask_len: c_int,
+    mask: *mut c_uchar,
+    values: *mut c_double,
+}
+
+#[repr(C)]
+struct XIRawEvent {
+    r#type: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    extension: c_int,
+    evtype: c_int,
+    time: c_ulong,
+    deviceid: c_int,
+    sourceid: c_int,
+    detail: c_int,
+    flags: c_int,
+    valuators: XIValuatorState,
+    raw_values: *mut c_double,
+}
+
+#[repr(C)]
+struct XIDeviceInfo {
+    deviceid: c_int,
+    name: *mut c_char,
+    r#use: c_int,
+    attachment: c_int,
+    enabled: c_int,
+    num_classes: c_int,
+    classes: *mut *mut c_void,
+}
+
+#[link(name = "X11")]
+extern "C" {
+    fn XOpenDisplay(display: *const c_char) -> *mut Display;
+    fn XCloseDisplay(display: *mut Display) -> c_int;
+    fn XDefaultRootWindow(display: *mut Display) -> c_ulong;
+    fn XConnectionNumber(display: *mut Display) -> c_int;
+    fn XFlush(display: *mut Display) -> c_int;
+    fn XGetEventData(display: *mut Display, cookie: *mut XGenericEventCookie) -> c_int;
+    fn XFreeEventData(display: *mut Display, cookie: *mut XGenericEventCookie);
+    fn XQueryExtension(
+        display: *mut Display,
+        name: *const c_char,
+        major_opcode: *mut c_int,
+        first_event: *mut c_int,
+        first_error: *mut c_int,
+    ) -> c_int;
+}
+
+#[link(name = "Xi")]
+extern "C" {
+    fn XIQueryVersion(display: *mut Display, major: *mut c_int, minor: *mut c_int) -> c_int;
+    fn XISelectEvents(
+        display: *mut Display,
+        window: c_ulong,
+        masks: *mut XIEventMask,
+        num_masks: c_int,
+    ) -> c_int;
+    fn XIQueryDevice(
+        display: *mut Display,
+        deviceid: c_int,
+        ndevices: *mut c_int,
+    ) -> *mut XIDeviceInfo;
+    fn XIFreeDeviceInfo(info: *mut XIDeviceInfo);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_events() {
+        assert_eq!(
+            to_button_event(1, true),
+            Some(MouseEvent::Press(MouseButton::Left))
+        );
+        assert_eq!(
+            to_button_event(9, false),
+            Some(MouseEvent::Release(MouseButton::Forward))
+        );
+        assert_eq!(
+            to_button_event(5, true),
+            Some(MouseEvent::Scroll(ScrollDirection::Down))
+        );
+        // Each detent is reported once
+        assert_eq!(to_button_event(5, false), None);
+        assert_eq!(to_button_event(12, true), None);
+    }
+
+    #[test]
+    fn motion_events() {
+        // Both axes are set
+        assert_eq!(
+            to_motion_event(&[0b11], &[2.4, -3.]),
+            Some(MouseEvent::RelativeMove(2, -3))
+        );
+        // Only the vertical axis is set, so its value comes first
+        assert_eq!(
+            to_motion_event(&[0b10], &[5.]),
+            Some(MouseEvent::RelativeMove(0, 5))
+        );
+        // Only a scroll valuator is set
+        assert_eq!(to_motion_event(&[0b1000], &[15.]), None);
+    }
+}
@@ -0,0 +1,114 @@
+///
+/// This module contains the monitor layout of X11 through the
+/// monitors of RandR 1.5, which also covers XWayland
+///
+use crate::display::Monitor;
+use crate::nix::x11::Display;
+use std::{
+    ffi::CStr,
+    os::raw::{c_char, c_int, c_ulong, c_void},
+    ptr::null,
+    slice,
+};
+
+/// Get the monitors of the X server, None if it can not be reached
+/// or it does not support RandR 1.5
+pub fn monitors(scale: f64) -> Option<Vec<Monitor>> {
+    unsafe {
+        let display = XOpenDisplay(null());
+        if display.is_null() {
+            return None;
+        }
+
+        let (mut event, mut error) = (0, 0);
+        let (mut major, mut minor) = (0, 0);
+        let supported = XRRQueryExtension(display, &mut event, &mut error) != 0
+            && XRRQueryVersion(display, &mut major, &mut minor) != 0
+            && (major, minor) >= (1, 5);
+        if !supported {
+            XCloseDisplay(display);
+            return None;
+        }
+
+        let mut count = 0;
+        let infos = XRRGetMonitors(display, XDefaultRootWindow(display), 1, &mut count);
+        let monitors = if infos.is_null() {
+            None
+        } else {
+            let monitors = slice::from_raw_parts(infos, count.max(0) as usize)
+                .iter()
+                .enumerate()
+                .map(|(id, info)| Monitor {
+                    id,
+                    name: atom_name(display, info.name),
+                    x: info.x,
+                    y: info.y,
+                    width: info.width,
+                    height: info.height,
+                    // X11 scales the whole desktop at once
+                    scale,
+                    primary: info.primary != 0,
+                })
+                .collect();
+            XRRFreeMonitors(infos);
+            Some(monitors)
+        };
+        XCloseDisplay(display);
+        monitors
+    }
+}
+
+/// Get the name of the given atom, e.g. the names of the outputs like `DP-1`
+unsafe fn atom_name(display: *mut Display, atom: Atom) -> String {
+    let name = XGetAtomName(display, atom);
+    if name.is_null() {
+        return String::new();
+    }
+    let owned = CStr::from_ptr(name).to_string_lossy().into_owned();
+    XFree(name as *mut c_void);
+    owned
+}
+
+/// Xlib and RandR types
+type Atom = c_ulong;
+
+#[repr(C)]
+struct XRRMonitorInfo {
+    name: Atom,
+    primary: c_int,
+    automatic: c_int,
+    noutput: c_int,
+    x: c_int,
+    y: c_int,
+    width: c_int,
+    height: c_int,
+    mwidth: c_int,
+    mheight: c_int,
+    outputs: *mut c_ulong,
+}
+
+#[link(name = "X11")]
+extern "C" {
+    fn XOpenDisplay(display: *const c_char) -> *mut Display;
+    fn XCloseDisplay(display: *mut Display) -> c_int;
+    fn XDefaultRootWindow(display: *mut Display) -> c_ulong;
+    fn XGetAtomName(display: *mut Display, atom: Atom) -> *mut c_char;
+    fn XFree(data: *mut c_void) -> c_int;
+}
+
+#[link(name = "Xrandr")]
+extern "C" {
+    fn XRRQueryExtension(
+        display: *mut Display,
+        event_base: *mut c_int,
+        error_base: *mut c_int,
+    ) -> c_int;
+    fn XRRQueryVersion(display: *mut Display, major: *mut c_int, minor: *mut c_int) -> c_int;
+    fn XRRGetMonitors(
+        display: *mut Display,
+        window: c_ulong,
+        get_active: c_int,
+        nmonitors: *mut c_int,
+    ) -> *mut XRRMonitorInfo;
+    fn XRRFreeMonitors(monitors: *mut XRRMonitorInfo);
+}
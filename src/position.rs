@@ -0,0 +1,160 @@
+///
+/// This module contains a watcher of the cursor position that polls it
+/// on a background thread, a portable way to notice the moves of the mouse
+/// where the events can not be listened, e.g. Wayland without the portals
+///
+use crate::common::MouseActions;
+use crate::error::Error;
+use std::{
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Polls the cursor position until it is dropped
+pub struct PositionWatcher {
+    /// Dropping the sender wakes up and stops the polling thread
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for PositionWatcher {
+    fn drop(&mut self) {
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Poll the cursor position in the given interval, and invoke the callback
+/// with the new position whenever it changes
+///
+/// The position is read through X11 on the unix-like systems, uinput does not
+/// report it. The polling stops once the returned watcher is dropped
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::position;
+/// use std::time::Duration;
+///
+/// let watcher = position::watch(
+///     Duration::from_millis(16),
+///     Box::new(|x, y| println!("The mouse is moved to {}, {}", x, y)),
+/// )
+/// .unwrap();
+/// // Stop watching
+/// drop(watcher);
+/// ```
+pub fn watch(
+    interval: Duration,
+    callback: Box<dyn Fn(i32, i32) + Send>,
+) -> Result<PositionWatcher, Error> {
+    Ok(watch_with(position_source()?, interval, callback))
+}
+
+/// Poll the cursor position that the given manager reports, see `watch`
+pub fn watch_with(
+    manager: Box<dyn MouseActions + Send>,
+    interval: Duration,
+    callback: Box<dyn Fn(i32, i32) + Send>,
+) -> PositionWatcher {
+    // The first position is only the starting point
+    let mut last = None;
+    poll_with(manager, interval, move |position| {
+        if last.is_some() && last != Some(position) {
+            callback(position.0, position.1);
+        }
+        last = Some(position);
+    })
+}
+
+/// Poll the cursor position that the given manager reports right away and then
+/// in the given interval, and invoke the callback with every position that is read
+pub(crate) fn poll_with(
+    manager: Box<dyn MouseActions + Send>,
+    interval: Duration,
+    mut callback: impl FnMut((i32, i32)) + Send + 'static,
+) -> PositionWatcher {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let thread = thread::spawn(move || loop {
+        // e.g. the pointer is on another screen of X11
+        if let Ok(position) = manager.get_position() {
+            callback(position);
+        }
+        if !matches!(
+            stopped.recv_timeout(interval),
+            Err(RecvTimeoutError::Timeout)
+        ) {
+            break;
+        }
+    });
+
+    PositionWatcher {
+        stop: Some(stop),
+        thread: Some(thread),
+    }
+}
+
+/// Get a manager that reports the position of the cursor
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub(crate) fn position_source() -> Result<Box<dyn MouseActions + Send>, Error> {
+    Ok(Box::new(crate::nix::X11MouseManager::try_new()?))
+}
+
+/// Android does not report the position of the pointer to the other processes
+#[cfg(target_os = "android")]
+pub(crate) fn position_source() -> Result<Box<dyn MouseActions + Send>, Error> {
+    Err(Error::NotImplemented)
+}
+
+#[cfg(target_vendor = "apple")]
+pub(crate) fn position_source() -> Result<Box<dyn MouseActions + Send>, Error> {
+    crate::darwin::DarwinMouseManager::new()
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn position_source() -> Result<Box<dyn MouseActions + Send>, Error> {
+    crate::windows::WindowsMouseManager::new()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn position_source() -> Result<Box<dyn MouseActions + Send>, Error> {
+    crate::wasm::WasmMouseManager::new()
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::common::MouseController;
+    use crate::mock::MockMouseManager;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn watch_moves() {
+        let mock = MockMouseManager::new();
+        let mut mouse = crate::SharedMouse::new(Box::new(mock));
+        let positions = Arc::new(Mutex::new(Vec::new()));
+
+        let received = positions.clone();
+        let watcher = watch_with(
+            Box::new(mouse.clone_handle()),
+            Duration::from_millis(1),
+            Box::new(move |x, y| received.lock().unwrap().push((x, y))),
+        );
+        thread::sleep(Duration::from_millis(20));
+        mouse.move_to(10, 20).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        drop(watcher);
+
+        // The unchanged positions are not reported
+        assert_eq!(*positions.lock().unwrap(), vec![(10, 20)]);
+    }
+}
@@ -0,0 +1,849 @@
+///
+/// This module contains the recording and replaying of the mouse events
+///
+/// The recorder attaches a callback with `hook` and stores every received
+/// event with the time it is received, relative to the start of the recording
+///
+use crate::cancel::CancellationToken;
+use crate::common::{
+    CallbackId, MouseButton, MouseController, MouseEvent, MouseListener, ScrollDirection,
+    ScrollPhase,
+};
+use crate::error::Error;
+use crate::evemu;
+use crate::hooks::LockUnpoisoned;
+use crate::json;
+use crate::scheduler::{self, SPIN_THRESHOLD};
+use std::{
+    io::Read,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// A mouse event with the time it happened, relative to the start of the recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedEvent {
+    pub time: Duration,
+    pub event: MouseEvent,
+}
+
+/// Records the mouse events until it is stopped
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::Mouse;
+/// use mouce::recorder::Recorder;
+/// use std::{thread, time::Duration};
+///
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// let recorder = Recorder::start(manager.as_mut()).unwrap();
+/// thread::sleep(Duration::from_secs(5));
+/// let recording = recorder.stop(manager.as_mut()).unwrap();
+///
+/// // Play the recorded events back twice as fast
+/// recording.replay(manager.as_mut(), 2.).unwrap();
+/// ```
+pub struct Recorder {
+    events: Arc<Mutex<Vec<RecordedEvent>>>,
+    callback_id: CallbackId,
+}
+
+impl Recorder {
+    /// Start recording the mouse events that the given manager listens to
+    pub fn start<M: MouseListener + ?Sized>(manager: &mut M) -> Result<Self, Error> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let start = Instant::now();
+
+        let recorded = events.clone();
+        let callback_id = manager.hook(Box::new(move |event| {
+            recorded.lock_unpoisoned().push(RecordedEvent {
+                time: start.elapsed(),
+                event: event.clone(),
+            });
+        }))?;
+
+        Ok(Recorder {
+            events,
+            callback_id,
+        })
+    }
+
+    /// Stop recording and get the recorded events
+    pub fn stop<M: MouseListener + ?Sized>(self, manager: &mut M) -> Result<Recording, Error> {
+        manager.unhook(self.callback_id)?;
+        let events = self.events.lock_unpoisoned().drain(..).collect();
+        Ok(Recording { events })
+    }
+}
+
+/// The options of `Recording::replay_with`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayOptions {
+    /// Multiplies the playback rate, e.g. 2.0 replays the events twice as fast
+    pub speed: f32,
+    /// How many times the events are replayed, `None` loops until the replay is stopped
+    pub loop_count: Option<u32>,
+    /// The time of the recording that the replay starts at, the earlier events are skipped
+    pub start_offset: Duration,
+    /// The time of the recording that the replay ends at, the later events are skipped.
+    /// `None` ends at the last event
+    pub end_offset: Option<Duration>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            speed: 1.,
+            loop_count: Some(1),
+            start_offset: Duration::ZERO,
+            end_offset: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ReplayState {
+    paused: bool,
+    stopped: bool,
+    /// The time of the recording to continue from, taken by the replay
+    seek: Option<Duration>,
+    /// The time of the recording that the replay reached
+    position: Duration,
+}
+
+#[derive(Default)]
+struct SharedReplay {
+    replay: Mutex<ReplayState>,
+    /// Notified whenever the replay is controlled, to wake up the waiting replay
+    changed: Condvar,
+}
+
+impl SharedReplay {
+    fn control(&self, change: impl FnOnce(&mut ReplayState)) {
+        change(&mut self.replay.lock_unpoisoned());
+        self.changed.notify_all();
+    }
+
+    fn wait<'a>(
+        &self,
+        state: MutexGuard<'a, ReplayState>,
+        timeout: Option<Duration>,
+    ) -> MutexGuard<'a, ReplayState> {
+        match timeout {
+            Some(timeout) => {
+                self.changed
+                    .wait_timeout(state, timeout)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0
+            }
+            None => self
+                .changed
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        }
+    }
+}
+
+/// Pauses, resumes, seeks or stops the replay that it is passed to, e.g. from
+/// the playback controls of a GUI. The clones of the handle share the same state
+#[derive(Clone, Default)]
+pub struct ReplayHandle {
+    state: Arc<SharedReplay>,
+}
+
+impl ReplayHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pause the replay before its next event, until it is resumed
+    pub fn pause(&self) {
+        self.state.control(|state| state.paused = true);
+    }
+
+    pub fn resume(&self) {
+        self.state.control(|state| state.paused = false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.replay.lock_unpoisoned().paused
+    }
+
+    /// Continue the replay from the given time of the recording, which is kept
+    /// within the start and the end offsets of the replay
+    pub fn seek(&self, position: Duration) {
+        self.state.control(|state| state.seek = Some(position));
+    }
+
+    /// Stop the replay, it fails with `Error::Cancelled`. The replays that are
+    /// started with the handle afterwards are stopped right away
+    pub fn stop(&self) {
+        self.state.control(|state| state.stopped = true);
+    }
+
+    /// The time of the recording that the replay reached, within the current loop
+    pub fn position(&self) -> Duration {
+        self.state.replay.lock_unpoisoned().position
+    }
+}
+
+/// A sequence of recorded mouse events that can be stored and replayed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    /// Simulate the recorded events with the given manager, keeping
+    /// the original timing. The `speed` multiplies the playback rate,
+    /// e.g. 2.0 replays the events twice as fast
+    pub fn replay<M: MouseController + ?Sized>(
+        &self,
+        manager: &mut M,
+        speed: f64,
+    ) -> Result<(), Error> {
+        self.replay_cancellable(manager, speed, &CancellationToken::new())
+    }
+
+    /// Replay the recorded events like `replay`, but stop once the given token is
+    /// cancelled and fail with `Error::Cancelled`. The buttons that the replay
+    /// pressed and did not release yet are released when it stops early
+    pub fn replay_cancellable<M: MouseController + ?Sized>(
+        &self,
+        manager: &mut M,
+        speed: f64,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        if speed <= 0. || !speed.is_finite() {
+            return Err(Error::CustomError(
+                "the replay speed must be a positive number".to_string(),
+            ));
+        }
+
+        let start = Instant::now();
+        let mut held: Vec<MouseButton> = Vec::new();
+        for recorded in self.events.iter() {
+            // Spin for the end of the wait like the scheduler, to keep the timing exact
+            let waited = scheduler::wait_until(start + recorded.time.div_f64(speed), token);
+            if let Err(err) = waited.and_then(|_| simulate(manager, &recorded.event)) {
+                release_held(manager, &mut held);
+                return Err(err);
+            }
+            track_held(&mut held, &recorded.event);
+        }
+        Ok(())
+    }
+
+    /// Replay the recorded events with the given options, e.g. a part of the
+    /// recording in a loop, while the given handle pauses, seeks or stops it
+    /// from another thread
+    ///
+    /// Fails with `Error::Cancelled` once the handle is stopped. The buttons that
+    /// the replay pressed and did not release yet are released when it stops, when
+    /// it seeks and at the end of every loop, but they are kept held while it is paused.
+    /// The releases of the buttons that the replay did not press are skipped
+    ///
+    /// A replay that loops forever fails at once if its start and end are at the same time
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::recorder::{Recording, ReplayHandle, ReplayOptions};
+    /// use mouce::Mouse;
+    /// use std::{fs, thread, time::Duration};
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let recording = Recording::from_json(&fs::read_to_string("recording.json").unwrap()).unwrap();
+    /// let handle = ReplayHandle::new();
+    /// let controls = handle.clone();
+    /// thread::spawn(move || {
+    ///     thread::sleep(Duration::from_secs(1));
+    ///     controls.pause();
+    ///     controls.seek(Duration::from_secs(3));
+    ///     controls.resume();
+    /// });
+    ///
+    /// // Replay the first 10 seconds three times, at half speed
+    /// let options = ReplayOptions {
+    ///     speed: 0.5,
+    ///     loop_count: Some(3),
+    ///     end_offset: Some(Duration::from_secs(10)),
+    ///     ..ReplayOptions::default()
+    /// };
+    /// recording.replay_with(manager.as_mut(), &options, &handle).unwrap();
+    /// ```
+    pub fn replay_with<M: MouseController + ?Sized>(
+        &self,
+        manager: &mut M,
+        options: &ReplayOptions,
+        handle: &ReplayHandle,
+    ) -> Result<(), Error> {
+        let speed = options.speed as f64;
+        if speed <= 0. || !speed.is_finite() {
+            return Err(Error::CustomError(
+                "the replay speed must be a positive number".to_string(),
+            ));
+        }
+        if options
+            .end_offset
+            .is_some_and(|end| end < options.start_offset)
+        {
+            return Err(Error::CustomError(
+                "the replay must not end before its start".to_string(),
+            ));
+        }
+
+        let events: Vec<&RecordedEvent> = self
+            .events
+            .iter()
+            .filter(|recorded| {
+                recorded.time >= options.start_offset
+                    && options.end_offset.is_none_or(|end| recorded.time <= end)
+            })
+            .collect();
+        // Nothing to wait for, looping forever would only spin
+        let Some(last) = events.last() else {
+            return Ok(());
+        };
+        let start = options.start_offset;
+        let end = options.end_offset.unwrap_or(last.time);
+        // A lap that takes no time would replay its events forever without a pause
+        if options.loop_count.is_none() && end <= start {
+            return Err(Error::CustomError(
+                "the replay must last a while to loop forever".to_string(),
+            ));
+        }
+        let first =
+            |position: Duration| events.partition_point(|recorded| recorded.time < position);
+
+        let mut held: Vec<MouseButton> = Vec::new();
+        let mut laps = 0;
+        // The time of the recording that is reached, and the instant it is reached at
+        let mut position = start;
+        let mut clock = Instant::now();
+        let mut playing = false;
+        let mut index = 0;
+        let mut state = handle.state.replay.lock_unpoisoned();
+        while options.loop_count.is_none_or(|count| laps < count) {
+            let now = Instant::now();
+            if playing {
+                position += (now - clock).mul_f64(speed);
+            }
+            clock = now;
+            if state.stopped {
+                drop(state);
+                release_held(manager, &mut held);
+                return Err(Error::Cancelled);
+            }
+            if let Some(target) = state.seek.take() {
+                position = target.clamp(start, end);
+                index = first(position);
+                drop(state);
+                release_held(manager, &mut held);
+                state = handle.state.replay.lock_unpoisoned();
+                continue;
+            }
+            state.position = position.min(end);
+            playing = !state.paused;
+            if state.paused {
+                state = handle.state.wait(state, None);
+                continue;
+            }
+
+            let target = events.get(index).map_or(end, |recorded| recorded.time);
+            if target > position {
+                // Sleep until shortly before the event and spin for the rest like the
+                // scheduler, the controls are checked again after the spin
+                let timeout = (target - position).div_f64(speed);
+                if timeout > SPIN_THRESHOLD {
+                    state = handle.state.wait(state, Some(timeout - SPIN_THRESHOLD));
+                } else {
+                    drop(state);
+                    scheduler::spin_until(clock + timeout);
+                    state = handle.state.replay.lock_unpoisoned();
+                }
+                continue;
+            }
+            match events.get(index) {
+                // The press of the button is before the start or skipped by a seek
+                Some(RecordedEvent {
+                    event: MouseEvent::Release(button),
+                    ..
+                }) if !held.contains(button) => index += 1,
+                Some(recorded) => {
+                    drop(state);
+                    if let Err(err) = simulate(manager, &recorded.event) {
+                        release_held(manager, &mut held);
+                        return Err(err);
+                    }
+                    track_held(&mut held, &recorded.event);
+                    index += 1;
+                    state = handle.state.replay.lock_unpoisoned();
+                }
+                None => {
+                    drop(state);
+                    release_held(manager, &mut held);
+                    laps += 1;
+                    position = start;
+                    index = 0;
+                    state = handle.state.replay.lock_unpoisoned();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize the recording to JSON
+    pub fn to_json(&self) -> String {
+        let events: Vec<String> = self
+            .events
+            .iter()
+            .map(|recorded| {
+                format!(
+                    "{{\"time\":{},{}}}",
+                    recorded.time.as_micros(),
+                    json::event_fields(&recorded.event)
+                )
+            })
+            .collect();
+        format!("{{\"events\":[{}]}}", events.join(","))
+    }
+
+    /// Deserialize a recording from the JSON written by `to_json`
+    pub fn from_json(input: &str) -> Result<Self, Error> {
+        let invalid =
+            |message: String| Error::CustomError(format!("invalid recording: {}", message));
+
+        let value = json::parse(input).map_err(invalid)?;
+        let events = value
+            .get("events")
+            .and_then(|events| events.as_array())
+            .ok_or_else(|| invalid("missing `events` array".to_string()))?;
+
+        let mut recording = Recording::default();
+        for event in events {
+            let time = event
+                .get("time")
+                .and_then(|time| time.as_f64())
+                .ok_or_else(|| invalid("missing `time` of the event".to_string()))?;
+            recording.events.push(RecordedEvent {
+                time: Duration::from_micros(time as u64),
+                event: json::event_from_object(event).map_err(invalid)?,
+            });
+        }
+        Ok(recording)
+    }
+
+    /// Read a recording from the text that `evemu-record` captures of a mouse,
+    /// the times are relative to the first event of the capture
+    ///
+    /// The buttons, the relative moves and the scrolls are read, the other
+    /// events of the device are skipped
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::recorder::Recording;
+    /// use mouce::Mouse;
+    /// use std::fs::File;
+    ///
+    /// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// let recording = Recording::from_evemu(File::open("mouse.evemu").unwrap()).unwrap();
+    /// recording.replay(manager.as_mut(), 1.).unwrap();
+    /// ```
+    pub fn from_evemu<R: Read>(reader: R) -> Result<Self, Error> {
+        evemu::read(reader)
+    }
+
+    /// Serialize the recording to the text format of `evemu-record`, as the capture
+    /// of a virtual mouse that `evemu-device` and `evemu-play` can replay
+    pub fn to_evemu(&self) -> String {
+        evemu::write(self)
+    }
+
+    /// Serialize the recording to a compact binary format
+    ///
+    /// Every event is stored as its time in microseconds (u64), a tag
+    /// byte for the event type and its payload, all in little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for recorded in self.events.iter() {
+            bytes.extend_from_slice(&(recorded.time.as_micros() as u64).to_le_bytes());
+            match &recorded.event {
+                MouseEvent::RelativeMove(x, y) => {
+                    bytes.push(TAG_RELATIVE_MOVE);
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                    bytes.extend_from_slice(&y.to_le_bytes());
+                }
+                MouseEvent::AbsoluteMove(x, y) => {
+                    bytes.push(TAG_ABSOLUTE_MOVE);
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                    bytes.extend_from_slice(&y.to_le_bytes());
+                }
+                MouseEvent::Press(button) => {
+                    bytes.push(TAG_PRESS);
+                    bytes.push(BUTTONS.iter().position(|b| b == button).unwrap() as u8);
+                }
+                MouseEvent::Release(button) => {
+                    bytes.push(TAG_RELEASE);
+                    bytes.push(BUTTONS.iter().position(|b| b == button).unwrap() as u8);
+                }
+                MouseEvent::Scroll(direction) => {
+                    bytes.push(TAG_SCROLL);
+                    bytes.push(DIRECTIONS.iter().position(|d| d == direction).unwrap() as u8);
+                }
+                MouseEvent::ScrollHiRes { dx, dy } => {
+                    bytes.push(TAG_SCROLL_HI_RES);
+                    bytes.extend_from_slice(&dx.to_le_bytes());
+                    bytes.extend_from_slice(&dy.to_le_bytes());
+                }
+                MouseEvent::Scroll2D { dx, dy } => {
+                    bytes.push(TAG_SCROLL_2D);
+                    bytes.extend_from_slice(&dx.to_le_bytes());
+                    bytes.extend_from_slice(&dy.to_le_bytes());
+                }
+                MouseEvent::ScrollPhase(phase) => {
+                    bytes.push(TAG_SCROLL_PHASE);
+                    bytes.push(PHASES.iter().position(|p| p == phase).unwrap() as u8);
+                }
+                MouseEvent::DeviceDisconnected(device_id) => {
+                    bytes.push(TAG_DEVICE_DISCONNECTED);
+                    bytes.extend_from_slice(&(device_id.len() as u32).to_le_bytes());
+                    bytes.extend_from_slice(device_id.as_bytes());
+                }
+                MouseEvent::Click(button)
+                | MouseEvent::DoubleClick(button)
+                | MouseEvent::TripleClick(button) => {
+                    bytes.push(match recorded.event {
+                        MouseEvent::Click(_) => TAG_CLICK,
+                        MouseEvent::DoubleClick(_) => TAG_DOUBLE_CLICK,
+                        _ => TAG_TRIPLE_CLICK,
+                    });
+                    bytes.push(BUTTONS.iter().position(|b| b == button).unwrap() as u8);
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Deserialize a recording from the bytes written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::CustomError("invalid recording: unexpected bytes".to_string());
+        let mut rest = bytes;
+
+        let mut recording = Recording::default();
+        while !rest.is_empty() {
+            let time =
+                u64::from_le_bytes(take(&mut rest, 8).ok_or_else(invalid)?.try_into().unwrap());
+            let tag = take(&mut rest, 1).ok_or_else(invalid)?[0];
+            let event = match tag {
+                TAG_RELATIVE_MOVE | TAG_ABSOLUTE_MOVE | TAG_SCROLL_HI_RES | TAG_SCROLL_2D => {
+                    let x = i32::from_le_bytes(
+                        take(&mut rest, 4).ok_or_else(invalid)?.try_into().unwrap(),
+                    );
+                    let y = i32::from_le_bytes(
+                        take(&mut rest, 4).ok_or_else(invalid)?.try_into().unwrap(),
+                    );
+                    match tag {
+                        TAG_RELATIVE_MOVE => MouseEvent::RelativeMove(x, y),
+                        TAG_ABSOLUTE_MOVE => MouseEvent::AbsoluteMove(x, y),
+                        TAG_SCROLL_HI_RES => MouseEvent::ScrollHiRes { dx: x, dy: y },
+                        _ => MouseEvent::Scroll2D { dx: x, dy: y },
+                    }
+                }
+                TAG_PRESS | TAG_RELEASE | TAG_CLICK | TAG_DOUBLE_CLICK | TAG_TRIPLE_CLICK => {
+                    let button = BUTTONS
+                        .get(take(&mut rest, 1).ok_or_else(invalid)?[0] as usize)
+                        .ok_or_else(invalid)?
+                        .clone();
+                    match tag {
+                        TAG_PRESS => MouseEvent::Press(button),
+                        TAG_RELEASE => MouseEvent::Release(button),
+                        TAG_CLICK => MouseEvent::Click(button),
+                        TAG_DOUBLE_CLICK => MouseEvent::DoubleClick(button),
+                        _ => MouseEvent::TripleClick(button),
+                    }
+                }
+                TAG_SCROLL => {
+                    let direction = DIRECTIONS
+                        .get(take(&mut rest, 1).ok_or_else(invalid)?[0] as usize)
+                        .ok_or_else(invalid)?;
+                    MouseEvent::Scroll(direction.clone())
+                }
+                TAG_SCROLL_PHASE => {
+                    let phase = PHASES
+                        .get(take(&mut rest, 1).ok_or_else(invalid)?[0] as usize)
+                        .ok_or_else(invalid)?;
+                    MouseEvent::ScrollPhase(*phase)
+                }
+                TAG_DEVICE_DISCONNECTED => {
+                    let len = u32::from_le_bytes(
+                        take(&mut rest, 4).ok_or_else(invalid)?.try_into().unwrap(),
+                    );
+                    let device_id = take(&mut rest, len as usize).ok_or_else(invalid)?;
+                    MouseEvent::DeviceDisconnected(
+                        String::from_utf8(device_id.to_vec()).map_err(|_| invalid())?,
+                    )
+                }
+                _ => return Err(invalid()),
+            };
+            recording.events.push(RecordedEvent {
+                time: Duration::from_micros(time),
+                event,
+            });
+        }
+        Ok(recording)
+    }
+}
+
+/// Keep track of the buttons that the replayed event presses and releases
+fn track_held(held: &mut Vec<MouseButton>, event: &MouseEvent) {
+    match event {
+        MouseEvent::Press(button) if !held.contains(button) => held.push(button.clone()),
+        MouseEvent::Release(button) => held.retain(|held| held != button),
+        _ => {}
+    }
+}
+
+/// Release the buttons that the replay left pressed
+fn release_held<M: MouseController + ?Sized>(manager: &mut M, held: &mut Vec<MouseButton>) {
+    for button in held.drain(..) {
+        let _ = manager.release_button(&button);
+    }
+}
+
+/// Split the given number of bytes from the front of the slice
+fn take<'a>(bytes: &mut &'a [u8], count: usize) -> Option<&'a [u8]> {
+    if bytes.len() < count {
+        return None;
+    }
+    let (taken, rest) = bytes.split_at(count);
+    *bytes = rest;
+    Some(taken)
+}
+
+/// Simulate the given event with the given manager
+pub(crate) fn simulate<M: MouseController + ?Sized>(
+    manager: &mut M,
+    event: &MouseEvent,
+) -> Result<(), Error> {
+    match event {
+        MouseEvent::RelativeMove(x, y) => manager.move_relative(*x, *y),
+        MouseEvent::AbsoluteMove(x, y) => {
+            manager.move_to((*x).max(0) as usize, (*y).max(0) as usize)
+        }
+        MouseEvent::Press(button) => manager.press_button(button),
+        MouseEvent::Release(button) => manager.release_button(button),
+        MouseEvent::Scroll(direction) => manager.scroll_wheel(direction),
+        // The high resolution and the combined scrolls and the phases are recorded
+        // along with the `Scroll` events, simulating them would scroll twice
+        MouseEvent::ScrollHiRes { .. }
+        | MouseEvent::Scroll2D { .. }
+        | MouseEvent::ScrollPhase(_) => Ok(()),
+        // The devices can not be simulated
+        MouseEvent::DeviceDisconnected(_) => Ok(()),
+        // The clicks are synthesized from the presses and the releases that are
+        // recorded along with them, simulating them would click twice
+        MouseEvent::Click(_) | MouseEvent::DoubleClick(_) | MouseEvent::TripleClick(_) => Ok(()),
+    }
+}
+
+/// Binary format definitions
+const TAG_RELATIVE_MOVE: u8 = 0;
+const TAG_ABSOLUTE_MOVE: u8 = 1;
+const TAG_PRESS: u8 = 2;
+const TAG_RELEASE: u8 = 3;
+const TAG_SCROLL: u8 = 4;
+const TAG_SCROLL_HI_RES: u8 = 5;
+const TAG_SCROLL_PHASE: u8 = 6;
+const TAG_SCROLL_2D: u8 = 7;
+const TAG_DEVICE_DISCONNECTED: u8 = 8;
+const TAG_CLICK: u8 = 9;
+const TAG_DOUBLE_CLICK: u8 = 10;
+const TAG_TRIPLE_CLICK: u8 = 11;
+const BUTTONS: [MouseButton; 8] = [
+    MouseButton::Left,
+    MouseButton::Middle,
+    MouseButton::Side,
+    MouseButton::Extra,
+    MouseButton::Right,
+    MouseButton::Back,
+    MouseButton::Forward,
+    MouseButton::Task,
+];
+const PHASES: [ScrollPhase; 8] = [
+    ScrollPhase::MayBegin,
+    ScrollPhase::Began,
+    ScrollPhase::Changed,
+    ScrollPhase::Ended,
+    ScrollPhase::Cancelled,
+    ScrollPhase::MomentumBegan,
+    ScrollPhase::MomentumChanged,
+    ScrollPhase::MomentumEnded,
+];
+#[cfg(any(
+    target_os = "windows",
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+const DIRECTIONS: [ScrollDirection; 4] = [
+    ScrollDirection::Up,
+    ScrollDirection::Down,
+    ScrollDirection::Left,
+    ScrollDirection::Right,
+];
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+const DIRECTIONS: [ScrollDirection; 2] = [ScrollDirection::Up, ScrollDirection::Down];
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordedEvent, Recording};
+    #[cfg(feature = "mock")]
+    use super::{ReplayHandle, ReplayOptions};
+    use crate::common::{MouseButton, MouseEvent, ScrollDirection, ScrollPhase};
+    #[cfg(feature = "mock")]
+    use crate::error::Error;
+    #[cfg(feature = "mock")]
+    use crate::mock::{MockCall, MockMouseManager};
+    #[cfg(feature = "mock")]
+    use std::thread;
+    use std::time::Duration;
+
+    fn recording() -> Recording {
+        let events = [
+            MouseEvent::RelativeMove(5, -2),
+            MouseEvent::AbsoluteMove(300, 400),
+            MouseEvent::Press(MouseButton::Left),
+            MouseEvent::Release(MouseButton::Forward),
+            MouseEvent::Scroll(ScrollDirection::Up),
+            MouseEvent::ScrollHiRes { dx: 0, dy: -60 },
+            MouseEvent::Scroll2D { dx: -1, dy: 2 },
+            MouseEvent::ScrollPhase(ScrollPhase::MomentumEnded),
+            MouseEvent::DeviceDisconnected("/dev/input/event7".to_string()),
+        ];
+        Recording {
+            events: events
+                .into_iter()
+                .enumerate()
+                .map(|(i, event)| RecordedEvent {
+                    time: Duration::from_millis(i as u64 * 15),
+                    event,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let recording = recording();
+        assert_eq!(Recording::from_json(&recording.to_json()), Ok(recording));
+        assert!(Recording::from_json("{\"events\": [{\"type\": \"press\"}]}").is_err());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let recording = recording();
+        let bytes = recording.to_bytes();
+        assert_eq!(Recording::from_bytes(&bytes), Ok(recording));
+        assert!(Recording::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[cfg(feature = "mock")]
+    fn drag() -> Recording {
+        let events = [
+            MouseEvent::RelativeMove(1, 0),
+            MouseEvent::Press(MouseButton::Left),
+            MouseEvent::RelativeMove(2, 0),
+            MouseEvent::Release(MouseButton::Left),
+        ];
+        Recording {
+            events: events
+                .into_iter()
+                .enumerate()
+                .map(|(i, event)| RecordedEvent {
+                    time: Duration::from_millis(i as u64 * 10),
+                    event,
+                })
+                .collect(),
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn replay_with_options() {
+        let mut manager = MockMouseManager::new();
+        let options = ReplayOptions {
+            speed: 4.,
+            loop_count: Some(2),
+            start_offset: Duration::from_millis(10),
+            end_offset: Some(Duration::from_millis(20)),
+        };
+        drag()
+            .replay_with(&mut manager, &options, &ReplayHandle::new())
+            .unwrap();
+        // The button that is pressed within the range is released at the end of every loop
+        let lap = [
+            MockCall::Press(MouseButton::Left),
+            MockCall::MoveRelative(2, 0),
+            MockCall::Release(MouseButton::Left),
+        ];
+        assert_eq!(manager.calls(), [lap.clone(), lap].concat());
+
+        let options = ReplayOptions {
+            speed: 0.,
+            ..ReplayOptions::default()
+        };
+        assert!(drag()
+            .replay_with(&mut manager, &options, &ReplayHandle::new())
+            .is_err());
+        let options = ReplayOptions {
+            loop_count: None,
+            start_offset: Duration::from_millis(10),
+            end_offset: Some(Duration::from_millis(10)),
+            ..ReplayOptions::default()
+        };
+        assert!(drag()
+            .replay_with(&mut manager, &options, &ReplayHandle::new())
+            .is_err());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn control_replay() {
+        let mut manager = MockMouseManager::new();
+        let handle = ReplayHandle::new();
+        handle.pause();
+        handle.seek(Duration::from_millis(20));
+        let controls = handle.clone();
+        let resumer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(controls.position(), Duration::from_millis(20));
+            controls.resume();
+        });
+        drag()
+            .replay_with(&mut manager, &ReplayOptions::default(), &handle)
+            .unwrap();
+        resumer.join().unwrap();
+        // The press is skipped by the seek, so is the release
+        assert_eq!(manager.calls(), vec![MockCall::MoveRelative(2, 0)]);
+
+        handle.stop();
+        assert_eq!(
+            drag().replay_with(&mut manager, &ReplayOptions::default(), &handle),
+            Err(Error::Cancelled)
+        );
+    }
+}
@@ -0,0 +1,310 @@
+///
+/// This module contains the button remapper, which grabs the mice with
+/// `hook_grab` and simulates the remapped buttons with another manager
+///
+/// The grab is only available on the unix-like systems, where the events of
+/// the mice are captured with EVIOCGRAB and the remapped buttons are emitted
+/// from the virtual mouse of the output manager, system-wide
+///
+use crate::common::{
+    CallbackId, EventAction, MouseActions, MouseButton, MouseEvent, MouseListener,
+    DEFAULT_CLICK_INTERVAL,
+};
+use crate::error::Error;
+use crate::hooks::LockUnpoisoned;
+use std::{
+    mem,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// What a remapped button does instead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemapAction {
+    /// Press and release the given button along with the remapped one
+    Button(MouseButton),
+    /// Click the given button the given number of times once the remapped
+    /// button is pressed, e.g. 2 for a double click
+    Click(MouseButton, usize),
+    /// Drop the events of the remapped button
+    Disable,
+}
+
+/// A button and what it does instead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemapRule {
+    pub from: MouseButton,
+    pub to: RemapAction,
+}
+
+impl RemapRule {
+    pub fn new(from: MouseButton, to: RemapAction) -> Self {
+        RemapRule { from, to }
+    }
+}
+
+/// Applies the remap rules until it is stopped
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::common::MouseButton;
+/// use mouce::remap::{RemapAction, RemapRule, Remapper};
+/// use mouce::Mouse;
+///
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// let output = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// let rules = vec![
+///     RemapRule::new(MouseButton::Side, RemapAction::Button(MouseButton::Middle)),
+///     RemapRule::new(MouseButton::Back, RemapAction::Click(MouseButton::Left, 2)),
+/// ];
+/// let remapper = Remapper::start(manager.as_mut(), output, rules).unwrap();
+/// // Give the buttons back
+/// remapper.stop(manager.as_mut()).unwrap();
+/// ```
+pub struct Remapper {
+    state: Arc<Mutex<State>>,
+    outputs: Sender<Output>,
+    callback_id: CallbackId,
+}
+
+/// The rules along with the buttons that are held
+struct State {
+    rules: Vec<RemapRule>,
+    /// The held buttons and what their rules did when they were pressed, so that
+    /// their releases undo the same actions even if the rules change meanwhile
+    held: Vec<(MouseButton, RemapAction)>,
+}
+
+/// An action of the output manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Output {
+    Press(MouseButton),
+    Release(MouseButton),
+    Click(MouseButton, usize),
+}
+
+impl Remapper {
+    /// Grab the mice that the given manager listens to and apply the given rules,
+    /// the remapped buttons are simulated with the `output` manager
+    ///
+    /// The output manager must not be grabbed, otherwise it would capture its
+    /// own buttons. The events of the buttons without a rule are passed through
+    pub fn start<M: MouseListener + ?Sized>(
+        manager: &mut M,
+        mut output: Box<dyn MouseActions + Send>,
+        rules: Vec<RemapRule>,
+    ) -> Result<Self, Error> {
+        let state = Arc::new(Mutex::new(State {
+            rules,
+            held: Vec::new(),
+        }));
+        // The output runs on its own thread, so the clicks do not hold up the
+        // events. The thread exits once the callback and the remapper are gone
+        let (outputs, rx) = mpsc::channel::<Output>();
+        thread::spawn(move || {
+            for action in rx {
+                // The original button is consumed even if its replacement fails
+                let _ = perform(output.as_mut(), &action);
+            }
+        });
+
+        let applied = state.clone();
+        let sender = outputs.clone();
+        let callback_id = manager.hook_grab(Box::new(move |event| {
+            let (action, output) = apply(&mut applied.lock_unpoisoned(), event);
+            if let Some(output) = output {
+                let _ = sender.send(output);
+            }
+            action
+        }))?;
+
+        Ok(Remapper {
+            state,
+            outputs,
+            callback_id,
+        })
+    }
+
+    /// Replace the rules while the remapper is running, the outputs of the buttons
+    /// that are held meanwhile are released, and their releases are still consumed
+    pub fn set_rules(&self, rules: Vec<RemapRule>) {
+        let mut state = self.state.lock_unpoisoned();
+        state.rules = rules;
+        self.release_held(&mut state);
+    }
+
+    /// Stop remapping, the mice are released once the manager has no other grab
+    ///
+    /// The outputs of the buttons that are held are released
+    pub fn stop<M: MouseListener + ?Sized>(self, manager: &mut M) -> Result<(), Error> {
+        self.release_held(&mut self.state.lock_unpoisoned());
+        manager.unhook(self.callback_id)
+    }
+
+    fn release_held(&self, state: &mut State) {
+        for output in release_held(state) {
+            let _ = self.outputs.send(output);
+        }
+    }
+}
+
+/// Decide what the rule of the button of the given event does, returns whether
+/// the event is consumed along with the action of the output manager
+fn apply(state: &mut State, event: &MouseEvent) -> (EventAction, Option<Output>) {
+    match event {
+        MouseEvent::Press(button) => {
+            let to = match state.rules.iter().find(|rule| rule.from == *button) {
+                Some(rule) => rule.to.clone(),
+                None => return (EventAction::PassThrough, None),
+            };
+            let output = match &to {
+                RemapAction::Button(to) => Some(Output::Press(to.clone())),
+                RemapAction::Click(to, count) => Some(Output::Click(to.clone(), *count)),
+                RemapAction::Disable => None,
+            };
+            state.held.retain(|(from, _)| from != button);
+            state.held.push((button.clone(), to));
+            (EventAction::Consume, output)
+        }
+        MouseEvent::Release(button) => {
+            // The buttons that are pressed before they are remapped are released as they are
+            let Some(index) = state.held.iter().position(|(from, _)| from == button) else {
+                return (EventAction::PassThrough, None);
+            };
+            let output = match state.held.remove(index).1 {
+                RemapAction::Button(to) => Some(Output::Release(to)),
+                RemapAction::Click(..) | RemapAction::Disable => None,
+            };
+            (EventAction::Consume, output)
+        }
+        _ => (EventAction::PassThrough, None),
+    }
+}
+
+/// Release the outputs of the held buttons, their releases are consumed without an output
+fn release_held(state: &mut State) -> Vec<Output> {
+    let mut outputs = Vec::new();
+    for (_, to) in state.held.iter_mut() {
+        if let RemapAction::Button(button) = mem::replace(to, RemapAction::Disable) {
+            outputs.push(Output::Release(button));
+        }
+    }
+    outputs
+}
+
+fn perform(output: &mut (dyn MouseActions + Send), action: &Output) -> Result<(), Error> {
+    match action {
+        Output::Press(button) => output.press_button(button),
+        Output::Release(button) => output.release_button(button),
+        Output::Click(button, count) => output.multi_click(button, *count, DEFAULT_CLICK_INTERVAL),
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{apply, perform, release_held, Output, RemapAction, RemapRule, Remapper, State};
+    use crate::common::{EventAction, MouseButton, MouseEvent};
+    use crate::mock::{MockCall, MockMouseManager};
+
+    #[test]
+    fn remap_buttons() {
+        let rules = vec![
+            RemapRule::new(MouseButton::Side, RemapAction::Button(MouseButton::Middle)),
+            RemapRule::new(MouseButton::Back, RemapAction::Click(MouseButton::Left, 2)),
+            RemapRule::new(MouseButton::Right, RemapAction::Disable),
+        ];
+        let mut state = State {
+            rules: rules.clone(),
+            held: Vec::new(),
+        };
+        let mut outputs = Vec::new();
+        for button in [MouseButton::Side, MouseButton::Back, MouseButton::Right] {
+            for event in [
+                MouseEvent::Press(button.clone()),
+                MouseEvent::Release(button),
+            ] {
+                let (action, output) = apply(&mut state, &event);
+                assert_eq!(action, EventAction::Consume);
+                outputs.extend(output);
+            }
+        }
+        assert_eq!(
+            outputs,
+            vec![
+                Output::Press(MouseButton::Middle),
+                Output::Release(MouseButton::Middle),
+                Output::Click(MouseButton::Left, 2),
+            ]
+        );
+        // The buttons without a rule are passed through
+        let event = MouseEvent::Press(MouseButton::Left);
+        assert_eq!(apply(&mut state, &event), (EventAction::PassThrough, None));
+
+        let mut output = MockMouseManager::new();
+        perform(&mut output, &Output::Click(MouseButton::Left, 2)).unwrap();
+        assert_eq!(
+            output.calls(),
+            vec![
+                MockCall::Press(MouseButton::Left),
+                MockCall::Release(MouseButton::Left),
+                MockCall::Press(MouseButton::Left),
+                MockCall::Release(MouseButton::Left),
+            ]
+        );
+
+        // The remapper consumes the buttons until it is stopped
+        let mut manager = MockMouseManager::new();
+        let remapper =
+            Remapper::start(&mut manager, Box::new(MockMouseManager::new()), rules).unwrap();
+        let event = MouseEvent::Press(MouseButton::Side);
+        assert_eq!(manager.inject(&event), EventAction::Consume);
+        remapper.stop(&mut manager).unwrap();
+        assert_eq!(manager.inject(&event), EventAction::PassThrough);
+    }
+
+    #[test]
+    fn release_what_is_pressed() {
+        let mut state = State {
+            rules: vec![RemapRule::new(
+                MouseButton::Side,
+                RemapAction::Button(MouseButton::Middle),
+            )],
+            held: Vec::new(),
+        };
+        let press = MouseEvent::Press(MouseButton::Side);
+        let release = MouseEvent::Release(MouseButton::Side);
+        apply(&mut state, &press);
+
+        // The release undoes the press even though the rule changed meanwhile
+        state.rules = vec![RemapRule::new(
+            MouseButton::Side,
+            RemapAction::Button(MouseButton::Right),
+        )];
+        assert_eq!(
+            apply(&mut state, &release),
+            (
+                EventAction::Consume,
+                Some(Output::Release(MouseButton::Middle))
+            )
+        );
+
+        // The held outputs are released on set_rules and stop, the releases are still consumed
+        apply(&mut state, &press);
+        assert_eq!(
+            release_held(&mut state),
+            vec![Output::Release(MouseButton::Right)]
+        );
+        assert_eq!(release_held(&mut state), Vec::new());
+        assert_eq!(apply(&mut state, &release), (EventAction::Consume, None));
+
+        // A button that is pressed before it is remapped is released as it is
+        assert_eq!(
+            apply(&mut state, &release),
+            (EventAction::PassThrough, None)
+        );
+    }
+}
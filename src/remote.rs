@@ -0,0 +1,512 @@
+///
+/// This module serves the mouse actions of a local manager over a socket,
+/// so that a privileged helper process can own the device (e.g. /dev/uinput)
+/// while the application that drives the mouse stays unprivileged
+///
+/// Every message is a little endian u32 length followed by its payload,
+/// a request is an opcode with its arguments and a response is a status
+/// byte followed by the position for `get_position` or by the error
+///
+use crate::common::{
+    Backend, Capability, MouseActions, MouseButton, MouseController, ScrollDirection,
+};
+use crate::error::Error;
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+};
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+};
+
+/// The largest message that is accepted, so a broken peer can not exhaust the memory
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// The mouse actions that are sent to the server
+#[derive(Debug, Clone, PartialEq)]
+enum Request {
+    MoveTo(u32, u32),
+    MoveRelative(i32, i32),
+    GetPosition,
+    Press(MouseButton),
+    Release(MouseButton),
+    Click(MouseButton),
+    ScrollWheel(ScrollDirection),
+    Scroll(i32, i32),
+    ScrollHiRes(i32, i32),
+}
+
+/// The result of a request, with the position for `get_position`
+type Response = Result<Option<(i32, i32)>, Error>;
+
+/// A bidirectional stream to the peer
+trait Connection: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Connection for T {}
+
+/// Executes the mouse actions that are received from the clients with a local manager
+pub struct RemoteServer {
+    manager: Box<dyn MouseActions>,
+}
+
+impl RemoteServer {
+    /// Create a server that executes the received actions with the given manager
+    pub fn new(manager: Box<dyn MouseActions>) -> Self {
+        RemoteServer { manager }
+    }
+
+    /// Accept the clients on the given TCP address and serve them one at a time,
+    /// returns only if accepting the clients fails
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::remote::RemoteServer;
+    /// use mouce::Mouse;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// let manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # let manager = Mouse::new().unwrap();
+    /// let mut server = RemoteServer::new(manager);
+    /// server.serve_tcp("127.0.0.1:7878").unwrap();
+    /// ```
+    pub fn serve_tcp(&mut self, address: impl ToSocketAddrs) -> Result<(), Error> {
+        let listener = TcpListener::bind(address)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            // Send the small responses right away
+            stream.set_nodelay(true)?;
+            // A client that goes away does not stop the server
+            let _ = self.serve(stream);
+        }
+        Ok(())
+    }
+
+    /// Accept the clients on the Unix socket at the given path and serve them
+    /// one at a time, returns only if accepting the clients fails
+    #[cfg(unix)]
+    pub fn serve_unix(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let listener = UnixListener::bind(path)?;
+        for stream in listener.incoming() {
+            let _ = self.serve(stream?);
+        }
+        Ok(())
+    }
+
+    /// Execute the requests that are received on the given connection until it is closed
+    pub fn serve(&mut self, mut stream: impl Read + Write) -> Result<(), Error> {
+        loop {
+            let message = match read_message(&mut stream) {
+                Ok(message) => message,
+                // The client closed the connection
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => return Err(err.into()),
+            };
+            let response = decode_request(&message).and_then(|request| self.execute(&request));
+            write_message(&mut stream, &encode_response(&response))?;
+        }
+    }
+
+    fn execute(&mut self, request: &Request) -> Response {
+        let manager = self.manager.as_mut();
+        match request {
+            Request::MoveTo(x, y) => manager.move_to(*x as usize, *y as usize),
+            Request::MoveRelative(x, y) => manager.move_relative(*x, *y),
+            Request::GetPosition => return manager.get_position().map(Some),
+            Request::Press(button) => manager.press_button(button),
+            Request::Release(button) => manager.release_button(button),
+            Request::Click(button) => manager.click_button(button),
+            Request::ScrollWheel(direction) => manager.scroll_wheel(direction),
+            Request::Scroll(dx, dy) => manager.scroll(*dx, *dy),
+            Request::ScrollHiRes(dx, dy) => manager.scroll_wheel_hi_res(*dx, *dy),
+        }
+        .map(|_| None)
+    }
+}
+
+/// Sends the mouse actions to a `RemoteServer`
+///
+/// Only the actions are forwarded, so it implements `MouseController` but not
+/// `MouseListener`, the events of the remote mice can not be hooked
+pub struct RemoteMouseManager {
+    connection: Mutex<Box<dyn Connection>>,
+}
+
+impl RemoteMouseManager {
+    /// Connect to the server on the given TCP address
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::remote::RemoteMouseManager;
+    /// use mouce::MouseController;
+    ///
+    /// let mut manager = RemoteMouseManager::connect_tcp("127.0.0.1:7878").unwrap();
+    /// assert_eq!(manager.move_to(960, 540), Ok(()));
+    /// ```
+    pub fn connect_tcp(address: impl ToSocketAddrs) -> Result<Self, Error> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(Self::from_connection(Box::new(stream)))
+    }
+
+    /// Connect to the server on the Unix socket at the given path
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::from_connection(Box::new(UnixStream::connect(path)?)))
+    }
+
+    fn from_connection(connection: Box<dyn Connection>) -> Self {
+        RemoteMouseManager {
+            connection: Mutex::new(connection),
+        }
+    }
+
+    /// Send the given request and wait for its response
+    fn call(&self, request: &Request) -> Response {
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|_| Error::CustomError("the connection is poisoned".to_string()))?;
+        write_message(&mut *connection, &encode_request(request))?;
+        let message = read_message(&mut *connection)?;
+        decode_response(&message)?
+    }
+}
+
+/// Implement the actions for both receivers of `MouseController`,
+/// the platforms differ in whether the actions take `&mut self`
+macro_rules! remote_mouse_actions {
+    ($($mutability:tt)?) => {
+        impl MouseController for RemoteMouseManager {
+            fn move_to(&$($mutability)? self, x: usize, y: usize) -> Result<(), Error> {
+                self.call(&Request::MoveTo(x as u32, y as u32)).map(|_| ())
+            }
+
+            fn move_relative(
+                &$($mutability)? self,
+                x_offset: i32,
+                y_offset: i32,
+            ) -> Result<(), Error> {
+                self.call(&Request::MoveRelative(x_offset, y_offset))
+                    .map(|_| ())
+            }
+
+            fn get_position(&self) -> Result<(i32, i32), Error> {
+                self.call(&Request::GetPosition)?
+                    .ok_or_else(|| invalid("the position is missing"))
+            }
+
+            fn backend(&self) -> Backend {
+                Backend::Remote
+            }
+
+            fn supports(&self, capability: Capability) -> bool {
+                matches!(
+                    capability,
+
+            Capability::GetPosition            | Capability::HiResScroll
+                )
+            }
+
+            fn press_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.call(&Request::Press(button.clone())).map(|_| ())
+            }
+
+            fn release_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.call(&Request::Release(button.clone())).map(|_| ())
+            }
+
+            fn click_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.call(&Request::Click(button.clone())).map(|_| ())
+            }
+
+            fn scroll_wheel(
+                &$($mutability)? self,
+                direction: &ScrollDirection,
+            ) -> Result<(), Error> {
+                self.call(&Request::ScrollWheel(direction.clone()))
+                    .map(|_| ())
+            }
+
+            fn scroll(&$($mutability)? self, dx: i32, dy: i32) -> Result<(), Error> {
+                self.call(&Request::Scroll(dx, dy)).map(|_| ())
+            }
+
+            fn scroll_wheel_hi_res(&$($mutability)? self, dx: i32, dy: i32) -> Result<(), Error> {
+                self.call(&Request::ScrollHiRes(dx, dy)).map(|_| ())
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+remote_mouse_actions!(mut);
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+remote_mouse_actions!();
+
+fn read_message(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut length = [0; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the message of {} bytes is too large", length),
+        ));
+    }
+    let mut message = vec![0; length];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+fn write_message(stream: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    let mut bytes = (message.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(message);
+    stream.write_all(&bytes)?;
+    stream.flush()
+}
+
+fn encode_request(request: &Request) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match request {
+        Request::MoveTo(x, y) => {
+            bytes.push(OP_MOVE_TO);
+            bytes.extend_from_slice(&x.to_le_bytes());
+            bytes.extend_from_slice(&y.to_le_bytes());
+        }
+        Request::MoveRelative(x, y) => put_pair(&mut bytes, OP_MOVE_RELATIVE, *x, *y),
+        Request::GetPosition => bytes.push(OP_GET_POSITION),
+        Request::Press(button) => put_name(&mut bytes, OP_PRESS, &button.to_string()),
+        Request::Release(button) => put_name(&mut bytes, OP_RELEASE, &button.to_string()),
+        Request::Click(button) => put_name(&mut bytes, OP_CLICK, &button.to_string()),
+        Request::ScrollWheel(direction) => {
+            put_name(&mut bytes, OP_SCROLL_WHEEL, &direction.to_string())
+        }
+        Request::Scroll(dx, dy) => put_pair(&mut bytes, OP_SCROLL, *dx, *dy),
+        Request::ScrollHiRes(dx, dy) => put_pair(&mut bytes, OP_SCROLL_HI_RES, *dx, *dy),
+    }
+    bytes
+}
+
+fn decode_request(bytes: &[u8]) -> Result<Request, Error> {
+    let mut rest = bytes;
+    let opcode = take(&mut rest, 1)?[0];
+    let request = match opcode {
+        OP_MOVE_TO => Request::MoveTo(take_u32(&mut rest)?, take_u32(&mut rest)?),
+        OP_MOVE_RELATIVE => Request::MoveRelative(take_i32(&mut rest)?, take_i32(&mut rest)?),
+        OP_GET_POSITION => Request::GetPosition,
+        // The buttons and the directions are sent by their names,
+        // so the platforms with different variants can talk to each other
+        OP_PRESS => Request::Press(take_str(&mut rest)?.parse()?),
+        OP_RELEASE => Request::Release(take_str(&mut rest)?.parse()?),
+        OP_CLICK => Request::Click(take_str(&mut rest)?.parse()?),
+        OP_SCROLL_WHEEL => Request::ScrollWheel(take_str(&mut rest)?.parse()?),
+        OP_SCROLL => Request::Scroll(take_i32(&mut rest)?, take_i32(&mut rest)?),
+        OP_SCROLL_HI_RES => Request::ScrollHiRes(take_i32(&mut rest)?, take_i32(&mut rest)?),
+        _ => return Err(invalid(&format!("unknown opcode {}", opcode))),
+    };
+    if !rest.is_empty() {
+        return Err(invalid("unexpected bytes after the request"));
+    }
+    Ok(request)
+}
+
+fn encode_response(response: &Response) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match response {
+        Ok(None) => bytes.push(STATUS_OK),
+        Ok(Some((x, y))) => put_pair(&mut bytes, STATUS_POSITION, *x, *y),
+        Err(error) => {
+            bytes.push(STATUS_ERROR);
+            let (code, message) = match error {
+                Error::PermissionDenied => (0, String::new()),
+                Error::NotImplemented => (1, String::new()),
+                Error::DeviceNotFound => (2, String::new()),
+                Error::CallbackNotFound => (3, String::new()),
+                Error::WriteFailed => (4, String::new()),
+                Error::Unsupported(backend) => (5, backend.to_string()),
+                Error::X11PointerWindowMismatch => (6, String::new()),
+                Error::CGCouldNotCreateEvent => (7, String::new()),
+                Error::CustomError(message) => (8, message.clone()),
+                Error::Cancelled => (9, String::new()),
+            };
+            put_name(&mut bytes, code, &message);
+        }
+    }
+    bytes
+}
+
+/// Decode the response of the server, the outer error is for the malformed responses
+fn decode_response(bytes: &[u8]) -> Result<Response, Error> {
+    let mut rest = bytes;
+    let response = match take(&mut rest, 1)?[0] {
+        STATUS_OK => Ok(None),
+        STATUS_POSITION => Ok(Some((take_i32(&mut rest)?, take_i32(&mut rest)?))),
+        STATUS_ERROR => {
+            let code = take(&mut rest, 1)?[0];
+            let message = take_str(&mut rest)?;
+            Err(match code {
+                0 => Error::PermissionDenied,
+                1 => Error::NotImplemented,
+                2 => Error::DeviceNotFound,
+                3 => Error::CallbackNotFound,
+                4 => Error::WriteFailed,
+                5 => match message.as_str() {
+                    "x11" => Error::Unsupported(Backend::X11),
+                    "uinput" => Error::Unsupported(Backend::UInput),
+                    "windows" => Error::Unsupported(Backend::Windows),
+                    "darwin" => Error::Unsupported(Backend::Darwin),
+                    "mock" => Error::Unsupported(Backend::Mock),
+                    "remote" => Error::Unsupported(Backend::Remote),
+                    _ => Error::CustomError(format!("the input is not supported on {}", message)),
+                },
+                6 => Error::X11PointerWindowMismatch,
+                7 => Error::CGCouldNotCreateEvent,
+                9 => Error::Cancelled,
+                _ => Error::CustomError(message),
+            })
+        }
+        status => return Err(invalid(&format!("unknown status {}", status))),
+    };
+    Ok(response)
+}
+
+fn put_pair(bytes: &mut Vec<u8>, tag: u8, x: i32, y: i32) {
+    bytes.push(tag);
+    bytes.extend_from_slice(&x.to_le_bytes());
+    bytes.extend_from_slice(&y.to_le_bytes());
+}
+
+/// Write the tag and the given string with its u16 length
+fn put_name(bytes: &mut Vec<u8>, tag: u8, name: &str) {
+    let name = &name.as_bytes()[..name.len().min(u16::MAX as usize)];
+    bytes.push(tag);
+    bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(name);
+}
+
+/// Split the given number of bytes from the front of the slice
+fn take<'a>(bytes: &mut &'a [u8], count: usize) -> Result<&'a [u8], Error> {
+    if bytes.len() < count {
+        return Err(invalid("the message is truncated"));
+    }
+    let (taken, rest) = bytes.split_at(count);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn take_u32(bytes: &mut &[u8]) -> Result<u32, Error> {
+    Ok(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+fn take_i32(bytes: &mut &[u8]) -> Result<i32, Error> {
+    Ok(i32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+fn take_str(bytes: &mut &[u8]) -> Result<String, Error> {
+    let length = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) as usize;
+    String::from_utf8(take(bytes, length)?.to_vec()).map_err(|_| invalid("the string is not UTF-8"))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::CustomError(format!("invalid remote message: {}", reason))
+}
+
+/// Protocol definitions
+const OP_MOVE_TO: u8 = 0;
+const OP_MOVE_RELATIVE: u8 = 1;
+const OP_GET_POSITION: u8 = 2;
+const OP_PRESS: u8 = 3;
+const OP_RELEASE: u8 = 4;
+const OP_CLICK: u8 = 5;
+const OP_SCROLL_WHEEL: u8 = 6;
+const OP_SCROLL: u8 = 7;
+const OP_SCROLL_HI_RES: u8 = 8;
+const STATUS_OK: u8 = 0;
+const STATUS_POSITION: u8 = 1;
+const STATUS_ERROR: u8 = 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_round_trip() {
+        let requests = [
+            Request::MoveTo(1920, 1080),
+            Request::MoveRelative(-5, 10),
+            Request::GetPosition,
+            Request::Press(MouseButton::Left),
+            Request::Release(MouseButton::Back),
+            Request::Click(MouseButton::Task),
+            Request::ScrollWheel(ScrollDirection::Down),
+            Request::Scroll(1, -2),
+            Request::ScrollHiRes(-60, 240),
+        ];
+        for request in requests.iter() {
+            assert_eq!(
+                decode_request(&encode_request(request)).as_ref(),
+                Ok(request)
+            );
+        }
+        assert!(decode_request(&[]).is_err());
+        assert!(decode_request(&[OP_MOVE_TO, 1, 2]).is_err());
+        assert!(decode_request(&[OP_GET_POSITION, 0]).is_err());
+        assert!(decode_request(&[255]).is_err());
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let responses = [
+            Ok(None),
+            Ok(Some((-10, 20))),
+            Err(Error::PermissionDenied),
+            Err(Error::Unsupported(Backend::UInput)),
+            Err(Error::CustomError("the device is gone".to_string())),
+        ];
+        for response in responses.iter() {
+            assert_eq!(
+                decode_response(&encode_response(response)).as_ref(),
+                Ok(response)
+            );
+        }
+    }
+
+    #[test]
+    fn message_framing() {
+        let mut stream = Vec::new();
+        write_message(&mut stream, &[1, 2, 3]).unwrap();
+        write_message(&mut stream, &[]).unwrap();
+        assert_eq!(stream, vec![3, 0, 0, 0, 1, 2, 3, 0, 0, 0, 0]);
+
+        let mut reader = stream.as_slice();
+        assert_eq!(read_message(&mut reader).unwrap(), vec![1, 2, 3]);
+        assert_eq!(read_message(&mut reader).unwrap(), Vec::<u8>::new());
+        assert_eq!(
+            read_message(&mut reader).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut too_large: &[u8] = &[0, 0, 1, 0];
+        assert!(read_message(&mut too_large).is_err());
+    }
+}
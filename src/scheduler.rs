@@ -0,0 +1,350 @@
+///
+/// This module contains the scheduler that does the actions at the given
+/// instants on a dedicated thread, e.g. to replay or to synthesize the motion
+/// with an exact timing instead of sleeping between the calls
+///
+/// The thread sleeps until shortly before the next action and spins for the
+/// rest of the wait, as the sleeps of the system overshoot by about its
+/// timer resolution while spinning is accurate to the microseconds
+///
+use crate::cancel::CancellationToken;
+use crate::common::MouseActions;
+use crate::error::Error;
+use crate::hooks::LockUnpoisoned;
+use crate::script::Action;
+use crate::trace;
+use std::{
+    collections::VecDeque,
+    hint,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long before its instant an action is waited for by spinning instead of sleeping
+#[cfg(target_os = "windows")]
+pub(crate) const SPIN_THRESHOLD: Duration = Duration::from_millis(16);
+#[cfg(not(target_os = "windows"))]
+pub(crate) const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+#[derive(Default)]
+struct Queue {
+    /// The scheduled actions sorted by their instants, the actions of
+    /// the same instant in the order they are scheduled
+    actions: VecDeque<(Instant, Action)>,
+    /// Whether an action is being done
+    running: bool,
+    /// Set once the scheduler is dropped, or once an action panics and takes the thread down
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Notified once the queue changes or an action is done
+    changed: Condvar,
+}
+
+impl Shared {
+    fn wait<'a>(&self, queue: MutexGuard<'a, Queue>) -> MutexGuard<'a, Queue> {
+        self.changed
+            .wait(queue)
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Does the scheduled actions with the given manager until it is dropped
+///
+/// The actions that are scheduled in the past are done right away, in order.
+/// An action that fails is skipped, the failure is logged with the `tracing` feature
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::scheduler::ActionScheduler;
+/// use mouce::script::Action;
+/// use mouce::Mouse;
+/// use std::time::{Duration, Instant};
+///
+/// # #[cfg(target_os = "linux")]
+/// let manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let manager = Mouse::new().unwrap();
+/// let scheduler = ActionScheduler::start(manager);
+/// // A move every millisecond
+/// let start = Instant::now();
+/// scheduler.schedule_all(
+///     (0..500).map(|i| (start + Duration::from_millis(i), Action::MoveBy(1, 0))),
+/// );
+/// scheduler.wait_idle();
+/// ```
+pub struct ActionScheduler {
+    shared: Arc<Shared>,
+    /// Cancels the waits of the action that is being done once the scheduler is dropped
+    token: CancellationToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ActionScheduler {
+    /// Start the thread that does the scheduled actions with the given manager
+    pub fn start(manager: Box<dyn MouseActions + Send>) -> Self {
+        let shared = Arc::new(Shared::default());
+        let token = CancellationToken::new();
+        let thread = {
+            let (shared, token) = (shared.clone(), token.clone());
+            thread::spawn(move || run(&shared, manager, &token))
+        };
+        ActionScheduler {
+            shared,
+            token,
+            thread: Some(thread),
+        }
+    }
+
+    /// Do the given action at the given instant
+    pub fn schedule(&self, at: Instant, action: Action) {
+        self.schedule_all([(at, action)]);
+    }
+
+    /// Do the given actions at their instants, they do not need to be sorted
+    pub fn schedule_all(&self, actions: impl IntoIterator<Item = (Instant, Action)>) {
+        let mut queue = self.shared.queue.lock_unpoisoned();
+        for (at, action) in actions {
+            let index = queue.actions.partition_point(|(queued, _)| *queued <= at);
+            queue.actions.insert(index, (at, action));
+        }
+        self.shared.changed.notify_all();
+    }
+
+    /// How many actions are waiting for their instants
+    pub fn pending(&self) -> usize {
+        self.shared.queue.lock_unpoisoned().actions.len()
+    }
+
+    /// Drop the actions that are not done yet, the action that
+    /// is being done meanwhile is finished
+    pub fn clear(&self) {
+        self.shared.queue.lock_unpoisoned().actions.clear();
+        self.shared.changed.notify_all();
+    }
+
+    /// Block until every scheduled action is done, or until an action panics
+    /// and the remaining ones are never done
+    pub fn wait_idle(&self) {
+        let mut queue = self.shared.queue.lock_unpoisoned();
+        while !queue.stopped && (!queue.actions.is_empty() || queue.running) {
+            queue = self.shared.wait(queue);
+        }
+    }
+}
+
+impl Drop for ActionScheduler {
+    fn drop(&mut self) {
+        self.shared.queue.lock_unpoisoned().stopped = true;
+        self.shared.changed.notify_all();
+        self.token.cancel();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Do the actions of the queue at their instants until the scheduler is stopped
+fn run(shared: &Shared, mut manager: Box<dyn MouseActions + Send>, token: &CancellationToken) {
+    let mut queue = shared.queue.lock_unpoisoned();
+    while !queue.stopped {
+        let at = match queue.actions.front() {
+            Some((at, _)) => *at,
+            None => {
+                queue = shared.wait(queue);
+                continue;
+            }
+        };
+        let now = Instant::now();
+        if at > now + SPIN_THRESHOLD {
+            // Check the queue again after the sleep, an earlier action may be scheduled meanwhile
+            queue = shared
+                .changed
+                .wait_timeout(queue, at - now - SPIN_THRESHOLD)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+            continue;
+        }
+        if at > now {
+            drop(queue);
+            spin_until(at);
+            queue = shared.queue.lock_unpoisoned();
+            // The queue may be cleared or stopped while spinning
+            continue;
+        }
+
+        let Some((_, action)) = queue.actions.pop_front() else {
+            continue;
+        };
+        queue.running = true;
+        drop(queue);
+        let running = Running(shared);
+        if let Err(err) = action.perform(manager.as_mut(), token) {
+            trace::warning!("the scheduled action {:?} failed: {}", action, err);
+        }
+        drop(running);
+        queue = shared.queue.lock_unpoisoned();
+    }
+}
+
+/// Clears `running` once the action is done, also when it panics
+struct Running<'a>(&'a Shared);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.0.queue.lock_unpoisoned();
+        queue.running = false;
+        // The thread is gone, nothing is done anymore
+        if thread::panicking() {
+            queue.stopped = true;
+        }
+        self.0.changed.notify_all();
+    }
+}
+
+/// Wait until the given instant like the scheduler, by sleeping until shortly before
+/// it and spinning for the rest, or fail with `Error::Cancelled` once the token is cancelled
+pub(crate) fn wait_until(at: Instant, token: &CancellationToken) -> Result<(), Error> {
+    let now = Instant::now();
+    if at > now + SPIN_THRESHOLD {
+        token.sleep(at - now - SPIN_THRESHOLD)?;
+    }
+    spin_until(at);
+    token.check()
+}
+
+/// Spin until the given instant, which is accurate to the microseconds
+pub(crate) fn spin_until(at: Instant) {
+    while Instant::now() < at {
+        hint::spin_loop();
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::ActionScheduler;
+    use crate::common::{
+        Backend, CallbackId, MouseButton, MouseController, MouseEvent, MouseListener,
+        ScrollDirection,
+    };
+    use crate::error::Error;
+    use crate::mock::MockMouseManager;
+    use crate::script::Action;
+    use crate::shared::SharedMouse;
+    use std::time::{Duration, Instant};
+
+    /// A manager that panics on every move
+    struct Panicking;
+
+    macro_rules! panicking_actions {
+        ($($mutability:tt)?) => {
+            impl MouseController for Panicking {
+                fn move_to(&$($mutability)? self, _: usize, _: usize) -> Result<(), Error> {
+                    panic!("the move failed")
+                }
+
+                fn move_relative(&$($mutability)? self, _: i32, _: i32) -> Result<(), Error> {
+                    panic!("the move failed")
+                }
+
+                fn get_position(&self) -> Result<(i32, i32), Error> {
+                    Err(Error::NotImplemented)
+                }
+
+                fn backend(&self) -> Backend {
+                    Backend::Mock
+                }
+
+                fn press_button(&$($mutability)? self, _: &MouseButton) -> Result<(), Error> {
+                    Err(Error::NotImplemented)
+                }
+
+                fn release_button(&$($mutability)? self, _: &MouseButton) -> Result<(), Error> {
+                    Err(Error::NotImplemented)
+                }
+
+                fn scroll_wheel(&$($mutability)? self, _: &ScrollDirection) -> Result<(), Error> {
+                    Err(Error::NotImplemented)
+                }
+            }
+        };
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    panicking_actions!(mut);
+    #[cfg(not(any(
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    panicking_actions!();
+
+    impl MouseListener for Panicking {
+        fn hook(&mut self, _: Box<dyn Fn(&MouseEvent) + Send + Sync>) -> Result<CallbackId, Error> {
+            Err(Error::NotImplemented)
+        }
+
+        fn unhook(&mut self, _: CallbackId) -> Result<(), Error> {
+            Err(Error::NotImplemented)
+        }
+
+        fn unhook_all(&mut self) -> Result<(), Error> {
+            Err(Error::NotImplemented)
+        }
+
+        fn stop_listening(&mut self) -> Result<(), Error> {
+            Err(Error::NotImplemented)
+        }
+    }
+
+    #[test]
+    fn scheduled_actions() {
+        let mouse = SharedMouse::new(Box::new(MockMouseManager::new()));
+        let scheduler = ActionScheduler::start(Box::new(mouse.clone()));
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        // The actions are done in the order of their instants
+        scheduler.schedule_all([
+            (at(60), Action::MoveBy(5, 5)),
+            (at(50), Action::Move(10, 20)),
+            (at(60), Action::MoveBy(1, 0)),
+        ]);
+        assert_eq!(scheduler.pending(), 3);
+        scheduler.wait_idle();
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert_eq!(mouse.get_position(), Ok((16, 25)));
+
+        scheduler.schedule(at(10_000), Action::MoveBy(1, 1));
+        scheduler.clear();
+        scheduler.wait_idle();
+        assert_eq!(scheduler.pending(), 0);
+        assert_eq!(mouse.get_position(), Ok((16, 25)));
+    }
+
+    #[test]
+    fn panicking_action() {
+        let scheduler = ActionScheduler::start(Box::new(Panicking));
+        scheduler.schedule_all([
+            (Instant::now(), Action::Move(1, 1)),
+            (Instant::now(), Action::Move(2, 2)),
+        ]);
+        // The thread is gone, so the second action is never done
+        scheduler.wait_idle();
+        assert_eq!(scheduler.pending(), 1);
+    }
+}
@@ -0,0 +1,315 @@
+///
+/// This module contains the parser and the runner of the action scripts,
+/// short texts of mouse actions e.g. `move 100 200; sleep 50; click left`
+///
+/// The statements are separated by semicolons or newlines, and `#` starts a
+/// comment until the end of the line, so the scripts can be kept in the
+/// configuration files as they are
+///
+use crate::cancel::CancellationToken;
+use crate::common::{MouseButton, MouseController, ScrollDirection, DEFAULT_CLICK_INTERVAL};
+use crate::error::Error;
+use std::{fmt, str::FromStr, time::Duration};
+
+/// The interval between the detents of the scrolls with a count
+const SCROLL_INTERVAL: Duration = Duration::from_millis(15);
+
+/// A statement of a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// `move X Y`, move to the given position
+    Move(usize, usize),
+    /// `move_by DX DY`, move relative to the current position
+    MoveBy(i32, i32),
+    /// `press [BUTTON]`, press down the button, the left one by default
+    Press(MouseButton),
+    /// `release [BUTTON]`, release the button, the left one by default
+    Release(MouseButton),
+    /// `click [BUTTON] [COUNT]`, click the button the given number of times,
+    /// the left one once by default
+    Click(MouseButton, usize),
+    /// `scroll DIRECTION [COUNT]`, scroll the given number of detents, one by default
+    Scroll(ScrollDirection, u32),
+    /// `sleep MILLISECONDS`, wait before the next action
+    Sleep(Duration),
+}
+
+impl Action {
+    /// Do the action with the given manager, the waits stop once the given token is cancelled
+    pub fn perform<M: MouseController + ?Sized>(
+        &self,
+        manager: &mut M,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        match self {
+            Action::Move(x, y) => manager.move_to(*x, *y),
+            Action::MoveBy(x, y) => manager.move_relative(*x, *y),
+            Action::Press(button) => manager.press_button(button),
+            Action::Release(button) => manager.release_button(button),
+            Action::Click(button, count) => {
+                manager.multi_click_cancellable(button, *count, DEFAULT_CLICK_INTERVAL, token)
+            }
+            Action::Scroll(direction, count) => {
+                manager.scroll_n_cancellable(direction, *count, SCROLL_INTERVAL, token)
+            }
+            Action::Sleep(duration) => token.sleep(*duration),
+        }
+    }
+}
+
+/// A parsed script, the actions are done in order
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::script::ActionPlan;
+/// use mouce::Mouse;
+///
+/// let plan: ActionPlan = "move 100 200; sleep 50; click left; scroll down 3"
+///     .parse()
+///     .unwrap();
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// plan.run(manager.as_mut()).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActionPlan {
+    pub actions: Vec<Action>,
+}
+
+impl ActionPlan {
+    /// Parse the given script, the error tells where the script is invalid
+    pub fn parse(script: &str) -> Result<Self, ParseError> {
+        let mut actions = Vec::new();
+        for line in script.split('\n') {
+            let code = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+            for statement in code.split(';') {
+                let words: Vec<&str> = statement.split_whitespace().collect();
+                if let Some(action) = parse_statement(script, statement, &words)? {
+                    actions.push(action);
+                }
+            }
+        }
+        Ok(ActionPlan { actions })
+    }
+
+    /// Do the actions with the given manager
+    pub fn run<M: MouseController + ?Sized>(&self, manager: &mut M) -> Result<(), Error> {
+        self.run_cancellable(manager, &CancellationToken::new())
+    }
+
+    /// Do the actions like `run`, but stop once the given token is cancelled and
+    /// fail with `Error::Cancelled`. The buttons that the script pressed and did
+    /// not release yet are released when it stops early, or when an action fails
+    pub fn run_cancellable<M: MouseController + ?Sized>(
+        &self,
+        manager: &mut M,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        let mut held: Vec<MouseButton> = Vec::new();
+        for action in self.actions.iter() {
+            if let Err(err) = token.check().and_then(|_| action.perform(manager, token)) {
+                for button in held.iter() {
+                    let _ = manager.release_button(button);
+                }
+                return Err(err);
+            }
+            match action {
+                Action::Press(button) if !held.contains(button) => held.push(button.clone()),
+                Action::Release(button) => held.retain(|held| held != button),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ActionPlan {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ActionPlan::parse(s)
+    }
+}
+
+/// Parse the given words of a statement of the script, None if it is empty
+fn parse_statement(
+    script: &str,
+    statement: &str,
+    words: &[&str],
+) -> Result<Option<Action>, ParseError> {
+    let Some((command, arguments)) = words.split_first() else {
+        return Ok(None);
+    };
+    let error = |word: &str, message: String| ParseError::new(script, word, message);
+    // The missing arguments are reported at the end of the statement
+    let end = &statement[statement.trim_end().len()..];
+    let argument = |index: usize, name: &str| {
+        arguments
+            .get(index)
+            .copied()
+            .ok_or_else(|| error(end, format!("`{}` needs {}", command, name)))
+    };
+    let button = |index: usize| match arguments.get(index) {
+        Some(word) => word.parse().map_err(|_| {
+            error(
+                word,
+                format!("`{}` is not a button, e.g. left, right or middle", word),
+            )
+        }),
+        None => Ok(MouseButton::Left),
+    };
+
+    let (action, count) = match *command {
+        "move" => (
+            Action::Move(
+                number(script, argument(0, "x")?)?,
+                number(script, argument(1, "y")?)?,
+            ),
+            2,
+        ),
+        "move_by" => (
+            Action::MoveBy(
+                number(script, argument(0, "dx")?)?,
+                number(script, argument(1, "dy")?)?,
+            ),
+            2,
+        ),
+        "press" => (Action::Press(button(0)?), 1),
+        "release" => (Action::Release(button(0)?), 1),
+        "click" => {
+            let count = match arguments.get(1) {
+                Some(word) => number(script, word)?,
+                None => 1,
+            };
+            (Action::Click(button(0)?, count), 2)
+        }
+        "scroll" => {
+            let word = argument(0, "a direction")?;
+            let direction = word.parse().map_err(|_| {
+                error(
+                    word,
+                    format!("`{}` is not a direction, e.g. up or down", word),
+                )
+            })?;
+            let count = match arguments.get(1) {
+                Some(word) => number(script, word)?,
+                None => 1,
+            };
+            (Action::Scroll(direction, count), 2)
+        }
+        "sleep" => (
+            Action::Sleep(Duration::from_millis(number(
+                script,
+                argument(0, "the milliseconds")?,
+            )?)),
+            1,
+        ),
+        _ => return Err(error(command, format!("`{}` is not an action", command))),
+    };
+    match arguments.get(count) {
+        Some(extra) => Err(error(
+            extra,
+            format!("`{}` takes at most {} arguments", command, count),
+        )),
+        None => Ok(Some(action)),
+    }
+}
+
+/// Parse the given word of the script as a number
+fn number<T: FromStr>(script: &str, word: &str) -> Result<T, ParseError> {
+    word.parse()
+        .map_err(|_| ParseError::new(script, word, format!("`{}` is not a number", word)))
+}
+
+/// Where and why a script is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset of the invalid part in the script
+    pub position: usize,
+    /// The line and the column of the invalid part, both start from 1
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    /// Point at the given part of the script, which must be a slice of it
+    fn new(script: &str, part: &str, message: String) -> Self {
+        let position = part.as_ptr() as usize - script.as_ptr() as usize;
+        let before = &script[..position];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        ParseError {
+            position,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::CustomError(format!("invalid script: {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, ActionPlan};
+    use crate::common::{MouseButton, ScrollDirection};
+    use std::time::Duration;
+
+    #[test]
+    fn parse_scripts() {
+        let plan = ActionPlan::parse(
+            "move 100 200; sleep 50; click left; scroll down 3\n\
+             # Drag to the right\n\
+             press; move_by 300 -5 ;release # done\n\
+             click right 2;;",
+        )
+        .unwrap();
+        assert_eq!(
+            plan.actions,
+            vec![
+                Action::Move(100, 200),
+                Action::Sleep(Duration::from_millis(50)),
+                Action::Click(MouseButton::Left, 1),
+                Action::Scroll(ScrollDirection::Down, 3),
+                Action::Press(MouseButton::Left),
+                Action::MoveBy(300, -5),
+                Action::Release(MouseButton::Left),
+                Action::Click(MouseButton::Right, 2),
+            ]
+        );
+
+        // The errors point at the invalid words
+        let error = ActionPlan::parse("click left;\n  move 10 ten").unwrap_err();
+        assert_eq!((error.position, error.line, error.column), (22, 2, 11));
+        assert_eq!(
+            error.to_string(),
+            "line 2, column 11: `ten` is not a number"
+        );
+        let error = ActionPlan::parse("sleep").unwrap_err();
+        assert_eq!(
+            (error.position, error.message.as_str()),
+            (5, "`sleep` needs the milliseconds")
+        );
+        assert_eq!(ActionPlan::parse("jump 1").unwrap_err().position, 0);
+        assert_eq!(ActionPlan::parse("press left left").unwrap_err().column, 12);
+    }
+}
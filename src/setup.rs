@@ -0,0 +1,156 @@
+///
+/// This module contains the setup of the permissions that the mouse actions
+/// need on Linux, so that they work without the super user privileges
+///
+/// The udev rule gives a group the access to /dev/uinput, which the virtual
+/// devices are created through, and to the event files of the mice, which the
+/// listeners read. Installing the rule needs the super user privileges once
+///
+use crate::error::Error;
+use std::{fs, path::PathBuf, process::Command};
+
+/// Where the rule is installed by default
+pub const UDEV_RULES_FILE: &str = "/etc/udev/rules.d/99-mouce.rules";
+
+/// The options of `install_udev_rule`
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::setup::{self, UdevRuleOptions};
+///
+/// let rule = setup::install_udev_rule(&UdevRuleOptions::new().group("plugdev").dry_run(true))
+///     .unwrap();
+/// assert!(rule.contains(r#"GROUP="plugdev""#));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdevRuleOptions {
+    group: String,
+    path: PathBuf,
+    dry_run: bool,
+    reload: bool,
+}
+
+impl UdevRuleOptions {
+    /// Give the `input` group the access, with the rule written to `UDEV_RULES_FILE`
+    pub fn new() -> Self {
+        UdevRuleOptions {
+            group: "input".to_string(),
+            path: PathBuf::from(UDEV_RULES_FILE),
+            dry_run: false,
+            reload: true,
+        }
+    }
+
+    /// The group that is given the access to the devices, the users of
+    /// the group can use the mouse once they log in again
+    pub fn group(mut self, group: &str) -> Self {
+        self.group = group.to_string();
+        self
+    }
+
+    /// Where the rule is written to
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Only return the text of the rule without writing it
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Whether udev reloads its rules and applies them to the existing
+    /// devices once the rule is written, with `udevadm`
+    pub fn reload(mut self, reload: bool) -> Self {
+        self.reload = reload;
+        self
+    }
+}
+
+impl Default for UdevRuleOptions {
+    fn default() -> Self {
+        UdevRuleOptions::new()
+    }
+}
+
+/// Get the text of the udev rule that gives the given group the access to
+/// /dev/uinput and to the event files of the mice
+pub fn udev_rule(group: &str) -> Result<String, Error> {
+    // The name is written into the rule as it is
+    let valid = !group.is_empty()
+        && !group.starts_with('-')
+        && group
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return Err(Error::CustomError(format!(
+            "{} is not a valid group name",
+            group
+        )));
+    }
+
+    Ok(format!(
+        "# Installed by mouce, gives the {group} group the access to the mice\n\
+         KERNEL==\"uinput\", SUBSYSTEM==\"misc\", GROUP=\"{group}\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"\n\
+         SUBSYSTEM==\"input\", KERNEL==\"event*\", ENV{{ID_INPUT_MOUSE}}==\"1\", GROUP=\"{group}\", MODE=\"0660\"\n"
+    ))
+}
+
+/// Write the udev rule with the given options, and get the text of the rule
+///
+/// Writing to /etc/udev/rules.d needs the super user privileges,
+/// `Error::PermissionDenied` is returned otherwise
+pub fn install_udev_rule(options: &UdevRuleOptions) -> Result<String, Error> {
+    let rule = udev_rule(&options.group)?;
+    if options.dry_run {
+        return Ok(rule);
+    }
+
+    fs::write(&options.path, &rule)?;
+    if options.reload {
+        udevadm(&["control", "--reload-rules"])?;
+        udevadm(&["trigger"])?;
+    }
+    Ok(rule)
+}
+
+/// Run udevadm with the given arguments
+fn udevadm(args: &[&str]) -> Result<(), Error> {
+    let status = Command::new("udevadm").args(args).status()?;
+    if !status.success() {
+        return Err(Error::CustomError(format!(
+            "udevadm {} failed with {}",
+            args.join(" "),
+            status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{install_udev_rule, udev_rule, UdevRuleOptions};
+    use std::{env, fs};
+
+    #[test]
+    fn udev_rules() {
+        let rule = udev_rule("plugdev").unwrap();
+        assert!(rule.contains(r#"KERNEL=="uinput", SUBSYSTEM=="misc", GROUP="plugdev""#));
+        assert!(rule.contains(r#"ENV{ID_INPUT_MOUSE}=="1", GROUP="plugdev""#));
+        assert!(udev_rule("").is_err());
+        assert!(udev_rule(r#"input", MODE="0666"#).is_err());
+
+        let path = env::temp_dir().join(format!("mouce-{}.rules", std::process::id()));
+        let options = UdevRuleOptions::new().path(&path).reload(false);
+        assert_eq!(
+            install_udev_rule(&options.clone().dry_run(true)),
+            udev_rule("input")
+        );
+        assert!(!path.exists());
+        assert_eq!(install_udev_rule(&options), udev_rule("input"));
+        assert_eq!(fs::read_to_string(&path).ok(), udev_rule("input").ok());
+        fs::remove_file(path).unwrap();
+    }
+}
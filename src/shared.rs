@@ -0,0 +1,435 @@
+///
+/// This module contains a handle of a mouse manager that can be
+/// cloned and used from several threads at the same time
+///
+use crate::cancel::CancellationToken;
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, CoordinateSpace, Easing,
+    EventAction, EventMask, EventWithTime, ListenerStats, MotionProfile, MouseActions, MouseButton,
+    MouseController, MouseEvent, MouseEventExt, MouseListener, Propagation, ScrollDirection,
+    ScrollPhase,
+};
+use crate::devices::MouseDeviceInfo;
+use crate::display::Rect;
+use crate::error::Error;
+use crate::hooks::LockUnpoisoned;
+use crate::keyboard::Modifiers;
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// A handle of a mouse manager that is shared between threads, e.g. so that
+/// a GUI thread and a worker thread can both drive the mouse
+///
+/// Every action locks the manager until it is done, so the actions of the handles
+/// do not interleave, e.g. a `click_button` is not split by a move of another thread.
+/// The callbacks must not wait for the other handles while the listener is being
+/// stopped, as stopping the listener waits for the callbacks with the manager locked
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::{Mouse, MouseController, SharedMouse};
+/// use std::thread;
+///
+/// # #[cfg(target_os = "linux")]
+/// let manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let manager = Mouse::new().unwrap();
+/// let mut mouse = SharedMouse::new(manager);
+///
+/// let mut worker = mouse.clone_handle();
+/// let moves = thread::spawn(move || worker.move_to(100, 100));
+/// mouse.move_relative(10, 0).unwrap();
+/// moves.join().unwrap().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SharedMouse {
+    manager: Arc<Mutex<Box<dyn MouseActions + Send>>>,
+}
+
+impl SharedMouse {
+    /// Share the given manager, e.g. the one that `Mouse::new` creates
+    pub fn new(manager: Box<dyn MouseActions + Send>) -> Self {
+        SharedMouse {
+            manager: Arc::new(Mutex::new(manager)),
+        }
+    }
+
+    /// Get another handle of the same manager, only the reference count is increased
+    pub fn clone_handle(&self) -> Self {
+        self.clone()
+    }
+
+    /// Lock the manager, e.g. to run several actions without the other handles interleaving
+    pub fn lock(&self) -> MutexGuard<'_, Box<dyn MouseActions + Send>> {
+        // A panic of another thread during an action does not break the manager
+        self.manager.lock_unpoisoned()
+    }
+}
+
+/// Implement the actions for both receivers of `MouseController`,
+/// the platforms differ in whether the actions take `&mut self`
+macro_rules! shared_mouse_actions {
+    ($($mutability:tt)?) => {
+        impl MouseController for SharedMouse {
+            fn move_to(&$($mutability)? self, x: usize, y: usize) -> Result<(), Error> {
+                self.lock().move_to(x, y)
+            }
+
+            fn move_to_virtual(&$($mutability)? self, x: i32, y: i32) -> Result<(), Error> {
+                self.lock().move_to_virtual(x, y)
+            }
+
+            fn move_relative(
+                &$($mutability)? self,
+                x_offset: i32,
+                y_offset: i32,
+            ) -> Result<(), Error> {
+                self.lock().move_relative(x_offset, y_offset)
+            }
+
+            fn move_to_smooth(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                duration: Duration,
+                easing: &Easing,
+            ) -> Result<(), Error> {
+                self.lock().move_to_smooth(x, y, duration, easing)
+            }
+
+            fn move_to_smooth_cancellable(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                duration: Duration,
+                easing: &Easing,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                self.lock().move_to_smooth_cancellable(x, y, duration, easing, token)
+            }
+
+            fn get_position(&self) -> Result<(i32, i32), Error> {
+                self.lock().get_position()
+            }
+
+            fn backend(&self) -> Backend {
+                self.lock().backend()
+            }
+
+            fn supports(&self, capability: Capability) -> bool {
+                self.lock().supports(capability)
+            }
+
+            fn get_button_state(&self) -> Result<ButtonState, Error> {
+                self.lock().get_button_state()
+            }
+
+            fn set_coordinate_space(&mut self, space: CoordinateSpace) -> Result<(), Error> {
+                self.lock().set_coordinate_space(space)
+            }
+
+            fn set_motion_profile(&mut self, profile: MotionProfile) -> Result<(), Error> {
+                self.lock().set_motion_profile(profile)
+            }
+
+            fn press_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.lock().press_button(button)
+            }
+
+            fn release_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.lock().release_button(button)
+            }
+
+            fn click_button(&$($mutability)? self, button: &MouseButton) -> Result<(), Error> {
+                self.lock().click_button(button)
+            }
+
+            fn click_with_modifiers(
+                &$($mutability)? self,
+                button: &MouseButton,
+                modifiers: Modifiers,
+            ) -> Result<(), Error> {
+                self.lock().click_with_modifiers(button, modifiers)
+            }
+
+            fn click_at(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                button: &MouseButton,
+            ) -> Result<(), Error> {
+                self.lock().click_at(x, y, button)
+            }
+
+            fn click_at_with_settle(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                button: &MouseButton,
+                settle: Duration,
+            ) -> Result<(), Error> {
+                self.lock().click_at_with_settle(x, y, button, settle)
+            }
+
+            fn click_at_with_settle_cancellable(
+                &$($mutability)? self,
+                x: usize,
+                y: usize,
+                button: &MouseButton,
+                settle: Duration,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                self.lock()
+                    .click_at_with_settle_cancellable(x, y, button, settle, token)
+            }
+
+            fn double_click_button(
+                &$($mutability)? self,
+                button: &MouseButton,
+            ) -> Result<(), Error> {
+                self.lock().double_click_button(button)
+            }
+
+            fn multi_click(
+                &$($mutability)? self,
+                button: &MouseButton,
+                n: usize,
+                interval: Duration,
+            ) -> Result<(), Error> {
+                self.lock().multi_click(button, n, interval)
+            }
+
+            fn multi_click_cancellable(
+                &$($mutability)? self,
+                button: &MouseButton,
+                n: usize,
+                interval: Duration,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                self.lock().multi_click_cancellable(button, n, interval, token)
+            }
+
+            fn scroll_n(
+                &$($mutability)? self,
+                direction: &ScrollDirection,
+                count: u32,
+                delay_between: Duration,
+            ) -> Result<(), Error> {
+                self.lock().scroll_n(direction, count, delay_between)
+            }
+
+            fn scroll_n_cancellable(
+                &$($mutability)? self,
+                direction: &ScrollDirection,
+                count: u32,
+                delay_between: Duration,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                self.lock()
+                    .scroll_n_cancellable(direction, count, delay_between, token)
+            }
+
+            fn drag_to(
+                &$($mutability)? self,
+                button: &MouseButton,
+                from: (i32, i32),
+                to: (i32, i32),
+                step_delay: Duration,
+            ) -> Result<(), Error> {
+                self.lock().drag_to(button, from, to, step_delay)
+            }
+
+            fn drag_to_cancellable(
+                &$($mutability)? self,
+                button: &MouseButton,
+                from: (i32, i32),
+                to: (i32, i32),
+                step_delay: Duration,
+                token: &CancellationToken,
+            ) -> Result<(), Error> {
+                self.lock().drag_to_cancellable(button, from, to, step_delay, token)
+            }
+
+            fn scroll_wheel(
+                &$($mutability)? self,
+                direction: &ScrollDirection,
+            ) -> Result<(), Error> {
+                self.lock().scroll_wheel(direction)
+            }
+
+            fn scroll(&$($mutability)? self, dx: i32, dy: i32) -> Result<(), Error> {
+                self.lock().scroll(dx, dy)
+            }
+
+            fn scroll_wheel_hi_res(&$($mutability)? self, dx: i32, dy: i32) -> Result<(), Error> {
+                self.lock().scroll_wheel_hi_res(dx, dy)
+            }
+
+            fn scroll_smooth(
+                &$($mutability)? self,
+                dx: f64,
+                dy: f64,
+                phase: Option<ScrollPhase>,
+            ) -> Result<(), Error> {
+                self.lock().scroll_smooth(dx, dy, phase)
+            }
+
+            fn confine_cursor(&$($mutability)? self, rect: Rect) -> Result<(), Error> {
+                self.lock().confine_cursor(rect)
+            }
+
+            fn release_cursor(&$($mutability)? self) -> Result<(), Error> {
+                self.lock().release_cursor()
+            }
+
+            fn set_cursor_visible(&$($mutability)? self, visible: bool) -> Result<(), Error> {
+                self.lock().set_cursor_visible(visible)
+            }
+        }
+
+        impl MouseListener for SharedMouse {
+            fn hook(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().hook(callback)
+            }
+
+            fn hook_with_time(
+                &mut self,
+                callback: Box<dyn Fn(&EventWithTime) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().hook_with_time(callback)
+            }
+
+            fn hook_ext(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().hook_ext(callback)
+            }
+
+            fn hook_filtered(
+                &mut self,
+                mask: EventMask,
+                callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().hook_filtered(mask, callback)
+            }
+
+            fn hook_grab(
+                &mut self,
+                callback: Box<dyn Fn(&MouseEvent) -> EventAction + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().hook_grab(callback)
+            }
+
+            fn hook_with_priority(
+                &mut self,
+                priority: i32,
+                callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().hook_with_priority(priority, callback)
+            }
+
+            fn hook_device(
+                &mut self,
+                device: &MouseDeviceInfo,
+                callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().hook_device(device, callback)
+            }
+
+            fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+                self.lock().inject_event(event)
+            }
+
+            fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                self.lock().unhook(callback_id)
+            }
+
+            fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                self.lock().pause_hook(callback_id)
+            }
+
+            fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+                self.lock().resume_hook(callback_id)
+            }
+
+            fn unhook_all(&mut self) -> Result<(), Error> {
+                self.lock().unhook_all()
+            }
+
+            fn stop_listening(&mut self) -> Result<(), Error> {
+                self.lock().stop_listening()
+            }
+
+            fn listener_stats(&self) -> Result<ListenerStats, Error> {
+                self.lock().listener_stats()
+            }
+
+            fn on_listener_restarted(
+                &mut self,
+                callback: Box<dyn Fn() + Send>,
+            ) -> Result<CallbackId, Error> {
+                self.lock().on_listener_restarted(callback)
+            }
+
+            fn on_callback_panic(
+                &mut self,
+                unhook: bool,
+                handler: Box<dyn Fn(&CallbackPanic) + Send>,
+            ) -> Result<(), Error> {
+                self.lock().on_callback_panic(unhook, handler)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+shared_mouse_actions!(mut);
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+shared_mouse_actions!();
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockMouseManager;
+    use std::thread;
+
+    #[test]
+    fn share_between_threads() {
+        let mock = MockMouseManager::new();
+        let mut mouse = SharedMouse::new(Box::new(mock));
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut worker = mouse.clone_handle();
+                thread::spawn(move || worker.click_button(&MouseButton::Left))
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+        mouse.move_relative(5, 5).unwrap();
+        assert_eq!(mouse.get_position(), Ok((5, 5)));
+        assert_eq!(mouse.backend(), Backend::Mock);
+    }
+}
@@ -0,0 +1,206 @@
+///
+/// This module contains the async interface for the mouse events
+///
+/// The stream does not depend on a specific async runtime, it implements
+/// `futures::Stream` on top of the `hook` function and wakes up the task that
+/// awaits the next event whenever the callback receives one
+///
+use crate::common::{CallbackId, MouseEvent};
+use crate::hooks::{self, LockUnpoisoned};
+use futures_core::Stream;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+};
+
+/// The callback that feeds a stream
+type Feed = Box<dyn Fn(&MouseEvent) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct State {
+    events: VecDeque<MouseEvent>,
+    waker: Option<Waker>,
+    /// Set once the callback that feeds the stream is unhooked
+    closed: bool,
+}
+
+/// A stream of the mouse events that are received after it is created
+///
+/// The callback that feeds the stream unhooks itself with the first event
+/// after the stream is dropped
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use futures::StreamExt;
+/// use mouce::{Mouse, MouseActions};
+///
+/// async fn print_events(manager: &mut dyn MouseActions) {
+///     let mut stream = manager.event_stream().unwrap();
+///     while let Some(event) = stream.next().await {
+///         println!("New event: {:?}", event);
+///     }
+/// }
+/// ```
+pub struct EventStream {
+    state: Arc<Mutex<State>>,
+    callback_id: CallbackId,
+}
+
+impl EventStream {
+    /// Create the state of a new stream and the callback that feeds it
+    pub(crate) fn channel() -> (Arc<Mutex<State>>, Feed) {
+        let state = Arc::new(Mutex::new(State::default()));
+        let feeder = Feeder(Arc::downgrade(&state));
+        let callback = Box::new(move |event: &MouseEvent| {
+            let Some(state) = feeder.0.upgrade() else {
+                // The stream is dropped
+                hooks::unhook_current();
+                return;
+            };
+            let mut state = state.lock_unpoisoned();
+            state.events.push_back(event.clone());
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        (state, callback)
+    }
+
+    pub(crate) fn new(state: Arc<Mutex<State>>, callback_id: CallbackId) -> Self {
+        EventStream { state, callback_id }
+    }
+
+    /// Get the id of the callback that feeds this stream, it can be passed
+    /// to `unhook` to end the stream while it is still in use
+    pub fn callback_id(&self) -> CallbackId {
+        self.callback_id
+    }
+}
+
+impl Stream for EventStream {
+    type Item = MouseEvent;
+
+    /// Returns `None` once the callback is unhooked or the listener is gone
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<MouseEvent>> {
+        let mut state = self.state.lock_unpoisoned();
+        match state.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => {
+                if state.closed {
+                    return Poll::Ready(None);
+                }
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// The callback only holds a weak reference to the state, so that the
+/// events are not queued anymore once the stream is dropped
+struct Feeder(Weak<Mutex<State>>);
+
+impl Drop for Feeder {
+    fn drop(&mut self) {
+        // The callback is unhooked, wake up the stream so it can finish
+        if let Some(state) = self.0.upgrade() {
+            let mut state = state.lock_unpoisoned();
+            state.closed = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventStream;
+    use crate::common::{EventAction, EventMask, MouseButton, MouseEvent};
+    use crate::hooks::{Handler, Hooks};
+    use futures::{executor::block_on, StreamExt};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll, Wake, Waker},
+        thread,
+        time::Duration,
+    };
+
+    /// Counts how many times the stream wakes up its task
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn receive_events_until_unhooked() {
+        let (state, callback) = EventStream::channel();
+        let mut stream = EventStream::new(state, 0);
+        let wakes = Arc::new(CountingWaker::default());
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+
+        callback(&MouseEvent::Press(MouseButton::Left));
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            stream.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(MouseEvent::Press(MouseButton::Left)))
+        );
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+
+        // Dropping the callback is what unhook does
+        drop(callback);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 2);
+        assert_eq!(stream.poll_next_unpin(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn await_events_from_another_thread() {
+        let (state, callback) = EventStream::channel();
+        let stream = EventStream::new(state, 0);
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            callback(&MouseEvent::RelativeMove(1, 0));
+            callback(&MouseEvent::RelativeMove(2, 0));
+        });
+        // The stream ends once the thread drops the callback
+        let events: Vec<MouseEvent> = block_on(stream.collect());
+        sender.join().unwrap();
+        assert_eq!(
+            events,
+            vec![
+                MouseEvent::RelativeMove(1, 0),
+                MouseEvent::RelativeMove(2, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn unhook_once_dropped() {
+        let hooks = Hooks::new();
+        let (state, callback) = EventStream::channel();
+        hooks.insert(
+            0,
+            0,
+            EventMask::ALL,
+            Handler::Action(Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            })),
+        );
+        drop(EventStream::new(state, 0));
+        hooks.dispatch(&MouseEvent::RelativeMove(1, 0));
+        assert!(!hooks.remove(0));
+    }
+}
@@ -0,0 +1,145 @@
+///
+/// This module contains the channel interface for the mouse events
+///
+/// A subscription is the receiving end of a channel that is fed by a hooked
+/// callback, so the events can be polled or received in an existing loop
+/// instead of handling them in a `'static + Send` closure
+///
+use crate::common::{CallbackId, MouseEvent, MouseListener};
+use crate::error::Error;
+use crate::hooks;
+use std::{
+    ops::Deref,
+    sync::mpsc::{self, Receiver},
+};
+
+/// The callback that feeds a subscription
+type Feed = Box<dyn Fn(&MouseEvent) + Send + Sync>;
+
+/// The receiver of the mouse events that are received after it is created
+///
+/// It dereferences to a `std::sync::mpsc::Receiver`, the receiver is disconnected
+/// once the callback that feeds it is unhooked. Dropping the subscription (or the
+/// receiver that `into_receiver` takes) unhooks the callback with the next event,
+/// `unsubscribe` unhooks it right away
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::{Mouse, MouseListener};
+/// use std::time::Duration;
+///
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// let events = manager.subscribe().unwrap();
+/// loop {
+///     // Handle the events along with the other work of the loop
+///     for event in events.try_iter() {
+///         println!("New event: {:?}", event);
+///     }
+///     std::thread::sleep(Duration::from_millis(16));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: Receiver<MouseEvent>,
+    callback_id: CallbackId,
+}
+
+impl Subscription {
+    /// Create the receiver of a new subscription and the callback that feeds it
+    pub(crate) fn channel() -> (Receiver<MouseEvent>, Feed) {
+        let (tx, rx) = mpsc::channel();
+        let callback = Box::new(move |event: &MouseEvent| {
+            // The callback removes itself once the subscription is gone
+            if tx.send(event.clone()).is_err() {
+                hooks::unhook_current();
+            }
+        });
+        (rx, callback)
+    }
+
+    pub(crate) fn new(receiver: Receiver<MouseEvent>, callback_id: CallbackId) -> Self {
+        Subscription {
+            receiver,
+            callback_id,
+        }
+    }
+
+    /// Get the id of the callback that feeds this subscription, it can be
+    /// passed to `unhook` to stop listening once it is no longer needed
+    pub fn callback_id(&self) -> CallbackId {
+        self.callback_id
+    }
+
+    /// Take the receiver of the subscription
+    pub fn into_receiver(self) -> Receiver<MouseEvent> {
+        self.receiver
+    }
+
+    /// Unhook the callback that feeds this subscription from the given manager,
+    /// which the subscription is created by
+    pub fn unsubscribe<M: MouseListener + ?Sized>(self, manager: &mut M) -> Result<(), Error> {
+        manager.unhook(self.callback_id)
+    }
+}
+
+impl Deref for Subscription {
+    type Target = Receiver<MouseEvent>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Subscription;
+    use crate::common::{MouseButton, MouseEvent};
+    use std::sync::mpsc::TryRecvError;
+
+    #[test]
+    fn receive_events() {
+        let (receiver, callback) = Subscription::channel();
+        let subscription = Subscription::new(receiver, 3);
+        assert_eq!(subscription.callback_id(), 3);
+        assert_eq!(subscription.try_recv(), Err(TryRecvError::Empty));
+
+        callback(&MouseEvent::Press(MouseButton::Left));
+        callback(&MouseEvent::RelativeMove(1, 2));
+        assert_eq!(
+            subscription.try_iter().collect::<Vec<_>>(),
+            vec![
+                MouseEvent::Press(MouseButton::Left),
+                MouseEvent::RelativeMove(1, 2)
+            ]
+        );
+
+        // The callback is unhooked
+        drop(callback);
+        assert_eq!(subscription.recv(), Err(std::sync::mpsc::RecvError));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn unhook_dropped_subscriptions() {
+        use crate::{common::MouseListener, error::Error, mock::MockMouseManager};
+
+        let mut manager = MockMouseManager::new();
+        let subscription = manager.subscribe().unwrap();
+        let id = subscription.callback_id();
+        drop(subscription);
+        // The callback is still hooked until it receives an event
+        manager
+            .inject_event(MouseEvent::Press(MouseButton::Left))
+            .unwrap();
+        assert_eq!(manager.unhook(id), Err(Error::CallbackNotFound));
+
+        let subscription = manager.subscribe().unwrap();
+        let id = subscription.callback_id();
+        subscription.unsubscribe(&mut manager).unwrap();
+        assert_eq!(manager.unhook(id), Err(Error::CallbackNotFound));
+    }
+}
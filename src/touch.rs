@@ -0,0 +1,42 @@
+///
+/// This module contains the touch actions that are
+/// implemented by the touchscreen managers
+///
+use crate::error::Error;
+
+/// Simulates the contacts of a multitouch screen, every contact is
+/// identified with its slot so that many fingers can be down at once
+pub trait TouchActions {
+    /// Put a finger down on the given slot at the given `x`, `y` coordinates
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// use mouce::nix::UInputTouchManager;
+    /// use mouce::TouchActions;
+    ///
+    /// let mut manager = UInputTouchManager::new((0, 1920), (0, 1080)).unwrap();
+    /// // Pinch out with two fingers
+    /// assert_eq!(manager.touch_down(0, 900, 540), Ok(()));
+    /// assert_eq!(manager.touch_down(1, 1020, 540), Ok(()));
+    /// for step in 1..=10 {
+    ///     assert_eq!(manager.touch_move(0, 900 - step * 20, 540), Ok(()));
+    ///     assert_eq!(manager.touch_move(1, 1020 + step * 20, 540), Ok(()));
+    /// }
+    /// assert_eq!(manager.touch_up(0), Ok(()));
+    /// assert_eq!(manager.touch_up(1), Ok(()));
+    /// # }
+    /// ```
+    fn touch_down(&mut self, slot: usize, x: i32, y: i32) -> Result<(), Error>;
+    /// Move the finger of the given slot to the given `x`, `y` coordinates
+    fn touch_move(&mut self, slot: usize, x: i32, y: i32) -> Result<(), Error>;
+    /// Lift the finger of the given slot
+    fn touch_up(&mut self, slot: usize) -> Result<(), Error>;
+    /// Put a finger down and lift it at the given `x`, `y` coordinates
+    fn tap(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        self.touch_down(0, x, y)?;
+        self.touch_up(0)
+    }
+}
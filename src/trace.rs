@@ -0,0 +1,55 @@
+///
+/// This module contains the logging macros of the crate, which forward
+/// to `tracing` with the `tracing` feature and compile to nothing otherwise
+///
+/// The messages are format strings, so that the arguments are still
+/// checked and used without the feature
+///
+#[cfg(feature = "tracing")]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        ::tracing::debug!($($arg)*)
+    };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        ::tracing::warn!($($arg)*)
+    };
+}
+
+/// Enter a debug span that is exited when the returned guard is dropped
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($name:literal) => {
+        ::tracing::debug_span!($name).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! warning {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($name:literal) => {
+        $crate::trace::NoSpan
+    };
+}
+
+/// The guard of the spans without the `tracing` feature
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+pub(crate) use {debug, span, warning};
@@ -0,0 +1,304 @@
+///
+/// This module contains the transforms of the grabbed mouse events, e.g. the
+/// natural scrolling, the swapped buttons for the left hand or the scaled moves
+///
+/// The mice are grabbed with `hook_grab` like the `remap` module does, the
+/// events that a transform changes are consumed and their transformed versions
+/// are simulated with another manager, the rest are passed through untouched
+///
+use crate::common::{
+    CallbackId, EventAction, MouseActions, MouseButton, MouseEvent, MouseListener, ScrollDirection,
+};
+use crate::error::Error;
+use crate::hooks::LockUnpoisoned;
+use crate::recorder;
+use std::sync::{Arc, Mutex};
+
+/// The identifier of an installed transform, which is used to toggle or remove it
+pub type TransformId = usize;
+
+/// A change of the mouse events
+///
+/// The changed scrolls are simulated as the wheel detents, so the high
+/// resolution scrolls of the transformed devices are replaced by the detents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    /// Scroll in the opposite directions, i.e. the natural scrolling
+    InvertScroll,
+    /// Swap the left and the right buttons for the left-handed use
+    SwapButtons,
+    /// Multiply the relative moves by the given factor, the fractions
+    /// of the pixels are carried over to the next moves
+    ScaleMovement(f64),
+}
+
+impl Transform {
+    /// Transform the given event, `remainder` keeps the fractions of the scaled moves
+    fn apply(&self, event: MouseEvent, remainder: &mut (f64, f64)) -> MouseEvent {
+        match (self, event) {
+            (Transform::InvertScroll, MouseEvent::Scroll(direction)) => {
+                MouseEvent::Scroll(match direction {
+                    ScrollDirection::Up => ScrollDirection::Down,
+                    ScrollDirection::Down => ScrollDirection::Up,
+                    ScrollDirection::Left => ScrollDirection::Right,
+                    ScrollDirection::Right => ScrollDirection::Left,
+                })
+            }
+            (Transform::InvertScroll, MouseEvent::ScrollHiRes { dx, dy }) => {
+                MouseEvent::ScrollHiRes { dx: -dx, dy: -dy }
+            }
+            (Transform::InvertScroll, MouseEvent::Scroll2D { dx, dy }) => {
+                MouseEvent::Scroll2D { dx: -dx, dy: -dy }
+            }
+            (Transform::SwapButtons, MouseEvent::Press(button)) => {
+                MouseEvent::Press(swap_button(button))
+            }
+            (Transform::SwapButtons, MouseEvent::Release(button)) => {
+                MouseEvent::Release(swap_button(button))
+            }
+            (Transform::ScaleMovement(factor), MouseEvent::RelativeMove(x, y)) => {
+                let scaled = (
+                    x as f64 * factor + remainder.0,
+                    y as f64 * factor + remainder.1,
+                );
+                let moved = (scaled.0.trunc(), scaled.1.trunc());
+                *remainder = (scaled.0 - moved.0, scaled.1 - moved.1);
+                MouseEvent::RelativeMove(moved.0 as i32, moved.1 as i32)
+            }
+            (_, event) => event,
+        }
+    }
+}
+
+fn swap_button(button: MouseButton) -> MouseButton {
+    match button {
+        MouseButton::Left => MouseButton::Right,
+        MouseButton::Right => MouseButton::Left,
+        button => button,
+    }
+}
+
+/// The installed transforms in the order that they are applied
+#[derive(Default)]
+struct Pipeline {
+    transforms: Vec<(TransformId, Transform, bool)>,
+    remainder: (f64, f64),
+    /// The held buttons and the buttons that their presses became, so that the
+    /// releases match the presses even if the transforms change meanwhile
+    pressed: Vec<(MouseButton, MouseButton)>,
+}
+
+impl Pipeline {
+    /// Transform the given event with the enabled transforms and simulate it with
+    /// the given output manager if it changed, returns whether the event is consumed
+    fn apply(&mut self, event: &MouseEvent, output: &mut (dyn MouseActions + Send)) -> EventAction {
+        if let MouseEvent::Release(button) = event {
+            return self.release(button, output);
+        }
+        let transformed = self.transform(event);
+        if let (MouseEvent::Press(button), MouseEvent::Press(pressed)) = (event, &transformed) {
+            self.pressed.retain(|(from, _)| from != button);
+            self.pressed.push((button.clone(), pressed.clone()));
+        }
+        if transformed == *event {
+            return EventAction::PassThrough;
+        }
+        // The moves that are scaled down to nothing wait for the next ones
+        if transformed != MouseEvent::RelativeMove(0, 0) {
+            let _ = recorder::simulate(output, &transformed);
+        }
+        EventAction::Consume
+    }
+
+    /// Release the button that the press of the given button became, the buttons that
+    /// are pressed before the transforms start are released as they are
+    fn release(
+        &mut self,
+        button: &MouseButton,
+        output: &mut (dyn MouseActions + Send),
+    ) -> EventAction {
+        let Some(index) = self.pressed.iter().position(|(from, _)| from == button) else {
+            return EventAction::PassThrough;
+        };
+        let (_, pressed) = self.pressed.remove(index);
+        if pressed == *button {
+            return EventAction::PassThrough;
+        }
+        let _ = output.release_button(&pressed);
+        EventAction::Consume
+    }
+
+    fn transform(&mut self, event: &MouseEvent) -> MouseEvent {
+        let mut transformed = event.clone();
+        for (_, transform, enabled) in self.transforms.iter() {
+            if *enabled {
+                transformed = transform.apply(transformed, &mut self.remainder);
+            }
+        }
+        transformed
+    }
+}
+
+/// Applies the installed transforms to the events of the grabbed mice until it is stopped
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::transforms::{Transform, Transforms};
+/// use mouce::Mouse;
+///
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// let output = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// let mut transforms = Transforms::start(manager.as_mut(), output).unwrap();
+/// let natural = transforms.add(Transform::InvertScroll);
+/// transforms.add(Transform::ScaleMovement(1.5));
+/// // Scroll the usual way again
+/// transforms.set_enabled(natural, false).unwrap();
+/// transforms.stop(manager.as_mut()).unwrap();
+/// ```
+pub struct Transforms {
+    pipeline: Arc<Mutex<Pipeline>>,
+    callback_id: CallbackId,
+    transform_counter: TransformId,
+}
+
+impl Transforms {
+    /// Grab the mice that the given manager listens to, the transformed events are
+    /// simulated with the `output` manager which must not be grabbed
+    ///
+    /// No transform is installed yet, so the events are passed through until `add`
+    pub fn start<M: MouseListener + ?Sized>(
+        manager: &mut M,
+        output: Box<dyn MouseActions + Send>,
+    ) -> Result<Self, Error> {
+        let pipeline = Arc::new(Mutex::new(Pipeline::default()));
+        let output = Mutex::new(output);
+
+        let applied = pipeline.clone();
+        let callback_id = manager.hook_grab(Box::new(move |event| {
+            applied
+                .lock_unpoisoned()
+                .apply(event, output.lock_unpoisoned().as_mut())
+        }))?;
+
+        Ok(Transforms {
+            pipeline,
+            callback_id,
+            transform_counter: 0,
+        })
+    }
+
+    /// Install the given transform after the others, it is enabled right away
+    pub fn add(&mut self, transform: Transform) -> TransformId {
+        let id = self.transform_counter;
+        self.pipeline
+            .lock_unpoisoned()
+            .transforms
+            .push((id, transform, true));
+        self.transform_counter += 1;
+        id
+    }
+
+    /// Enable or disable the transform with the given `TransformId`
+    pub fn set_enabled(&self, transform_id: TransformId, enabled: bool) -> Result<(), Error> {
+        match self
+            .pipeline
+            .lock_unpoisoned()
+            .transforms
+            .iter_mut()
+            .find(|(id, _, _)| *id == transform_id)
+        {
+            Some((_, _, toggled)) => {
+                *toggled = enabled;
+                Ok(())
+            }
+            None => Err(Error::CallbackNotFound),
+        }
+    }
+
+    /// Remove the transform with the given `TransformId`
+    pub fn remove(&mut self, transform_id: TransformId) -> Result<(), Error> {
+        let mut pipeline = self.pipeline.lock_unpoisoned();
+        let count = pipeline.transforms.len();
+        pipeline.transforms.retain(|(id, _, _)| *id != transform_id);
+        if pipeline.transforms.len() != count {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    /// Stop transforming, the mice are released once the manager has no other grab
+    pub fn stop<M: MouseListener + ?Sized>(self, manager: &mut M) -> Result<(), Error> {
+        manager.unhook(self.callback_id)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::{Pipeline, Transform};
+    use crate::common::{EventAction, MouseButton, MouseEvent, ScrollDirection};
+    use crate::mock::{MockCall, MockMouseManager};
+
+    #[test]
+    fn transform_events() {
+        let mut pipeline = Pipeline {
+            transforms: vec![
+                (0, Transform::InvertScroll, true),
+                (1, Transform::SwapButtons, true),
+                (2, Transform::ScaleMovement(0.5), true),
+            ],
+            ..Pipeline::default()
+        };
+        let mut output = MockMouseManager::new();
+        let mut apply = |event| pipeline.apply(&event, &mut output);
+
+        assert_eq!(
+            apply(MouseEvent::Scroll(ScrollDirection::Up)),
+            EventAction::Consume
+        );
+        assert_eq!(
+            apply(MouseEvent::Press(MouseButton::Left)),
+            EventAction::Consume
+        );
+        // The halves of the pixels add up
+        assert_eq!(apply(MouseEvent::RelativeMove(3, 1)), EventAction::Consume);
+        assert_eq!(apply(MouseEvent::RelativeMove(3, 3)), EventAction::Consume);
+        // The events that no transform changes are passed through
+        assert_eq!(
+            apply(MouseEvent::Press(MouseButton::Middle)),
+            EventAction::PassThrough
+        );
+        assert_eq!(
+            output.calls(),
+            vec![
+                MockCall::ScrollWheel(ScrollDirection::Down),
+                MockCall::Press(MouseButton::Right),
+                MockCall::MoveRelative(1, 0),
+                MockCall::MoveRelative(2, 2),
+            ]
+        );
+
+        // The release matches the press even once the buttons are not swapped anymore
+        pipeline.transforms[1].2 = false;
+        let mut output = MockMouseManager::new();
+        let event = MouseEvent::Release(MouseButton::Left);
+        assert_eq!(pipeline.apply(&event, &mut output), EventAction::Consume);
+        assert_eq!(output.calls(), vec![MockCall::Release(MouseButton::Right)]);
+        // The buttons that are passed through are released the same way
+        let event = MouseEvent::Release(MouseButton::Middle);
+        assert_eq!(
+            pipeline.apply(&event, &mut output),
+            EventAction::PassThrough
+        );
+
+        // The disabled transforms are skipped
+        pipeline.transforms[0].2 = false;
+        let mut output = MockMouseManager::new();
+        let event = MouseEvent::Scroll(ScrollDirection::Up);
+        assert_eq!(
+            pipeline.apply(&event, &mut output),
+            EventAction::PassThrough
+        );
+    }
+}
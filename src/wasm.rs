@@ -0,0 +1,669 @@
+///
+/// This module contains the mouse and keyboard managers for the
+/// browsers (wasm32), so that the same code can be built for the web
+///
+/// The actions are synthetic DOM events that are dispatched at the element
+/// under the given point of the viewport, the browsers do not move the real
+/// cursor for them. The hooks are DOM listeners on the document, which receive
+/// the events of the page including the synthetic ones
+///
+use crate::cancel::CancellationToken;
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, Easing, EventAction, EventMask,
+    MouseActions, MouseButton, MouseController, MouseEvent, MouseListener, Propagation,
+    ScrollDirection, ScrollPhase,
+};
+use crate::error::Error;
+use crate::hooks::{Dispatcher, Handler, Hooks, LockUnpoisoned, DEFAULT_PRIORITY};
+use crate::keyboard::{Key, KeyboardActions, ModifierKeys, Modifiers};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{
+    AddEventListenerOptions, Document, EventTarget, KeyboardEvent, KeyboardEventInit,
+    MouseEventInit, WheelEvent, WheelEventInit,
+};
+
+/// The lines that the browsers scroll for a wheel detent
+const LINES_PER_DETENT: f64 = 3.;
+/// The DOM events that the hooks listen to
+const LISTENED_EVENTS: [&str; 4] = ["mousemove", "mousedown", "mouseup", "wheel"];
+
+type DomListener = Closure<dyn FnMut(web_sys::Event)>;
+
+thread_local! {
+    /// The DOM listeners of the managers by their ids, the closures can not leave
+    /// the thread of the page, so the managers only keep their ids
+    static LISTENERS: RefCell<HashMap<usize, Vec<(&'static str, DomListener)>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// The ids of the managers in `LISTENERS`
+static MANAGER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Simulates the mouse with the synthetic DOM events of the page
+///
+/// The coordinates are the CSS pixels of the viewport, the same as `clientX`
+/// and `clientY` of the DOM events. `get_position` returns the last position that
+/// the manager moved to, or that the hooks received once they are listening
+pub struct WasmMouseManager {
+    id: usize,
+    position: Arc<Mutex<(i32, i32)>>,
+    /// The buttons that are pressed and not released yet
+    pressed: Mutex<Vec<MouseButton>>,
+    callbacks: Arc<Hooks<MouseEvent>>,
+    callback_counter: CallbackId,
+    /// The keyboard manager that holds the modifiers of `click_with_modifiers`
+    modifier_keys: ModifierKeys,
+}
+
+impl WasmMouseManager {
+    pub fn new() -> Result<Box<dyn MouseActions + Send>, Error> {
+        // Fail early outside of a page, e.g. in a web worker
+        document()?;
+        Ok(Box::new(WasmMouseManager {
+            id: MANAGER_COUNTER.fetch_add(1, Ordering::Relaxed),
+            position: Arc::new(Mutex::new((0, 0))),
+            pressed: Mutex::new(Vec::new()),
+            callbacks: Arc::new(Hooks::new()),
+            callback_counter: 0,
+            modifier_keys: ModifierKeys::default(),
+        }))
+    }
+
+    /// Get the initial values of the mouse events at the current position
+    fn event_init(&self) -> MouseEventInit {
+        let (x, y) = *self.position.lock_unpoisoned();
+        let init = MouseEventInit::new();
+        init.set_bubbles(true);
+        init.set_cancelable(true);
+        init.set_composed(true);
+        init.set_client_x(x);
+        init.set_client_y(y);
+        init.set_buttons(buttons_mask(&self.pressed.lock_unpoisoned()));
+        init
+    }
+
+    /// Dispatch the given mouse event at the element under the current position
+    fn dispatch_mouse_event(&self, event_type: &str, init: &MouseEventInit) -> Result<(), Error> {
+        let event = web_sys::MouseEvent::new_with_mouse_event_init_dict(event_type, init)
+            .map_err(|_| Error::CustomError(format!("failed to create the {event_type} event")))?;
+        self.target()?.dispatch_event(&event).map_err(|_| {
+            Error::CustomError(format!("failed to dispatch the {event_type} event"))
+        })?;
+        Ok(())
+    }
+
+    fn dispatch_wheel_event(&self, dx: f64, dy: f64, delta_mode: u32) -> Result<(), Error> {
+        let (x, y) = *self.position.lock_unpoisoned();
+        let init = WheelEventInit::new();
+        init.set_bubbles(true);
+        init.set_cancelable(true);
+        init.set_composed(true);
+        init.set_client_x(x);
+        init.set_client_y(y);
+        init.set_buttons(buttons_mask(&self.pressed.lock_unpoisoned()));
+        // The DOM scrolls down and right for the positive deltas
+        init.set_delta_x(dx);
+        init.set_delta_y(-dy);
+        init.set_delta_mode(delta_mode);
+        let event = WheelEvent::new_with_event_init_dict("wheel", &init)
+            .map_err(|_| Error::CustomError("failed to create the wheel event".to_string()))?;
+        self.target()?
+            .dispatch_event(&event)
+            .map_err(|_| Error::CustomError("failed to dispatch the wheel event".to_string()))?;
+        Ok(())
+    }
+
+    /// Get the element under the current position, or the document
+    /// if the position is outside of the viewport
+    fn target(&self) -> Result<EventTarget, Error> {
+        let document = document()?;
+        let (x, y) = *self.position.lock_unpoisoned();
+        Ok(match document.element_from_point(x as f32, y as f32) {
+            Some(element) => element.into(),
+            None => document.into(),
+        })
+    }
+
+    fn button_event(&self, button: &MouseButton, is_press: bool) -> Result<(), Error> {
+        let code = button_code(button)?;
+        {
+            let mut pressed = self.pressed.lock_unpoisoned();
+            pressed.retain(|held| held != button);
+            if is_press {
+                pressed.push(button.clone());
+            }
+        }
+        let init = self.event_init();
+        init.set_button(code);
+        if is_press {
+            return self.dispatch_mouse_event("mousedown", &init);
+        }
+        self.dispatch_mouse_event("mouseup", &init)?;
+        // The browsers do not follow the synthetic releases with the clicks
+        let click = if *button == MouseButton::Left {
+            "click"
+        } else {
+            "auxclick"
+        };
+        self.dispatch_mouse_event(click, &init)
+    }
+
+    /// Start the DOM listeners if they are not running yet and add the
+    /// given handler in the order of the given priority
+    fn add_handler(
+        &mut self,
+        priority: i32,
+        handler: Handler<MouseEvent>,
+    ) -> Result<CallbackId, Error> {
+        if !LISTENERS.with(|listeners| listeners.borrow().contains_key(&self.id)) {
+            self.start_listener()?;
+        }
+
+        let id = self.callback_counter;
+        self.callbacks.insert(id, priority, EventMask::ALL, handler);
+        self.callback_counter += 1;
+        Ok(id)
+    }
+
+    fn start_listener(&self) -> Result<(), Error> {
+        let document = document()?;
+        // The capturing listeners receive the events before the page does, so that
+        // the consumed events do not reach it. The wheel listener is not passive,
+        // otherwise the browsers ignore `preventDefault`
+        let options = AddEventListenerOptions::new();
+        options.set_capture(true);
+        options.set_passive(false);
+
+        // The listeners share one dispatcher, the page runs them on a single thread
+        let dispatcher: Rc<RefCell<Dispatcher<MouseEvent>>> =
+            Rc::new(RefCell::new(self.callbacks.dispatcher()));
+        let mut added = Vec::new();
+        for event_type in LISTENED_EVENTS {
+            let callbacks = self.callbacks.clone();
+            let dispatcher = dispatcher.clone();
+            let position = self.position.clone();
+            let listener = DomListener::new(move |event: web_sys::Event| {
+                let Some(mouse_event) = map_event(&event) else {
+                    return;
+                };
+                if let MouseEvent::AbsoluteMove(x, y) = mouse_event {
+                    *position.lock_unpoisoned() = (x, y);
+                }
+                let action = match dispatcher.try_borrow_mut() {
+                    Ok(mut dispatcher) => dispatcher.dispatch(&mouse_event).0,
+                    // A callback that dispatches a synthetic event runs the listener again
+                    Err(_) => callbacks.dispatch(&mouse_event),
+                };
+                if action == EventAction::Consume {
+                    event.prevent_default();
+                    event.stop_propagation();
+                }
+            });
+            let result = document.add_event_listener_with_callback_and_add_event_listener_options(
+                event_type,
+                listener.as_ref().unchecked_ref(),
+                &options,
+            );
+            if result.is_err() {
+                remove_listeners(&document, added);
+                return Err(Error::CustomError(format!(
+                    "failed to listen to the {event_type} events"
+                )));
+            }
+            added.push((event_type, listener));
+        }
+        LISTENERS.with(|listeners| listeners.borrow_mut().insert(self.id, added));
+        Ok(())
+    }
+}
+
+impl Drop for WasmMouseManager {
+    fn drop(&mut self) {
+        let _ = self.stop_listening();
+    }
+}
+
+/// Get the document of the page
+fn document() -> Result<Document, Error> {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| {
+            Error::CustomError("there is no document to dispatch the events".to_string())
+        })
+}
+
+/// Remove the given DOM listeners from the document, which drops their closures
+fn remove_listeners(document: &Document, listeners: Vec<(&'static str, DomListener)>) {
+    for (event_type, listener) in listeners {
+        let _ = document.remove_event_listener_with_callback_and_bool(
+            event_type,
+            listener.as_ref().unchecked_ref(),
+            true,
+        );
+    }
+}
+
+/// Map the given button to the `button` value of the DOM events
+///
+/// The DOM only knows two extra buttons, the back and the forward buttons.
+/// Side and Extra are the evdev names of the same buttons on most mice
+fn button_code(button: &MouseButton) -> Result<i16, Error> {
+    match button {
+        MouseButton::Left => Ok(0),
+        MouseButton::Middle => Ok(1),
+        MouseButton::Right => Ok(2),
+        MouseButton::Back | MouseButton::Side => Ok(3),
+        MouseButton::Forward | MouseButton::Extra => Ok(4),
+        MouseButton::Task => Err(Error::Unsupported(Backend::Web)),
+    }
+}
+
+/// Map the `button` value of a DOM event to the library's MouseButton
+fn map_button(code: i16) -> Option<MouseButton> {
+    match code {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        3 => Some(MouseButton::Back),
+        4 => Some(MouseButton::Forward),
+        _ => None,
+    }
+}
+
+/// Get the `buttons` bitmask of the DOM events for the given pressed buttons
+fn buttons_mask(pressed: &[MouseButton]) -> u16 {
+    pressed
+        .iter()
+        .map(|button| match button_code(button) {
+            // The bits of the middle and the right buttons are swapped in the mask
+            Ok(1) => 4,
+            Ok(2) => 2,
+            Ok(code) => 1 << code,
+            Err(_) => 0,
+        })
+        .fold(0, |mask, bit| mask | bit)
+}
+
+/// Map the given DOM event to the library's MouseEvent
+fn map_event(event: &web_sys::Event) -> Option<MouseEvent> {
+    if let Some(wheel) = event.dyn_ref::<WheelEvent>() {
+        // The DOM scrolls down for the positive deltas
+        return match wheel.delta_y() {
+            dy if dy < 0. => Some(MouseEvent::Scroll(ScrollDirection::Up)),
+            dy if dy > 0. => Some(MouseEvent::Scroll(ScrollDirection::Down)),
+            _ => None,
+        };
+    }
+    let mouse = event.dyn_ref::<web_sys::MouseEvent>()?;
+    match event.type_().as_str() {
+        "mousemove" => Some(MouseEvent::AbsoluteMove(mouse.client_x(), mouse.client_y())),
+        "mousedown" => map_button(mouse.button()).map(MouseEvent::Press),
+        "mouseup" => map_button(mouse.button()).map(MouseEvent::Release),
+        _ => None,
+    }
+}
+
+impl MouseController for WasmMouseManager {
+    fn move_to(&self, x: usize, y: usize) -> Result<(), Error> {
+        let (old_x, old_y) =
+            std::mem::replace(&mut *self.position.lock_unpoisoned(), (x as i32, y as i32));
+        let init = self.event_init();
+        init.set_movement_x(x as i32 - old_x);
+        init.set_movement_y(y as i32 - old_y);
+        self.dispatch_mouse_event("mousemove", &init)
+    }
+
+    fn move_relative(&self, x_offset: i32, y_offset: i32) -> Result<(), Error> {
+        let (x, y) = *self.position.lock_unpoisoned();
+        self.move_to(
+            (x + x_offset).max(0) as usize,
+            (y + y_offset).max(0) as usize,
+        )
+    }
+
+    fn get_position(&self) -> Result<(i32, i32), Error> {
+        Ok(*self.position.lock_unpoisoned())
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Web
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::GetPosition
+                | Capability::ButtonState
+                | Capability::HiResScroll
+                | Capability::SmoothScroll
+                | Capability::InjectEvent
+                | Capability::PauseHook
+                | Capability::CallbackPanics
+        )
+    }
+
+    fn get_button_state(&self) -> Result<ButtonState, Error> {
+        Ok(ButtonState {
+            pressed: self.pressed.lock_unpoisoned().clone(),
+        })
+    }
+
+    fn press_button(&self, button: &MouseButton) -> Result<(), Error> {
+        self.button_event(button, true)
+    }
+
+    fn release_button(&self, button: &MouseButton) -> Result<(), Error> {
+        self.button_event(button, false)
+    }
+
+    fn click_with_modifiers(
+        &self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        self.modifier_keys
+            .hold(modifiers, || self.click_button(button))
+    }
+
+    fn scroll_wheel(&self, direction: &ScrollDirection) -> Result<(), Error> {
+        match direction {
+            ScrollDirection::Up => self.scroll(0, 1),
+            ScrollDirection::Down => self.scroll(0, -1),
+        }
+    }
+
+    fn scroll(&self, dx: i32, dy: i32) -> Result<(), Error> {
+        self.dispatch_wheel_event(
+            f64::from(dx) * LINES_PER_DETENT,
+            f64::from(dy) * LINES_PER_DETENT,
+            WheelEvent::DOM_DELTA_LINE,
+        )
+    }
+
+    fn scroll_wheel_hi_res(&self, dx: i32, dy: i32) -> Result<(), Error> {
+        self.dispatch_wheel_event(
+            f64::from(dx) * LINES_PER_DETENT / 120.,
+            f64::from(dy) * LINES_PER_DETENT / 120.,
+            WheelEvent::DOM_DELTA_LINE,
+        )
+    }
+
+    fn scroll_smooth(&self, dx: f64, dy: f64, _phase: Option<ScrollPhase>) -> Result<(), Error> {
+        // The DOM has no scroll phases
+        self.dispatch_wheel_event(dx, dy, WheelEvent::DOM_DELTA_PIXEL)
+    }
+
+    // wasm32-unknown-unknown can not sleep, so the actions that wait between
+    // their steps fail instead of panicking in the default methods
+
+    fn move_to_smooth(
+        &self,
+        _x: usize,
+        _y: usize,
+        _duration: Duration,
+        _easing: &Easing,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn move_to_smooth_cancellable(
+        &self,
+        _x: usize,
+        _y: usize,
+        _duration: Duration,
+        _easing: &Easing,
+        _token: &CancellationToken,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn click_at(&self, x: usize, y: usize, button: &MouseButton) -> Result<(), Error> {
+        self.move_to(x, y)?;
+        self.click_button(button)
+    }
+
+    fn click_at_with_settle(
+        &self,
+        x: usize,
+        y: usize,
+        button: &MouseButton,
+        settle: Duration,
+    ) -> Result<(), Error> {
+        if !settle.is_zero() {
+            return Err(Error::NotImplemented);
+        }
+        self.click_at(x, y, button)
+    }
+
+    fn click_at_with_settle_cancellable(
+        &self,
+        x: usize,
+        y: usize,
+        button: &MouseButton,
+        settle: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        self.click_at_with_settle(x, y, button, settle)
+    }
+
+    fn multi_click(
+        &self,
+        button: &MouseButton,
+        n: usize,
+        _interval: Duration,
+    ) -> Result<(), Error> {
+        match n {
+            0 => Ok(()),
+            1 => self.click_button(button),
+            _ => Err(Error::NotImplemented),
+        }
+    }
+
+    fn multi_click_cancellable(
+        &self,
+        button: &MouseButton,
+        n: usize,
+        interval: Duration,
+        token: &CancellationToken,
+    ) -> Result<(), Error> {
+        token.check()?;
+        self.multi_click(button, n, interval)
+    }
+
+    fn drag_to(
+        &self,
+        _button: &MouseButton,
+        _from: (i32, i32),
+        _to: (i32, i32),
+        _step_delay: Duration,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn drag_to_cancellable(
+        &self,
+        _button: &MouseButton,
+        _from: (i32, i32),
+        _to: (i32, i32),
+        _step_delay: Duration,
+        _token: &CancellationToken,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn scroll_n(
+        &self,
+        _direction: &ScrollDirection,
+        _count: u32,
+        _delay_between: Duration,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn scroll_n_cancellable(
+        &self,
+        _direction: &ScrollDirection,
+        _count: u32,
+        _delay_between: Duration,
+        _token: &CancellationToken,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+}
+
+impl MouseListener for WasmMouseManager {
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(
+            DEFAULT_PRIORITY,
+            Handler::Action(Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            })),
+        )
+    }
+
+    fn hook_with_priority(
+        &mut self,
+        priority: i32,
+        callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(priority, Handler::Propagate(callback))
+    }
+
+    fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+        Ok(self.callbacks.dispatch(&event))
+    }
+
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.remove(callback_id) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, true) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        if self.callbacks.set_paused(callback_id, false) {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    fn on_callback_panic(
+        &mut self,
+        unhook: bool,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
+        self.callbacks.set_panic_handler(handler, unhook);
+        Ok(())
+    }
+
+    fn unhook_all(&mut self) -> Result<(), Error> {
+        self.callbacks.clear();
+        Ok(())
+    }
+
+    fn stop_listening(&mut self) -> Result<(), Error> {
+        let listeners = LISTENERS.with(|listeners| listeners.borrow_mut().remove(&self.id));
+        if let Some(listeners) = listeners {
+            remove_listeners(&document()?, listeners);
+        }
+        Ok(())
+    }
+}
+
+/// Simulates the keyboard with the synthetic DOM events of the page, which are
+/// dispatched at the focused element
+///
+/// The browsers do not type the text of the synthetic key events into the
+/// inputs, only the listeners of the page receive them
+pub struct WasmKeyboardManager {}
+
+impl WasmKeyboardManager {
+    pub fn new() -> Result<Box<dyn KeyboardActions + Send>, Error> {
+        document()?;
+        Ok(Box::new(WasmKeyboardManager {}))
+    }
+
+    fn key_event(&self, key: &Key, event_type: &str) -> Result<(), Error> {
+        let init = KeyboardEventInit::new();
+        init.set_bubbles(true);
+        init.set_cancelable(true);
+        init.set_composed(true);
+        init.set_key(&key_name(key)?);
+        let event = KeyboardEvent::new_with_keyboard_event_init_dict(event_type, &init)
+            .map_err(|_| Error::CustomError(format!("failed to create the {event_type} event")))?;
+        let document = document()?;
+        let target: EventTarget = match document.active_element() {
+            Some(element) => element.into(),
+            None => document.into(),
+        };
+        target.dispatch_event(&event).map_err(|_| {
+            Error::CustomError(format!("failed to dispatch the {event_type} event"))
+        })?;
+        Ok(())
+    }
+}
+
+/// Map the given key to the `key` value of the DOM events
+fn key_name(key: &Key) -> Result<String, Error> {
+    let name = match key {
+        Key::Char(ch) if ch.is_ascii_graphic() => return Ok(ch.to_string()),
+        Key::Enter => "Enter",
+        Key::Escape => "Escape",
+        Key::Backspace => "Backspace",
+        Key::Tab => "Tab",
+        Key::Space => " ",
+        Key::Shift => "Shift",
+        Key::Control => "Control",
+        Key::Alt => "Alt",
+        Key::Meta => "Meta",
+        Key::CapsLock => "CapsLock",
+        Key::Left => "ArrowLeft",
+        Key::Right => "ArrowRight",
+        Key::Up => "ArrowUp",
+        Key::Down => "ArrowDown",
+        Key::Home => "Home",
+        Key::End => "End",
+        Key::PageUp => "PageUp",
+        Key::PageDown => "PageDown",
+        Key::Insert => "Insert",
+        Key::Delete => "Delete",
+        Key::F(n @ 1..=12) => return Ok(format!("F{n}")),
+        _ => return Err(Error::Unsupported(Backend::Web)),
+    };
+    Ok(name.to_string())
+}
+
+impl KeyboardActions for WasmKeyboardManager {
+    fn press_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, "keydown")
+    }
+
+    fn release_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, "keyup")
+    }
+}
@@ -1,46 +1,125 @@
 ///
-/// This module contains the mouse action functions
-/// for the windows opearting system
+/// This module contains the mouse and keyboard action
+/// functions for the windows opearting system
 /// Uses the User32 system library
 ///
-use crate::common::{CallbackId, MouseActions, MouseButton, MouseEvent, ScrollDirection};
+use crate::common::{
+    Backend, ButtonState, CallbackId, CallbackPanic, Capability, EventAction, EventMask, Motion,
+    MotionProfile, MouseActions, MouseButton, MouseController, MouseEvent, MouseEventExt,
+    MouseListener, Propagation, ScrollDirection,
+};
+use crate::detect::DetectorConfig;
+use crate::devices::MouseDeviceInfo;
+use crate::display::Rect;
+use crate::error::Error;
+use crate::hooks::{
+    invoke_all, CallbackEntries, Dispatcher, Handler, Hooks, LockUnpoisoned, DEFAULT_PRIORITY,
+};
+use crate::keyboard::{Key, KeyboardActions, ModifierKeys, Modifiers};
+use crate::trace;
 use std::{
+    cell::RefCell,
     collections::HashMap,
-    io::{Error, ErrorKind, Result},
-    mem::size_of,
-    os::raw::{c_int, c_long, c_short, c_uint, c_ulong, c_ushort},
-    ptr::null_mut,
-    sync::Mutex,
-    thread,
+    ffi::{c_void, CStr, CString},
+    mem::{size_of, zeroed},
+    os::raw::{c_char, c_int, c_long, c_short, c_uint, c_ulong, c_ushort},
+    ptr::{null, null_mut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
 };
 
 static mut HOOK: HHook = null_mut();
-static mut CALLBACKS: Option<Mutex<HashMap<CallbackId, Box<dyn Fn(&MouseEvent) + Send>>>> = None;
+static mut CALLBACKS: Option<Arc<Hooks<MouseEventExt>>> = None;
+/// The callbacks of `on_listener_restarted`, they share the ids with the other callbacks
+static RESTART_CALLBACKS: CallbackEntries<dyn Fn() + Send> = Mutex::new(Vec::new());
+/// How many events the hook has received, the watchdog compares it between its checks
+static HOOK_EVENTS: AtomicUsize = AtomicUsize::new(0);
+/// How many probes of the watchdog the hook has received
+static HOOK_PROBES: AtomicUsize = AtomicUsize::new(0);
+/// How often the hook thread checks that the hook still receives the moves of the cursor
+const WATCHDOG_INTERVAL_MS: c_uint = 500;
+/// The extra info of the moves that the watchdog sends, so the hook tells them apart
+const PROBE_EXTRA_INFO: usize = 0x6d6f_7563;
+
+/// The sources of the events that the listener of `WindowsMouseManager` receives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowsListener {
+    /// The low level mouse hook (`WH_MOUSE_LL`), which reports the moves of the
+    /// cursor. Windows removes the hook silently if it takes too long to return,
+    /// the listener installs it again and invokes the callbacks of `on_listener_restarted`
+    Hook,
+    /// The raw input of the mice (`WM_INPUT`), which reports the moves of the devices
+    /// before the pointer acceleration, and the device of every event
+    RawInput,
+}
 
 pub struct WindowsMouseManager {
     callback_counter: CallbackId,
-    is_listening: bool,
+    listener_kind: WindowsListener,
+    /// The id and the handle of the thread that runs the listener
+    listener: Option<(DWord, JoinHandle<()>)>,
+    /// Maps the offsets of the relative moves to the moved pixels, the moves
+    /// only borrow the manager so it is locked
+    motion: Mutex<Motion>,
+    /// The keyboard manager that holds the modifiers of `click_with_modifiers`
+    modifier_keys: ModifierKeys,
 }
 
 impl WindowsMouseManager {
-    pub fn new() -> Result<Box<dyn MouseActions>> {
+    pub fn new() -> Result<Box<dyn MouseActions + Send>, Error> {
+        Self::with_listener(WindowsListener::Hook)
+    }
+
+    /// Create the manager with the given source of the mouse events, see `WindowsListener`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mouce::windows::{WindowsListener, WindowsMouseManager};
+    ///
+    /// let mut manager = WindowsMouseManager::with_listener(WindowsListener::RawInput).unwrap();
+    /// manager
+    ///     .hook_ext(Box::new(|e| println!("{:?} from {}", e.event, e.device_name)))
+    ///     .unwrap();
+    /// ```
+    pub fn with_listener(kind: WindowsListener) -> Result<Box<dyn MouseActions + Send>, Error> {
         Ok(Box::new(WindowsMouseManager {
             callback_counter: 0,
-            is_listening: false,
+            listener_kind: kind,
+            listener: None,
+            motion: Mutex::default(),
+            modifier_keys: ModifierKeys::default(),
         }))
     }
 
-    fn send_input(&self, event: WindowsMouseEvent, mouse_data: i32) -> Result<()> {
+    fn send_input(&self, event: WindowsMouseEvent, mouse_data: i32) -> Result<(), Error> {
         let (x, y) = self.get_position()?;
+        self.send_mouse_input(x, y, mouse_data, event as u32)
+    }
+
+    fn send_mouse_input(
+        &self,
+        dx: c_long,
+        dy: c_long,
+        mouse_data: i32,
+        flags: DWord,
+    ) -> Result<(), Error> {
         let mut input = Input {
             r#type: INPUT_MOUSE,
-            mi: MouseInput {
-                dx: x,
-                dy: y,
-                mouse_data,
-                dw_flags: event as u32,
-                time: 0,
-                dw_extra_info: unsafe { GetMessageExtraInfo() as *mut c_ulong },
+            u: InputUnion {
+                mi: MouseInput {
+                    dx,
+                    dy,
+                    mouse_data,
+                    dw_flags: flags,
+                    time: 0,
+                    dw_extra_info: unsafe { GetMessageExtraInfo() as *mut c_ulong },
+                },
             },
         };
 
@@ -48,136 +127,607 @@ impl WindowsMouseManager {
             let result = SendInput(1, &mut input, size_of::<Input>() as i32);
             // If the function returns 0, it means the input was blocked by another thread
             if result == 0 {
-                return Err(Error::from(ErrorKind::WouldBlock));
+                return Err(Error::WriteFailed);
             }
         }
         Ok(())
     }
 
-    fn start_listener(&mut self) -> Result<()> {
-        thread::spawn(move || {
-            unsafe extern "system" fn low_level_mouse_handler(
-                code: c_int,
-                param: WParam,
-                lpdata: LParam,
-            ) -> LResult {
-                // Construct the library's MouseEvent
-                let w_param = param as u32;
-
-                let mouse_event = match w_param {
-                    WM_MOUSEMOVE => {
-                        let (x, y) = get_point(lpdata);
-                        Some(MouseEvent::AbsoluteMove(x, y))
-                    }
-                    WM_LBUTTONDOWN => Some(MouseEvent::Press(MouseButton::Left)),
-                    WM_MBUTTONDOWN => Some(MouseEvent::Press(MouseButton::Middle)),
-                    WM_RBUTTONDOWN => Some(MouseEvent::Press(MouseButton::Right)),
-                    WM_LBUTTONUP => Some(MouseEvent::Release(MouseButton::Left)),
-                    WM_MBUTTONUP => Some(MouseEvent::Release(MouseButton::Middle)),
-                    WM_RBUTTONUP => Some(MouseEvent::Release(MouseButton::Right)),
-                    WM_MOUSEWHEEL => {
-                        let delta = get_delta(lpdata) / WHEEL_DELTA as u16;
-                        match delta {
-                            1 => Some(MouseEvent::Scroll(ScrollDirection::Up)),
-                            _ => Some(MouseEvent::Scroll(ScrollDirection::Down)),
-                        }
-                    }
-                    WM_MOUSEHWHEEL => {
-                        let delta = get_delta(lpdata) / WHEEL_DELTA as u16;
-                        match delta {
-                            1 => Some(MouseEvent::Scroll(ScrollDirection::Right)),
-                            _ => Some(MouseEvent::Scroll(ScrollDirection::Left)),
-                        }
-                    }
-                    _ => None,
-                };
-
-                match (mouse_event, &mut CALLBACKS) {
-                    (Some(event), Some(callbacks)) => {
-                        for callback in callbacks.lock().unwrap().values() {
-                            callback(&event);
-                        }
-                    }
-                    _ => {}
+    fn start_listener(&mut self) -> Result<(), Error> {
+        let (tx, rx) = mpsc::channel();
+        let handle = match self.listener_kind {
+            WindowsListener::Hook => thread::spawn(move || run_hook(tx)),
+            WindowsListener::RawInput => thread::spawn(move || run_raw_input(tx)),
+        };
+
+        let thread_id = rx
+            .recv()
+            .map_err(|_| Error::CustomError("failed to start the listener thread".to_string()))?;
+        match thread_id {
+            Ok(thread_id) => {
+                self.listener = Some((thread_id, handle));
+                Ok(())
+            }
+            Err(err) => {
+                let _ = handle.join();
+                Err(err)
+            }
+        }
+    }
+
+    fn add_callback(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(
+            DEFAULT_PRIORITY,
+            Handler::Action(Box::new(move |event| {
+                callback(event);
+                EventAction::PassThrough
+            })),
+        )
+    }
+
+    /// Start the listener if it is not running yet and add the given
+    /// handler in the order of the given priority
+    fn add_handler(
+        &mut self,
+        priority: i32,
+        handler: Handler<MouseEventExt>,
+    ) -> Result<CallbackId, Error> {
+        if self.listener.is_none() {
+            self.start_listener()?;
+        }
+
+        let id = self.callback_counter;
+        unsafe {
+            initialize_callbacks();
+            if let Some(callbacks) = &CALLBACKS {
+                callbacks.insert(id, priority, EventMask::ALL, handler);
+            }
+        }
+        self.callback_counter += 1;
+        Ok(id)
+    }
+}
+
+thread_local! {
+    /// The dispatcher of the hook or the raw input thread, it keeps the snapshot
+    /// of the callbacks until they change
+    static DISPATCHER: RefCell<Option<Dispatcher<MouseEventExt>>> = const { RefCell::new(None) };
+}
+
+/// Invoke the callbacks with the given event
+fn dispatch(event: &MouseEventExt) {
+    let callbacks = match unsafe { &CALLBACKS } {
+        Some(callbacks) => callbacks,
+        None => return,
+    };
+    DISPATCHER.with(|dispatcher| match dispatcher.try_borrow_mut() {
+        Ok(mut dispatcher) => {
+            dispatcher
+                .get_or_insert_with(|| callbacks.dispatcher())
+                .dispatch(event);
+        }
+        // The hook is invoked again while a callback runs, e.g. when it moves the cursor
+        Err(_) => {
+            callbacks.dispatch(event);
+        }
+    });
+}
+
+/// Invoke the callbacks of `on_listener_restarted`, the list is not
+/// locked while they run so they may attach or remove callbacks
+fn notify_restarted() {
+    unsafe {
+        if let Some(callbacks) = &CALLBACKS {
+            invoke_all(&RESTART_CALLBACKS, &callbacks.panics(), |callback| {
+                callback()
+            });
+        }
+    }
+}
+
+/// Get the position of the cursor, None if it can not be read e.g. on the secure desktop
+fn cursor_position() -> Option<(c_long, c_long)> {
+    let mut point = Point { x: 0, y: 0 };
+    match unsafe { GetCursorPos(&mut point) } {
+        0 => None,
+        _ => Some((point.x, point.y)),
+    }
+}
+
+/// Install the low level mouse hook and run the message loop of the
+/// current thread, which delivers the events to the hook procedure
+///
+/// Windows removes the hook without telling once its procedure takes longer than
+/// `LowLevelHooksTimeout`, so a watchdog checks periodically whether the cursor moved
+/// while the hook received nothing. The cursor that an application warps looks the
+/// same, so the watchdog then sends a move by zero pixels, and installs the hook again
+/// only if the hook does not receive it until the next check
+///
+/// The probes are only sent once the hook looks lost, as every injected move resets
+/// the idle time of the system
+fn run_hook(tx: Sender<Result<DWord, Error>>) {
+    unsafe extern "system" fn low_level_mouse_handler(
+        code: c_int,
+        param: WParam,
+        lpdata: LParam,
+    ) -> LResult {
+        // The probes of the watchdog are not delivered to the callbacks
+        if (*(lpdata as *const MSLLHookStruct)).dw_extra_info == PROBE_EXTRA_INFO {
+            HOOK_PROBES.fetch_add(1, Ordering::Relaxed);
+            return CallNextHookEx(HOOK, code, param, lpdata);
+        }
+        HOOK_EVENTS.fetch_add(1, Ordering::Relaxed);
+        // Construct the library's MouseEvent
+        let w_param = param as u32;
+
+        let mouse_event = match w_param {
+            WM_MOUSEMOVE => {
+                let (x, y) = get_point(lpdata);
+                Some(MouseEvent::AbsoluteMove(x, y))
+            }
+            WM_LBUTTONDOWN => Some(MouseEvent::Press(MouseButton::Left)),
+            WM_MBUTTONDOWN => Some(MouseEvent::Press(MouseButton::Middle)),
+            WM_RBUTTONDOWN => Some(MouseEvent::Press(MouseButton::Right)),
+            WM_LBUTTONUP => Some(MouseEvent::Release(MouseButton::Left)),
+            WM_MBUTTONUP => Some(MouseEvent::Release(MouseButton::Middle)),
+            WM_RBUTTONUP => Some(MouseEvent::Release(MouseButton::Right)),
+            WM_XBUTTONDOWN => map_xbutton(get_delta(lpdata)).map(MouseEvent::Press),
+            WM_XBUTTONUP => map_xbutton(get_delta(lpdata)).map(MouseEvent::Release),
+            WM_MOUSEWHEEL => {
+                let delta = get_delta(lpdata) / WHEEL_DELTA as u16;
+                match delta {
+                    1 => Some(MouseEvent::Scroll(ScrollDirection::Up)),
+                    _ => Some(MouseEvent::Scroll(ScrollDirection::Down)),
+                }
+            }
+            WM_MOUSEHWHEEL => {
+                let delta = get_delta(lpdata) / WHEEL_DELTA as u16;
+                match delta {
+                    1 => Some(MouseEvent::Scroll(ScrollDirection::Right)),
+                    _ => Some(MouseEvent::Scroll(ScrollDirection::Left)),
                 }
+            }
+            _ => None,
+        };
+        // The wheel events also report the scrolled detents, the
+        // delta is signed and a multiple of WHEEL_DELTA for most mice
+        let detents = || get_delta(lpdata) as i16 as i32 / WHEEL_DELTA as i32;
+        let scroll_2d = match w_param {
+            WM_MOUSEWHEEL => Some(MouseEvent::Scroll2D {
+                dx: 0,
+                dy: detents(),
+            }),
+            WM_MOUSEHWHEEL => Some(MouseEvent::Scroll2D {
+                dx: detents(),
+                dy: 0,
+            }),
+            _ => None,
+        };
+
+        for event in mouse_event.into_iter().chain(scroll_2d) {
+            dispatch(&MouseEventExt {
+                device_id: String::new(),
+                device_name: String::new(),
+                event,
+                time: SystemTime::now(),
+            });
+        }
+
+        CallNextHookEx(HOOK, code, param, lpdata)
+    }
+    unsafe {
+        HOOK = SetWindowsHookExA(WH_MOUSE_LL, Some(low_level_mouse_handler), null_mut(), 0);
+        let _ = tx.send(Ok(GetCurrentThreadId()));
 
-                CallNextHookEx(HOOK, code, param, lpdata)
+        // The timer wakes up the message loop for the checks of the watchdog
+        let timer = SetTimer(null_mut(), 0, WATCHDOG_INTERVAL_MS, null_mut());
+        let mut last = (cursor_position(), HOOK_EVENTS.load(Ordering::Relaxed));
+        // The count of the received probes when the last probe is sent
+        let mut probe = None;
+        // Runs until stop_listening posts WM_QUIT to this thread
+        let mut msg: Msg = zeroed();
+        while GetMessageA(&mut msg, null_mut(), 0, 0) > 0 {
+            if msg.message != WM_TIMER {
+                continue;
             }
-            unsafe {
-                HOOK = SetWindowsHookExA(WH_MOUSE_LL, Some(low_level_mouse_handler), null_mut(), 0);
-                GetMessageA(null_mut(), null_mut(), 0, 0);
+            let current = (cursor_position(), HOOK_EVENTS.load(Ordering::Relaxed));
+            match probe.take() {
+                Some(probes) if HOOK_PROBES.load(Ordering::Relaxed) == probes => {
+                    trace::warning!("the mouse hook is removed by Windows, installing it again");
+                    UnhookWindowsHookEx(HOOK);
+                    HOOK = SetWindowsHookExA(
+                        WH_MOUSE_LL,
+                        Some(low_level_mouse_handler),
+                        null_mut(),
+                        0,
+                    );
+                    notify_restarted();
+                }
+                Some(_) => {}
+                None if current.0 != last.0 && current.1 == last.1 => {
+                    let probes = HOOK_PROBES.load(Ordering::Relaxed);
+                    if send_probe() {
+                        probe = Some(probes);
+                    }
+                }
+                None => {}
             }
-        });
+            last = current;
+        }
+        KillTimer(null_mut(), timer);
 
-        Ok(())
+        // Remove the procedure installed in the hook chain
+        UnhookWindowsHookEx(HOOK);
+        HOOK = null_mut();
     }
 }
 
-impl Drop for WindowsMouseManager {
-    fn drop(&mut self) {
-        unsafe {
-            if HOOK.is_null() {
-                // Remove the procedure installed in the hook chain
-                UnhookWindowsHookEx(HOOK);
+/// Send a move by zero pixels that the hook recognizes, returns whether it is sent
+fn send_probe() -> bool {
+    let mut input = Input {
+        r#type: INPUT_MOUSE,
+        u: InputUnion {
+            mi: MouseInput {
+                dx: 0,
+                dy: 0,
+                mouse_data: 0,
+                dw_flags: MOUSEEVENTF_MOVE,
+                time: 0,
+                dw_extra_info: PROBE_EXTRA_INFO as *mut c_ulong,
+            },
+        },
+    };
+    unsafe { SendInput(1, &mut input, size_of::<Input>() as i32) != 0 }
+}
+
+/// Register a hidden message window for the raw input of the mice and run its
+/// message loop, the events are received even if the window is never focused
+fn run_raw_input(tx: Sender<Result<DWord, Error>>) {
+    unsafe {
+        // A message-only window of a predefined class does not need a window procedure
+        let class = CString::new("STATIC").unwrap();
+        let window = CreateWindowExA(
+            0,
+            class.as_ptr(),
+            null(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            null_mut(),
+            null_mut(),
+            null_mut(),
+        );
+        if window.is_null() {
+            let _ = tx.send(Err(Error::CustomError(
+                "failed to create the raw input window".to_string(),
+            )));
+            return;
+        }
+
+        let mut device = RawInputDevice {
+            us_usage_page: HID_USAGE_PAGE_GENERIC,
+            us_usage: HID_USAGE_GENERIC_MOUSE,
+            dw_flags: RIDEV_INPUTSINK,
+            hwnd_target: window,
+        };
+        if RegisterRawInputDevices(&device, 1, size_of::<RawInputDevice>() as c_uint) == 0 {
+            DestroyWindow(window);
+            let _ = tx.send(Err(Error::CustomError(
+                "failed to register for the raw input of the mice".to_string(),
+            )));
+            return;
+        }
+        let _ = tx.send(Ok(GetCurrentThreadId()));
+
+        // The names of the devices, by their handles
+        let mut devices: HashMap<usize, (String, String)> = HashMap::new();
+        // Runs until stop_listening posts WM_QUIT to this thread
+        let mut msg: Msg = zeroed();
+        while GetMessageA(&mut msg, null_mut(), 0, 0) > 0 {
+            if msg.message == WM_INPUT {
+                if let Some((device, mouse)) = read_raw_input(msg.l_param as Handle) {
+                    let (device_id, device_name) = devices
+                        .entry(device as usize)
+                        .or_insert_with(|| raw_device_names(device))
+                        .clone();
+                    for event in raw_mouse_events(&mouse) {
+                        dispatch(&MouseEventExt {
+                            device_id: device_id.clone(),
+                            device_name: device_name.clone(),
+                            event,
+                            time: SystemTime::now(),
+                        });
+                    }
+                }
             }
+            // Lets the system clean up after the raw input
+            DispatchMessageA(&msg);
         }
+
+        device.dw_flags = RIDEV_REMOVE;
+        device.hwnd_target = null_mut();
+        RegisterRawInputDevices(&device, 1, size_of::<RawInputDevice>() as c_uint);
+        DestroyWindow(window);
     }
 }
 
-impl MouseActions for WindowsMouseManager {
-    fn move_to(&self, x: usize, y: usize) -> Result<()> {
-        unsafe {
-            let result = SetCursorPos(x as c_int, y as c_int);
-            if result == 0 {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "failed to set the cursor position",
-                ));
+/// Read the mouse input of the given `WM_INPUT` message, with the device that reported it
+unsafe fn read_raw_input(raw_input: Handle) -> Option<(Handle, RawMouse)> {
+    let mut input: RawInputMouse = zeroed();
+    let mut size = size_of::<RawInputMouse>() as c_uint;
+    let read = GetRawInputData(
+        raw_input,
+        RID_INPUT,
+        &mut input as *mut RawInputMouse as *mut c_void,
+        &mut size,
+        size_of::<RawInputHeader>() as c_uint,
+    );
+    if read == c_uint::MAX || input.header.dw_type != RIM_TYPEMOUSE {
+        return None;
+    }
+    Some((input.header.h_device, input.mouse))
+}
+
+/// Get the id and the name of the given raw input device, the injected
+/// events, e.g. of `SendInput`, do not have a device
+unsafe fn raw_device_names(device: Handle) -> (String, String) {
+    if device.is_null() {
+        return (String::new(), String::new());
+    }
+    let path = device_path(device).unwrap_or_default();
+    let name = product_name(&path).unwrap_or_else(|| path.clone());
+    (path, name)
+}
+
+/// Get the left, the top, the width and the height of the virtual desktop,
+/// the bounding box of all the monitors
+fn virtual_screen() -> (c_int, c_int, c_int, c_int) {
+    unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        )
+    }
+}
+
+/// Get the double click time and slop of the system, the second click
+/// must be within the rectangle of `SM_CXDOUBLECLK` by `SM_CYDOUBLECLK`
+/// pixels that is centered on the first one
+pub(crate) fn detector_config() -> DetectorConfig {
+    unsafe {
+        DetectorConfig::new()
+            .double_click_interval(Duration::from_millis(GetDoubleClickTime() as u64))
+            .double_click_slop(
+                (GetSystemMetrics(SM_CXDOUBLECLK) / 2).max(0) as u32,
+                (GetSystemMetrics(SM_CYDOUBLECLK) / 2).max(0) as u32,
+            )
+    }
+}
+
+/// Normalize the given coordinate of the virtual desktop to 0..65535, the
+/// absolute coordinates of `SendInput` with `MOUSEEVENTF_VIRTUALDESK`
+fn normalize(value: c_int, origin: c_int, size: c_int) -> c_long {
+    let offset = (value - origin).clamp(0, (size - 1).max(0)) as i64;
+    let size = (size as i64 - 1).max(1);
+    ((offset * 65535 + size / 2) / size) as c_long
+}
+
+/// Construct the library's MouseEvents from the given raw mouse input
+fn raw_mouse_events(mouse: &RawMouse) -> Vec<MouseEvent> {
+    let mut events = Vec::new();
+    if mouse.us_flags & MOUSE_MOVE_ABSOLUTE != 0 {
+        // The absolute positions are normalized to 0..65535 over the screen
+        let (left, top, width, height) = if mouse.us_flags & MOUSE_VIRTUAL_DESKTOP != 0 {
+            virtual_screen()
+        } else {
+            unsafe {
+                (
+                    0,
+                    0,
+                    GetSystemMetrics(SM_CXSCREEN),
+                    GetSystemMetrics(SM_CYSCREEN),
+                )
             }
+        };
+        let scale = |value: c_long, size: c_int| (value as i64 * size as i64 / 65535) as i32;
+        events.push(MouseEvent::AbsoluteMove(
+            left + scale(mouse.l_last_x, width),
+            top + scale(mouse.l_last_y, height),
+        ));
+    } else if mouse.l_last_x != 0 || mouse.l_last_y != 0 {
+        events.push(MouseEvent::RelativeMove(mouse.l_last_x, mouse.l_last_y));
+    }
+
+    let buttons = [
+        (
+            RI_MOUSE_LEFT_BUTTON_DOWN,
+            RI_MOUSE_LEFT_BUTTON_UP,
+            MouseButton::Left,
+        ),
+        (
+            RI_MOUSE_RIGHT_BUTTON_DOWN,
+            RI_MOUSE_RIGHT_BUTTON_UP,
+            MouseButton::Right,
+        ),
+        (
+            RI_MOUSE_MIDDLE_BUTTON_DOWN,
+            RI_MOUSE_MIDDLE_BUTTON_UP,
+            MouseButton::Middle,
+        ),
+        (
+            RI_MOUSE_BUTTON_4_DOWN,
+            RI_MOUSE_BUTTON_4_UP,
+            MouseButton::Back,
+        ),
+        (
+            RI_MOUSE_BUTTON_5_DOWN,
+            RI_MOUSE_BUTTON_5_UP,
+            MouseButton::Forward,
+        ),
+    ];
+    for (down, up, button) in buttons {
+        if mouse.us_button_flags & down != 0 {
+            events.push(MouseEvent::Press(button.clone()));
         }
-        Ok(())
+        if mouse.us_button_flags & up != 0 {
+            events.push(MouseEvent::Release(button));
+        }
+    }
+
+    // The wheel delta is signed, in the units of 1/120 of a detent. The high resolution
+    // and the combined forms only reach the callbacks that subscribe to them in their mask
+    let delta = mouse.us_button_data as i16 as i32;
+    let detents = delta / WHEEL_DELTA as i32;
+    if mouse.us_button_flags & RI_MOUSE_WHEEL != 0 && delta != 0 {
+        events.push(MouseEvent::Scroll(if delta > 0 {
+            ScrollDirection::Up
+        } else {
+            ScrollDirection::Down
+        }));
+        events.push(MouseEvent::ScrollHiRes { dx: 0, dy: delta });
+        events.push(MouseEvent::Scroll2D { dx: 0, dy: detents });
     }
+    if mouse.us_button_flags & RI_MOUSE_HWHEEL != 0 && delta != 0 {
+        events.push(MouseEvent::Scroll(if delta > 0 {
+            ScrollDirection::Right
+        } else {
+            ScrollDirection::Left
+        }));
+        events.push(MouseEvent::ScrollHiRes { dx: delta, dy: 0 });
+        events.push(MouseEvent::Scroll2D { dx: detents, dy: 0 });
+    }
+    events
+}
+
+impl Drop for WindowsMouseManager {
+    fn drop(&mut self) {
+        let _ = self.stop_listening();
+    }
+}
 
-    fn get_position(&self) -> Result<(i32, i32)> {
+impl MouseController for WindowsMouseManager {
+    fn move_to(&self, x: usize, y: usize) -> Result<(), Error> {
+        self.move_to_virtual(x as c_int, y as c_int)
+    }
+
+    fn move_to_virtual(&self, x: i32, y: i32) -> Result<(), Error> {
+        // The absolute moves span all the monitors with MOUSEEVENTF_VIRTUALDESK,
+        // otherwise they are only mapped to the primary monitor
+        let (left, top, width, height) = virtual_screen();
+        self.send_mouse_input(
+            normalize(x, left, width),
+            normalize(y, top, height),
+            0,
+            MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK,
+        )
+    }
+
+    fn move_relative(&self, x_offset: i32, y_offset: i32) -> Result<(), Error> {
+        let (x_offset, y_offset) = self.motion.lock_unpoisoned().apply(x_offset, y_offset);
+        // The cursor may be on a monitor with negative coordinates
+        let (x, y) = self.get_position()?;
+        self.move_to_virtual(x + x_offset, y + y_offset)
+    }
+
+    fn get_position(&self) -> Result<(i32, i32), Error> {
         let mut out = Point { x: 0, y: 0 };
         unsafe {
             let result = GetCursorPos(&mut out);
             if result == 0 {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "failed to get the cursor position",
+                return Err(Error::CustomError(
+                    "failed to get the cursor position".to_string(),
                 ));
             }
         }
         return Ok((out.x, out.y));
     }
 
-    fn press_button(&self, button: &MouseButton) -> Result<()> {
-        let event = match button {
-            MouseButton::Left => WindowsMouseEvent::LeftDown,
-            MouseButton::Middle => WindowsMouseEvent::MiddleDown,
-            MouseButton::Right => WindowsMouseEvent::RightDown,
+    fn backend(&self) -> Backend {
+        Backend::Windows
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::GetPosition
+                | Capability::ButtonState
+                | Capability::MotionProfile
+                | Capability::HiResScroll
+                | Capability::ConfineCursor
+                | Capability::CursorVisibility
+                | Capability::InjectEvent
+                | Capability::PauseHook
+                | Capability::CallbackPanics
+        ) || (capability == Capability::ListenerRestarts
+            && self.listener_kind == WindowsListener::Hook)
+    }
+
+    fn get_button_state(&self) -> Result<ButtonState, Error> {
+        // The state of the physical buttons, whatever they are swapped to
+        let pressed = [
+            (VK_LBUTTON, MouseButton::Left),
+            (VK_RBUTTON, MouseButton::Right),
+            (VK_MBUTTON, MouseButton::Middle),
+            (VK_XBUTTON1, MouseButton::Back),
+            (VK_XBUTTON2, MouseButton::Forward),
+        ]
+        .into_iter()
+        .filter(|(key, _)| unsafe { GetAsyncKeyState(*key) } as u16 & 0x8000 != 0)
+        .map(|(_, button)| button)
+        .collect();
+        Ok(ButtonState { pressed })
+    }
+
+    fn set_motion_profile(&mut self, profile: MotionProfile) -> Result<(), Error> {
+        self.motion.lock_unpoisoned().set_profile(profile);
+        Ok(())
+    }
+
+    fn press_button(&self, button: &MouseButton) -> Result<(), Error> {
+        let (event, mouse_data) = match button {
+            MouseButton::Left => (WindowsMouseEvent::LeftDown, 0),
+            MouseButton::Middle => (WindowsMouseEvent::MiddleDown, 0),
+            MouseButton::Right => (WindowsMouseEvent::RightDown, 0),
+            _ => (WindowsMouseEvent::XDown, xbutton(button)?),
         };
 
-        self.send_input(event, 0)
+        self.send_input(event, mouse_data)
     }
 
-    fn release_button(&self, button: &MouseButton) -> Result<()> {
-        let event = match button {
-            MouseButton::Left => WindowsMouseEvent::LeftUp,
-            MouseButton::Middle => WindowsMouseEvent::MiddleUp,
-            MouseButton::Right => WindowsMouseEvent::RightUp,
+    fn release_button(&self, button: &MouseButton) -> Result<(), Error> {
+        let (event, mouse_data) = match button {
+            MouseButton::Left => (WindowsMouseEvent::LeftUp, 0),
+            MouseButton::Middle => (WindowsMouseEvent::MiddleUp, 0),
+            MouseButton::Right => (WindowsMouseEvent::RightUp, 0),
+            _ => (WindowsMouseEvent::XUp, xbutton(button)?),
         };
 
-        self.send_input(event, 0)
+        self.send_input(event, mouse_data)
     }
 
-    fn click_button(&self, button: &MouseButton) -> Result<()> {
+    fn click_button(&self, button: &MouseButton) -> Result<(), Error> {
         self.press_button(button)?;
         self.release_button(button)
     }
 
-    fn scroll_wheel(&self, direction: &ScrollDirection) -> Result<()> {
+    fn click_with_modifiers(
+        &self,
+        button: &MouseButton,
+        modifiers: Modifiers,
+    ) -> Result<(), Error> {
+        self.modifier_keys
+            .hold(modifiers, || self.click_button(button))
+    }
+
+    fn scroll_wheel(&self, direction: &ScrollDirection) -> Result<(), Error> {
         let (event, scroll_amount) = match direction {
             ScrollDirection::Up => (WindowsMouseEvent::Wheel, 150),
             ScrollDirection::Down => (WindowsMouseEvent::Wheel, -150),
@@ -187,51 +737,158 @@ impl MouseActions for WindowsMouseManager {
         self.send_input(event, scroll_amount)
     }
 
-    fn hook(&mut self, callback: Box<dyn Fn(&MouseEvent) + Send>) -> Result<CallbackId> {
-        if !self.is_listening {
-            self.start_listener()?;
-            self.is_listening = true;
+    fn scroll(&self, dx: i32, dy: i32) -> Result<(), Error> {
+        let delta = WHEEL_DELTA as i32;
+        // The large counts saturate instead of overflowing and scrolling the other way
+        self.scroll_wheel_hi_res(dx.saturating_mul(delta), dy.saturating_mul(delta))
+    }
+
+    fn scroll_wheel_hi_res(&self, dx: i32, dy: i32) -> Result<(), Error> {
+        // WHEEL_DELTA is also 120 units per detent
+        if dy != 0 {
+            self.send_input(WindowsMouseEvent::Wheel, dy)?;
+        }
+        if dx != 0 {
+            self.send_input(WindowsMouseEvent::HWheel, dx)?;
         }
+        Ok(())
+    }
 
-        let id = self.callback_counter;
+    fn confine_cursor(&self, rect: Rect) -> Result<(), Error> {
+        // Windows also moves the cursor into the rectangle
+        let clip = ClipRect {
+            left: rect.x,
+            top: rect.y,
+            right: rect.x + rect.width,
+            bottom: rect.y + rect.height,
+        };
+        if unsafe { ClipCursor(&clip) } == 0 {
+            return Err(Error::CustomError(
+                "failed to confine the cursor".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn release_cursor(&self) -> Result<(), Error> {
+        if unsafe { ClipCursor(null()) } == 0 {
+            return Err(Error::CustomError(
+                "failed to release the cursor".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn set_cursor_visible(&self, visible: bool) -> Result<(), Error> {
+        // ShowCursor keeps a display counter, the cursor is shown while it is not negative
+        unsafe {
+            if visible {
+                while ShowCursor(1) < 0 {}
+            } else {
+                while ShowCursor(0) >= 0 {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MouseListener for WindowsMouseManager {
+    fn hook(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEvent) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_callback(Box::new(move |event| callback(&event.event)))
+    }
+
+    fn hook_with_priority(
+        &mut self,
+        priority: i32,
+        callback: Box<dyn Fn(&MouseEvent) -> Propagation + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        self.add_handler(
+            priority,
+            Handler::Propagate(Box::new(move |event| callback(&event.event))),
+        )
+    }
+
+    fn hook_ext(
+        &mut self,
+        callback: Box<dyn Fn(&MouseEventExt) + Send + Sync>,
+    ) -> Result<CallbackId, Error> {
+        // The hook does not know the devices of the events
+        if self.listener_kind != WindowsListener::RawInput {
+            return Err(Error::NotImplemented);
+        }
+        self.add_callback(callback)
+    }
+
+    fn inject_event(&self, event: MouseEvent) -> Result<EventAction, Error> {
+        unsafe {
+            if let Some(callbacks) = &CALLBACKS {
+                callbacks.dispatch(&MouseEventExt {
+                    device_id: String::new(),
+                    device_name: String::new(),
+                    event,
+                    time: SystemTime::now(),
+                });
+            }
+        }
+        // The callbacks can not consume the events here
+        Ok(EventAction::PassThrough)
+    }
+
+    fn unhook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
         unsafe {
             match &mut CALLBACKS {
                 Some(callbacks) => {
-                    callbacks.lock().unwrap().insert(id, callback);
+                    if callbacks.remove(callback_id) {
+                        return Ok(());
+                    }
+                    let mut restart_callbacks = RESTART_CALLBACKS.lock_unpoisoned();
+                    let count = restart_callbacks.len();
+                    restart_callbacks.retain(|(id, _)| *id != callback_id);
+                    if restart_callbacks.len() != count {
+                        Ok(())
+                    } else {
+                        Err(Error::CallbackNotFound)
+                    }
                 }
                 None => {
                     initialize_callbacks();
-                    return self.hook(callback);
+                    self.unhook(callback_id)
                 }
             }
         }
-        self.callback_counter += 1;
-        Ok(id)
     }
 
-    fn unhook(&mut self, callback_id: CallbackId) -> Result<()> {
+    fn pause_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        set_paused(callback_id, true)
+    }
+
+    fn resume_hook(&mut self, callback_id: CallbackId) -> Result<(), Error> {
+        set_paused(callback_id, false)
+    }
+
+    fn on_callback_panic(
+        &mut self,
+        unhook: bool,
+        handler: Box<dyn Fn(&CallbackPanic) + Send>,
+    ) -> Result<(), Error> {
         unsafe {
-            match &mut CALLBACKS {
-                Some(callbacks) => match callbacks.lock().unwrap().remove(&callback_id) {
-                    Some(_) => Ok(()),
-                    None => Err(Error::new(
-                        ErrorKind::NotFound,
-                        format!("callback id {} not found", callback_id),
-                    )),
-                },
-                None => {
-                    initialize_callbacks();
-                    self.unhook(callback_id)
-                }
+            initialize_callbacks();
+            if let Some(callbacks) = &CALLBACKS {
+                callbacks.set_panic_handler(handler, unhook);
             }
         }
+        Ok(())
     }
 
-    fn unhook_all(&mut self) -> Result<()> {
+    fn unhook_all(&mut self) -> Result<(), Error> {
         unsafe {
             match &mut CALLBACKS {
                 Some(callbacks) => {
-                    callbacks.lock().unwrap().clear();
+                    callbacks.clear();
+                    RESTART_CALLBACKS.lock_unpoisoned().clear();
                 }
                 None => {
                     initialize_callbacks();
@@ -241,6 +898,127 @@ impl MouseActions for WindowsMouseManager {
         }
         Ok(())
     }
+
+    fn on_listener_restarted(
+        &mut self,
+        callback: Box<dyn Fn() + Send>,
+    ) -> Result<CallbackId, Error> {
+        // Only the hook is removed by Windows, the raw input keeps arriving
+        if self.listener_kind != WindowsListener::Hook {
+            return Err(Error::NotImplemented);
+        }
+        let id = self.callback_counter;
+        RESTART_CALLBACKS
+            .lock_unpoisoned()
+            .push((id, Arc::new(Mutex::new(callback))));
+        self.callback_counter += 1;
+        Ok(id)
+    }
+
+    fn stop_listening(&mut self) -> Result<(), Error> {
+        if let Some((thread_id, handle)) = self.listener.take() {
+            unsafe {
+                PostThreadMessageA(thread_id, WM_QUIT, 0, null_mut());
+            }
+            let _ = handle.join();
+        }
+        Ok(())
+    }
+}
+
+/// Simulates the keyboard with the virtual-key codes
+pub struct WindowsKeyboardManager {}
+
+impl WindowsKeyboardManager {
+    pub fn new() -> Result<Box<dyn KeyboardActions + Send>, Error> {
+        Ok(Box::new(WindowsKeyboardManager {}))
+    }
+
+    fn key_event(&self, key: &Key, key_up: bool) -> Result<(), Error> {
+        let (virtual_key, extended) = match key {
+            Key::Char(ch @ ('a'..='z' | '0'..='9')) => (ch.to_ascii_uppercase() as u8, false),
+            Key::Char('-') => (VK_OEM_MINUS, false),
+            Key::Char('=') => (VK_OEM_PLUS, false),
+            Key::Char('[') => (VK_OEM_4, false),
+            Key::Char(']') => (VK_OEM_6, false),
+            Key::Char('\\') => (VK_OEM_5, false),
+            Key::Char(';') => (VK_OEM_1, false),
+            Key::Char('\'') => (VK_OEM_7, false),
+            Key::Char('`') => (VK_OEM_3, false),
+            Key::Char(',') => (VK_OEM_COMMA, false),
+            Key::Char('.') => (VK_OEM_PERIOD, false),
+            Key::Char('/') => (VK_OEM_2, false),
+            Key::Enter => (VK_RETURN, false),
+            Key::Escape => (VK_ESCAPE, false),
+            Key::Backspace => (VK_BACK, false),
+            Key::Tab => (VK_TAB, false),
+            Key::Space => (VK_SPACE, false),
+            Key::Shift => (VK_SHIFT, false),
+            Key::Control => (VK_CONTROL, false),
+            Key::Alt => (VK_MENU, false),
+            Key::Meta => (VK_LWIN, true),
+            Key::CapsLock => (VK_CAPITAL, false),
+            // The navigation keys are the extended keys next to the numpad
+            Key::Left => (VK_LEFT, true),
+            Key::Right => (VK_RIGHT, true),
+            Key::Up => (VK_UP, true),
+            Key::Down => (VK_DOWN, true),
+            Key::Home => (VK_HOME, true),
+            Key::End => (VK_END, true),
+            Key::PageUp => (VK_PRIOR, true),
+            Key::PageDown => (VK_NEXT, true),
+            Key::Insert => (VK_INSERT, true),
+            Key::Delete => (VK_DELETE, true),
+            Key::F(n @ 1..=12) => (VK_F1 + n - 1, false),
+            _ => return Err(Error::Unsupported(Backend::Windows)),
+        };
+
+        let mut flags = 0;
+        if extended {
+            flags |= KEYEVENTF_EXTENDEDKEY;
+        }
+        if key_up {
+            flags |= KEYEVENTF_KEYUP;
+        }
+        let mut input = Input {
+            r#type: INPUT_KEYBOARD,
+            u: InputUnion {
+                ki: KeybdInput {
+                    w_vk: virtual_key as Word,
+                    w_scan: 0,
+                    dw_flags: flags,
+                    time: 0,
+                    dw_extra_info: unsafe { GetMessageExtraInfo() as *mut c_ulong },
+                },
+            },
+        };
+        // The key is not typed if the input is blocked by another thread
+        if unsafe { SendInput(1, &mut input, size_of::<Input>() as i32) } == 0 {
+            return Err(Error::WriteFailed);
+        }
+        Ok(())
+    }
+}
+
+impl KeyboardActions for WindowsKeyboardManager {
+    fn press_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, false)
+    }
+
+    fn release_key(&mut self, key: &Key) -> Result<(), Error> {
+        self.key_event(key, true)
+    }
+}
+
+/// Pause or resume the callback with the given id
+fn set_paused(callback_id: CallbackId, paused: bool) -> Result<(), Error> {
+    unsafe {
+        initialize_callbacks();
+        match &CALLBACKS {
+            Some(callbacks) if callbacks.set_paused(callback_id, paused) => Ok(()),
+            _ => Err(Error::CallbackNotFound),
+        }
+    }
 }
 
 fn initialize_callbacks() {
@@ -248,17 +1026,154 @@ fn initialize_callbacks() {
         match CALLBACKS {
             Some(_) => {}
             None => {
-                CALLBACKS = Some(Mutex::new(HashMap::new()));
+                CALLBACKS = Some(Arc::new(Hooks::new()));
             }
         }
     }
 }
 
+/// List the mouse devices through the raw input API
+pub fn list_mice() -> Result<Vec<MouseDeviceInfo>, Error> {
+    let mut mice = Vec::new();
+    unsafe {
+        let list_size = size_of::<RawInputDeviceList>() as c_uint;
+        let mut count: c_uint = 0;
+        if GetRawInputDeviceList(null_mut(), &mut count, list_size) == c_uint::MAX {
+            return Err(Error::CustomError(
+                "failed to get the input devices".to_string(),
+            ));
+        }
+        let mut devices: Vec<RawInputDeviceList> = vec![zeroed(); count as usize];
+        let count = GetRawInputDeviceList(devices.as_mut_ptr(), &mut count, list_size);
+        if count == c_uint::MAX {
+            return Err(Error::CustomError(
+                "failed to get the input devices".to_string(),
+            ));
+        }
+
+        // The vertical wheel is only reported for the whole system
+        let wheel = GetSystemMetrics(SM_MOUSEWHEELPRESENT) != 0;
+
+        for device in devices.iter().take(count as usize) {
+            if device.dw_type != RIM_TYPEMOUSE {
+                continue;
+            }
+
+            let mut info: RidDeviceInfo = zeroed();
+            info.cb_size = size_of::<RidDeviceInfo>() as DWord;
+            let mut size = info.cb_size as c_uint;
+            if GetRawInputDeviceInfoA(
+                device.h_device,
+                RIDI_DEVICEINFO,
+                &mut info as *mut RidDeviceInfo as *mut c_void,
+                &mut size,
+            ) == c_uint::MAX
+            {
+                continue;
+            }
+
+            let path = device_path(device.h_device).unwrap_or_default();
+            // The path looks like \\?\HID#VID_046D&PID_C077#...
+            let id = |key: &str| {
+                let upper = path.to_uppercase();
+                let start = upper.find(key)? + key.len();
+                u16::from_str_radix(upper.get(start..start + 4)?, 16).ok()
+            };
+
+            mice.push(MouseDeviceInfo {
+                name: product_name(&path).unwrap_or_else(|| path.clone()),
+                vendor_id: id("VID_").unwrap_or(0),
+                product_id: id("PID_").unwrap_or(0),
+                wheel,
+                hwheel: info.mouse.f_has_horizontal_wheel != 0,
+                extra_buttons: info.mouse.dw_number_of_buttons > 3,
+                path,
+            });
+        }
+    }
+    Ok(mice)
+}
+
+/// Get the device interface path of the given raw input device
+unsafe fn device_path(device: Handle) -> Option<String> {
+    let mut size: c_uint = 0;
+    GetRawInputDeviceInfoA(device, RIDI_DEVICENAME, null_mut(), &mut size);
+    let mut buffer = vec![0 as c_char; size as usize + 1];
+    if GetRawInputDeviceInfoA(
+        device,
+        RIDI_DEVICENAME,
+        buffer.as_mut_ptr() as *mut c_void,
+        &mut size,
+    ) == c_uint::MAX
+    {
+        return None;
+    }
+    Some(
+        CStr::from_ptr(buffer.as_ptr())
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// Get the product name that the HID device at the given path reports
+unsafe fn product_name(path: &str) -> Option<String> {
+    let path = CString::new(path).ok()?;
+    // No access rights are needed to query the device attributes
+    let file = CreateFileA(
+        path.as_ptr(),
+        0,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        null_mut(),
+        OPEN_EXISTING,
+        0,
+        null_mut(),
+    );
+    if file == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    let mut buffer = [0u16; 128];
+    let found = HidD_GetProductString(
+        file,
+        buffer.as_mut_ptr() as *mut c_void,
+        (buffer.len() * size_of::<u16>()) as c_ulong,
+    );
+    CloseHandle(file);
+    if !found {
+        return None;
+    }
+
+    let len = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
 unsafe fn get_point(lpdata: LParam) -> (c_long, c_long) {
     let mouse = *(lpdata as *const MSLLHookStruct);
     (mouse.pt.x, mouse.pt.y)
 }
 
+/// Map the given extra button to its XBUTTON value
+///
+/// Windows only knows two extra buttons, the back and the forward buttons.
+/// Side and Extra are the evdev names of the same buttons on most mice
+fn xbutton(button: &MouseButton) -> Result<i32, Error> {
+    match button {
+        MouseButton::Back | MouseButton::Side => Ok(XBUTTON1),
+        MouseButton::Forward | MouseButton::Extra => Ok(XBUTTON2),
+        _ => Err(Error::Unsupported(Backend::Windows)),
+    }
+}
+
+/// Map the XBUTTON value of a hook event to the library's MouseButton,
+/// the same buttons that X11 reports as the back and the forward buttons
+fn map_xbutton(xbutton: Word) -> Option<MouseButton> {
+    match xbutton as i32 {
+        XBUTTON1 => Some(MouseButton::Back),
+        XBUTTON2 => Some(MouseButton::Forward),
+        _ => None,
+    }
+}
+
 unsafe fn get_delta(lpdata: LParam) -> Word {
     let mouse = *(lpdata as *const MSLLHookStruct);
     ((mouse.mouse_data >> 16) & 0xffff) as Word
@@ -277,6 +1192,10 @@ type HookProc =
 type LPMsg = *mut Msg;
 type HWND = *mut HWND__;
 type Word = c_ushort;
+type Handle = *mut c_void;
+const WM_QUIT: c_uint = 0x0012;
+const WM_TIMER: c_uint = 0x0113;
+const WM_INPUT: c_uint = 0x00FF;
 const WM_MOUSEMOVE: c_uint = 0x0200;
 const WM_LBUTTONDOWN: c_uint = 0x0201;
 const WM_LBUTTONUP: c_uint = 0x0202;
@@ -285,14 +1204,99 @@ const WM_RBUTTONUP: c_uint = 0x0205;
 const WM_MBUTTONDOWN: c_uint = 0x0207;
 const WM_MBUTTONUP: c_uint = 0x0208;
 const WM_MOUSEWHEEL: c_uint = 0x020A;
+const WM_XBUTTONDOWN: c_uint = 0x020B;
+const WM_XBUTTONUP: c_uint = 0x020C;
 const WM_MOUSEHWHEEL: c_uint = 0x020E;
 const WHEEL_DELTA: c_short = 120;
+const XBUTTON1: i32 = 0x0001;
+const XBUTTON2: i32 = 0x0002;
+/// The virtual keys of the mouse buttons
+const VK_LBUTTON: c_int = 0x01;
+const VK_RBUTTON: c_int = 0x02;
+const VK_MBUTTON: c_int = 0x04;
+const VK_XBUTTON1: c_int = 0x05;
+const VK_XBUTTON2: c_int = 0x06;
 const WH_MOUSE_LL: c_int = 14;
+const KEYEVENTF_EXTENDEDKEY: DWord = 0x0001;
+const KEYEVENTF_KEYUP: DWord = 0x0002;
+const VK_BACK: u8 = 0x08;
+const VK_TAB: u8 = 0x09;
+const VK_RETURN: u8 = 0x0D;
+const VK_SHIFT: u8 = 0x10;
+const VK_CONTROL: u8 = 0x11;
+const VK_MENU: u8 = 0x12;
+const VK_CAPITAL: u8 = 0x14;
+const VK_ESCAPE: u8 = 0x1B;
+const VK_SPACE: u8 = 0x20;
+const VK_PRIOR: u8 = 0x21;
+const VK_NEXT: u8 = 0x22;
+const VK_END: u8 = 0x23;
+const VK_HOME: u8 = 0x24;
+const VK_LEFT: u8 = 0x25;
+const VK_UP: u8 = 0x26;
+const VK_RIGHT: u8 = 0x27;
+const VK_DOWN: u8 = 0x28;
+const VK_INSERT: u8 = 0x2D;
+const VK_DELETE: u8 = 0x2E;
+const VK_LWIN: u8 = 0x5B;
+const VK_F1: u8 = 0x70;
+const VK_OEM_1: u8 = 0xBA;
+const VK_OEM_PLUS: u8 = 0xBB;
+const VK_OEM_COMMA: u8 = 0xBC;
+const VK_OEM_MINUS: u8 = 0xBD;
+const VK_OEM_PERIOD: u8 = 0xBE;
+const VK_OEM_2: u8 = 0xBF;
+const VK_OEM_3: u8 = 0xC0;
+const VK_OEM_4: u8 = 0xDB;
+const VK_OEM_5: u8 = 0xDC;
+const VK_OEM_6: u8 = 0xDD;
+const VK_OEM_7: u8 = 0xDE;
 enum Hhook__ {}
 enum HInstance__ {}
 enum HWND__ {}
 const INPUT_MOUSE: DWord = 0;
+const INPUT_KEYBOARD: DWord = 1;
+const MOUSEEVENTF_MOVE: DWord = 0x0001;
+const MOUSEEVENTF_VIRTUALDESK: DWord = 0x4000;
+const MOUSEEVENTF_ABSOLUTE: DWord = 0x8000;
+const RIM_TYPEMOUSE: DWord = 0;
+const RIDI_DEVICENAME: c_uint = 0x20000007;
+const RIDI_DEVICEINFO: c_uint = 0x2000000b;
+const SM_MOUSEWHEELPRESENT: c_int = 75;
+const SM_CXSCREEN: c_int = 0;
+const SM_CYSCREEN: c_int = 1;
+const SM_XVIRTUALSCREEN: c_int = 76;
+const SM_YVIRTUALSCREEN: c_int = 77;
+const SM_CXVIRTUALSCREEN: c_int = 78;
+const SM_CYVIRTUALSCREEN: c_int = 79;
+const SM_CXDOUBLECLK: c_int = 36;
+const SM_CYDOUBLECLK: c_int = 37;
+const HWND_MESSAGE: HWND = -3isize as HWND;
+const HID_USAGE_PAGE_GENERIC: Word = 0x01;
+const HID_USAGE_GENERIC_MOUSE: Word = 0x02;
+const RIDEV_REMOVE: DWord = 0x00000001;
+const RIDEV_INPUTSINK: DWord = 0x00000100;
+const RID_INPUT: c_uint = 0x10000003;
+const MOUSE_MOVE_ABSOLUTE: Word = 0x01;
+const MOUSE_VIRTUAL_DESKTOP: Word = 0x02;
+const RI_MOUSE_LEFT_BUTTON_DOWN: Word = 0x0001;
+const RI_MOUSE_LEFT_BUTTON_UP: Word = 0x0002;
+const RI_MOUSE_RIGHT_BUTTON_DOWN: Word = 0x0004;
+const RI_MOUSE_RIGHT_BUTTON_UP: Word = 0x0008;
+const RI_MOUSE_MIDDLE_BUTTON_DOWN: Word = 0x0010;
+const RI_MOUSE_MIDDLE_BUTTON_UP: Word = 0x0020;
+const RI_MOUSE_BUTTON_4_DOWN: Word = 0x0040;
+const RI_MOUSE_BUTTON_4_UP: Word = 0x0080;
+const RI_MOUSE_BUTTON_5_DOWN: Word = 0x0100;
+const RI_MOUSE_BUTTON_5_UP: Word = 0x0200;
+const RI_MOUSE_WHEEL: Word = 0x0400;
+const RI_MOUSE_HWHEEL: Word = 0x0800;
+const FILE_SHARE_READ: DWord = 0x1;
+const FILE_SHARE_WRITE: DWord = 0x2;
+const OPEN_EXISTING: DWord = 3;
+const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
 #[repr(C)]
+#[derive(Clone, Copy)]
 struct MouseInput {
     dx: c_long,
     dy: c_long,
@@ -302,9 +1306,24 @@ struct MouseInput {
     dw_extra_info: *mut c_ulong,
 }
 #[repr(C)]
+#[derive(Clone, Copy)]
+struct KeybdInput {
+    w_vk: Word,
+    w_scan: Word,
+    dw_flags: DWord,
+    time: DWord,
+    dw_extra_info: *mut c_ulong,
+}
+/// The union of the inputs, the mouse input is the largest member
+#[repr(C)]
+union InputUnion {
+    mi: MouseInput,
+    ki: KeybdInput,
+}
+#[repr(C)]
 struct Input {
     r#type: DWord,
-    mi: MouseInput,
+    u: InputUnion,
 }
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -312,6 +1331,14 @@ struct Point {
     x: c_long,
     y: c_long,
 }
+/// RECT, the right and the bottom edges are exclusive
+#[repr(C)]
+struct ClipRect {
+    left: c_long,
+    top: c_long,
+    right: c_long,
+    bottom: c_long,
+}
 #[repr(C)]
 enum WindowsMouseEvent {
     LeftDown = 0x0002,
@@ -320,6 +1347,8 @@ enum WindowsMouseEvent {
     RightUp = 0x0010,
     MiddleDown = 0x0020,
     MiddleUp = 0x0040,
+    XDown = 0x0080,
+    XUp = 0x0100,
     Wheel = 0x0800,
     HWheel = 0x01000,
 }
@@ -334,6 +1363,67 @@ struct Msg {
     pt: Point,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RawInputDeviceList {
+    h_device: Handle,
+    dw_type: DWord,
+}
+
+#[repr(C)]
+struct RidDeviceInfoMouse {
+    dw_id: DWord,
+    dw_number_of_buttons: DWord,
+    dw_sample_rate: DWord,
+    f_has_horizontal_wheel: c_int,
+}
+
+#[repr(C)]
+struct RidDeviceInfo {
+    cb_size: DWord,
+    dw_type: DWord,
+    mouse: RidDeviceInfoMouse,
+    /// The keyboard info is the largest member of the union
+    _padding: [DWord; 2],
+}
+
+#[repr(C)]
+struct RawInputDevice {
+    us_usage_page: Word,
+    us_usage: Word,
+    dw_flags: DWord,
+    hwnd_target: HWND,
+}
+
+#[repr(C)]
+struct RawInputHeader {
+    dw_type: DWord,
+    dw_size: DWord,
+    h_device: Handle,
+    w_param: WParam,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RawMouse {
+    us_flags: Word,
+    /// The button flags are in a union with a ULONG, which is aligned to 4 bytes
+    _padding: Word,
+    us_button_flags: Word,
+    us_button_data: Word,
+    ul_raw_buttons: DWord,
+    l_last_x: c_long,
+    l_last_y: c_long,
+    ul_extra_information: DWord,
+}
+
+/// The RAWINPUT structure of the mice
+#[repr(C)]
+struct RawInputMouse {
+    header: RawInputHeader,
+    mouse: RawMouse,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 struct MSLLHookStruct {
@@ -344,11 +1434,39 @@ struct MSLLHookStruct {
     dw_extra_info: usize,
 }
 
+// Kernel32 function definitions
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetCurrentThreadId() -> DWord;
+    fn CreateFileA(
+        lp_file_name: *const c_char,
+        dw_desired_access: DWord,
+        dw_share_mode: DWord,
+        lp_security_attributes: *mut c_void,
+        dw_creation_disposition: DWord,
+        dw_flags_and_attributes: DWord,
+        h_template_file: Handle,
+    ) -> Handle;
+    fn CloseHandle(h_object: Handle) -> c_int;
+}
+
+// Hid function definitions
+#[link(name = "hid")]
+extern "system" {
+    fn HidD_GetProductString(
+        hid_device_object: Handle,
+        buffer: *mut c_void,
+        buffer_length: c_ulong,
+    ) -> bool;
+}
+
 // User32 function definitions
 #[link(name = "user32")]
 extern "system" {
-    fn SetCursorPos(x: c_int, y: c_int) -> c_int;
     fn GetCursorPos(lp_point: *mut Point) -> c_int;
+    fn ClipCursor(lp_rect: *const ClipRect) -> c_int;
+    fn ShowCursor(b_show: c_int) -> c_int;
+    fn GetAsyncKeyState(v_key: c_int) -> i16;
     fn SendInput(c_inputs: c_uint, p_inputs: LPInput, cb_size: c_int) -> c_uint;
     fn GetMessageExtraInfo() -> LParam;
     fn SetWindowsHookExA(
@@ -363,6 +1481,55 @@ extern "system" {
         h_wnd: HWND,
         w_msg_filter_min: c_uint,
         w_msg_filter_max: c_uint,
-    ) -> bool;
+    ) -> c_int;
+    fn DispatchMessageA(lp_msg: *const Msg) -> LResult;
+    fn CreateWindowExA(
+        dw_ex_style: DWord,
+        lp_class_name: *const c_char,
+        lp_window_name: *const c_char,
+        dw_style: DWord,
+        x: c_int,
+        y: c_int,
+        n_width: c_int,
+        n_height: c_int,
+        h_wnd_parent: HWND,
+        h_menu: *mut c_void,
+        h_instance: HInstance,
+        lp_param: *mut c_void,
+    ) -> HWND;
+    fn DestroyWindow(h_wnd: HWND) -> c_int;
+    fn RegisterRawInputDevices(
+        p_raw_input_devices: *const RawInputDevice,
+        ui_num_devices: c_uint,
+        cb_size: c_uint,
+    ) -> c_int;
+    fn GetRawInputData(
+        h_raw_input: Handle,
+        ui_command: c_uint,
+        p_data: *mut c_void,
+        pcb_size: *mut c_uint,
+        cb_size_header: c_uint,
+    ) -> c_uint;
     fn UnhookWindowsHookEx(hhk: HHook) -> bool;
+    fn SetTimer(
+        h_wnd: HWND,
+        n_id_event: usize,
+        u_elapse: c_uint,
+        lp_timer_func: *mut c_void,
+    ) -> usize;
+    fn KillTimer(h_wnd: HWND, u_id_event: usize) -> c_int;
+    fn PostThreadMessageA(id_thread: DWord, msg: c_uint, w_param: WParam, l_param: LParam) -> bool;
+    fn GetRawInputDeviceList(
+        p_raw_input_device_list: *mut RawInputDeviceList,
+        pui_num_devices: *mut c_uint,
+        cb_size: c_uint,
+    ) -> c_uint;
+    fn GetRawInputDeviceInfoA(
+        h_device: Handle,
+        ui_command: c_uint,
+        p_data: *mut c_void,
+        pcb_size: *mut c_uint,
+    ) -> c_uint;
+    fn GetSystemMetrics(n_index: c_int) -> c_int;
+    fn GetDoubleClickTime() -> c_uint;
 }
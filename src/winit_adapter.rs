@@ -0,0 +1,195 @@
+///
+/// This module contains the glue between the mouse events and `winit`
+///
+/// The events are converted to the `DeviceEvent`s that winit reports for the raw
+/// input of the devices, and the events of a hooked callback can be forwarded to
+/// the event loop of a GUI application through its `EventLoopProxy`
+///
+use crate::common::{CallbackId, MouseButton, MouseEvent, MouseListener, ScrollDirection};
+use crate::error::Error;
+use winit::event::{DeviceEvent, ElementState, MouseScrollDelta};
+use winit::event_loop::EventLoopProxy;
+
+/// The units of a wheel detent in the high resolution scroll events
+const HI_RES_DETENT: f32 = 120.;
+
+/// Get the winit `ButtonId` of the given button, the buttons are numbered the
+/// same as the X11 buttons, where 4 to 7 are reserved for the scroll wheel
+pub fn button_id(button: &MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Right => 3,
+        MouseButton::Side | MouseButton::Back => 8,
+        MouseButton::Extra | MouseButton::Forward => 9,
+        MouseButton::Task => 10,
+    }
+}
+
+/// Convert the given mouse event to the winit device events it corresponds to
+///
+/// An absolute move is reported as a `Motion` on both of the axes, `0` for `x`
+/// and `1` for `y`. The scroll phases have no winit counterpart and the 2D scrolls
+/// are also reported as `Scroll` events, so they convert to no events at all
+///
+/// # Examples
+///
+/// ```rust
+/// use mouce::common::{MouseButton, MouseEvent};
+/// use mouce::winit_adapter::to_device_events;
+/// use winit::event::{DeviceEvent, ElementState};
+///
+/// let events = to_device_events(&MouseEvent::Press(MouseButton::Left));
+/// assert_eq!(
+///     events,
+///     vec![DeviceEvent::Button { button: 1, state: ElementState::Pressed }]
+/// );
+/// ```
+pub fn to_device_events(event: &MouseEvent) -> Vec<DeviceEvent> {
+    // winit scrolls up and left with positive values
+    let wheel = |dx: f32, dy: f32| DeviceEvent::MouseWheel {
+        delta: MouseScrollDelta::LineDelta(-dx, dy),
+    };
+    match event {
+        MouseEvent::RelativeMove(x, y) => vec![DeviceEvent::MouseMotion {
+            delta: (*x as f64, *y as f64),
+        }],
+        MouseEvent::AbsoluteMove(x, y) => vec![
+            DeviceEvent::Motion {
+                axis: 0,
+                value: *x as f64,
+            },
+            DeviceEvent::Motion {
+                axis: 1,
+                value: *y as f64,
+            },
+        ],
+        MouseEvent::Press(button) => vec![DeviceEvent::Button {
+            button: button_id(button),
+            state: ElementState::Pressed,
+        }],
+        MouseEvent::Release(button) => vec![DeviceEvent::Button {
+            button: button_id(button),
+            state: ElementState::Released,
+        }],
+        MouseEvent::Scroll(direction) => vec![match direction {
+            ScrollDirection::Up => wheel(0., 1.),
+            ScrollDirection::Down => wheel(0., -1.),
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "android",
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            ScrollDirection::Left => wheel(-1., 0.),
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "android",
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            ScrollDirection::Right => wheel(1., 0.),
+        }],
+        MouseEvent::ScrollHiRes { dx, dy } => vec![wheel(
+            *dx as f32 / HI_RES_DETENT,
+            *dy as f32 / HI_RES_DETENT,
+        )],
+        MouseEvent::Scroll2D { .. } | MouseEvent::ScrollPhase(_) => Vec::new(),
+        MouseEvent::DeviceDisconnected(_) => vec![DeviceEvent::Removed],
+        // The clicks are already sent as the presses and the releases
+        MouseEvent::Click(_) | MouseEvent::DoubleClick(_) | MouseEvent::TripleClick(_) => {
+            Vec::new()
+        }
+    }
+}
+
+/// Attach a callback that sends the mouse events to the event loop of the given proxy,
+/// they are received as user events in the `ApplicationHandler::user_event` of winit
+///
+/// The events are dropped once the event loop is closed
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::common::MouseEvent;
+/// use mouce::winit_adapter::forward_to_proxy;
+/// use mouce::Mouse;
+/// use winit::event_loop::EventLoop;
+///
+/// let event_loop = EventLoop::<MouseEvent>::with_user_event().build().unwrap();
+/// # #[cfg(target_os = "linux")]
+/// let mut manager = Mouse::new((0, 1920), (0, 1080)).unwrap();
+/// # #[cfg(not(target_os = "linux"))]
+/// # let mut manager = Mouse::new().unwrap();
+/// forward_to_proxy(manager.as_mut(), event_loop.create_proxy()).unwrap();
+/// ```
+pub fn forward_to_proxy<L, T>(
+    listener: &mut L,
+    proxy: EventLoopProxy<T>,
+) -> Result<CallbackId, Error>
+where
+    L: MouseListener + ?Sized,
+    T: From<MouseEvent> + Send + 'static,
+{
+    listener.hook(Box::new(move |event| {
+        let _ = proxy.send_event(T::from(event.clone()));
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_device_events;
+    use crate::common::{MouseButton, MouseEvent, ScrollDirection};
+    use winit::event::{DeviceEvent, ElementState, MouseScrollDelta};
+
+    #[test]
+    fn convert_events() {
+        assert_eq!(
+            to_device_events(&MouseEvent::RelativeMove(3, -2)),
+            vec![DeviceEvent::MouseMotion { delta: (3., -2.) }]
+        );
+        assert_eq!(
+            to_device_events(&MouseEvent::AbsoluteMove(10, 20)),
+            vec![
+                DeviceEvent::Motion {
+                    axis: 0,
+                    value: 10.
+                },
+                DeviceEvent::Motion {
+                    axis: 1,
+                    value: 20.
+                }
+            ]
+        );
+        assert_eq!(
+            to_device_events(&MouseEvent::Release(MouseButton::Back)),
+            vec![DeviceEvent::Button {
+                button: 8,
+                state: ElementState::Released
+            }]
+        );
+        assert_eq!(
+            to_device_events(&MouseEvent::Scroll(ScrollDirection::Down)),
+            vec![DeviceEvent::MouseWheel {
+                delta: MouseScrollDelta::LineDelta(0., -1.)
+            }]
+        );
+        assert_eq!(
+            to_device_events(&MouseEvent::ScrollHiRes { dx: 60, dy: 0 }),
+            vec![DeviceEvent::MouseWheel {
+                delta: MouseScrollDelta::LineDelta(-0.5, 0.)
+            }]
+        );
+        assert_eq!(
+            to_device_events(&MouseEvent::DeviceDisconnected(String::from("mouse"))),
+            vec![DeviceEvent::Removed]
+        );
+        assert!(to_device_events(&MouseEvent::Scroll2D { dx: 1, dy: 0 }).is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+///
+/// This module contains the serde representation of the mouse events,
+/// which follows the events of the JSON recordings so that both stay stable
+///
+use crate::common::{MouseButton, MouseEvent, ScrollDirection, ScrollPhase};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum WireEvent {
+    RelativeMove {
+        x: i32,
+        y: i32,
+    },
+    AbsoluteMove {
+        x: i32,
+        y: i32,
+    },
+    Press {
+        button: MouseButton,
+    },
+    Release {
+        button: MouseButton,
+    },
+    Scroll {
+        direction: ScrollDirection,
+    },
+    ScrollHiRes {
+        dx: i32,
+        dy: i32,
+    },
+    #[serde(rename = "scroll_2d")]
+    Scroll2D {
+        dx: i32,
+        dy: i32,
+    },
+    ScrollPhase {
+        phase: ScrollPhase,
+    },
+    DeviceDisconnected {
+        device_id: String,
+    },
+    Click {
+        button: MouseButton,
+    },
+    DoubleClick {
+        button: MouseButton,
+    },
+    TripleClick {
+        button: MouseButton,
+    },
+}
+
+impl From<MouseEvent> for WireEvent {
+    fn from(event: MouseEvent) -> Self {
+        match event {
+            MouseEvent::RelativeMove(x, y) => WireEvent::RelativeMove { x, y },
+            MouseEvent::AbsoluteMove(x, y) => WireEvent::AbsoluteMove { x, y },
+            MouseEvent::Press(button) => WireEvent::Press { button },
+            MouseEvent::Release(button) => WireEvent::Release { button },
+            MouseEvent::Scroll(direction) => WireEvent::Scroll { direction },
+            MouseEvent::ScrollHiRes { dx, dy } => WireEvent::ScrollHiRes { dx, dy },
+            MouseEvent::Scroll2D { dx, dy } => WireEvent::Scroll2D { dx, dy },
+            MouseEvent::ScrollPhase(phase) => WireEvent::ScrollPhase { phase },
+            MouseEvent::DeviceDisconnected(device_id) => {
+                WireEvent::DeviceDisconnected { device_id }
+            }
+            MouseEvent::Click(button) => WireEvent::Click { button },
+            MouseEvent::DoubleClick(button) => WireEvent::DoubleClick { button },
+            MouseEvent::TripleClick(button) => WireEvent::TripleClick { button },
+        }
+    }
+}
+
+impl From<WireEvent> for MouseEvent {
+    fn from(event: WireEvent) -> Self {
+        match event {
+            WireEvent::RelativeMove { x, y } => MouseEvent::RelativeMove(x, y),
+            WireEvent::AbsoluteMove { x, y } => MouseEvent::AbsoluteMove(x, y),
+            WireEvent::Press { button } => MouseEvent::Press(button),
+            WireEvent::Release { button } => MouseEvent::Release(button),
+            WireEvent::Scroll { direction } => MouseEvent::Scroll(direction),
+            WireEvent::ScrollHiRes { dx, dy } => MouseEvent::ScrollHiRes { dx, dy },
+            WireEvent::Scroll2D { dx, dy } => MouseEvent::Scroll2D { dx, dy },
+            WireEvent::ScrollPhase { phase } => MouseEvent::ScrollPhase(phase),
+            WireEvent::DeviceDisconnected { device_id } => {
+                MouseEvent::DeviceDisconnected(device_id)
+            }
+            WireEvent::Click { button } => MouseEvent::Click(button),
+            WireEvent::DoubleClick { button } => MouseEvent::DoubleClick(button),
+            WireEvent::TripleClick { button } => MouseEvent::TripleClick(button),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{MouseButton, MouseEvent, ScrollDirection, ScrollPhase};
+
+    #[test]
+    fn event_representation() {
+        let events = [
+            (
+                MouseEvent::RelativeMove(-3, 4),
+                r#"{"type":"relative_move","x":-3,"y":4}"#,
+            ),
+            (
+                MouseEvent::Press(MouseButton::Left),
+                r#"{"type":"press","button":"left"}"#,
+            ),
+            (
+                MouseEvent::Scroll(ScrollDirection::Down),
+                r#"{"type":"scroll","direction":"down"}"#,
+            ),
+            (
+                MouseEvent::Scroll2D { dx: 1, dy: -1 },
+                r#"{"type":"scroll_2d","dx":1,"dy":-1}"#,
+            ),
+            (
+                MouseEvent::ScrollPhase(ScrollPhase::MomentumBegan),
+                r#"{"type":"scroll_phase","phase":"momentum_began"}"#,
+            ),
+            (
+                MouseEvent::DoubleClick(MouseButton::Right),
+                r#"{"type":"double_click","button":"right"}"#,
+            ),
+            (
+                MouseEvent::DeviceDisconnected("/dev/input/event7".to_string()),
+                r#"{"type":"device_disconnected","device_id":"/dev/input/event7"}"#,
+            ),
+        ];
+        for (event, json) in events.iter() {
+            assert_eq!(serde_json::to_string(event).unwrap(), *json);
+            assert_eq!(&serde_json::from_str::<MouseEvent>(json).unwrap(), event);
+            // The recordings read the same representation
+            assert_eq!(
+                &crate::json::event_from_object(&crate::json::parse(json).unwrap()).unwrap(),
+                event
+            );
+        }
+    }
+}
@@ -0,0 +1,252 @@
+///
+/// This module contains the trigger zones, the rectangles of the screen
+/// that invoke their callbacks when the cursor enters, leaves or dwells in
+/// them, e.g. for the hot corners or for waking up a kiosk
+///
+/// The cursor position is polled like the `position` watcher does, so the
+/// zones work wherever the position can be read, even without a listener
+///
+use crate::common::MouseActions;
+use crate::display::Rect;
+use crate::error::Error;
+use crate::hooks::LockUnpoisoned;
+use crate::position::{self, PositionWatcher};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The identifier of a zone, which is used to remove it
+pub type ZoneId = usize;
+
+/// When the callback of a zone is invoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneEvent {
+    /// The cursor moves into the zone
+    Enter,
+    /// The cursor moves out of the zone
+    Leave,
+    /// The cursor stays in the zone for the given duration, the callback
+    /// is invoked once until the cursor leaves the zone and enters it again
+    Dwell(Duration),
+}
+
+struct Zone {
+    id: ZoneId,
+    rect: Rect,
+    event: ZoneEvent,
+    callback: Box<dyn Fn(i32, i32) + Send>,
+    /// Since when the cursor is in the zone
+    entered_at: Option<Instant>,
+    /// Whether the dwell callback is already invoked for the current stay
+    dwelled: bool,
+}
+
+/// The zones and the state of the cursor in them
+///
+/// The zones are checked with the positions that `update` is given, or that
+/// `watch` polls. A zone starts with the cursor outside of it, so the cursor
+/// that is already in a new zone enters it on the next update
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mouce::display::Rect;
+/// use mouce::zones::{ZoneEvent, Zones};
+/// use std::time::Duration;
+///
+/// let mut zones = Zones::new();
+/// zones.add_zone(
+///     Rect::new(0, 0, 5, 5),
+///     ZoneEvent::Dwell(Duration::from_millis(300)),
+///     Box::new(|_, _| println!("The top left corner is hot")),
+/// );
+/// let watcher = zones.watch(Duration::from_millis(16)).unwrap();
+/// // Stop watching
+/// drop(watcher);
+/// ```
+#[derive(Default)]
+pub struct Zones {
+    zones: Vec<Zone>,
+    zone_counter: ZoneId,
+}
+
+impl Zones {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Invoke the given callback with the cursor position when the given event
+    /// happens in the given rectangle, returns the identifier of the zone
+    pub fn add_zone(
+        &mut self,
+        rect: Rect,
+        event: ZoneEvent,
+        callback: Box<dyn Fn(i32, i32) + Send>,
+    ) -> ZoneId {
+        let id = self.zone_counter;
+        self.zones.push(Zone {
+            id,
+            rect,
+            event,
+            callback,
+            entered_at: None,
+            dwelled: false,
+        });
+        self.zone_counter += 1;
+        id
+    }
+
+    /// Remove the zone with the given `ZoneId`
+    pub fn remove_zone(&mut self, zone_id: ZoneId) -> Result<(), Error> {
+        let count = self.zones.len();
+        self.zones.retain(|zone| zone.id != zone_id);
+        if self.zones.len() != count {
+            Ok(())
+        } else {
+            Err(Error::CallbackNotFound)
+        }
+    }
+
+    /// Check the zones with the cursor at the given position at the given time,
+    /// and invoke the callbacks of the events that happen
+    ///
+    /// The dwells are only noticed on the updates, so the position should be
+    /// updated periodically even when it does not change
+    pub fn update(&mut self, x: i32, y: i32, now: Instant) {
+        for zone in self.zones.iter_mut() {
+            let inside = zone.rect.contains(x, y);
+            let fired = match (zone.entered_at, inside) {
+                (None, true) => {
+                    zone.entered_at = Some(now);
+                    zone.dwelled = false;
+                    zone.event == ZoneEvent::Enter
+                }
+                (Some(_), false) => {
+                    zone.entered_at = None;
+                    zone.event == ZoneEvent::Leave
+                }
+                _ => false,
+            };
+            let dwelled = match (zone.event, zone.entered_at) {
+                (ZoneEvent::Dwell(duration), Some(entered_at))
+                    if !zone.dwelled && now.saturating_duration_since(entered_at) >= duration =>
+                {
+                    zone.dwelled = true;
+                    true
+                }
+                _ => false,
+            };
+            if fired || dwelled {
+                (zone.callback)(x, y);
+            }
+        }
+    }
+
+    /// Poll the cursor position in the given interval and check the zones with it,
+    /// until the returned watcher is dropped
+    ///
+    /// The position is read like `position::watch` reads it
+    pub fn watch(self, interval: Duration) -> Result<ZoneWatcher, Error> {
+        Ok(self.watch_with(position::position_source()?, interval))
+    }
+
+    /// Poll the cursor position that the given manager reports, see `watch`
+    pub fn watch_with(
+        self,
+        manager: Box<dyn MouseActions + Send>,
+        interval: Duration,
+    ) -> ZoneWatcher {
+        let zones = Arc::new(Mutex::new(self));
+        let polled = zones.clone();
+        let watcher = position::poll_with(manager, interval, move |(x, y)| {
+            polled.lock_unpoisoned().update(x, y, Instant::now());
+        });
+        ZoneWatcher {
+            zones,
+            _watcher: watcher,
+        }
+    }
+}
+
+/// Checks the zones with the polled cursor position until it is dropped
+///
+/// The callbacks are invoked on the polling thread while the zones are locked,
+/// so they must not add or remove the zones of the same watcher
+pub struct ZoneWatcher {
+    zones: Arc<Mutex<Zones>>,
+    _watcher: PositionWatcher,
+}
+
+impl ZoneWatcher {
+    /// Add a zone while the zones are being watched, see `Zones::add_zone`
+    pub fn add_zone(
+        &self,
+        rect: Rect,
+        event: ZoneEvent,
+        callback: Box<dyn Fn(i32, i32) + Send>,
+    ) -> ZoneId {
+        self.zones.lock_unpoisoned().add_zone(rect, event, callback)
+    }
+
+    /// Remove the zone with the given `ZoneId` while the zones are being watched
+    pub fn remove_zone(&self, zone_id: ZoneId) -> Result<(), Error> {
+        self.zones.lock_unpoisoned().remove_zone(zone_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ZoneEvent, Zones};
+    use crate::display::Rect;
+    use crate::error::Error;
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn zone_events() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let mut zones = Zones::new();
+        let corner = Rect::new(0, 0, 10, 10);
+        for (name, event) in [
+            ("enter", ZoneEvent::Enter),
+            ("leave", ZoneEvent::Leave),
+            ("dwell", ZoneEvent::Dwell(Duration::from_millis(100))),
+        ] {
+            let fired = fired.clone();
+            zones.add_zone(
+                corner,
+                event,
+                Box::new(move |x, y| fired.lock().unwrap().push((name, x, y))),
+            );
+        }
+
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        zones.update(50, 50, at(0));
+        zones.update(5, 5, at(10));
+        zones.update(6, 5, at(60));
+        // The dwell is reported once per stay
+        zones.update(6, 5, at(110));
+        zones.update(6, 6, at(300));
+        zones.update(20, 6, at(310));
+        // Leaving before the duration is not a dwell
+        zones.update(1, 1, at(320));
+        zones.update(30, 30, at(330));
+        assert_eq!(
+            *fired.lock().unwrap(),
+            vec![
+                ("enter", 5, 5),
+                ("dwell", 6, 5),
+                ("leave", 20, 6),
+                ("enter", 1, 1),
+                ("leave", 30, 30),
+            ]
+        );
+
+        assert_eq!(zones.remove_zone(0), Ok(()));
+        assert_eq!(zones.remove_zone(0), Err(Error::CallbackNotFound));
+    }
+}